@@ -0,0 +1,61 @@
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::prelude::*;
+
+/// An entry of the audit log. The format is the same as the format
+/// of the datashed audit log (see `datashed log`).
+#[derive(Debug, Serialize)]
+struct AuditEntry {
+    /// The time of the operation (milliseconds since the UNIX epoch).
+    timestamp: u64,
+
+    /// The user, who ran the command.
+    user: String,
+
+    /// The name of the command (e.g. `ids repair`).
+    command: String,
+
+    /// The command line arguments (without the program name).
+    args: Vec<String>,
+
+    /// The number of affected records.
+    affected: u64,
+}
+
+/// Appends an entry for the running command to the audit log of the
+/// dataset (`.dataset/audit.jsonl`).
+pub(crate) fn record(
+    dataset: &Dataset,
+    command: &str,
+    affected: usize,
+) -> DatasetResult<()> {
+    let entry = AuditEntry {
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64,
+        user: env::var("DATASET_USERNAME")
+            .or_else(|_| env::var("USER"))
+            .unwrap_or_default(),
+        command: command.into(),
+        args: env::args().skip(1).collect(),
+        affected: affected as u64,
+    };
+
+    let mut line =
+        serde_json::to_string(&entry).map_err(DatasetError::other)?;
+    line.push('\n');
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dataset.dot_dir().join(Dataset::AUDIT_LOG))?
+        .write_all(line.as_bytes())?;
+
+    Ok(())
+}
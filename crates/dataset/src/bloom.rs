@@ -0,0 +1,157 @@
+use std::io::{self, Read};
+
+use sha2::{Digest, Sha256};
+
+/// A Bloom filter over the PPNs of a remote index, as published by
+/// `datashed serve` (`/index.bloom`). False positives are possible,
+/// false negatives are not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    const MAGIC: &'static [u8; 4] = b"DSBF";
+
+    /// Creates a new (empty) filter with the given number of bits and
+    /// hash functions.
+    #[cfg(test)]
+    fn new(num_bits: u64, num_hashes: u32) -> Self {
+        Self {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    /// Returns the bit positions of an item.
+    fn positions(&self, item: &[u8]) -> impl Iterator<Item = u64> + '_ {
+        let digest = Sha256::digest(item);
+        let h1 = u64::from_le_bytes(digest[0..8].try_into().unwrap());
+        let h2 =
+            u64::from_le_bytes(digest[8..16].try_into().unwrap()) | 1;
+
+        (0..self.num_hashes as u64).map(move |i| {
+            h1.wrapping_add(i.wrapping_mul(h2)) % self.num_bits
+        })
+    }
+
+    /// Adds an item.
+    #[cfg(test)]
+    fn insert(&mut self, item: &str) {
+        let positions: Vec<u64> =
+            self.positions(item.as_bytes()).collect();
+        for pos in positions {
+            self.bits[(pos / 64) as usize] |= 1 << (pos % 64);
+        }
+    }
+
+    /// Returns `true` if the item is probably part of the set and
+    /// `false` if it's definitely not.
+    pub(crate) fn contains(&self, item: &str) -> bool {
+        self.positions(item.as_bytes()).all(|pos| {
+            self.bits[(pos / 64) as usize] & (1 << (pos % 64)) != 0
+        })
+    }
+
+    /// Writes the filter in the binary format of `datashed`.
+    pub(crate) fn write<W: io::Write>(
+        &self,
+        mut wtr: W,
+    ) -> io::Result<()> {
+        wtr.write_all(Self::MAGIC)?;
+        wtr.write_all(&self.num_hashes.to_le_bytes())?;
+        wtr.write_all(&self.num_bits.to_le_bytes())?;
+        for word in self.bits.iter() {
+            wtr.write_all(&word.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a filter written by `datashed` (see [BloomFilter::write]).
+    pub(crate) fn read<R: Read>(mut rdr: R) -> io::Result<Self> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid bloom filter",
+            )
+        };
+
+        let mut magic = [0u8; 4];
+        rdr.read_exact(&mut magic)?;
+        if &magic != Self::MAGIC {
+            return Err(invalid());
+        }
+
+        let mut buf = [0u8; 4];
+        rdr.read_exact(&mut buf)?;
+        let num_hashes = u32::from_le_bytes(buf);
+
+        let mut buf = [0u8; 8];
+        rdr.read_exact(&mut buf)?;
+        let num_bits = u64::from_le_bytes(buf);
+        if num_bits == 0 || num_hashes == 0 {
+            return Err(invalid());
+        }
+
+        let mut bits =
+            Vec::with_capacity(num_bits.div_ceil(64) as usize);
+        for _ in 0..num_bits.div_ceil(64) {
+            rdr.read_exact(&mut buf)?;
+            bits.push(u64::from_le_bytes(buf));
+        }
+
+        Ok(Self {
+            bits,
+            num_bits,
+            num_hashes,
+        })
+    }
+}
+
+/// The Bloom filters of a set of remotes, which are used to discard
+/// labels of documents not present in any of the remotes.
+#[derive(Debug)]
+pub(crate) struct PpnFilter(pub(crate) Vec<BloomFilter>);
+
+impl PpnFilter {
+    /// Returns `false` if no remote contains a document with the given
+    /// PPN.
+    pub(crate) fn contains(&self, ppn: &str) -> bool {
+        self.0.iter().any(|filter| filter.contains(ppn))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bloom_filter() -> io::Result<()> {
+        let mut foo = BloomFilter::new(1 << 14, 7);
+        let mut bar = BloomFilter::new(1 << 14, 7);
+        for i in 0..1000 {
+            foo.insert(&format!("{i:09}"));
+            bar.insert(&format!("{:09}", i + 1000));
+        }
+
+        let mut buf = vec![];
+        foo.write(&mut buf)?;
+        assert_eq!(&buf[..4], b"DSBF");
+        assert_eq!(BloomFilter::read(&buf[..])?, foo);
+        assert!(BloomFilter::read(&b"XXXX"[..]).is_err());
+
+        let filter = PpnFilter(vec![foo, bar]);
+        assert!((0..2000).all(|i| filter.contains(&format!("{i:09}"))));
+
+        let false_positives = (2000..12_000)
+            .filter(|i| filter.contains(&format!("{i:09}")))
+            .count();
+        assert!(false_positives < 300);
+
+        Ok(())
+    }
+}
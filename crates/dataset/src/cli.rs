@@ -22,12 +22,23 @@ use crate::commands::*;
 
 #[derive(Debug, Subcommand)]
 pub(crate) enum Command {
+    Annif(Annif),
     Completions(Completions),
     Config(Config),
+    Eval(Eval),
+    Export(Export),
     Fetch(Fetch),
+    Grep(Grep),
+    Ids(Ids),
     #[clap(alias = "new")]
     Init(Init),
+    PicaTest(PicaTest),
+    Prune(Prune),
+    Publish(Publish),
     Remote(Remote),
+    Sru(Sru),
+    Status(Status),
     Version(Version),
     Vocab(Vocab),
+    WeakLabel(WeakLabel),
 }
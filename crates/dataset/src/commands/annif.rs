@@ -0,0 +1,440 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use polars::prelude::*;
+use serde::Serialize;
+use url::Url;
+
+use crate::http::HttpClient;
+use crate::prelude::*;
+use crate::table::{read_table, strings};
+use crate::vocab::{read_vocab, Subject};
+
+/// Export the dataset as an Annif corpus.
+///
+/// The subject vocabulary (`vocab.csv`) is written as an Annif subject
+/// file (`subjects.tsv`) and the documents of the dataset, which have
+/// at least one label, are written either as a short-text corpus
+/// (`corpus.tsv`) or as a full-text corpus (a `.txt` and a `.tsv` file
+/// per document in the `fulltext` directory). The documents are
+/// downloaded from the remotes. Optionally, the corpus is pushed to
+/// the `learn` endpoint of a running Annif instance.
+#[derive(Debug, Parser)]
+pub(crate) struct Annif {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    #[clap(subcommand)]
+    cmd: Command,
+}
+
+#[derive(Debug, clap::Parser)]
+pub(crate) enum Command {
+    /// Generate the corpus and the subject vocabulary.
+    Export {
+        /// The labels of the documents (CSV or IPC) with the columns
+        /// `ppn` and `label_uri`.
+        #[arg(short, long, value_name = "filename")]
+        labels: PathBuf,
+
+        /// The corpus format.
+        #[arg(long, default_value = "tsv")]
+        format: CorpusFormat,
+
+        /// The maximum number of characters of a document in the
+        /// short-text corpus. A value of "0" disables the limit.
+        #[arg(long, default_value = "5000", value_name = "n")]
+        max_chars: usize,
+
+        /// Export only the documents of the given remote. This option
+        /// can be specified multiple times. By default, the documents
+        /// of all remotes are exported.
+        #[arg(short, long = "remote", value_name = "name")]
+        remotes: Vec<String>,
+
+        /// The name of the user (HTTP basic authentication).
+        #[arg(short, long, requires = "secret")]
+        username: Option<String>,
+
+        /// The secret of the user.
+        #[arg(long, env = "DATASET_SECRET", hide_env_values = true)]
+        secret: Option<String>,
+
+        /// Push the documents to the Annif instance at `url` (e.g.
+        /// `http://localhost:5000`).
+        #[arg(long, value_name = "url", requires = "project")]
+        push: Option<Url>,
+
+        /// The id of the Annif project, which learns from the pushed
+        /// documents.
+        #[arg(long, value_name = "id")]
+        project: Option<String>,
+
+        /// The number of documents per `learn` request.
+        #[arg(long, default_value = "32", value_name = "n")]
+        batch_size: usize,
+
+        /// The output directory. By default, the corpus is written
+        /// into the `annif` directory of the dataset.
+        #[arg(short, long, value_name = "path")]
+        output: Option<PathBuf>,
+    },
+}
+
+/// The corpus format of Annif.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum CorpusFormat {
+    /// A short-text document corpus (`text<TAB><uri> <uri>`).
+    Tsv,
+    /// A full-text document corpus (`.txt` and `.tsv` files).
+    Fulltext,
+}
+
+/// A subject of the `learn` request.
+#[derive(Debug, Serialize)]
+struct LearnSubject<'a> {
+    uri: &'a str,
+    label: &'a str,
+}
+
+/// A document of the `learn` request.
+#[derive(Debug, Serialize)]
+struct LearnDocument<'a> {
+    text: String,
+    subjects: Vec<LearnSubject<'a>>,
+}
+
+const PBAR_EXPORT: &str =
+    "Exporting documents: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// Collapses all whitespace of the text into single spaces and
+/// truncates the text to `max_chars` characters (0 = no limit).
+fn short_text(text: &str, max_chars: usize) -> String {
+    let mut out = String::with_capacity(text.len().min(max_chars * 4));
+    for (idx, word) in text.split_whitespace().enumerate() {
+        if idx > 0 {
+            out.push(' ');
+        }
+        out.push_str(word);
+    }
+
+    if max_chars > 0 {
+        if let Some((idx, _)) = out.char_indices().nth(max_chars) {
+            out.truncate(idx);
+        }
+    }
+
+    out
+}
+
+/// Returns the subject file of a document (`<uri><TAB>label`).
+fn subject_file(
+    uris: &BTreeSet<String>,
+    vocab: &HashMap<String, Subject>,
+) -> String {
+    uris.iter()
+        .map(|uri| {
+            let label = vocab.get(uri).map_or("", |s| s.label.as_str());
+            format!("<{uri}>\t{label}\n")
+        })
+        .collect()
+}
+
+impl Annif {
+    pub(crate) async fn execute(self) -> DatasetResult<()> {
+        match self.cmd {
+            Command::Export { .. } => self.export().await,
+        }
+    }
+
+    async fn export(&self) -> DatasetResult<()> {
+        let Command::Export {
+            labels,
+            format,
+            max_chars,
+            remotes,
+            username,
+            secret,
+            push,
+            project,
+            batch_size,
+            output,
+        } = &self.cmd;
+
+        if *batch_size == 0 {
+            bail!("batch size must be greater than zero");
+        }
+
+        let dataset = Dataset::discover()?;
+        let config = dataset.config()?;
+
+        for name in remotes.iter() {
+            if !config.remotes.contains_key(name) {
+                bail!("unknown remote '{name}'");
+            }
+        }
+
+        let out_dir = output
+            .clone()
+            .unwrap_or_else(|| dataset.base_dir().join("annif"));
+        fs::create_dir_all(&out_dir)?;
+
+        let subjects =
+            read_vocab(&dataset.base_dir().join(Dataset::VOCAB))?;
+        let mut writer =
+            BufWriter::new(File::create(out_dir.join("subjects.tsv"))?);
+        for subject in subjects.iter() {
+            if subject.notation.is_empty() {
+                writeln!(
+                    writer,
+                    "<{}>\t{}",
+                    subject.uri, subject.label
+                )?;
+            } else {
+                writeln!(
+                    writer,
+                    "<{}>\t{}\t{}",
+                    subject.uri, subject.label, subject.notation
+                )?;
+            }
+        }
+        writer.flush()?;
+
+        let vocab: HashMap<String, Subject> = subjects
+            .into_iter()
+            .map(|subject| (subject.uri.clone(), subject))
+            .collect();
+
+        let filter = dataset.ppn_filter(&config, remotes)?;
+        let labels_df = read_table(labels)?;
+        let mut gold: BTreeMap<String, BTreeSet<String>> =
+            BTreeMap::new();
+        let mut unknown = 0;
+        let mut discarded = 0;
+        for (ppn, uri) in strings(&labels_df, "ppn")?
+            .into_iter()
+            .zip(strings(&labels_df, "label_uri")?)
+        {
+            if let (Some(ppn), Some(uri)) = (ppn, uri) {
+                if !vocab.contains_key(&uri) {
+                    unknown += 1;
+                    continue;
+                }
+
+                if filter
+                    .as_ref()
+                    .is_some_and(|filter| !filter.contains(&ppn))
+                {
+                    discarded += 1;
+                    continue;
+                }
+
+                gold.entry(ppn).or_default().insert(uri);
+            }
+        }
+
+        if self.verbose && unknown > 0 {
+            eprintln!(
+                "ignored {unknown} label(s) not in the vocabulary"
+            );
+        }
+
+        if self.verbose && discarded > 0 {
+            eprintln!(
+                "discarded {discarded} label(s) of documents not \
+                present in any remote"
+            );
+        }
+
+        let mut df = dataset.remotes()?;
+        if !remotes.is_empty() {
+            df = df
+                .lazy()
+                .filter(col("remote").is_in(lit(Series::from_iter(
+                    remotes.iter().map(String::as_str),
+                ))))
+                .collect()?;
+        }
+
+        let names = strings(&df, "remote")?;
+        let paths = strings(&df, "path")?;
+        let idns = strings(&df, "idn")?;
+        let docs: Vec<(String, String, String)> = names
+            .into_iter()
+            .zip(paths)
+            .zip(idns)
+            .filter_map(|((name, path), idn)| match (name, path, idn) {
+                (Some(name), Some(path), Some(idn))
+                    if gold.contains_key(&idn) =>
+                {
+                    Some((name, path, idn))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let fulltext_dir = out_dir.join("fulltext");
+        let mut corpus = match format {
+            CorpusFormat::Tsv => Some(BufWriter::new(File::create(
+                out_dir.join("corpus.tsv"),
+            )?)),
+            CorpusFormat::Fulltext => {
+                fs::create_dir_all(&fulltext_dir)?;
+                None
+            }
+        };
+
+        let annif = match (push, project) {
+            (Some(url), Some(project)) => {
+                let mut url = url.clone();
+                url.set_path(&format!(
+                    "{}/v1/projects/{project}/learn",
+                    url.path().trim_end_matches('/')
+                ));
+                Some((HttpClient::from_config(&config.http)?, url))
+            }
+            _ => None,
+        };
+
+        let mut clients = HashMap::new();
+        let mut batch: Vec<LearnDocument> = vec![];
+        let mut pushed = 0;
+
+        let pbar = ProgressBarBuilder::new(PBAR_EXPORT, self.quiet)
+            .len(docs.len() as u64)
+            .build();
+
+        for (name, path, idn) in docs.iter() {
+            let remote = &config.remotes[name];
+            if !clients.contains_key(name) {
+                clients.insert(name.clone(), remote.client(&config)?);
+            }
+
+            let text = remote
+                .fetch_document(
+                    name,
+                    &clients[name],
+                    path,
+                    username.as_ref(),
+                    secret.as_ref(),
+                )
+                .await?;
+            let uris = &gold[idn];
+
+            match corpus {
+                Some(ref mut writer) => {
+                    let uris: Vec<String> = uris
+                        .iter()
+                        .map(|uri| format!("<{uri}>"))
+                        .collect();
+                    writeln!(
+                        writer,
+                        "{}\t{}",
+                        short_text(&text, *max_chars),
+                        uris.join(" ")
+                    )?;
+                }
+                None => {
+                    fs::write(
+                        fulltext_dir.join(format!("{idn}.txt")),
+                        &text,
+                    )?;
+                    fs::write(
+                        fulltext_dir.join(format!("{idn}.tsv")),
+                        subject_file(uris, &vocab),
+                    )?;
+                }
+            }
+
+            if let Some((ref client, ref url)) = annif {
+                batch.push(LearnDocument {
+                    text: short_text(&text, *max_chars),
+                    subjects: uris
+                        .iter()
+                        .map(|uri| LearnSubject {
+                            uri,
+                            label: &vocab[uri].label,
+                        })
+                        .collect(),
+                });
+
+                if batch.len() >= *batch_size {
+                    pushed += learn(client, url, &batch).await?;
+                    batch.clear();
+                }
+            }
+
+            pbar.inc(1);
+        }
+
+        if let Some((ref client, ref url)) = annif {
+            if !batch.is_empty() {
+                pushed += learn(client, url, &batch).await?;
+            }
+        }
+
+        if let Some(ref mut writer) = corpus {
+            writer.flush()?;
+        }
+
+        pbar.finish_using_style();
+
+        if self.verbose {
+            eprintln!(
+                "exported {} document(s) and {} subject(s) to {}",
+                docs.len(),
+                vocab.len(),
+                out_dir.display()
+            );
+            if annif.is_some() {
+                eprintln!("pushed {pushed} document(s) to Annif");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Sends a batch of documents to the `learn` endpoint of Annif and
+/// returns the number of documents.
+async fn learn(
+    client: &HttpClient,
+    url: &Url,
+    batch: &[LearnDocument<'_>],
+) -> DatasetResult<usize> {
+    let response = client
+        .send_once(|client| client.post(url.clone()).json(batch))
+        .await?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let message = response.text().await.unwrap_or_default();
+        bail!("unable to push documents to Annif (status = {status}): {message}");
+    }
+
+    Ok(batch.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn annif_short_text() {
+        assert_eq!(short_text("  foo\n\tbar  baz ", 0), "foo bar baz");
+        assert_eq!(short_text("äöü bar", 2), "äö");
+        assert_eq!(short_text("foo", 10), "foo");
+    }
+}
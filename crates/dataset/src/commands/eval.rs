@@ -0,0 +1,365 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+
+use clap::Parser;
+use polars::prelude::*;
+use serde::Serialize;
+
+use crate::prelude::*;
+use crate::table::{read_table, strings};
+
+/// Compare predicted subject labels against gold labels.
+///
+/// The predictions (`ppn`, `label_uri`, `score`) are ranked by score
+/// per document and compared against the gold labels (`ppn`,
+/// `label_uri` and an optional `ddc` column). The metrics are
+/// averaged over all documents of the gold standard; documents
+/// without predictions count as misses. If the gold standard contains
+/// a `ddc` column, the metrics are broken down by the DDC main class
+/// (first digit) too.
+#[derive(Debug, Parser)]
+pub(crate) struct Eval {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The gold labels (CSV or IPC).
+    #[arg(short, long, value_name = "filename")]
+    gold: PathBuf,
+
+    /// The cut-off ranks of the precision, recall and F1 scores. This
+    /// option can be specified multiple times.
+    #[arg(
+        short,
+        default_values = ["1", "3", "5", "10"],
+        value_name = "k"
+    )]
+    k: Vec<usize>,
+
+    /// Write the per-document errors (false positives and false
+    /// negatives at the largest `k`) in CSV format into `filename`.
+    #[arg(long, value_name = "filename")]
+    errors: Option<PathBuf>,
+
+    /// Write the summary (JSON) into `filename`. By default, the
+    /// summary is written to the standard output (`stdout`).
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    /// The predictions (CSV or IPC).
+    #[arg(value_name = "predictions")]
+    predictions: PathBuf,
+}
+
+/// The scores at a cut-off rank.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+struct Scores {
+    precision: f64,
+    recall: f64,
+    f1: f64,
+}
+
+/// The (averaged) metrics of a set of documents.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+struct Metrics {
+    docs: usize,
+    r_precision: f64,
+    at: BTreeMap<usize, Scores>,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    #[serde(flatten)]
+    metrics: Metrics,
+
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    ddc: BTreeMap<String, Metrics>,
+}
+
+/// Ranks the predicted labels of a document by score (descending).
+/// Repeated predictions of a label are counted once (with the maximum
+/// score).
+fn rank(mut predictions: Vec<(String, f64)>) -> Vec<String> {
+    predictions.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut seen = HashSet::new();
+    predictions
+        .into_iter()
+        .filter(|(label, _)| seen.insert(label.clone()))
+        .map(|(label, _)| label)
+        .collect()
+}
+
+/// Computes the metrics of a single document.
+fn evaluate(
+    ranked: &[&str],
+    gold: &HashSet<&str>,
+    ks: &[usize],
+) -> Metrics {
+    let hits_at = |k: usize| {
+        ranked
+            .iter()
+            .take(k)
+            .filter(|label| gold.contains(*label))
+            .count()
+    };
+
+    let mut at = BTreeMap::new();
+    for k in ks.iter().copied() {
+        let hits = hits_at(k) as f64;
+        let precision = hits / k as f64;
+        let recall = if gold.is_empty() {
+            0.0
+        } else {
+            hits / gold.len() as f64
+        };
+        let f1 = if precision + recall > 0.0 {
+            2.0 * precision * recall / (precision + recall)
+        } else {
+            0.0
+        };
+
+        at.insert(
+            k,
+            Scores {
+                precision,
+                recall,
+                f1,
+            },
+        );
+    }
+
+    let r_precision = if gold.is_empty() {
+        0.0
+    } else {
+        hits_at(gold.len()) as f64 / gold.len() as f64
+    };
+
+    Metrics {
+        docs: 1,
+        r_precision,
+        at,
+    }
+}
+
+/// Averages the metrics of documents.
+fn average<'a, I: IntoIterator<Item = &'a Metrics>>(
+    metrics: I,
+) -> Metrics {
+    let mut result = Metrics::default();
+    for m in metrics {
+        result.docs += m.docs;
+        result.r_precision += m.r_precision;
+        for (k, scores) in m.at.iter() {
+            let entry = result.at.entry(*k).or_default();
+            entry.precision += scores.precision;
+            entry.recall += scores.recall;
+            entry.f1 += scores.f1;
+        }
+    }
+
+    if result.docs > 0 {
+        let n = result.docs as f64;
+        result.r_precision /= n;
+        for scores in result.at.values_mut() {
+            scores.precision /= n;
+            scores.recall /= n;
+            scores.f1 /= n;
+        }
+    }
+
+    result
+}
+
+impl Eval {
+    pub(crate) fn execute(self) -> DatasetResult<()> {
+        if self.k.contains(&0) {
+            bail!("k must be greater than zero");
+        }
+
+        let gold_df = read_table(&self.gold)?;
+        let pred_df = read_table(&self.predictions)?;
+
+        let mut gold: HashMap<String, HashSet<String>> = HashMap::new();
+        let mut ddc: HashMap<String, String> = HashMap::new();
+        let ppns = strings(&gold_df, "ppn")?;
+        let labels = strings(&gold_df, "label_uri")?;
+        let classes = match gold_df.column("ddc") {
+            Ok(_) => strings(&gold_df, "ddc")?,
+            Err(_) => vec![None; gold_df.height()],
+        };
+
+        for ((ppn, label), class) in
+            ppns.into_iter().zip(labels).zip(classes)
+        {
+            let Some(ppn) = ppn else {
+                continue;
+            };
+
+            if let Some(class) =
+                class.and_then(|c| c.get(..1).map(String::from))
+            {
+                ddc.entry(ppn.clone()).or_insert(class);
+            }
+
+            let entry = gold.entry(ppn).or_default();
+            if let Some(label) = label {
+                entry.insert(label);
+            }
+        }
+
+        let mut predictions: HashMap<String, Vec<(String, f64)>> =
+            HashMap::new();
+        let ppns = strings(&pred_df, "ppn")?;
+        let labels = strings(&pred_df, "label_uri")?;
+        let scores =
+            pred_df.column("score")?.cast(&DataType::Float64)?;
+        for ((ppn, label), score) in
+            ppns.into_iter().zip(labels).zip(scores.f64()?.iter())
+        {
+            if let (Some(ppn), Some(label)) = (ppn, label) {
+                predictions
+                    .entry(ppn)
+                    .or_default()
+                    .push((label, score.unwrap_or_default()));
+            }
+        }
+
+        let max_k = self.k.iter().copied().max().unwrap_or(1);
+        let mut docs: Vec<&String> = gold.keys().collect();
+        docs.sort_unstable();
+
+        let mut per_doc = Vec::with_capacity(docs.len());
+        let mut errors = csv::Writer::from_writer(match self.errors {
+            Some(ref path) => {
+                Box::new(File::create(path)?) as Box<dyn Write>
+            }
+            None => Box::new(std::io::sink()),
+        });
+        errors.write_record(["ppn", "kind", "label_uri", "rank"])?;
+
+        for ppn in docs {
+            let labels = &gold[ppn];
+            let ranked =
+                rank(predictions.remove(ppn).unwrap_or_default());
+            let ranked: Vec<&str> =
+                ranked.iter().map(String::as_str).collect();
+            let gold: HashSet<&str> =
+                labels.iter().map(String::as_str).collect();
+
+            for (rank, label) in ranked.iter().take(max_k).enumerate() {
+                if !gold.contains(label) {
+                    let rank = (rank + 1).to_string();
+                    errors.write_record([
+                        ppn.as_str(),
+                        "fp",
+                        label,
+                        &rank,
+                    ])?;
+                }
+            }
+
+            let mut missed: Vec<&str> = gold
+                .iter()
+                .filter(|label| {
+                    !ranked.iter().take(max_k).any(|l| l == *label)
+                })
+                .copied()
+                .collect();
+            missed.sort_unstable();
+            for label in missed {
+                errors.write_record([ppn.as_str(), "fn", label, ""])?;
+            }
+
+            per_doc.push((ppn, evaluate(&ranked, &gold, &self.k)));
+        }
+
+        errors.flush()?;
+
+        let mut by_class: BTreeMap<String, Vec<&Metrics>> =
+            BTreeMap::new();
+        for (ppn, metrics) in per_doc.iter() {
+            if let Some(class) = ddc.get(*ppn) {
+                by_class
+                    .entry(class.clone())
+                    .or_default()
+                    .push(metrics);
+            }
+        }
+
+        let summary = Summary {
+            metrics: average(per_doc.iter().map(|(_, m)| m)),
+            ddc: by_class
+                .into_iter()
+                .map(|(class, metrics)| (class, average(metrics)))
+                .collect(),
+        };
+
+        if self.verbose {
+            eprintln!(
+                "evaluated {} document(s), {} without gold labels ignored",
+                summary.metrics.docs,
+                predictions.len()
+            );
+        }
+
+        let mut out: Box<dyn Write> = match self.output {
+            Some(ref path) => Box::new(File::create(path)?),
+            None => Box::new(stdout().lock()),
+        };
+
+        serde_json::to_writer_pretty(&mut out, &summary)
+            .map_err(DatasetError::other)?;
+        writeln!(out)?;
+        out.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eval_metrics() {
+        let gold = HashSet::from(["a", "b"]);
+        let m = evaluate(&["a", "c", "b"], &gold, &[1, 3]);
+        assert_eq!(m.r_precision, 0.5);
+        assert_eq!(m.at[&1].precision, 1.0);
+        assert_eq!(m.at[&1].recall, 0.5);
+        assert_eq!(m.at[&3].recall, 1.0);
+
+        let empty = evaluate(&[], &gold, &[1, 3]);
+        let avg = average([&m, &empty]);
+        assert_eq!(avg.docs, 2);
+        assert_eq!(avg.r_precision, 0.25);
+        assert_eq!(avg.at[&1].precision, 0.5);
+    }
+
+    #[test]
+    fn eval_rank() {
+        let ranked = rank(vec![
+            ("b".into(), 0.2),
+            ("a".into(), 0.9),
+            ("c".into(), 0.5),
+            ("b".into(), 0.7),
+        ]);
+        assert_eq!(ranked, ["a", "b", "c"]);
+
+        let gold = HashSet::from(["b"]);
+        let ranked: Vec<&str> =
+            ranked.iter().map(String::as_str).collect();
+        let m = evaluate(&ranked, &gold, &[3]);
+        assert_eq!(m.at[&3].recall, 1.0);
+    }
+}
@@ -0,0 +1,544 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::{Parser, ValueEnum};
+use polars::prelude::*;
+use serde_json::{Map, Value};
+
+use crate::prelude::*;
+use crate::table::{read_table, strings};
+
+/// Export the documents of the dataset for training.
+///
+/// The documents are downloaded from the remotes and written into
+/// shards of the given format. The `webdataset` format produces tar
+/// shards (`{prefix}-000000.tar`, ...), which contain a text file
+/// (`{key}.txt`) and a JSON file with the metadata (`{key}.json`: the
+/// index columns and the labels) per sample. The shards can be
+/// streamed by the webdataset loader without unpacking. If the dataset
+/// has an id registry (see `dataset ids`), the metadata contains the
+/// stable id of the document (`doc_id`), which doesn't change between
+/// exports and is never reused for another document.
+///
+/// With `--max-tokens` the texts are cut into model-sized chunks of at
+/// most `n` (whitespace-separated) tokens: by default, only the first
+/// chunk is exported (truncation); with `--window` overlapping chunks
+/// are exported, which start every `stride` tokens. Each chunk is a
+/// sample of its own (`{key}_{chunk}`), which carries the metadata and
+/// the labels of its document and the fields `chunk` (the number of
+/// the chunk), `chunk_start` and `chunk_end` (the byte offsets of the
+/// chunk within the document text).
+#[derive(Debug, Parser)]
+pub(crate) struct Export {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The export format.
+    #[arg(long, default_value = "webdataset")]
+    format: ExportFormat,
+
+    /// The labels of the documents (CSV or IPC) with the columns `ppn`
+    /// and `label_uri`.
+    #[arg(short, long, value_name = "filename")]
+    labels: Option<PathBuf>,
+
+    /// The maximum number of samples per shard.
+    #[arg(long, default_value = "100000", value_name = "n")]
+    max_count: usize,
+
+    /// The maximum size of a shard (in bytes). A shard is closed
+    /// before a sample would exceed the limit (unless the shard is
+    /// empty).
+    #[arg(long, default_value = "3000000000", value_name = "bytes")]
+    max_size: u64,
+
+    /// Cut the texts into chunks of at most `n` tokens. Without
+    /// `--window`, the texts are truncated after `n` tokens.
+    #[arg(long, value_name = "n")]
+    max_tokens: Option<usize>,
+
+    /// Export overlapping windows of `--max-tokens` tokens, which
+    /// start every `stride` tokens, instead of truncating the texts.
+    /// The stride must not exceed `--max-tokens`.
+    #[arg(long, value_name = "stride", requires = "max_tokens")]
+    window: Option<usize>,
+
+    /// The prefix of the shard names. By default, the name of the
+    /// dataset is used.
+    #[arg(long)]
+    prefix: Option<String>,
+
+    /// Export only the documents of the given remote. This option can
+    /// be specified multiple times. By default, the documents of all
+    /// remotes are exported.
+    #[arg(short, long = "remote", value_name = "name")]
+    remotes: Vec<String>,
+
+    /// The name of the user (HTTP basic authentication).
+    #[arg(short, long, requires = "secret")]
+    username: Option<String>,
+
+    /// The secret of the user.
+    #[arg(long, env = "DATASET_SECRET", hide_env_values = true)]
+    secret: Option<String>,
+
+    /// The output directory.
+    #[arg(short, long, value_name = "path", default_value = "export")]
+    output: PathBuf,
+}
+
+/// The export format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ExportFormat {
+    /// Tar shards of text and JSON metadata files.
+    Webdataset,
+}
+
+const PBAR_EXPORT: &str =
+    "Exporting documents: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// The size of a tar header (and the block size of the archive).
+const TAR_BLOCK: u64 = 512;
+
+/// Returns the size of a tar entry (header and padded data).
+fn entry_size(len: usize) -> u64 {
+    TAR_BLOCK + (len as u64).div_ceil(TAR_BLOCK) * TAR_BLOCK
+}
+
+/// Returns the sample key of a document. The webdataset loader splits
+/// the file name at the first dot, so the key must not contain dots
+/// (or path separators).
+fn sample_key(remote: &str, idn: &str) -> String {
+    format!("{remote}_{idn}")
+        .chars()
+        .map(|c| match c {
+            '.' | '/' | '\\' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Returns the byte ranges of the chunks of a text, which consist of
+/// at most `max_tokens` whitespace-separated tokens. Without a
+/// `stride`, only the first chunk is returned (truncation). Otherwise
+/// a chunk starts every `stride` tokens until the end of the text is
+/// covered.
+fn chunks(
+    text: &str,
+    max_tokens: usize,
+    stride: Option<usize>,
+) -> Vec<(usize, usize)> {
+    let tokens: Vec<(usize, usize)> = text
+        .split_whitespace()
+        .map(|token| {
+            let start =
+                token.as_ptr() as usize - text.as_ptr() as usize;
+            (start, start + token.len())
+        })
+        .collect();
+
+    if tokens.is_empty() {
+        return vec![(0, 0)];
+    }
+
+    let mut result = vec![];
+    let mut start = 0;
+    loop {
+        let end = (start + max_tokens).min(tokens.len());
+        result.push((tokens[start].0, tokens[end - 1].1));
+
+        match stride {
+            Some(stride) if end < tokens.len() => {
+                start += stride;
+                if start >= tokens.len() {
+                    break;
+                }
+            }
+            _ => break,
+        }
+    }
+
+    result
+}
+
+/// Converts a value of the index into a JSON value.
+fn to_json(value: AnyValue) -> Value {
+    match value {
+        AnyValue::Null => Value::Null,
+        AnyValue::Boolean(b) => Value::Bool(b),
+        AnyValue::String(s) => Value::String(s.to_string()),
+        AnyValue::StringOwned(s) => Value::String(s.to_string()),
+        AnyValue::UInt8(n) => n.into(),
+        AnyValue::UInt16(n) => n.into(),
+        AnyValue::UInt32(n) => n.into(),
+        AnyValue::UInt64(n) => n.into(),
+        AnyValue::Int8(n) => n.into(),
+        AnyValue::Int16(n) => n.into(),
+        AnyValue::Int32(n) => n.into(),
+        AnyValue::Int64(n) => n.into(),
+        AnyValue::Float32(n) => n.into(),
+        AnyValue::Float64(n) => n.into(),
+        value => Value::String(value.to_string()),
+    }
+}
+
+/// A writer of tar shards.
+struct ShardWriter {
+    dir: PathBuf,
+    prefix: String,
+    max_count: usize,
+    max_size: u64,
+    shard: usize,
+    count: usize,
+    size: u64,
+    builder: Option<tar::Builder<BufWriter<File>>>,
+}
+
+impl ShardWriter {
+    fn new(
+        dir: &Path,
+        prefix: &str,
+        max_count: usize,
+        max_size: u64,
+    ) -> Self {
+        Self {
+            dir: dir.into(),
+            prefix: prefix.into(),
+            max_count,
+            max_size,
+            shard: 0,
+            count: 0,
+            size: 0,
+            builder: None,
+        }
+    }
+
+    /// Returns the number of written shards.
+    fn shards(&self) -> usize {
+        self.shard
+    }
+
+    fn append(
+        &mut self,
+        key: &str,
+        files: &[(&str, &[u8])],
+    ) -> DatasetResult<()> {
+        let size: u64 =
+            files.iter().map(|(_, data)| entry_size(data.len())).sum();
+
+        if self.builder.is_some()
+            && (self.count >= self.max_count
+                || self.size + size > self.max_size)
+        {
+            self.close()?;
+        }
+
+        if self.builder.is_none() {
+            let path = self
+                .dir
+                .join(format!("{}-{:06}.tar", self.prefix, self.shard));
+            self.builder = Some(tar::Builder::new(BufWriter::new(
+                File::create(path)?,
+            )));
+            self.shard += 1;
+            self.count = 0;
+            self.size = 0;
+        }
+
+        let mtime = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let builder = self.builder.as_mut().unwrap();
+        for (ext, data) in files.iter() {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(mtime);
+            header.set_cksum();
+            builder.append_data(
+                &mut header,
+                format!("{key}.{ext}"),
+                *data,
+            )?;
+        }
+
+        self.count += 1;
+        self.size += size;
+        Ok(())
+    }
+
+    fn close(&mut self) -> DatasetResult<()> {
+        if let Some(builder) = self.builder.take() {
+            builder.into_inner()?.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Export {
+    pub(crate) async fn execute(self) -> DatasetResult<()> {
+        let dataset = Dataset::discover()?;
+        let config = dataset.config()?;
+
+        if self.max_count == 0 {
+            bail!("max count must be greater than zero");
+        }
+
+        if self.max_tokens == Some(0) || self.window == Some(0) {
+            bail!("max tokens and window stride must be greater than zero");
+        }
+
+        if let (Some(max_tokens), Some(stride)) =
+            (self.max_tokens, self.window)
+        {
+            if stride > max_tokens {
+                bail!(
+                    "window stride must not exceed max tokens \
+                    ({stride} > {max_tokens})"
+                );
+            }
+        }
+
+        for name in self.remotes.iter() {
+            if !config.remotes.contains_key(name) {
+                bail!("unknown remote '{name}'");
+            }
+        }
+
+        let mut df = dataset.remotes()?;
+        if !self.remotes.is_empty() {
+            df = df
+                .lazy()
+                .filter(col("remote").is_in(lit(Series::from_iter(
+                    self.remotes.iter().map(String::as_str),
+                ))))
+                .collect()?;
+        }
+
+        let mut gold: HashMap<String, Vec<String>> = HashMap::new();
+        if let Some(ref path) = self.labels {
+            let filter = dataset.ppn_filter(&config, &self.remotes)?;
+            let mut discarded = 0;
+
+            let labels_df = read_table(path)?;
+            for (ppn, uri) in strings(&labels_df, "ppn")?
+                .into_iter()
+                .zip(strings(&labels_df, "label_uri")?)
+            {
+                if let (Some(ppn), Some(uri)) = (ppn, uri) {
+                    if filter
+                        .as_ref()
+                        .is_some_and(|filter| !filter.contains(&ppn))
+                    {
+                        discarded += 1;
+                        continue;
+                    }
+
+                    gold.entry(ppn).or_default().push(uri);
+                }
+            }
+
+            if self.verbose && discarded > 0 {
+                eprintln!(
+                    "discarded {discarded} label(s) of documents not \
+                    present in any remote"
+                );
+            }
+        }
+
+        let prefix = self
+            .prefix
+            .clone()
+            .unwrap_or_else(|| config.metadata.name.clone());
+        let prefix = if prefix.is_empty() {
+            "shard".into()
+        } else {
+            prefix
+        };
+
+        fs::create_dir_all(&self.output)?;
+        let mut writer = match self.format {
+            ExportFormat::Webdataset => ShardWriter::new(
+                &self.output,
+                &prefix,
+                self.max_count,
+                self.max_size,
+            ),
+        };
+
+        let names = strings(&df, "remote")?;
+        let paths = strings(&df, "path")?;
+        let idns = strings(&df, "idn")?;
+        // Categorical columns (e.g. `kind`) are exported as strings.
+        let columns = df
+            .get_columns()
+            .iter()
+            .map(|column| match column.dtype() {
+                DataType::Categorical(..) | DataType::Enum(..) => {
+                    column.cast(&DataType::String)
+                }
+                _ => Ok(column.clone()),
+            })
+            .collect::<PolarsResult<Vec<_>>>()?;
+        let mut clients = HashMap::new();
+        let mut documents = 0;
+        let mut samples = 0;
+
+        let pbar = ProgressBarBuilder::new(PBAR_EXPORT, self.quiet)
+            .len(df.height() as u64)
+            .build();
+
+        for row in 0..df.height() {
+            pbar.inc(1);
+
+            let (Some(name), Some(path), Some(idn)) =
+                (&names[row], &paths[row], &idns[row])
+            else {
+                continue;
+            };
+
+            let remote = &config.remotes[name];
+            if !clients.contains_key(name) {
+                clients.insert(name.clone(), remote.client(&config)?);
+            }
+
+            let text = remote
+                .fetch_document(
+                    name,
+                    &clients[name],
+                    path,
+                    self.username.as_ref(),
+                    self.secret.as_ref(),
+                )
+                .await?;
+
+            let mut metadata = Map::new();
+            for column in columns.iter() {
+                metadata.insert(
+                    column.name().to_string(),
+                    to_json(column.get(row)?),
+                );
+            }
+
+            metadata.insert(
+                "labels".into(),
+                gold.get(idn).cloned().unwrap_or_default().into(),
+            );
+
+            let key = sample_key(name, idn);
+            let Some(max_tokens) = self.max_tokens else {
+                let json = serde_json::to_vec(&metadata)
+                    .map_err(DatasetError::other)?;
+                writer.append(
+                    &key,
+                    &[("txt", text.as_bytes()), ("json", &json)],
+                )?;
+                documents += 1;
+                continue;
+            };
+
+            let spans = chunks(&text, max_tokens, self.window);
+            if !spans.is_empty() {
+                documents += 1;
+            }
+
+            for (chunk, (start, end)) in spans.into_iter().enumerate() {
+                metadata.insert("chunk".into(), chunk.into());
+                metadata.insert("chunk_start".into(), start.into());
+                metadata.insert("chunk_end".into(), end.into());
+
+                let json = serde_json::to_vec(&metadata)
+                    .map_err(DatasetError::other)?;
+                writer.append(
+                    &format!("{key}_{chunk}"),
+                    &[
+                        ("txt", text[start..end].as_bytes()),
+                        ("json", &json),
+                    ],
+                )?;
+                samples += 1;
+            }
+        }
+
+        writer.close()?;
+        pbar.finish_using_style();
+
+        if self.verbose {
+            eprintln!(
+                "exported {documents} document(s) into {} shard(s)",
+                writer.shards()
+            );
+
+            if self.max_tokens.is_some() {
+                eprintln!("exported {samples} chunk(s)");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn webdataset_shards() -> TestResult {
+        let dir = temp_dir()?;
+        let dir = dir.path();
+
+        assert_eq!(sample_key("foo", "1.2/3"), "foo_1_2_3");
+        assert_eq!(entry_size(0), 512);
+        assert_eq!(entry_size(513), 1536);
+
+        let mut writer = ShardWriter::new(dir, "test", 2, u64::MAX);
+        for key in ["a", "b", "c"] {
+            writer.append(
+                key,
+                &[("txt", b"text".as_slice()), ("json", b"{}")],
+            )?;
+        }
+        writer.close()?;
+
+        assert_eq!(writer.shards(), 2);
+        let mut archive =
+            tar::Archive::new(File::open(dir.join("test-000000.tar"))?);
+        let names: Vec<String> = archive
+            .entries()?
+            .map(|e| e.unwrap().path().unwrap().display().to_string())
+            .collect();
+        assert_eq!(names, ["a.txt", "a.json", "b.txt", "b.json"]);
+        Ok(())
+    }
+
+    #[test]
+    fn text_chunks() {
+        let text = "a bb  ccc\nd e";
+        assert_eq!(chunks(text, 2, None), [(0, 4)]);
+        assert_eq!(chunks(text, 10, None), [(0, 13)]);
+        assert_eq!(
+            chunks(text, 2, Some(2)),
+            [(0, 4), (6, 11), (12, 13)]
+        );
+        assert_eq!(chunks(text, 3, Some(2)), [(0, 9), (6, 13)]);
+        assert_eq!(chunks("", 2, Some(1)), [(0, 0)]);
+    }
+}
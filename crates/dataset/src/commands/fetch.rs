@@ -1,13 +1,15 @@
-use std::fs::File;
-use std::io::{stdout, Cursor};
+use std::fs;
+use std::io::stdout;
 use std::path::PathBuf;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use clap::Parser;
 use indicatif::{HumanCount, ProgressBar};
 use polars::prelude::*;
-use polars::sql::SQLContext;
 
+use crate::atomic::AtomicFile;
+use crate::ids::{doc_keys, IdRegistry};
+use crate::lockfile::{LockedRemote, Lockfile};
 use crate::prelude::*;
 
 #[derive(Debug, Parser)]
@@ -33,6 +35,12 @@ pub(crate) struct Fetch {
     /// the root directory.
     #[arg(short, long, value_name = "filename")]
     output: Option<PathBuf>,
+
+    /// Fetch only the remotes of the given group. The documents of
+    /// all other remotes are taken from the current compound index.
+    /// This option can be specified multiple times.
+    #[arg(short, long = "group", value_name = "name")]
+    groups: Vec<String>,
 }
 
 impl Fetch {
@@ -40,10 +48,55 @@ impl Fetch {
         let dataset = Dataset::discover()?;
         let dot_dir = dataset.dot_dir();
         let config = dataset.config()?;
-        let remotes = config.remotes;
+        let mut lockfile = Lockfile::default();
+        let mut blooms = vec![];
         let mut dfs = vec![];
 
-        for (name, remote) in remotes.iter() {
+        for group in self.groups.iter() {
+            if !config.groups.contains_key(group) {
+                bail!("unknown group '{group}'");
+            }
+        }
+
+        let remotes = config
+            .remotes
+            .iter()
+            .filter(|(_, remote)| remote.in_groups(&self.groups));
+
+        if !self.groups.is_empty() {
+            let fetched: Vec<&str> = remotes
+                .clone()
+                .map(|(name, _)| name.as_str())
+                .collect();
+
+            if dot_dir.join(Dataset::REMOTES).is_file() {
+                let cached = dataset
+                    .remotes()?
+                    .lazy()
+                    .filter(
+                        col("remote")
+                            .is_in(lit(Series::from_iter(
+                                fetched.clone(),
+                            )))
+                            .not(),
+                    )
+                    .collect()?;
+
+                if cached.height() > 0 {
+                    dfs.push(cached.lazy());
+                }
+            }
+
+            let locked =
+                Lockfile::from_path(dot_dir.join(Dataset::LOCK))?;
+            lockfile.remotes = locked
+                .remotes
+                .into_iter()
+                .filter(|(name, _)| !fetched.contains(&name.as_str()))
+                .collect();
+        }
+
+        for (name, remote) in remotes {
             let pbar = if !self.quiet {
                 ProgressBar::new_spinner()
             } else {
@@ -53,34 +106,44 @@ impl Fetch {
             pbar.enable_steady_tick(Duration::from_millis(100));
             pbar.set_message(format!("Fetching {name}..."));
 
-            let mut index_url = remote.url.clone();
-            index_url.set_path("/index.ipc");
-
-            let body = reqwest::get(index_url).await?.bytes().await?;
-            if body.is_empty() {
-                bail!(
-                    "unable to get datashed index (remote = {})",
-                    name
-                );
-            }
-
-            let mut index =
-                IpcReader::new(Cursor::new(body)).finish()?;
-            if let Some(ref predicate) = remote.predicate {
-                let mut ctx = SQLContext::new();
-                ctx.register("index", index.lazy());
-                index = ctx
-                    .execute(&format!(
-                        "SELECT * FROM index WHERE {predicate}"
-                    ))?
-                    .collect()?
-            }
-
-            let cnt = index.height();
+            let index = remote.fetch_index(name, &config).await?;
+            let cnt = index.df.height();
             if cnt > 0 {
-                dfs.push(index.lazy());
+                dfs.push(index.df.lazy());
             }
 
+            let bloom = match remote
+                .fetch_bloom(&config, index.etag.as_deref())
+                .await
+            {
+                Ok(bloom) => bloom,
+                Err(e) => {
+                    if self.verbose {
+                        eprintln!(
+                            "warning: unable to get the Bloom filter \
+                            of {name}: {e}"
+                        );
+                    }
+
+                    None
+                }
+            };
+            blooms.push((name.clone(), bloom));
+
+            lockfile.remotes.insert(
+                name.clone(),
+                LockedRemote {
+                    url: remote.url.clone(),
+                    hash: index.hash,
+                    etag: index.etag,
+                    documents: cnt as u64,
+                    fetched: SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or_default(),
+                },
+            );
+
             pbar.finish_and_clear();
 
             if !self.quiet {
@@ -109,22 +172,60 @@ impl Fetch {
             .select([col("*").shrink_dtype()])
             .collect()?;
 
+        let ids_path = dot_dir.join(Dataset::IDS);
+        if ids_path.is_file() {
+            pbar.set_message("Assigning document ids");
+
+            let mut registry = IdRegistry::from_path(&ids_path)?;
+            if registry.check(&[]).has_collisions() {
+                bail!(
+                    "id registry contains collisions \
+                    (run `dataset ids repair`)"
+                );
+            }
+
+            registry.assign(&doc_keys(&df)?);
+            df = registry.attach(df)?;
+            registry.save(&ids_path)?;
+        }
+
         match self.output {
             Some(path) => {
-                let mut writer = IpcWriter::new(File::create(path)?)
+                let mut out = AtomicFile::create(path)?;
+                let mut writer = IpcWriter::new(&mut out)
                     .with_compression(Some(IpcCompression::ZSTD));
                 writer.finish(&mut df)?;
+                out.commit()?;
             }
             None if self.stdout => {
                 let mut writer = CsvWriter::new(stdout().lock());
                 writer.finish(&mut df)?;
             }
             None => {
-                let mut writer = IpcWriter::new(File::create(
-                    dot_dir.join(Dataset::REMOTES),
-                )?)
-                .with_compression(Some(IpcCompression::ZSTD));
+                let mut out =
+                    AtomicFile::create(dot_dir.join(Dataset::REMOTES))?;
+                let mut writer = IpcWriter::new(&mut out)
+                    .with_compression(Some(IpcCompression::ZSTD));
                 writer.finish(&mut df)?;
+                out.commit()?;
+                lockfile.save(dot_dir.join(Dataset::LOCK))?;
+
+                let bloom_dir = dataset.bloom_dir();
+                fs::create_dir_all(&bloom_dir)?;
+                for (name, bloom) in blooms.into_iter() {
+                    let path = bloom_dir.join(format!("{name}.bloom"));
+                    match bloom {
+                        Some(bloom) => {
+                            let mut out = AtomicFile::create(path)?;
+                            bloom.write(&mut out)?;
+                            out.commit()?;
+                        }
+                        None if path.is_file() => {
+                            fs::remove_file(path)?
+                        }
+                        None => (),
+                    }
+                }
             }
         }
 
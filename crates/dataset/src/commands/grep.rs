@@ -0,0 +1,244 @@
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+
+use clap::Parser;
+use csv::WriterBuilder;
+use polars::prelude::*;
+use serde::Deserialize;
+
+use crate::atomic::AtomicFile;
+use crate::prelude::*;
+
+/// Search the documents of the remotes for a pattern.
+///
+/// The search is executed by the servers of the remotes (`datashed
+/// serve`, route `/api/grep`), so that no filesystem access to the
+/// datasheds is required. Only documents the user is allowed to
+/// access are searched. The matching documents are streamed back and
+/// written as CSV (remote, path, hash and snippet) to the standard
+/// output, unless `--output` is set.
+#[derive(Debug, Parser)]
+pub(crate) struct Grep {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Search only the given remote. This option can be specified
+    /// multiple times. By default, all remotes are searched.
+    #[arg(short, long = "remote", value_name = "name")]
+    remotes: Vec<String>,
+
+    /// If set, the pattern will be searched case insensitive.
+    #[arg(long = "ignore-case", short = 'i')]
+    case_ignore: bool,
+
+    /// Return documents that don't match.
+    #[arg(long = "invert-match")]
+    invert: bool,
+
+    /// Use only the first NUM bytes to search for the given pattern.
+    #[arg(long, short = 'n', value_name = "NUM")]
+    max_bytes: Option<u64>,
+
+    /// Include a snippet of the first match of each document.
+    #[arg(long)]
+    snippets: bool,
+
+    /// Return at most `n` documents per remote.
+    #[arg(long, value_name = "n")]
+    limit: Option<usize>,
+
+    /// The name of the user (HTTP basic authentication).
+    #[arg(short, long, requires = "secret")]
+    username: Option<String>,
+
+    /// The secret of the user.
+    #[arg(long, env = "DATASET_SECRET", hide_env_values = true)]
+    secret: Option<String>,
+
+    /// Write the matches into `filename` (Arrow IPC). By default the
+    /// matches are written in CSV format to the standard output.
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    /// An optional predicate to filter the documents (evaluated by
+    /// the server).
+    #[arg(long = "where")]
+    predicate: Option<String>,
+
+    /// A regular expression used for searching.
+    pattern: String,
+}
+
+/// A line of the `/api/grep` response.
+#[derive(Debug, Deserialize)]
+struct Match {
+    #[serde(default)]
+    path: String,
+    #[serde(default)]
+    hash: String,
+    snippet: Option<String>,
+    error: Option<String>,
+}
+
+impl Grep {
+    pub(crate) async fn execute(self) -> DatasetResult<()> {
+        let dataset = Dataset::discover()?;
+        let config = dataset.config()?;
+
+        for name in self.remotes.iter() {
+            if !config.remotes.contains_key(name) {
+                bail!("unknown remote '{name}'");
+            }
+        }
+
+        let mut remotes: Vec<_> = config
+            .remotes
+            .iter()
+            .filter(|(name, _)| {
+                self.remotes.is_empty() || self.remotes.contains(name)
+            })
+            .collect();
+        remotes.sort_by_key(|(name, _)| *name);
+
+        let mut writer = self.output.is_none().then(|| {
+            let mut writer = WriterBuilder::new().from_writer(stdout());
+            let _ = writer
+                .write_record(["remote", "path", "hash", "snippet"]);
+            writer
+        });
+
+        let mut matches: Vec<(String, Match)> = vec![];
+
+        for (name, remote) in remotes.into_iter() {
+            let client = remote.client(&config)?;
+            let mut url = remote.grep_url();
+            {
+                let mut query = url.query_pairs_mut();
+                query.append_pair("pattern", &self.pattern);
+                if self.case_ignore {
+                    query.append_pair("ignore_case", "true");
+                }
+                if self.invert {
+                    query.append_pair("invert_match", "true");
+                }
+                if self.snippets {
+                    query.append_pair("snippets", "true");
+                }
+                if let Some(ref predicate) = self.predicate {
+                    query.append_pair("where", predicate);
+                }
+                if let Some(n) = self.max_bytes {
+                    query.append_pair("max_bytes", &n.to_string());
+                }
+                if let Some(n) = self.limit {
+                    query.append_pair("limit", &n.to_string());
+                }
+            }
+
+            let mut response = client
+                .send(|client| {
+                    let request = client.get(url.clone());
+                    match self.username {
+                        Some(ref username) => request
+                            .basic_auth(username, self.secret.as_ref()),
+                        None => request,
+                    }
+                })
+                .await?;
+
+            if !response.status().is_success() {
+                bail!(
+                    "unable to search remote '{name}' (status = {})",
+                    response.status()
+                );
+            }
+
+            let mut count = 0;
+            let mut buf: Vec<u8> = vec![];
+
+            while let Some(chunk) = response.chunk().await? {
+                buf.extend_from_slice(&chunk);
+
+                while let Some(pos) =
+                    buf.iter().position(|b| *b == b'\n')
+                {
+                    let line: Vec<u8> = buf.drain(..=pos).collect();
+                    let m: Match = serde_json::from_slice(&line)
+                        .map_err(DatasetError::other)?;
+
+                    if let Some(ref error) = m.error {
+                        bail!(
+                            "unable to search remote '{name}': {error}"
+                        );
+                    }
+
+                    count += 1;
+                    if let Some(ref mut writer) = writer {
+                        writer.write_record([
+                            name.as_str(),
+                            &m.path,
+                            &m.hash,
+                            m.snippet.as_deref().unwrap_or_default(),
+                        ])?;
+                        writer.flush()?;
+                    } else {
+                        matches.push((name.clone(), m));
+                    }
+                }
+            }
+
+            if self.verbose {
+                eprintln!("{name}: {count} matching document(s).");
+            }
+        }
+
+        if let Some(path) = self.output {
+            let mut df = DataFrame::new(vec![
+                Column::new(
+                    "remote".into(),
+                    matches
+                        .iter()
+                        .map(|(name, _)| name.as_str())
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "path".into(),
+                    matches
+                        .iter()
+                        .map(|(_, m)| m.path.as_str())
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "hash".into(),
+                    matches
+                        .iter()
+                        .map(|(_, m)| m.hash.as_str())
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "snippet".into(),
+                    matches
+                        .iter()
+                        .map(|(_, m)| m.snippet.as_deref())
+                        .collect::<Vec<_>>(),
+                ),
+            ])?;
+
+            let mut out = AtomicFile::create(path)?;
+            IpcWriter::new(&mut out)
+                .with_compression(Some(IpcCompression::ZSTD))
+                .finish(&mut df)?;
+            out.commit()?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use clap::Parser;
+
+use crate::audit;
+use crate::ids::{doc_keys, IdRegistry};
+use crate::prelude::*;
+
+/// Manage the stable document ids of the dataset.
+///
+/// The ids are stored in the registry `.dataset/ids.csv`, which maps
+/// the key of a document (remote and path) to its id. Once the
+/// registry exists, `dataset fetch` assigns ids to new documents and
+/// adds them as `doc_id` column to the compound index (and thus to the
+/// metadata of exported samples). A document keeps its id as long as
+/// its key doesn't change; ids of removed documents are never reused.
+/// Changes of the registry (`assign` and `repair`) are recorded in the
+/// audit log `.dataset/audit.jsonl`.
+#[derive(Debug, Parser)]
+pub(crate) struct Ids {
+    #[clap(subcommand)]
+    cmd: Command,
+}
+
+#[derive(Debug, Parser)]
+pub(crate) enum Command {
+    /// Assign ids to all documents of the compound index without an
+    /// id. If the registry doesn't exist, it's created.
+    Assign {
+        /// Run verbosely. Print additional progress information to the
+        /// standard error stream.
+        #[arg(short, long)]
+        verbose: bool,
+    },
+
+    /// Check the registry for collisions (duplicate ids or keys) and
+    /// report documents without an id and ids of removed documents.
+    /// The command fails, if the registry contains collisions.
+    Check {
+        /// Operate quietly; only report collisions.
+        #[arg(short, long)]
+        quiet: bool,
+    },
+
+    /// Resolve the collisions of the registry. Records with an id,
+    /// which is already taken, get new ids and duplicate ids of the
+    /// same document are retired (the smallest id is kept).
+    Repair {
+        /// Run verbosely. Print additional progress information to the
+        /// standard error stream.
+        #[arg(short, long)]
+        verbose: bool,
+    },
+}
+
+impl Ids {
+    pub(crate) fn execute(self) -> DatasetResult<()> {
+        let dataset = Dataset::discover()?;
+        let path = dataset.dot_dir().join(Dataset::IDS);
+        let mut registry = IdRegistry::from_path(&path)?;
+
+        match self.cmd {
+            Command::Assign { verbose } => {
+                let check = registry.check(&[]);
+                if check.has_collisions() {
+                    bail!(
+                        "id registry contains collisions \
+                        (run `dataset ids repair`)"
+                    );
+                }
+
+                let keys = doc_keys(&dataset.remotes()?)?;
+                let count = registry.assign(&keys);
+                registry.save(&path)?;
+
+                if count > 0 {
+                    audit::record(&dataset, "ids assign", count)?;
+                }
+
+                if verbose {
+                    eprintln!("assigned {count} new id(s).");
+                }
+            }
+            Command::Check { quiet } => {
+                let keys = doc_keys(&dataset.remotes()?)?;
+                let check = registry.check(&keys);
+
+                for id in check.duplicate_ids.iter() {
+                    eprintln!("duplicate id: {id}");
+                }
+
+                for (remote, path) in check.duplicate_keys.iter() {
+                    eprintln!("duplicate key: {remote}:{path}");
+                }
+
+                if !quiet {
+                    for (remote, path) in check.missing.iter() {
+                        eprintln!("missing id: {remote}:{path}");
+                    }
+
+                    for id in check.orphaned.iter() {
+                        eprintln!("orphaned id: {id}");
+                    }
+                }
+
+                if check.has_collisions() {
+                    bail!(
+                        "id registry contains {} duplicate id(s) and \
+                        {} duplicate key(s)",
+                        check.duplicate_ids.len(),
+                        check.duplicate_keys.len()
+                    );
+                }
+            }
+            Command::Repair { verbose } => {
+                let count = registry.repair();
+                registry.save(&path)?;
+
+                if count > 0 {
+                    audit::record(&dataset, "ids repair", count)?;
+                }
+
+                if verbose {
+                    eprintln!("repaired {count} record(s).");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,15 +1,37 @@
+pub(crate) use annif::Annif;
 pub(crate) use completions::Completions;
 pub(crate) use config::Config;
+pub(crate) use eval::Eval;
+pub(crate) use export::Export;
 pub(crate) use fetch::Fetch;
+pub(crate) use grep::Grep;
+pub(crate) use ids::Ids;
 pub(crate) use init::Init;
+pub(crate) use pica_test::PicaTest;
+pub(crate) use prune::Prune;
+pub(crate) use publish::Publish;
 pub(crate) use remote::Remote;
+pub(crate) use sru::Sru;
+pub(crate) use status::Status;
 pub(crate) use version::Version;
 pub(crate) use vocab::Vocab;
+pub(crate) use weak_label::WeakLabel;
 
+mod annif;
 mod completions;
 mod config;
+mod eval;
+mod export;
 mod fetch;
+mod grep;
+mod ids;
 mod init;
+mod pica_test;
+mod prune;
+mod publish;
 mod remote;
+mod sru;
+mod status;
 mod version;
 mod vocab;
+mod weak_label;
@@ -0,0 +1,267 @@
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use clap::Parser;
+use pica_record::prelude::*;
+
+use crate::prelude::*;
+
+/// Evaluate PICA+ filter and path expressions against a dump.
+///
+/// This command helps to develop the record matchers and path
+/// expressions of the config (e.g. kind refinements or vocab targets)
+/// without a full sync run. For each record, which matches the
+/// `--filter` expression, the PPN and the values of the `--path`
+/// expressions are printed; the evaluation stops after `--limit`
+/// matches.
+///
+/// Without `--filter` and `--path`, an interactive prompt is started,
+/// which reads one command per line: `filter <expr>` and `path <expr>`
+/// set the filter and add a path expression, `clear` removes the path
+/// expressions, `limit <n>` changes the number of matches, `run`
+/// re-evaluates the expressions and `quit` leaves the prompt. Each
+/// change of the expressions is evaluated immediately.
+#[derive(Debug, Parser)]
+pub(crate) struct PicaTest {
+    /// The record matcher, which selects the records. By default, all
+    /// records are selected.
+    #[arg(short, long, value_name = "expr")]
+    filter: Option<String>,
+
+    /// A path expression, whose values are shown for each matching
+    /// record. This option can be specified multiple times.
+    #[arg(short, long = "path", value_name = "expr")]
+    paths: Vec<String>,
+
+    /// The maximum number of matching records to show.
+    #[arg(short, long, default_value = "10", value_name = "n")]
+    limit: usize,
+
+    /// The maximum number of records to read from the dump. By
+    /// default, the whole dump is read (until `--limit` records
+    /// matched).
+    #[arg(long, value_name = "n")]
+    sample: Option<usize>,
+
+    /// Compare strings case-insensitively.
+    #[arg(short = 'i', long)]
+    case_ignore: bool,
+
+    /// The threshold of string similarity comparisons.
+    #[arg(long, default_value = "0.8", value_name = "value")]
+    strsim_threshold: f64,
+
+    /// The path to the PICA+ dump.
+    dump: PathBuf,
+}
+
+/// The expressions, which are evaluated against the dump.
+#[derive(Default)]
+struct Query {
+    filter: Option<(String, RecordMatcher)>,
+    paths: Vec<(String, Path)>,
+    limit: usize,
+}
+
+impl Query {
+    fn set_filter(&mut self, expr: &str) -> DatasetResult<()> {
+        self.filter = Some((expr.into(), RecordMatcher::new(expr)?));
+        Ok(())
+    }
+
+    fn add_path(&mut self, expr: &str) -> DatasetResult<()> {
+        self.paths.push((expr.into(), Path::new(expr)?));
+        Ok(())
+    }
+}
+
+impl PicaTest {
+    /// Evaluates the query against the dump and writes the matching
+    /// records into `out`. Returns the number of matching and read
+    /// records.
+    fn evaluate<W: Write>(
+        &self,
+        query: &Query,
+        out: &mut W,
+    ) -> DatasetResult<(usize, usize)> {
+        let options = MatcherOptions::new()
+            .strsim_threshold(self.strsim_threshold)
+            .case_ignore(self.case_ignore);
+
+        let mut reader = ReaderBuilder::new().from_path(&self.dump)?;
+        let (mut matches, mut records) = (0, 0);
+
+        while let Some(result) = reader.next_byte_record() {
+            if matches >= query.limit
+                || self.sample.is_some_and(|n| records >= n)
+            {
+                break;
+            }
+
+            records += 1;
+            let Ok(record) = result else {
+                continue;
+            };
+
+            if let Some((_, ref matcher)) = query.filter {
+                if !matcher.is_match(&record, &options) {
+                    continue;
+                }
+            }
+
+            matches += 1;
+            writeln!(out, "{}", record.ppn())?;
+
+            for (expr, path) in query.paths.iter() {
+                let values: Vec<String> = record
+                    .path(path, &options)
+                    .map(ToString::to_string)
+                    .collect();
+
+                writeln!(out, "  {expr}: {}", values.join("; "))?;
+            }
+        }
+
+        Ok((matches, records))
+    }
+
+    fn prompt(&self, mut query: Query) -> DatasetResult<()> {
+        let stdin = io::stdin();
+        let mut stdout = io::stdout().lock();
+        let mut lines = stdin.lock().lines();
+
+        loop {
+            eprint!("pica> ");
+            io::stderr().flush()?;
+
+            let Some(line) = lines.next() else {
+                eprintln!();
+                break;
+            };
+
+            let line = line?;
+            let (cmd, arg) = match line.trim().split_once(' ') {
+                Some((cmd, arg)) => (cmd, arg.trim()),
+                None => (line.trim(), ""),
+            };
+
+            let result = match cmd {
+                "" => continue,
+                "quit" | "exit" => break,
+                "run" => Ok(()),
+                "filter" if arg.is_empty() => {
+                    query.filter = None;
+                    Ok(())
+                }
+                "filter" => query.set_filter(arg),
+                "path" => query.add_path(arg),
+                "clear" => {
+                    query.paths.clear();
+                    Ok(())
+                }
+                "limit" => match arg.parse() {
+                    Ok(limit) => {
+                        query.limit = limit;
+                        Ok(())
+                    }
+                    Err(_) => Err(DatasetError::other(format!(
+                        "invalid limit '{arg}'"
+                    ))),
+                },
+                _ => Err(DatasetError::other(format!(
+                    "unknown command '{cmd}' (expected filter, path, \
+                        clear, limit, run or quit)"
+                ))),
+            };
+
+            if let Err(e) = result {
+                eprintln!("error: {e}");
+                continue;
+            }
+
+            match self.evaluate(&query, &mut stdout) {
+                Ok((matches, records)) => {
+                    stdout.flush()?;
+                    eprintln!(
+                        "{matches} of {records} record(s) matched."
+                    );
+                }
+                Err(e) => eprintln!("error: {e}"),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn execute(self) -> DatasetResult<()> {
+        let mut query = Query {
+            limit: self.limit,
+            ..Default::default()
+        };
+
+        if let Some(ref filter) = self.filter {
+            query.set_filter(filter)?;
+        }
+
+        for path in self.paths.iter() {
+            query.add_path(path)?;
+        }
+
+        if self.filter.is_none() && self.paths.is_empty() {
+            return self.prompt(query);
+        }
+
+        let mut out = io::stdout().lock();
+        self.evaluate(&query, &mut out)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::testing::temp_dir;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn pica_test_evaluate() -> TestResult {
+        let dir = temp_dir()?;
+        let dump = dir.path().join("dump.dat");
+        fs::write(
+            &dump,
+            b"003@ \x1f01\x1e002@ \x1f0Aa\x1e\n\
+            003@ \x1f02\x1e002@ \x1f0Oa\x1e\n\
+            003@ \x1f03\x1e002@ \x1f0Aa\x1e\n",
+        )?;
+
+        let cmd = PicaTest::try_parse_from([
+            "pica-test",
+            dump.to_str().unwrap(),
+        ])?;
+
+        let mut query = Query {
+            limit: 10,
+            ..Default::default()
+        };
+        query.set_filter("002@.0 == 'Aa'")?;
+        query.add_path("002@.0")?;
+        assert!(query.set_filter("002@.0 ==").is_err());
+        assert!(query.add_path("300@.0").is_err());
+
+        let mut out = vec![];
+        assert_eq!(cmd.evaluate(&query, &mut out)?, (2, 3));
+        assert_eq!(
+            String::from_utf8(out)?,
+            "1\n  002@.0: Aa\n3\n  002@.0: Aa\n"
+        );
+
+        query.limit = 1;
+        let mut out = vec![];
+        assert_eq!(cmd.evaluate(&query, &mut out)?, (1, 1));
+        Ok(())
+    }
+}
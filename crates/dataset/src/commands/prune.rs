@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+
+use clap::Parser;
+use csv::WriterBuilder;
+use polars::prelude::*;
+
+use crate::atomic::AtomicFile;
+use crate::prelude::*;
+use crate::prune::{optional_strings, prune, Exclusions};
+
+/// Drop documents violating dataset-level constraints.
+///
+/// The constraints of the `[prune]` config (or of the command line
+/// options, which take precedence) are applied to the compound index:
+/// exclusion lists, a language whitelist, the maximum number of
+/// documents per record (`idn`) and per-class caps. If a cap is
+/// exceeded, the documents are kept in the order of the compound index
+/// or, with `--prefer`, by the largest values of the given column.
+///
+/// The pruned index replaces the compound index (unless `--output` or
+/// `--dry-run` is given); the next `dataset fetch` restores the
+/// removed documents. The reasons table (`remote`, `path`, `idn`,
+/// `reason` and `value`) explains each removal.
+#[derive(Debug, Parser)]
+pub(crate) struct Prune {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The maximum number of documents per record (`idn`).
+    #[arg(long, value_name = "n")]
+    max_per_ppn: Option<usize>,
+
+    /// An allowed language (`lang_code`). This option can be specified
+    /// multiple times.
+    #[arg(long = "lang", value_name = "code")]
+    languages: Vec<String>,
+
+    /// The column of the per-class caps (default: `kind`).
+    #[arg(long, value_name = "column")]
+    class_column: Option<String>,
+
+    /// The maximum number of documents of a class (`class=n`). This
+    /// option can be specified multiple times.
+    #[arg(long = "cap", value_parser = parse_cap, value_name = "class=n")]
+    caps: Vec<(String, usize)>,
+
+    /// A table (CSV or IPC) of excluded documents with an `idn` (or
+    /// `ppn`) and/or a `path` column. This option can be specified
+    /// multiple times.
+    #[arg(long, value_name = "filename")]
+    exclude: Vec<PathBuf>,
+
+    /// Keep the documents with the largest values of the given
+    /// (numeric) column first, if a cap is exceeded.
+    #[arg(long, value_name = "column")]
+    prefer: Option<String>,
+
+    /// Don't write the pruned index; only report the removals.
+    #[arg(long, conflicts_with = "output")]
+    dry_run: bool,
+
+    /// Write the pruned index into `filename` instead of replacing the
+    /// compound index.
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    /// Write the reasons table (CSV) into `filename`. By default, the
+    /// table is written to the standard output (`stdout`).
+    #[arg(long, value_name = "filename")]
+    reasons: Option<PathBuf>,
+}
+
+fn parse_cap(s: &str) -> Result<(String, usize), String> {
+    let Some((class, n)) = s.rsplit_once('=') else {
+        return Err(format!("expected class=n, got '{s}'"));
+    };
+
+    let n = n.parse().map_err(|_| format!("invalid cap '{n}'"))?;
+    Ok((class.into(), n))
+}
+
+impl Prune {
+    pub(crate) fn execute(self) -> DatasetResult<()> {
+        let dataset = Dataset::discover()?;
+        let mut config = dataset.config()?.prune;
+
+        if self.max_per_ppn.is_some() {
+            config.max_per_ppn = self.max_per_ppn;
+        }
+
+        if !self.languages.is_empty() {
+            config.languages = self.languages;
+        }
+
+        if self.class_column.is_some() {
+            config.class_column = self.class_column;
+        }
+
+        if !self.caps.is_empty() {
+            config.caps = BTreeMap::from_iter(self.caps);
+        }
+
+        if !self.exclude.is_empty() {
+            config.exclude = self.exclude;
+        }
+
+        if self.prefer.is_some() {
+            config.prefer = self.prefer;
+        }
+
+        let exclusions = Exclusions::from_paths(
+            &config.exclude,
+            dataset.base_dir(),
+        )?;
+        let df = dataset.remotes()?;
+        let (keep, reasons) = prune(&df, &config, &exclusions)?;
+
+        let remotes = optional_strings(&df, "remote")?;
+        let paths = optional_strings(&df, "path")?;
+        let idns = optional_strings(&df, "idn")?;
+
+        let inner: Box<dyn Write> = match self.reasons {
+            Some(ref path) => Box::new(File::create(path)?),
+            None => Box::new(stdout().lock()),
+        };
+
+        let mut writer = WriterBuilder::new().from_writer(inner);
+        writer.write_record([
+            "remote", "path", "idn", "reason", "value",
+        ])?;
+        for (idx, (reason, value)) in reasons.iter() {
+            writer.write_record([
+                remotes[*idx].as_deref().unwrap_or_default(),
+                paths[*idx].as_deref().unwrap_or_default(),
+                idns[*idx].as_deref().unwrap_or_default(),
+                reason.to_string().as_str(),
+                value.as_str(),
+            ])?;
+        }
+
+        writer.flush()?;
+
+        if self.verbose {
+            eprintln!(
+                "removed {} of {} document(s).",
+                reasons.len(),
+                df.height()
+            );
+        }
+
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let mask = BooleanChunked::from_slice("keep".into(), &keep);
+        let mut df = df.filter(&mask)?;
+
+        let path = self.output.unwrap_or_else(|| {
+            dataset.dot_dir().join(Dataset::REMOTES)
+        });
+        let mut out = AtomicFile::create(path)?;
+        let mut writer = IpcWriter::new(&mut out)
+            .with_compression(Some(IpcCompression::ZSTD));
+        writer.finish(&mut df)?;
+        out.commit()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,438 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use clap::Parser;
+use polars::prelude::*;
+use url::Url;
+
+use crate::http::HttpClient;
+use crate::hub::{is_repo_id, shard_path, Hub, HubFile};
+use crate::prelude::*;
+use crate::table::{read_table, strings};
+
+/// Publish the dataset on the Hugging Face Hub.
+///
+/// The documents of the dataset are downloaded from the remotes and
+/// written into Parquet shards (the index columns, the `text` and the
+/// `labels` of each document), following the layout of the datasets
+/// library (`data/{split}-00000-of-00001.parquet`). A dataset card
+/// (`README.md`) is generated from the config metadata and summary
+/// statistics. The files are uploaded via the Hub API; large files are
+/// uploaded in chunks and files already stored by the Hub are skipped,
+/// so an interrupted upload can be resumed by running the command
+/// again.
+#[derive(Debug, Parser)]
+pub(crate) struct Publish {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The dataset repository on the Hub (`namespace/name`).
+    #[arg(long, value_name = "repo")]
+    hf_repo: String,
+
+    /// The labels of the documents (CSV or IPC) with the columns `ppn`
+    /// and `label_uri`.
+    #[arg(short, long, value_name = "filename")]
+    labels: Option<PathBuf>,
+
+    /// The name of the split.
+    #[arg(long, default_value = "train")]
+    split: String,
+
+    /// The maximum number of documents per Parquet shard.
+    #[arg(long, default_value = "10000", value_name = "n")]
+    shard_size: usize,
+
+    /// The license identifier of the dataset card (e.g. `cc-by-4.0`).
+    #[arg(long, value_name = "id")]
+    license: Option<String>,
+
+    /// The branch of the repository.
+    #[arg(long, default_value = "main")]
+    revision: String,
+
+    /// Create a private repository, if the repository doesn't exist.
+    #[arg(long)]
+    private: bool,
+
+    /// Publish only the documents of the given remote. This option
+    /// can be specified multiple times. By default, the documents of
+    /// all remotes are published.
+    #[arg(short, long = "remote", value_name = "name")]
+    remotes: Vec<String>,
+
+    /// The name of the user (HTTP basic authentication) of the
+    /// remotes.
+    #[arg(short, long, requires = "secret")]
+    username: Option<String>,
+
+    /// The secret of the user.
+    #[arg(long, env = "DATASET_SECRET", hide_env_values = true)]
+    secret: Option<String>,
+
+    /// The access token of the Hub.
+    #[arg(long, env = "HF_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    /// The endpoint of the Hub.
+    #[arg(
+        long,
+        env = "HF_ENDPOINT",
+        default_value = Hub::DEFAULT_ENDPOINT,
+        value_name = "url"
+    )]
+    endpoint: Url,
+
+    /// Create the Parquet shards and the dataset card, but don't
+    /// upload them.
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+}
+
+const PBAR_DOCS: &str =
+    "Fetching documents: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+const PBAR_UPLOAD: &str = "Uploading: {bytes}/{total_bytes} \
+        ({percent}%) | elapsed: {elapsed_precise}{msg}";
+
+/// The summary statistics of the dataset card.
+#[derive(Debug, Default)]
+struct Stats {
+    docs: usize,
+    labeled: usize,
+    labels: BTreeSet<String>,
+    bytes: u64,
+    remotes: BTreeMap<String, usize>,
+}
+
+/// Returns the dataset card (`README.md`).
+fn dataset_card(
+    config: &Config,
+    split: &str,
+    license: Option<&str>,
+    columns: &[String],
+    stats: &Stats,
+) -> String {
+    let metadata = &config.metadata;
+    let mut out = String::from("---\n");
+    let _ = writeln!(out, "pretty_name: {}", metadata.name);
+    if let Some(license) = license {
+        let _ = writeln!(out, "license: {license}");
+    }
+    let _ = writeln!(
+        out,
+        "configs:\n- config_name: default\n  data_files:\n  \
+        - split: {split}\n    path: data/{split}-*"
+    );
+    out.push_str("---\n\n");
+
+    let _ = writeln!(out, "# {}\n", metadata.name);
+    if let Some(ref description) = metadata.description {
+        let _ = writeln!(out, "{description}\n");
+    }
+
+    let _ = writeln!(out, "Version: {}\n", metadata.version);
+
+    out.push_str("## Dataset Structure\n\n");
+    for column in columns.iter() {
+        let _ = writeln!(out, "- `{column}`");
+    }
+
+    out.push_str("\n## Statistics\n\n| | |\n|---|---:|\n");
+    let _ = writeln!(out, "| Documents | {} |", stats.docs);
+    let _ = writeln!(out, "| Labeled documents | {} |", stats.labeled);
+    let _ =
+        writeln!(out, "| Distinct labels | {} |", stats.labels.len());
+    let _ = writeln!(out, "| Text size (bytes) | {} |", stats.bytes);
+    for (remote, docs) in stats.remotes.iter() {
+        let _ = writeln!(out, "| Documents ({remote}) | {docs} |");
+    }
+
+    if !metadata.authors.is_empty() {
+        out.push_str("\n## Authors\n\n");
+        for author in metadata.authors.iter() {
+            let _ = writeln!(out, "- {author}");
+        }
+    }
+
+    out
+}
+
+impl Publish {
+    pub(crate) async fn execute(self) -> DatasetResult<()> {
+        let dataset = Dataset::discover()?;
+        let config = dataset.config()?;
+
+        if !is_repo_id(&self.hf_repo) {
+            bail!("invalid repository id '{}'", self.hf_repo);
+        }
+
+        if self.shard_size == 0 {
+            bail!("shard size must be greater than zero");
+        }
+
+        let token = match self.token {
+            Some(ref token) => token.clone(),
+            None if self.dry_run => String::new(),
+            None => bail!("missing access token (--token or HF_TOKEN)"),
+        };
+
+        for name in self.remotes.iter() {
+            if !config.remotes.contains_key(name) {
+                bail!("unknown remote '{name}'");
+            }
+        }
+
+        let mut df = dataset.remotes()?;
+        if !self.remotes.is_empty() {
+            df = df
+                .lazy()
+                .filter(col("remote").is_in(lit(Series::from_iter(
+                    self.remotes.iter().map(String::as_str),
+                ))))
+                .collect()?;
+        }
+
+        let mut gold: HashMap<String, Vec<String>> = HashMap::new();
+        if let Some(ref path) = self.labels {
+            let filter = dataset.ppn_filter(&config, &self.remotes)?;
+            let mut discarded = 0;
+
+            let labels_df = read_table(path)?;
+            for (ppn, uri) in strings(&labels_df, "ppn")?
+                .into_iter()
+                .zip(strings(&labels_df, "label_uri")?)
+            {
+                if let (Some(ppn), Some(uri)) = (ppn, uri) {
+                    if filter
+                        .as_ref()
+                        .is_some_and(|filter| !filter.contains(&ppn))
+                    {
+                        discarded += 1;
+                        continue;
+                    }
+
+                    gold.entry(ppn).or_default().push(uri);
+                }
+            }
+
+            if self.verbose && discarded > 0 {
+                eprintln!(
+                    "discarded {discarded} label(s) of documents not \
+                    present in any remote"
+                );
+            }
+        }
+
+        let staging = dataset.tmp_dir().join("publish");
+        if staging.is_dir() {
+            fs::remove_dir_all(&staging)?;
+        }
+        fs::create_dir_all(staging.join("data"))?;
+
+        let names = strings(&df, "remote")?;
+        let paths = strings(&df, "path")?;
+        let idns = strings(&df, "idn")?;
+        let total = df.height().div_ceil(self.shard_size).max(1);
+
+        let mut stats = Stats::default();
+        let mut clients = HashMap::new();
+        let mut files = vec![];
+        let mut columns = vec![];
+
+        let pbar = ProgressBarBuilder::new(PBAR_DOCS, self.quiet)
+            .len(df.height() as u64)
+            .build();
+
+        for idx in 0..total {
+            let offset = idx * self.shard_size;
+            let mut shard = df.slice(offset as i64, self.shard_size);
+            let mut texts = Vec::with_capacity(shard.height());
+            let mut labels = Vec::with_capacity(shard.height());
+
+            for row in offset..offset + shard.height() {
+                let (Some(name), Some(path)) =
+                    (&names[row], &paths[row])
+                else {
+                    texts.push(None);
+                    labels.push(Series::new_empty(
+                        "".into(),
+                        &DataType::String,
+                    ));
+                    pbar.inc(1);
+                    continue;
+                };
+
+                let remote = &config.remotes[name];
+                if !clients.contains_key(name) {
+                    clients
+                        .insert(name.clone(), remote.client(&config)?);
+                }
+
+                let text = remote
+                    .fetch_document(
+                        name,
+                        &clients[name],
+                        path,
+                        self.username.as_ref(),
+                        self.secret.as_ref(),
+                    )
+                    .await?;
+
+                let uris = idns[row]
+                    .as_ref()
+                    .and_then(|idn| gold.get(idn))
+                    .cloned()
+                    .unwrap_or_default();
+
+                stats.docs += 1;
+                stats.bytes += text.len() as u64;
+                *stats.remotes.entry(name.clone()).or_default() += 1;
+                if !uris.is_empty() {
+                    stats.labeled += 1;
+                    stats.labels.extend(uris.iter().cloned());
+                }
+
+                texts.push(Some(text));
+                labels.push(Series::new("".into(), uris));
+                pbar.inc(1);
+            }
+
+            let labels: ListChunked =
+                labels.into_iter().map(Some).collect();
+            shard.with_column(Column::new("text".into(), texts))?;
+            shard.with_column(
+                labels.into_series().with_name("labels".into()),
+            )?;
+
+            if columns.is_empty() {
+                columns = shard
+                    .get_column_names()
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect();
+            }
+
+            let path = shard_path(&self.split, idx, total);
+            let local = staging.join(&path);
+            ParquetWriter::new(File::create(&local)?)
+                .with_compression(ParquetCompression::Zstd(None))
+                .finish(&mut shard)?;
+            files.push((path, local));
+        }
+
+        pbar.finish_using_style();
+
+        let card = dataset_card(
+            &config,
+            &self.split,
+            self.license.as_deref(),
+            &columns,
+            &stats,
+        );
+        let local = staging.join("README.md");
+        fs::write(&local, card)?;
+        files.push(("README.md".into(), local));
+
+        let files: Vec<HubFile> = files
+            .into_iter()
+            .map(|(path, local)| HubFile::new(path, local))
+            .collect::<DatasetResult<_>>()?;
+
+        if self.dry_run {
+            for file in files.iter() {
+                eprintln!(
+                    "would upload {} ({} bytes)",
+                    file.path, file.size
+                );
+            }
+
+            eprintln!("staged files in {}", staging.display());
+            return Ok(());
+        }
+
+        let hub = Hub::new(
+            HttpClient::from_config(&config.http)?,
+            self.endpoint.clone(),
+            token,
+        );
+
+        hub.create_repo(&self.hf_repo, self.private).await?;
+
+        let pbar = ProgressBarBuilder::new(PBAR_UPLOAD, self.quiet)
+            .len(files.iter().map(|file| file.size).sum())
+            .build();
+
+        let summary = format!(
+            "Publish {} {}",
+            config.metadata.name, config.metadata.version
+        );
+
+        hub.upload(
+            &self.hf_repo,
+            &self.revision,
+            &files,
+            &summary,
+            |n| pbar.inc(n),
+        )
+        .await?;
+
+        pbar.finish_using_style();
+        fs::remove_dir_all(&staging)?;
+
+        if self.verbose {
+            eprintln!(
+                "published {} document(s) in {} shard(s) to {}",
+                stats.docs, total, self.hf_repo
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publish_dataset_card() {
+        let mut config = Config::default();
+        config.metadata.name = "toc-de".into();
+        config.metadata.description =
+            Some("Tables of contents.".into());
+
+        let stats = Stats {
+            docs: 3,
+            labeled: 2,
+            labels: BTreeSet::from(["a".into(), "b".into()]),
+            bytes: 42,
+            remotes: BTreeMap::from([("foo".into(), 3)]),
+        };
+
+        let card = dataset_card(
+            &config,
+            "train",
+            Some("cc0-1.0"),
+            &["idn".into(), "text".into()],
+            &stats,
+        );
+
+        assert!(card.starts_with("---\npretty_name: toc-de\n"));
+        assert!(card.contains("license: cc0-1.0\n"));
+        assert!(card.contains("    path: data/train-*\n---\n"));
+        assert!(card.contains("| Distinct labels | 2 |"));
+        assert!(card.contains("| Documents (foo) | 3 |"));
+    }
+}
@@ -18,6 +18,15 @@ pub(crate) enum Command {
         #[arg(long = "where", short = 'W')]
         query: Option<String>,
 
+        /// The group the remote belongs to.
+        #[arg(long, short)]
+        group: Option<String>,
+
+        /// The pinned public key of the remote, which is used to
+        /// verify the signature of the remote index.
+        #[arg(long, value_name = "key")]
+        public_key: Option<String>,
+
         /// The name of the remote.
         name: String,
 
@@ -49,6 +58,26 @@ pub(crate) enum Command {
         /// The where clause to filter documents.
         predicate: String,
     },
+
+    /// Changes the group of the remote `name`. If no group is given,
+    /// the remote is removed from its group.
+    SetGroup {
+        /// The name of the remote.
+        name: String,
+
+        /// The name of the group.
+        group: Option<String>,
+    },
+
+    /// Changes the pinned public key of the remote `name`. If no key
+    /// is given, the signature of the remote index isn't verified.
+    SetPublicKey {
+        /// The name of the remote.
+        name: String,
+
+        /// The (base64 encoded) ed25519 public key.
+        public_key: Option<String>,
+    },
 }
 
 impl Remote {
@@ -59,12 +88,25 @@ impl Remote {
         let mut config = dataset.config()?;
 
         match self.cmd {
-            Command::Add { query, name, url } => {
+            Command::Add {
+                query,
+                group,
+                public_key,
+                name,
+                url,
+            } => {
                 if config.remotes.contains_key(&name) {
                     bail!("remote '{name}' already exist.")
                 }
 
-                let remote = Remote::new(url, query)?;
+                let mut remote = Remote::new(url, query)?;
+                if let Some(group) = group {
+                    config.groups.entry(group.clone()).or_default();
+                    remote.group = Some(group);
+                }
+
+                remote.public_key = public_key;
+
                 config.remotes.insert(name, remote);
             }
             Command::Remove { name } => {
@@ -88,6 +130,24 @@ impl Remote {
                     bail!("remote '{name}' does not exist.")
                 }
             }
+            Command::SetGroup { name, group } => {
+                if let Some(remote) = config.remotes.get_mut(&name) {
+                    if let Some(ref group) = group {
+                        config.groups.entry(group.clone()).or_default();
+                    }
+
+                    remote.group = group;
+                } else {
+                    bail!("remote '{name}' does not exist.")
+                }
+            }
+            Command::SetPublicKey { name, public_key } => {
+                if let Some(remote) = config.remotes.get_mut(&name) {
+                    remote.public_key = public_key;
+                } else {
+                    bail!("remote '{name}' does not exist.")
+                }
+            }
         }
 
         config.save()?;
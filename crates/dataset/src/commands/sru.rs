@@ -0,0 +1,345 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use clap::Parser;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use url::Url;
+
+use crate::atomic::AtomicFile;
+use crate::http::HttpClient;
+use crate::prelude::*;
+
+/// The maximum number of records per SRU request.
+const PAGE_SIZE: usize = 100;
+
+const PBAR_QUERY: &str =
+    "Querying records: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// Query the SRU interface of the DNB for a set of records.
+///
+/// Each query result is converted into normalized PICA+ and cached
+/// in the dot directory of the dataset, so that repeated lookups of
+/// the same record don't hit the SRU interface again. The resulting
+/// dump can be used as input of `dataset vocab update` or `datashed
+/// index`.
+#[derive(Debug, Parser)]
+pub(crate) struct Sru {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Interpret the given identifiers as ISBNs instead of PPNs.
+    #[arg(long)]
+    isbn: bool,
+
+    /// Ignore cached query results and query the SRU interface again.
+    #[arg(long)]
+    refresh: bool,
+
+    /// The base URL of the SRU interface.
+    #[arg(
+        long,
+        default_value = "https://services.dnb.de/sru/dnb",
+        value_name = "url"
+    )]
+    url: Url,
+
+    /// The access token required to retrieve PICA+ records.
+    #[arg(long, env = "DATASET_SRU_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    /// Read additional identifiers (one per line) from `filename`.
+    #[arg(short = 'f', long = "file", value_name = "filename")]
+    file: Option<PathBuf>,
+
+    /// Write the records into `filename` instead of `stdout`.
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    /// A list of PPNs (or ISBNs, if `--isbn` is set).
+    ids: Vec<String>,
+}
+
+/// Converts a SRU response (PicaPlus-xml) into normalized PICA+ and
+/// returns the records and the total number of matching records
+/// (`numberOfRecords`). A response with diagnostics (e.g. an invalid
+/// query or access token) is an error.
+fn xml_to_pica(xml: &str) -> DatasetResult<(Vec<u8>, usize)> {
+    let mut reader = Reader::from_str(xml);
+    let mut out: Vec<u8> = vec![];
+    let mut record: Vec<u8> = vec![];
+    let mut in_subfield = false;
+    let mut in_total = false;
+    let mut in_message = false;
+    let mut diagnostics = false;
+    let mut messages: Vec<String> = vec![];
+    let mut total = 0;
+
+    loop {
+        match reader.read_event().map_err(DatasetError::other)? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"datafield" => {
+                    let Some(tag) = e
+                        .try_get_attribute("tag")
+                        .map_err(DatasetError::other)?
+                    else {
+                        bail!("invalid SRU response: missing tag");
+                    };
+
+                    let tag = tag
+                        .unescape_value()
+                        .map_err(DatasetError::other)?;
+                    record.extend_from_slice(tag.as_bytes());
+
+                    if let Some(occ) = e
+                        .try_get_attribute("occurrence")
+                        .map_err(DatasetError::other)?
+                    {
+                        let occ = occ
+                            .unescape_value()
+                            .map_err(DatasetError::other)?;
+                        if !occ.is_empty() && occ != "00" {
+                            record.push(b'/');
+                            record.extend_from_slice(occ.as_bytes());
+                        }
+                    }
+
+                    record.push(b' ');
+                }
+                b"subfield" => {
+                    let Some(code) = e
+                        .try_get_attribute("code")
+                        .map_err(DatasetError::other)?
+                    else {
+                        bail!("invalid SRU response: missing code");
+                    };
+
+                    let code = code
+                        .unescape_value()
+                        .map_err(DatasetError::other)?;
+                    record.push(b'\x1f');
+                    record.extend_from_slice(code.as_bytes());
+                    in_subfield = true;
+                }
+                b"numberOfRecords" => in_total = true,
+                b"diagnostics" => diagnostics = true,
+                b"message" if diagnostics => in_message = true,
+                _ => (),
+            },
+            Event::Text(text) if in_subfield => {
+                let value =
+                    text.unescape().map_err(DatasetError::other)?;
+                record.extend_from_slice(value.as_bytes());
+            }
+            Event::Text(text) if in_total => {
+                let value =
+                    text.unescape().map_err(DatasetError::other)?;
+                total = value.trim().parse().map_err(|_| {
+                    DatasetError::other(format!(
+                        "invalid SRU response: numberOfRecords \
+                        '{value}'"
+                    ))
+                })?;
+            }
+            Event::Text(text) if in_message => {
+                let value =
+                    text.unescape().map_err(DatasetError::other)?;
+                messages.push(value.trim().into());
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"numberOfRecords" => in_total = false,
+                b"message" => in_message = false,
+                b"subfield" => in_subfield = false,
+                b"datafield" => record.push(b'\x1e'),
+                b"record" if !record.is_empty() => {
+                    out.append(&mut record);
+                    out.push(b'\n');
+                }
+                _ => (),
+            },
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+
+    if diagnostics {
+        bail!("SRU diagnostics: {}", messages.join("; "));
+    }
+
+    Ok((out, total))
+}
+
+impl Sru {
+    /// Requests a single page of the query result, starting at the
+    /// (1-based) position `start`.
+    async fn search(
+        &self,
+        client: &HttpClient,
+        query: &str,
+        start: usize,
+    ) -> DatasetResult<(Vec<u8>, usize)> {
+        let start = start.to_string();
+        let page_size = PAGE_SIZE.to_string();
+        let mut params = vec![
+            ("version", "1.1"),
+            ("operation", "searchRetrieve"),
+            ("query", query),
+            ("recordSchema", "PicaPlus-xml"),
+            ("startRecord", start.as_str()),
+            ("maximumRecords", page_size.as_str()),
+        ];
+
+        if let Some(ref token) = self.token {
+            params.push(("accessToken", token.as_str()));
+        }
+
+        let url = Url::parse_with_params(self.url.as_str(), params)
+            .map_err(DatasetError::other)?;
+
+        let response = client.get(url).await?;
+        if !response.status().is_success() {
+            bail!(
+                "SRU request failed ({query}): {}",
+                response.status()
+            );
+        }
+
+        xml_to_pica(&response.text().await?)
+    }
+
+    pub(crate) async fn execute(self) -> DatasetResult<()> {
+        let dataset = Dataset::discover()?;
+        let client = HttpClient::from_config(&dataset.config()?.http)?;
+        let sru_dir = dataset.sru_dir();
+        if !sru_dir.exists() {
+            fs::create_dir_all(&sru_dir)?;
+        }
+
+        let mut ids = self.ids.clone();
+        if let Some(ref path) = self.file {
+            ids.extend(
+                fs::read_to_string(path)?
+                    .lines()
+                    .map(str::trim)
+                    .filter(|id| !id.is_empty())
+                    .map(String::from),
+            );
+        }
+
+        let index = if self.isbn { "num" } else { "idn" };
+        let pbar = ProgressBarBuilder::new(PBAR_QUERY, self.quiet)
+            .len(ids.len() as u64)
+            .build();
+
+        let mut out: Box<dyn Write> = match self.output {
+            Some(ref path) => Box::new(File::create(path)?),
+            None => Box::new(io::stdout().lock()),
+        };
+
+        for id in ids.iter() {
+            let id = id.replace('-', "");
+            let key = format!("{index}-{id}")
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+            let cache = sru_dir.join(format!("{key}.dat"));
+
+            if self.refresh || !cache.is_file() {
+                let query = format!("{index}={id}");
+                let mut records = vec![];
+                let mut start = 1;
+
+                // Only complete results are cached; a failing request
+                // or a response with diagnostics aborts the command.
+                loop {
+                    let (page, total) =
+                        self.search(&client, &query, start).await?;
+                    records.extend(page);
+
+                    start += PAGE_SIZE;
+                    if start > total {
+                        break;
+                    }
+                }
+
+                if records.is_empty() && self.verbose {
+                    pbar.println(format!("no records found ({query})"));
+                }
+
+                // The cache file is written atomically, so that an
+                // interrupted run doesn't leave a truncated entry.
+                let mut file = AtomicFile::create(&cache)?;
+                file.write_all(&records)?;
+                file.commit()?;
+            }
+
+            out.write_all(&fs::read(&cache)?)?;
+            pbar.inc(1);
+        }
+
+        pbar.finish_using_style();
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn xml_to_pica_ok() -> TestResult {
+        let xml = r#"<records><record><recordData>
+            <record xmlns="info:srw/schema/5/picaXML-v1.0">
+            <datafield tag="003@"><subfield code="0">123</subfield>
+            </datafield>
+            <datafield tag="041A" occurrence="01">
+            <subfield code="9">456</subfield>
+            <subfield code="a">Foo &amp; Bar</subfield></datafield>
+            </record></recordData></record></records>"#;
+
+        let (records, _) = xml_to_pica(xml)?;
+        assert_eq!(
+            records,
+            b"003@ \x1f0123\x1e041A/01 \x1f9456\x1faFoo & Bar\x1e\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn xml_to_pica_total() -> TestResult {
+        let xml = r#"<searchRetrieveResponse>
+            <version>1.1</version>
+            <numberOfRecords>250</numberOfRecords>
+            <records></records></searchRetrieveResponse>"#;
+
+        let (records, total) = xml_to_pica(xml)?;
+        assert!(records.is_empty());
+        assert_eq!(total, 250);
+
+        Ok(())
+    }
+
+    #[test]
+    fn xml_to_pica_diagnostics() {
+        let xml = r#"<searchRetrieveResponse>
+            <numberOfRecords>0</numberOfRecords>
+            <diagnostics><diagnostic>
+            <uri>info:srw/diagnostic/1/3</uri>
+            <message>Unsupported operation</message>
+            </diagnostic></diagnostics></searchRetrieveResponse>"#;
+
+        let err = xml_to_pica(xml).unwrap_err();
+        assert!(err.to_string().contains("Unsupported operation"));
+    }
+}
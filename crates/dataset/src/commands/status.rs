@@ -0,0 +1,162 @@
+use std::collections::HashSet;
+
+use clap::Parser;
+use polars::prelude::*;
+
+use crate::lockfile::Lockfile;
+use crate::prelude::*;
+
+/// Show which remotes changed since the last fetch.
+///
+/// The state of each remote, recorded by `dataset fetch` in the lock
+/// file, is compared against the live index of the remote. If the
+/// server provides an entity tag, unchanged remotes are detected by a
+/// HEAD request; otherwise the index is downloaded and its digest is
+/// compared. For changed remotes, the number of added and removed
+/// documents (compared to the local compound index) is reported.
+#[derive(Debug, Parser)]
+pub(crate) struct Status {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+/// Returns the paths of all documents of the given remote.
+fn paths(df: &DataFrame, name: &str) -> DatasetResult<HashSet<String>> {
+    if df.column("remote").is_err() {
+        return Ok(HashSet::new());
+    }
+
+    let df = df
+        .clone()
+        .lazy()
+        .filter(col("remote").eq(lit(name)))
+        .select([col("path")])
+        .collect()?;
+
+    Ok(df
+        .column("path")?
+        .str()?
+        .into_iter()
+        .flatten()
+        .map(ToString::to_string)
+        .collect())
+}
+
+impl Status {
+    pub(crate) async fn execute(self) -> DatasetResult<()> {
+        let dataset = Dataset::discover()?;
+        let config = dataset.config()?;
+        let lockfile =
+            Lockfile::from_path(dataset.dot_dir().join(Dataset::LOCK))?;
+
+        let cached =
+            if dataset.dot_dir().join(Dataset::REMOTES).is_file() {
+                dataset.remotes()?
+            } else {
+                DataFrame::empty()
+            };
+
+        let mut names: Vec<&String> = config.remotes.keys().collect();
+        names.sort();
+
+        let mut stale = false;
+        for name in names {
+            let remote = &config.remotes[name];
+            let Some(locked) = lockfile.remotes.get(name) else {
+                println!("{name}: not fetched yet");
+                stale = true;
+                continue;
+            };
+
+            if locked.url != remote.url {
+                println!(
+                    "{name}: url changed ({} -> {})",
+                    locked.url, remote.url
+                );
+                stale = true;
+                continue;
+            }
+
+            if locked.etag.is_some()
+                && remote.index_etag(&config).await? == locked.etag
+            {
+                if self.verbose {
+                    println!("{name}: up to date (etag)");
+                }
+
+                continue;
+            }
+
+            let index = remote.fetch_index(name, &config).await?;
+            if index.hash == locked.hash {
+                if self.verbose {
+                    println!("{name}: up to date");
+                }
+
+                continue;
+            }
+
+            let old = paths(&cached, name)?;
+            let new = paths(&index.df, name)?;
+            let added = new.difference(&old).count();
+            let removed = old.difference(&new).count();
+
+            println!(
+                "{name}: changed (+{added}, -{removed} documents)"
+            );
+            stale = true;
+        }
+
+        for name in lockfile.remotes.keys() {
+            if !config.remotes.contains_key(name) {
+                println!("{name}: removed from config");
+                stale = true;
+            }
+        }
+
+        if stale {
+            if !self.quiet {
+                eprintln!(
+                    "The lock file is stale. Update the compound index \
+                    with `dataset fetch`."
+                );
+            }
+        } else if !self.quiet {
+            eprintln!("All remotes are up to date.");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn status_paths() -> TestResult {
+        let df = df!(
+            "remote" => ["foo", "bar", "foo"],
+            "path" => ["a.txt", "b.txt", "c.txt"],
+        )?;
+
+        let mut foo: Vec<String> =
+            paths(&df, "foo")?.into_iter().collect();
+        foo.sort();
+        assert_eq!(foo, ["a.txt", "c.txt"]);
+        assert!(paths(&df, "baz")?.is_empty());
+        assert!(paths(&DataFrame::empty(), "foo")?.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,401 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+
+use clap::Parser;
+use csv::WriterBuilder;
+use pica_record::prelude::*;
+use polars::prelude::*;
+
+use crate::prelude::*;
+use crate::table::{read_table, strings};
+use crate::vocab::read_vocab;
+use crate::weak_label::{
+    aggregate, Aggregation, LabelingFunction, RegexFunction,
+    VocabIndex, Votes,
+};
+
+/// Generate silver labels by weak supervision.
+///
+/// The labeling functions of the config (`[weak-label.function.*]`)
+/// emit candidate labels with a confidence for each document of the
+/// dataset: regex functions match the text of the document, pica
+/// functions match the PICA+ record of the document and vocab
+/// functions link the labels of the vocabulary in the text. The
+/// candidates are aggregated into silver labels (`ppn`, `label_uri`,
+/// `score`, `source` and `functions`). Documents with gold labels keep
+/// their gold labels, which are written alongside the silver labels.
+#[derive(Debug, Parser)]
+pub(crate) struct WeakLabel {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The gold labels (CSV or IPC) with the columns `ppn` and
+    /// `label_uri`. The gold labels are used to estimate the precision
+    /// of the labeling functions (`--aggregation model`).
+    #[arg(short, long, value_name = "filename")]
+    gold: Option<PathBuf>,
+
+    /// The PICA+ dump, which is required by pica labeling functions.
+    #[arg(long, value_name = "filename")]
+    pica: Option<PathBuf>,
+
+    /// The method to aggregate the candidate labels. This option
+    /// overrides the `weak-label.aggregation` config.
+    #[arg(long)]
+    aggregation: Option<Aggregation>,
+
+    /// The minimum score of a silver label. This option overrides the
+    /// `weak-label.threshold` config (default: 0.5).
+    #[arg(long, value_name = "score")]
+    threshold: Option<f64>,
+
+    /// Label only the documents of the given remote. This option can
+    /// be specified multiple times. By default, the documents of all
+    /// remotes are labeled.
+    #[arg(short, long = "remote", value_name = "name")]
+    remotes: Vec<String>,
+
+    /// The name of the user (HTTP basic authentication).
+    #[arg(short, long, requires = "secret")]
+    username: Option<String>,
+
+    /// The secret of the user.
+    #[arg(long, env = "DATASET_SECRET", hide_env_values = true)]
+    secret: Option<String>,
+
+    /// Write the labels into `filename`. By default, the labels are
+    /// written in CSV format to the standard output.
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+}
+
+const PBAR_RECORDS: &str = "Processing records: {human_pos} | \
+        elapsed: {elapsed_precise}{msg}";
+
+const PBAR_DOCS: &str =
+    "Labeling documents: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// The default minimum score of a silver label.
+const DEFAULT_THRESHOLD: f64 = 0.5;
+
+impl WeakLabel {
+    pub(crate) async fn execute(self) -> DatasetResult<()> {
+        let dataset = Dataset::discover()?;
+        let config = dataset.config()?;
+        let weak = &config.weak_label;
+
+        if weak.functions.is_empty() {
+            bail!(
+                "no labeling functions (see `[weak-label.function.*]`)"
+            );
+        }
+
+        for name in self.remotes.iter() {
+            if !config.remotes.contains_key(name) {
+                bail!("unknown remote '{name}'");
+            }
+        }
+
+        let aggregation = self.aggregation.unwrap_or(weak.aggregation);
+        let threshold = self
+            .threshold
+            .or(weak.threshold)
+            .unwrap_or(DEFAULT_THRESHOLD);
+
+        let mut df = dataset.remotes()?;
+        if !self.remotes.is_empty() {
+            df = df
+                .lazy()
+                .filter(col("remote").is_in(lit(Series::from_iter(
+                    self.remotes.iter().map(String::as_str),
+                ))))
+                .collect()?;
+        }
+
+        let names = strings(&df, "remote")?;
+        let paths = strings(&df, "path")?;
+        let idns = strings(&df, "idn")?;
+        let docs: Vec<(String, String, String)> = names
+            .into_iter()
+            .zip(paths)
+            .zip(idns)
+            .filter_map(|((name, path), idn)| {
+                Some((name?, path?, idn?))
+            })
+            .collect();
+
+        let mut votes: BTreeMap<String, Votes> = docs
+            .iter()
+            .map(|(_, _, idn)| (idn.clone(), Votes::new()))
+            .collect();
+
+        let mut gold: BTreeMap<String, BTreeSet<String>> =
+            BTreeMap::new();
+        if let Some(ref path) = self.gold {
+            let gold_df = read_table(path)?;
+            for (ppn, uri) in strings(&gold_df, "ppn")?
+                .into_iter()
+                .zip(strings(&gold_df, "label_uri")?)
+            {
+                if let (Some(ppn), Some(uri)) = (ppn, uri) {
+                    gold.entry(ppn).or_default().insert(uri);
+                }
+            }
+        }
+
+        // pica functions
+        let matchers: Vec<(&String, RecordMatcher, &Vec<String>, f64)> =
+            weak.functions
+                .iter()
+                .filter_map(|(name, function)| match function {
+                    LabelingFunction::Pica {
+                        filter,
+                        labels,
+                        confidence,
+                    } => Some(
+                        RecordMatcher::new(filter)
+                            .map(|m| (name, m, labels, *confidence))
+                            .map_err(DatasetError::from),
+                    ),
+                    _ => None,
+                })
+                .collect::<DatasetResult<_>>()?;
+
+        if !matchers.is_empty() {
+            let Some(ref path) = self.pica else {
+                bail!("pica labeling functions require a PICA+ dump (--pica)");
+            };
+
+            let mut reader = ReaderBuilder::new().from_path(path)?;
+            let options = MatcherOptions::default();
+            let pbar =
+                ProgressBarBuilder::new(PBAR_RECORDS, self.quiet)
+                    .build();
+
+            while let Some(result) = reader.next_byte_record() {
+                pbar.inc(1);
+
+                let Ok(record) = result else {
+                    continue;
+                };
+
+                let Some(doc) =
+                    votes.get_mut(&record.ppn().to_string())
+                else {
+                    continue;
+                };
+
+                for (name, matcher, labels, confidence) in
+                    matchers.iter()
+                {
+                    if matcher.is_match(&record, &options) {
+                        for label in labels.iter() {
+                            doc.entry(label.clone())
+                                .or_default()
+                                .insert(name.to_string(), *confidence);
+                        }
+                    }
+                }
+            }
+
+            pbar.finish_using_style();
+        }
+
+        // text functions (regex and vocab)
+        let regexes = RegexFunction::compile(weak)?;
+        let linkers: Vec<(&String, usize, f64)> = weak
+            .functions
+            .iter()
+            .filter_map(|(name, function)| match function {
+                LabelingFunction::Vocab {
+                    min_length,
+                    confidence,
+                } => Some((name, *min_length, *confidence)),
+                _ => None,
+            })
+            .collect();
+
+        if !regexes.is_empty() || !linkers.is_empty() {
+            let subjects = if linkers.is_empty() {
+                vec![]
+            } else {
+                read_vocab(dataset.base_dir().join(Dataset::VOCAB))?
+            };
+
+            let indices: Vec<(&String, VocabIndex, f64)> = linkers
+                .into_iter()
+                .map(|(name, min_length, confidence)| {
+                    let labels = subjects
+                        .iter()
+                        .map(|s| (s.uri.as_str(), s.label.as_str()));
+                    (
+                        name,
+                        VocabIndex::new(labels, min_length),
+                        confidence,
+                    )
+                })
+                .collect();
+
+            let mut clients = HashMap::new();
+            let pbar = ProgressBarBuilder::new(PBAR_DOCS, self.quiet)
+                .len(docs.len() as u64)
+                .build();
+
+            for (name, path, idn) in docs.iter() {
+                let remote = &config.remotes[name];
+                if !clients.contains_key(name) {
+                    clients
+                        .insert(name.clone(), remote.client(&config)?);
+                }
+
+                let text = remote
+                    .fetch_document(
+                        name,
+                        &clients[name],
+                        path,
+                        self.username.as_ref(),
+                        self.secret.as_ref(),
+                    )
+                    .await?;
+
+                let doc = votes.get_mut(idn).unwrap();
+                for function in regexes.iter() {
+                    if function.re.is_match(&text) {
+                        for label in function.labels.iter() {
+                            doc.entry(label.clone())
+                                .or_default()
+                                .insert(
+                                    function.name.to_string(),
+                                    function.confidence,
+                                );
+                        }
+                    }
+                }
+
+                for (name, index, confidence) in indices.iter() {
+                    for uri in index.matches(&text) {
+                        doc.entry(uri.to_string())
+                            .or_default()
+                            .insert(name.to_string(), *confidence);
+                    }
+                }
+
+                pbar.inc(1);
+            }
+
+            pbar.finish_using_style();
+        }
+
+        // Estimate the precision of each function on the documents
+        // with gold labels.
+        let mut hits: HashMap<String, (usize, usize)> = HashMap::new();
+        for (idn, labels) in gold.iter() {
+            let Some(doc) = votes.get(idn) else {
+                continue;
+            };
+
+            for (label, functions) in doc.iter() {
+                for name in functions.keys() {
+                    let entry = hits.entry(name.clone()).or_default();
+                    entry.1 += 1;
+                    if labels.contains(label) {
+                        entry.0 += 1;
+                    }
+                }
+            }
+        }
+
+        let weights: HashMap<String, f64> = hits
+            .iter()
+            .map(|(name, (tp, n))| {
+                (name.clone(), *tp as f64 / *n as f64)
+            })
+            .collect();
+
+        if self.verbose {
+            for name in weak.functions.keys() {
+                let coverage = votes
+                    .values()
+                    .filter(|doc| {
+                        doc.values().any(|f| f.contains_key(name))
+                    })
+                    .count();
+                match weights.get(name) {
+                    Some(precision) => eprintln!(
+                        "{name}: coverage = {coverage}, \
+                        precision = {precision:.3}"
+                    ),
+                    None => eprintln!("{name}: coverage = {coverage}"),
+                }
+            }
+        }
+
+        let inner: Box<dyn Write> = match self.output {
+            Some(ref path) => Box::new(File::create(path)?),
+            None => Box::new(stdout().lock()),
+        };
+
+        let mut writer = WriterBuilder::new().from_writer(inner);
+        writer.write_record([
+            "ppn",
+            "label_uri",
+            "score",
+            "source",
+            "functions",
+        ])?;
+
+        let mut silver = 0;
+        for (idn, doc) in votes.iter() {
+            if let Some(labels) = gold.get(idn) {
+                for label in labels.iter() {
+                    writer.write_record([
+                        idn.as_str(),
+                        label,
+                        "1",
+                        "gold",
+                        "",
+                    ])?;
+                }
+
+                continue;
+            }
+
+            for (label, score) in aggregate(doc, aggregation, &weights)
+            {
+                if score < threshold {
+                    continue;
+                }
+
+                let functions: Vec<&str> =
+                    doc[&label].keys().map(String::as_str).collect();
+                writer.write_record([
+                    idn.as_str(),
+                    &label,
+                    &format!("{score:.4}"),
+                    "silver",
+                    &functions.join("|"),
+                ])?;
+                silver += 1;
+            }
+        }
+
+        writer.flush()?;
+
+        if self.verbose {
+            eprintln!("generated {silver} silver label(s)");
+        }
+
+        Ok(())
+    }
+}
@@ -6,9 +6,13 @@ use std::path::{Path, PathBuf};
 use semver::Version;
 use serde::{Deserialize, Serialize};
 
+use crate::http::HttpConfig;
 use crate::prelude::*;
-use crate::remote::Remote;
+use crate::prune::PruneConfig;
+use crate::remote::{Group, Remote};
+use crate::schema::Schema;
 use crate::vocab::VocabConfig;
+use crate::weak_label::WeakLabelConfig;
 
 /// Dataset config.
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -30,9 +34,37 @@ pub(crate) struct Config {
     )]
     pub(crate) remotes: HashMap<String, Remote>,
 
+    #[serde(
+        rename = "group",
+        skip_serializing_if = "HashMap::is_empty",
+        default
+    )]
+    pub(crate) groups: HashMap<String, Group>,
+
     #[serde(default, skip_serializing_if = "VocabConfig::is_empty")]
     pub(crate) vocab: VocabConfig,
 
+    /// Column rename and cast rules applied to all fetched indices
+    /// (before the rules of the remote).
+    #[serde(default, skip_serializing_if = "Schema::is_empty")]
+    pub(crate) schema: Schema,
+
+    /// HTTP client options.
+    #[serde(default, skip_serializing_if = "HttpConfig::is_empty")]
+    pub(crate) http: HttpConfig,
+
+    /// Labeling functions and the aggregation of silver labels.
+    #[serde(
+        rename = "weak-label",
+        default,
+        skip_serializing_if = "WeakLabelConfig::is_empty"
+    )]
+    pub(crate) weak_label: WeakLabelConfig,
+
+    /// Dataset-level constraints (see `dataset prune`).
+    #[serde(default, skip_serializing_if = "PruneConfig::is_empty")]
+    pub(crate) prune: PruneConfig,
+
     /// This structure should always be constructed using a public
     /// constructor or using the update syntax:
     ///
@@ -1,9 +1,11 @@
 use std::env;
 use std::fs::{self, File};
+use std::io::BufReader;
 use std::path::PathBuf;
 
 use polars::prelude::*;
 
+use crate::bloom::{BloomFilter, PpnFilter};
 use crate::config::Config;
 use crate::prelude::*;
 
@@ -15,11 +17,16 @@ pub(crate) struct Dataset {
 impl Dataset {
     pub(crate) const CONFIG: &'static str = "config.toml";
     pub(crate) const REMOTES: &'static str = "remotes.ipc";
+    pub(crate) const LOCK: &'static str = "remotes.lock";
     pub(crate) const VOCAB: &'static str = "vocab.csv";
+    pub(crate) const IDS: &'static str = "ids.csv";
+    pub(crate) const AUDIT_LOG: &'static str = "audit.jsonl";
 
     pub(crate) const DOT_DIR: &'static str = ".dataset";
     pub(crate) const DATA_DIR: &'static str = "data";
     pub(crate) const TMP_DIR: &'static str = "tmp";
+    pub(crate) const SRU_DIR: &'static str = "sru";
+    pub(crate) const BLOOM_DIR: &'static str = "bloom";
 
     /// Discovers the root of the dataset.
     ///
@@ -75,6 +82,52 @@ impl Dataset {
         self.dot_dir().join(Self::TMP_DIR)
     }
 
+    /// Returns the directory of cached SRU query results.
+    #[inline]
+    pub(crate) fn sru_dir(&self) -> PathBuf {
+        self.dot_dir().join(Self::SRU_DIR)
+    }
+
+    /// Returns the directory of the Bloom filters of the remotes.
+    #[inline]
+    pub(crate) fn bloom_dir(&self) -> PathBuf {
+        self.dot_dir().join(Self::BLOOM_DIR)
+    }
+
+    /// Returns the Bloom filters of the given remotes (all remotes of
+    /// the config, if none is given), which are used to discard labels
+    /// of documents not present in any of the remotes (see `dataset
+    /// fetch`). If a remote has no filter, `None` is returned.
+    pub(crate) fn ppn_filter(
+        &self,
+        config: &Config,
+        remotes: &[String],
+    ) -> DatasetResult<Option<PpnFilter>> {
+        let names: Vec<&String> = if remotes.is_empty() {
+            config.remotes.keys().collect()
+        } else {
+            remotes.iter().collect()
+        };
+
+        let mut filters = vec![];
+        for name in names {
+            let path = self.bloom_dir().join(format!("{name}.bloom"));
+            if !path.is_file() {
+                return Ok(None);
+            }
+
+            filters.push(BloomFilter::read(BufReader::new(
+                File::open(path)?,
+            ))?);
+        }
+
+        if filters.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(PpnFilter(filters)))
+    }
+
     /// Returns the remote index.
     #[inline]
     pub(crate) fn remotes(&self) -> DatasetResult<DataFrame> {
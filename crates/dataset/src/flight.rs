@@ -0,0 +1,68 @@
+#[cfg(feature = "flight")]
+use std::io::Cursor;
+
+use polars::prelude::*;
+use url::Url;
+
+use crate::prelude::*;
+
+/// Fetches the index of a datashed via Arrow Flight (see `datashed
+/// serve --flight-port`).
+///
+/// The predicate is evaluated by the server. The function returns the
+/// received data as IPC stream together with the decoded data frame.
+#[cfg(feature = "flight")]
+pub(crate) async fn fetch(
+    url: &Url,
+    predicate: Option<String>,
+) -> DatasetResult<(Vec<u8>, DataFrame)> {
+    use arrow_flight::{FlightClient, Ticket};
+    use arrow_ipc::writer::StreamWriter;
+    use futures::TryStreamExt;
+    use tonic::transport::Channel;
+
+    let channel = Channel::from_shared(url.to_string())
+        .map_err(DatasetError::other)?
+        .connect()
+        .await
+        .map_err(DatasetError::other)?;
+
+    let ticket = serde_json::json!({
+        "table": "index",
+        "predicate": predicate,
+    });
+
+    let mut client = FlightClient::new(channel);
+    let batches: Vec<_> = client
+        .do_get(Ticket::new(ticket.to_string()))
+        .await
+        .map_err(DatasetError::other)?
+        .try_collect()
+        .await
+        .map_err(DatasetError::other)?;
+
+    let Some(first) = batches.first() else {
+        bail!("empty flight response");
+    };
+
+    let mut buf = vec![];
+    let mut writer = StreamWriter::try_new(&mut buf, &first.schema())
+        .map_err(DatasetError::other)?;
+    for batch in batches.iter() {
+        writer.write(batch).map_err(DatasetError::other)?;
+    }
+
+    writer.finish().map_err(DatasetError::other)?;
+    drop(writer);
+
+    let df = IpcStreamReader::new(Cursor::new(&buf)).finish()?;
+    Ok((buf, df))
+}
+
+#[cfg(not(feature = "flight"))]
+pub(crate) async fn fetch(
+    _url: &Url,
+    _predicate: Option<String>,
+) -> DatasetResult<(Vec<u8>, DataFrame)> {
+    bail!("dataset was built without Arrow Flight support (feature `flight`)")
+}
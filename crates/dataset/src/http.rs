@@ -0,0 +1,431 @@
+use std::fs::read;
+use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use reqwest::header::{HeaderMap, HeaderValue, FROM, RETRY_AFTER};
+use reqwest::{
+    Certificate, Client, Proxy, RequestBuilder, Response, StatusCode,
+    Url,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+
+use crate::prelude::*;
+
+/// HTTP client options.
+///
+/// ```toml
+/// [http]
+/// connect-timeout = 10
+/// timeout = 60
+/// retries = 3
+/// backoff = 500
+/// proxy = "http://proxy.example.com:3128"
+/// ca-bundle = "/etc/ssl/certs/internal-ca.pem"
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct HttpConfig {
+    /// The timeout (in seconds) for establishing a connection.
+    pub(crate) connect_timeout: Option<u64>,
+
+    /// The timeout (in seconds) of a whole request, including the
+    /// transfer of the response body.
+    pub(crate) timeout: Option<u64>,
+
+    /// The number of retries of failed requests (connection errors,
+    /// timeouts and status codes 429 and 5xx).
+    pub(crate) retries: Option<u32>,
+
+    /// The initial delay (in milliseconds) between two retries. The
+    /// delay is doubled on each retry.
+    pub(crate) backoff: Option<u64>,
+
+    /// The proxy used for all requests. If not set, the proxy is taken
+    /// from the environment (`HTTP_PROXY`, `HTTPS_PROXY`).
+    pub(crate) proxy: Option<String>,
+
+    /// A PEM file with additional root certificates.
+    pub(crate) ca_bundle: Option<PathBuf>,
+}
+
+impl HttpConfig {
+    /// Returns `true` if no option is set.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.connect_timeout.is_none()
+            && self.timeout.is_none()
+            && self.retries.is_none()
+            && self.backoff.is_none()
+            && self.proxy.is_none()
+            && self.ca_bundle.is_none()
+    }
+}
+
+/// Politeness settings of a remote, which limit the load a dataset
+/// puts on the servers of partner institutions.
+///
+/// ```toml
+/// [remote.foo.politeness]
+/// max-concurrency = 2
+/// requests-per-second = 5.0
+/// user-agent = "dataset-bot/1.0"
+/// contact = "mailto:dataset@example.com"
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Politeness {
+    /// The maximum number of concurrent requests.
+    pub(crate) max_concurrency: Option<usize>,
+
+    /// The maximum number of requests per second.
+    pub(crate) requests_per_second: Option<f64>,
+
+    /// The value of the `User-Agent` header. By default, the name and
+    /// the version of the tool are sent.
+    pub(crate) user_agent: Option<String>,
+
+    /// A contact address (e.g. `mailto:...`), which is sent in the
+    /// `From` header.
+    pub(crate) contact: Option<String>,
+}
+
+impl Politeness {
+    /// Returns `true` if no option is set.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.max_concurrency.is_none()
+            && self.requests_per_second.is_none()
+            && self.user_agent.is_none()
+            && self.contact.is_none()
+    }
+}
+
+/// Limits the number of concurrent requests and the request rate.
+#[derive(Debug)]
+struct Limiter {
+    permits: Option<Arc<Semaphore>>,
+    interval: Option<Duration>,
+    next: Mutex<Instant>,
+}
+
+impl Limiter {
+    fn new(politeness: &Politeness) -> Option<Self> {
+        let interval = politeness
+            .requests_per_second
+            .filter(|rps| *rps > 0.0)
+            .map(|rps| Duration::from_secs_f64(1.0 / rps));
+        let permits = politeness
+            .max_concurrency
+            .map(|n| Arc::new(Semaphore::new(n.max(1))));
+
+        if interval.is_none() && permits.is_none() {
+            return None;
+        }
+
+        Some(Self {
+            permits,
+            interval,
+            next: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// Waits until the rate limit permits the next request.
+    async fn wait(&self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+
+        let mut next = self.next.lock().await;
+        let now = Instant::now();
+        if *next > now {
+            tokio::time::sleep(*next - now).await;
+        }
+
+        *next = Instant::now().max(*next) + interval;
+    }
+
+    /// Waits for a free slot (see `max-concurrency`) and returns the
+    /// permit, which is released when dropped.
+    async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        match self.permits {
+            Some(ref permits) => {
+                Some(permits.clone().acquire_owned().await.unwrap())
+            }
+            None => None,
+        }
+    }
+}
+
+/// The response of a request. The concurrency permit of the request
+/// (see [Politeness]) is held until the body has been read or the
+/// response is dropped.
+#[derive(Debug)]
+pub(crate) struct HttpResponse {
+    inner: Response,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl HttpResponse {
+    /// Returns the full response body as bytes.
+    pub(crate) async fn bytes(self) -> reqwest::Result<Vec<u8>> {
+        self.inner.bytes().await.map(Vec::from)
+    }
+
+    /// Returns the full response body as text.
+    pub(crate) async fn text(self) -> reqwest::Result<String> {
+        self.inner.text().await
+    }
+
+    /// Deserializes the full response body as JSON.
+    pub(crate) async fn json<T: DeserializeOwned>(
+        self,
+    ) -> reqwest::Result<T> {
+        self.inner.json().await
+    }
+}
+
+impl Deref for HttpResponse {
+    type Target = Response;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl DerefMut for HttpResponse {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+/// A HTTP client, which retries failed requests with an exponential
+/// backoff.
+#[derive(Debug, Clone)]
+pub(crate) struct HttpClient {
+    client: Client,
+    retries: u32,
+    backoff: Duration,
+    limiter: Option<Arc<Limiter>>,
+}
+
+impl HttpClient {
+    const DEFAULT_CONNECT_TIMEOUT: u64 = 10;
+    const DEFAULT_RETRIES: u32 = 3;
+    const DEFAULT_BACKOFF: u64 = 500;
+    const DEFAULT_USER_AGENT: &'static str =
+        concat!("dataset/", env!("CARGO_PKG_VERSION"));
+
+    pub(crate) fn from_config(
+        config: &HttpConfig,
+    ) -> DatasetResult<Self> {
+        Self::with_politeness(config, &Politeness::default())
+    }
+
+    /// Creates a client, which respects the politeness settings of a
+    /// remote.
+    pub(crate) fn with_politeness(
+        config: &HttpConfig,
+        politeness: &Politeness,
+    ) -> DatasetResult<Self> {
+        let mut builder =
+            Client::builder().connect_timeout(Duration::from_secs(
+                config
+                    .connect_timeout
+                    .unwrap_or(Self::DEFAULT_CONNECT_TIMEOUT),
+            ));
+
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(Duration::from_secs(timeout));
+        }
+
+        if let Some(ref proxy) = config.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+
+        if let Some(ref path) = config.ca_bundle {
+            for cert in Certificate::from_pem_bundle(&read(path)?)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        builder = builder.user_agent(
+            politeness
+                .user_agent
+                .as_deref()
+                .unwrap_or(Self::DEFAULT_USER_AGENT),
+        );
+
+        if let Some(ref contact) = politeness.contact {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                FROM,
+                HeaderValue::from_str(contact)
+                    .map_err(DatasetError::other)?,
+            );
+            builder = builder.default_headers(headers);
+        }
+
+        Ok(Self {
+            client: builder.build()?,
+            retries: config.retries.unwrap_or(Self::DEFAULT_RETRIES),
+            backoff: Duration::from_millis(
+                config.backoff.unwrap_or(Self::DEFAULT_BACKOFF),
+            ),
+            limiter: Limiter::new(politeness).map(Arc::new),
+        })
+    }
+
+    /// Returns the delay before the given retry (starting at 0).
+    fn delay(&self, retry: u32) -> Duration {
+        self.backoff * 2u32.saturating_pow(retry)
+    }
+
+    /// Sends the request built by `f`. The request is retried, if it
+    /// fails with a connection error, a timeout or a status code,
+    /// which indicates a temporary failure (429 and 5xx). A
+    /// `Retry-After` header (in seconds) takes precedence over the
+    /// backoff delay.
+    pub(crate) async fn send<F>(
+        &self,
+        f: F,
+    ) -> DatasetResult<HttpResponse>
+    where
+        F: Fn(&Client) -> RequestBuilder,
+    {
+        self.send_with_retries(f, true).await
+    }
+
+    /// Sends the non-idempotent request built by `f` (e.g. a POST
+    /// request). The request is only retried, if the connection
+    /// couldn't be established or the server rejected it (429); after
+    /// a timeout or a server error the request may have been processed
+    /// already.
+    pub(crate) async fn send_once<F>(
+        &self,
+        f: F,
+    ) -> DatasetResult<HttpResponse>
+    where
+        F: Fn(&Client) -> RequestBuilder,
+    {
+        self.send_with_retries(f, false).await
+    }
+
+    async fn send_with_retries<F>(
+        &self,
+        f: F,
+        idempotent: bool,
+    ) -> DatasetResult<HttpResponse>
+    where
+        F: Fn(&Client) -> RequestBuilder,
+    {
+        let mut retry = 0;
+
+        loop {
+            let (permit, result) = match self.limiter {
+                Some(ref limiter) => {
+                    let permit = limiter.acquire().await;
+                    limiter.wait().await;
+                    (permit, f(&self.client).send().await)
+                }
+                None => (None, f(&self.client).send().await),
+            };
+
+            let retryable = match result {
+                Ok(ref response) => {
+                    response.status() == StatusCode::TOO_MANY_REQUESTS
+                        || (idempotent
+                            && response.status().is_server_error())
+                }
+                Err(ref e) => {
+                    e.is_connect() || (idempotent && e.is_timeout())
+                }
+            };
+
+            if !retryable || retry >= self.retries {
+                return result
+                    .map(|inner| HttpResponse {
+                        inner,
+                        _permit: permit,
+                    })
+                    .map_err(DatasetError::from);
+            }
+
+            drop(permit);
+
+            let retry_after = result
+                .as_ref()
+                .ok()
+                .and_then(|response| {
+                    response.headers().get(RETRY_AFTER)
+                })
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok())
+                .map(Duration::from_secs);
+
+            tokio::time::sleep(
+                retry_after.unwrap_or(self.delay(retry)),
+            )
+            .await;
+            retry += 1;
+        }
+    }
+
+    /// Sends a GET request.
+    #[inline]
+    pub(crate) async fn get(
+        &self,
+        url: Url,
+    ) -> DatasetResult<HttpResponse> {
+        self.send(|client| client.get(url.clone())).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limiter_disabled() {
+        assert!(Limiter::new(&Politeness::default()).is_none());
+        assert!(Limiter::new(&Politeness {
+            requests_per_second: Some(0.0),
+            ..Default::default()
+        })
+        .is_none());
+    }
+
+    #[tokio::test]
+    async fn limiter_permits() {
+        let limiter = Limiter::new(&Politeness {
+            max_concurrency: Some(1),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let permits = limiter.permits.clone().unwrap();
+        let permit = limiter.acquire().await;
+        assert!(permit.is_some());
+        assert_eq!(permits.available_permits(), 0);
+
+        drop(permit);
+        assert_eq!(permits.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn limiter_rate() {
+        let limiter = Limiter::new(&Politeness {
+            requests_per_second: Some(50.0),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.wait().await;
+        }
+
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
@@ -0,0 +1,605 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use base64::prelude::*;
+use reqwest::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE, ETAG};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::http::HttpClient;
+use crate::prelude::*;
+
+/// The media type of the Git LFS batch API.
+const LFS_MEDIA_TYPE: &str = "application/vnd.git-lfs+json";
+
+/// A file of a commit to the Hugging Face Hub.
+#[derive(Debug)]
+pub(crate) struct HubFile {
+    /// The path of the file in the repository.
+    pub(crate) path: String,
+
+    /// The local path of the file.
+    pub(crate) local: PathBuf,
+
+    /// The size of the file (in bytes).
+    pub(crate) size: u64,
+
+    /// The hex-encoded SHA256 digest of the file.
+    pub(crate) oid: String,
+}
+
+impl HubFile {
+    pub(crate) fn new<S, P>(path: S, local: P) -> DatasetResult<Self>
+    where
+        S: Into<String>,
+        P: Into<PathBuf>,
+    {
+        let local = local.into();
+        let mut file = File::open(&local)?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; 1 << 16];
+        let mut size = 0;
+
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+
+            hasher.update(&buf[..n]);
+            size += n as u64;
+        }
+
+        let oid = hasher.finalize().iter().fold(
+            String::new(),
+            |mut out, b| {
+                let _ = write!(out, "{b:02x}");
+                out
+            },
+        );
+
+        Ok(Self {
+            path: path.into(),
+            local,
+            size,
+            oid,
+        })
+    }
+
+    /// Returns the first 512 bytes of the file (base64 encoded), which
+    /// are used by the Hub to decide the upload mode.
+    fn sample(&self) -> DatasetResult<String> {
+        let mut buf = vec![];
+        File::open(&self.local)?.take(512).read_to_end(&mut buf)?;
+        Ok(BASE64_STANDARD.encode(buf))
+    }
+
+    fn read_range(
+        &self,
+        offset: u64,
+        len: u64,
+    ) -> DatasetResult<Vec<u8>> {
+        let mut file = File::open(&self.local)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut buf = Vec::with_capacity(len as usize);
+        file.take(len).read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PreuploadFile {
+    path: String,
+    upload_mode: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PreuploadResponse {
+    files: Vec<PreuploadFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LfsAction {
+    href: String,
+    #[serde(default)]
+    header: serde_json::Map<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LfsActions {
+    upload: Option<LfsAction>,
+    verify: Option<LfsAction>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LfsError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LfsObject {
+    oid: String,
+    actions: Option<LfsActions>,
+    error: Option<LfsError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LfsBatchResponse {
+    objects: Vec<LfsObject>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CompletedPart {
+    part_number: usize,
+    etag: String,
+}
+
+/// A client of the Hugging Face Hub API.
+pub(crate) struct Hub {
+    client: HttpClient,
+    endpoint: Url,
+    token: String,
+}
+
+impl Hub {
+    pub(crate) const DEFAULT_ENDPOINT: &'static str =
+        "https://huggingface.co";
+
+    pub(crate) fn new(
+        client: HttpClient,
+        endpoint: Url,
+        token: String,
+    ) -> Self {
+        Self {
+            client,
+            endpoint,
+            token,
+        }
+    }
+
+    fn url(&self, path: &str) -> DatasetResult<Url> {
+        self.endpoint.join(path).map_err(DatasetError::other)
+    }
+
+    fn bearer(&self) -> String {
+        format!("Bearer {}", self.token)
+    }
+
+    /// Creates the dataset repository, unless it already exists.
+    pub(crate) async fn create_repo(
+        &self,
+        repo: &str,
+        private: bool,
+    ) -> DatasetResult<()> {
+        let (organization, name) = match repo.split_once('/') {
+            Some((org, name)) => (Some(org), name),
+            None => (None, repo),
+        };
+
+        let url = self.url("/api/repos/create")?;
+        let body = json!({
+            "type": "dataset",
+            "name": name,
+            "organization": organization,
+            "private": private,
+        });
+
+        let response = self
+            .client
+            .send(|client| {
+                client
+                    .post(url.clone())
+                    .header(AUTHORIZATION, self.bearer())
+                    .json(&body)
+            })
+            .await?;
+
+        // The Hub responds with 409 (Conflict), if the repository
+        // already exists.
+        let status = response.status();
+        if !status.is_success() && status.as_u16() != 409 {
+            let message = response.text().await.unwrap_or_default();
+            bail!("unable to create repository '{repo}' ({status}): {message}");
+        }
+
+        Ok(())
+    }
+
+    /// Uploads the files and commits them to the `revision` of the
+    /// repository.
+    ///
+    /// Large files are uploaded to the LFS storage of the Hub, in
+    /// chunks, if the Hub requests a multipart upload. Objects, which
+    /// are already stored by the Hub, are skipped, so an interrupted
+    /// upload resumes with the missing files.
+    pub(crate) async fn upload<F>(
+        &self,
+        repo: &str,
+        revision: &str,
+        files: &[HubFile],
+        summary: &str,
+        mut progress: F,
+    ) -> DatasetResult<()>
+    where
+        F: FnMut(u64),
+    {
+        let modes = self.preupload(repo, revision, files).await?;
+        let lfs: Vec<&HubFile> = files
+            .iter()
+            .filter(|file| {
+                modes.iter().any(|m| {
+                    m.path == file.path && m.upload_mode == "lfs"
+                })
+            })
+            .collect();
+
+        if !lfs.is_empty() {
+            let objects = self.lfs_batch(repo, &lfs).await?;
+            for object in objects {
+                if let Some(error) = object.error {
+                    bail!(
+                        "unable to upload object {}: {}",
+                        object.oid,
+                        error.message
+                    );
+                }
+
+                let file = lfs
+                    .iter()
+                    .find(|file| file.oid == object.oid)
+                    .ok_or_else(|| {
+                        DatasetError::other("unknown LFS object")
+                    })?;
+
+                match object.actions {
+                    Some(LfsActions {
+                        upload: Some(upload),
+                        verify,
+                    }) => {
+                        self.lfs_upload(file, &upload, &mut progress)
+                            .await?;
+                        if let Some(verify) = verify {
+                            self.lfs_verify(file, &verify).await?;
+                        }
+                    }
+                    // The object is already stored by the Hub.
+                    _ => progress(file.size),
+                }
+            }
+        }
+
+        self.commit(repo, revision, files, &lfs, summary, &mut progress)
+            .await
+    }
+
+    async fn preupload(
+        &self,
+        repo: &str,
+        revision: &str,
+        files: &[HubFile],
+    ) -> DatasetResult<Vec<PreuploadFile>> {
+        let url = self.url(&format!(
+            "/api/datasets/{repo}/preupload/{revision}"
+        ))?;
+        let mut entries = vec![];
+        for file in files.iter() {
+            entries.push(json!({
+                "path": file.path,
+                "sample": file.sample()?,
+                "size": file.size,
+            }));
+        }
+
+        let body = json!({ "files": entries });
+        let response = self
+            .client
+            .send(|client| {
+                client
+                    .post(url.clone())
+                    .header(AUTHORIZATION, self.bearer())
+                    .json(&body)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let message = response.text().await.unwrap_or_default();
+            bail!("unable to prepare upload ({status}): {message}");
+        }
+
+        Ok(response.json::<PreuploadResponse>().await?.files)
+    }
+
+    async fn lfs_batch(
+        &self,
+        repo: &str,
+        files: &[&HubFile],
+    ) -> DatasetResult<Vec<LfsObject>> {
+        let url = self.url(&format!(
+            "/datasets/{repo}.git/info/lfs/objects/batch"
+        ))?;
+        let body = json!({
+            "operation": "upload",
+            "transfers": ["basic", "multipart"],
+            "hash_algo": "sha256",
+            "objects": files
+                .iter()
+                .map(|file| json!({ "oid": file.oid, "size": file.size }))
+                .collect::<Vec<_>>(),
+        });
+
+        let response = self
+            .client
+            .send(|client| {
+                client
+                    .post(url.clone())
+                    .header(AUTHORIZATION, self.bearer())
+                    .header(ACCEPT, LFS_MEDIA_TYPE)
+                    .header(CONTENT_TYPE, LFS_MEDIA_TYPE)
+                    .body(body.to_string())
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let message = response.text().await.unwrap_or_default();
+            bail!(
+                "unable to negotiate LFS upload ({status}): {message}"
+            );
+        }
+
+        Ok(response.json::<LfsBatchResponse>().await?.objects)
+    }
+
+    async fn lfs_upload<F>(
+        &self,
+        file: &HubFile,
+        action: &LfsAction,
+        progress: &mut F,
+    ) -> DatasetResult<()>
+    where
+        F: FnMut(u64),
+    {
+        let chunk_size = action.header.get("chunk_size").and_then(
+            |value| match value {
+                Value::String(s) => s.parse::<u64>().ok(),
+                Value::Number(n) => n.as_u64(),
+                _ => None,
+            },
+        );
+
+        let Some(chunk_size) = chunk_size.filter(|n| *n > 0) else {
+            // basic transfer
+            let body = file.read_range(0, file.size)?;
+            let response = self
+                .client
+                .send(|client| {
+                    client.put(&action.href).body(body.clone())
+                })
+                .await?;
+
+            if !response.status().is_success() {
+                bail!(
+                    "unable to upload '{}' ({})",
+                    file.path,
+                    response.status()
+                );
+            }
+
+            progress(file.size);
+            return Ok(());
+        };
+
+        // multipart transfer: the presigned URLs of the parts are
+        // given by the numeric header keys ("00001", "00002", ...).
+        let mut parts: Vec<(usize, &str)> = action
+            .header
+            .iter()
+            .filter_map(|(key, value)| {
+                Some((key.parse::<usize>().ok()?, value.as_str()?))
+            })
+            .collect();
+        parts.sort_unstable();
+
+        let mut completed = vec![];
+        for (part_number, url) in parts {
+            let offset = (part_number as u64 - 1) * chunk_size;
+            let len = chunk_size.min(file.size.saturating_sub(offset));
+            let chunk = file.read_range(offset, len)?;
+
+            let response = self
+                .client
+                .send(|client| client.put(url).body(chunk.clone()))
+                .await?;
+
+            if !response.status().is_success() {
+                bail!(
+                    "unable to upload part {part_number} of '{}' ({})",
+                    file.path,
+                    response.status()
+                );
+            }
+
+            let etag = response
+                .headers()
+                .get(ETAG)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+
+            completed.push(CompletedPart { part_number, etag });
+            progress(len);
+        }
+
+        let body = json!({ "oid": file.oid, "parts": completed });
+        let response = self
+            .client
+            .send_once(|client| {
+                client
+                    .post(&action.href)
+                    .header(ACCEPT, LFS_MEDIA_TYPE)
+                    .header(CONTENT_TYPE, LFS_MEDIA_TYPE)
+                    .body(body.to_string())
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            bail!(
+                "unable to complete upload of '{}' ({})",
+                file.path,
+                response.status()
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn lfs_verify(
+        &self,
+        file: &HubFile,
+        action: &LfsAction,
+    ) -> DatasetResult<()> {
+        let body = json!({ "oid": file.oid, "size": file.size });
+        let response = self
+            .client
+            .send(|client| {
+                client
+                    .post(&action.href)
+                    .header(AUTHORIZATION, self.bearer())
+                    .header(CONTENT_TYPE, LFS_MEDIA_TYPE)
+                    .body(body.to_string())
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            bail!(
+                "unable to verify upload of '{}' ({})",
+                file.path,
+                response.status()
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn commit<F>(
+        &self,
+        repo: &str,
+        revision: &str,
+        files: &[HubFile],
+        lfs: &[&HubFile],
+        summary: &str,
+        progress: &mut F,
+    ) -> DatasetResult<()>
+    where
+        F: FnMut(u64),
+    {
+        let url = self
+            .url(&format!("/api/datasets/{repo}/commit/{revision}"))?;
+
+        let mut body = json!({
+            "key": "header",
+            "value": { "summary": summary, "description": "" },
+        })
+        .to_string();
+
+        for file in files.iter() {
+            body.push('\n');
+            if lfs.iter().any(|f| f.path == file.path) {
+                body.push_str(
+                    &json!({
+                        "key": "lfsFile",
+                        "value": {
+                            "path": file.path,
+                            "algo": "sha256",
+                            "oid": file.oid,
+                            "size": file.size,
+                        },
+                    })
+                    .to_string(),
+                );
+            } else {
+                let content = file.read_range(0, file.size)?;
+                body.push_str(
+                    &json!({
+                        "key": "file",
+                        "value": {
+                            "path": file.path,
+                            "encoding": "base64",
+                            "content": BASE64_STANDARD.encode(content),
+                        },
+                    })
+                    .to_string(),
+                );
+                progress(file.size);
+            }
+        }
+
+        // The commit isn't retried after a timeout or a server error,
+        // since it may have been applied already.
+        let response = self
+            .client
+            .send_once(|client| {
+                client
+                    .post(url.clone())
+                    .header(AUTHORIZATION, self.bearer())
+                    .header(CONTENT_TYPE, "application/x-ndjson")
+                    .body(body.clone())
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let message = response.text().await.unwrap_or_default();
+            bail!("unable to commit to '{repo}' ({status}): {message}");
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the repository path of a Parquet shard, following the
+/// naming scheme of the datasets library.
+pub(crate) fn shard_path(
+    split: &str,
+    idx: usize,
+    total: usize,
+) -> String {
+    format!("data/{split}-{idx:05}-of-{total:05}.parquet")
+}
+
+/// Returns `true`, if the path is a valid repository id
+/// (`namespace/name`).
+pub(crate) fn is_repo_id(repo: &str) -> bool {
+    let valid = |s: &str| {
+        !s.is_empty()
+            && !s.starts_with(['-', '.'])
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+    };
+
+    matches!(repo.split_once('/'), Some((ns, name)) if valid(ns) && valid(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hub_repo_ids() {
+        assert!(is_repo_id("dnb/toc-de"));
+        assert!(!is_repo_id("toc-de"));
+        assert!(!is_repo_id("dnb/"));
+        assert!(!is_repo_id("dnb/toc de"));
+        assert_eq!(
+            shard_path("train", 1, 12),
+            "data/train-00001-of-00012.parquet"
+        );
+    }
+}
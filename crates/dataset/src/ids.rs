@@ -0,0 +1,308 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use csv::{ReaderBuilder, WriterBuilder};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::atomic::AtomicFile;
+use crate::prelude::*;
+use crate::table::strings;
+
+/// The key of a document: the name of the remote and the path of the
+/// document within the remote.
+pub(crate) type DocKey = (String, String);
+
+/// An entry of the id registry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct IdRecord {
+    /// The (stable) id of the document.
+    pub(crate) id: u64,
+
+    /// The name of the remote.
+    pub(crate) remote: String,
+
+    /// The path of the document within the remote.
+    pub(crate) path: String,
+
+    /// Whether the id is retired. A retired id doesn't belong to any
+    /// document anymore, but is kept to prevent its reuse.
+    #[serde(default)]
+    pub(crate) retired: bool,
+}
+
+/// The registry of the document ids (`.dataset/ids.csv`).
+///
+/// The registry guarantees, that (1) a document keeps its id as long
+/// as its key (remote and path) doesn't change, (2) an id is never
+/// reused, even if its document is removed from the remote (the id is
+/// kept and re-activated, if the document re-appears) and (3) new
+/// documents get ids greater than all ids ever assigned.
+#[derive(Debug, Default)]
+pub(crate) struct IdRegistry {
+    records: Vec<IdRecord>,
+}
+
+/// The result of a consistency check of the registry.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct IdCheck {
+    /// Ids, which are assigned to more than one document.
+    pub(crate) duplicate_ids: Vec<u64>,
+
+    /// Keys, which have more than one (active) id.
+    pub(crate) duplicate_keys: Vec<DocKey>,
+
+    /// Documents of the compound index without an id.
+    pub(crate) missing: Vec<DocKey>,
+
+    /// Ids, whose documents aren't part of the compound index.
+    pub(crate) orphaned: Vec<u64>,
+}
+
+impl IdCheck {
+    /// Returns true, if the registry contains collisions (duplicate
+    /// ids or keys), which must be fixed by `dataset ids repair`.
+    pub(crate) fn has_collisions(&self) -> bool {
+        !self.duplicate_ids.is_empty()
+            || !self.duplicate_keys.is_empty()
+    }
+}
+
+/// Returns the keys of the documents of the compound index.
+pub(crate) fn doc_keys(df: &DataFrame) -> DatasetResult<Vec<DocKey>> {
+    Ok(strings(df, "remote")?
+        .into_iter()
+        .zip(strings(df, "path")?)
+        .filter_map(|(remote, path)| Some((remote?, path?)))
+        .collect())
+}
+
+impl IdRegistry {
+    /// Loads the registry. A missing registry results in an empty
+    /// registry.
+    pub(crate) fn from_path<P: AsRef<Path>>(
+        path: P,
+    ) -> DatasetResult<Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+
+        let mut reader = ReaderBuilder::new().from_path(path)?;
+        let records = reader
+            .deserialize()
+            .collect::<Result<Vec<IdRecord>, _>>()?;
+
+        Ok(Self { records })
+    }
+
+    /// Saves the registry (sorted by id).
+    pub(crate) fn save<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> DatasetResult<()> {
+        self.records.sort_by_key(|record| record.id);
+
+        let mut out = AtomicFile::create(path.as_ref())?;
+        let mut writer = WriterBuilder::new().from_writer(&mut out);
+        for record in self.records.iter() {
+            writer.serialize(record)?;
+        }
+
+        writer.flush()?;
+        drop(writer);
+        out.commit()?;
+        Ok(())
+    }
+
+    /// Returns the id, which is assigned to the next new document.
+    fn next_id(&self) -> u64 {
+        self.records.iter().map(|r| r.id + 1).max().unwrap_or(1)
+    }
+
+    /// Returns the active ids by document key. If a key has more than
+    /// one id, the smallest one is returned.
+    pub(crate) fn ids(&self) -> HashMap<DocKey, u64> {
+        let mut ids: HashMap<DocKey, u64> = HashMap::new();
+        for record in self.records.iter().filter(|r| !r.retired) {
+            let key = (record.remote.clone(), record.path.clone());
+            ids.entry(key)
+                .and_modify(|id| *id = (*id).min(record.id))
+                .or_insert(record.id);
+        }
+
+        ids
+    }
+
+    /// Assigns new ids to all documents without an (active) id and
+    /// returns the number of newly assigned ids. Retired ids aren't
+    /// re-activated; a document, which re-appears with the same key,
+    /// keeps its (active) id.
+    pub(crate) fn assign<'a, I>(&mut self, keys: I) -> usize
+    where
+        I: IntoIterator<Item = &'a DocKey>,
+    {
+        let mut ids = self.ids();
+        let mut next = self.next_id();
+        let mut count = 0;
+
+        for key in keys {
+            if ids.contains_key(key) {
+                continue;
+            }
+
+            ids.insert(key.clone(), next);
+            self.records.push(IdRecord {
+                id: next,
+                remote: key.0.clone(),
+                path: key.1.clone(),
+                retired: false,
+            });
+
+            next += 1;
+            count += 1;
+        }
+
+        count
+    }
+
+    /// Checks the registry against the documents of the compound
+    /// index.
+    pub(crate) fn check(&self, keys: &[DocKey]) -> IdCheck {
+        let mut result = IdCheck::default();
+
+        let mut seen_ids = HashSet::new();
+        let mut seen_keys = HashSet::new();
+        for record in self.records.iter() {
+            if !seen_ids.insert(record.id) {
+                result.duplicate_ids.push(record.id);
+            }
+
+            let key = (record.remote.clone(), record.path.clone());
+            if !record.retired && !seen_keys.insert(key.clone()) {
+                result.duplicate_keys.push(key);
+            }
+        }
+
+        let ids = self.ids();
+        let keys: HashSet<&DocKey> = keys.iter().collect();
+        result.missing = keys
+            .iter()
+            .filter(|key| !ids.contains_key(**key))
+            .map(|key| (*key).clone())
+            .collect();
+        result.missing.sort();
+
+        result.orphaned = ids
+            .iter()
+            .filter(|(key, _)| !keys.contains(key))
+            .map(|(_, id)| *id)
+            .collect();
+        result.orphaned.sort_unstable();
+
+        result.duplicate_ids.sort_unstable();
+        result.duplicate_ids.dedup();
+        result.duplicate_keys.sort();
+        result.duplicate_keys.dedup();
+        result
+    }
+
+    /// Resolves the collisions of the registry and returns the number
+    /// of changed records.
+    ///
+    /// If an id is assigned to more than one record, the first record
+    /// keeps the id and all other records get new ids. If a key has
+    /// more than one active id, the smallest id is kept and all other
+    /// ids are retired.
+    pub(crate) fn repair(&mut self) -> usize {
+        let mut next = self.next_id();
+        let mut seen_ids = HashSet::new();
+        let mut changed = 0;
+
+        for record in self.records.iter_mut() {
+            if !seen_ids.insert(record.id) {
+                record.id = next;
+                next += 1;
+                changed += 1;
+            }
+        }
+
+        let ids = self.ids();
+        for record in self.records.iter_mut().filter(|r| !r.retired) {
+            let key = (record.remote.clone(), record.path.clone());
+            if ids[&key] != record.id {
+                record.retired = true;
+                changed += 1;
+            }
+        }
+
+        changed
+    }
+
+    /// Adds the (active) ids of the documents as `doc_id` column to
+    /// the compound index. An existing `doc_id` column is replaced.
+    pub(crate) fn attach(
+        &self,
+        mut df: DataFrame,
+    ) -> DatasetResult<DataFrame> {
+        let ids = self.ids();
+        let values: Vec<Option<u64>> = strings(&df, "remote")?
+            .into_iter()
+            .zip(strings(&df, "path")?)
+            .map(|key| match key {
+                (Some(remote), Some(path)) => {
+                    ids.get(&(remote, path)).copied()
+                }
+                _ => None,
+            })
+            .collect();
+
+        let _ = df.drop_in_place("doc_id");
+        df.with_column(Column::new("doc_id".into(), values))?;
+        Ok(df)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(remote: &str, path: &str) -> DocKey {
+        (remote.into(), path.into())
+    }
+
+    #[test]
+    fn id_registry() {
+        let mut registry = IdRegistry::default();
+        let keys = vec![key("a", "1.txt"), key("a", "2.txt")];
+        assert_eq!(registry.assign(&keys), 2);
+        assert_eq!(registry.assign(&keys), 0);
+        assert_eq!(registry.ids()[&key("a", "2.txt")], 2);
+
+        let keys = vec![key("a", "2.txt"), key("b", "1.txt")];
+        assert_eq!(registry.assign(&keys), 1);
+        assert_eq!(registry.ids()[&key("b", "1.txt")], 3);
+
+        let check = registry.check(&keys);
+        assert!(!check.has_collisions());
+        assert_eq!(check.orphaned, vec![1]);
+        assert!(check.missing.is_empty());
+
+        registry.records.push(IdRecord {
+            id: 2,
+            remote: "b".into(),
+            path: "1.txt".into(),
+            retired: false,
+        });
+
+        let check = registry.check(&keys);
+        assert_eq!(check.duplicate_ids, vec![2]);
+        assert_eq!(check.duplicate_keys, vec![key("b", "1.txt")]);
+
+        assert_eq!(registry.repair(), 2);
+        assert!(!registry.check(&keys).has_collisions());
+        assert_eq!(registry.ids()[&key("a", "2.txt")], 2);
+        assert_eq!(registry.ids()[&key("b", "1.txt")], 3);
+        assert_eq!(registry.next_id(), 5);
+    }
+}
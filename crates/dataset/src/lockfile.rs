@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::atomic::AtomicFile;
+use crate::prelude::*;
+
+/// The state of the remotes at the time of the last fetch.
+///
+/// The lock file is written by `dataset fetch` next to the compound
+/// index and is used to detect changes of the remotes without
+/// re-fetching them.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Lockfile {
+    #[serde(rename = "remote", default)]
+    pub(crate) remotes: BTreeMap<String, LockedRemote>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct LockedRemote {
+    /// The URL of the remote.
+    pub(crate) url: Url,
+
+    /// The SHA256 digest of the remote index.
+    pub(crate) hash: String,
+
+    /// The entity tag of the remote index, if provided by the server.
+    pub(crate) etag: Option<String>,
+
+    /// The number of documents (after applying the predicate).
+    pub(crate) documents: u64,
+
+    /// The time of the fetch (seconds since the UNIX epoch).
+    pub(crate) fetched: u64,
+}
+
+impl Lockfile {
+    /// Loads the lock file. A missing lock file results in an empty
+    /// lock file.
+    pub(crate) fn from_path<P: AsRef<Path>>(
+        path: P,
+    ) -> DatasetResult<Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Saves the lock file.
+    pub(crate) fn save<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> DatasetResult<()> {
+        let content = toml::to_string(self).expect("valid toml");
+        let mut out = AtomicFile::create(path.as_ref())?;
+        out.write_all(content.as_bytes())?;
+        out.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn lockfile_roundtrip() -> TestResult {
+        let dir = temp_dir()?;
+        let path = dir.path().join("remotes.lock");
+        assert!(Lockfile::from_path(&path)?.remotes.is_empty());
+
+        let mut lockfile = Lockfile::default();
+        lockfile.remotes.insert(
+            "foo".into(),
+            LockedRemote {
+                url: Url::parse("http://localhost:9001")?,
+                hash: "abc".into(),
+                etag: None,
+                documents: 3,
+                fetched: 1700000000,
+            },
+        );
+        lockfile.save(&path)?;
+
+        let content = fs::read_to_string(&path)?;
+        assert!(content.contains("[remote.foo]"));
+
+        let lockfile = Lockfile::from_path(&path)?;
+        let locked = &lockfile.remotes["foo"];
+        assert_eq!(locked.url.as_str(), "http://localhost:9001/");
+        assert_eq!(locked.hash, "abc");
+        assert!(locked.etag.is_none());
+        assert_eq!(locked.documents, 3);
+        Ok(())
+    }
+}
@@ -7,25 +7,51 @@ use dataset::Dataset;
 use error::{DatasetError, DatasetResult};
 use rayon::ThreadPoolBuilder;
 
+mod atomic;
+mod audit;
+mod bloom;
 mod cli;
 mod commands;
 mod config;
 mod dataset;
 mod error;
+mod flight;
+mod http;
+mod hub;
+mod ids;
+mod lockfile;
 mod prelude;
 mod progress;
+mod prune;
 mod remote;
+mod schema;
+mod signature;
+mod table;
+#[cfg(test)]
+mod testing;
 mod vocab;
+mod weak_label;
 
 async fn run(args: Args) -> DatasetResult<()> {
     match args.cmd {
+        Command::Annif(cmd) => cmd.execute().await,
         Command::Completions(cmd) => cmd.execute(),
         Command::Config(cmd) => cmd.execute(),
+        Command::Eval(cmd) => cmd.execute(),
+        Command::Export(cmd) => cmd.execute().await,
         Command::Fetch(cmd) => cmd.execute().await,
+        Command::Grep(cmd) => cmd.execute().await,
+        Command::Ids(cmd) => cmd.execute(),
         Command::Init(cmd) => cmd.execute(),
+        Command::PicaTest(cmd) => cmd.execute(),
+        Command::Prune(cmd) => cmd.execute(),
+        Command::Publish(cmd) => cmd.execute().await,
         Command::Remote(cmd) => cmd.execute(),
+        Command::Sru(cmd) => cmd.execute().await,
+        Command::Status(cmd) => cmd.execute().await,
         Command::Version(cmd) => cmd.execute(),
         Command::Vocab(cmd) => cmd.execute(),
+        Command::WeakLabel(cmd) => cmd.execute().await,
     }
 }
 
@@ -54,6 +80,10 @@ async fn main() {
         .build_global()
         .unwrap();
 
+    // The categorical columns of the remote indices must share their
+    // categories, when the indices are combined.
+    polars::enable_string_cache();
+
     match run(args).await {
         Ok(()) => process::exit(0),
         Err(DatasetError::IO(e))
@@ -0,0 +1,327 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+use crate::table::{read_table, strings};
+
+/// The default column of the per-class caps.
+const CLASS_COLUMN: &str = "kind";
+
+/// Dataset-level constraints (see `dataset prune`).
+///
+/// ```toml
+/// [prune]
+/// max-per-ppn = 1
+/// languages = ["ger", "eng"]
+/// class-column = "msc"
+/// exclude = ["exclude.csv"]
+/// prefer = "lang_score"
+///
+/// [prune.caps]
+/// "830" = 10000
+/// "B" = 500
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct PruneConfig {
+    /// The maximum number of documents per record (`idn`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) max_per_ppn: Option<usize>,
+
+    /// The allowed languages (`lang_code`). An empty list allows all
+    /// languages.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) languages: Vec<String>,
+
+    /// The column of the per-class caps (default: `kind`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) class_column: Option<String>,
+
+    /// The maximum number of documents per class.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub(crate) caps: BTreeMap<String, usize>,
+
+    /// Tables (CSV or IPC) of excluded documents with an `idn` (or
+    /// `ppn`) and/or a `path` column, relative to the root directory
+    /// of the dataset.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) exclude: Vec<PathBuf>,
+
+    /// The numeric column, whose largest values are kept first, if a
+    /// cap is exceeded. By default, the documents are kept in the
+    /// order of the compound index.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) prefer: Option<String>,
+}
+
+impl PruneConfig {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.max_per_ppn.is_none()
+            && self.languages.is_empty()
+            && self.class_column.is_none()
+            && self.caps.is_empty()
+            && self.exclude.is_empty()
+            && self.prefer.is_none()
+    }
+}
+
+/// The reason of a removal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Reason {
+    Excluded,
+    Language,
+    MaxPerPpn,
+    ClassCap,
+}
+
+impl Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Excluded => write!(f, "excluded"),
+            Self::Language => write!(f, "language"),
+            Self::MaxPerPpn => write!(f, "max-per-ppn"),
+            Self::ClassCap => write!(f, "class-cap"),
+        }
+    }
+}
+
+/// The documents of the exclusion lists.
+#[derive(Debug, Default)]
+pub(crate) struct Exclusions {
+    idns: HashSet<String>,
+    paths: HashSet<String>,
+}
+
+impl Exclusions {
+    /// Reads the exclusion lists. Relative paths are resolved against
+    /// the given base directory.
+    pub(crate) fn from_paths(
+        paths: &[PathBuf],
+        base_dir: &Path,
+    ) -> DatasetResult<Self> {
+        let mut exclusions = Self::default();
+
+        for path in paths.iter() {
+            let df = read_table(&base_dir.join(path))?;
+            let mut found = false;
+
+            for name in ["idn", "ppn"] {
+                if df.column(name).is_ok() {
+                    exclusions.idns.extend(
+                        strings(&df, name)?.into_iter().flatten(),
+                    );
+                    found = true;
+                }
+            }
+
+            if df.column("path").is_ok() {
+                exclusions.paths.extend(
+                    strings(&df, "path")?.into_iter().flatten(),
+                );
+                found = true;
+            }
+
+            if !found {
+                bail!(
+                    "exclusion list '{}' has neither an idn, ppn nor \
+                    path column",
+                    path.display()
+                );
+            }
+        }
+
+        Ok(exclusions)
+    }
+
+    /// Returns the excluded value (the `idn` or the `path`), which
+    /// matches the document.
+    fn matches<'a>(
+        &self,
+        idn: Option<&'a str>,
+        path: Option<&'a str>,
+    ) -> Option<&'a str> {
+        idn.filter(|idn| self.idns.contains(*idn))
+            .or(path.filter(|path| self.paths.contains(*path)))
+    }
+}
+
+/// Returns the values of a column as strings or `None` for each row,
+/// if the column doesn't exist.
+pub(crate) fn optional_strings(
+    df: &DataFrame,
+    name: &str,
+) -> DatasetResult<Vec<Option<String>>> {
+    if df.column(name).is_err() {
+        return Ok(vec![None; df.height()]);
+    }
+
+    strings(df, name)
+}
+
+/// Applies the constraints to the compound index and returns a mask of
+/// the kept documents and the reason (and the offending value) of each
+/// removed document.
+///
+/// The constraints are checked in the following order: exclusion
+/// lists, languages, documents per record and per-class caps.
+/// Removed documents don't count towards a cap.
+pub(crate) fn prune(
+    df: &DataFrame,
+    config: &PruneConfig,
+    exclusions: &Exclusions,
+) -> DatasetResult<(Vec<bool>, BTreeMap<usize, (Reason, String)>)> {
+    let idns = optional_strings(df, "idn")?;
+    let paths = optional_strings(df, "path")?;
+    let languages = optional_strings(df, "lang_code")?;
+
+    let class_column =
+        config.class_column.as_deref().unwrap_or(CLASS_COLUMN);
+    let classes = if config.caps.is_empty() {
+        vec![None; df.height()]
+    } else if df.column(class_column).is_ok() {
+        strings(df, class_column)?
+    } else {
+        bail!("column '{class_column}' not found");
+    };
+
+    let mut order: Vec<usize> = (0..df.height()).collect();
+    if let Some(ref prefer) = config.prefer {
+        let values = df.column(prefer)?.cast(&DataType::Float64)?;
+        let values: Vec<Option<f64>> = values
+            .f64()?
+            .into_iter()
+            .map(|value| value.filter(|value| !value.is_nan()))
+            .collect();
+
+        // stable sort, descending; nulls (and NaN) last
+        order.sort_by(|a, b| match (values[*a], values[*b]) {
+            (Some(a), Some(b)) => b.total_cmp(&a),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+    }
+
+    let mut keep = vec![false; df.height()];
+    let mut reasons = BTreeMap::new();
+    let mut per_ppn: HashMap<&str, usize> = HashMap::new();
+    let mut per_class: HashMap<&str, usize> = HashMap::new();
+
+    for idx in order {
+        let idn = idns[idx].as_deref();
+        let class = classes[idx].as_deref();
+        let lang = languages[idx].as_deref();
+
+        if let Some(value) =
+            exclusions.matches(idn, paths[idx].as_deref())
+        {
+            reasons.insert(idx, (Reason::Excluded, value.into()));
+            continue;
+        }
+
+        if !config.languages.is_empty()
+            && !lang.is_some_and(|lang| {
+                config.languages.iter().any(|code| code == lang)
+            })
+        {
+            reasons.insert(
+                idx,
+                (Reason::Language, lang.unwrap_or_default().into()),
+            );
+            continue;
+        }
+
+        if let (Some(max), Some(idn)) = (config.max_per_ppn, idn) {
+            if per_ppn.get(idn).copied().unwrap_or_default() >= max {
+                reasons.insert(idx, (Reason::MaxPerPpn, idn.into()));
+                continue;
+            }
+        }
+
+        if let Some(class) = class {
+            let count =
+                per_class.get(class).copied().unwrap_or_default();
+            if config.caps.get(class).is_some_and(|cap| count >= *cap) {
+                reasons.insert(idx, (Reason::ClassCap, class.into()));
+                continue;
+            }
+
+            per_class.insert(class, count + 1);
+        }
+
+        if let Some(idn) = idn {
+            *per_ppn.entry(idn).or_default() += 1;
+        }
+
+        keep[idx] = true;
+    }
+
+    Ok((keep, reasons))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn prune_index() -> TestResult {
+        let df = df!(
+            "path" => ["a.txt", "b.txt", "c.txt", "d.txt", "e.txt"],
+            "idn" => ["1", "1", "2", "3", "4"],
+            "kind" => ["book", "book", "book", "article", "book"],
+            "lang_code" => [Some("ger"), Some("ger"), None, Some("eng"), Some("ger")],
+            "lang_score" => [0.5, 0.9, 0.8, 0.7, 0.6],
+        )?;
+
+        let config = PruneConfig {
+            max_per_ppn: Some(1),
+            languages: vec!["ger".into(), "eng".into()],
+            caps: [("book".to_string(), 1)].into(),
+            prefer: Some("lang_score".into()),
+            ..Default::default()
+        };
+
+        let exclusions = Exclusions {
+            paths: ["d.txt".to_string()].into(),
+            ..Default::default()
+        };
+
+        let (keep, reasons) = prune(&df, &config, &exclusions)?;
+        assert_eq!(keep, [false, true, false, false, false]);
+        assert_eq!(
+            reasons.into_iter().collect::<Vec<_>>(),
+            [
+                (0, (Reason::MaxPerPpn, "1".into())),
+                (2, (Reason::Language, "".into())),
+                (3, (Reason::Excluded, "d.txt".into())),
+                (4, (Reason::ClassCap, "book".into())),
+            ]
+        );
+        Ok(())
+    }
+    #[test]
+    fn prune_prefer_nan() -> TestResult {
+        let df = df!(
+            "path" => ["a.txt", "b.txt", "c.txt"],
+            "idn" => ["1", "1", "1"],
+            "lang_score" => [Some(f64::NAN), None, Some(0.5)],
+        )?;
+
+        let config = PruneConfig {
+            max_per_ppn: Some(1),
+            prefer: Some("lang_score".into()),
+            ..Default::default()
+        };
+
+        let (keep, _) = prune(&df, &config, &Exclusions::default())?;
+        assert_eq!(keep, [false, false, true]);
+        Ok(())
+    }
+}
@@ -1,12 +1,52 @@
+use std::fmt::Write;
+use std::io::Cursor;
+use std::sync::OnceLock;
+
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use reqwest::header::ETAG;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use url::Url;
 
+use crate::bloom::BloomFilter;
+use crate::config::Config;
+use crate::http::{HttpClient, Politeness};
 use crate::prelude::*;
+use crate::schema::Schema;
+use crate::{flight, signature};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct Remote {
     pub(crate) url: Url,
     pub(crate) predicate: Option<String>,
+
+    /// The name of the group the remote belongs to.
+    pub(crate) group: Option<String>,
+
+    /// Column rename and cast rules applied to the index of the
+    /// remote.
+    #[serde(default, skip_serializing_if = "Schema::is_empty")]
+    pub(crate) schema: Schema,
+
+    /// The pinned (base64 encoded) ed25519 public key of the remote.
+    /// If set, the signature of the remote index is verified.
+    pub(crate) public_key: Option<String>,
+
+    /// The URL of the Arrow Flight service of the remote (e.g.
+    /// `http://localhost:9002`). If set (and no public key is pinned),
+    /// the index is fetched via Arrow Flight and the predicates are
+    /// evaluated by the server, unless a schema mapping is applied.
+    pub(crate) flight: Option<Url>,
+
+    /// Concurrency caps, rate limits and identifying headers of the
+    /// requests to the remote.
+    #[serde(default, skip_serializing_if = "Politeness::is_empty")]
+    pub(crate) politeness: Politeness,
+
+    /// The HTTP client of the remote, which is created on first use.
+    #[serde(skip)]
+    client: OnceLock<HttpClient>,
 }
 
 impl Remote {
@@ -24,6 +64,12 @@ impl Remote {
         Ok(Self {
             url,
             predicate: query.map(|s| s.to_string()),
+            group: None,
+            schema: Schema::default(),
+            public_key: None,
+            flight: None,
+            politeness: Politeness::default(),
+            client: OnceLock::new(),
         })
     }
 
@@ -46,4 +92,397 @@ impl Remote {
     pub(crate) fn set_predicate<S: ToString>(&mut self, predicate: S) {
         self.predicate = Some(predicate.to_string());
     }
+
+    /// Returns `true`, if the remote belongs to one of the given groups
+    /// or if no group is given.
+    pub(crate) fn in_groups(&self, groups: &[String]) -> bool {
+        groups.is_empty()
+            || self.group.as_ref().is_some_and(|g| groups.contains(g))
+    }
+}
+
+/// A group of remotes.
+///
+/// The predicate and the schema mapping of a group are applied to all
+/// remotes of the group (before those of the remote).
+///
+/// ```toml
+/// [group.toc-sheds]
+/// predicate = "kind = 'toc'"
+///
+/// [remote.foo]
+/// url = "http://localhost:9999"
+/// group = "toc-sheds"
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Group {
+    pub(crate) predicate: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Schema::is_empty")]
+    pub(crate) schema: Schema,
+}
+
+/// The index of a remote as fetched from the server.
+pub(crate) struct RemoteIndex {
+    /// The index (after applying the schema and the predicate).
+    pub(crate) df: DataFrame,
+
+    /// The SHA256 digest of the index file.
+    pub(crate) hash: String,
+
+    /// The entity tag of the index file, if provided by the server.
+    pub(crate) etag: Option<String>,
+}
+
+/// Returns the hex-encoded SHA256 digest of the data.
+fn sha256(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .fold(String::new(), |mut out, b| {
+            let _ = write!(out, "{b:02x}");
+            out
+        })
+}
+
+impl Remote {
+    /// Returns the URL of the remote index.
+    pub(crate) fn index_url(&self) -> Url {
+        let mut url = self.url.clone();
+        url.set_path("/index.ipc");
+        url
+    }
+
+    /// Returns the URL of the detached signature of the remote index.
+    pub(crate) fn signature_url(&self) -> Url {
+        let mut url = self.url.clone();
+        url.set_path("/index.ipc.sig");
+        url
+    }
+
+    /// Returns the URL of the Bloom filter over the PPNs of the remote
+    /// index.
+    pub(crate) fn bloom_url(&self) -> Url {
+        let mut url = self.url.clone();
+        url.set_path("/index.bloom");
+        url
+    }
+
+    /// Returns the URL of the search API of the remote.
+    pub(crate) fn grep_url(&self) -> Url {
+        let mut url = self.url.clone();
+        url.set_path("/api/grep");
+        url
+    }
+
+    /// Returns the URL of a document (`path` relative to the root
+    /// directory of the datashed).
+    pub(crate) fn document_url(&self, path: &str) -> Url {
+        let mut url = self.url.clone();
+        url.set_path(&format!("/{}", path.trim_start_matches('/')));
+        url
+    }
+
+    /// Downloads the document `path` of the remote `name` (HTTP basic
+    /// authentication, if `username` is set).
+    pub(crate) async fn fetch_document(
+        &self,
+        name: &str,
+        client: &HttpClient,
+        path: &str,
+        username: Option<&String>,
+        secret: Option<&String>,
+    ) -> DatasetResult<String> {
+        let response = client
+            .send(|client| {
+                let request = client.get(self.document_url(path));
+                match username {
+                    Some(username) => {
+                        request.basic_auth(username, secret)
+                    }
+                    None => request,
+                }
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            bail!(
+                "unable to get document '{path}' (remote = {name}, \
+                status = {})",
+                response.status()
+            );
+        }
+
+        Ok(response.text().await?)
+    }
+
+    /// Returns the HTTP client of the remote, which respects the
+    /// politeness settings of the remote. The client is created once;
+    /// all clones share the same concurrency and rate limits.
+    pub(crate) fn client(
+        &self,
+        config: &Config,
+    ) -> DatasetResult<HttpClient> {
+        if let Some(client) = self.client.get() {
+            return Ok(client.clone());
+        }
+
+        let client = HttpClient::with_politeness(
+            &config.http,
+            &self.politeness,
+        )?;
+        Ok(self.client.get_or_init(|| client).clone())
+    }
+
+    /// Fetches the index of the remote.
+    ///
+    /// The schema mappings (the global one, the one of the group and
+    /// the one of the remote) and the predicates (of the group and of
+    /// the remote) are applied and the `remote` column is set to the
+    /// name of the remote.
+    pub(crate) async fn fetch_index(
+        &self,
+        name: &str,
+        config: &Config,
+    ) -> DatasetResult<RemoteIndex> {
+        let group = match self.group {
+            None => None,
+            Some(ref group) => match config.groups.get(group) {
+                Some(group) => Some(group),
+                None => {
+                    bail!("unknown group '{group}' (remote = {name})")
+                }
+            },
+        };
+
+        let predicates: Vec<&String> = group
+            .and_then(|group| group.predicate.as_ref())
+            .into_iter()
+            .chain(self.predicate.as_ref())
+            .collect();
+
+        if let Some(ref url) = self.flight {
+            if self.public_key.is_none() {
+                return self
+                    .fetch_flight(name, url, config, group, &predicates)
+                    .await;
+            }
+        }
+
+        let client = self.client(config)?;
+        let response = client.get(self.index_url()).await?;
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string);
+
+        let body = response.bytes().await?;
+        if body.is_empty() {
+            bail!("unable to get datashed index (remote = {name})");
+        }
+
+        if let Some(ref public_key) = self.public_key {
+            let response = client.get(self.signature_url()).await?;
+            if !response.status().is_success() {
+                bail!(
+                    "unable to get index signature (remote = {name})"
+                );
+            }
+
+            let signature = response.text().await?;
+            if let Err(e) =
+                signature::verify(public_key, &body, &signature)
+            {
+                bail!("{e} (remote = {name})");
+            }
+        }
+
+        let hash = sha256(&body);
+        let df = IpcReader::new(Cursor::new(body)).finish()?;
+        let df = self.finish(name, df, config, group, &predicates)?;
+
+        Ok(RemoteIndex { df, hash, etag })
+    }
+
+    /// Fetches the index of the remote via Arrow Flight. If no schema
+    /// mapping is applied, the predicates are pushed down to the
+    /// server.
+    async fn fetch_flight(
+        &self,
+        name: &str,
+        url: &Url,
+        config: &Config,
+        group: Option<&Group>,
+        predicates: &[&String],
+    ) -> DatasetResult<RemoteIndex> {
+        let pushdown = config.schema.is_empty()
+            && group.is_none_or(|group| group.schema.is_empty())
+            && self.schema.is_empty();
+
+        let predicate =
+            (pushdown && !predicates.is_empty()).then(|| {
+                predicates
+                    .iter()
+                    .map(|predicate| format!("({predicate})"))
+                    .collect::<Vec<_>>()
+                    .join(" AND ")
+            });
+
+        let (body, df) = flight::fetch(url, predicate).await?;
+        let predicates = if pushdown { &[][..] } else { predicates };
+        let df = self.finish(name, df, config, group, predicates)?;
+
+        Ok(RemoteIndex {
+            df,
+            hash: sha256(&body),
+            etag: None,
+        })
+    }
+
+    /// Applies the schema mappings and the predicates to the index and
+    /// sets the `remote` column.
+    fn finish(
+        &self,
+        name: &str,
+        mut df: DataFrame,
+        config: &Config,
+        group: Option<&Group>,
+        predicates: &[&String],
+    ) -> DatasetResult<DataFrame> {
+        df = config.schema.apply(df)?;
+        if let Some(group) = group {
+            df = group.schema.apply(df)?;
+        }
+
+        df = self.schema.apply(df)?;
+
+        for predicate in predicates {
+            let mut ctx = SQLContext::new();
+            ctx.register("index", df.lazy());
+            df = ctx
+                .execute(&format!(
+                    "SELECT * FROM index WHERE {predicate}"
+                ))?
+                .collect()?
+        }
+
+        Ok(df
+            .lazy()
+            .with_column(lit(name).alias("remote"))
+            .collect()?)
+    }
+
+    /// Fetches the Bloom filter over the PPNs of the remote index
+    /// (`etag` is the entity tag of the fetched index).
+    ///
+    /// The filter is computed over the `idn` column of the unmapped
+    /// index, so `None` is returned, if a schema mapping is applied.
+    /// `None` is also returned, if the remote doesn't publish a filter
+    /// or if the index has changed since it was fetched.
+    pub(crate) async fn fetch_bloom(
+        &self,
+        config: &Config,
+        etag: Option<&str>,
+    ) -> DatasetResult<Option<BloomFilter>> {
+        let Some(etag) = etag else {
+            return Ok(None);
+        };
+
+        let group = self
+            .group
+            .as_ref()
+            .and_then(|group| config.groups.get(group));
+
+        if !config.schema.is_empty()
+            || group.is_some_and(|group| !group.schema.is_empty())
+            || !self.schema.is_empty()
+        {
+            return Ok(None);
+        }
+
+        let client = self.client(config)?;
+        let response = client.get(self.bloom_url()).await?;
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+
+        let body = response.bytes().await?;
+        if self.index_etag(config).await?.as_deref() != Some(etag) {
+            return Ok(None);
+        }
+
+        Ok(Some(BloomFilter::read(&body[..])?))
+    }
+
+    /// Returns the entity tag of the remote index (HEAD request), if
+    /// provided by the server.
+    pub(crate) async fn index_etag(
+        &self,
+        config: &Config,
+    ) -> DatasetResult<Option<String>> {
+        let client = self.client(config)?;
+        let response =
+            client.send(|client| client.head(self.index_url())).await?;
+
+        Ok(response
+            .headers()
+            .get(ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn remote_groups() -> TestResult {
+        let config: Config = toml::from_str(
+            r#"
+            [metadata]
+            name = "foo"
+            version = "0.1.0"
+
+            [group.toc]
+            predicate = "kind = 'toc'"
+
+            [remote.foo]
+            url = "http://localhost:9001"
+            predicate = "lang = 'ger'"
+            group = "toc"
+
+            [remote.bar]
+            url = "http://localhost:9002"
+            "#,
+        )?;
+
+        let foo = &config.remotes["foo"];
+        let bar = &config.remotes["bar"];
+        let groups = ["toc".to_string()];
+        assert!(foo.in_groups(&groups) && foo.in_groups(&[]));
+        assert!(!bar.in_groups(&groups) && bar.in_groups(&[]));
+
+        let group = config.groups.get("toc");
+        let predicates: Vec<&String> = group
+            .and_then(|group| group.predicate.as_ref())
+            .into_iter()
+            .chain(foo.predicate.as_ref())
+            .collect();
+
+        let df = df!(
+            "path" => ["a.txt", "b.txt", "c.txt"],
+            "kind" => ["toc", "toc", "book"],
+            "lang" => ["ger", "eng", "ger"],
+        )?;
+
+        let df = foo.finish("foo", df, &config, group, &predicates)?;
+        assert_eq!(df.column("path")?.str()?.get(0), Some("a.txt"));
+        assert_eq!(df.column("remote")?.str()?.get(0), Some("foo"));
+        assert_eq!(df.height(), 1);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,35 @@
+use base64::prelude::*;
+use ed25519_dalek::{Signature, VerifyingKey};
+
+use crate::prelude::*;
+
+/// Verifies the (base64 encoded) detached signature of the data
+/// against the (base64 encoded) ed25519 public key.
+pub(crate) fn verify(
+    public_key: &str,
+    data: &[u8],
+    signature: &str,
+) -> DatasetResult<()> {
+    let Some(key) = BASE64_STANDARD
+        .decode(public_key.trim())
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+    else {
+        bail!("invalid public key '{public_key}'");
+    };
+
+    let Some(signature) = BASE64_STANDARD
+        .decode(signature.trim())
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+    else {
+        bail!("invalid signature");
+    };
+
+    if key.verify_strict(data, &signature).is_err() {
+        bail!("signature verification failed");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use std::ffi::OsStr;
+use std::fs::File;
+use std::path::Path;
+
+use polars::prelude::*;
+
+use crate::prelude::*;
+
+/// Reads a table in CSV or Arrow IPC format (depending on the file
+/// extension). All columns of a CSV file are read as strings.
+pub(crate) fn read_table(path: &Path) -> DatasetResult<DataFrame> {
+    Ok(match path.extension().and_then(OsStr::to_str) {
+        Some("ipc" | "arrow") => {
+            IpcReader::new(File::open(path)?).finish()?
+        }
+        _ => CsvReadOptions::default()
+            .with_has_header(true)
+            .with_infer_schema_length(Some(0))
+            .try_into_reader_with_file_path(Some(path.into()))?
+            .finish()?,
+    })
+}
+
+/// Returns the values of a column as strings.
+pub(crate) fn strings(
+    df: &DataFrame,
+    name: &str,
+) -> DatasetResult<Vec<Option<String>>> {
+    let column = df.column(name)?.cast(&DataType::String)?;
+    Ok(column
+        .str()?
+        .iter()
+        .map(|value| value.map(ToString::to_string))
+        .collect())
+}
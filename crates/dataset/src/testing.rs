@@ -0,0 +1,7 @@
+use tempfile::TempDir;
+
+/// Creates a temporary directory, which is removed when the returned
+/// handle is dropped.
+pub(crate) fn temp_dir() -> anyhow::Result<TempDir> {
+    Ok(tempfile::Builder::new().prefix("dataset-").tempdir()?)
+}
@@ -1,7 +1,11 @@
 use std::collections::HashMap;
+use std::path::Path;
 
+use csv::ReaderBuilder;
 use serde::{Deserialize, Serialize};
 
+use crate::prelude::*;
+
 #[derive(
     Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize,
 )]
@@ -87,3 +91,30 @@ impl Default for VocabConfig {
         }
     }
 }
+
+/// An entry of the vocabulary (`vocab.csv`).
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct Subject {
+    pub(crate) uri: String,
+    pub(crate) label: String,
+    #[serde(default)]
+    pub(crate) notation: String,
+}
+
+/// Reads the vocabulary of the dataset.
+pub(crate) fn read_vocab<P: AsRef<Path>>(
+    path: P,
+) -> DatasetResult<Vec<Subject>> {
+    let path = path.as_ref();
+    if !path.is_file() {
+        bail!("missing vocabulary (run `dataset vocab update` first)");
+    }
+
+    let mut reader = ReaderBuilder::new().from_path(path)?;
+    let mut subjects = vec![];
+    for result in reader.deserialize() {
+        subjects.push(result?);
+    }
+
+    Ok(subjects)
+}
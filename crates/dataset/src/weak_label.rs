@@ -0,0 +1,317 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// Weak-supervision config.
+///
+/// Each labeling function emits candidate labels (with a confidence)
+/// for a document. The candidates of all functions are aggregated
+/// into silver labels.
+///
+/// ```toml
+/// [weak-label]
+/// aggregation = "majority"
+/// threshold = 0.5
+///
+/// [weak-label.function.quantum]
+/// kind = "regex"
+/// pattern = "(?i)quantenmechanik"
+/// labels = ["https://d-nb.info/gnd/4047989-4"]
+/// confidence = 0.8
+///
+/// [weak-label.function.physics-ddc]
+/// kind = "pica"
+/// filter = "045E.e == '530'"
+/// labels = ["https://d-nb.info/gnd/4045956-1"]
+///
+/// [weak-label.function.vocab]
+/// kind = "vocab"
+/// min-length = 5
+/// confidence = 0.4
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct WeakLabelConfig {
+    /// The method to aggregate the candidate labels.
+    #[serde(default)]
+    pub(crate) aggregation: Aggregation,
+
+    /// The minimum score of a silver label.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) threshold: Option<f64>,
+
+    #[serde(
+        rename = "function",
+        skip_serializing_if = "BTreeMap::is_empty",
+        default
+    )]
+    pub(crate) functions: BTreeMap<String, LabelingFunction>,
+}
+
+impl WeakLabelConfig {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+            && self.threshold.is_none()
+            && self.aggregation == Aggregation::default()
+    }
+}
+
+/// The method to aggregate candidate labels into silver labels.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Aggregation {
+    /// The score of a label is the confidence-weighted share of the
+    /// functions (which fired on the document) voting for the label.
+    #[default]
+    Majority,
+
+    /// The confidences are weighted by the precision of the functions
+    /// on the gold labels and combined by a noisy-or.
+    Model,
+}
+
+/// A labeling function.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub(crate) enum LabelingFunction {
+    /// Emits the labels, if the regular expression matches the text
+    /// of the document.
+    Regex {
+        pattern: String,
+        labels: Vec<String>,
+        #[serde(default = "default_confidence")]
+        confidence: f64,
+    },
+
+    /// Emits the labels, if the PICA+ record of the document matches
+    /// the filter expression.
+    Pica {
+        filter: String,
+        labels: Vec<String>,
+        #[serde(default = "default_confidence")]
+        confidence: f64,
+    },
+
+    /// Emits the labels of the vocabulary, whose label occurs in the
+    /// text of the document (case-insensitive, on word boundaries).
+    #[serde(rename_all = "kebab-case")]
+    Vocab {
+        #[serde(default = "default_min_length")]
+        min_length: usize,
+        #[serde(default = "default_confidence")]
+        confidence: f64,
+    },
+}
+
+fn default_confidence() -> f64 {
+    1.0
+}
+
+fn default_min_length() -> usize {
+    4
+}
+
+/// The candidate labels of a document (label URI → function →
+/// confidence).
+pub(crate) type Votes = BTreeMap<String, BTreeMap<String, f64>>;
+
+/// A compiled regex labeling function.
+#[derive(Debug)]
+pub(crate) struct RegexFunction<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) re: Regex,
+    pub(crate) labels: &'a [String],
+    pub(crate) confidence: f64,
+}
+
+impl<'a> RegexFunction<'a> {
+    pub(crate) fn compile(
+        config: &'a WeakLabelConfig,
+    ) -> DatasetResult<Vec<Self>> {
+        let mut result = vec![];
+        for (name, function) in config.functions.iter() {
+            if let LabelingFunction::Regex {
+                pattern,
+                labels,
+                confidence,
+            } = function
+            {
+                let re = Regex::new(pattern).map_err(|e| {
+                    DatasetError::other(format!(
+                        "invalid pattern of labeling function \
+                        '{name}': {e}"
+                    ))
+                })?;
+
+                result.push(Self {
+                    name,
+                    re,
+                    labels,
+                    confidence: *confidence,
+                });
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// An index of the labels of the vocabulary (lowercase word n-grams →
+/// label URI).
+#[derive(Debug, Default)]
+pub(crate) struct VocabIndex {
+    labels: HashMap<String, String>,
+    max_words: usize,
+}
+
+impl VocabIndex {
+    /// The maximum number of words of a label.
+    const MAX_WORDS: usize = 5;
+
+    pub(crate) fn new<'a, I>(labels: I, min_length: usize) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let mut index = Self::default();
+        for (uri, label) in labels {
+            let words: Vec<String> = label
+                .split_whitespace()
+                .map(str::to_lowercase)
+                .collect();
+            if words.is_empty()
+                || words.len() > Self::MAX_WORDS
+                || label.chars().count() < min_length
+            {
+                continue;
+            }
+
+            index.max_words = index.max_words.max(words.len());
+            index.labels.insert(words.join(" "), uri.to_string());
+        }
+
+        index
+    }
+
+    /// Returns the URIs of the labels, which occur in the text.
+    pub(crate) fn matches(&self, text: &str) -> HashSet<&str> {
+        let words: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric() && c != '-')
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+
+        let mut result = HashSet::new();
+        for n in 1..=self.max_words {
+            for window in words.windows(n) {
+                if let Some(uri) = self.labels.get(&window.join(" ")) {
+                    result.insert(uri.as_str());
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// Aggregates the candidate labels of a document.
+///
+/// The `weights` are the (estimated) precisions of the functions,
+/// which are used by the model-based aggregation. Functions without a
+/// weight are weighted by 1.0.
+pub(crate) fn aggregate(
+    votes: &Votes,
+    aggregation: Aggregation,
+    weights: &HashMap<String, f64>,
+) -> Vec<(String, f64)> {
+    let mut fired: HashMap<&str, f64> = HashMap::new();
+    for functions in votes.values() {
+        for (name, confidence) in functions.iter() {
+            let entry = fired.entry(name.as_str()).or_default();
+            *entry = entry.max(*confidence);
+        }
+    }
+
+    let total: f64 = fired.values().sum();
+    let mut result: Vec<(String, f64)> = votes
+        .iter()
+        .map(|(label, functions)| {
+            let score = match aggregation {
+                Aggregation::Majority if total > 0.0 => {
+                    functions.values().sum::<f64>() / total
+                }
+                Aggregation::Majority => 0.0,
+                Aggregation::Model => {
+                    1.0 - functions
+                        .iter()
+                        .map(|(name, confidence)| {
+                            let weight = weights
+                                .get(name)
+                                .copied()
+                                .unwrap_or(1.0);
+                            1.0 - weight * confidence
+                        })
+                        .product::<f64>()
+                }
+            };
+
+            (label.clone(), score)
+        })
+        .collect();
+
+    result.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregate_votes() {
+        let mut votes = Votes::new();
+        votes.entry("a".into()).or_default().insert("f".into(), 1.0);
+        votes.entry("a".into()).or_default().insert("g".into(), 0.5);
+        votes.entry("b".into()).or_default().insert("g".into(), 0.5);
+
+        let result =
+            aggregate(&votes, Aggregation::Majority, &[].into());
+        assert_eq!(
+            result,
+            vec![("a".into(), 1.0), ("b".into(), 1.0 / 3.0)]
+        );
+
+        let weights = [("f".to_string(), 0.5)].into();
+        let result = aggregate(&votes, Aggregation::Model, &weights);
+        assert_eq!(result, vec![("a".into(), 0.75), ("b".into(), 0.5)]);
+    }
+
+    #[test]
+    fn vocab_matches() {
+        let index = VocabIndex::new(
+            [
+                ("u1", "Quantum Mechanics"),
+                ("u2", "Physik"),
+                ("u3", "AI"),
+            ],
+            4,
+        );
+
+        let matches = index.matches(
+            "Eine Einführung in die PHYSIK und quantum mechanics; AI.",
+        );
+        assert_eq!(matches, HashSet::from(["u1", "u2"]));
+    }
+}
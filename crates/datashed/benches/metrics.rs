@@ -0,0 +1,145 @@
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkId, Criterion,
+    Throughput,
+};
+use tempfile::TempDir;
+
+// The modules of the document metrics and their (transitive)
+// dependencies.
+#[path = "../src/campaign.rs"]
+mod campaign;
+#[path = "../src/collate.rs"]
+mod collate;
+#[path = "../src/config.rs"]
+mod config;
+#[path = "../src/datashed.rs"]
+mod datashed;
+#[path = "../src/discovery.rs"]
+mod discovery;
+#[path = "../src/document.rs"]
+mod document;
+#[path = "../src/error.rs"]
+mod error;
+#[path = "../src/freshness.rs"]
+mod freshness;
+#[path = "../src/http.rs"]
+mod http;
+#[path = "../src/lfreq.rs"]
+mod lfreq;
+#[path = "../src/lock.rs"]
+mod lock;
+#[path = "../src/metrics.rs"]
+mod metrics;
+#[path = "../src/prelude.rs"]
+mod prelude;
+#[path = "../src/preprocess.rs"]
+mod preprocess;
+#[path = "../src/progress.rs"]
+mod progress;
+#[path = "../src/projection.rs"]
+mod projection;
+#[path = "../src/ratings.rs"]
+mod ratings;
+#[path = "../src/redact.rs"]
+mod redact;
+#[path = "../src/schema.rs"]
+mod schema;
+#[path = "../src/script.rs"]
+mod script;
+#[path = "../src/sql.rs"]
+mod sql;
+#[path = "../src/synth.rs"]
+mod synth;
+#[cfg(test)]
+#[path = "../src/testing.rs"]
+mod testing;
+#[path = "../src/throttle.rs"]
+mod throttle;
+#[path = "../src/utils.rs"]
+mod utils;
+
+use document::Document;
+use synth::{words, Generator};
+
+/// Writes synthetic documents of different sizes into a temporary
+/// directory and returns the directory (which is removed when dropped)
+/// and the paths of the documents.
+fn corpus() -> (TempDir, Vec<(usize, PathBuf)>) {
+    let dir = tempfile::Builder::new()
+        .prefix("datashed-bench-")
+        .tempdir()
+        .unwrap();
+
+    let paths = [100, 1_000, 10_000]
+        .into_iter()
+        .map(|n| {
+            let mut generator = Generator::new(n as u64, n, n);
+            let doc = generator.document(words("ger").unwrap());
+            let path = dir.path().join(format!("{n}.txt"));
+            fs::write(&path, doc).unwrap();
+            (n, path)
+        })
+        .collect();
+
+    (dir, paths)
+}
+
+fn metrics(c: &mut Criterion) {
+    let (_dir, corpus) = corpus();
+    let mut group = c.benchmark_group("metrics");
+
+    for (n, path) in corpus.iter() {
+        let doc = Document::from_path(path).unwrap();
+        group.throughput(Throughput::Bytes(doc.size()));
+
+        group.bench_with_input(
+            BenchmarkId::new("hash", n),
+            &doc,
+            |b, doc| b.iter(|| black_box(doc.hash())),
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("lang", n),
+            path,
+            |b, path| {
+                b.iter_batched(
+                    || Document::from_path(path).unwrap(),
+                    |mut doc| black_box(doc.lang()),
+                    criterion::BatchSize::SmallInput,
+                )
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("lfreq", n),
+            path,
+            |b, path| {
+                let mut doc = Document::from_path(path).unwrap();
+                let _ = doc.lang();
+                b.iter(|| black_box(doc.lfreq()))
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("alpha", n),
+            &doc,
+            |b, doc| b.iter(|| black_box(doc.alpha())),
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("ttr", n),
+            &doc,
+            |b, doc| b.iter(|| black_box(doc.type_token_ratio())),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, metrics);
+criterion_main!(benches);
@@ -0,0 +1,8 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/datashed.proto");
+        tonic_build::compile_protos("proto/datashed.proto")
+            .expect("valid protocol buffers");
+    }
+}
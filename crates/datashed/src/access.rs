@@ -0,0 +1,346 @@
+use std::fs::File;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::prelude::*;
+use csv::Writer;
+use hashbrown::{HashMap, HashSet};
+use polars::prelude::*;
+use polars::sql::SQLContext;
+
+use crate::config::{AccessRule, Config, User};
+use crate::datashed::Datashed;
+use crate::error::DatashedResult;
+use crate::sql::select_where;
+
+/// The access level of a single document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Access {
+    Public,
+    Roles(HashSet<String>),
+    Denied,
+}
+
+/// An access map, which is shared by the services of a server and
+/// replaced after each re-index. Readers take a [snapshot] of the map,
+/// so that the lock isn't held while the map is in use.
+pub(crate) type SharedAccessMap = Arc<RwLock<Arc<AccessMap>>>;
+
+/// Returns the current access map of a server.
+pub(crate) fn snapshot(acl: &SharedAccessMap) -> Arc<AccessMap> {
+    acl.read().unwrap().clone()
+}
+
+/// A CSV writer (e.g. of the ratings or the audit log), which is shared
+/// by the services of a server.
+pub(crate) type SharedWriter = Arc<Mutex<Writer<File>>>;
+
+/// The access levels of all documents of a datashed.
+///
+/// The levels are computed once from the access rules of the config
+/// and the index. If the config doesn't contain any access rule, all
+/// documents are public.
+#[derive(Debug, Default)]
+pub(crate) struct AccessMap {
+    inner: Option<HashMap<String, Access>>,
+    restricted: HashSet<String>,
+    denied: Mutex<HashSet<String>>,
+}
+
+impl AccessMap {
+    pub(crate) fn from_index(
+        config: &Config,
+        index: DataFrame,
+    ) -> DatashedResult<Self> {
+        if config.access.is_empty() {
+            return Ok(Self::default());
+        }
+
+        let mut inner = HashMap::new();
+        for rule in config.access.iter() {
+            let access = if rule.public {
+                Access::Public
+            } else {
+                Access::Roles(rule.roles.iter().cloned().collect())
+            };
+
+            let df = matching(rule, index.clone())?;
+            for path in df.column("path")?.str()?.into_iter().flatten()
+            {
+                inner.entry(path.to_string()).or_insert(access.clone());
+            }
+        }
+
+        Ok(Self {
+            inner: Some(inner),
+            ..Default::default()
+        })
+    }
+
+    /// Returns the access levels of the documents of the datashed.
+    /// Unless `force` is set, restricted documents (see [restricted])
+    /// are denied.
+    pub(crate) fn for_datashed(
+        datashed: &Datashed,
+        config: &Config,
+        force: bool,
+    ) -> DatashedResult<Self> {
+        let mut acl = if config.access.is_empty() {
+            Self::default()
+        } else {
+            Self::from_index(config, datashed.index()?)?
+        };
+
+        if !force && config.license.is_some() {
+            let restricted = restricted(config, &datashed.index()?)?;
+            acl = acl.with_restricted(restricted);
+        }
+
+        Ok(acl)
+    }
+
+    /// Denies the access to the given (restricted) documents,
+    /// regardless of the access rules.
+    pub(crate) fn with_restricted(
+        mut self,
+        restricted: HashSet<String>,
+    ) -> Self {
+        self.restricted = restricted;
+        self
+    }
+
+    /// Denies the access to a document (e.g. an uploaded document,
+    /// which isn't indexed yet).
+    pub(crate) fn deny<S: Into<String>>(&self, path: S) {
+        self.denied.lock().unwrap().insert(path.into());
+    }
+
+    /// Revokes the denial of a document (see [AccessMap::deny]).
+    pub(crate) fn allow(&self, path: &str) {
+        self.denied.lock().unwrap().remove(path);
+    }
+
+    /// Returns the access level of a document (the path relative to
+    /// the root directory of the datashed).
+    pub(crate) fn get(&self, path: &str) -> Access {
+        if self.restricted.contains(path)
+            || self.denied.lock().unwrap().contains(path)
+        {
+            return Access::Denied;
+        }
+
+        match self.inner {
+            None => Access::Public,
+            Some(ref inner) => {
+                inner.get(path).cloned().unwrap_or(Access::Denied)
+            }
+        }
+    }
+
+    /// Returns `true` if the document is accessible by the given
+    /// (authenticated) user.
+    pub(crate) fn is_allowed(
+        &self,
+        path: &str,
+        user: Option<&User>,
+    ) -> bool {
+        match self.get(path) {
+            Access::Public => true,
+            Access::Denied => false,
+            Access::Roles(roles) => user
+                .map(|user| {
+                    user.roles.iter().any(|r| roles.contains(r))
+                })
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// Returns the name of the authenticated user, if the value of an
+/// authorization header (or gRPC metadata) contains valid basic
+/// authentication credentials.
+pub(crate) fn authenticate(
+    authorization: &str,
+    config: &Config,
+) -> Option<String> {
+    let credentials = authorization.strip_prefix("Basic ")?;
+    let credentials =
+        BASE64_STANDARD.decode(credentials.trim()).ok()?;
+    let credentials = String::from_utf8(credentials).ok()?;
+    let (username, secret) = credentials.split_once(':')?;
+
+    match config.users.get(username) {
+        Some(user) if user.secret == secret => Some(username.into()),
+        _ => None,
+    }
+}
+
+/// Appends an access record (timestamp, username, path and HTTP status
+/// code) to the audit log.
+pub(crate) fn audit(
+    wtr: &SharedWriter,
+    username: Option<&str>,
+    path: &str,
+    status: &str,
+) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis()
+        .to_string();
+
+    let mut wtr = wtr.lock().unwrap();
+    let _ = wtr.write_record([
+        timestamp.as_str(),
+        username.unwrap_or_default(),
+        path,
+        status,
+    ]);
+    let _ = wtr.flush();
+}
+
+/// Returns the paths of all documents, whose access/license code (the
+/// `license` column of the index) is restricted by the config.
+///
+/// If restricted codes are configured, documents without a code and
+/// all documents of an index without a `license` column (e.g. built
+/// without the PICA+ dump) are restricted as well.
+pub(crate) fn restricted(
+    config: &Config,
+    index: &DataFrame,
+) -> DatashedResult<HashSet<String>> {
+    let Some(ref license) = config.license else {
+        return Ok(HashSet::new());
+    };
+
+    if license.restricted.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let df = if index.column("license").is_err() {
+        index.clone()
+    } else {
+        let codes =
+            Series::new("codes".into(), license.restricted.clone());
+        index
+            .clone()
+            .lazy()
+            .filter(col("license").is_null().or(
+                col("license").cast(DataType::String).is_in(lit(codes)),
+            ))
+            .collect()?
+    };
+
+    Ok(df
+        .column("path")?
+        .str()?
+        .into_iter()
+        .flatten()
+        .map(String::from)
+        .collect())
+}
+
+/// Returns all rows of the index matching the rule.
+fn matching(
+    rule: &AccessRule,
+    index: DataFrame,
+) -> DatashedResult<DataFrame> {
+    let mut df = index.lazy();
+    if let Some(ref kind) = rule.kind {
+        df = df.filter(
+            col("kind")
+                .cast(DataType::String)
+                .eq(lit(kind.to_string())),
+        );
+    }
+
+    if let Some(ref predicate) = rule.predicate {
+        let mut ctx = SQLContext::new();
+        ctx.register("df", df);
+        df = ctx.execute(&select_where(predicate))?;
+    }
+
+    Ok(df.collect()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn access_map() -> TestResult {
+        let config: Config = toml::from_str(
+            r#"
+            [metadata]
+            name = "foo"
+            version = "0.1.0"
+
+            [[access]]
+            kind = "blurb"
+            public = true
+
+            [[access]]
+            predicate = "kind = 'book'"
+            roles = ["internal"]
+            "#,
+        )?;
+
+        let index = df!(
+            "path" => ["a.txt", "b.txt", "c.txt"],
+            "kind" => ["blurb", "book", "toc"],
+        )?;
+
+        let acl = AccessMap::from_index(&config, index)?;
+        let user = User {
+            secret: "secret".into(),
+            roles: vec!["internal".into()],
+        };
+
+        assert!(acl.is_allowed("a.txt", None));
+        assert!(!acl.is_allowed("b.txt", None));
+        assert!(acl.is_allowed("b.txt", Some(&user)));
+        assert!(!acl.is_allowed("c.txt", Some(&user)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn restricted_documents() -> TestResult {
+        let config: Config = toml::from_str(
+            r#"
+            [metadata]
+            name = "foo"
+            version = "0.1.0"
+
+            [license]
+            paths = ["047I.u"]
+            restricted = ["embargo"]
+            "#,
+        )?;
+
+        let index = df!(
+            "path" => ["a.txt", "b.txt", "c.txt"],
+            "license" => [Some("embargo"), Some("cc-by"), None],
+        )?;
+
+        let restricted = restricted(&config, &index)?;
+        assert_eq!(
+            restricted,
+            HashSet::from(["a.txt".to_string(), "c.txt".to_string()])
+        );
+
+        let acl = AccessMap::default().with_restricted(restricted);
+        assert!(!acl.is_allowed("a.txt", None));
+        assert!(acl.is_allowed("b.txt", None));
+
+        acl.deny("b.txt");
+        assert!(!acl.is_allowed("b.txt", None));
+
+        let index = df!("path" => ["a.txt", "b.txt"])?;
+        assert_eq!(restricted(&config, &index)?.len(), 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::atomic::AtomicFile;
+use crate::datashed::Datashed;
+use crate::error::DatashedResult;
+
+/// A file, which was derived from other files by a command.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Artifact {
+    /// The name of the command (e.g. `lm train`).
+    pub(crate) command: String,
+
+    /// The command line arguments (without the program name).
+    pub(crate) args: Vec<String>,
+
+    /// The time of the creation (seconds since the UNIX epoch).
+    pub(crate) created: u64,
+
+    /// The SHA256 digests of the input files by path.
+    #[serde(default)]
+    pub(crate) inputs: BTreeMap<String, String>,
+}
+
+/// The registry of the derived files of the datashed
+/// (`artifacts.toml`).
+///
+/// The paths of files within the root directory are stored relative
+/// to the root directory, all other paths are absolute.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Artifacts {
+    #[serde(rename = "artifact", default)]
+    pub(crate) artifacts: BTreeMap<String, Artifact>,
+}
+
+/// The reason why an artifact is outdated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Staleness {
+    /// The artifact itself doesn't exist anymore.
+    MissingOutput,
+
+    /// An input file doesn't exist anymore.
+    MissingInput(String),
+
+    /// The content of an input file changed.
+    ChangedInput(String),
+}
+
+impl std::fmt::Display for Staleness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingOutput => write!(f, "missing output"),
+            Self::MissingInput(path) => {
+                write!(f, "missing input {path}")
+            }
+            Self::ChangedInput(path) => {
+                write!(f, "changed input {path}")
+            }
+        }
+    }
+}
+
+/// Returns the SHA256 digest of the file at `path`.
+fn digest<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+
+    Ok(hasher.finalize().iter().fold(String::new(), |mut out, b| {
+        let _ = write!(out, "{b:02x}");
+        out
+    }))
+}
+
+/// Returns the key of the file at `path`.
+fn key(base_dir: &Path, path: &Path) -> String {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.into());
+    path.strip_prefix(base_dir)
+        .unwrap_or(&path)
+        .to_string_lossy()
+        .to_string()
+}
+
+impl Artifacts {
+    /// Loads the registry. A missing file results in an empty
+    /// registry.
+    pub(crate) fn from_path<P: AsRef<Path>>(
+        path: P,
+    ) -> DatashedResult<Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Saves the registry.
+    pub(crate) fn save<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> DatashedResult<()> {
+        let content = toml::to_string(self).expect("valid toml");
+        let mut out = AtomicFile::create(path.as_ref())?;
+        out.write_all(content.as_bytes())?;
+        out.commit()?;
+        Ok(())
+    }
+
+    /// Returns the reasons why the artifact `key` is outdated. An
+    /// empty list means, that the artifact is up to date.
+    pub(crate) fn staleness(
+        &self,
+        base_dir: &Path,
+        key: &str,
+    ) -> Vec<Staleness> {
+        let Some(artifact) = self.artifacts.get(key) else {
+            return vec![];
+        };
+
+        let mut result = vec![];
+        if !base_dir.join(key).is_file() {
+            result.push(Staleness::MissingOutput);
+        }
+
+        for (input, hash) in artifact.inputs.iter() {
+            match digest(base_dir.join(input)) {
+                Ok(ref current) if current == hash => {}
+                Ok(_) => {
+                    result.push(Staleness::ChangedInput(input.clone()))
+                }
+                Err(_) => {
+                    result.push(Staleness::MissingInput(input.clone()))
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// Registers the `output` of the running command, which was derived
+/// from the given `inputs`, in the artifact registry of the datashed.
+/// Inputs, which don't exist, are ignored.
+pub(crate) fn register<P: AsRef<Path>>(
+    datashed: &Datashed,
+    command: &str,
+    output: P,
+    inputs: &[PathBuf],
+) -> DatashedResult<()> {
+    let base_dir = datashed.base_dir();
+    let path = base_dir.join(Datashed::ARTIFACTS);
+    let mut registry = Artifacts::from_path(&path)?;
+
+    let mut hashes = BTreeMap::new();
+    for input in inputs.iter().filter(|input| input.is_file()) {
+        hashes.insert(key(base_dir, input), digest(input)?);
+    }
+
+    registry.artifacts.insert(
+        key(base_dir, output.as_ref()),
+        Artifact {
+            command: command.into(),
+            args: env::args().skip(1).collect(),
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            inputs: hashes,
+        },
+    );
+
+    registry.save(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_datashed;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn artifact_staleness() -> TestResult {
+        let (_dir, datashed) = temp_datashed()?;
+        let base_dir = datashed.base_dir();
+        fs::write(base_dir.join("input.txt"), "foo")?;
+        fs::write(base_dir.join("output.txt"), "bar")?;
+
+        register(
+            &datashed,
+            "test",
+            base_dir.join("output.txt"),
+            &[base_dir.join("input.txt"), base_dir.join("missing.txt")],
+        )?;
+
+        let registry =
+            Artifacts::from_path(base_dir.join(Datashed::ARTIFACTS))?;
+        assert_eq!(registry.artifacts["output.txt"].inputs.len(), 1);
+        assert!(registry.staleness(base_dir, "output.txt").is_empty());
+
+        fs::write(base_dir.join("input.txt"), "baz")?;
+        fs::remove_file(base_dir.join("output.txt"))?;
+        assert_eq!(
+            registry.staleness(base_dir, "output.txt"),
+            vec![
+                Staleness::MissingOutput,
+                Staleness::ChangedInput("input.txt".into())
+            ]
+        );
+
+        Ok(())
+    }
+}
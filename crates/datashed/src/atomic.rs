@@ -0,0 +1,99 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+
+/// A file, which is written into a temporary file in the same
+/// directory and atomically renamed to its destination on
+/// [`AtomicFile::commit`].
+///
+/// If the file is dropped without being committed (e.g. due to an
+/// error), the temporary file is removed and the destination is left
+/// untouched.
+#[derive(Debug)]
+pub(crate) struct AtomicFile {
+    path: PathBuf,
+    temp_path: PathBuf,
+    file: Option<File>,
+}
+
+impl AtomicFile {
+    /// Creates a new temporary file for the given destination.
+    pub(crate) fn create<P: Into<PathBuf>>(
+        path: P,
+    ) -> io::Result<Self> {
+        let path = path.into();
+        let mut name = path.file_name().unwrap_or_default().to_owned();
+        name.push(format!(".tmp-{}", process::id()));
+
+        let temp_path =
+            path.with_file_name(format!(".{}", name.to_string_lossy()));
+
+        Ok(Self {
+            file: Some(File::create(&temp_path)?),
+            temp_path,
+            path,
+        })
+    }
+
+    /// Flushes the temporary file to disk and renames it to its
+    /// destination.
+    pub(crate) fn commit(mut self) -> io::Result<()> {
+        if let Some(file) = self.file.take() {
+            file.sync_all()?;
+        }
+
+        fs::rename(&self.temp_path, &self.path)
+    }
+
+    #[inline]
+    fn file(&mut self) -> &mut File {
+        self.file.as_mut().expect("uncommitted file")
+    }
+}
+
+impl Write for AtomicFile {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file().write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.file().flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn atomic_file_commit() -> TestResult {
+        let dir = temp_dir()?;
+        let path = dir.path().join("atomic.txt");
+
+        fs::write(&path, "old")?;
+        let mut file = AtomicFile::create(&path)?;
+        file.write_all(b"new")?;
+        drop(file);
+        assert_eq!(fs::read_to_string(&path)?, "old");
+
+        let mut file = AtomicFile::create(&path)?;
+        file.write_all(b"new")?;
+        file.commit()?;
+        assert_eq!(fs::read_to_string(&path)?, "new");
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::datashed::Datashed;
+use crate::error::{DatashedError, DatashedResult};
+
+/// An entry of the audit log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct AuditEntry {
+    /// The time of the operation (milliseconds since the UNIX epoch).
+    pub(crate) timestamp: u64,
+
+    /// The user, who ran the command.
+    pub(crate) user: String,
+
+    /// The name of the command (e.g. `clean`).
+    pub(crate) command: String,
+
+    /// The command line arguments (without the program name).
+    pub(crate) args: Vec<String>,
+
+    /// The number of affected documents or index rows.
+    pub(crate) affected: u64,
+}
+
+impl AuditEntry {
+    /// Creates an entry for the running command.
+    pub(crate) fn new(command: &str, affected: usize) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64,
+            user: env::var("DATASHED_USERNAME")
+                .or_else(|_| env::var("USER"))
+                .unwrap_or_default(),
+            command: command.into(),
+            args: env::args().skip(1).collect(),
+            affected: affected as u64,
+        }
+    }
+}
+
+/// Appends an entry for the running command to the audit log of the
+/// datashed (`audit.jsonl`).
+///
+/// The audit log is append-only; each entry is written as a single
+/// JSON line.
+pub(crate) fn record(
+    datashed: &Datashed,
+    command: &str,
+    affected: usize,
+) -> DatashedResult<()> {
+    append(datashed, &AuditEntry::new(command, affected))
+}
+
+/// Appends an entry to the audit log of the datashed. In contrast to
+/// [record], the entry may describe an operation of another user
+/// (e.g. an upload via `datashed serve`).
+pub(crate) fn append(
+    datashed: &Datashed,
+    entry: &AuditEntry,
+) -> DatashedResult<()> {
+    let mut line =
+        serde_json::to_string(entry).map_err(DatashedError::other)?;
+    line.push('\n');
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(datashed.base_dir().join(Datashed::AUDIT_LOG))?
+        .write_all(line.as_bytes())?;
+
+    Ok(())
+}
+
+/// Reads the audit log. A missing file is an empty log.
+pub(crate) fn read_log<P: AsRef<Path>>(
+    path: P,
+) -> DatashedResult<Vec<AuditEntry>> {
+    let path = path.as_ref();
+    if !path.is_file() {
+        return Ok(vec![]);
+    }
+
+    BufReader::new(File::open(path)?)
+        .lines()
+        .filter(|line| {
+            !line.as_ref().is_ok_and(|l| l.trim().is_empty())
+        })
+        .map(|line| {
+            serde_json::from_str(&line?).map_err(DatashedError::other)
+        })
+        .collect()
+}
+
+/// Returns the entries of the audit log as a data frame with the
+/// columns `timestamp` (datetime), `user`, `command`, `args` (list)
+/// and `affected`.
+pub(crate) fn to_df(
+    entries: &[AuditEntry],
+) -> DatashedResult<DataFrame> {
+    let args: Vec<Series> = entries
+        .iter()
+        .map(|e| Series::from_iter(e.args.iter().map(String::as_str)))
+        .collect();
+
+    Ok(DataFrame::new(vec![
+        Column::new(
+            "timestamp".into(),
+            entries
+                .iter()
+                .map(|e| e.timestamp as i64)
+                .collect::<Vec<_>>(),
+        )
+        .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))?,
+        Column::new(
+            "user".into(),
+            entries.iter().map(|e| e.user.as_str()).collect::<Vec<_>>(),
+        ),
+        Column::new(
+            "command".into(),
+            entries
+                .iter()
+                .map(|e| e.command.as_str())
+                .collect::<Vec<_>>(),
+        ),
+        Column::new("args".into(), args)
+            .cast(&DataType::List(Box::new(DataType::String)))?,
+        Column::new(
+            "affected".into(),
+            entries.iter().map(|e| e.affected).collect::<Vec<_>>(),
+        ),
+    ])?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn audit_log() -> TestResult {
+        let dir = temp_dir()?;
+        let path = dir.path().join("audit.jsonl");
+        let mut out = File::create(&path)?;
+        for (command, affected) in [("clean", 3), ("apply", 1)] {
+            let entry = AuditEntry {
+                user: "x".into(),
+                args: vec![command.into(), "--force".into()],
+                ..AuditEntry::new(command, affected)
+            };
+
+            serde_json::to_writer(&mut out, &entry)?;
+            writeln!(out)?;
+        }
+
+        let entries = read_log(&path)?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].command, "clean");
+        assert_eq!(entries[1].affected, 1);
+
+        let df = to_df(&entries)?;
+        assert_eq!(df.height(), 2);
+        assert_eq!(
+            df.column("timestamp")?.dtype(),
+            &DataType::Datetime(TimeUnit::Milliseconds, None)
+        );
+
+        Ok(())
+    }
+}
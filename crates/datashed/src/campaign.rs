@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+
+use hashbrown::{HashMap, HashSet};
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use serde::{Deserialize, Serialize};
+
+use crate::datashed::Datashed;
+use crate::error::{bail, DatashedResult};
+use crate::ratings::read_ratings;
+use crate::sql::select_where;
+
+/// A rating campaign.
+///
+/// A campaign covers all documents of the index, which satisfy the
+/// `predicate` (an SQL expression over the index). A document counts
+/// as rated, if it has been rated by at least `raters` users (default:
+/// 1). The campaign is finished, if `target` documents (default: all
+/// documents of the campaign) are rated. The ratings are distributed
+/// evenly among the `users` of the campaign. The ratings are given on
+/// the rating `scale` (the name of a scale defined in `[scales]`) or
+/// the default scale.
+///
+/// ```toml
+/// [campaigns.ocr-2024]
+/// predicate = "kind = 'book'"
+/// target = 500
+/// raters = 2
+/// users = ["alice", "bob"]
+/// scale = "likert"
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct Campaign {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) predicate: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) target: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) raters: Option<usize>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) users: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) scale: Option<String>,
+}
+
+/// The progress of a user.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub(crate) struct UserProgress {
+    /// The number of ratings of the user.
+    pub(crate) ratings: usize,
+
+    /// The number of ratings expected from the user (an even share of
+    /// the ratings of the campaign), if the user is a member of the
+    /// campaign.
+    pub(crate) expected: Option<usize>,
+}
+
+impl UserProgress {
+    /// Returns the number of ratings the user is behind the expected
+    /// share.
+    pub(crate) fn behind(&self) -> usize {
+        self.expected
+            .unwrap_or_default()
+            .saturating_sub(self.ratings)
+    }
+}
+
+/// The coverage of a document kind.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub(crate) struct KindCoverage {
+    /// The number of documents of the kind.
+    pub(crate) documents: usize,
+
+    /// The number of rated documents of the kind.
+    pub(crate) rated: usize,
+}
+
+/// The progress of a campaign.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct Progress {
+    pub(crate) campaign: String,
+
+    /// The number of documents of the campaign.
+    pub(crate) documents: usize,
+
+    /// The number of documents to be rated.
+    pub(crate) target: usize,
+
+    /// The number of rated documents.
+    pub(crate) rated: usize,
+
+    /// The number of documents, which remain to be rated.
+    pub(crate) remaining: usize,
+
+    /// The progress per user.
+    pub(crate) users: BTreeMap<String, UserProgress>,
+
+    /// The coverage per document kind.
+    pub(crate) kinds: BTreeMap<String, KindCoverage>,
+}
+
+impl Campaign {
+    /// Computes the progress of the campaign.
+    ///
+    /// The `ratings` consist of the columns `path`, `hash`, `rating`
+    /// and `username` (see `read_ratings`). Only the ratings of the
+    /// current document versions are taken into account and each user
+    /// counts at most once per document.
+    pub(crate) fn progress(
+        &self,
+        name: &str,
+        index: DataFrame,
+        ratings: &DataFrame,
+    ) -> DatashedResult<Progress> {
+        let index = match self.predicate {
+            Some(ref predicate) => {
+                let mut ctx = SQLContext::new();
+                ctx.register("df", index.lazy());
+                ctx.execute(&select_where(predicate))?.collect()?
+            }
+            None => index,
+        };
+
+        let kind = index.column("kind")?.cast(&DataType::String)?;
+        let kind = kind.str()?;
+        let path = index.column("path")?.str()?;
+        let hash = index.column("hash")?.str()?;
+
+        let mut docs: HashMap<(&str, &str), &str> = HashMap::new();
+        for idx in 0..index.height() {
+            if let (Some(path), Some(hash)) =
+                (path.get(idx), hash.get(idx))
+            {
+                let hash = hash.get(..8).unwrap_or(hash);
+                docs.insert(
+                    (path, hash),
+                    kind.get(idx).unwrap_or_default(),
+                );
+            }
+        }
+
+        let r_path = ratings.column("path")?.str()?;
+        let r_hash = ratings.column("hash")?.str()?;
+        let r_user = ratings.column("username")?.str()?;
+
+        let mut seen: HashSet<(&str, &str, &str)> = HashSet::new();
+        let mut raters: HashMap<(&str, &str), usize> = HashMap::new();
+        let mut users: BTreeMap<String, UserProgress> = BTreeMap::new();
+
+        for idx in 0..ratings.height() {
+            let (Some(path), Some(hash)) =
+                (r_path.get(idx), r_hash.get(idx))
+            else {
+                continue;
+            };
+
+            let hash = hash.get(..8).unwrap_or(hash);
+            let user = r_user.get(idx).unwrap_or_default();
+            if !docs.contains_key(&(path, hash))
+                || !seen.insert((path, hash, user))
+            {
+                continue;
+            }
+
+            *raters.entry((path, hash)).or_default() += 1;
+            users.entry(user.into()).or_default().ratings += 1;
+        }
+
+        let min_raters = self.raters.unwrap_or(1).max(1);
+        let target = self.target.unwrap_or(docs.len());
+
+        let mut kinds: BTreeMap<String, KindCoverage> = BTreeMap::new();
+        let mut rated = 0;
+        for (key, kind) in docs.iter() {
+            let coverage = kinds.entry(kind.to_string()).or_default();
+            coverage.documents += 1;
+
+            if raters.get(key).copied().unwrap_or_default()
+                >= min_raters
+            {
+                coverage.rated += 1;
+                rated += 1;
+            }
+        }
+
+        if !self.users.is_empty() {
+            let share =
+                (target * min_raters).div_ceil(self.users.len());
+            for user in self.users.iter() {
+                users.entry(user.clone()).or_default().expected =
+                    Some(share);
+            }
+        }
+
+        Ok(Progress {
+            campaign: name.into(),
+            documents: docs.len(),
+            target,
+            rated,
+            remaining: target.saturating_sub(rated),
+            users,
+            kinds,
+        })
+    }
+}
+
+/// Returns the progress of the campaign `name` of the datashed.
+///
+/// The ratings of the datashed (`ratings.csv`) and the ratings
+/// received by `datashed serve` (in the temp directory) are taken
+/// into account.
+pub(crate) fn campaign_progress(
+    datashed: &Datashed,
+    name: &str,
+) -> DatashedResult<Progress> {
+    let config = datashed.config()?;
+    let Some(campaign) = config.campaigns.get(name) else {
+        bail!("unknown campaign '{name}'");
+    };
+
+    let mut ratings = DataFrame::new(
+        ["path", "hash", "rating", "username"]
+            .into_iter()
+            .map(|name| {
+                Column::new_empty(name.into(), &DataType::String)
+            })
+            .collect(),
+    )?;
+
+    for path in [
+        datashed.base_dir().join(Datashed::RATINGS),
+        datashed.temp_dir().join(Datashed::RATINGS),
+    ] {
+        if path.is_file() {
+            ratings.vstack_mut(&read_ratings(path)?)?;
+        }
+    }
+
+    campaign.progress(name, datashed.index()?, &ratings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn campaign_coverage() -> TestResult {
+        let index = df!(
+            "path" => ["a.txt", "b.txt", "c.txt", "d.txt"],
+            "hash" => ["01234567", "89abcdef", "00000000", "11111111"],
+            "kind" => ["book", "book", "toc", "blurb"],
+        )?;
+
+        let ratings = df!(
+            "path" => ["a.txt", "a.txt", "a.txt", "b.txt", "c.txt", "d.txt"],
+            "hash" => ["0123456789", "01234567", "01234567", "ff", "00000000", "11111111"],
+            "rating" => ["C", "C", "P", "I", "C", "C"],
+            "username" => ["x", "x", "y", "x", "y", "x"],
+        )?;
+
+        let campaign = Campaign {
+            predicate: Some("kind != 'blurb'".into()),
+            target: Some(2),
+            users: vec!["x".into(), "y".into(), "z".into()],
+            ..Default::default()
+        };
+
+        let progress = campaign.progress("test", index, &ratings)?;
+        assert_eq!(progress.documents, 3);
+        assert_eq!(progress.rated, 2);
+        assert_eq!(progress.remaining, 0);
+        assert_eq!(progress.users["x"].ratings, 1);
+        assert_eq!(progress.users["y"].ratings, 2);
+        assert_eq!(progress.users["z"].behind(), 1);
+        assert_eq!(progress.kinds["book"].rated, 1);
+        assert_eq!(progress.kinds["toc"].documents, 1);
+        Ok(())
+    }
+}
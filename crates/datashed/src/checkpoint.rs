@@ -0,0 +1,287 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::atomic::AtomicFile;
+use crate::error::{bail, DatashedError, DatashedResult};
+
+/// A partial result of a long-running command, which can be stored
+/// in a [Checkpoint].
+pub(crate) trait Partial: Sized {
+    /// The file extension of a stored batch.
+    const EXTENSION: &'static str;
+
+    /// Writes a batch of partial results.
+    fn write_batch<W: Write>(
+        items: Vec<Self>,
+        out: W,
+    ) -> DatashedResult<()>;
+
+    /// Reads a batch of partial results.
+    fn read_batch(path: &Path) -> DatashedResult<Vec<Self>>;
+}
+
+/// Writes the items in JSON Lines format.
+pub(crate) fn write_json_lines<T: Serialize, W: Write>(
+    items: &[T],
+    out: W,
+) -> DatashedResult<()> {
+    let mut out = BufWriter::new(out);
+    for item in items.iter() {
+        serde_json::to_writer(&mut out, item)
+            .map_err(DatashedError::other)?;
+        writeln!(out)?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Reads items in JSON Lines format.
+pub(crate) fn read_json_lines<T: DeserializeOwned>(
+    path: &Path,
+) -> DatashedResult<Vec<T>> {
+    BufReader::new(File::open(path)?)
+        .lines()
+        .map(|line| {
+            serde_json::from_str(&line?).map_err(DatashedError::other)
+        })
+        .collect()
+}
+
+/// The options of the command, which created the checkpoint.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Meta {
+    options: String,
+}
+
+/// The partial results, which haven't been written yet.
+#[derive(Debug)]
+struct Pending<T> {
+    batch: usize,
+    paths: Vec<PathBuf>,
+    items: Vec<T>,
+}
+
+/// The state of a checkpoint restored by `--resume`.
+#[derive(Debug)]
+pub(crate) struct Restored<T> {
+    /// The documents, which were already processed.
+    pub(crate) processed: HashSet<PathBuf>,
+
+    /// The partial results of the processed documents.
+    pub(crate) items: Vec<T>,
+}
+
+/// A checkpoint of a long-running command.
+///
+/// The partial results are written in batches of `every` documents
+/// into the checkpoint directory. Each batch consists of the list of
+/// processed paths (`batch-000000.paths`) and the partial results
+/// (e.g. `batch-000000.jsonl`), which are written atomically in this
+/// order; a batch counts as complete, if its results exist. Thus, a
+/// crashed command loses at most the last (incomplete) batch.
+#[derive(Debug)]
+pub(crate) struct Checkpoint<T> {
+    dir: PathBuf,
+    every: usize,
+    pending: Mutex<Pending<T>>,
+}
+
+impl<T: Partial> Checkpoint<T> {
+    const META: &'static str = "checkpoint.json";
+
+    /// Opens the checkpoint in the given directory.
+    ///
+    /// If `resume` is set and the directory contains a checkpoint,
+    /// the processed documents and their partial results are
+    /// restored. The checkpoint must have been created with the same
+    /// `options`. Otherwise, an existing checkpoint is discarded. If
+    /// `every` is zero, no checkpoints are written.
+    pub(crate) fn open(
+        dir: PathBuf,
+        options: &str,
+        resume: bool,
+        every: usize,
+    ) -> DatashedResult<(Self, Restored<T>)> {
+        let meta = Meta {
+            options: options.into(),
+        };
+
+        let mut restored = Restored {
+            processed: HashSet::new(),
+            items: vec![],
+        };
+
+        let mut batch = 0;
+        if resume && dir.join(Self::META).is_file() {
+            let found: Meta = serde_json::from_reader(File::open(
+                dir.join(Self::META),
+            )?)
+            .map_err(DatashedError::other)?;
+
+            if found != meta {
+                bail!(
+                    "checkpoint '{}' was created with different options",
+                    dir.display()
+                );
+            }
+
+            loop {
+                let data = Self::batch_path(&dir, batch, T::EXTENSION);
+                if !data.is_file() {
+                    break;
+                }
+
+                let paths = Self::batch_path(&dir, batch, "paths");
+                for line in BufReader::new(File::open(paths)?).lines() {
+                    restored.processed.insert(PathBuf::from(line?));
+                }
+
+                restored.items.extend(T::read_batch(&data)?);
+                batch += 1;
+            }
+        } else {
+            if dir.exists() {
+                fs::remove_dir_all(&dir)?;
+            }
+
+            if every > 0 {
+                fs::create_dir_all(&dir)?;
+                let mut out = AtomicFile::create(dir.join(Self::META))?;
+                serde_json::to_writer(&mut out, &meta)
+                    .map_err(DatashedError::other)?;
+                out.commit()?;
+            }
+        }
+
+        let checkpoint = Self {
+            dir,
+            every,
+            pending: Mutex::new(Pending {
+                batch,
+                paths: vec![],
+                items: vec![],
+            }),
+        };
+
+        Ok((checkpoint, restored))
+    }
+
+    fn batch_path(dir: &Path, batch: usize, ext: &str) -> PathBuf {
+        dir.join(format!("batch-{batch:06}.{ext}"))
+    }
+
+    /// Records the partial result of a processed document. The
+    /// pending results are written, if the batch is full.
+    pub(crate) fn record(
+        &self,
+        path: &Path,
+        item: T,
+    ) -> DatashedResult<()> {
+        if self.every == 0 {
+            return Ok(());
+        }
+
+        let mut pending = self.pending.lock().unwrap();
+        pending.paths.push(path.into());
+        pending.items.push(item);
+
+        if pending.paths.len() >= self.every {
+            let batch = pending.batch;
+            let paths = std::mem::take(&mut pending.paths);
+            let items = std::mem::take(&mut pending.items);
+
+            let mut out = AtomicFile::create(Self::batch_path(
+                &self.dir, batch, "paths",
+            ))?;
+            let mut writer = BufWriter::new(&mut out);
+            for path in paths.iter() {
+                writeln!(writer, "{}", path.display())?;
+            }
+            writer.flush()?;
+            drop(writer);
+            out.commit()?;
+
+            let mut out = AtomicFile::create(Self::batch_path(
+                &self.dir,
+                batch,
+                T::EXTENSION,
+            ))?;
+            T::write_batch(items, &mut out)?;
+            out.commit()?;
+
+            pending.batch += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the checkpoint after the command has finished
+    /// successfully.
+    pub(crate) fn finish(self) -> DatashedResult<()> {
+        if self.dir.exists() {
+            fs::remove_dir_all(&self.dir)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    impl Partial for String {
+        const EXTENSION: &'static str = "jsonl";
+
+        fn write_batch<W: Write>(
+            items: Vec<Self>,
+            out: W,
+        ) -> DatashedResult<()> {
+            write_json_lines(&items, out)
+        }
+
+        fn read_batch(path: &Path) -> DatashedResult<Vec<Self>> {
+            read_json_lines(path)
+        }
+    }
+
+    #[test]
+    fn checkpoint_resume() -> TestResult {
+        let tmp = crate::testing::temp_dir()?;
+        let dir = tmp.path().join("checkpoint");
+
+        let (checkpoint, restored) =
+            Checkpoint::<String>::open(dir.clone(), "a", false, 2)?;
+        assert!(restored.processed.is_empty());
+
+        for name in ["1", "2", "3"] {
+            checkpoint.record(Path::new(name), name.into())?;
+        }
+
+        // The incomplete batch (`3`) is lost, as if the command
+        // crashed.
+        drop(checkpoint);
+
+        assert!(Checkpoint::<String>::open(dir.clone(), "b", true, 2)
+            .is_err());
+
+        let (checkpoint, restored) =
+            Checkpoint::<String>::open(dir.clone(), "a", true, 2)?;
+        assert_eq!(restored.items, ["1", "2"]);
+        assert!(restored.processed.contains(Path::new("2")));
+        assert!(!restored.processed.contains(Path::new("3")));
+
+        checkpoint.finish()?;
+        assert!(!dir.exists());
+        Ok(())
+    }
+}
@@ -16,27 +16,86 @@ pub(crate) struct Args {
     )]
     pub(crate) num_jobs: Option<usize>,
 
+    /// The seed of the random number generator, which is used by all
+    /// commands that sample, split or shuffle documents. The seed is
+    /// recorded in the metadata of the written index. If this option
+    /// isn't set, the seed of the config (`runtime.seed`) or a default
+    /// seed (0) is used.
+    #[clap(
+        long,
+        global = true,
+        env = "DATASHED_SEED",
+        hide_env_values = true
+    )]
+    pub(crate) seed: Option<u64>,
+
+    /// Use the index even if it's older than the data directory. By
+    /// default, commands warn about an outdated index or refuse to use
+    /// it (see `runtime.stale_index`).
+    #[clap(
+        long,
+        global = true,
+        env = "DATASHED_ALLOW_STALE",
+        hide_env_values = true
+    )]
+    pub(crate) allow_stale: bool,
+
     #[command(subcommand)]
     pub(crate) cmd: Command,
 }
 
 #[derive(Debug, Subcommand)]
 pub(crate) enum Command {
+    Apply(Apply),
     Archive(Archive),
+    Bench(Bench),
     Bibrefs(BibRefs),
     Clean(Clean),
     Completions(Completions),
     Config(Config),
+    Contains(Contains),
+    Daemon(Daemon),
+    Deboilerplate(Deboilerplate),
+    DiffDocs(DiffDocs),
+    DuckdbInit(DuckdbInit),
+    EncodingReport(EncodingReport),
+    Gc(Gc),
+    Graph(Graph),
     Grep(Grep),
     Index(Index),
     #[clap(alias = "new")]
     Init(Init),
+    Keywords(Keywords),
     Lfreq(Lfreq),
+    Link(Link),
+    Lm(Lm),
+    Log(Log),
+    Merge(Merge),
+    Mirror(Mirror),
+    #[cfg(feature = "fuse")]
+    Mount(Mount),
+    OcrConfusions(OcrConfusions),
+    Outdated(Outdated),
+    Quarantine(Quarantine),
     Rate(Rate),
+    Ratings(Ratings),
+    RedactIndex(RedactIndex),
+    Refine(Refine),
+    RepeatedLines(RepeatedLines),
+    Report(Report),
     Restore(Restore),
+    Sample(Sample),
+    Select(Select),
     Serve(Serve),
+    Shard(Shard),
+    Sign(Sign),
     Status(Status),
+    Stopwords(Stopwords),
     Summary(Summary),
+    Synth(Synth),
+    Tag(Tag),
+    Tocparse(Tocparse),
+    UndoClean(UndoClean),
     User(User),
     Verify(Verify),
     Version(Version),
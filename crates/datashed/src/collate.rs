@@ -0,0 +1,271 @@
+use std::cmp::Ordering;
+
+use clap::ValueEnum;
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::config::Config;
+
+/// The locale of a collation.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    ValueEnum,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Locale {
+    /// English; letters with diacritics are sorted next to their base
+    /// letter and `ß` is treated as `ss`.
+    #[default]
+    En,
+
+    /// German (DIN 5007-1, dictionary order); umlauts are sorted like
+    /// their base letter (`ä` = `a`) and `ß` is treated as `ss`.
+    De,
+
+    /// German (DIN 5007-2, phonebook order); umlauts are expanded
+    /// (`ä` = `ae`, `ö` = `oe`, `ü` = `ue`) and `ß` is treated as
+    /// `ss`.
+    DePhonebook,
+}
+
+/// The strength of a collation, i.e. the differences which are
+/// significant.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    ValueEnum,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Strength {
+    /// Only base letters are significant (`a` = `ä` = `A`).
+    Primary,
+
+    /// Base letters and diacritics are significant (`a` = `A` <
+    /// `ä`).
+    Secondary,
+
+    /// Base letters, diacritics and case are significant (`a` < `A` <
+    /// `ä`).
+    #[default]
+    Tertiary,
+}
+
+/// Collation options for sorting tokens.
+///
+/// Strings which are equal with respect to the strength of the
+/// collation are ordered bytewise, so that the output is
+/// deterministic.
+///
+/// ```toml
+/// [collation]
+/// locale = "de"
+/// strength = "secondary"
+/// ```
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub(crate) struct Collation {
+    #[serde(default)]
+    pub(crate) locale: Locale,
+
+    #[serde(default)]
+    pub(crate) strength: Strength,
+}
+
+/// The sort key of a string; one entry per level.
+#[derive(Debug)]
+struct SortKey {
+    primary: Vec<char>,
+    secondary: Vec<u32>,
+    tertiary: Vec<u8>,
+}
+
+impl Collation {
+    /// Returns the collation of the config, whose options are
+    /// overridden by the given command line options.
+    pub(crate) fn from_config(
+        config: &Config,
+        locale: Option<Locale>,
+        strength: Option<Strength>,
+    ) -> Self {
+        let mut collation = config.collation.unwrap_or_default();
+        if let Some(locale) = locale {
+            collation.locale = locale;
+        }
+
+        if let Some(strength) = strength {
+            collation.strength = strength;
+        }
+
+        collation
+    }
+
+    fn key(&self, s: &str) -> SortKey {
+        let mut key = SortKey {
+            primary: vec![],
+            secondary: vec![],
+            tertiary: vec![],
+        };
+
+        for c in s.nfd() {
+            if is_combining_mark(c) {
+                // The (first) mark belongs to the previous base letter.
+                if let Some(last) = key.secondary.last_mut() {
+                    if *last == 0 {
+                        *last = c as u32;
+                    }
+                }
+
+                continue;
+            }
+
+            let case = c.is_uppercase() as u8;
+            for lower in c.to_lowercase() {
+                let expansion: &[char] = match lower {
+                    'ß' => &['s', 's'],
+                    _ => &[lower],
+                };
+
+                for c in expansion {
+                    key.primary.push(*c);
+                    key.secondary.push(0);
+                    key.tertiary.push(case);
+                }
+            }
+        }
+
+        if self.locale == Locale::DePhonebook {
+            // Umlauts are decomposed into the base letter and U+0308
+            // (diaeresis); the expansion is `{base}e` with the mark on
+            // the `e`.
+            let mut idx = 0;
+            while idx < key.primary.len() {
+                let umlaut = key.secondary[idx] == 0x308
+                    && matches!(key.primary[idx], 'a' | 'o' | 'u');
+                if umlaut {
+                    key.secondary[idx] = 0;
+                    key.primary.insert(idx + 1, 'e');
+                    key.secondary.insert(idx + 1, 0x308);
+                    key.tertiary.insert(idx + 1, key.tertiary[idx]);
+                    idx += 1;
+                }
+
+                idx += 1;
+            }
+        }
+
+        key
+    }
+
+    /// Compares two strings.
+    pub(crate) fn compare(&self, a: &str, b: &str) -> Ordering {
+        self.compare_keys(&self.key(a), &self.key(b)).then(a.cmp(b))
+    }
+
+    /// Sorts a list of strings.
+    pub(crate) fn sort(&self, values: &mut [String]) {
+        let mut keyed: Vec<(SortKey, String)> = values
+            .iter_mut()
+            .map(|s| (self.key(s), std::mem::take(s)))
+            .collect();
+
+        keyed.sort_by(|(ka, a), (kb, b)| {
+            self.compare_keys(ka, kb).then(a.cmp(b))
+        });
+
+        for (value, (_, s)) in values.iter_mut().zip(keyed) {
+            *value = s;
+        }
+    }
+
+    /// Returns the rank of each value in collation order. The ranks
+    /// can be used as a sort column of a data frame. Null values are
+    /// ranked last.
+    pub(crate) fn rank(&self, values: &StringChunked) -> IdxCa {
+        let mut keyed: Vec<(usize, Option<(SortKey, &str)>)> = values
+            .iter()
+            .enumerate()
+            .map(|(idx, s)| (idx, s.map(|s| (self.key(s), s))))
+            .collect();
+
+        keyed.sort_by(|(_, a), (_, b)| match (a, b) {
+            (Some((ka, a)), Some((kb, b))) => {
+                self.compare_keys(ka, kb).then(a.cmp(b))
+            }
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        });
+
+        let mut ranks = vec![0 as IdxSize; keyed.len()];
+        for (rank, (idx, _)) in keyed.into_iter().enumerate() {
+            ranks[idx] = rank as IdxSize;
+        }
+
+        IdxCa::from_vec(values.name().clone(), ranks)
+    }
+
+    fn compare_keys(&self, a: &SortKey, b: &SortKey) -> Ordering {
+        let mut ordering = a.primary.cmp(&b.primary);
+        if self.strength >= Strength::Secondary {
+            ordering = ordering.then(a.secondary.cmp(&b.secondary));
+        }
+
+        if self.strength >= Strength::Tertiary {
+            ordering = ordering.then(a.tertiary.cmp(&b.tertiary));
+        }
+
+        ordering
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collation() {
+        let words =
+            ["Zug", "Äpfel", "apfel", "Apfel", "ärger", "Aerger"];
+        let sorted = |locale, strength| {
+            let mut words: Vec<String> =
+                words.iter().map(|w| w.to_string()).collect();
+            Collation { locale, strength }.sort(&mut words);
+            words
+        };
+
+        assert_eq!(
+            sorted(Locale::De, Strength::Tertiary),
+            ["Aerger", "apfel", "Apfel", "Äpfel", "ärger", "Zug"]
+        );
+        assert_eq!(
+            sorted(Locale::DePhonebook, Strength::Tertiary),
+            ["Äpfel", "Aerger", "ärger", "apfel", "Apfel", "Zug"]
+        );
+
+        let collation = Collation {
+            locale: Locale::De,
+            strength: Strength::Primary,
+        };
+        assert_eq!(
+            collation.compare_keys(
+                &collation.key("Straße"),
+                &collation.key("strasse")
+            ),
+            Ordering::Equal
+        );
+    }
+}
@@ -0,0 +1,591 @@
+use std::fs;
+use std::io::{stderr, stdin, IsTerminal};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::{Parser, ValueEnum};
+use dialoguer::{Input, Select};
+use hashbrown::HashMap;
+use polars::prelude::*;
+
+use crate::atomic::AtomicFile;
+use crate::audit;
+use crate::document::kind_from_path;
+use crate::plan::Plan;
+use crate::prelude::*;
+use crate::stats::write_index;
+
+/// Apply a patch to the index.
+///
+/// A patch is a CSV file with a `path` column and one column per index
+/// column to be updated, e.g.:
+///
+/// ```csv
+/// path,kind,msc
+/// data/toc/1234.txt,book,
+/// ```
+///
+/// Empty patch values are ignored. A patch value conflicts with the
+/// index, if the index already contains a different value. Conflicts
+/// are resolved interactively (keep the index value, keep the patch
+/// value, skip the row or edit the value) or by the given `--strategy`.
+/// All conflicts and their resolution are written into a conflicts
+/// report (CSV).
+#[derive(Debug, Parser)]
+pub(crate) struct Apply {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The strategy to resolve conflicts: interactive (default),
+    /// keep-index, keep-patch or skip (ignore the whole patch row).
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Strategy::default(),
+        value_name = "strategy"
+    )]
+    strategy: Strategy,
+
+    /// Write the conflicts report into `filename`. By default, the
+    /// report is written into the temp directory of the datashed.
+    #[arg(long, value_name = "filename")]
+    report: Option<PathBuf>,
+
+    /// Print a plan of the operations (counts and example paths)
+    /// without modifying the index. Conflicts aren't resolved.
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// Wait for the index lock held by another process to be
+    /// released. By default (`--no-wait`), the command fails
+    /// immediately, if the index is locked.
+    #[arg(long, overrides_with = "no_wait")]
+    wait: bool,
+
+    /// Fail immediately, if the index is locked by another process.
+    #[arg(long, overrides_with = "wait")]
+    no_wait: bool,
+
+    /// The patch file (CSV).
+    patch: PathBuf,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Strategy {
+    /// Ask for each conflict how to resolve it.
+    #[default]
+    Interactive,
+
+    /// Keep the value of the index.
+    KeepIndex,
+
+    /// Overwrite the index value with the value of the patch.
+    KeepPatch,
+
+    /// Ignore the whole patch row.
+    Skip,
+}
+
+/// The resolution of a conflict.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Resolution {
+    KeepIndex,
+    KeepPatch,
+    Skip,
+    Edit(String),
+}
+
+impl Resolution {
+    fn as_str(&self) -> &str {
+        match self {
+            Self::KeepIndex => "keep-index",
+            Self::KeepPatch => "keep-patch",
+            Self::Skip => "skip",
+            Self::Edit(_) => "edit",
+        }
+    }
+}
+
+/// A conflict between a patch value and the index.
+#[derive(Debug)]
+struct Conflict {
+    path: String,
+    column: String,
+    index: String,
+    patch: String,
+
+    /// The value derived from the document path (`kind` only).
+    document: Option<String>,
+}
+
+impl Conflict {
+    /// Asks the user how to resolve the conflict.
+    fn prompt(&self) -> Resolution {
+        let mut prompt = format!(
+            "{}: {} (index = {}, patch = {}",
+            self.path, self.column, self.index, self.patch
+        );
+
+        if let Some(ref document) = self.document {
+            prompt.push_str(&format!(", path = {document}"));
+        }
+
+        prompt.push(')');
+
+        let selection = Select::new()
+            .with_prompt(prompt)
+            .items(&["keep index", "keep patch", "skip row", "edit"])
+            .default(0)
+            .interact()
+            .unwrap();
+
+        match selection {
+            0 => Resolution::KeepIndex,
+            1 => Resolution::KeepPatch,
+            2 => Resolution::Skip,
+            _ => Resolution::Edit(
+                Input::new()
+                    .with_prompt(&self.column)
+                    .with_initial_text(&self.patch)
+                    .interact_text()
+                    .unwrap(),
+            ),
+        }
+    }
+
+    fn resolve(&self, strategy: Strategy) -> Resolution {
+        match strategy {
+            Strategy::Interactive => self.prompt(),
+            Strategy::KeepIndex => Resolution::KeepIndex,
+            Strategy::KeepPatch => Resolution::KeepPatch,
+            Strategy::Skip => Resolution::Skip,
+        }
+    }
+}
+
+/// The values of an index column, which may be updated by the patch.
+struct Values {
+    name: String,
+    dtype: DataType,
+    values: Vec<Option<String>>,
+    modified: bool,
+}
+
+/// Returns `true`, if the value can be cast (strictly) into the type of
+/// the index column.
+fn is_valid(value: &str, dtype: &DataType) -> bool {
+    Series::new("value".into(), [value])
+        .strict_cast(dtype)
+        .is_ok()
+}
+
+/// The updates (position of the column and new value) and the resolved
+/// conflicts of a single patch row.
+type RowPatch =
+    (Option<Vec<(usize, String)>>, Vec<(Conflict, Resolution)>);
+
+/// Compares the (non-empty) patch values of a row with the index
+/// values and resolves the conflicts with `resolve`. Returns no
+/// updates, if a conflict is resolved by skipping the row; in this
+/// case, all conflicts of the row are reported as skipped.
+fn patch_row<F>(
+    path: &str,
+    row: usize,
+    patch: &[Option<&str>],
+    columns: &[Values],
+    mut resolve: F,
+) -> RowPatch
+where
+    F: FnMut(&Conflict) -> Resolution,
+{
+    let mut updates = vec![];
+    let mut conflicts = vec![];
+
+    for (i, (values, new)) in columns.iter().zip(patch).enumerate() {
+        let Some(new) = *new else {
+            continue;
+        };
+
+        let old = values.values[row].as_deref();
+        if old == Some(new) {
+            continue;
+        }
+
+        let Some(old) = old else {
+            updates.push((i, new.to_string()));
+            continue;
+        };
+
+        let conflict = Conflict {
+            path: path.into(),
+            column: values.name.clone(),
+            index: old.into(),
+            patch: new.into(),
+            document: (values.name == "kind")
+                .then(|| kind_from_path(path).to_string()),
+        };
+
+        let resolution = resolve(&conflict);
+        match resolution {
+            Resolution::KeepIndex => (),
+            Resolution::KeepPatch => {
+                updates.push((i, conflict.patch.clone()))
+            }
+            Resolution::Edit(ref value) => {
+                updates.push((i, value.clone()))
+            }
+            Resolution::Skip => {
+                conflicts.push((conflict, resolution));
+                for (_, resolution) in conflicts.iter_mut() {
+                    *resolution = Resolution::Skip;
+                }
+
+                return (None, conflicts);
+            }
+        }
+
+        conflicts.push((conflict, resolution));
+    }
+
+    (Some(updates), conflicts)
+}
+
+impl Apply {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let base_dir = datashed.base_dir();
+        let _lock = datashed.lock(self.wait && !self.no_wait)?;
+
+        if self.strategy == Strategy::Interactive
+            && !self.dry_run
+            && !stdin().is_terminal()
+        {
+            bail!(
+                "interactive conflict resolution requires a terminal"
+            );
+        }
+
+        let mut index = datashed.index()?;
+        let patch = CsvReadOptions::default()
+            .with_has_header(true)
+            .with_infer_schema_length(Some(0))
+            .try_into_reader_with_file_path(Some(self.patch.clone()))?
+            .finish()?;
+
+        let Ok(patch_path) = patch.column("path") else {
+            bail!("patch is missing the 'path' column");
+        };
+
+        let patch_path = patch_path.str()?;
+        let names: Vec<String> = patch
+            .get_column_names()
+            .into_iter()
+            .filter(|name| *name != "path")
+            .map(ToString::to_string)
+            .collect();
+
+        let mut columns = names
+            .iter()
+            .map(|name| {
+                let Ok(column) = index.column(name) else {
+                    bail!("unknown index column '{name}'");
+                };
+
+                Ok(Values {
+                    name: name.clone(),
+                    dtype: column.dtype().clone(),
+                    values: column
+                        .cast(&DataType::String)?
+                        .str()?
+                        .into_iter()
+                        .map(|value| value.map(String::from))
+                        .collect(),
+                    modified: false,
+                })
+            })
+            .collect::<DatashedResult<Vec<_>>>()?;
+
+        let rows: HashMap<&str, usize> = index
+            .column("path")?
+            .str()?
+            .into_iter()
+            .enumerate()
+            .filter_map(|(idx, path)| Some((path?, idx)))
+            .collect();
+
+        let patch_columns = names
+            .iter()
+            .map(|name| Ok(patch.column(name)?.str()?))
+            .collect::<DatashedResult<Vec<_>>>()?;
+
+        let mut plan = Plan::default();
+        let mut report = vec![];
+        let mut affected = 0;
+
+        for idx in 0..patch.height() {
+            let Some(path) = patch_path.get(idx) else {
+                continue;
+            };
+
+            let Some(&row) = rows.get(path) else {
+                plan.add("skip (not indexed)", path, 0);
+                continue;
+            };
+
+            let values: Vec<Option<&str>> = patch_columns
+                .iter()
+                .map(|column| column.get(idx))
+                .collect();
+
+            let (updates, conflicts) = if self.dry_run {
+                // Conflicts aren't resolved in a dry run.
+                patch_row(path, row, &values, &columns, |conflict| {
+                    plan.add(
+                        "conflict",
+                        format!("{path} ({})", conflict.column),
+                        0,
+                    );
+                    Resolution::KeepIndex
+                })
+            } else {
+                patch_row(path, row, &values, &columns, |conflict| {
+                    conflict.resolve(self.strategy)
+                })
+            };
+
+            if !self.dry_run {
+                report.extend(conflicts);
+            }
+
+            let Some(updates) = updates else {
+                plan.add("skip (conflict)", path, 0);
+                continue;
+            };
+
+            for (i, value) in updates.iter() {
+                if !is_valid(value, &columns[*i].dtype) {
+                    bail!(
+                        "invalid value '{value}' of column '{}' in \
+                        line {} of the patch ({path})",
+                        columns[*i].name,
+                        idx + 2
+                    );
+                }
+            }
+
+            if !updates.is_empty() {
+                affected += 1;
+            }
+
+            for (i, value) in updates {
+                plan.add(
+                    "update",
+                    format!("{path} ({})", columns[i].name),
+                    0,
+                );
+
+                columns[i].values[row] = Some(value);
+                columns[i].modified = true;
+            }
+        }
+
+        if self.dry_run {
+            plan.print()?;
+            return Ok(());
+        }
+
+        if !report.is_empty() {
+            let path = match self.report {
+                Some(ref path) => path.clone(),
+                None => {
+                    let millis = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_millis();
+
+                    let temp_dir = datashed.temp_dir();
+                    fs::create_dir_all(&temp_dir)?;
+                    temp_dir.join(format!("apply-{millis}.csv"))
+                }
+            };
+
+            let mut writer = csv::Writer::from_path(&path)?;
+            writer.write_record([
+                "path",
+                "column",
+                "index",
+                "patch",
+                "document",
+                "resolution",
+                "value",
+            ])?;
+
+            for (conflict, resolution) in report.iter() {
+                let value = match resolution {
+                    Resolution::KeepIndex | Resolution::Skip => {
+                        &conflict.index
+                    }
+                    Resolution::KeepPatch => &conflict.patch,
+                    Resolution::Edit(value) => value,
+                };
+
+                writer.write_record([
+                    conflict.path.as_str(),
+                    conflict.column.as_str(),
+                    conflict.index.as_str(),
+                    conflict.patch.as_str(),
+                    conflict.document.as_deref().unwrap_or_default(),
+                    resolution.as_str(),
+                    value.as_str(),
+                ])?;
+            }
+
+            writer.flush()?;
+
+            if !self.quiet {
+                eprintln!(
+                    "{} conflict(s) written to '{}'.",
+                    report.len(),
+                    path.display()
+                );
+            }
+        }
+
+        if self.verbose {
+            plan.write(stderr().lock())?;
+        }
+
+        if !columns.iter().any(|values| values.modified) {
+            return Ok(());
+        }
+
+        for values in
+            columns.into_iter().filter(|values| values.modified)
+        {
+            let column = Column::new(values.name.into(), values.values)
+                .strict_cast(&values.dtype)?;
+            index.with_column(column)?;
+        }
+
+        let mut out =
+            AtomicFile::create(base_dir.join(Datashed::INDEX))?;
+        write_index(&mut index, &mut out)?;
+        out.commit()?;
+
+        audit::record(&datashed, "apply", affected)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns() -> Vec<Values> {
+        vec![
+            Values {
+                name: "kind".into(),
+                dtype: DataType::String,
+                values: vec![Some("toc".into()), None],
+                modified: false,
+            },
+            Values {
+                name: "year".into(),
+                dtype: DataType::Int32,
+                values: vec![Some("2001".into()), Some("2002".into())],
+                modified: false,
+            },
+        ]
+    }
+
+    fn resolutions(conflicts: &[(Conflict, Resolution)]) -> Vec<&str> {
+        conflicts.iter().map(|(_, r)| r.as_str()).collect()
+    }
+
+    #[test]
+    fn apply_patch_row() {
+        let columns = columns();
+        let path = "data/book/1.txt";
+        let patch = [Some("book"), Some("2003")];
+
+        let (updates, conflicts) =
+            patch_row(path, 0, &patch, &columns, |c| {
+                c.resolve(Strategy::KeepIndex)
+            });
+        assert_eq!(updates, Some(vec![]));
+        assert_eq!(
+            resolutions(&conflicts),
+            ["keep-index", "keep-index"]
+        );
+        assert_eq!(conflicts[0].0.document.as_deref(), Some("book"));
+
+        let (updates, conflicts) =
+            patch_row(path, 0, &patch, &columns, |c| {
+                c.resolve(Strategy::KeepPatch)
+            });
+        assert_eq!(
+            updates,
+            Some(vec![(0, "book".into()), (1, "2003".into())])
+        );
+        assert_eq!(
+            resolutions(&conflicts),
+            ["keep-patch", "keep-patch"]
+        );
+
+        let (updates, conflicts) =
+            patch_row(path, 0, &patch, &columns, |c| {
+                c.resolve(Strategy::Skip)
+            });
+        assert_eq!(updates, None);
+        assert_eq!(resolutions(&conflicts), ["skip"]);
+
+        // A later skip overrides the resolutions of the earlier
+        // conflicts of the row.
+        let (updates, conflicts) =
+            patch_row(path, 0, &patch, &columns, |c| {
+                if c.column == "kind" {
+                    Resolution::KeepPatch
+                } else {
+                    Resolution::Skip
+                }
+            });
+        assert_eq!(updates, None);
+        assert_eq!(resolutions(&conflicts), ["skip", "skip"]);
+
+        let (updates, conflicts) =
+            patch_row(path, 0, &patch, &columns, |_| {
+                Resolution::Edit("x".into())
+            });
+        assert_eq!(
+            updates,
+            Some(vec![(0, "x".into()), (1, "x".into())])
+        );
+        assert_eq!(resolutions(&conflicts), ["edit", "edit"]);
+
+        // Missing index values and equal values aren't conflicts.
+        let (updates, conflicts) = patch_row(
+            path,
+            1,
+            &[Some("book"), Some("2002")],
+            &columns,
+            |_| unreachable!(),
+        );
+        assert_eq!(updates, Some(vec![(0, "book".into())]));
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn apply_valid_values() {
+        assert!(is_valid("2003", &DataType::Int32));
+        assert!(!is_valid("x", &DataType::Int32));
+        assert!(is_valid("x", &DataType::String));
+    }
+}
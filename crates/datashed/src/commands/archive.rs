@@ -1,13 +1,18 @@
 use std::fs::File;
 use std::io::{stdout, Write};
 use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use clap::Parser;
 use flate2::write::GzEncoder;
 use flate2::Compression;
 use indicatif::ProgressIterator;
+use polars::prelude::*;
 
+use crate::crypto::{self, Key};
 use crate::prelude::*;
+use crate::stats::write_index;
+use crate::{access, licenses, throttle};
 
 const PBAR_ARCHIVE: &str =
     "Archive documents: {human_pos} ({percent}%) | \
@@ -18,6 +23,18 @@ const PBAR_ARCHIVE: &str =
 /// By default, the compression is biased towards high compression ratio
 /// at expense of speed. To change this setting, use the `--fast` or
 /// `--best` flag.
+///
+/// The archive contains a `LICENSES` manifest, which lists the
+/// documents grouped by their license terms (see the `[license]`
+/// config section).
+///
+/// Documents, which are excluded by the discovery rules (see the
+/// `[discovery]` config section), are skipped and removed from the
+/// archived index.
+///
+/// The archive can optionally be encrypted (age format) for one or
+/// more recipients or with a passphrase. The passphrase is read from
+/// the `DATASHED_PASSPHRASE` environment variable or prompted for.
 #[derive(Debug, Default, Parser)]
 pub(crate) struct Archive {
     /// Run verbosely. Print additional progress information to the
@@ -39,16 +56,129 @@ pub(crate) struct Archive {
     #[arg(long, conflicts_with = "fast")]
     best: bool,
 
+    /// Encrypt the archive for the given recipient (an age public key
+    /// `age1...`). This option can be specified multiple times.
+    #[arg(
+        short,
+        long = "recipient",
+        value_name = "recipient",
+        conflicts_with = "passphrase"
+    )]
+    recipients: Vec<String>,
+
+    /// Encrypt the archive for the identities of an age key file.
+    #[arg(short, long, value_name = "filename")]
+    key_file: Option<PathBuf>,
+
+    /// Encrypt the archive with a passphrase.
+    #[arg(long, conflicts_with_all = ["recipients", "key_file"])]
+    passphrase: bool,
+
+    /// Include documents with a restricted access/license code (see
+    /// the `[license]` config section), e.g. embargoed documents. By
+    /// default, the command fails if the index contains such
+    /// documents.
+    #[arg(long)]
+    force: bool,
+
     /// Write the archive to `filename` instead of stdout.
     #[arg(short, long, value_name = "filename")]
     output: Option<PathBuf>,
 }
 
+/// Returns the header of an archive entry of `len` bytes.
+fn header(len: usize) -> tar::Header {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(len as u64);
+    header.set_mode(0o644);
+    header.set_mtime(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+    );
+    header.set_cksum();
+    header
+}
+
 impl Archive {
     pub(crate) fn execute(self) -> DatashedResult<()> {
         let datashed = Datashed::discover()?;
+
+        if !self.force {
+            let restricted = access::restricted(
+                &datashed.config()?,
+                &datashed.index()?,
+            )?;
+            if !restricted.is_empty() {
+                bail!(
+                    "index contains {} document(s) with a restricted \
+                    license (use --force to include them)",
+                    restricted.len()
+                );
+            }
+        }
+
+        let mut recipients = self
+            .recipients
+            .iter()
+            .map(|r| crypto::parse_recipient(r))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(DatashedError::other)?;
+
+        if let Some(ref path) = self.key_file {
+            recipients.extend(
+                crypto::read_identities(path)?
+                    .iter()
+                    .map(|identity| identity.to_public()),
+            );
+        }
+
+        let key = if self.passphrase {
+            Some(Key::Passphrase(crypto::passphrase(true)?))
+        } else if !recipients.is_empty() {
+            Some(Key::Recipients(recipients))
+        } else {
+            None
+        };
+
+        let out: Box<dyn Write> = match self.output {
+            Some(ref path) => Box::new(File::create(path)?),
+            None => Box::new(stdout().lock()),
+        };
+
+        match key {
+            Some(key) => {
+                let out =
+                    self.write(&datashed, crypto::encrypt(out, key)?)?;
+                out.finish()?;
+            }
+            None => {
+                let mut out = self.write(&datashed, out)?;
+                out.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write<W: Write>(
+        &self,
+        datashed: &Datashed,
+        out: W,
+    ) -> DatashedResult<W> {
         let index = datashed.index()?;
-        let paths = index.column("path")?.str()?;
+        let matcher = datashed.config()?.discovery.matcher()?;
+        let mask: BooleanChunked = index
+            .column("path")?
+            .str()?
+            .iter()
+            .map(|path| path.is_some_and(|path| matcher.is_match(path)))
+            .collect();
+
+        let excluded = index.height() - mask.num_trues();
+        let mut index = index.filter(&mask)?;
+        let paths = index.column("path")?.str()?.clone();
 
         let level = if self.fast {
             Compression::fast()
@@ -58,11 +188,6 @@ impl Archive {
             Compression::default()
         };
 
-        let out: Box<dyn Write> = match self.output {
-            Some(path) => Box::new(File::create(path)?),
-            None => Box::new(stdout().lock()),
-        };
-
         let gzip = GzEncoder::new(out, level);
         let mut archive = tar::Builder::new(gzip);
 
@@ -73,22 +198,46 @@ impl Archive {
         paths.iter().progress_with(pbar).try_for_each(|path| {
             let path = path.unwrap();
 
-            let mut file =
-                File::open(datashed.base_dir().join(path)).unwrap();
+            let file_path = datashed.base_dir().join(path);
+            if let Ok(metadata) = file_path.metadata() {
+                throttle::acquire(metadata.len());
+            }
+
+            let mut file = File::open(file_path).unwrap();
             archive.append_file(path, &mut file).unwrap();
 
             Ok::<(), DatashedError>(())
         })?;
 
-        let mut index =
-            File::open(datashed.base_dir().join(Datashed::INDEX))?;
-        archive.append_file(Datashed::INDEX, &mut index)?;
+        if excluded > 0 {
+            let mut buf = vec![];
+            write_index(&mut index, &mut buf)?;
+            archive.append_data(
+                &mut header(buf.len()),
+                Datashed::INDEX,
+                buf.as_slice(),
+            )?;
+
+            if self.verbose {
+                eprintln!("skipped {excluded} excluded document(s).");
+            }
+        } else {
+            let mut file =
+                File::open(datashed.base_dir().join(Datashed::INDEX))?;
+            archive.append_file(Datashed::INDEX, &mut file)?;
+        }
 
-        let mut config =
+        let mut file =
             File::open(datashed.base_dir().join(Datashed::CONFIG))?;
-        archive.append_file(Datashed::CONFIG, &mut config)?;
+        archive.append_file(Datashed::CONFIG, &mut file)?;
 
-        archive.finish()?;
-        Ok(())
+        let manifest = licenses::manifest(&datashed.config()?, &index)?;
+        archive.append_data(
+            &mut header(manifest.len()),
+            licenses::MANIFEST,
+            manifest.as_bytes(),
+        )?;
+
+        Ok(archive.into_inner()?.finish()?)
     }
 }
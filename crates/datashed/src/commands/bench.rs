@@ -0,0 +1,208 @@
+use std::time::{Duration, Instant};
+
+use clap::{Parser, ValueEnum};
+use comfy_table::{presets, Row, Table};
+use humansize::{make_format, BINARY};
+use polars::prelude::*;
+use polars::sql::SQLContext;
+
+use crate::lfreq::LfreqProfiles;
+use crate::prelude::*;
+use crate::sql::select_where;
+
+const PBAR_BENCH: &str =
+    "Benchmarking documents: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Metric {
+    Read,
+    Hash,
+    Lang,
+    Lfreq,
+    Alpha,
+    Ttr,
+}
+
+impl Metric {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Hash => "hash",
+            Self::Lang => "lang",
+            Self::Lfreq => "lfreq",
+            Self::Alpha => "alpha",
+            Self::Ttr => "ttr",
+        }
+    }
+}
+
+/// Measure the throughput of the document metrics.
+///
+/// Each metric is computed over a sample of the indexed documents on a
+/// single thread. The report contains the number of documents and
+/// bytes processed per second and the share of each metric of the
+/// total time. The `lfreq` metric is measured with an already known
+/// language, i.e. it doesn't include the language detection.
+#[derive(Debug, Parser)]
+pub(crate) struct Bench {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The (maximum) number of documents to sample. Documents are
+    /// chosen at evenly spaced positions of the index.
+    #[arg(short = 'n', long, default_value = "500", value_name = "n")]
+    sample: usize,
+
+    /// A comma-separated list of metrics to benchmark. By default, all
+    /// metrics are measured.
+    #[arg(
+        short,
+        long,
+        value_enum,
+        value_delimiter = ',',
+        value_name = "metrics"
+    )]
+    metrics: Vec<Metric>,
+
+    /// An optional predicate to filter the document-set.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+}
+
+impl Bench {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let base_dir = datashed.base_dir();
+        let config = datashed.config()?;
+        let profiles = LfreqProfiles::from_config(&config, base_dir)?;
+        let mut index = datashed.index_lazy()?;
+
+        if let Some(predicate) = self.predicate {
+            let mut ctx = SQLContext::new();
+            ctx.register("df", index);
+            index = ctx.execute(&select_where(&predicate))?;
+        }
+
+        let df = index.select([col("path")]).collect()?;
+
+        let metrics = if self.metrics.is_empty() {
+            Metric::value_variants().to_vec()
+        } else {
+            self.metrics.clone()
+        };
+
+        let height = df.height();
+        let n = self.sample.min(height);
+        if n == 0 {
+            bail!("no documents to benchmark");
+        }
+
+        let step = height as f64 / n as f64;
+        let path = df.column("path")?.str()?;
+        let pbar = ProgressBarBuilder::new(PBAR_BENCH, self.quiet)
+            .len(n as u64)
+            .build();
+
+        let mut timings = vec![Duration::ZERO; metrics.len()];
+        let mut bytes = 0u64;
+
+        for i in 0..n {
+            let idx = (i as f64 * step) as usize;
+            let path = base_dir.join(path.get(idx).unwrap());
+
+            let now = Instant::now();
+            let mut doc = Document::from_path(&path)?;
+            let read = now.elapsed();
+            bytes += doc.size();
+
+            for (metric, elapsed) in
+                metrics.iter().zip(timings.iter_mut())
+            {
+                let now = Instant::now();
+                match metric {
+                    Metric::Read => *elapsed += read,
+                    Metric::Hash => {
+                        let _ = doc.hash();
+                    }
+                    Metric::Lang => {
+                        let _ = doc.lang();
+                    }
+                    Metric::Lfreq => {
+                        let _ = doc.lang();
+                        let now = Instant::now();
+                        let _ = doc.lfreq_with(&profiles);
+                        *elapsed += now.elapsed();
+                        continue;
+                    }
+                    Metric::Alpha => {
+                        let _ = doc.alpha();
+                    }
+                    Metric::Ttr => {
+                        let _ = doc.type_token_ratio();
+                    }
+                }
+
+                if *metric != Metric::Read {
+                    *elapsed += now.elapsed();
+                }
+            }
+
+            pbar.inc(1);
+        }
+
+        pbar.finish_using_style();
+
+        let total: f64 =
+            timings.iter().map(Duration::as_secs_f64).sum();
+        let formatter = make_format(BINARY);
+
+        let mut table = Table::new();
+        table.load_preset(presets::UTF8_FULL_CONDENSED);
+        table.set_header(Row::from(vec![
+            "metric", "time", "docs/s", "bytes/s", "share",
+        ]));
+
+        for (metric, elapsed) in metrics.iter().zip(timings.iter()) {
+            let secs = elapsed.as_secs_f64();
+            let (docs_per_sec, bytes_per_sec) = if secs > 0.0 {
+                (n as f64 / secs, bytes as f64 / secs)
+            } else {
+                (f64::INFINITY, f64::INFINITY)
+            };
+
+            table.add_row(vec![
+                metric.name().to_string(),
+                format!("{secs:.3}s"),
+                format!("{docs_per_sec:.1}"),
+                if bytes_per_sec.is_finite() {
+                    format!("{}/s", formatter(bytes_per_sec as u64))
+                } else {
+                    "-".into()
+                },
+                format!(
+                    "{:.1}%",
+                    100.0 * secs / total.max(f64::EPSILON)
+                ),
+            ]);
+        }
+
+        if self.verbose {
+            eprintln!(
+                "sampled {n} of {height} documents ({})",
+                formatter(bytes)
+            );
+        }
+
+        println!("{table}");
+        Ok(())
+    }
+}
@@ -1,9 +1,10 @@
 use std::fmt::{self, Display};
 use std::fs::File;
-use std::io::stdout;
-use std::path::PathBuf;
+use std::io::{stdout, BufWriter, Write};
+use std::path::{Path, PathBuf};
 
-use clap::Parser;
+use bstr::ByteSlice;
+use clap::{Parser, ValueEnum};
 use ddc::DdcMatcher;
 use indicatif::ParallelProgressIterator;
 use isbn::IsbnMatcher;
@@ -12,14 +13,22 @@ use issn::IssnMatcher;
 use orcid::OrcidMatcher;
 use polars::prelude::*;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
+use crate::checkpoint::{
+    read_json_lines, write_json_lines, Checkpoint, Partial,
+};
+use crate::prefetch::documents;
 use crate::prelude::*;
+use crate::schedule::Schedule;
 
 mod ddc;
 mod isbn;
 mod isni;
 mod issn;
 mod orcid;
+#[cfg(feature = "wasm")]
+mod wasm;
 
 #[derive(Debug)]
 pub(crate) struct Reference {
@@ -36,6 +45,7 @@ pub(crate) enum RefKind {
     Ddc,
     Orcid,
     Isni,
+    Plugin(String),
 }
 
 impl Display for RefKind {
@@ -46,6 +56,7 @@ impl Display for RefKind {
             Self::Ddc => write!(f, "ddc"),
             Self::Orcid => write!(f, "orcid"),
             Self::Isni => write!(f, "isni"),
+            Self::Plugin(kind) => write!(f, "{kind}"),
         }
     }
 }
@@ -54,11 +65,72 @@ trait Matcher: Sync {
     fn matches(&self, content: &[u8]) -> Vec<Reference>;
 }
 
+/// The unit of text surrounding a reference.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum ContextUnit {
+    #[default]
+    Sentence,
+    Paragraph,
+}
+
+/// Returns the byte range of the sentence or paragraph containing the
+/// given range.
+///
+/// Paragraphs are separated by blank lines. Sentences end with a `.`,
+/// `!` or `?` followed by whitespace, or at a paragraph boundary.
+fn context(
+    content: &[u8],
+    start: usize,
+    end: usize,
+    unit: ContextUnit,
+) -> (usize, usize) {
+    let mut lo = content[..start]
+        .rfind("\n\n")
+        .map(|pos| pos + 2)
+        .unwrap_or(0);
+
+    let mut hi = content[end..]
+        .find("\n\n")
+        .map(|pos| end + pos)
+        .unwrap_or(content.len());
+
+    if unit == ContextUnit::Sentence {
+        let is_eos = |pos: usize| {
+            matches!(content[pos], b'.' | b'!' | b'?')
+                && content
+                    .get(pos + 1)
+                    .is_none_or(|c| c.is_ascii_whitespace())
+        };
+
+        if let Some(pos) = (lo..start).rev().find(|pos| is_eos(*pos)) {
+            lo = pos + 1;
+        }
+
+        if let Some(pos) = (end..hi).find(|pos| is_eos(*pos)) {
+            hi = pos + 1;
+        }
+    }
+
+    while lo < hi && content[lo].is_ascii_whitespace() {
+        lo += 1;
+    }
+
+    while hi > lo && content[hi - 1].is_ascii_whitespace() {
+        hi -= 1;
+    }
+
+    (lo, hi)
+}
+
 const PBAR_PROCESS: &str =
     "Processing documents: {human_pos} ({percent}%) | \
         elapsed: {elapsed_precise}{msg}";
 
-/// Verify that the inventory of documents matches the index.
+/// Extract bibliographic references (ISBN, ISSN, DDC, ORCID, ISNI).
+///
+/// If built with the `wasm` feature, the matchers of the WASM plugins
+/// in the `plugins` directory are applied as well; their references
+/// are typed by the plugin (or named after it).
 #[derive(Debug, Default, Parser)]
 pub(crate) struct BibRefs {
     /// Run verbosely. Print additional progress information to the
@@ -77,15 +149,81 @@ pub(crate) struct BibRefs {
     /// the root directory.
     #[arg(short, long, value_name = "filename")]
     output: Option<PathBuf>,
+
+    /// Read documents sequentially in on-disk order with a read-ahead
+    /// of `n` documents instead of reading them in parallel. This
+    /// reduces random I/O on spinning disks.
+    #[arg(long, value_name = "n")]
+    prefetch: Option<usize>,
+
+    /// The scheduling strategy of the parallel document processing:
+    /// uniform (default), size (largest documents first) or dynamic.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Schedule::default(),
+        conflicts_with = "prefetch",
+        value_name = "strategy"
+    )]
+    schedule: Schedule,
+
+    /// Export the references together with their surrounding text
+    /// (citation contexts) as JSON Lines instead of a table. This
+    /// option conflicts with the `--columns` option.
+    #[arg(long, conflicts_with = "columns")]
+    export_contexts: bool,
+
+    /// The unit of the citation context: sentence (default) or
+    /// paragraph.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = ContextUnit::Sentence,
+        requires = "export_contexts",
+        value_name = "unit"
+    )]
+    context: ContextUnit,
+
+    /// Resume an interrupted run from its last checkpoint. Documents,
+    /// which were already processed, are skipped. The checkpoint must
+    /// have been created with the same `--export-contexts` and
+    /// `--context` options.
+    #[arg(long)]
+    resume: bool,
+
+    /// Write a checkpoint (into the temp directory) after every `n`
+    /// processed documents. A value of zero disables checkpoints.
+    #[arg(long, default_value = "1000", value_name = "n")]
+    checkpoint_every: usize,
+
+    #[command(flatten)]
+    columns: Columns,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct Record {
     path: String,
     r#type: String,
     value: String,
     start: u64,
     end: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context: Option<String>,
+}
+
+impl Partial for Vec<Record> {
+    const EXTENSION: &'static str = "jsonl";
+
+    fn write_batch<W: Write>(
+        items: Vec<Self>,
+        out: W,
+    ) -> DatashedResult<()> {
+        write_json_lines(&items, out)
+    }
+
+    fn read_batch(path: &Path) -> DatashedResult<Vec<Self>> {
+        read_json_lines(path)
+    }
 }
 
 impl BibRefs {
@@ -93,7 +231,8 @@ impl BibRefs {
         let datashed = Datashed::discover()?;
         let index = datashed.index()?;
 
-        let matchers: Vec<Box<dyn Matcher>> = vec![
+        #[allow(unused_mut)]
+        let mut matchers: Vec<Box<dyn Matcher>> = vec![
             Box::new(IsbnMatcher::default()),
             Box::new(IssnMatcher::default()),
             Box::new(DdcMatcher::default()),
@@ -101,33 +240,110 @@ impl BibRefs {
             Box::new(IsniMatcher::default()),
         ];
 
+        #[cfg(feature = "wasm")]
+        matchers.extend(
+            crate::plugins::Plugins::load(datashed.plugins_dir())?
+                .matchers()
+                .map(|plugin| {
+                    Box::new(wasm::WasmMatcher::new(plugin.clone()))
+                        as Box<dyn Matcher>
+                }),
+        );
+
+        let (checkpoint, restored) = Checkpoint::<Vec<Record>>::open(
+            datashed.checkpoints_dir().join("bibrefs"),
+            &format!(
+                "export_contexts={},context={:?}",
+                self.export_contexts, self.context
+            ),
+            self.resume,
+            self.checkpoint_every,
+        )?;
+
+        if self.verbose && !restored.processed.is_empty() {
+            eprintln!(
+                "resuming after {} processed document(s)",
+                restored.processed.len()
+            );
+        }
+
+        let paths: Vec<PathBuf> = index
+            .column("path")?
+            .str()?
+            .into_no_null_iter()
+            .map(PathBuf::from)
+            .filter(|path| !restored.processed.contains(path))
+            .collect();
+
         let pbar = ProgressBarBuilder::new(PBAR_PROCESS, self.quiet)
-            .len(index.height() as u64)
+            .len(paths.len() as u64)
             .build();
 
-        let path = index.column("path")?.str()?;
-
-        let records: Vec<Record> = (0..index.height())
-            .into_par_iter()
-            .progress_with(pbar)
-            .flat_map(|idx| {
-                let path = path.get(idx).unwrap();
-                let doc = Document::from_path(path).unwrap();
-                let content = doc.as_ref();
-                matchers
-                    .iter()
-                    .flat_map(|m| m.matches(content))
-                    .map(|reference| Record {
-                        path: path.to_string(),
-                        r#type: reference.kind.to_string(),
-                        value: reference.value,
-                        start: reference.start as u64,
-                        end: reference.end as u64,
-                    })
-                    .collect::<Vec<Record>>()
-            })
+        let processed: Vec<Vec<Record>> =
+            documents(paths.clone(), self.prefetch, self.schedule)
+                .progress_with(pbar)
+                .map(|(idx, doc)| -> DatashedResult<Vec<Record>> {
+                    let path = paths[idx].to_str().unwrap_or_default();
+                    let doc = doc?;
+                    let content = doc.as_ref();
+                    let records = matchers
+                        .iter()
+                        .flat_map(|m| m.matches(content))
+                        .map(|reference| {
+                            let context =
+                                self.export_contexts.then(|| {
+                                    let (lo, hi) = context(
+                                        content,
+                                        reference.start,
+                                        reference.end,
+                                        self.context,
+                                    );
+
+                                    content[lo..hi]
+                                        .to_str_lossy()
+                                        .into_owned()
+                                });
+
+                            Record {
+                                path: path.to_string(),
+                                r#type: reference.kind.to_string(),
+                                value: reference.value,
+                                start: reference.start as u64,
+                                end: reference.end as u64,
+                                context,
+                            }
+                        })
+                        .collect::<Vec<Record>>();
+
+                    checkpoint.record(&paths[idx], records.clone())?;
+                    Ok(records)
+                })
+                .collect::<DatashedResult<_>>()?;
+
+        let records: Vec<Record> = restored
+            .items
+            .into_iter()
+            .chain(processed)
+            .flatten()
             .collect();
 
+        if self.export_contexts {
+            let mut writer: Box<dyn Write> = match self.output {
+                Some(path) => Box::new(File::create(path)?),
+                None => Box::new(stdout().lock()),
+            };
+
+            let mut writer = BufWriter::new(writer.as_mut());
+            for record in records.iter() {
+                serde_json::to_writer(&mut writer, record)
+                    .map_err(DatashedError::other)?;
+                writeln!(writer)?;
+            }
+
+            writer.flush()?;
+            return checkpoint.finish();
+        }
+
         let mut path = vec![];
         let mut r#type = vec![];
         let mut value = vec![];
@@ -142,13 +358,14 @@ impl BibRefs {
             end.push(record.end);
         }
 
-        let mut df = DataFrame::new(vec![
+        let df = DataFrame::new(vec![
             Column::new("path".into(), path),
             Column::new("type".into(), r#type),
             Column::new("value".into(), value),
             Column::new("start".into(), start),
             Column::new("end".into(), end),
         ])?;
+        let mut df = self.columns.project(df, &datashed)?;
 
         if let Some(path) = self.output {
             let mut writer = IpcWriter::new(File::create(path)?)
@@ -159,6 +376,30 @@ impl BibRefs {
             writer.finish(&mut df)?;
         }
 
-        Ok(())
+        checkpoint.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_ranges() {
+        let content =
+            b"Intro. See ISBN 3-16-148410-0 for details! Next.\n\n\
+            Second paragraph.";
+
+        let (lo, hi) = context(content, 11, 29, ContextUnit::Sentence);
+        assert_eq!(
+            &content[lo..hi],
+            b"See ISBN 3-16-148410-0 for details!"
+        );
+
+        let (lo, hi) = context(content, 11, 29, ContextUnit::Paragraph);
+        assert_eq!(
+            &content[lo..hi],
+            b"Intro. See ISBN 3-16-148410-0 for details! Next."
+        );
     }
 }
@@ -0,0 +1,34 @@
+use super::{Matcher, RefKind, Reference};
+use crate::plugins::Plugin;
+
+/// A matcher implemented by a WASM plugin (see `plugins` directory).
+pub(crate) struct WasmMatcher {
+    plugin: Plugin,
+}
+
+impl WasmMatcher {
+    pub(crate) fn new(plugin: Plugin) -> Self {
+        Self { plugin }
+    }
+}
+
+impl Matcher for WasmMatcher {
+    fn matches(&self, content: &[u8]) -> Vec<Reference> {
+        // A failing plugin (e.g. a trap or exhausted fuel) doesn't
+        // find any references.
+        self.plugin
+            .matches(content)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|m| m.start <= m.end && m.end <= content.len())
+            .map(|m| Reference {
+                kind: RefKind::Plugin(
+                    m.kind.unwrap_or_else(|| self.plugin.name().into()),
+                ),
+                value: m.value,
+                start: m.start,
+                end: m.end,
+            })
+            .collect()
+    }
+}
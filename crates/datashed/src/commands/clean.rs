@@ -1,21 +1,32 @@
-use std::fs::{remove_file, File};
+use std::fs::{metadata, remove_file};
 
 use clap::Parser;
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::Confirm;
-use glob::glob_with;
 use hashbrown::HashSet;
 use indicatif::ProgressIterator;
 use polars::prelude::*;
 
+use crate::atomic::AtomicFile;
+use crate::audit;
 use crate::datashed::Datashed;
 use crate::error::{DatashedError, DatashedResult};
+use crate::plan::Plan;
 use crate::progress::ProgressBarBuilder;
+use crate::stats::write_index;
+use crate::trash::Trash;
 use crate::utils::relpath;
 
 const PBAR_COLLECT: &str = "Collecting documents: {human_pos} | \
         elapsed: {elapsed_precise}{msg}";
 
+/// Remove untracked documents and missing index entries.
+///
+/// Untracked documents are moved into a new snapshot of the trash
+/// (`tmp/trash/<timestamp>/`), which also holds a copy of the index, if
+/// missing index entries are removed. The last run can be reverted by
+/// `datashed undo-clean`. Snapshots are removed by `datashed gc` once
+/// they are older than the trash retention window.
 #[derive(Debug, Default, Parser)]
 pub(crate) struct Clean {
     /// Run verbosely. Print additional progress information to the
@@ -32,6 +43,26 @@ pub(crate) struct Clean {
     /// Whether to confirm delete operations or not.
     #[arg(short, long)]
     force: bool,
+
+    /// Delete untracked documents permanently instead of moving them
+    /// into the trash. The index isn't backed up either.
+    #[arg(long)]
+    no_trash: bool,
+
+    /// Print a plan of the operations (counts, example paths and
+    /// affected bytes) without removing anything.
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// Wait for the index lock held by another process to be
+    /// released. By default (`--no-wait`), the command fails
+    /// immediately, if the index is locked.
+    #[arg(long, overrides_with = "no_wait")]
+    wait: bool,
+
+    /// Fail immediately, if the index is locked by another process.
+    #[arg(long, overrides_with = "wait")]
+    no_wait: bool,
 }
 
 impl Clean {
@@ -39,19 +70,20 @@ impl Clean {
         let datashed = Datashed::discover()?;
         let data_dir = datashed.data_dir();
         let base_dir = datashed.base_dir();
+        let _lock = datashed.lock(self.wait && !self.no_wait)?;
+        let matcher = datashed.config()?.discovery.matcher()?;
 
-        let pattern = format!("{}/**/*.txt", data_dir.display());
         let pbar =
             ProgressBarBuilder::new(PBAR_COLLECT, self.quiet).build();
 
+        let mut trash: Option<Trash> = None;
+        let mut affected = 0;
         let mut missing: Vec<_> = vec![];
-        let mut untracked: HashSet<_> =
-            glob_with(&pattern, Default::default())
-                .map_err(|e| DatashedError::Other(e.to_string()))?
-                .progress_with(pbar)
-                .filter_map(Result::ok)
-                .map(|path| relpath(path, base_dir))
-                .collect();
+        let mut untracked: HashSet<_> = matcher
+            .documents(base_dir, &data_dir)?
+            .progress_with(pbar)
+            .map(|path| path.map(|path| relpath(path, base_dir)))
+            .collect::<DatashedResult<_>>()?;
 
         let index = datashed.index()?;
         let path = index.column("path")?.str()?;
@@ -64,6 +96,37 @@ impl Clean {
             }
         }
 
+        if self.dry_run {
+            let mut plan = Plan::default();
+            let operation =
+                if self.no_trash { "delete" } else { "trash" };
+
+            let mut untracked: Vec<_> = untracked.into_iter().collect();
+            untracked.sort_unstable();
+
+            for relpath in untracked {
+                let size = metadata(base_dir.join(&relpath))?.len();
+                plan.add(operation, relpath, size);
+            }
+
+            for relpath in missing.iter() {
+                plan.add("remove index entry", *relpath, 0);
+            }
+
+            if !missing.is_empty() {
+                let path = base_dir.join(Datashed::INDEX);
+                let size = metadata(&path)?.len();
+                if !self.no_trash {
+                    plan.add("backup", Datashed::INDEX, size);
+                }
+
+                plan.add("rewrite", Datashed::INDEX, size);
+            }
+
+            plan.print()?;
+            return Ok(());
+        }
+
         if !untracked.is_empty() {
             let confirm = self.force
                 || Confirm::with_theme(&ColorfulTheme::default())
@@ -77,8 +140,19 @@ impl Clean {
                     .unwrap();
 
             if confirm {
+                if !self.no_trash {
+                    trash = Some(Trash::create(&datashed)?);
+                }
+
+                affected += untracked.len();
                 untracked.into_iter().try_for_each(|relpath| {
-                    remove_file(base_dir.join(relpath))?;
+                    match trash {
+                        Some(ref trash) => {
+                            trash.put(base_dir, &relpath)?
+                        }
+                        None => remove_file(base_dir.join(relpath))?,
+                    }
+
                     Ok::<_, DatashedError>(())
                 })?;
             }
@@ -97,6 +171,15 @@ impl Clean {
                     .unwrap();
 
             if confirm {
+                if trash.is_none() && !self.no_trash {
+                    trash = Some(Trash::create(&datashed)?);
+                }
+
+                if let Some(ref trash) = trash {
+                    trash.backup(base_dir, Datashed::INDEX)?;
+                }
+
+                affected += missing.len();
                 let missing = Series::from_iter(missing);
                 let mut df = index
                     .lazy()
@@ -104,12 +187,29 @@ impl Clean {
                     .collect()?;
 
                 let path = base_dir.join(Datashed::INDEX);
-                let mut writer = IpcWriter::new(File::create(path)?)
-                    .with_compression(Some(IpcCompression::ZSTD));
-                writer.finish(&mut df)?;
+                let mut out = AtomicFile::create(&path)?;
+                write_index(&mut df, &mut out)?;
+                out.commit()?;
+
+                if let Some(ref trash) = trash {
+                    trash.stamp_index(&path)?;
+                }
             }
         }
 
+        if let Some(trash) = trash {
+            if self.verbose {
+                eprintln!(
+                    "moved removed objects into '{}'.",
+                    trash.dir().display()
+                );
+            }
+        }
+
+        if affected > 0 {
+            audit::record(&datashed, "clean", affected)?;
+        }
+
         Ok(())
     }
 }
@@ -2,7 +2,7 @@ use std::net::IpAddr;
 
 use clap::Parser;
 
-use crate::config::Server;
+use crate::config::{parse_size, Server};
 use crate::prelude::*;
 
 /// Get and set datashed config options.
@@ -49,6 +49,8 @@ impl Config {
 
         let name = match self.name.as_str() {
             name if name == "runtime.num_jobs" => name,
+            name if name == "runtime.max_memory" => name,
+            name if name == "runtime.seed" => name,
             name if name == "server.address" => name,
             name if name == "server.port" => name,
             name => {
@@ -66,6 +68,39 @@ impl Config {
                         } else {
                             config.runtime = Some(Runtime {
                                 num_jobs: Some(value),
+                                ..Default::default()
+                            });
+                        }
+
+                        config.save()?;
+                    } else {
+                        bail!("invalid value `{value}`");
+                    }
+                }
+                "runtime.max_memory" => {
+                    if parse_size(&value).is_ok() {
+                        if let Some(ref mut runtime) = config.runtime {
+                            runtime.max_memory = Some(value);
+                        } else {
+                            config.runtime = Some(Runtime {
+                                max_memory: Some(value),
+                                ..Default::default()
+                            });
+                        }
+
+                        config.save()?;
+                    } else {
+                        bail!("invalid value `{value}`");
+                    }
+                }
+                "runtime.seed" => {
+                    if let Ok(value) = value.parse::<u64>() {
+                        if let Some(ref mut runtime) = config.runtime {
+                            runtime.seed = Some(value);
+                        } else {
+                            config.runtime = Some(Runtime {
+                                seed: Some(value),
+                                ..Default::default()
                             });
                         }
 
@@ -108,9 +143,28 @@ impl Config {
             }
         } else if self.unset {
             match name {
-                "runtime.num_jobs" => {
-                    config.runtime = None;
-                    config.save()?;
+                "runtime.num_jobs" | "runtime.max_memory"
+                | "runtime.seed" => {
+                    if let Some(ref mut runtime) = config.runtime {
+                        match name {
+                            "runtime.num_jobs" => {
+                                runtime.num_jobs = None
+                            }
+                            "runtime.max_memory" => {
+                                runtime.max_memory = None
+                            }
+                            _ => runtime.seed = None,
+                        }
+
+                        if runtime.num_jobs.is_none()
+                            && runtime.max_memory.is_none()
+                            && runtime.seed.is_none()
+                        {
+                            config.runtime = None;
+                        }
+
+                        config.save()?;
+                    }
                 }
                 "server.address" => {
                     if let Some(ref mut server) = config.server {
@@ -142,6 +196,14 @@ impl Config {
                         config.runtime.and_then(|rt| rt.num_jobs),
                     );
                 }
+                "runtime.max_memory" => print_option(
+                    name,
+                    config.runtime.and_then(|rt| rt.max_memory),
+                ),
+                "runtime.seed" => print_option(
+                    name,
+                    config.runtime.and_then(|rt| rt.seed),
+                ),
                 "server.address" => print_option(
                     name,
                     config.server.and_then(|srv| srv.address),
@@ -0,0 +1,105 @@
+use std::io::{stdin, stdout, BufRead};
+
+use clap::Parser;
+use csv::WriterBuilder;
+use hashbrown::HashSet;
+use polars::prelude::{col, DataType};
+
+use crate::prelude::*;
+use crate::sketch::BloomFilter;
+
+/// Test whether documents with the given PPNs are part of the index.
+///
+/// The test uses the Bloom filter `index.bloom`, which is written by
+/// `datashed index`. A negative answer is exact; a positive answer
+/// may be a false positive (with a probability of about 0.1%). With
+/// `--exact`, if there is no Bloom filter or if the index has been
+/// rewritten since (e.g. by `datashed merge`), the index is scanned
+/// instead. The result is written as CSV (`ppn,contains`) to
+/// the standard output.
+#[derive(Debug, Parser)]
+pub(crate) struct Contains {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Scan the index instead of using the Bloom filter, which rules
+    /// out false positives.
+    #[arg(long)]
+    exact: bool,
+
+    /// The PPNs to test. If no PPN is given, the PPNs are read from
+    /// the standard input (one per line).
+    ppns: Vec<String>,
+}
+
+/// A membership test.
+enum Lookup {
+    Bloom(BloomFilter),
+    Index(HashSet<String>),
+}
+
+/// Returns the set of all PPNs of the index.
+fn read_ppns(datashed: &Datashed) -> DatashedResult<HashSet<String>> {
+    let index =
+        datashed.index_lazy()?.select([col("idn")]).collect()?;
+    let idn = index.column("idn")?.cast(&DataType::String)?;
+
+    Ok(idn.str()?.into_iter().flatten().map(String::from).collect())
+}
+
+impl Contains {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let bloom = if self.exact { None } else { datashed.bloom()? };
+
+        let lookup = if let Some(filter) = bloom {
+            Lookup::Bloom(filter)
+        } else {
+            if self.verbose {
+                eprintln!("scanning the index.");
+            }
+
+            Lookup::Index(read_ppns(&datashed)?)
+        };
+
+        let ppns: Vec<String> = if self.ppns.is_empty() {
+            stdin()
+                .lock()
+                .lines()
+                .map(|line| line.map(|line| line.trim().to_string()))
+                .filter(
+                    |line| !matches!(line, Ok(line) if line.is_empty()),
+                )
+                .collect::<Result<_, _>>()?
+        } else {
+            self.ppns
+        };
+
+        let mut writer =
+            WriterBuilder::new().from_writer(stdout().lock());
+        writer.write_record(["ppn", "contains"])?;
+
+        for ppn in ppns.iter() {
+            let contains = match lookup {
+                Lookup::Bloom(ref filter) => filter.contains(ppn),
+                Lookup::Index(ref ppns) => ppns.contains(ppn),
+            };
+
+            writer.write_record([
+                ppn,
+                if contains { "true" } else { "false" },
+            ])?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
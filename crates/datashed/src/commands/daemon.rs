@@ -0,0 +1,301 @@
+use std::collections::BTreeMap;
+use std::env::current_exe;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::process;
+use std::thread::sleep;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+
+use crate::atomic::AtomicFile;
+use crate::config::Job;
+use crate::cron::CronExpr;
+use crate::prelude::*;
+
+/// Run the recurring jobs of the datashed.
+///
+/// The jobs are configured in the `[schedule]` table of the datashed
+/// config, e.g.:
+///
+/// ```toml
+/// [schedule.nightly-index]
+/// cron = "0 2 * * *"
+/// command = "index"
+/// args = ["--wait"]
+///
+/// [schedule.weekly-verify]
+/// cron = "0 4 * * 0"
+/// command = "verify"
+/// ```
+///
+/// Cron expressions are evaluated in UTC. Each job is executed as a
+/// separate `datashed` process in the root directory of the datashed;
+/// jobs are executed one after another. The output of a job is
+/// appended to `tmp/jobs/<name>.log` and the status of the last run of
+/// each job is recorded in `tmp/jobs/status.json`, which is exposed by
+/// `datashed serve` under `/admin/jobs`. The config is reloaded every
+/// minute; if it can't be read (or contains an invalid cron
+/// expression), the error is reported and the previous jobs are kept.
+#[derive(Debug, Parser)]
+pub(crate) struct Daemon {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Run the given job immediately and exit.
+    #[arg(long, value_name = "name")]
+    run: Option<String>,
+}
+
+/// The status of a job.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JobStatus {
+    /// The last run of the job.
+    last_run: Option<LastRun>,
+
+    /// The next scheduled run (seconds since the epoch).
+    next_run: Option<u64>,
+}
+
+/// The result of a job run.
+#[derive(Debug, Serialize, Deserialize)]
+struct LastRun {
+    /// The start of the run (seconds since the epoch).
+    started_at: u64,
+
+    /// The duration of the run (in seconds).
+    duration: f64,
+
+    /// Whether the run succeeded or not.
+    success: bool,
+
+    /// The exit code of the job process.
+    exit_code: Option<i32>,
+}
+
+type StatusMap = BTreeMap<String, JobStatus>;
+
+#[inline]
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Runs a job as a separate process and returns its status.
+fn run_job(
+    datashed: &Datashed,
+    name: &str,
+    job: &Job,
+) -> DatashedResult<LastRun> {
+    let jobs_dir = datashed.jobs_dir();
+    fs::create_dir_all(&jobs_dir)?;
+
+    let mut log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(jobs_dir.join(format!("{name}.log")))?;
+
+    let started_at = now();
+    writeln!(
+        log,
+        "--- {started_at}: datashed {} {}",
+        job.command,
+        job.args.join(" ")
+    )?;
+
+    let start = Instant::now();
+    let status = process::Command::new(current_exe()?)
+        .arg(&job.command)
+        .args(&job.args)
+        .current_dir(datashed.base_dir())
+        .stdout(log.try_clone()?)
+        .stderr(log.try_clone()?)
+        .status();
+
+    let (success, exit_code) = match status {
+        Ok(status) => (status.success(), status.code()),
+        Err(e) => {
+            writeln!(log, "error: unable to run job: {e}")?;
+            (false, None)
+        }
+    };
+
+    Ok(LastRun {
+        started_at,
+        duration: start.elapsed().as_secs_f64(),
+        success,
+        exit_code,
+    })
+}
+
+/// Reads the job status of the datashed.
+fn read_status(datashed: &Datashed) -> StatusMap {
+    fs::read(datashed.jobs_dir().join(Datashed::JOBS_STATUS))
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Writes the job status of the datashed.
+fn write_status(
+    datashed: &Datashed,
+    status: &StatusMap,
+) -> DatashedResult<()> {
+    let jobs_dir = datashed.jobs_dir();
+    fs::create_dir_all(&jobs_dir)?;
+
+    let mut out =
+        AtomicFile::create(jobs_dir.join(Datashed::JOBS_STATUS))?;
+    serde_json::to_writer_pretty(&mut out, status)
+        .map_err(DatashedError::other)?;
+    out.commit()?;
+    Ok(())
+}
+
+/// Parses the cron expressions of the configured jobs.
+fn jobs(
+    config: &Config,
+) -> DatashedResult<BTreeMap<String, (CronExpr, Job)>> {
+    config
+        .schedule
+        .iter()
+        .map(|(name, job)| {
+            let expr = job.cron.parse::<CronExpr>().map_err(|e| {
+                DatashedError::other(format!("job '{name}': {e}"))
+            })?;
+
+            Ok((name.clone(), (expr, job.clone())))
+        })
+        .collect()
+}
+
+impl Daemon {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let mut status = read_status(&datashed);
+
+        if let Some(ref name) = self.run {
+            let config = datashed.config()?;
+            let Some(job) = config.schedule.get(name) else {
+                bail!("unknown job '{name}'");
+            };
+
+            let result = run_job(&datashed, name, job)?;
+            let success = result.success;
+            status.entry(name.clone()).or_default().last_run =
+                Some(result);
+            write_status(&datashed, &status)?;
+
+            if !success {
+                bail!("job '{name}' failed");
+            }
+
+            return Ok(());
+        }
+
+        let mut scheduled: BTreeMap<String, (CronExpr, Job)> =
+            BTreeMap::new();
+        let mut next: BTreeMap<String, u64> = BTreeMap::new();
+
+        loop {
+            // An invalid config (or cron expression) must not stop the
+            // daemon; the previous job table is kept until the config
+            // has been fixed.
+            match datashed.config().and_then(|config| jobs(&config)) {
+                Ok(jobs) => {
+                    // recompute the next run of new and changed jobs
+                    next.retain(|name, _| {
+                        jobs.get(name).is_some_and(|(_, job)| {
+                            scheduled.get(name).is_some_and(
+                                |(_, prev)| prev.cron == job.cron,
+                            )
+                        })
+                    });
+                    status.retain(|name, _| jobs.contains_key(name));
+                    scheduled = jobs;
+                }
+                Err(e) => {
+                    if !self.quiet {
+                        eprintln!("error: unable to reload jobs: {e}");
+                    }
+                }
+            }
+
+            for (name, (expr, _)) in scheduled.iter() {
+                if !next.contains_key(name) {
+                    if let Some(t) = expr.next_after(now()) {
+                        next.insert(name.clone(), t);
+                    }
+                }
+            }
+
+            for (name, (expr, job)) in scheduled.iter() {
+                if next.get(name).is_none_or(|t| *t > now()) {
+                    continue;
+                }
+
+                if self.verbose {
+                    eprintln!("running job '{name}'.");
+                }
+
+                let log =
+                    datashed.jobs_dir().join(format!("{name}.log"));
+                match run_job(&datashed, name, job) {
+                    Ok(result) => {
+                        if !self.quiet && !result.success {
+                            eprintln!(
+                                "job '{name}' failed (see {}).",
+                                log.display()
+                            );
+                        }
+
+                        status
+                            .entry(name.clone())
+                            .or_default()
+                            .last_run = Some(result);
+                    }
+                    Err(e) => {
+                        if !self.quiet {
+                            eprintln!(
+                                "error: unable to run job '{name}': {e}"
+                            );
+                        }
+                    }
+                }
+
+                match expr.next_after(now()) {
+                    Some(t) => next.insert(name.clone(), t),
+                    None => next.remove(name),
+                };
+            }
+
+            for name in scheduled.keys() {
+                status.entry(name.clone()).or_default().next_run =
+                    next.get(name).copied();
+            }
+
+            if let Err(e) = write_status(&datashed, &status) {
+                if !self.quiet {
+                    eprintln!("error: unable to write job status: {e}");
+                }
+            }
+
+            let wait = next
+                .values()
+                .min()
+                .map_or(60, |t| t.saturating_sub(now()).clamp(1, 60));
+            sleep(Duration::from_secs(wait));
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use std::fs::{create_dir_all, write, File};
+use std::io::stdout;
+use std::path::PathBuf;
+
+use bstr::ByteSlice;
+use clap::Parser;
+use hashbrown::{HashMap, HashSet};
+use indicatif::ParallelProgressIterator;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::document::normalize_line;
+use crate::prelude::*;
+use crate::sql::select_where;
+
+const PBAR_COUNT: &str = "Counting lines: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+const PBAR_CLEAN: &str =
+    "Cleaning documents: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// Remove repeated headers, footers and other boilerplate text.
+///
+/// A line is considered boilerplate, if its normalized form (collapsed
+/// whitespace, digits replaced) occurs in at least `--min-docs`
+/// documents of the corpus. The cleaned copies of the documents are
+/// written into the destination directory (relative to the root
+/// directory of the datashed), retaining their relative path; the
+/// original documents are left untouched. Bytes of the documents,
+/// which aren't valid UTF-8, are copied unchanged. The report
+/// contains the columns `path`, `size`, `removed` (the number of
+/// removed bytes) and `lines` (the number of removed lines).
+#[derive(Debug, Parser)]
+pub(crate) struct Deboilerplate {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The minimum number of documents a line must occur in to be
+    /// considered boilerplate.
+    #[arg(long, default_value = "5", value_name = "n")]
+    min_docs: u32,
+
+    /// Ignore lines with less than `n` characters (after
+    /// normalization). Empty lines are never removed.
+    #[arg(long, default_value = "1", value_name = "n")]
+    min_line_len: usize,
+
+    /// The destination directory of the cleaned documents. A relative
+    /// path is resolved against the root directory of the datashed.
+    #[arg(short = 'C', long = "directory", default_value = "derived")]
+    dest: PathBuf,
+
+    /// Write the report into `filename` instead of `stdout`.
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    /// An optional predicate to filter the document-set.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+}
+
+/// Returns true, if the normalized line is long enough to be
+/// considered boilerplate.
+fn is_candidate(line: &str, min_line_len: usize) -> bool {
+    let len = line.chars().count();
+    len > 0 && len >= min_line_len
+}
+
+/// Removes the boilerplate lines of a text. All other lines are copied
+/// byte by byte. Returns the cleaned text and the number of removed
+/// lines.
+fn strip(
+    text: &[u8],
+    boilerplate: &HashSet<String>,
+    min_line_len: usize,
+) -> (Vec<u8>, u64) {
+    let mut cleaned = Vec::with_capacity(text.len());
+    let mut lines = 0;
+
+    for line in text.lines_with_terminator() {
+        let normalized = normalize_line(&line.to_str_lossy());
+        if is_candidate(&normalized, min_line_len)
+            && boilerplate.contains(&normalized)
+        {
+            lines += 1;
+        } else {
+            cleaned.extend_from_slice(line);
+        }
+    }
+
+    (cleaned, lines)
+}
+
+impl Deboilerplate {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let base_dir = datashed.base_dir();
+        let mut index = datashed.index_lazy()?;
+
+        if let Some(ref predicate) = self.predicate {
+            let mut ctx = SQLContext::new();
+            ctx.register("df", index);
+            index = ctx.execute(&select_where(predicate))?;
+        }
+
+        let df = index.select([col("path")]).collect()?;
+
+        let n = df.height();
+        let path = df.column("path")?.str()?;
+        let dest_dir = base_dir.join(&self.dest);
+
+        let pbar = ProgressBarBuilder::new(PBAR_COUNT, self.quiet)
+            .len(n as u64)
+            .build();
+
+        let freqs = (0..n)
+            .into_par_iter()
+            .progress_with(pbar)
+            .map(|idx| -> DatashedResult<HashMap<String, u32>> {
+                let path = path.get(idx).unwrap();
+                let doc = Document::from_path(base_dir.join(path))?;
+
+                Ok(doc
+                    .as_ref()
+                    .to_str_lossy()
+                    .lines()
+                    .map(normalize_line)
+                    .filter(|line| {
+                        is_candidate(line, self.min_line_len)
+                    })
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .map(|line| (line, 1))
+                    .collect())
+            })
+            .try_reduce(HashMap::new, |mut acc, rhs| {
+                for (line, df) in rhs.into_iter() {
+                    *acc.entry(line).or_default() += df;
+                }
+
+                Ok(acc)
+            })?;
+
+        let boilerplate: HashSet<String> = freqs
+            .into_iter()
+            .filter(|(_, df)| *df >= self.min_docs)
+            .map(|(line, _)| line)
+            .collect();
+
+        if self.verbose {
+            eprintln!("found {} boilerplate lines.", boilerplate.len());
+        }
+
+        let pbar = ProgressBarBuilder::new(PBAR_CLEAN, self.quiet)
+            .len(n as u64)
+            .build();
+
+        let rows = (0..n)
+            .into_par_iter()
+            .progress_with(pbar)
+            .map(|idx| -> DatashedResult<(u64, u64, u64)> {
+                let path = path.get(idx).unwrap();
+                let doc = Document::from_path(base_dir.join(path))?;
+                let text = doc.as_ref();
+                let (cleaned, lines) =
+                    strip(text, &boilerplate, self.min_line_len);
+
+                let dest = dest_dir.join(path);
+                if let Some(parent) = dest.parent() {
+                    create_dir_all(parent)?;
+                }
+
+                write(dest, &cleaned)?;
+                Ok((
+                    doc.size(),
+                    (text.len() - cleaned.len()) as u64,
+                    lines,
+                ))
+            })
+            .collect::<DatashedResult<Vec<_>>>()?;
+
+        let mut size = Vec::with_capacity(n);
+        let mut removed = Vec::with_capacity(n);
+        let mut lines = Vec::with_capacity(n);
+
+        for row in rows.into_iter() {
+            size.push(row.0);
+            removed.push(row.1);
+            lines.push(row.2);
+        }
+
+        if self.verbose {
+            eprintln!(
+                "removed {} bytes in total.",
+                removed.iter().sum::<u64>()
+            );
+        }
+
+        let mut df = DataFrame::new(vec![
+            df.column("path")?.clone(),
+            Column::new("size".into(), size),
+            Column::new("removed".into(), removed),
+            Column::new("lines".into(), lines),
+        ])?;
+
+        if let Some(path) = self.output {
+            let mut writer = IpcWriter::new(File::create(path)?)
+                .with_compression(Some(IpcCompression::ZSTD));
+            writer.finish(&mut df)?;
+        } else {
+            let mut writer = CsvWriter::new(stdout().lock());
+            writer.finish(&mut df)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_boilerplate() {
+        let boilerplate: HashSet<String> =
+            ["Page 0".to_string(), "x".to_string()].into();
+
+        let text = b"Page   1\nfoo \xff bar\nx\n\nPage 1";
+        let (cleaned, lines) = strip(text, &boilerplate, 2);
+        assert_eq!(cleaned, b"foo \xff bar\nx\n\n");
+        assert_eq!(lines, 2);
+
+        let (cleaned, lines) = strip(text, &boilerplate, 1);
+        assert_eq!(cleaned, b"foo \xff bar\n\n");
+        assert_eq!(lines, 3);
+    }
+}
@@ -0,0 +1,290 @@
+use std::fs::{create_dir_all, write, File};
+use std::io::{stdout, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use bstr::ByteSlice;
+use clap::Parser;
+use flate2::read::GzDecoder;
+use indicatif::ParallelProgressIterator;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use rayon::prelude::*;
+use similar::{ChangeTag, TextDiff};
+
+use crate::crypto;
+use crate::prelude::*;
+use crate::sql::select_where;
+
+const PBAR_DIFF: &str =
+    "Comparing documents: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// Compare the documents with those of an older release.
+///
+/// The older release is either a datashed directory or a datashed
+/// archive (see `datashed archive`). For each document present in both
+/// releases with differing hashes, the number of added and removed
+/// lines and the similarity ratio (in the range `[0, 1]`) are reported.
+/// The result is a table with the columns `path`, `hash_old`, `hash`,
+/// `added`, `removed` and `similarity`, sorted by similarity.
+#[derive(Debug, Parser)]
+pub(crate) struct DiffDocs {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The age key file used to decrypt an encrypted archive.
+    #[arg(short, long, value_name = "filename")]
+    key_file: Option<PathBuf>,
+
+    /// Write a unified diff of each changed document into `dir`. The
+    /// diffs are stored under the path of the document with the
+    /// extension `.diff`.
+    #[arg(long, value_name = "dir")]
+    diff_dir: Option<PathBuf>,
+
+    /// The number of context lines of the unified diffs.
+    #[arg(long, default_value = "3", value_name = "n")]
+    context: usize,
+
+    /// Write output into `filename` instead of `stdout`.
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    #[command(flatten)]
+    columns: Columns,
+
+    /// An optional predicate to filter the document-set.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+
+    /// The older release (a datashed directory or archive).
+    other: PathBuf,
+}
+
+type Row = (String, String, String, u64, u64, f32);
+
+/// Returns the number of added and removed lines of a diff.
+fn line_changes(diff: &TextDiff<'_, '_, '_, str>) -> (u64, u64) {
+    diff.iter_all_changes()
+        .fold((0, 0), |(added, removed), change| match change.tag() {
+            ChangeTag::Insert => (added + 1, removed),
+            ChangeTag::Delete => (added, removed + 1),
+            ChangeTag::Equal => (added, removed),
+        })
+}
+
+impl DiffDocs {
+    /// Unpacks the archive into the given directory.
+    fn unpack(&self, dest: &Path) -> DatashedResult<()> {
+        let mut reader = BufReader::new(File::open(&self.other)?);
+        let reader: Box<dyn Read> =
+            if crypto::is_encrypted(&mut reader)? {
+                let identities = match self.key_file {
+                    Some(ref path) => crypto::read_identities(path)?,
+                    None => vec![],
+                };
+
+                crypto::decrypt(reader, &identities)?
+            } else {
+                Box::new(reader)
+            };
+
+        create_dir_all(dest)?;
+        tar::Archive::new(GzDecoder::new(reader)).unpack(dest)?;
+        Ok(())
+    }
+
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let index = datashed.index()?;
+
+        // The archive is unpacked into a scratch directory, which is
+        // removed when the guard is dropped (even on errors).
+        let unpacked = if self.other.is_file() {
+            let temp_dir = datashed.temp_dir();
+            create_dir_all(&temp_dir)?;
+
+            let dest = tempfile::Builder::new()
+                .prefix("diff-docs-")
+                .tempdir_in(temp_dir)?;
+
+            if self.verbose {
+                eprintln!(
+                    "unpacking archive '{}'.",
+                    self.other.display()
+                );
+            }
+
+            self.unpack(dest.path())?;
+            Some(dest)
+        } else {
+            None
+        };
+
+        let result = self.compare(
+            &datashed,
+            index,
+            unpacked
+                .as_ref()
+                .map_or(self.other.as_path(), |dir| dir.path()),
+        );
+
+        if let Some(dir) = unpacked {
+            dir.close()?;
+        }
+
+        let mut df = self.columns.project(result?, &datashed)?;
+        if let Some(ref path) = self.output {
+            let mut writer = IpcWriter::new(File::create(path)?)
+                .with_compression(Some(IpcCompression::ZSTD));
+            writer.finish(&mut df)?;
+        } else {
+            let mut writer = CsvWriter::new(stdout().lock());
+            writer.finish(&mut df)?;
+        }
+
+        Ok(())
+    }
+
+    fn compare(
+        &self,
+        datashed: &Datashed,
+        index: DataFrame,
+        other: &Path,
+    ) -> DatashedResult<DataFrame> {
+        let other = Datashed::from_path(other)?;
+        let base_dir = datashed.base_dir();
+        let other_dir = other.base_dir();
+
+        let index: DataFrame =
+            if let Some(ref predicate) = self.predicate {
+                let mut ctx = SQLContext::new();
+                ctx.register("df", index.lazy());
+                ctx.execute(&select_where(predicate))?.collect()?
+            } else {
+                index
+            };
+
+        let df = index
+            .lazy()
+            .select([col("path"), col("hash")])
+            .join(
+                other.index()?.lazy().select([
+                    col("path"),
+                    col("hash").alias("hash_old"),
+                ]),
+                [col("path")],
+                [col("path")],
+                JoinArgs::new(JoinType::Inner),
+            )
+            .filter(col("hash").neq(col("hash_old")))
+            .collect()?;
+
+        if self.verbose {
+            eprintln!("found {} changed documents.", df.height());
+        }
+
+        let path = df.column("path")?.str()?;
+        let hash = df.column("hash")?.str()?;
+        let hash_old = df.column("hash_old")?.str()?;
+
+        let pbar = ProgressBarBuilder::new(PBAR_DIFF, self.quiet)
+            .len(df.height() as u64)
+            .build();
+
+        let rows = (0..df.height())
+            .into_par_iter()
+            .progress_with(pbar)
+            .map(|idx| -> DatashedResult<Row> {
+                let path = path.get(idx).unwrap();
+                let old = Document::from_path(other_dir.join(path))?;
+                let new = Document::from_path(base_dir.join(path))?;
+                let old = old.as_ref().to_str_lossy();
+                let new = new.as_ref().to_str_lossy();
+
+                let diff = TextDiff::from_lines(&*old, &*new);
+                let (added, removed) = line_changes(&diff);
+
+                if let Some(ref diff_dir) = self.diff_dir {
+                    let mut filename =
+                        diff_dir.join(path).into_os_string();
+                    filename.push(".diff");
+                    let filename = PathBuf::from(filename);
+                    if let Some(parent) = filename.parent() {
+                        create_dir_all(parent)?;
+                    }
+
+                    let unified = diff
+                        .unified_diff()
+                        .context_radius(self.context)
+                        .header(
+                            &format!("a/{path}"),
+                            &format!("b/{path}"),
+                        )
+                        .to_string();
+                    write(filename, unified)?;
+                }
+
+                Ok((
+                    path.to_string(),
+                    hash_old.get(idx).unwrap().to_string(),
+                    hash.get(idx).unwrap().to_string(),
+                    added,
+                    removed,
+                    diff.ratio(),
+                ))
+            })
+            .collect::<DatashedResult<Vec<_>>>()?;
+
+        let mut paths = Vec::with_capacity(rows.len());
+        let mut old_hashes = Vec::with_capacity(rows.len());
+        let mut hashes = Vec::with_capacity(rows.len());
+        let mut added = Vec::with_capacity(rows.len());
+        let mut removed = Vec::with_capacity(rows.len());
+        let mut similarity = Vec::with_capacity(rows.len());
+
+        for row in rows.into_iter() {
+            paths.push(row.0);
+            old_hashes.push(row.1);
+            hashes.push(row.2);
+            added.push(row.3);
+            removed.push(row.4);
+            similarity.push(row.5);
+        }
+
+        let df = DataFrame::new(vec![
+            Column::new("path".into(), paths),
+            Column::new("hash_old".into(), old_hashes),
+            Column::new("hash".into(), hashes),
+            Column::new("added".into(), added),
+            Column::new("removed".into(), removed),
+            Column::new("similarity".into(), similarity),
+        ])?;
+
+        Ok(df.sort(["similarity"], Default::default())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_docs_line_changes() {
+        let diff = TextDiff::from_lines("a\nb\nc\n", "a\nB\nc\nd\n");
+        assert_eq!(line_changes(&diff), (2, 1));
+        assert!(diff.ratio() < 1.0);
+
+        let diff = TextDiff::from_lines("a\nb\n", "a\nb\n");
+        assert_eq!(line_changes(&diff), (0, 0));
+        assert_eq!(diff.ratio(), 1.0);
+    }
+}
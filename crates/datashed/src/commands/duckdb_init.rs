@@ -0,0 +1,318 @@
+use std::ffi::OsStr;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use polars::prelude::*;
+
+use crate::prelude::*;
+use crate::ratings::SERVE_COLUMNS;
+
+/// The columns of the `documents` and `pages` views.
+const DOCUMENT_COLUMNS: [&str; 29] = [
+    "remote",
+    "path",
+    "idn",
+    "kind",
+    "msc",
+    "msc_all",
+    "license",
+    "first_entered",
+    "last_changed",
+    "lang_code",
+    "lang_score",
+    "script",
+    "rtl_ratio",
+    "lfreq",
+    "perplexity",
+    "alpha",
+    "words",
+    "avg_word_len",
+    "ttr",
+    "digit_ratio",
+    "currency_token_ratio",
+    "year_mention_count",
+    "repetition",
+    "suspicious",
+    "size",
+    "strlen",
+    "mtime",
+    "hash",
+    "link_target",
+];
+
+/// The columns of the `bibrefs` view.
+const BIBREFS_COLUMNS: [&str; 5] =
+    ["path", "type", "value", "start", "end"];
+
+/// The columns of the `vocab` view.
+const VOCAB_COLUMNS: [&str; 3] = ["token", "tf", "df"];
+
+/// Generate a DuckDB init script for the datashed.
+///
+/// The script creates a schema named after the datashed and the
+/// following views, which have a stable set of columns (columns
+/// missing in the underlying file are `NULL`):
+///
+///   * `documents` (index): remote, path, idn, kind, msc, msc_all,
+///     license, first_entered, last_changed, lang_code, lang_score,
+///     script, rtl_ratio, lfreq, perplexity, alpha, words,
+///     avg_word_len, ttr, digit_ratio, currency_token_ratio,
+///     year_mention_count, repetition, suspicious, size, strlen,
+///     mtime, hash, link_target
+///   * `pages` (page index, if available): the columns of `documents`
+///     and page_no
+///   * `ratings` (ratings collected by `datashed serve`): remote, path,
+///     hash, rating, comment, username, created_at
+///   * `bibrefs` (with `--bibrefs`): path, type, value, start, end
+///   * `vocab` (with `--vocab`): token, tf, df
+///
+/// IPC files are scanned with the `read_arrow` function of the DuckDB
+/// `arrow` extension; Parquet and CSV files are scanned natively. The
+/// script can be loaded with `duckdb -init <script>` or `.read
+/// <script>`.
+#[derive(Debug, Parser)]
+pub(crate) struct DuckdbInit {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The name of the schema. By default, the (normalized) name of
+    /// the datashed is used.
+    #[arg(long, value_name = "name")]
+    schema: Option<String>,
+
+    /// A table of bibliographic references (see `datashed bibrefs`).
+    #[arg(long, value_name = "filename")]
+    bibrefs: Option<PathBuf>,
+
+    /// A vocabulary (see `datashed vocab`).
+    #[arg(long, value_name = "filename")]
+    vocab: Option<PathBuf>,
+
+    /// Write output into `filename` instead of `stdout`.
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+}
+
+/// Quotes a string literal.
+fn literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Quotes an identifier.
+fn ident(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+/// Returns the table function, which scans the given file.
+fn scan(path: &Path) -> DatashedResult<String> {
+    let extension = path.extension().and_then(OsStr::to_str);
+    let path = literal(&path.canonicalize()?.to_string_lossy());
+
+    Ok(match extension {
+        Some("csv") => format!("read_csv({path}, all_varchar = true)"),
+        Some("parquet") => format!("read_parquet({path})"),
+        _ => format!("read_arrow({path})"),
+    })
+}
+
+/// Returns the statement, which creates a view with the given columns.
+/// Columns which aren't contained in `available` are `NULL`.
+fn view(
+    schema: &str,
+    name: &str,
+    columns: &[&str],
+    available: &[String],
+    source: &str,
+) -> String {
+    let columns = columns
+        .iter()
+        .map(|column| {
+            if available.iter().any(|name| name == column) {
+                ident(column)
+            } else {
+                format!("NULL AS {}", ident(column))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        "CREATE OR REPLACE VIEW {}.{} AS SELECT {columns} FROM {source};\n",
+        ident(schema),
+        ident(name)
+    )
+}
+
+/// Returns the column names of an IPC, Parquet or CSV file.
+fn column_names(path: &Path) -> DatashedResult<Vec<String>> {
+    let schema = match path.extension().and_then(OsStr::to_str) {
+        Some("csv") => CsvReadOptions::default()
+            .with_n_rows(Some(1))
+            .try_into_reader_with_file_path(Some(path.into()))?
+            .finish()?
+            .schema(),
+        Some("parquet") => {
+            LazyFrame::scan_parquet(path, ScanArgsParquet::default())?
+                .collect_schema()?
+                .as_ref()
+                .clone()
+        }
+        _ => IpcReader::new(File::open(path)?)
+            .with_n_rows(Some(0))
+            .finish()?
+            .schema(),
+    };
+
+    Ok(schema.iter_names().map(ToString::to_string).collect())
+}
+
+impl DuckdbInit {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let config = datashed.config()?;
+        let base_dir = datashed.base_dir();
+
+        let schema = self.schema.unwrap_or_else(|| {
+            config
+                .metadata
+                .name
+                .to_lowercase()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        });
+
+        let schema = if schema.is_empty() {
+            "datashed".to_string()
+        } else {
+            schema
+        };
+
+        let mut script = String::new();
+        let _ = writeln!(
+            script,
+            "-- DuckDB init script of the datashed '{}'.\n\
+            INSTALL arrow FROM community;\nLOAD arrow;\n\
+            CREATE SCHEMA IF NOT EXISTS {};",
+            config.metadata.name,
+            ident(&schema)
+        );
+
+        let index = base_dir.join(Datashed::INDEX);
+        script.push_str(&view(
+            &schema,
+            "documents",
+            &DOCUMENT_COLUMNS,
+            &column_names(&index)?,
+            &scan(&index)?,
+        ));
+
+        let pages = base_dir.join(Datashed::PAGES);
+        if pages.is_file() {
+            let mut columns = DOCUMENT_COLUMNS.to_vec();
+            columns.insert(3, "page_no");
+            script.push_str(&view(
+                &schema,
+                "pages",
+                &columns,
+                &column_names(&pages)?,
+                &scan(&pages)?,
+            ));
+        }
+
+        let ratings = datashed.temp_dir().join(Datashed::RATINGS);
+        if ratings.is_file() {
+            let source = format!(
+                "read_csv({}, header = false, all_varchar = true, \
+                    names = [{}])",
+                literal(&ratings.canonicalize()?.to_string_lossy()),
+                SERVE_COLUMNS
+                    .iter()
+                    .map(|name| literal(name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+
+            let available: Vec<String> =
+                SERVE_COLUMNS.iter().map(ToString::to_string).collect();
+            script.push_str(&view(
+                &schema,
+                "ratings",
+                &SERVE_COLUMNS,
+                &available,
+                &source,
+            ));
+        }
+
+        for (name, path, columns) in [
+            ("bibrefs", &self.bibrefs, &BIBREFS_COLUMNS[..]),
+            ("vocab", &self.vocab, &VOCAB_COLUMNS[..]),
+        ] {
+            let Some(path) = path else {
+                continue;
+            };
+
+            // If the schema of the file can't be read (e.g. the file
+            // is missing or corrupt), all columns are assumed to be
+            // present.
+            let available = match column_names(path) {
+                Ok(names) => names,
+                Err(_) => {
+                    columns.iter().map(ToString::to_string).collect()
+                }
+            };
+
+            script.push_str(&view(
+                &schema,
+                name,
+                columns,
+                &available,
+                &scan(path)?,
+            ));
+        }
+
+        let mut out: Box<dyn Write> = match self.output {
+            Some(ref path) => Box::new(File::create(path)?),
+            None => Box::new(stdout().lock()),
+        };
+
+        out.write_all(script.as_bytes())?;
+        out.flush()?;
+
+        if self.verbose {
+            eprintln!("created views in schema '{schema}'.");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_view() {
+        assert_eq!(
+            view(
+                "my_shed",
+                "vocab",
+                &VOCAB_COLUMNS,
+                &["token".into(), "tf".into()],
+                "read_arrow('/tmp/it''s.ipc')"
+            ),
+            "CREATE OR REPLACE VIEW \"my_shed\".\"vocab\" AS SELECT \
+            \"token\", \"tf\", NULL AS \"df\" FROM \
+            read_arrow('/tmp/it''s.ipc');\n"
+        );
+    }
+}
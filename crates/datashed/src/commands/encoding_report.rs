@@ -0,0 +1,291 @@
+use std::fs::{read, File};
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+
+use bstr::ByteSlice;
+use clap::Parser;
+use encoding_rs::WINDOWS_1252;
+use indicatif::ParallelProgressIterator;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use rayon::prelude::*;
+
+use crate::atomic::AtomicFile;
+use crate::prelude::*;
+use crate::sql::select_where;
+
+const PBAR_SCAN: &str =
+    "Scanning documents: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// The anomalies found in a single document.
+#[derive(Debug, Default, PartialEq)]
+struct Anomalies {
+    /// The number of invalid UTF-8 sequences.
+    invalid: u64,
+    /// The number of replacement characters (U+FFFD).
+    replacement: u64,
+    /// The number of unusual control characters.
+    control: u64,
+    /// The number of mojibake sequences (UTF-8 decoded as Latin-1 and
+    /// re-encoded as UTF-8, e.g. `Ã¤` instead of `ä`).
+    mojibake: u64,
+    /// The byte offsets of the first anomalies.
+    positions: Vec<usize>,
+}
+
+impl Anomalies {
+    fn is_empty(&self) -> bool {
+        self.invalid + self.replacement + self.control + self.mojibake
+            == 0
+    }
+
+    fn push(&mut self, pos: usize, max_positions: usize) {
+        if self.positions.len() < max_positions {
+            self.positions.push(pos);
+        }
+    }
+}
+
+/// Scans the content of a document for encoding anomalies.
+fn scan(buf: &[u8], max_positions: usize) -> Anomalies {
+    let mut result = Anomalies::default();
+    let mut offset = 0;
+
+    for chunk in buf.utf8_chunks() {
+        let mut prev: Option<char> = None;
+        for (pos, c) in chunk.valid().char_indices() {
+            match c {
+                '\u{FFFD}' => {
+                    result.replacement += 1;
+                    result.push(offset + pos, max_positions);
+                }
+                '\t' | '\n' | '\r' | '\x0c' => (),
+                '\u{80}'..='\u{bf}'
+                    if matches!(prev, Some('Ã' | 'Â')) =>
+                {
+                    result.mojibake += 1;
+                    result.push(offset + pos - 2, max_positions);
+                }
+                c if c.is_control() => {
+                    result.control += 1;
+                    result.push(offset + pos, max_positions);
+                }
+                _ => (),
+            }
+
+            prev = Some(c);
+        }
+
+        offset += chunk.valid().len();
+        if !chunk.invalid().is_empty() {
+            result.invalid += 1;
+            result.push(offset, max_positions);
+            offset += chunk.invalid().len();
+        }
+    }
+
+    result
+}
+
+/// Transcodes all invalid UTF-8 sequences from Windows-1252 (a superset
+/// of Latin-1) to UTF-8. Valid UTF-8 sequences are left untouched,
+/// which allows to repair documents with mixed encodings.
+fn transcode(buf: &[u8]) -> String {
+    let mut result = String::with_capacity(buf.len());
+    for chunk in buf.utf8_chunks() {
+        result.push_str(chunk.valid());
+        if !chunk.invalid().is_empty() {
+            let (s, _) = WINDOWS_1252
+                .decode_without_bom_handling(chunk.invalid());
+            result.push_str(&s);
+        }
+    }
+
+    result
+}
+
+/// Report invalid UTF-8 sequences and other encoding anomalies.
+///
+/// Each document is scanned for invalid UTF-8 sequences, replacement
+/// characters (U+FFFD), unusual control characters and mojibake
+/// (mixed encodings). The result is a table with the columns `path`,
+/// `invalid`, `replacement`, `control`, `mojibake` and `positions` (the
+/// byte offsets of the first anomalies), which contains only documents
+/// with at least one anomaly.
+#[derive(Debug, Parser)]
+pub(crate) struct EncodingReport {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The maximum number of positions reported per document.
+    #[arg(long, default_value = "10", value_name = "n")]
+    max_positions: usize,
+
+    /// Transcode invalid UTF-8 sequences from Latin-1/Windows-1252 to
+    /// UTF-8 (in-place). Since the hashes of the fixed documents
+    /// change, the index must be rebuilt afterwards.
+    #[arg(long)]
+    fix: bool,
+
+    /// Wait for the index lock held by another process to be
+    /// released. By default (`--no-wait`), the command fails
+    /// immediately, if the index is locked. The lock is only
+    /// acquired with `--fix`.
+    #[arg(long, overrides_with = "no_wait")]
+    wait: bool,
+
+    /// Fail immediately, if the index is locked by another process.
+    #[arg(long, overrides_with = "wait")]
+    no_wait: bool,
+
+    /// Write output into `filename` instead of `stdout`.
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    /// An optional predicate to filter the document-set.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+}
+
+type Row = (String, Anomalies);
+
+impl EncodingReport {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let base_dir = datashed.base_dir();
+        let _lock = if self.fix {
+            Some(datashed.lock(self.wait && !self.no_wait)?)
+        } else {
+            None
+        };
+
+        let mut index = datashed.index_lazy()?;
+
+        if let Some(predicate) = self.predicate {
+            let mut ctx = SQLContext::new();
+            ctx.register("df", index);
+            index = ctx.execute(&select_where(&predicate))?;
+        }
+
+        let df = index.select([col("path")]).collect()?;
+
+        let path = df.column("path")?.str()?;
+        let pbar = ProgressBarBuilder::new(PBAR_SCAN, self.quiet)
+            .len(df.height() as u64)
+            .build();
+
+        let rows = (0..df.height())
+            .into_par_iter()
+            .progress_with(pbar)
+            .map(|idx| -> DatashedResult<Option<Row>> {
+                let path = path.get(idx).unwrap();
+                let filename = base_dir.join(path);
+                let buf = read(&filename)?;
+
+                let anomalies = scan(&buf, self.max_positions);
+                if anomalies.is_empty() {
+                    return Ok(None);
+                }
+
+                if self.fix && anomalies.invalid > 0 {
+                    let mut out = AtomicFile::create(&filename)?;
+                    out.write_all(transcode(&buf).as_bytes())?;
+                    out.commit()?;
+                }
+
+                Ok(Some((path.to_string(), anomalies)))
+            })
+            .collect::<DatashedResult<Vec<_>>>()?;
+
+        let rows: Vec<Row> = rows.into_iter().flatten().collect();
+        let mut paths = Vec::with_capacity(rows.len());
+        let mut invalid = Vec::with_capacity(rows.len());
+        let mut replacement = Vec::with_capacity(rows.len());
+        let mut control = Vec::with_capacity(rows.len());
+        let mut mojibake = Vec::with_capacity(rows.len());
+        let mut positions = Vec::with_capacity(rows.len());
+
+        for (path, anomalies) in rows.into_iter() {
+            paths.push(path);
+            invalid.push(anomalies.invalid);
+            replacement.push(anomalies.replacement);
+            control.push(anomalies.control);
+            mojibake.push(anomalies.mojibake);
+            positions.push(
+                anomalies
+                    .positions
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            );
+        }
+
+        let fixed = invalid.iter().filter(|n| **n > 0).count();
+        if self.verbose {
+            eprintln!(
+                "found {} documents with anomalies.",
+                paths.len()
+            );
+        }
+
+        if self.fix && fixed > 0 && !self.quiet {
+            eprintln!(
+                "Transcoded {fixed} documents. Update the index with \
+                `datashed index`."
+            );
+        }
+
+        let mut df = DataFrame::new(vec![
+            Column::new("path".into(), paths),
+            Column::new("invalid".into(), invalid),
+            Column::new("replacement".into(), replacement),
+            Column::new("control".into(), control),
+            Column::new("mojibake".into(), mojibake),
+            Column::new("positions".into(), positions),
+        ])?;
+
+        if let Some(path) = self.output {
+            let mut writer = IpcWriter::new(File::create(path)?)
+                .with_compression(Some(IpcCompression::ZSTD));
+            writer.finish(&mut df)?;
+        } else {
+            let mut writer = CsvWriter::new(stdout().lock());
+            writer.finish(&mut df)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_and_transcode() {
+        let buf = b"Gr\xfc\xdfe \xc3\x83\xc2\xa4 \x07 \xef\xbf\xbd";
+        assert_eq!(
+            scan(buf, 10),
+            Anomalies {
+                invalid: 2,
+                replacement: 1,
+                control: 1,
+                mojibake: 1,
+                positions: vec![2, 3, 6, 11, 13],
+            }
+        );
+
+        assert_eq!(transcode(b"Gr\xfc\xdfe K\xc3\xb6ln"), "Grüße Köln");
+        assert!(scan("Grüße\n\tKöln".as_bytes(), 10).is_empty());
+    }
+}
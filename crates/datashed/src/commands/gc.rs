@@ -0,0 +1,374 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::Parser;
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use hashbrown::HashMap;
+use indicatif::HumanBytes;
+
+use crate::atomic::AtomicFile;
+use crate::audit;
+use crate::prelude::*;
+use crate::ratings::SERVE_COLUMNS;
+use crate::trash::Trash;
+use crate::utils::state_dir;
+
+/// Files of the temp directory, which are in use by `datashed serve`.
+const LIVE_FILES: [&str; 5] = [
+    Datashed::RATINGS,
+    Datashed::RATINGS_LOCK,
+    Datashed::ACCESS_LOG,
+    Datashed::JOBS_DIR,
+    Datashed::TRASH_DIR,
+];
+
+/// Remove unreferenced objects from internal directories.
+///
+/// The following objects are collected:
+///
+///   * entries of the temp directory (e.g. left-over working
+///     directories of interrupted commands), which weren't modified
+///     within the retention window,
+///   * snapshots of the trash (written by `datashed clean`), which are
+///     older than the trash retention window,
+///   * temporary ratings (written by `datashed serve`), which are older
+///     than the retention window and refer to a document version that
+///     is no longer part of the index,
+///   * session entries of `datashed rate` (stored in the state
+///     directory) of this datashed, which refer to a document version
+///     that is no longer part of the index.
+///
+/// This command fails, if the ratings store is in use by another
+/// process (e.g. while the datashed is served).
+#[derive(Debug, Parser)]
+pub(crate) struct Gc {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Only report what would be removed, without removing anything.
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// The retention window (in days). Objects which are younger than
+    /// the retention window are kept.
+    #[arg(long, value_name = "days", default_value = "7")]
+    retention: u64,
+
+    /// The retention window (in days) of the trash. Snapshots of the
+    /// trash which are younger than the retention window are kept.
+    #[arg(long, value_name = "days", default_value = "30")]
+    trash_retention: u64,
+}
+
+/// Returns the (recursive) size of a file or directory.
+fn disk_usage(path: &Path) -> DatashedResult<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    fs::read_dir(path)?
+        .try_fold(0, |acc, entry| Ok(acc + disk_usage(&entry?.path())?))
+}
+
+/// Returns `true` if the version (path and hash) of a document is part
+/// of the index. Ratings may refer to a prefix of the hash only.
+fn is_referenced(
+    index: &HashMap<String, String>,
+    path: &str,
+    hash: &str,
+) -> bool {
+    index.get(path).is_some_and(|value| {
+        !hash.is_empty() && value.starts_with(hash)
+    })
+}
+
+/// Returns the value of the column `name` of a ratings record.
+fn field<'a>(
+    columns: &HashMap<&str, usize>,
+    record: &'a StringRecord,
+    name: &str,
+) -> &'a str {
+    columns
+        .get(name)
+        .and_then(|i| record.get(*i))
+        .unwrap_or_default()
+}
+
+/// Removes all rows of a ratings file, for which `keep` returns
+/// `false`. Returns the number of removed rows and the number of
+/// reclaimed bytes.
+fn prune_ratings<F>(
+    path: &Path,
+    dry_run: bool,
+    keep: F,
+) -> DatashedResult<(usize, u64)>
+where
+    F: Fn(&HashMap<&str, usize>, &StringRecord) -> bool,
+{
+    if !path.is_file() {
+        return Ok((0, 0));
+    }
+
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)?;
+
+    let mut records = reader.records();
+    let Some(first) = records.next().transpose()? else {
+        return Ok((0, 0));
+    };
+
+    let has_header = first.iter().any(|name| name == "path");
+    let columns: HashMap<&str, usize> = if has_header {
+        first
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name, i))
+            .collect()
+    } else {
+        SERVE_COLUMNS
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (*name, i))
+            .collect()
+    };
+
+    let mut writer =
+        WriterBuilder::new().flexible(true).from_writer(vec![]);
+    let mut removed = 0;
+
+    if has_header || keep(&columns, &first) {
+        writer.write_record(&first)?;
+    } else {
+        removed += 1;
+    }
+
+    for record in records {
+        let record = record?;
+        if keep(&columns, &record) {
+            writer.write_record(&record)?;
+        } else {
+            removed += 1;
+        }
+    }
+
+    let data = writer.into_inner().map_err(DatashedError::other)?;
+    let reclaimed =
+        fs::metadata(path)?.len().saturating_sub(data.len() as u64);
+
+    if removed > 0 && !dry_run {
+        let mut out = AtomicFile::create(path)?;
+        out.write_all(&data)?;
+        out.commit()?;
+    }
+
+    Ok((removed, reclaimed))
+}
+
+impl Gc {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let config = datashed.config()?;
+        let temp_dir = datashed.temp_dir();
+
+        // The temporary ratings are rewritten below, which must not
+        // happen while another process (e.g. `datashed serve`) appends
+        // to the ratings store.
+        let _ratings_lock = match datashed.lock_ratings(false) {
+            Err(DatashedError::Other(_)) => bail!(
+                "the ratings store is in use by another process \
+                (lock file = {})",
+                temp_dir.join(Datashed::RATINGS_LOCK).display()
+            ),
+            result => result?,
+        };
+        let retention = Duration::from_secs(self.retention * 86_400);
+        let cutoff = SystemTime::now()
+            .checked_sub(retention)
+            .unwrap_or(UNIX_EPOCH);
+
+        let index = datashed.index()?;
+        let index: HashMap<String, String> = index
+            .column("path")?
+            .str()?
+            .into_iter()
+            .zip(index.column("hash")?.str()?.into_iter())
+            .filter_map(|(path, hash)| {
+                Some((path?.into(), hash?.into()))
+            })
+            .collect();
+
+        let action = if self.dry_run {
+            "would remove"
+        } else {
+            "removed"
+        };
+        let mut reclaimed = 0;
+        let mut affected = 0;
+
+        // Left-over entries of the temp directory.
+        if temp_dir.is_dir() {
+            for entry in fs::read_dir(&temp_dir)? {
+                let path = entry?.path();
+                let name = path.file_name().unwrap_or_default();
+                if LIVE_FILES.iter().any(|live| name == *live) {
+                    continue;
+                }
+
+                let modified =
+                    fs::symlink_metadata(&path)?.modified()?;
+                if modified > cutoff {
+                    continue;
+                }
+
+                let size = disk_usage(&path)?;
+                if !self.dry_run {
+                    if path.is_dir() {
+                        fs::remove_dir_all(&path)?;
+                    } else {
+                        fs::remove_file(&path)?;
+                    }
+                }
+
+                if self.verbose || self.dry_run {
+                    eprintln!(
+                        "{action} '{}' ({}).",
+                        path.display(),
+                        HumanBytes(size)
+                    );
+                }
+
+                reclaimed += size;
+                affected += 1;
+            }
+        }
+
+        // Expired snapshots of the trash.
+        let trash_cutoff = SystemTime::now()
+            .checked_sub(Duration::from_secs(
+                self.trash_retention * 86_400,
+            ))
+            .unwrap_or(UNIX_EPOCH)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        for trash in Trash::list(&datashed)? {
+            let expired = trash
+                .name()
+                .parse::<u128>()
+                .is_ok_and(|created_at| created_at <= trash_cutoff);
+            if !expired {
+                continue;
+            }
+
+            let size = disk_usage(trash.dir())?;
+            if !self.dry_run {
+                fs::remove_dir_all(trash.dir())?;
+            }
+
+            if self.verbose || self.dry_run {
+                eprintln!(
+                    "{action} trash snapshot {} ({}).",
+                    trash.name(),
+                    HumanBytes(size)
+                );
+            }
+
+            reclaimed += size;
+            affected += 1;
+        }
+
+        // Temporary ratings of outdated document versions.
+        let cutoff_ms = cutoff
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        let (removed, size) = prune_ratings(
+            &temp_dir.join(Datashed::RATINGS),
+            self.dry_run,
+            |columns, record| {
+                let get = |name| field(columns, record, name);
+                let recent = get("created_at")
+                    .parse::<u128>()
+                    .is_ok_and(|created_at| created_at > cutoff_ms);
+
+                recent
+                    || is_referenced(&index, get("path"), get("hash"))
+            },
+        )?;
+
+        if removed > 0 && (self.verbose || self.dry_run) {
+            eprintln!("{action} {removed} temporary rating(s).");
+        }
+
+        reclaimed += size;
+        affected += removed;
+
+        // Rating sessions of outdated document versions.
+        let remote = config.metadata.name;
+        let (removed, size) = prune_ratings(
+            &state_dir()?.join(Datashed::RATINGS),
+            self.dry_run,
+            |columns, record| {
+                let get = |name| field(columns, record, name);
+                get("remote") != remote
+                    || is_referenced(&index, get("path"), get("hash"))
+            },
+        )?;
+
+        if removed > 0 && (self.verbose || self.dry_run) {
+            eprintln!("{action} {removed} rating session entries.");
+        }
+
+        reclaimed += size;
+        affected += removed;
+
+        if affected > 0 && !self.dry_run {
+            audit::record(&datashed, "gc", affected)?;
+        }
+
+        if !self.quiet {
+            if self.dry_run {
+                eprintln!(
+                    "{} would be reclaimed.",
+                    HumanBytes(reclaimed)
+                );
+            } else {
+                eprintln!("reclaimed {}.", HumanBytes(reclaimed));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn referenced_versions() {
+        let index =
+            HashMap::from([("data/1.txt".into(), "ab12cd".into())]);
+
+        assert!(is_referenced(&index, "data/1.txt", "ab12cd"));
+        assert!(is_referenced(&index, "data/1.txt", "ab12"));
+        assert!(!is_referenced(&index, "data/1.txt", "ff"));
+        assert!(!is_referenced(&index, "data/1.txt", ""));
+        assert!(!is_referenced(&index, "data/2.txt", "ab12cd"));
+    }
+}
@@ -0,0 +1,373 @@
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{stdout, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, ValueEnum};
+use hashbrown::HashSet;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+
+use crate::prelude::*;
+use crate::sql::select_where;
+use crate::utils::escape_xml;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum GraphFormat {
+    /// GraphML (XML), e.g. for Gephi, networkx or igraph.
+    #[default]
+    Graphml,
+
+    /// An edge list (Parquet), one row per edge with the node
+    /// attributes of the document.
+    Parquet,
+
+    /// An edge list (CSV), one row per edge with the node attributes
+    /// of the document.
+    Csv,
+}
+
+/// Export a bipartite graph of documents and identifiers.
+///
+/// The edges are taken from the result of `datashed bibrefs` (the
+/// identifier is `{type}:{value}`, e.g. `isbn:9783...`) and/or
+/// `datashed link` (the identifier is the GND URI). Multiple mentions
+/// of an identifier in a document are merged into a single edge,
+/// whose `weight` is the number of mentions. Document nodes are
+/// annotated with the given index columns.
+#[derive(Debug, Parser)]
+pub(crate) struct Graph {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The bibliographic references (IPC or CSV) produced by
+    /// `datashed bibrefs`.
+    #[arg(
+        long,
+        value_name = "filename",
+        required_unless_present = "links"
+    )]
+    bibrefs: Option<PathBuf>,
+
+    /// The authority links (IPC or CSV) produced by `datashed link`.
+    #[arg(long, value_name = "filename")]
+    links: Option<PathBuf>,
+
+    /// The index columns, which are attached to the document nodes.
+    /// This option can be specified multiple times.
+    #[arg(
+        short,
+        long = "attribute",
+        value_name = "column",
+        default_values = ["idn", "kind", "msc", "lang_code"]
+    )]
+    attributes: Vec<String>,
+
+    /// Ignore edges with less than `n` mentions.
+    #[arg(long, default_value = "1", value_name = "n")]
+    min_weight: u32,
+
+    /// The output format.
+    #[arg(long, value_enum, default_value_t = GraphFormat::Graphml)]
+    format: GraphFormat,
+
+    /// Write the graph into `filename`. By default, the graph is
+    /// written to the standard output (`stdout`).
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    /// An optional predicate to filter the document-set.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+}
+
+fn read_table(path: &Path) -> DatashedResult<LazyFrame> {
+    Ok(match path.extension().and_then(OsStr::to_str) {
+        Some("ipc" | "arrow") => {
+            IpcReader::new(File::open(path)?).finish()?.lazy()
+        }
+        _ => CsvReadOptions::default()
+            .with_has_header(true)
+            .with_infer_schema_length(Some(0))
+            .try_into_reader_with_file_path(Some(path.into()))?
+            .finish()?
+            .lazy(),
+    })
+}
+
+/// Returns the value of a column as string.
+fn value(
+    column: &Column,
+    idx: usize,
+) -> DatashedResult<Option<String>> {
+    Ok(match column.get(idx)? {
+        AnyValue::Null => None,
+        AnyValue::String(s) => Some(s.to_string()),
+        AnyValue::StringOwned(s) => Some(s.to_string()),
+        value => Some(value.to_string()),
+    })
+}
+
+/// Writes the edge list as GraphML.
+fn write_graphml<W: Write>(
+    df: &DataFrame,
+    attributes: &[String],
+    mut wtr: W,
+) -> DatashedResult<()> {
+    writeln!(wtr, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        wtr,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    writeln!(
+        wtr,
+        r#"  <key id="type" for="node" attr.name="type" attr.type="string"/>"#
+    )?;
+    writeln!(
+        wtr,
+        r#"  <key id="label" for="node" attr.name="label" attr.type="string"/>"#
+    )?;
+    for name in attributes {
+        let name = escape_xml(name);
+        writeln!(
+            wtr,
+            r#"  <key id="d_{name}" for="node" attr.name="{name}" attr.type="string"/>"#
+        )?;
+    }
+    writeln!(
+        wtr,
+        r#"  <key id="relation" for="edge" attr.name="relation" attr.type="string"/>"#
+    )?;
+    writeln!(
+        wtr,
+        r#"  <key id="weight" for="edge" attr.name="weight" attr.type="int"/>"#
+    )?;
+    writeln!(wtr, r#"  <graph id="G" edgedefault="undirected">"#)?;
+
+    let path = df.column("path")?.str()?;
+    let target = df.column("target")?.str()?;
+    let relation = df.column("relation")?.str()?;
+    let label = df.column("label")?.str()?;
+    let weight = df.column("weight")?.cast(&DataType::UInt32)?;
+    let weight = weight.u32()?;
+
+    let mut seen = HashSet::new();
+    for idx in 0..df.height() {
+        let path = path.get(idx).unwrap_or_default();
+        if seen.insert(format!("d:{path}")) {
+            writeln!(wtr, r#"    <node id="d:{}">"#, escape_xml(path))?;
+            writeln!(wtr, r#"      <data key="type">document</data>"#)?;
+            for name in attributes {
+                if let Some(value) = value(df.column(name)?, idx)? {
+                    writeln!(
+                        wtr,
+                        r#"      <data key="d_{}">{}</data>"#,
+                        escape_xml(name),
+                        escape_xml(&value)
+                    )?;
+                }
+            }
+            writeln!(wtr, "    </node>")?;
+        }
+
+        let target = target.get(idx).unwrap_or_default();
+        if seen.insert(format!("i:{target}")) {
+            writeln!(
+                wtr,
+                r#"    <node id="i:{}">"#,
+                escape_xml(target)
+            )?;
+            writeln!(
+                wtr,
+                r#"      <data key="type">identifier</data>"#
+            )?;
+            if let Some(label) = label.get(idx) {
+                writeln!(
+                    wtr,
+                    r#"      <data key="label">{}</data>"#,
+                    escape_xml(label)
+                )?;
+            }
+            writeln!(wtr, "    </node>")?;
+        }
+    }
+
+    for idx in 0..df.height() {
+        writeln!(
+            wtr,
+            r#"    <edge source="d:{}" target="i:{}">"#,
+            escape_xml(path.get(idx).unwrap_or_default()),
+            escape_xml(target.get(idx).unwrap_or_default())
+        )?;
+        writeln!(
+            wtr,
+            r#"      <data key="relation">{}</data>"#,
+            escape_xml(relation.get(idx).unwrap_or_default())
+        )?;
+        writeln!(
+            wtr,
+            r#"      <data key="weight">{}</data>"#,
+            weight.get(idx).unwrap_or_default()
+        )?;
+        writeln!(wtr, "    </edge>")?;
+    }
+
+    writeln!(wtr, "  </graph>")?;
+    writeln!(wtr, "</graphml>")?;
+    wtr.flush()?;
+    Ok(())
+}
+
+impl Graph {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let index = datashed.index()?;
+
+        for name in self.attributes.iter() {
+            if index.column(name).is_err() {
+                bail!("unknown index column '{name}'");
+            }
+        }
+
+        let index: LazyFrame = if let Some(predicate) = self.predicate {
+            let mut ctx = SQLContext::new();
+            ctx.register("df", index.lazy());
+            ctx.execute(&select_where(&predicate))?
+        } else {
+            index.lazy()
+        };
+
+        let mut edges = vec![];
+        if let Some(ref path) = self.bibrefs {
+            edges.push(
+                read_table(path)?.select([
+                    col("path"),
+                    concat_str(
+                        [
+                            col("type").cast(DataType::String),
+                            col("value").cast(DataType::String),
+                        ],
+                        ":",
+                        false,
+                    )
+                    .alias("target"),
+                    col("type")
+                        .cast(DataType::String)
+                        .alias("relation"),
+                    lit(NULL).cast(DataType::String).alias("label"),
+                ]),
+            );
+        }
+
+        if let Some(ref path) = self.links {
+            edges.push(read_table(path)?.select([
+                col("path"),
+                col("gnd_uri").cast(DataType::String).alias("target"),
+                lit("gnd").alias("relation"),
+                col("label").cast(DataType::String),
+            ]));
+        }
+
+        let mut columns = vec![col("path")];
+        columns.extend(
+            self.attributes
+                .iter()
+                .filter(|name| *name != "path")
+                .map(|name| col(name.as_str())),
+        );
+
+        let mut df = concat(edges, UnionArgs::default())?
+            .group_by([col("path"), col("target"), col("relation")])
+            .agg([
+                len().cast(DataType::UInt32).alias("weight"),
+                col("label").first(),
+            ])
+            .filter(col("weight").gt_eq(lit(self.min_weight)))
+            .join(
+                index.select(columns),
+                [col("path")],
+                [col("path")],
+                JoinArgs::new(JoinType::Inner),
+            )
+            .sort(["path", "target"], Default::default())
+            .collect()?;
+
+        // Categorical columns (e.g. `kind`) are exported as strings.
+        for name in self.attributes.iter() {
+            let column = df.column(name)?;
+            if matches!(
+                column.dtype(),
+                DataType::Categorical(..) | DataType::Enum(..)
+            ) {
+                let column = column.cast(&DataType::String)?;
+                df.with_column(column)?;
+            }
+        }
+
+        if self.verbose {
+            eprintln!("exporting {} edge(s).", df.height());
+        }
+
+        let out: Box<dyn Write> = match self.output {
+            Some(ref path) => {
+                Box::new(BufWriter::new(File::create(path)?))
+            }
+            None => Box::new(stdout().lock()),
+        };
+
+        match self.format {
+            GraphFormat::Graphml => {
+                write_graphml(&df, &self.attributes, out)?;
+            }
+            GraphFormat::Parquet => {
+                if self.output.is_none() {
+                    bail!("--format parquet requires --output");
+                }
+
+                ParquetWriter::new(out).finish(&mut df)?;
+            }
+            GraphFormat::Csv => {
+                CsvWriter::new(out).finish(&mut df)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn graphml() -> TestResult {
+        let df = df!(
+            "path" => ["data/1.txt", "data/1.txt"],
+            "target" => ["isbn:123", "http://d-nb.info/gnd/4<5"],
+            "relation" => ["isbn", "gnd"],
+            "label" => [None, Some("Köln")],
+            "weight" => [1u32, 2],
+            "kind" => ["book", "book"],
+        )?;
+
+        let mut out = vec![];
+        write_graphml(&df, &["kind".into()], &mut out)?;
+        let out = String::from_utf8(out)?;
+
+        assert_eq!(out.matches("<node ").count(), 3);
+        assert_eq!(out.matches("<edge ").count(), 2);
+        assert!(out.contains(r#"<data key="d_kind">book</data>"#));
+        assert!(out.contains("gnd/4&lt;5"));
+        Ok(())
+    }
+}
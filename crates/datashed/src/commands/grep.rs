@@ -7,10 +7,14 @@ use clap::Parser;
 use indicatif::ParallelProgressIterator;
 use polars::prelude::*;
 use polars::sql::SQLContext;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rayon::iter::ParallelIterator;
 use regex::bytes::RegexBuilder;
 
+use crate::atomic::AtomicFile;
+use crate::prefetch::documents;
 use crate::prelude::*;
+use crate::schedule::Schedule;
+use crate::sql::select_where;
 
 const PBAR_PROCESS: &str =
     "Processing documents: {human_pos} ({percent}%) | \
@@ -59,6 +63,26 @@ pub(crate) struct Grep {
     #[arg(short, long, value_name = "filename")]
     output: Option<PathBuf>,
 
+    /// Read documents sequentially in on-disk order with a read-ahead
+    /// of `n` documents instead of reading them in parallel. This
+    /// reduces random I/O on spinning disks.
+    #[arg(long, value_name = "n")]
+    prefetch: Option<usize>,
+
+    /// The scheduling strategy of the parallel document processing:
+    /// uniform (default), size (largest documents first) or dynamic.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Schedule::default(),
+        conflicts_with = "prefetch",
+        value_name = "strategy"
+    )]
+    schedule: Schedule,
+
+    #[command(flatten)]
+    columns: Columns,
+
     /// An optional predicate to filter the document-set.
     #[arg(long = "where")]
     predicate: Option<String>,
@@ -95,7 +119,7 @@ impl Grep {
         {
             let mut ctx = SQLContext::new();
             ctx.register("df", index.lazy());
-            ctx.execute(&format!("SELECT * FROM df WHERE {predicate}"))?
+            ctx.execute(&select_where(&predicate))?
         } else {
             index.lazy()
         };
@@ -116,57 +140,64 @@ impl Grep {
             );
         }
 
-        let df = df.collect()?;
+        let df = df
+            .with_streaming(datashed.config()?.max_memory()?.is_some())
+            .collect()?;
         let path = df.column("path")?.str()?;
         let pbar = ProgressBarBuilder::new(PBAR_PROCESS, self.quiet)
             .len(df.height() as u64)
             .build();
 
-        let paths: Vec<String> = (0..df.height())
-            .into_par_iter()
-            .progress_with(pbar)
-            .filter_map(|idx| -> Option<String> {
-                let path = path.get(idx).unwrap();
-                let doc =
-                    Document::from_path(base_dir.join(path)).unwrap();
-
-                let mut bytes = doc.as_ref();
-                if let Some(n) = self.max_bytes {
-                    if n < doc.size() && n > 0 {
-                        bytes = &bytes[0..=(n as usize)];
-                    }
+        let paths: Vec<String> = documents(
+            path.into_no_null_iter()
+                .map(|path| base_dir.join(path))
+                .collect(),
+            self.prefetch,
+            self.schedule,
+        )
+        .progress_with(pbar)
+        .filter_map(|(idx, doc)| -> Option<String> {
+            let path = path.get(idx).unwrap();
+            let doc = doc.unwrap();
+
+            let mut bytes = doc.as_ref();
+            if let Some(n) = self.max_bytes {
+                if n < doc.size() && n > 0 {
+                    bytes = &bytes[0..=(n as usize)];
                 }
+            }
 
-                if re.is_match(bytes) ^ self.invert {
-                    Some(path.to_string())
-                } else {
-                    None
-                }
-            })
-            .collect();
+            if re.is_match(bytes) ^ self.invert {
+                Some(path.to_string())
+            } else {
+                None
+            }
+        })
+        .collect();
 
         let paths =
             DataFrame::new(vec![Column::new("path".into(), &paths)])?;
 
-        let mut df = df
+        let df = df
             .lazy()
             .semi_join(paths.lazy(), col("path"), col("path"))
             .collect()?;
+        let mut df = self.columns.project(df, &datashed)?;
 
         if let Some(path) = self.output {
             match path.extension().and_then(OsStr::to_str) {
                 Some("csv") => {
-                    let mut writer =
-                        CsvWriter::new(File::create(path)?);
+                    let mut out = AtomicFile::create(path)?;
+                    let mut writer = CsvWriter::new(&mut out);
                     writer.finish(&mut df)?;
+                    out.commit()?;
                 }
                 _ => {
-                    let mut writer =
-                        IpcWriter::new(File::create(path)?)
-                            .with_compression(Some(
-                                IpcCompression::ZSTD,
-                            ));
+                    let mut out = AtomicFile::create(path)?;
+                    let mut writer = IpcWriter::new(&mut out)
+                        .with_compression(Some(IpcCompression::ZSTD));
                     writer.finish(&mut df)?;
+                    out.commit()?;
                 }
             }
         } else {
@@ -0,0 +1,221 @@
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::dates::{CatalogDates, DatesMap};
+use super::kind::KindMap;
+use super::license::LicenseMap;
+use super::msc::MscMap;
+use crate::atomic::AtomicFile;
+use crate::document::DocumentKind;
+use crate::prelude::*;
+
+/// The version of the cache format and of the extraction rules, which
+/// aren't part of the config (e.g. the MSC paths).
+const VERSION: u32 = 2;
+
+/// The name of the file (in the metadata directory), which contains the
+/// key of the cache used by the last run with a PICA+ dump.
+const LAST: &str = "last";
+
+/// The maps, which are extracted from a PICA+ dump.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct MetadataCache {
+    kinds: Vec<(String, DocumentKind, DocumentKind)>,
+    msc: Vec<(String, Vec<String>)>,
+    licenses: Vec<(String, String)>,
+    dates: Vec<(String, CatalogDates)>,
+}
+
+/// Returns the key of the metadata cache, which is the SHA256 digest of
+/// the dump and the extraction rules (kind refinements, subject
+/// categories and license paths).
+pub(crate) fn cache_key(
+    dump: &Path,
+    config: &Config,
+    kind_map: &KindMap,
+) -> DatashedResult<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(dump)?, &mut hasher)?;
+    hasher.update(format!("\0version={VERSION}\n"));
+
+    for rule in kind_map.rules() {
+        hasher.update(format!(
+            "kind={}:{}:{}\n",
+            rule.from, rule.to, rule.filter
+        ));
+    }
+
+    if let Some(ref msc) = config.msc {
+        for path in msc.paths.iter() {
+            hasher.update(format!("msc={path}\n"));
+        }
+
+        for msc in msc.allow.iter() {
+            hasher.update(format!("msc.allow={msc}\n"));
+        }
+    }
+
+    if let Some(ref license) = config.license {
+        for path in license.paths.iter() {
+            hasher.update(format!("license={path}\n"));
+        }
+    }
+
+    Ok(hasher.finalize().iter().fold(String::new(), |mut out, b| {
+        let _ = write!(out, "{b:02x}");
+        out
+    }))
+}
+
+impl MetadataCache {
+    pub(crate) fn from_maps(
+        kind_map: &KindMap,
+        msc_map: &MscMap,
+        license_map: &LicenseMap,
+        dates_map: &DatesMap,
+    ) -> Self {
+        Self {
+            kinds: kind_map
+                .iter()
+                .map(|((idn, from), to)| {
+                    (idn.clone(), from.clone(), to.clone())
+                })
+                .collect(),
+            msc: msc_map
+                .iter()
+                .map(|(idn, msc)| (idn.clone(), msc.clone()))
+                .collect(),
+            licenses: license_map
+                .iter()
+                .map(|(idn, code)| (idn.clone(), code.clone()))
+                .collect(),
+            dates: dates_map
+                .iter()
+                .map(|(idn, dates)| (idn.clone(), *dates))
+                .collect(),
+        }
+    }
+
+    /// Moves the cached entries into the (empty) maps.
+    pub(crate) fn apply(
+        self,
+        kind_map: &mut KindMap,
+        msc_map: &mut MscMap,
+        license_map: &mut LicenseMap,
+        dates_map: &mut DatesMap,
+    ) {
+        kind_map.extend(
+            self.kinds
+                .into_iter()
+                .map(|(idn, from, to)| ((idn, from), to)),
+        );
+        msc_map.extend(self.msc);
+        license_map.extend(self.licenses);
+        dates_map.extend(self.dates);
+    }
+
+    /// Reads the cache. Returns `None`, if the file doesn't exist or
+    /// can't be parsed (e.g. written by an older version).
+    pub(crate) fn read<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let file = File::open(path).ok()?;
+        serde_json::from_reader(BufReader::new(file)).ok()
+    }
+
+    /// Returns the key of the cache used by the last run with a PICA+
+    /// dump, if any.
+    pub(crate) fn last_key<P: AsRef<Path>>(dir: P) -> Option<String> {
+        let key = fs::read_to_string(dir.as_ref().join(LAST)).ok()?;
+        Some(key.trim().to_string()).filter(|key| !key.is_empty())
+    }
+
+    /// Records the key of the cache used by the current run.
+    pub(crate) fn set_last_key<P: AsRef<Path>>(
+        dir: P,
+        key: &str,
+    ) -> DatashedResult<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let mut out = AtomicFile::create(dir.join(LAST))?;
+        writeln!(out, "{key}")?;
+        out.commit()?;
+        Ok(())
+    }
+
+    /// Writes the cache (atomically).
+    pub(crate) fn write<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> DatashedResult<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut writer = BufWriter::new(AtomicFile::create(path)?);
+        serde_json::to_writer(&mut writer, self)
+            .map_err(DatashedError::other)?;
+        writer.flush()?;
+
+        writer.into_inner().map_err(|e| e.into_error())?.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn metadata_cache() -> TestResult {
+        let mut msc_map = MscMap::default();
+        msc_map.insert(
+            "118540238".into(),
+            vec!["830".into(), "900".into()],
+        );
+        let mut dates_map = DatesMap::default();
+        dates_map.insert("118540238".into(), (Some(1), None));
+
+        let cache = MetadataCache::from_maps(
+            &KindMap::default(),
+            &msc_map,
+            &LicenseMap::default(),
+            &dates_map,
+        );
+
+        let dir = crate::testing::temp_dir()?;
+        let path = dir.path().join("metadata/cache.json");
+        cache.write(&path)?;
+        assert_eq!(MetadataCache::read(&path), Some(cache));
+
+        let (mut kind_map, mut msc_map) =
+            (KindMap::default(), MscMap::default());
+        let (mut license_map, mut dates_map) =
+            (LicenseMap::default(), DatesMap::default());
+        MetadataCache::read(&path).unwrap().apply(
+            &mut kind_map,
+            &mut msc_map,
+            &mut license_map,
+            &mut dates_map,
+        );
+        assert_eq!(msc_map["118540238"], ["830", "900"]);
+        assert_eq!(dates_map.get("118540238"), Some(&(Some(1), None)));
+        assert!(license_map.is_empty());
+
+        let dir = dir.path().join("metadata");
+        assert_eq!(MetadataCache::last_key(&dir), None);
+        MetadataCache::set_last_key(&dir, "abc")?;
+        assert_eq!(
+            MetadataCache::last_key(&dir).as_deref(),
+            Some("abc")
+        );
+        Ok(())
+    }
+}
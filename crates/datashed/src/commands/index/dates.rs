@@ -0,0 +1,115 @@
+use std::ops::{Deref, DerefMut};
+
+use hashbrown::HashMap;
+use pica_record::prelude::*;
+
+/// The number of days between 0001-01-01 (CE) and 1970-01-01.
+const UNIX_EPOCH_DAYS: i32 = 719_162;
+
+/// The catalog dates of a record: the date of creation (`001A`) and
+/// the date of the last change (`001B`) as days since the epoch.
+pub(crate) type CatalogDates = (Option<i32>, Option<i32>);
+
+/// The catalog dates of all records (PPN).
+#[derive(Debug)]
+pub(crate) struct DatesMap {
+    first_entered: Path,
+    last_changed: Path,
+    map: HashMap<String, CatalogDates>,
+}
+
+impl Deref for DatesMap {
+    type Target = HashMap<String, CatalogDates>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+impl DerefMut for DatesMap {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.map
+    }
+}
+
+impl Default for DatesMap {
+    fn default() -> Self {
+        Self {
+            first_entered: Path::new("001A.0").unwrap(),
+            last_changed: Path::new("001B.0").unwrap(),
+            map: HashMap::new(),
+        }
+    }
+}
+
+/// Parses a catalog date (`{source}:{dd}-{mm}-{yy}`, e.g.
+/// `1100:24-03-21`) and returns the number of days since the epoch.
+/// Two-digit years greater than 50 belong to the 20th century.
+fn parse_date(value: &str) -> Option<i32> {
+    let date = value.split_once(':').map_or(value, |(_, date)| date);
+    let mut parts = date.trim().splitn(3, '-');
+    let day: u32 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let year = parts.next()?;
+    let year: i32 = match year.len() {
+        2 => match year.parse::<i32>().ok()? {
+            yy if yy > 50 => 1900 + yy,
+            yy => 2000 + yy,
+        },
+        4 => year.parse().ok()?,
+        _ => return None,
+    };
+
+    days_from_ce(year, month, day).map(|days| days - UNIX_EPOCH_DAYS)
+}
+
+/// Returns the number of days since 0001-01-01 (proleptic Gregorian
+/// calendar), if the date is valid.
+fn days_from_ce(year: i32, month: u32, day: u32) -> Option<i32> {
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days_in_month =
+        [31, 28 + leap as u32, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+    if !(1..=12).contains(&month)
+        || day == 0
+        || day > days_in_month[month as usize - 1]
+    {
+        return None;
+    }
+
+    let y = year - 1;
+    let mut days = y * 365 + y / 4 - y / 100 + y / 400;
+    days +=
+        days_in_month[..month as usize - 1].iter().sum::<u32>() as i32;
+    Some(days + day as i32 - 1)
+}
+
+impl DatesMap {
+    pub(crate) fn process_record(&mut self, record: &ByteRecord) {
+        let date = |path: &Path| {
+            record
+                .path(path, &Default::default())
+                .next()
+                .and_then(|value| parse_date(&value.to_string()))
+        };
+
+        let dates =
+            (date(&self.first_entered), date(&self.last_changed));
+        if dates != (None, None) {
+            self.map.insert(record.ppn().to_string(), dates);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_catalog_dates() {
+        assert_eq!(parse_date("1100:01-01-70"), Some(0));
+        assert_eq!(parse_date("0001:02-01-1970"), Some(1));
+        assert_eq!(parse_date("1100:01-03-20"), Some(18322));
+        assert_eq!(parse_date("1100:29-02-21"), None);
+        assert_eq!(parse_date("foo"), None);
+    }
+}
@@ -7,9 +7,10 @@ use crate::document::DocumentKind;
 use crate::prelude::*;
 
 #[derive(Debug)]
-struct Matcher {
-    from: DocumentKind,
-    to: DocumentKind,
+pub(crate) struct Matcher {
+    pub(crate) from: DocumentKind,
+    pub(crate) to: DocumentKind,
+    pub(crate) filter: String,
     matcher: RecordMatcher,
 }
 
@@ -44,7 +45,13 @@ impl KindMap {
     pub(crate) fn from_config(config: &Config) -> DatashedResult<Self> {
         let mut matchers = vec![];
 
-        for (from, spec) in config.kinds.iter() {
+        // The refinements are applied in a fixed order (sorted by the
+        // source kind), so that the last matching rule wins
+        // deterministically.
+        let mut kinds: Vec<_> = config.kinds.iter().collect();
+        kinds.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (from, spec) in kinds.into_iter() {
             for refinement in spec.refinements.iter() {
                 let filter = &refinement.filter;
                 let to = &refinement.target;
@@ -59,6 +66,7 @@ impl KindMap {
                 matchers.push(Matcher {
                     from: from.clone(),
                     to: to.clone(),
+                    filter: filter.clone(),
                     matcher,
                 });
             }
@@ -70,6 +78,22 @@ impl KindMap {
         })
     }
 
+    /// Returns the refinement rules (in the order of application).
+    #[inline]
+    pub(crate) fn rules(&self) -> &[Matcher] {
+        &self.matchers
+    }
+
+    /// Returns the positions of the rules, which match the record.
+    pub(crate) fn matches(&self, record: &ByteRecord) -> Vec<usize> {
+        self.matchers
+            .iter()
+            .enumerate()
+            .filter(|(_, matcher)| matcher.is_match(record))
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
     pub(crate) fn process_record(&mut self, record: &ByteRecord) {
         self.matchers.iter().for_each(|matcher| {
             if matcher.is_match(record) {
@@ -82,3 +106,64 @@ impl KindMap {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn kind_map_rules() -> TestResult {
+        let config: Config = toml::from_str(
+            r#"
+            [metadata]
+            name = "foo"
+            version = "0.1.0"
+
+            [[kinds.toc.refinements]]
+            target = "blurb"
+            filter = "003@.0 == '123'"
+
+            [[kinds.book.refinements]]
+            target = "article"
+            filter = "002@.0 == 'Aa'"
+            "#,
+        )?;
+
+        let mut kind_map = KindMap::from_config(&config)?;
+        let rules: Vec<_> = kind_map
+            .rules()
+            .iter()
+            .map(|rule| (rule.from.clone(), rule.to.clone()))
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                (DocumentKind::Book, DocumentKind::Article),
+                (DocumentKind::Toc, DocumentKind::Blurb),
+            ]
+        );
+
+        let record = ByteRecord::from_bytes(
+            b"002@ \x1f0Aa\x1e003@ \x1f0123\x1e\n",
+        )?;
+        assert_eq!(kind_map.matches(&record), [0, 1]);
+
+        kind_map.process_record(&record);
+        assert_eq!(
+            kind_map.get(&("123".to_string(), DocumentKind::Toc)),
+            Some(&DocumentKind::Blurb)
+        );
+
+        let record = ByteRecord::from_bytes(
+            b"002@ \x1f0Oa\x1e003@ \x1f0456\x1e\n",
+        )?;
+        assert!(kind_map.matches(&record).is_empty());
+
+        kind_map.process_record(&record);
+        assert_eq!(kind_map.len(), 2);
+
+        Ok(())
+    }
+}
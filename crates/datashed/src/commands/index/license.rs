@@ -0,0 +1,84 @@
+use std::fs;
+use std::ops::{Deref, DerefMut};
+
+use hashbrown::HashMap;
+use pica_record::prelude::*;
+
+use crate::prelude::*;
+
+/// The access/license codes of all records (PPN), which are extracted
+/// from the PICA+ paths of the `[license]` config section.
+#[derive(Debug, Default)]
+pub(crate) struct LicenseMap {
+    paths: Vec<Path>,
+    sidecar: Option<String>,
+    map: HashMap<String, String>,
+}
+
+impl Deref for LicenseMap {
+    type Target = HashMap<String, String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+impl DerefMut for LicenseMap {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.map
+    }
+}
+
+impl LicenseMap {
+    pub(crate) fn from_config(config: &Config) -> DatashedResult<Self> {
+        let Some(ref license) = config.license else {
+            return Ok(Self::default());
+        };
+
+        let paths = license
+            .paths
+            .iter()
+            .map(|path| {
+                Path::new(path).map_err(|_| {
+                    DatashedError::other(format!(
+                        "invalid license path '{path}'"
+                    ))
+                })
+            })
+            .collect::<DatashedResult<Vec<_>>>()?;
+
+        Ok(Self {
+            paths,
+            sidecar: license.sidecar.clone(),
+            ..Default::default()
+        })
+    }
+
+    /// Returns the code of the sidecar file of a document, if any.
+    pub(crate) fn sidecar(
+        &self,
+        path: &std::path::Path,
+    ) -> Option<String> {
+        let suffix = self.sidecar.as_ref()?;
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(suffix);
+
+        let content = fs::read_to_string(sidecar).ok()?;
+        content
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(ToString::to_string)
+    }
+
+    pub(crate) fn process_record(&mut self, record: &ByteRecord) {
+        if let Some(code) = self.paths.iter().find_map(|path| {
+            record
+                .path(path, &Default::default())
+                .map(ToString::to_string)
+                .next()
+        }) {
+            self.insert(record.ppn().to_string(), code);
+        }
+    }
+}
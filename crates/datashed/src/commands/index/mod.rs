@@ -1,19 +1,44 @@
-use std::fs::File;
-use std::io::stdout;
-use std::path::PathBuf;
-
-use clap::Parser;
-use glob::glob_with;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+
+use bstr::ByteSlice;
+use cache::{cache_key, MetadataCache};
+use clap::{Parser, ValueEnum};
+use dates::DatesMap;
 use indicatif::{ParallelProgressIterator, ProgressIterator};
 use kind::KindMap;
+use license::LicenseMap;
 use msc::MscMap;
 use pica_record::prelude::*;
 use polars::prelude::*;
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-
-use crate::document::DocumentKind;
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, ParallelIterator,
+};
+use serde::{Deserialize, Serialize};
+use workers::{
+    merge_parts, part_path, spawn_worker, split_partition, write_part,
+    Partition,
+};
+
+use crate::atomic::AtomicFile;
+use crate::checkpoint::{
+    read_json_lines, write_json_lines, Checkpoint, Partial,
+};
+use crate::document::{DocumentKind, MIN_LINE_REPEATS};
+use crate::lfreq::LfreqProfiles;
+use crate::lm::NgramModel;
+use crate::metrics::{self, Metric};
+#[cfg(feature = "wasm")]
+use crate::plugins::Plugins;
 use crate::prelude::*;
-use crate::utils::relpath;
+use crate::ratings::{aggregate, read_ratings};
+use crate::schedule::Schedule;
+use crate::stats::write_index;
+use crate::suspicious::Suspicious;
+use crate::utils::{relpath, write_df, OutputFormat};
+use crate::{artifacts, audit, discovery, redact};
 
 const PBAR_METADATA: &str = "Collecting metadata: {human_pos} | \
         elapsed: {elapsed_precise}{msg}";
@@ -25,10 +50,55 @@ const PBAR_INDEX: &str =
     "Indexing documents: {human_pos} ({percent}%) | \
         elapsed: {elapsed_precise}{msg}";
 
-mod kind;
+mod cache;
+mod dates;
+pub(crate) mod kind;
+mod license;
 mod msc;
+mod workers;
 
 /// Create an index of all available documents.
+///
+/// Symlinked documents are handled according to the `symlinks` policy
+/// of the `[discovery]` config (follow, skip or error); the target of
+/// a followed link is recorded in the column `link_target`. Documents,
+/// which refer to the same file, are indexed only once.
+///
+/// All subject categories of a document, which are extracted from the
+/// PICA+ dump according to the `[msc]` config, are recorded in the
+/// list column `msc_all` (in the order of priority); the primary
+/// category is recorded in the column `msc`.
+///
+/// If the datashed contains a language model (see `datashed lm
+/// train`), the perplexity of each document against the model is
+/// recorded in the column `perplexity`.
+///
+/// The dominant Unicode script of a document (e.g. `latin`, `cyrillic`
+/// or `arabic`) and the ratio of right-to-left letters are recorded in
+/// the columns `script` and `rtl_ratio`, e.g. to route non-Latin
+/// documents to a dedicated processing branch. The numeric profile
+/// of a document is recorded in the columns `digit_ratio`,
+/// `currency_token_ratio` and `year_mention_count`, e.g. to filter
+/// price lists or bibliographies. The fraction of lines, which are
+/// repeated verbatim (modulo digits) at least three times within a
+/// document (e.g. running headers or page numbers), is recorded in the
+/// column `repetition` (see `datashed repeated-lines`).
+///
+/// External metrics, which are defined in the `[metrics.external]`
+/// config, are computed by commands or HTTP endpoints and appended as
+/// columns named `{metric}_{column}`. A metric, which fails or exceeds
+/// its timeout, is recorded as null or aborts the indexing, depending
+/// on its `on-failure` policy. If built with the `wasm` feature, the
+/// metrics of the WASM plugins in the `plugins` directory are appended
+/// as columns named after the plugin.
+///
+/// The indexing can be distributed across nodes, which share the
+/// root directory of the datashed (e.g. via a network file system):
+/// `--workers host1,host2` runs one worker per host via SSH and merges
+/// the partial indices. Alternatively, each task of a SLURM array job
+/// indexes its partition (`datashed index --partition slurm`) into
+/// the temp directory and the partial indices are merged afterwards
+/// (`datashed index --merge tmp/index.part-*.ipc`).
 #[derive(Debug, Default, Parser)]
 pub(crate) struct Index {
     /// Run verbosely. Print additional progress information to the
@@ -53,34 +123,201 @@ pub(crate) struct Index {
     #[arg(short, long, value_name = "filename")]
     output: Option<PathBuf>,
 
+    /// The format of the index written to the standard output
+    /// (default: CSV) or into `--output` (default: IPC). The
+    /// `ndjson` format writes one JSON object per row, e.g. for
+    /// the ingestion into a search engine.
+    #[arg(long, value_name = "format")]
+    format: Option<OutputFormat>,
+
+    /// Aggregate the human ratings of the given ratings file into the
+    /// index columns `rating_majority`, `rating_mean` and
+    /// `rating_count`. Ratings of outdated document versions (hash
+    /// mismatch) are ignored.
+    #[arg(long, value_name = "filename")]
+    with_ratings: Option<PathBuf>,
+
+    /// Split the documents into pages (separated by form-feed
+    /// characters) and create one row per page with the additional
+    /// column `page_no`. The `hash` column still refers to the whole
+    /// document. By default (if neither `--stdout` nor `--output` is
+    /// set), the page index will be written to `pages.ipc` into the
+    /// root directory.
+    #[arg(long)]
+    per_page: bool,
+
+    /// The scheduling strategy of the parallel document processing:
+    /// uniform (default), size (largest documents first) or dynamic.
+    /// The order of the index rows isn't affected.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Schedule::default(),
+        value_name = "strategy"
+    )]
+    schedule: Schedule,
+
+    /// Wait for the index lock held by another process to be
+    /// released. By default (`--no-wait`), the command fails
+    /// immediately, if the index is locked.
+    #[arg(long, overrides_with = "no_wait")]
+    wait: bool,
+
+    /// Fail immediately, if the index is locked by another process.
+    #[arg(long, overrides_with = "wait")]
+    no_wait: bool,
+
+    /// Resume an interrupted run from its last checkpoint. Documents,
+    /// which were already processed, are skipped. The checkpoint must
+    /// have been created with the same `--per-page` option.
+    #[arg(long)]
+    resume: bool,
+
+    /// Write a checkpoint (into the temp directory) after every `n`
+    /// processed documents. A value of zero disables checkpoints.
+    #[arg(long, default_value = "1000", value_name = "n")]
+    checkpoint_every: usize,
+
+    /// Index only the partition `i` of `n` partitions (`i/n`,
+    /// zero-based) of the documents and write the partial index into
+    /// `--output` (default: `tmp/index.part-{i}.ipc`). The value
+    /// `slurm` takes the partition from the environment of a SLURM
+    /// array job.
+    #[arg(
+        long,
+        value_name = "i/n",
+        conflicts_with_all = ["stdout", "format", "with_ratings", "workers", "merge"]
+    )]
+    partition: Option<Partition>,
+
+    /// Distribute the indexing across the given hosts
+    /// (comma-separated). A worker is started on each host via SSH,
+    /// which indexes its partition of the documents; the partial
+    /// indices are merged by this process. The root directory must
+    /// be available under the same path on all hosts.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "hosts",
+        conflicts_with = "merge"
+    )]
+    workers: Vec<String>,
+
+    /// The program, which is run on the worker hosts.
+    #[arg(
+        long,
+        default_value = "datashed",
+        requires = "workers",
+        value_name = "program"
+    )]
+    worker_program: String,
+
+    /// Merge the given partial indices (in the given order) instead
+    /// of indexing the documents.
+    #[arg(
+        long,
+        num_args = 1..,
+        value_name = "filename",
+        conflicts_with = "path"
+    )]
+    merge: Vec<PathBuf>,
+
+    /// Rebuild the metadata extracted from the PICA+ dump. By default,
+    /// the metadata is cached in the temp directory (keyed by the
+    /// SHA256 digest of the dump and the extraction rules), so that
+    /// repeated runs against the same dump skip scanning it.
+    #[arg(long, requires = "path")]
+    refresh_metadata: bool,
+
+    /// Use the cached metadata of the last run with a PICA+ dump (if
+    /// any) instead of a dump, e.g. to re-index the datashed after new
+    /// documents have been added (see `datashed serve`).
+    #[arg(long, conflicts_with = "path")]
+    cached_metadata: bool,
+
     /// The path to the PICA+ dump
     path: Option<PathBuf>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 struct Row {
     path: PathBuf,
     idn: String,
+    page_no: Option<u32>,
     kind: DocumentKind,
     msc: Option<String>,
     lang_code: Option<String>,
     lang_score: Option<f64>,
+    #[serde(default)]
+    script: Option<String>,
+    #[serde(default)]
+    rtl_ratio: f64,
     lfreq: Option<f64>,
+    #[serde(default)]
+    perplexity: Option<f64>,
     alpha: f64,
     words: u64,
     avg_word_len: f32,
     ttr: f64,
+    #[serde(default)]
+    digit_ratio: f64,
+    #[serde(default)]
+    currency_token_ratio: f64,
+    #[serde(default)]
+    year_mention_count: u64,
+    #[serde(default)]
+    repetition: f64,
+    #[serde(default)]
+    external: Vec<Option<f64>>,
+    suspicious: Option<String>,
     size: u64,
     strlen: u64,
     mtime: u64,
     hash: String,
 }
 
-impl TryFrom<&PathBuf> for Row {
-    type Error = DatashedError;
-
-    fn try_from(path: &PathBuf) -> Result<Self, Self::Error> {
+impl Row {
+    fn from_path(
+        path: &PathBuf,
+        profiles: &LfreqProfiles,
+        model: Option<&NgramModel>,
+        metrics: &[&dyn Metric],
+        per_page: bool,
+    ) -> DatashedResult<Vec<Self>> {
         let mut doc = Document::from_path(path)?;
+        if !per_page {
+            return Ok(vec![Self {
+                external: metrics::evaluate(metrics, doc.as_ref())?,
+                ..Self::from_document(path, &mut doc, profiles, model)
+            }]);
+        }
+
+        let hash = doc.hash();
+        doc.pages()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, mut page)| {
+                Ok(Self {
+                    page_no: Some(idx as u32 + 1),
+                    hash: hash.clone(),
+                    external: metrics::evaluate(
+                        metrics,
+                        page.as_ref(),
+                    )?,
+                    ..Self::from_document(
+                        path, &mut page, profiles, model,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn from_document(
+        path: &PathBuf,
+        doc: &mut Document,
+        profiles: &LfreqProfiles,
+        model: Option<&NgramModel>,
+    ) -> Self {
         let (lang_code, lang_score) = match doc.lang() {
             Some((lang_code, lang_score)) => {
                 (Some(lang_code), Some(lang_score))
@@ -88,95 +325,280 @@ impl TryFrom<&PathBuf> for Row {
             _ => (None, None),
         };
 
-        Ok(Row {
+        let (script, rtl_ratio) = doc.script();
+
+        Row {
             path: path.into(),
             idn: doc.idn(),
             kind: doc.kind(),
-            lfreq: doc.lfreq(),
+            lfreq: doc.lfreq_with(profiles),
+            perplexity: model.and_then(|model| {
+                model.perplexity(&doc.as_ref().to_str_lossy())
+            }),
             alpha: doc.alpha(),
             words: doc.word_count(),
             avg_word_len: doc.avg_word_len(),
             ttr: doc.type_token_ratio(),
+            digit_ratio: doc.digit_ratio(),
+            currency_token_ratio: doc.currency_token_ratio(),
+            year_mention_count: doc.year_mention_count(),
+            repetition: doc.repetition(MIN_LINE_REPEATS),
+            suspicious: Some(Suspicious::detect(doc.as_ref()))
+                .filter(|flags| !flags.is_clean())
+                .map(|flags| flags.to_string()),
             size: doc.size(),
             strlen: doc.strlen(),
             mtime: doc.modified(),
             hash: doc.hash(),
             lang_code,
             lang_score,
+            script: script.map(|script| script.to_string()),
+            rtl_ratio,
             ..Default::default()
-        })
+        }
+    }
+}
+
+impl Partial for Vec<Row> {
+    const EXTENSION: &'static str = "jsonl";
+
+    fn write_batch<W: Write>(
+        items: Vec<Self>,
+        out: W,
+    ) -> DatashedResult<()> {
+        write_json_lines(&items, out)
+    }
+
+    fn read_batch(path: &Path) -> DatashedResult<Vec<Self>> {
+        read_json_lines(path)
     }
 }
 
 impl Index {
-    pub(crate) fn execute(self) -> DatashedResult<()> {
-        let datashed = Datashed::discover()?;
+    /// Indexes the documents (of the given partition) and returns the
+    /// index, before the ratings and the schema are applied, and the
+    /// checkpoint of the run.
+    fn collect(
+        &self,
+        datashed: &Datashed,
+        config: &Config,
+        partition: Option<(usize, usize)>,
+    ) -> DatashedResult<(DataFrame, Checkpoint<Vec<Row>>)> {
         let data_dir = datashed.data_dir();
         let base_dir = datashed.base_dir();
-        let config = datashed.config()?;
 
-        let mut kind_map = KindMap::from_config(&config)?;
-        let mut msc_map = MscMap::from_config(&config)?;
+        let mut kind_map = KindMap::from_config(config)?;
+        let mut msc_map = MscMap::from_config(config)?;
+        let mut license_map = LicenseMap::from_config(config)?;
+        let mut dates_map = DatesMap::default();
+        let profiles = LfreqProfiles::from_config(config, base_dir)?;
+        config.metrics.validate()?;
+        let external = config.metrics.with_client(&config.http)?;
+
+        #[cfg(feature = "wasm")]
+        let plugins = Plugins::load(datashed.plugins_dir())?;
+        #[allow(unused_mut)]
+        let mut metrics: Vec<&dyn Metric> = vec![&external];
+        #[cfg(feature = "wasm")]
+        metrics.extend(plugins.metrics());
+        let model = match base_dir.join(Datashed::LM) {
+            path if path.is_file() => {
+                Some(NgramModel::from_path(path)?)
+            }
+            _ => None,
+        };
 
-        if let Some(path) = self.path {
-            let pbar =
-                ProgressBarBuilder::new(PBAR_METADATA, self.quiet)
-                    .build();
+        let key = match self.path {
+            Some(ref path) => Some(cache_key(path, config, &kind_map)?),
+            None if self.cached_metadata => {
+                MetadataCache::last_key(datashed.metadata_dir())
+            }
+            None => None,
+        };
 
-            let mut reader = ReaderBuilder::new().from_path(path)?;
-            while let Some(result) = reader.next_byte_record() {
-                if let Ok(record) = result {
-                    kind_map.process_record(&record);
-                    msc_map.process_record(&record);
+        if let Some(ref key) = key {
+            let cache_path =
+                datashed.metadata_dir().join(format!("{key}.json"));
+
+            match MetadataCache::read(&cache_path) {
+                Some(cache) if !self.refresh_metadata => {
+                    if self.verbose {
+                        eprintln!("using cached metadata ({key})");
+                    }
+
+                    cache.apply(
+                        &mut kind_map,
+                        &mut msc_map,
+                        &mut license_map,
+                        &mut dates_map,
+                    );
                 }
+                _ => {
+                    let Some(ref path) = self.path else {
+                        bail!("cached metadata ({key}) not found");
+                    };
+
+                    let pbar = ProgressBarBuilder::new(
+                        PBAR_METADATA,
+                        self.quiet,
+                    )
+                    .build();
 
-                pbar.inc(1);
+                    let mut reader =
+                        ReaderBuilder::new().from_path(path)?;
+                    while let Some(result) = reader.next_byte_record() {
+                        if let Ok(record) = result {
+                            kind_map.process_record(&record);
+                            msc_map.process_record(&record);
+                            license_map.process_record(&record);
+                            dates_map.process_record(&record);
+                        }
+
+                        pbar.inc(1);
+                    }
+
+                    pbar.finish_using_style();
+                    MetadataCache::from_maps(
+                        &kind_map,
+                        &msc_map,
+                        &license_map,
+                        &dates_map,
+                    )
+                    .write(&cache_path)?;
+                }
             }
 
-            pbar.finish_using_style();
+            MetadataCache::set_last_key(datashed.metadata_dir(), key)?;
         }
 
-        let pattern = format!("{}/**/*.txt", data_dir.display());
+        let matcher = config.discovery.matcher()?;
         let pbar =
             ProgressBarBuilder::new(PBAR_COLLECT, self.quiet).build();
 
-        let files: Vec<_> = glob_with(&pattern, Default::default())
-            .map_err(|e| DatashedError::Other(e.to_string()))?
+        let mut files: Vec<_> = matcher
+            .documents(base_dir, &data_dir)?
             .progress_with(pbar)
-            .filter_map(Result::ok)
+            .collect::<DatashedResult<_>>()?;
+        files.sort_unstable();
+
+        let files = match partition {
+            Some((index, count)) => {
+                split_partition(files, index, count)
+            }
+            None => files,
+        };
+
+        let (checkpoint, restored) = Checkpoint::<Vec<Row>>::open(
+            datashed.checkpoints_dir().join(match partition {
+                Some((index, _)) => format!("index.part-{index:04}"),
+                None => "index".into(),
+            }),
+            &format!(
+                "per_page={},partition={:?},metrics={:?}",
+                self.per_page,
+                partition,
+                metrics::columns(&metrics)
+            ),
+            self.resume,
+            self.checkpoint_every,
+        )?;
+
+        if self.verbose && !restored.processed.is_empty() {
+            eprintln!(
+                "resuming after {} processed document(s)",
+                restored.processed.len()
+            );
+        }
+
+        // The restored rows are assigned to the position of their
+        // document, so that the order of the index isn't affected by
+        // resuming.
+        let position: HashMap<&PathBuf, usize> = files
+            .iter()
+            .enumerate()
+            .map(|(idx, path)| (path, idx))
+            .collect();
+
+        let restored_rows: Vec<(usize, Vec<Row>)> = restored
+            .items
+            .into_iter()
+            .filter_map(|rows| {
+                let idx = *position.get(&rows.first()?.path)?;
+                Some((idx, rows))
+            })
+            .collect();
+
+        let (order, max_len) = self.schedule.order(&files);
+        let order: Vec<usize> = order
+            .into_iter()
+            .filter(|idx| !restored.processed.contains(&files[*idx]))
             .collect();
 
         let pbar = ProgressBarBuilder::new(PBAR_INDEX, self.quiet)
-            .len(files.len() as u64)
+            .len(order.len() as u64)
             .build();
 
-        let rows = files
-            .par_iter()
+        let mut rows = order
+            .into_par_iter()
+            .with_max_len(max_len)
             .progress_with(pbar)
-            .map(Row::try_from)
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|_| {
-                DatashedError::other("unable to index documents!")
+            .map(|idx| -> DatashedResult<(usize, Vec<Row>)> {
+                let rows = Row::from_path(
+                    &files[idx],
+                    &profiles,
+                    model.as_ref(),
+                    &metrics,
+                    self.per_page,
+                )?;
+                checkpoint.record(&files[idx], rows.clone())?;
+                Ok((idx, rows))
+            })
+            .collect::<DatashedResult<Vec<_>>>()
+            .map_err(|e| {
+                DatashedError::other(format!(
+                    "unable to index documents: {e}"
+                ))
             })?;
 
+        rows.extend(restored_rows);
+        rows.sort_unstable_by_key(|(idx, _)| *idx);
+        let rows = rows.into_iter().map(|(_, rows)| rows);
+
         let mut remote: Vec<&str> = vec![];
         let mut path: Vec<String> = vec![];
         let mut idn: Vec<String> = vec![];
+        let mut page_no: Vec<Option<u32>> = vec![];
         let mut kind: Vec<String> = vec![];
         let mut msc: Vec<Option<String>> = vec![];
+        let mut msc_all: Vec<Series> = vec![];
+        let mut license: Vec<Option<String>> = vec![];
+        let mut first_entered: Vec<Option<i32>> = vec![];
+        let mut last_changed: Vec<Option<i32>> = vec![];
         let mut lang_code: Vec<Option<String>> = vec![];
         let mut lang_score: Vec<Option<f64>> = vec![];
+        let mut script: Vec<Option<String>> = vec![];
+        let mut rtl_ratio: Vec<f64> = vec![];
         let mut lfreq: Vec<Option<f64>> = vec![];
+        let mut perplexity: Vec<Option<f64>> = vec![];
         let mut alpha: Vec<f64> = vec![];
         let mut words: Vec<u64> = vec![];
         let mut avg_word_len: Vec<f32> = vec![];
         let mut ttr: Vec<f64> = vec![];
+        let mut digit_ratio: Vec<f64> = vec![];
+        let mut currency_token_ratio: Vec<f64> = vec![];
+        let mut year_mention_count: Vec<u64> = vec![];
+        let mut repetition: Vec<f64> = vec![];
+        let mut suspicious: Vec<Option<String>> = vec![];
         let mut size: Vec<u64> = vec![];
         let mut strlen: Vec<u64> = vec![];
         let mut mtime: Vec<u64> = vec![];
         let mut hash: Vec<String> = vec![];
+        let mut link_target: Vec<Option<String>> = vec![];
+        let external_names = metrics::columns(&metrics);
+        let mut external: Vec<Vec<Option<f64>>> =
+            vec![vec![]; external_names.len()];
 
-        for row in rows.into_iter() {
+        for row in rows.flatten() {
             let new_kind = kind_map
                 .get(&(row.idn.clone(), row.kind.clone()))
                 .unwrap_or(&row.kind)
@@ -185,62 +607,328 @@ impl Index {
             remote.push(&config.metadata.name);
             path.push(relpath(&row.path, base_dir));
             kind.push(new_kind.to_string());
-            msc.push(msc_map.get(&row.idn).cloned());
+            let categories = msc_map.get(&row.idn);
+            msc.push(categories.and_then(|msc| msc.first().cloned()));
+            msc_all.push(Series::from_iter(
+                categories.into_iter().flatten().map(String::as_str),
+            ));
+            license.push(
+                license_map
+                    .sidecar(&row.path)
+                    .or_else(|| license_map.get(&row.idn).cloned()),
+            );
+            let dates = dates_map.get(&row.idn).copied();
+            first_entered.push(dates.and_then(|dates| dates.0));
+            last_changed.push(dates.and_then(|dates| dates.1));
             lang_code.push(row.lang_code);
             lang_score.push(row.lang_score);
+            script.push(row.script);
+            rtl_ratio.push(row.rtl_ratio);
             lfreq.push(row.lfreq);
+            perplexity.push(row.perplexity);
             alpha.push(row.alpha);
             words.push(row.words);
             avg_word_len.push(row.avg_word_len);
             ttr.push(row.ttr);
+            digit_ratio.push(row.digit_ratio);
+            currency_token_ratio.push(row.currency_token_ratio);
+            year_mention_count.push(row.year_mention_count);
+            repetition.push(row.repetition);
+            suspicious.push(row.suspicious);
             size.push(row.size);
             strlen.push(row.strlen);
             mtime.push(row.mtime);
             hash.push(row.hash[0..8].to_string());
+            for (idx, values) in external.iter_mut().enumerate() {
+                values.push(row.external.get(idx).copied().flatten());
+            }
+            page_no.push(row.page_no);
             idn.push(row.idn);
+            link_target.push(
+                discovery::link_target(&row.path, base_dir)
+                    .map(|target| target.to_string_lossy().into()),
+            );
+        }
+
+        let mut msc_all = Series::new("msc_all".into(), msc_all);
+        if msc_all.is_empty() {
+            msc_all = msc_all
+                .cast(&DataType::List(Box::new(DataType::String)))?;
         }
 
-        let df = DataFrame::new(vec![
+        let mut columns = vec![
             Column::new("remote".into(), remote),
             Column::new("path".into(), path),
             Column::new("idn".into(), idn),
             Column::new("kind".into(), kind),
             Column::new("msc".into(), msc),
+            msc_all.into_column(),
+            Column::new("license".into(), license),
+            Column::new("first_entered".into(), first_entered)
+                .cast(&DataType::Date)?,
+            Column::new("last_changed".into(), last_changed)
+                .cast(&DataType::Date)?,
             Column::new("lang_code".into(), lang_code),
             Column::new("lang_score".into(), lang_score),
+            Column::new("script".into(), script),
+            Column::new("rtl_ratio".into(), rtl_ratio),
             Column::new("lfreq".into(), lfreq),
+            Column::new("perplexity".into(), perplexity),
             Column::new("alpha".into(), alpha),
             Column::new("words".into(), words),
             Column::new("avg_word_len".into(), avg_word_len),
             Column::new("ttr".into(), ttr),
+            Column::new("digit_ratio".into(), digit_ratio),
+            Column::new(
+                "currency_token_ratio".into(),
+                currency_token_ratio,
+            ),
+            Column::new(
+                "year_mention_count".into(),
+                year_mention_count,
+            ),
+            Column::new("repetition".into(), repetition),
+            Column::new("suspicious".into(), suspicious),
             Column::new("size".into(), size),
             Column::new("strlen".into(), strlen),
             Column::new("mtime".into(), mtime),
             Column::new("hash".into(), hash),
-        ])?;
+            Column::new("link_target".into(), link_target),
+        ];
+
+        for (name, values) in external_names.into_iter().zip(external) {
+            columns.push(Column::new(name.into(), values));
+        }
+
+        if self.per_page {
+            columns.insert(3, Column::new("page_no".into(), page_no));
+        }
+
+        Ok((DataFrame::new(columns)?, checkpoint))
+    }
+
+    /// Runs a worker per host, which indexes its partition of the
+    /// documents, and merges the partial indices.
+    fn run_workers(
+        &self,
+        datashed: &Datashed,
+    ) -> DatashedResult<DataFrame> {
+        let temp_dir = datashed.temp_dir();
+        fs::create_dir_all(&temp_dir)?;
+
+        let mut args = vec![
+            "--schedule".to_string(),
+            self.schedule
+                .to_possible_value()
+                .map(|value| value.get_name().to_string())
+                .unwrap_or_default(),
+            "--checkpoint-every".to_string(),
+            self.checkpoint_every.to_string(),
+        ];
+
+        if self.per_page {
+            args.push("--per-page".into());
+        }
+
+        if self.resume {
+            args.push("--resume".into());
+        }
+
+        if self.refresh_metadata {
+            args.push("--refresh-metadata".into());
+        }
+
+        if self.cached_metadata {
+            args.push("--cached-metadata".into());
+        }
+
+        if let Some(ref path) = self.path {
+            args.push(fs::canonicalize(path)?.to_string_lossy().into());
+        }
+
+        let count = self.workers.len();
+        let parts: Vec<PathBuf> =
+            (0..count).map(|idx| part_path(&temp_dir, idx)).collect();
+
+        let children = self
+            .workers
+            .iter()
+            .enumerate()
+            .map(|(idx, host)| {
+                if self.verbose {
+                    eprintln!(
+                        "starting worker {idx}/{count} on {host}"
+                    );
+                }
+
+                spawn_worker(
+                    host,
+                    &self.worker_program,
+                    datashed.base_dir(),
+                    idx,
+                    count,
+                    &parts[idx],
+                    &args,
+                )
+            })
+            .collect::<DatashedResult<Vec<_>>>()?;
+
+        let mut failed = vec![];
+        for (host, mut child) in self.workers.iter().zip(children) {
+            if !child.wait()?.success() {
+                failed.push(host.as_str());
+            }
+        }
+
+        if !failed.is_empty() {
+            bail!("worker(s) failed: {}", failed.join(", "));
+        }
+
+        let df = merge_parts(&parts)?;
+        for path in parts.iter() {
+            fs::remove_file(path)?;
+        }
 
-        let mut df: DataFrame =
-            df.lazy().select([col("*").shrink_dtype()]).collect()?;
+        Ok(df)
+    }
+
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let base_dir = datashed.base_dir();
+        let config = datashed.config()?;
+        if self.format.is_some()
+            && self.output.is_none()
+            && !self.stdout
+        {
+            bail!("--format requires either --stdout or --output");
+        }
+
+        let partition =
+            self.partition.map(Partition::resolve).transpose()?;
+
+        let _lock = if self.output.is_none()
+            && !self.stdout
+            && partition.is_none()
+        {
+            Some(datashed.lock(self.wait && !self.no_wait)?)
+        } else {
+            None
+        };
+
+        let (mut df, checkpoint) = if !self.merge.is_empty() {
+            (merge_parts(&self.merge)?, None)
+        } else if !self.workers.is_empty() {
+            (self.run_workers(&datashed)?, None)
+        } else {
+            let (df, checkpoint) =
+                self.collect(&datashed, &config, partition)?;
+            (df, Some(checkpoint))
+        };
+
+        if let Some((index, count)) = partition {
+            let path = match self.output {
+                Some(path) => path,
+                None => {
+                    fs::create_dir_all(datashed.temp_dir())?;
+                    part_path(&datashed.temp_dir(), index)
+                }
+            };
+
+            write_part(&mut df, &path)?;
+            if let Some(checkpoint) = checkpoint {
+                checkpoint.finish()?;
+            }
+
+            if self.verbose {
+                eprintln!(
+                    "wrote partition {index}/{count} into '{}'.",
+                    path.display()
+                );
+            }
+
+            return Ok(());
+        }
+
+        let df = if let Some(ref path) = self.with_ratings {
+            let scale = config.rating_scale(None)?;
+            let ratings = aggregate(&read_ratings(path)?, &scale)?;
+            df.lazy()
+                .join(
+                    ratings.lazy(),
+                    [col("path"), col("hash")],
+                    [col("path"), col("hash")],
+                    JoinArgs::new(JoinType::Left),
+                )
+                .collect()?
+        } else {
+            df
+        };
+
+        let mut df: DataFrame = config
+            .schema
+            .apply(df)?
+            .lazy()
+            .select([col("*").shrink_dtype()])
+            .collect()?;
 
         match self.output {
             Some(path) => {
-                let mut writer = IpcWriter::new(File::create(path)?)
-                    .with_compression(Some(IpcCompression::ZSTD));
-                writer.finish(&mut df)?;
+                let mut out = AtomicFile::create(path)?;
+                let format = self.format.unwrap_or(OutputFormat::Ipc);
+                write_df(&mut df, format, &mut out)?;
+                out.commit()?;
             }
             None if self.stdout => {
-                let mut writer = CsvWriter::new(stdout().lock());
-                writer.finish(&mut df)?;
+                let format = self.format.unwrap_or(OutputFormat::Csv);
+                write_df(&mut df, format, stdout().lock())?;
             }
             None => {
-                let mut writer = IpcWriter::new(File::create(
-                    base_dir.join(Datashed::INDEX),
-                )?)
-                .with_compression(Some(IpcCompression::ZSTD));
-                writer.finish(&mut df)?;
+                let filename = if self.per_page {
+                    Datashed::PAGES
+                } else {
+                    Datashed::INDEX
+                };
+
+                let mut out =
+                    AtomicFile::create(base_dir.join(filename))?;
+                write_index(&mut df, &mut out)?;
+                out.commit()?;
+
+                if !self.per_page {
+                    datashed.write_bloom(&df)?;
+                }
+
+                // The published (redacted) index must not lag behind
+                // the index.
+                if !self.per_page && !config.redact.is_empty() {
+                    let output =
+                        base_dir.join(Datashed::REDACTED_INDEX);
+                    redact::publish(
+                        &datashed,
+                        &config,
+                        &output,
+                        &redact::manifest_path(&output),
+                    )?;
+                }
+
+                let mut inputs = vec![base_dir.join(Datashed::CONFIG)];
+                inputs.extend(self.path.clone());
+                inputs.extend(self.with_ratings.clone());
+                artifacts::register(
+                    &datashed,
+                    "index",
+                    base_dir.join(filename),
+                    &inputs,
+                )?;
+
+                audit::record(&datashed, "index", df.height())?;
             }
         }
 
+        if let Some(checkpoint) = checkpoint {
+            checkpoint.finish()?;
+        }
+
         Ok(())
     }
 }
@@ -5,15 +5,17 @@ use pica_record::prelude::*;
 
 use crate::prelude::*;
 
+/// The subject categories (MSC) of all records (PPN) in the order of
+/// the PICA+ paths (priority). The first category is the primary one.
 #[derive(Debug, Default)]
 pub(crate) struct MscMap {
     paths: Vec<Path>,
     allow: HashSet<String>,
-    map: HashMap<String, String>,
+    map: HashMap<String, Vec<String>>,
 }
 
 impl Deref for MscMap {
-    type Target = HashMap<String, String>;
+    type Target = HashMap<String, Vec<String>>;
 
     fn deref(&self) -> &Self::Target {
         &self.map
@@ -27,9 +29,36 @@ impl DerefMut for MscMap {
 }
 
 impl MscMap {
-    pub(crate) fn from_config(
-        _config: &Config,
-    ) -> DatashedResult<Self> {
+    pub(crate) fn from_config(config: &Config) -> DatashedResult<Self> {
+        let msc = config.msc.as_ref();
+        let mut map = Self::builtin();
+
+        if let Some(paths) = msc.map(|msc| &msc.paths) {
+            if !paths.is_empty() {
+                map.paths = paths
+                    .iter()
+                    .map(|path| {
+                        Path::new(path).map_err(|_| {
+                            DatashedError::other(format!(
+                                "invalid msc path '{path}'"
+                            ))
+                        })
+                    })
+                    .collect::<DatashedResult<Vec<_>>>()?;
+            }
+        }
+
+        if let Some(allow) = msc.map(|msc| &msc.allow) {
+            if !allow.is_empty() {
+                map.allow = allow.iter().cloned().collect();
+            }
+        }
+
+        Ok(map)
+    }
+
+    /// Returns the map with the built-in paths and categories.
+    fn builtin() -> Self {
         let paths = vec![
             r#"045E{ e | E == "i" && H == "dnb" }"#,
             r#"045E{ e | E == "i" && H == "dnb-pa" }"#,
@@ -58,29 +87,68 @@ impl MscMap {
             .map(String::from),
         );
 
-        Ok(Self {
+        Self {
             paths: paths
                 .into_iter()
                 .filter_map(|path| Path::new(path).ok())
                 .collect(),
             allow,
             ..Default::default()
-        })
+        }
     }
 
     pub(crate) fn process_record(&mut self, record: &ByteRecord) {
-        if let Some(msc) = self
-            .paths
-            .iter()
-            .flat_map(|path| {
-                record
-                    .path(path, &Default::default())
-                    .map(ToString::to_string)
-                    .collect::<Vec<_>>()
-            })
-            .find(|msc| self.allow.get(&msc.to_string()).is_some())
-        {
-            self.insert(record.ppn().to_string(), msc.to_string());
+        let mut categories: Vec<String> = vec![];
+        for path in self.paths.iter() {
+            for msc in record.path(path, &Default::default()) {
+                let msc = msc.to_string();
+                if self.allow.contains(&msc)
+                    && !categories.contains(&msc)
+                {
+                    categories.push(msc);
+                }
+            }
+        }
+
+        if !categories.is_empty() {
+            self.insert(record.ppn().to_string(), categories);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn msc_map_process_record() -> TestResult {
+        let mut msc_map = MscMap::builtin();
+        let record = ByteRecord::from_bytes(
+            b"003@ \x1f0123\x1e\
+            045E \x1fe510\x1e\
+            045E \x1fe100\x1fEi\x1fHdnb\x1e\
+            045E \x1fe510\x1fEa\x1e\
+            045E \x1fe999\x1e\
+            045E \x1fe530\x1fEa\x1e\n",
+        )?;
+
+        // The categories are ordered by the priority of the paths
+        // (not by the order of the fields); duplicates and categories,
+        // which aren't allowed, are removed.
+        msc_map.process_record(&record);
+        assert_eq!(
+            msc_map.get("123").map(Vec::as_slice),
+            Some(["100", "510", "530"].map(String::from).as_slice())
+        );
+
+        let record = ByteRecord::from_bytes(
+            b"003@ \x1f0456\x1e045E \x1fe999\x1e\n",
+        )?;
+        msc_map.process_record(&record);
+        assert!(!msc_map.contains_key("456"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+use std::env;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::{self, Child};
+use std::str::FromStr;
+
+use polars::prelude::*;
+
+use crate::atomic::AtomicFile;
+use crate::prelude::*;
+
+/// A partition of the document list.
+///
+/// The documents (in path order) are split into `count` contiguous
+/// partitions of (almost) equal size, so that the concatenation of
+/// the partial indices in partition order equals the full index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Partition {
+    /// The partition `index` (zero-based) of `count` partitions.
+    Explicit { index: usize, count: usize },
+
+    /// The partition is taken from the environment of a SLURM array
+    /// job (`SLURM_ARRAY_TASK_ID`, `SLURM_ARRAY_TASK_MIN` and
+    /// `SLURM_ARRAY_TASK_COUNT`).
+    Slurm,
+}
+
+impl FromStr for Partition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "slurm" {
+            return Ok(Self::Slurm);
+        }
+
+        let Some((index, count)) = s.split_once('/') else {
+            return Err(format!("invalid partition '{s}' (i/n)"));
+        };
+
+        let index = index.parse::<usize>().map_err(|_| {
+            format!("invalid partition index '{index}'")
+        })?;
+        let count = count.parse::<usize>().map_err(|_| {
+            format!("invalid partition count '{count}'")
+        })?;
+
+        if index >= count {
+            return Err(format!(
+                "partition index {index} out of range (0..{count})"
+            ));
+        }
+
+        Ok(Self::Explicit { index, count })
+    }
+}
+
+impl Partition {
+    /// Resolves the partition of a SLURM array job and returns the
+    /// (zero-based) index and the number of partitions.
+    pub(crate) fn resolve(self) -> DatashedResult<(usize, usize)> {
+        let (index, count) = match self {
+            Self::Explicit { index, count } => (index, count),
+            Self::Slurm => {
+                let var = |name: &str| -> DatashedResult<usize> {
+                    env::var(name)
+                        .map_err(|_| {
+                            DatashedError::other(format!(
+                                "environment variable {name} not set"
+                            ))
+                        })?
+                        .parse::<usize>()
+                        .map_err(DatashedError::other)
+                };
+
+                let id = var("SLURM_ARRAY_TASK_ID")?;
+                let min = var("SLURM_ARRAY_TASK_MIN").unwrap_or(0);
+                let count = var("SLURM_ARRAY_TASK_COUNT")?;
+                (id.saturating_sub(min), count)
+            }
+        };
+
+        if index >= count {
+            bail!("partition index {index} out of range (0..{count})");
+        }
+
+        Ok((index, count))
+    }
+}
+
+/// Returns the documents of the given partition.
+pub(crate) fn split_partition<T>(
+    mut items: Vec<T>,
+    index: usize,
+    count: usize,
+) -> Vec<T> {
+    let len = items.len();
+    let (lo, hi) = (index * len / count, (index + 1) * len / count);
+    items.truncate(hi);
+    items.split_off(lo)
+}
+
+/// Returns the path of a partial index.
+pub(crate) fn part_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("index.part-{index:04}.ipc"))
+}
+
+/// Writes a partial index.
+pub(crate) fn write_part(
+    df: &mut DataFrame,
+    path: &Path,
+) -> DatashedResult<()> {
+    let mut out = AtomicFile::create(path)?;
+    IpcWriter::new(&mut out)
+        .with_compression(Some(IpcCompression::ZSTD))
+        .finish(df)?;
+    out.commit()?;
+    Ok(())
+}
+
+/// Merges the partial indices (in the given order).
+pub(crate) fn merge_parts(
+    paths: &[PathBuf],
+) -> DatashedResult<DataFrame> {
+    let frames = paths
+        .iter()
+        .map(|path| {
+            Ok(IpcReader::new(File::open(path)?).finish()?.lazy())
+        })
+        .collect::<DatashedResult<Vec<_>>>()?;
+
+    Ok(concat(frames, UnionArgs::default())?.collect()?)
+}
+
+/// Quotes an argument for the remote shell.
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Starts a worker on a remote host via SSH, which indexes the
+/// partition `index` of `count` partitions into `output`. The root
+/// directory of the datashed must be available under the same path on
+/// all hosts (e.g. a shared file system).
+pub(crate) fn spawn_worker(
+    host: &str,
+    program: &str,
+    root_dir: &Path,
+    index: usize,
+    count: usize,
+    output: &Path,
+    args: &[String],
+) -> DatashedResult<Child> {
+    let mut command = format!(
+        "cd {} && {} index --quiet --partition {index}/{count} \
+            --output {}",
+        quote(&root_dir.to_string_lossy()),
+        program,
+        quote(&output.to_string_lossy()),
+    );
+
+    for arg in args.iter() {
+        command.push(' ');
+        command.push_str(&quote(arg));
+    }
+
+    Ok(process::Command::new("ssh")
+        .arg("-o")
+        .arg("BatchMode=yes")
+        .arg(host)
+        .arg(command)
+        .spawn()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_partitions() {
+        assert_eq!(
+            "1/3".parse::<Partition>(),
+            Ok(Partition::Explicit { index: 1, count: 3 })
+        );
+        assert_eq!("slurm".parse::<Partition>(), Ok(Partition::Slurm));
+        assert!("3/3".parse::<Partition>().is_err());
+        assert!("1".parse::<Partition>().is_err());
+
+        let items: Vec<usize> = (0..10).collect();
+        let parts: Vec<Vec<usize>> = (0..3)
+            .map(|idx| split_partition(items.clone(), idx, 3))
+            .collect();
+        assert_eq!(
+            parts,
+            [vec![0, 1, 2], vec![3, 4, 5], vec![6, 7, 8, 9]]
+        );
+        assert_eq!(parts.concat(), items);
+
+        assert_eq!(quote("it's"), r"'it'\''s'");
+    }
+}
@@ -11,7 +11,8 @@ use semver::Version;
 use crate::prelude::*;
 
 const RATINGS: &str = "path,hash,rating,comment,user,created\n";
-const GITIGNORE: &str = "# datashed\n/data\n/index.ipc\n";
+const GITIGNORE: &str =
+    "# datashed\n/data\n/index.ipc\n/index.lock\n/pages.ipc\n";
 
 /// Initialize a new or re-initialize an existing datashed.
 #[derive(Debug, Parser)]
@@ -0,0 +1,361 @@
+use std::fs::read_to_string;
+use std::io::stdout;
+use std::path::PathBuf;
+
+use bstr::ByteSlice;
+use clap::{Parser, ValueEnum};
+use hashbrown::{HashMap, HashSet};
+use indicatif::ParallelProgressIterator;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::atomic::AtomicFile;
+use crate::prelude::*;
+use crate::preprocess::Preprocess;
+use crate::sql::select_where;
+
+/// The punctuation, which ends a sentence (or clause). RAKE keyphrase
+/// candidates never span these delimiters.
+const SENTENCE_DELIMITERS: [char; 5] = ['.', '!', '?', ';', ':'];
+
+const PBAR_PROCESS: &str =
+    "Processing documents: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Method {
+    #[default]
+    #[clap(name = "textrank")]
+    TextRank,
+    #[clap(name = "rake")]
+    Rake,
+}
+
+/// Extract the top-k keywords of each document.
+///
+/// Keywords are either single tokens ranked by TextRank or keyphrases
+/// ranked by RAKE (Rapid Automatic Keyword Extraction). The result is
+/// a table with the columns `path`, `rank`, `keyword` and `score`.
+#[derive(Debug, Default, Parser)]
+pub(crate) struct Keywords {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The method used to rank the keywords.
+    #[arg(short, long, value_enum, default_value_t = Method::TextRank)]
+    method: Method,
+
+    /// The number of keywords per document.
+    #[arg(short = 'k', long = "top-k", default_value = "10")]
+    top_k: usize,
+
+    /// The size of the co-occurrence window (TextRank only).
+    #[arg(long, default_value = "4", value_name = "n")]
+    window: usize,
+
+    /// A list of stopwords (one per line). Stopwords are never
+    /// keywords and delimit keyphrase candidates (RAKE).
+    #[arg(long)]
+    stopwords: Option<PathBuf>,
+
+    /// The name of a preprocessing profile of the datashed config. If
+    /// not set, the default preprocessing (NFC, lowercase) is used.
+    #[arg(long, value_name = "profile")]
+    preprocess: Option<String>,
+
+    /// Ignore tokens with a length less than `n`. The minimum token
+    /// length of a preprocessing profile takes precedence.
+    #[arg(
+        long = "min-tl",
+        short = 'l',
+        default_value = "2",
+        value_name = "n"
+    )]
+    min_token_len: usize,
+
+    /// Write the keywords into `filename`. By default output will be
+    /// written in CSV format to the standard output (`stdout`).
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    /// An optional predicate to filter the document-set.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+}
+
+/// A single keyword of a document (path, rank, keyword, score).
+type Row = (String, u32, String, f64);
+
+/// Returns the keywords of the given tokens ranked by TextRank.
+///
+/// Stopwords (`None`) aren't part of the graph, but the window slides
+/// over them.
+fn textrank(
+    tokens: &[Option<String>],
+    window: usize,
+) -> Vec<(String, f64)> {
+    const DAMPING: f64 = 0.85;
+    const MAX_ITER: usize = 50;
+    const EPSILON: f64 = 1e-6;
+
+    let mut graph: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for (i, lhs) in tokens.iter().enumerate() {
+        let Some(lhs) = lhs else {
+            continue;
+        };
+
+        graph.entry(lhs.as_str()).or_default();
+        for rhs in tokens.iter().skip(i + 1).take(window.max(2) - 1) {
+            match rhs {
+                Some(rhs) if rhs != lhs => {
+                    graph
+                        .entry(lhs.as_str())
+                        .or_default()
+                        .insert(rhs.as_str());
+                    graph
+                        .entry(rhs.as_str())
+                        .or_default()
+                        .insert(lhs.as_str());
+                }
+                _ => (),
+            }
+        }
+    }
+
+    let mut scores: HashMap<&str, f64> =
+        graph.keys().map(|k| (*k, 1.0)).collect();
+
+    for _ in 0..MAX_ITER {
+        let mut delta = 0.0f64;
+        let mut next = HashMap::with_capacity(scores.len());
+
+        for (node, neighbours) in graph.iter() {
+            let sum: f64 = neighbours
+                .iter()
+                .map(|n| scores[n] / graph[n].len() as f64)
+                .sum();
+
+            let score = (1.0 - DAMPING) + DAMPING * sum;
+            delta = delta.max((score - scores[node]).abs());
+            next.insert(*node, score);
+        }
+
+        scores = next;
+        if delta < EPSILON {
+            break;
+        }
+    }
+
+    scores
+        .into_iter()
+        .map(|(token, score)| (token.to_string(), score))
+        .collect()
+}
+
+/// Returns the keyphrases of the given tokens ranked by RAKE.
+///
+/// Keyphrase candidates are maximal sequences of tokens which aren't
+/// stopwords or sentence boundaries (`None`). The score of a candidate
+/// is the sum of the degree/frequency ratios of its words.
+fn rake(tokens: &[Option<String>]) -> Vec<(String, f64)> {
+    let phrases: Vec<&[Option<String>]> = tokens
+        .split(Option::is_none)
+        .filter(|phrase| !phrase.is_empty())
+        .collect();
+
+    let mut freq: HashMap<&str, f64> = HashMap::new();
+    let mut degree: HashMap<&str, f64> = HashMap::new();
+
+    for phrase in phrases.iter() {
+        for word in phrase.iter().flatten() {
+            *freq.entry(word.as_str()).or_default() += 1.0;
+            *degree.entry(word.as_str()).or_default() +=
+                phrase.len() as f64;
+        }
+    }
+
+    let mut result: HashMap<String, f64> = HashMap::new();
+    for phrase in phrases.into_iter() {
+        let score = phrase
+            .iter()
+            .flatten()
+            .map(|w| degree[w.as_str()] / freq[w.as_str()])
+            .sum();
+
+        let keyword = phrase
+            .iter()
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        result.insert(keyword, score);
+    }
+
+    result.into_iter().collect()
+}
+
+impl Keywords {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let base_dir = datashed.base_dir();
+        let mut index = datashed.index_lazy()?;
+        let preprocess = Preprocess::from_config(
+            &datashed.config()?,
+            self.preprocess.as_deref(),
+        )?
+        .with_min_token_len(self.min_token_len);
+
+        if let Some(predicate) = self.predicate {
+            let mut ctx = SQLContext::new();
+            ctx.register("df", index);
+            index = ctx.execute(&select_where(&predicate))?;
+        }
+
+        let df = index.select([col("path")]).collect()?;
+
+        let stopwords: HashSet<String> =
+            if let Some(ref path) = self.stopwords {
+                read_to_string(path)?
+                    .lines()
+                    .map(|word| preprocess.normalize(word))
+                    .collect()
+            } else {
+                HashSet::new()
+            };
+
+        let path = df.column("path")?.str()?;
+        let pbar = ProgressBarBuilder::new(PBAR_PROCESS, self.quiet)
+            .len(df.height() as u64)
+            .build();
+
+        let result: DatashedResult<Vec<_>> = (0..df.height())
+            .into_par_iter()
+            .progress_with(pbar)
+            .map(|idx| -> DatashedResult<Vec<Row>> {
+                let path = path.get(idx).unwrap();
+                let doc = Document::from_path(base_dir.join(path))?;
+
+                let text = doc.as_ref().to_str_lossy();
+                let sentences: Vec<&str> = match self.method {
+                    Method::Rake => {
+                        text.split(SENTENCE_DELIMITERS).collect()
+                    }
+                    Method::TextRank => vec![text.as_ref()],
+                };
+
+                // Sentences are separated by a boundary (`None`).
+                let tokens: Vec<Option<String>> = sentences
+                    .into_iter()
+                    .flat_map(|sentence| {
+                        preprocess
+                            .tokens(sentence)
+                            .into_iter()
+                            .map(|word| {
+                                if stopwords.contains(&word) {
+                                    None
+                                } else {
+                                    Some(word)
+                                }
+                            })
+                            .chain(std::iter::once(None))
+                    })
+                    .collect();
+
+                let mut keywords = match self.method {
+                    Method::TextRank => textrank(&tokens, self.window),
+                    Method::Rake => rake(&tokens),
+                };
+
+                keywords.sort_by(|a, b| {
+                    b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0))
+                });
+
+                Ok(keywords
+                    .into_iter()
+                    .take(self.top_k)
+                    .enumerate()
+                    .map(|(rank, (keyword, score))| {
+                        (
+                            path.to_string(),
+                            rank as u32 + 1,
+                            keyword,
+                            score,
+                        )
+                    })
+                    .collect())
+            })
+            .collect();
+
+        let rows = result?;
+        let capacity = rows.iter().map(Vec::len).sum();
+        let mut paths = Vec::with_capacity(capacity);
+        let mut ranks = Vec::with_capacity(capacity);
+        let mut keywords = Vec::with_capacity(capacity);
+        let mut scores = Vec::with_capacity(capacity);
+
+        for (path, rank, keyword, score) in rows.into_iter().flatten() {
+            paths.push(path);
+            ranks.push(rank);
+            keywords.push(keyword);
+            scores.push(score);
+        }
+
+        let mut df = DataFrame::new(vec![
+            Column::new("path".into(), paths),
+            Column::new("rank".into(), ranks),
+            Column::new("keyword".into(), keywords),
+            Column::new("score".into(), scores),
+        ])?;
+
+        if let Some(path) = self.output {
+            let mut out = AtomicFile::create(path)?;
+            let mut writer = IpcWriter::new(&mut out)
+                .with_compression(Some(IpcCompression::ZSTD));
+            writer.finish(&mut df)?;
+            out.commit()?;
+        } else {
+            let mut writer = CsvWriter::new(stdout().lock());
+            writer.finish(&mut df)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(s: &str) -> Vec<Option<String>> {
+        s.split(' ')
+            .map(|w| if w == "_" { None } else { Some(w.to_string()) })
+            .collect()
+    }
+
+    #[test]
+    fn rake_scores() {
+        let mut result = rake(&tokens("linear system _ system _ set"));
+        result.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        assert_eq!(result[0].0, "linear system");
+        assert_eq!(result[0].1, 2.0 + 1.5);
+        assert_eq!(result.len(), 3);
+    }
+
+    #[test]
+    fn textrank_central_node() {
+        let mut result = textrank(&tokens("a b _ a c _ a d"), 2);
+        result.sort_by(|a, b| b.1.total_cmp(&a.1));
+        assert_eq!(result[0].0, "a");
+    }
+}
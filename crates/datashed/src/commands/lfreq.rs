@@ -10,13 +10,18 @@ use polars::prelude::*;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use unicode_normalization::UnicodeNormalization;
 
+use crate::lfreq::LfreqProfile;
 use crate::prelude::*;
+use crate::preprocess::Preprocess;
 
 const PBAR_PROCESS: &str =
     "Processing documents: {human_pos} ({percent}%) | \
         elapsed: {elapsed_precise}{msg}";
 
 /// Create a frequency table over a fixed alphabet.
+///
+/// The table can either contain the letter frequencies of each
+/// document or a single corpus-level distribution (`--aggregate`).
 #[derive(Debug, clap::Parser)]
 pub(crate) struct Lfreq {
     /// Run verbosely. Print additional progress information to the
@@ -42,6 +47,25 @@ pub(crate) struct Lfreq {
     )]
     alphabet: String,
 
+    /// Compute a single corpus-level frequency distribution instead
+    /// of one row per document. The output consists of the columns
+    /// `letter`, `count` and `frequency` and can be used as a
+    /// reference profile.
+    #[arg(long)]
+    aggregate: bool,
+
+    /// A reference profile (CSV with the columns `letter` and
+    /// `frequency`). If set, the distance of each document to the
+    /// reference is emitted as an additional column `distance`. The
+    /// letters of the profile are added to the alphabet.
+    #[arg(long, value_name = "profile")]
+    reference: Option<PathBuf>,
+
+    /// The name of a preprocessing profile of the datashed config. If
+    /// not set, the default preprocessing (NFC, lowercase) is used.
+    #[arg(long, value_name = "profile")]
+    preprocess: Option<String>,
+
     /// Write output to `filename` instead of `stdout`.
     #[arg(short, long)]
     output: Option<PathBuf>,
@@ -56,7 +80,12 @@ struct Row {
 impl Lfreq {
     pub(crate) fn execute(self) -> DatashedResult<()> {
         let datashed = Datashed::discover()?;
-        let index = datashed.index()?;
+        let index =
+            datashed.index_lazy()?.select([col("path")]).collect()?;
+        let preprocess = Preprocess::from_config(
+            &datashed.config()?,
+            self.preprocess.as_deref(),
+        )?;
         let path = index.column("path")?.str()?;
 
         let pbar = ProgressBarBuilder::new(PBAR_PROCESS, self.quiet)
@@ -70,6 +99,15 @@ impl Lfreq {
             .nfc()
             .collect::<Vec<char>>();
 
+        let reference = match self.reference {
+            Some(ref path) => Some(LfreqProfile::from_csv(path)?),
+            None => None,
+        };
+
+        if let Some(ref reference) = reference {
+            alphabet.extend(reference.alphabet());
+        }
+
         alphabet.sort_unstable();
         alphabet.dedup();
 
@@ -91,9 +129,9 @@ impl Lfreq {
                     .to_str()
                     .map_err(|_| DatashedError::other("utf8 error"))?;
 
-                let freqs = content
-                    .to_lowercase()
-                    .nfc()
+                let freqs = preprocess
+                    .normalize(content)
+                    .chars()
                     .filter(|c| alphabet.contains(c))
                     .fold(HashMap::<char, u64>::new(), |mut acc, x| {
                         acc.entry(x)
@@ -112,38 +150,105 @@ impl Lfreq {
 
         let rows = result?;
 
-        let mut freqs = HashMap::<char, Vec<u64>>::new();
-        let mut path = vec![];
-        let mut total = vec![];
+        let df = if self.aggregate {
+            let counts = rows.into_iter().fold(
+                HashMap::<char, u64>::new(),
+                |mut acc, row| {
+                    for (c, count) in row.freqs.into_iter() {
+                        acc.entry(c)
+                            .and_modify(|e| *e += count)
+                            .or_insert(count);
+                    }
+                    acc
+                },
+            );
+
+            let total = counts.values().sum::<u64>();
+            let mut letter = vec![];
+            let mut count = vec![];
+            let mut frequency = vec![];
 
-        for row in rows.into_iter() {
             for c in alphabet.iter() {
-                let count = row.freqs.get(c).unwrap_or(&0);
-                freqs
-                    .entry(*c)
-                    .and_modify(|e| e.push(*count))
-                    .or_insert(vec![*count]);
+                let value = *counts.get(c).unwrap_or(&0);
+                letter.push(c.to_string());
+                count.push(value);
+                frequency.push(if total > 0 {
+                    value as f64 / total as f64
+                } else {
+                    0.0
+                });
             }
 
-            total.push(row.total);
-            path.push(row.path);
-        }
+            if self.verbose {
+                if let Some(ref reference) = reference {
+                    if let Some(distance) = reference
+                        .distance_from_counts(
+                            &counts
+                                .iter()
+                                .map(|(c, n)| (*c, *n))
+                                .collect(),
+                        )
+                    {
+                        eprintln!("distance to reference: {distance}");
+                    }
+                }
+            }
 
-        let mut series = vec![];
-        series.push(Column::new("path".into(), path));
-        series.push(Column::new("total".into(), total));
+            DataFrame::new(vec![
+                Column::new("letter".into(), letter),
+                Column::new("count".into(), count),
+                Column::new("frequency".into(), frequency),
+            ])?
+        } else {
+            let mut freqs = HashMap::<char, Vec<u64>>::new();
+            let mut path = vec![];
+            let mut total = vec![];
+            let mut distance = vec![];
 
-        for c in alphabet {
-            series.push(Column::new(
-                c.to_string().into(),
-                freqs.get(&c).unwrap(),
-            ));
-        }
+            for row in rows.into_iter() {
+                for c in alphabet.iter() {
+                    let count = row.freqs.get(c).unwrap_or(&0);
+                    freqs
+                        .entry(*c)
+                        .and_modify(|e| e.push(*count))
+                        .or_insert(vec![*count]);
+                }
+
+                if let Some(ref reference) = reference {
+                    distance.push(
+                        reference.distance_from_counts(
+                            &row.freqs
+                                .iter()
+                                .map(|(c, n)| (*c, *n))
+                                .collect(),
+                        ),
+                    );
+                }
+
+                total.push(row.total);
+                path.push(row.path);
+            }
+
+            let mut series = vec![];
+            series.push(Column::new("path".into(), path));
+            series.push(Column::new("total".into(), total));
+
+            if reference.is_some() {
+                series.push(Column::new("distance".into(), distance));
+            }
+
+            for c in alphabet {
+                series.push(Column::new(
+                    c.to_string().into(),
+                    freqs.get(&c).unwrap(),
+                ));
+            }
+
+            DataFrame::new(series)?
+        };
 
-        let mut df: DataFrame = DataFrame::new(series)?
-            .lazy()
-            .select([col("*").shrink_dtype()])
-            .collect()?;
+        let mut df: DataFrame =
+            df.lazy().select([col("*").shrink_dtype()]).collect()?;
 
         match self.output {
             Some(path) => {
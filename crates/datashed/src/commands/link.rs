@@ -0,0 +1,219 @@
+use std::io::stdout;
+use std::path::PathBuf;
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use bstr::ByteSlice;
+use clap::Parser;
+use indicatif::ParallelProgressIterator;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::Deserialize;
+
+use crate::atomic::AtomicFile;
+use crate::prelude::*;
+use crate::sql::select_where;
+use crate::utils::fold;
+
+const PBAR_PROCESS: &str =
+    "Processing documents: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// Find mentions of authority records in the documents.
+///
+/// The document text is matched against the labels of the vocabulary
+/// produced by `dataset vocab`. Both labels and text are folded
+/// (lowercase, diacritics removed) before matching; matches must start
+/// and end at word boundaries. The result is a table with the columns
+/// `path`, `start`, `end`, `gnd_uri`, `label` and `score`, where
+/// `start` and `end` are byte offsets into the original document.
+#[derive(Debug, Parser)]
+pub(crate) struct Link {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Ignore labels with a length (in characters) less than `n`.
+    #[arg(long = "min-len", default_value = "3", value_name = "n")]
+    min_label_len: usize,
+
+    /// Write the mentions into `filename`. By default output will be
+    /// written in CSV format to the standard output (`stdout`).
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    /// An optional predicate to filter the document-set.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+
+    /// The vocabulary (CSV) with the columns `uri` and `label`.
+    vocab: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct Concept {
+    uri: String,
+    label: String,
+}
+
+/// A mention of a concept (path, start, end, uri, label, score).
+type Mention = (String, u64, u64, String, String, f64);
+
+/// Returns the score of a mention. Exact matches get the highest
+/// score, matches that differ only in case a slightly lower one and
+/// matches that differ in diacritics the lowest.
+#[inline]
+fn score(surface: &str, label: &str) -> f64 {
+    if surface == label {
+        1.0
+    } else if surface.to_lowercase() == label.to_lowercase() {
+        0.9
+    } else {
+        0.8
+    }
+}
+
+#[inline]
+fn is_boundary(c: Option<char>) -> bool {
+    c.map(|c| !c.is_alphanumeric()).unwrap_or(true)
+}
+
+impl Link {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let base_dir = datashed.base_dir();
+        let mut index = datashed.index_lazy()?;
+
+        if let Some(predicate) = self.predicate {
+            let mut ctx = SQLContext::new();
+            ctx.register("df", index);
+            index = ctx.execute(&select_where(&predicate))?;
+        }
+
+        let df = index.select([col("path")]).collect()?;
+
+        let mut reader = csv::Reader::from_path(&self.vocab)?;
+        let mut concepts = vec![];
+        for result in reader.deserialize() {
+            let concept: Concept = result?;
+            if concept.label.chars().count() >= self.min_label_len {
+                concepts.push(concept);
+            }
+        }
+
+        if self.verbose {
+            eprintln!("loaded {} labels", concepts.len());
+        }
+
+        let patterns: Vec<String> =
+            concepts.iter().map(|c| fold(&c.label).0).collect();
+        let matcher = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(&patterns)
+            .map_err(DatashedError::other)?;
+
+        let path = df.column("path")?.str()?;
+        let pbar = ProgressBarBuilder::new(PBAR_PROCESS, self.quiet)
+            .len(df.height() as u64)
+            .build();
+
+        let result: DatashedResult<Vec<_>> = (0..df.height())
+            .into_par_iter()
+            .progress_with(pbar)
+            .map(|idx| -> DatashedResult<Vec<Mention>> {
+                let path = path.get(idx).unwrap();
+                let doc = Document::from_path(base_dir.join(path))?;
+                let text = doc.as_ref().to_str_lossy();
+                let (folded, offsets) = fold(&text);
+
+                Ok(matcher
+                    .find_iter(&folded)
+                    .filter(|m| {
+                        !m.is_empty()
+                            && is_boundary(
+                                folded[..m.start()].chars().last(),
+                            )
+                            && is_boundary(
+                                folded[m.end()..].chars().next(),
+                            )
+                    })
+                    .map(|m| {
+                        let concept = &concepts[m.pattern().as_usize()];
+                        let start = offsets[m.start()].0;
+                        let end = offsets[m.end() - 1].1;
+
+                        (
+                            path.to_string(),
+                            start as u64,
+                            end as u64,
+                            concept.uri.clone(),
+                            concept.label.clone(),
+                            score(&text[start..end], &concept.label),
+                        )
+                    })
+                    .collect())
+            })
+            .collect();
+
+        let rows = result?;
+        let capacity = rows.iter().map(Vec::len).sum();
+        let mut paths = Vec::with_capacity(capacity);
+        let mut starts = Vec::with_capacity(capacity);
+        let mut ends = Vec::with_capacity(capacity);
+        let mut uris = Vec::with_capacity(capacity);
+        let mut labels = Vec::with_capacity(capacity);
+        let mut scores = Vec::with_capacity(capacity);
+
+        for (path, start, end, uri, label, score) in
+            rows.into_iter().flatten()
+        {
+            paths.push(path);
+            starts.push(start);
+            ends.push(end);
+            uris.push(uri);
+            labels.push(label);
+            scores.push(score);
+        }
+
+        let mut df = DataFrame::new(vec![
+            Column::new("path".into(), paths),
+            Column::new("start".into(), starts),
+            Column::new("end".into(), ends),
+            Column::new("gnd_uri".into(), uris),
+            Column::new("label".into(), labels),
+            Column::new("score".into(), scores),
+        ])?;
+
+        if let Some(path) = self.output {
+            let mut out = AtomicFile::create(path)?;
+            let mut writer = IpcWriter::new(&mut out)
+                .with_compression(Some(IpcCompression::ZSTD));
+            writer.finish(&mut df)?;
+            out.commit()?;
+        } else {
+            let mut writer = CsvWriter::new(stdout().lock());
+            writer.finish(&mut df)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_ok() {
+        assert_eq!(score("Köln", "Köln"), 1.0);
+        assert_eq!(score("KÖLN", "Köln"), 0.9);
+        assert_eq!(score("Koln", "Köln"), 0.8);
+    }
+}
@@ -0,0 +1,155 @@
+use std::path::PathBuf;
+
+use bstr::ByteSlice;
+use indicatif::ParallelProgressIterator;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::artifacts;
+use crate::atomic::AtomicFile;
+use crate::lm::{LmUnit, NgramModel};
+use crate::prelude::*;
+use crate::sql::select_where;
+
+const PBAR_TRAIN: &str =
+    "Training language model: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// Manage the n-gram language model of the datashed.
+///
+/// If the datashed contains a language model (`lm.json`), `datashed
+/// index` computes the perplexity of each document against the model
+/// (column `perplexity`). A low perplexity indicates a text, which is
+/// similar to the reference documents; OCR errors and garbage text
+/// increase the perplexity.
+#[derive(Debug, clap::Parser)]
+pub(crate) struct Lm {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    #[clap(subcommand)]
+    cmd: Command,
+}
+
+#[derive(Debug, clap::Parser)]
+pub(crate) enum Command {
+    /// Fit an n-gram language model on a reference subset of the
+    /// indexed documents.
+    Train {
+        /// The order of the model (the length of the n-grams).
+        #[arg(long, default_value = "3", value_name = "n")]
+        order: usize,
+
+        /// The unit of the n-grams.
+        #[arg(long, default_value = "char", value_name = "unit")]
+        unit: LmUnit,
+
+        /// The add-k smoothing constant (must be positive).
+        #[arg(short, default_value = "0.01", value_name = "k")]
+        k: f64,
+
+        /// Select the reference documents, which match the predicate
+        /// (e.g. `rating = 'C'`). By default, all documents are used.
+        #[arg(long = "where")]
+        predicate: Option<String>,
+
+        /// Write the model into `filename` instead of the default
+        /// location (`lm.json` in the root directory).
+        #[arg(short, long, value_name = "filename")]
+        output: Option<PathBuf>,
+    },
+}
+
+impl Lm {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+
+        match self.cmd {
+            Command::Train {
+                order,
+                unit,
+                k,
+                predicate,
+                output,
+            } => {
+                if order == 0 {
+                    bail!("the order of the model must be at least 1");
+                }
+
+                if !k.is_finite() || k <= 0.0 {
+                    bail!("the smoothing constant must be positive");
+                }
+
+                let mut index = datashed.index_lazy()?;
+                if let Some(predicate) = predicate {
+                    let mut ctx = SQLContext::new();
+                    ctx.register("df", index);
+                    index = ctx.execute(&select_where(&predicate))?;
+                }
+
+                let df = index.select([col("path")]).collect()?;
+
+                let base_dir = datashed.base_dir();
+                let path = df.column("path")?.str()?;
+                let pbar =
+                    ProgressBarBuilder::new(PBAR_TRAIN, self.quiet)
+                        .len(df.height() as u64)
+                        .build();
+
+                let mut model = (0..df.height())
+                    .into_par_iter()
+                    .progress_with(pbar)
+                    .try_fold(
+                        || NgramModel::new(unit, order, k),
+                        |mut model, idx| -> DatashedResult<_> {
+                            let path = path.get(idx).unwrap();
+                            let doc = Document::from_path(
+                                base_dir.join(path),
+                            )?;
+                            model.add(&doc.as_ref().to_str_lossy());
+                            Ok(model)
+                        },
+                    )
+                    .try_reduce(
+                        || NgramModel::new(unit, order, k),
+                        |a, b| Ok(a.merge(b)),
+                    )?;
+
+                model.finish();
+
+                if self.verbose {
+                    eprintln!(
+                        "trained {order}-gram model on {} document(s) \
+                            ({} n-grams).",
+                        df.height(),
+                        model.len()
+                    );
+                }
+
+                let path = output
+                    .unwrap_or_else(|| base_dir.join(Datashed::LM));
+                let mut out = AtomicFile::create(&path)?;
+                model.write(&mut out)?;
+                out.commit()?;
+
+                artifacts::register(
+                    &datashed,
+                    "lm train",
+                    &path,
+                    &[base_dir.join(Datashed::INDEX)],
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
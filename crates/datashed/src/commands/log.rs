@@ -0,0 +1,73 @@
+use std::io::stdout;
+
+use clap::Parser;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+
+use crate::audit::{read_log, to_df};
+use crate::prelude::*;
+use crate::sql::select_where;
+use crate::utils::{write_df, OutputFormat};
+
+/// Show the audit log of the datashed.
+///
+/// All commands, which modify the documents, the index or the tags
+/// (`index`, `clean`, `undo-clean`, `apply`, `quarantine` and `tag`),
+/// append an entry to the audit log (`audit.jsonl`) consisting of the
+/// time of the operation (`timestamp`), the `user` (taken from
+/// `DATASHED_USERNAME` or `USER`), the `command`, its arguments
+/// (`args`) and the number of affected documents or index rows
+/// (`affected`). The entries can be filtered by a `--where`
+/// predicate, e.g. `--where "command = 'clean' AND timestamp >= DATE
+/// '2024-01-01'"`.
+#[derive(Debug, Parser)]
+pub(crate) struct Log {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Show only the last `n` entries.
+    #[arg(short = 'n', long, value_name = "n")]
+    limit: Option<usize>,
+
+    /// The output format.
+    #[arg(long, value_name = "format", default_value = "csv")]
+    format: OutputFormat,
+
+    /// An optional predicate to filter the audit log entries.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+}
+
+impl Log {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let entries =
+            read_log(datashed.base_dir().join(Datashed::AUDIT_LOG))?;
+        let mut df = to_df(&entries)?;
+
+        if let Some(predicate) = self.predicate {
+            let mut ctx = SQLContext::new();
+            ctx.register("df", df.lazy());
+            df = ctx.execute(&select_where(&predicate))?.collect()?;
+        }
+
+        if let Some(limit) = self.limit {
+            df = df.tail(Some(limit));
+        }
+
+        if self.verbose {
+            eprintln!("{} of {} entries.", df.height(), entries.len());
+        }
+
+        write_df(&mut df, self.format, stdout().lock())?;
+        Ok(())
+    }
+}
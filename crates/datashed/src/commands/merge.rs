@@ -0,0 +1,483 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use clap::{Parser, ValueEnum};
+use hashbrown::{HashMap, HashSet};
+use indicatif::ProgressIterator;
+use polars::prelude::*;
+
+use crate::atomic::AtomicFile;
+use crate::audit;
+use crate::plan::Plan;
+use crate::prelude::*;
+use crate::stats::write_index;
+
+const PBAR_MERGE: &str =
+    "Merging documents: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// The policy for documents of the other datashed, whose path exists
+/// in this datashed, but whose content differs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum CollisionPolicy {
+    /// Keep the document of this datashed.
+    #[default]
+    Skip,
+
+    /// Import the document under a new path (`{stem}-{n}.{ext}`).
+    Rename,
+
+    /// Keep the document with the more recent modification time.
+    PreferNewer,
+}
+
+/// The policy for documents of the other datashed, whose content
+/// (hash) exists in this datashed under a different path.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum DuplicatePolicy {
+    /// Don't import the duplicate.
+    #[default]
+    Skip,
+
+    /// Import the duplicate.
+    Keep,
+}
+
+/// The action taken for a document of the other datashed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Action {
+    /// Import the document under the given path.
+    Import(String),
+
+    /// Replace the document of this datashed.
+    Replace(String),
+
+    /// Ignore the document (identical, duplicate or collision).
+    Ignore(&'static str),
+}
+
+/// Merge the documents and the index of another datashed into this
+/// datashed.
+///
+/// Documents, which exist in both datasheds with the same path and
+/// content, are skipped. Path collisions (same path, different
+/// content) and duplicates (same content, different path) are
+/// resolved according to the given policies. Files of this datashed,
+/// which aren't part of its index, are never overwritten. This is the
+/// reverse operation of `datashed shard`.
+#[derive(Debug, Parser)]
+pub(crate) struct Merge {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// How to resolve path collisions.
+    #[arg(long, value_enum, default_value_t = CollisionPolicy::Skip)]
+    on_collision: CollisionPolicy,
+
+    /// How to handle documents, whose content already exists in this
+    /// datashed.
+    #[arg(long, value_enum, default_value_t = DuplicatePolicy::Skip)]
+    on_duplicate: DuplicatePolicy,
+
+    /// Print a plan of the operations (counts, example paths and
+    /// affected bytes) without changing anything.
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// Wait for the index lock held by another process to be
+    /// released. By default (`--no-wait`), the command fails
+    /// immediately, if the index is locked.
+    #[arg(long, overrides_with = "no_wait")]
+    wait: bool,
+
+    /// Fail immediately, if the index is locked by another process.
+    #[arg(long, overrides_with = "wait")]
+    no_wait: bool,
+
+    /// The root directory of the other datashed.
+    #[arg(value_name = "datashed")]
+    other: PathBuf,
+}
+
+/// Returns a path, which neither exists in `taken` nor in the
+/// filesystem (see `exists`), by appending a counter to the file stem.
+fn rename(
+    path: &str,
+    taken: &HashSet<String>,
+    exists: &dyn Fn(&str) -> bool,
+) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let ext = path.extension().and_then(|s| s.to_str());
+    let parent = path.parent().unwrap_or(Path::new(""));
+
+    (1..)
+        .map(|n| {
+            let name = match ext {
+                Some(ext) => format!("{stem}-{n}.{ext}"),
+                None => format!("{stem}-{n}"),
+            };
+
+            parent.join(name).to_str().unwrap().to_string()
+        })
+        .find(|candidate| {
+            !taken.contains(candidate) && !exists(candidate)
+        })
+        .unwrap()
+}
+
+/// Returns the (path, hash, mtime) triples of an index.
+fn entries(
+    df: &DataFrame,
+) -> DatashedResult<Vec<(String, String, u64)>> {
+    let path = df.column("path")?.str()?;
+    let hash = df.column("hash")?.str()?;
+    let mtime = df.column("mtime")?.cast(&DataType::UInt64)?;
+
+    Ok(path
+        .iter()
+        .zip(hash.iter())
+        .zip(mtime.u64()?.iter())
+        .map(|((path, hash), mtime)| {
+            (
+                path.unwrap_or_default().to_string(),
+                hash.unwrap_or_default().to_string(),
+                mtime.unwrap_or_default(),
+            )
+        })
+        .collect())
+}
+
+/// Decides the action for each document of the other datashed. Files,
+/// which exist in this datashed (see `exists`), but aren't part of the
+/// index, are never overwritten.
+fn resolve(
+    ours: &[(String, String, u64)],
+    theirs: &[(String, String, u64)],
+    on_collision: CollisionPolicy,
+    on_duplicate: DuplicatePolicy,
+    exists: &dyn Fn(&str) -> bool,
+) -> Vec<Action> {
+    let by_path: HashMap<&str, (&str, u64)> = ours
+        .iter()
+        .map(|(path, hash, mtime)| {
+            (path.as_str(), (hash.as_str(), *mtime))
+        })
+        .collect();
+    let mut hashes: HashSet<&str> =
+        ours.iter().map(|(_, hash, _)| hash.as_str()).collect();
+    let mut taken: HashSet<String> =
+        ours.iter().map(|(path, _, _)| path.clone()).collect();
+
+    let mut actions = Vec::with_capacity(theirs.len());
+    for (path, hash, mtime) in theirs.iter() {
+        let action = match by_path.get(path.as_str()) {
+            _ if path.is_empty() => Action::Ignore("missing path"),
+            Some((other, _)) if *other == hash.as_str() => {
+                Action::Ignore("identical")
+            }
+            Some((_, ours)) => match on_collision {
+                CollisionPolicy::Skip => Action::Ignore("collision"),
+                CollisionPolicy::PreferNewer if mtime > ours => {
+                    Action::Replace(path.clone())
+                }
+                CollisionPolicy::PreferNewer => Action::Ignore("older"),
+                CollisionPolicy::Rename
+                    if on_duplicate == DuplicatePolicy::Skip
+                        && hashes.contains(hash.as_str()) =>
+                {
+                    Action::Ignore("duplicate")
+                }
+                CollisionPolicy::Rename => {
+                    Action::Import(rename(path, &taken, exists))
+                }
+            },
+            None if on_duplicate == DuplicatePolicy::Skip
+                && hashes.contains(hash.as_str()) =>
+            {
+                Action::Ignore("duplicate")
+            }
+            None if taken.contains(path) || exists(path) => {
+                match on_collision {
+                    CollisionPolicy::Rename => {
+                        Action::Import(rename(path, &taken, exists))
+                    }
+                    _ => Action::Ignore("untracked"),
+                }
+            }
+            None => Action::Import(path.clone()),
+        };
+
+        if let Action::Import(ref path) | Action::Replace(ref path) =
+            action
+        {
+            taken.insert(path.clone());
+            hashes.insert(hash.as_str());
+        }
+
+        actions.push(action);
+    }
+
+    actions
+}
+
+impl Merge {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let other = Datashed::from_path(&self.other)?;
+        let base_dir = datashed.base_dir();
+
+        if *other.base_dir() == base_dir.canonicalize()? {
+            bail!("unable to merge a datashed into itself");
+        }
+
+        let _lock = datashed.lock(self.wait && !self.no_wait)?;
+
+        let index = datashed.index()?;
+        let theirs = other.index()?;
+        let actions = resolve(
+            &entries(&index)?,
+            &entries(&theirs)?,
+            self.on_collision,
+            self.on_duplicate,
+            &|path| base_dir.join(path).exists(),
+        );
+
+        let sizes = theirs.column("size")?.cast(&DataType::UInt64)?;
+        let sizes = sizes.u64()?;
+        let paths = theirs.column("path")?.str()?;
+
+        if self.dry_run {
+            let mut plan = Plan::default();
+            for (idx, action) in actions.iter().enumerate() {
+                let size = sizes.get(idx).unwrap_or_default();
+                match action {
+                    Action::Import(path) => {
+                        plan.add("import", path, size)
+                    }
+                    Action::Replace(path) => {
+                        plan.add("replace", path, size)
+                    }
+                    Action::Ignore(reason) => plan.add(
+                        *reason,
+                        paths.get(idx).unwrap_or_default(),
+                        size,
+                    ),
+                }
+            }
+
+            plan.add("rewrite", Datashed::INDEX, 0);
+            plan.add("rewrite", Datashed::BLOOM, 0);
+            plan.print()?;
+            return Ok(());
+        }
+
+        let pbar = ProgressBarBuilder::new(PBAR_MERGE, self.quiet)
+            .len(actions.len() as u64)
+            .build();
+
+        let mut imported = vec![];
+        let mut replaced = vec![];
+        let mut mtimes = vec![];
+        let mut mask = Vec::with_capacity(actions.len());
+
+        for (idx, action) in
+            actions.iter().enumerate().progress_with(pbar)
+        {
+            let src = other
+                .base_dir()
+                .join(paths.get(idx).unwrap_or_default());
+            let dest = match action {
+                Action::Import(path) => {
+                    imported.push(path.as_str());
+                    path
+                }
+                Action::Replace(path) => {
+                    replaced.push(path.as_str());
+                    path
+                }
+                Action::Ignore(_) => {
+                    mask.push(false);
+                    continue;
+                }
+            };
+
+            let dest = base_dir.join(dest);
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            // The copy gets a new modification time, which must be
+            // recorded in the index (see `datashed status`).
+            fs::copy(&src, &dest)?;
+            mtimes.push(
+                fs::metadata(&dest)?
+                    .modified()?
+                    .duration_since(UNIX_EPOCH)
+                    .map(|mtime| mtime.as_secs())
+                    .unwrap_or_default(),
+            );
+            mask.push(true);
+        }
+
+        let mask: BooleanChunked = mask.into_iter().collect();
+        let mut rows = theirs.filter(&mask)?;
+        let new_paths: Vec<&str> = actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::Import(path) | Action::Replace(path) => {
+                    Some(path.as_str())
+                }
+                Action::Ignore(_) => None,
+            })
+            .collect();
+        rows.replace("path", Series::new("path".into(), new_paths))?;
+        rows.replace("mtime", Series::new("mtime".into(), mtimes))?;
+
+        if rows.column("remote").is_ok() {
+            let remote = datashed.config()?.metadata.name;
+            let remote = vec![remote.as_str(); rows.height()];
+            rows.replace(
+                "remote",
+                Series::new("remote".into(), remote),
+            )?;
+        }
+
+        let replaced_paths = Series::new(
+            "path".into(),
+            replaced.iter().copied().collect::<Vec<_>>(),
+        );
+        let mut df = concat(
+            [
+                index.lazy().filter(
+                    col("path").is_in(lit(replaced_paths)).not(),
+                ),
+                rows.lazy(),
+            ],
+            UnionArgs {
+                diagonal: true,
+                to_supertypes: true,
+                ..Default::default()
+            },
+        )?
+        .sort(["path"], Default::default())
+        .collect()?;
+
+        let mut out =
+            AtomicFile::create(base_dir.join(Datashed::INDEX))?;
+        write_index(&mut df, &mut out)?;
+        out.commit()?;
+
+        datashed.write_bloom(&df)?;
+        audit::record(
+            &datashed,
+            "merge",
+            imported.len() + replaced.len(),
+        )?;
+
+        if self.verbose {
+            eprintln!(
+                "imported {} and replaced {} document(s), ignored {}.",
+                imported.len(),
+                replaced.len(),
+                actions.len() - imported.len() - replaced.len(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_conflicts() {
+        let ours = vec![
+            ("data/1.txt".into(), "a".into(), 10),
+            ("data/2.txt".into(), "b".into(), 10),
+        ];
+        let theirs = vec![
+            ("data/1.txt".into(), "a".into(), 20),
+            ("data/2.txt".into(), "c".into(), 20),
+            ("data/3.txt".into(), "b".into(), 20),
+            ("data/4.txt".into(), "d".into(), 20),
+        ];
+
+        let actions = resolve(
+            &ours,
+            &theirs,
+            CollisionPolicy::Rename,
+            DuplicatePolicy::Skip,
+            &|_| false,
+        );
+        assert_eq!(
+            actions,
+            [
+                Action::Ignore("identical"),
+                Action::Import("data/2-1.txt".into()),
+                Action::Ignore("duplicate"),
+                Action::Import("data/4.txt".into()),
+            ]
+        );
+
+        let actions = resolve(
+            &ours,
+            &theirs,
+            CollisionPolicy::PreferNewer,
+            DuplicatePolicy::Keep,
+            &|_| false,
+        );
+        assert_eq!(actions[1], Action::Replace("data/2.txt".into()));
+        assert_eq!(actions[2], Action::Import("data/3.txt".into()));
+    }
+
+    #[test]
+    fn resolve_untracked() {
+        let ours = vec![("data/1.txt".into(), "a".into(), 10)];
+        let theirs = vec![
+            ("data/4.txt".into(), "d".into(), 20),
+            ("data/2.txt".into(), "c".into(), 20),
+            ("".into(), "e".into(), 20),
+        ];
+        let exists = |path: &str| {
+            ["data/4.txt", "data/2.txt", "data/2-1.txt"].contains(&path)
+        };
+
+        let actions = resolve(
+            &ours,
+            &theirs,
+            CollisionPolicy::Skip,
+            DuplicatePolicy::Skip,
+            &exists,
+        );
+        assert_eq!(
+            actions,
+            [
+                Action::Ignore("untracked"),
+                Action::Ignore("untracked"),
+                Action::Ignore("missing path"),
+            ]
+        );
+
+        let actions = resolve(
+            &ours,
+            &theirs,
+            CollisionPolicy::Rename,
+            DuplicatePolicy::Skip,
+            &exists,
+        );
+        assert_eq!(actions[0], Action::Import("data/4-1.txt".into()));
+        assert_eq!(actions[1], Action::Import("data/2-2.txt".into()));
+    }
+}
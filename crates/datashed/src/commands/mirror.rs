@@ -0,0 +1,404 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::{Component, Path};
+
+use clap::Parser;
+use polars::prelude::*;
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+
+use crate::atomic::AtomicFile;
+use crate::audit;
+use crate::http::HttpClient;
+use crate::prelude::*;
+
+const PBAR_TRANSFER: &str =
+    "Transferring documents: {human_pos}/{human_len} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// Replicate documents and the index from one datashed to another.
+///
+/// By default, the current datashed is pushed to the target datashed,
+/// which must be a local (initialized) datashed. With `--pull`, the
+/// current datashed is updated from the target, which is either a
+/// local datashed or the URL of a datashed served by `datashed serve`.
+///
+/// Only documents that are missing in the destination or whose hash
+/// differs from the hash in the index of the source are transferred.
+/// The hashes of the destination are taken from its index, which is
+/// replaced by the index of the source afterwards. Each transferred
+/// document is verified against the hash in the index of the source;
+/// documents without a hash are skipped.
+#[derive(Debug, Parser)]
+pub(crate) struct Mirror {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Update the current datashed from the target (instead of
+    /// pushing the current datashed to the target).
+    #[arg(long)]
+    pull: bool,
+
+    /// Remove documents from the destination, which aren't contained
+    /// in the index of the source.
+    #[arg(long)]
+    delete: bool,
+
+    /// Wait for the index lock held by another process to be
+    /// released. By default (`--no-wait`), the command fails
+    /// immediately, if the index is locked.
+    #[arg(long, overrides_with = "no_wait")]
+    wait: bool,
+
+    /// Fail immediately, if the index is locked by another process.
+    #[arg(long, overrides_with = "wait")]
+    no_wait: bool,
+
+    /// The path or URL of the target datashed.
+    target: String,
+}
+
+/// The source of a mirror operation.
+enum Source {
+    Local(Datashed),
+    Remote(HttpClient, Url),
+}
+
+impl Source {
+    /// Returns the raw (IPC) index of the source.
+    async fn index(&self) -> DatashedResult<Vec<u8>> {
+        match self {
+            Self::Local(datashed) => {
+                Ok(fs::read(datashed.base_dir().join(Datashed::INDEX))?)
+            }
+            Self::Remote(client, url) => {
+                let url = url.join(Datashed::INDEX).map_err(|_| {
+                    DatashedError::other(format!("invalid url '{url}'"))
+                })?;
+
+                let response =
+                    client.get(url).await?.error_for_status()?;
+                Ok(response.bytes().await?.to_vec())
+            }
+        }
+    }
+
+    /// Returns the content of the document with the given (relative)
+    /// path.
+    async fn document(&self, path: &str) -> DatashedResult<Vec<u8>> {
+        match self {
+            Self::Local(datashed) => {
+                Ok(fs::read(datashed.base_dir().join(path))?)
+            }
+            Self::Remote(client, url) => {
+                let url = url.join(path).map_err(|_| {
+                    DatashedError::other(format!(
+                        "invalid path '{path}'"
+                    ))
+                })?;
+
+                let response =
+                    client.get(url).await?.error_for_status()?;
+                Ok(response.bytes().await?.to_vec())
+            }
+        }
+    }
+}
+
+/// Returns a map from the path of each document to its hash.
+fn hashes(df: &DataFrame) -> DatashedResult<HashMap<String, String>> {
+    let path = df.column("path")?.str()?;
+    let hash = df.column("hash")?.str()?;
+
+    Ok(path
+        .into_iter()
+        .zip(hash.into_iter())
+        .filter_map(|(path, hash)| Some((path?.into(), hash?.into())))
+        .collect())
+}
+
+/// Returns the SHA256 digest of a document.
+fn digest(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .fold(String::new(), |mut out, b| {
+            let _ = write!(out, "{b:02x}");
+            out
+        })
+}
+
+/// Returns the URL of the datashed, if the target is a HTTP(S) URL.
+fn parse_url(target: &str) -> Option<Url> {
+    let mut url = Url::parse(target).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+
+    // Ensure that relative paths are resolved against the root of the
+    // datashed and not its parent.
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+
+    Some(url)
+}
+
+impl Mirror {
+    pub(crate) async fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let config = datashed.config()?;
+        let url = parse_url(&self.target);
+
+        let (source, dest) = match (self.pull, url) {
+            (true, Some(url)) => {
+                let client = HttpClient::from_config(&config.http)?;
+                (Source::Remote(client, url), datashed)
+            }
+            (true, None) => (
+                Source::Local(Datashed::from_path(&self.target)?),
+                datashed,
+            ),
+            (false, Some(_)) => {
+                bail!(
+                    "unable to push to a remote datashed; run \
+                    `datashed mirror --pull` on the target instead"
+                );
+            }
+            (false, None) => (
+                Source::Local(datashed),
+                Datashed::from_path(&self.target)?,
+            ),
+        };
+
+        if let Source::Local(ref src) = source {
+            if src.base_dir() == dest.base_dir() {
+                bail!("source and destination must be different");
+            }
+        }
+
+        let _lock = dest.lock(self.wait && !self.no_wait)?;
+        self.replicate(&source, &dest).await
+    }
+
+    /// Transfers the documents and the index from the source to the
+    /// destination, whose index lock must be held by the caller.
+    async fn replicate(
+        &self,
+        source: &Source,
+        dest: &Datashed,
+    ) -> DatashedResult<()> {
+        let index = source.index().await?;
+        let src_index = IpcReader::new(Cursor::new(&index)).finish()?;
+        let src_hashes = hashes(&src_index)?;
+        let skipped = src_index.column("hash")?.null_count();
+
+        let index_path = dest.base_dir().join(Datashed::INDEX);
+        let dest_hashes = if index_path.is_file() {
+            hashes(&dest.index()?)?
+        } else {
+            HashMap::new()
+        };
+
+        let base_dir = dest.base_dir();
+        let mut transfers: Vec<&String> = src_hashes
+            .iter()
+            .filter(|(path, hash)| {
+                dest_hashes.get(*path) != Some(hash)
+                    || !base_dir.join(path).is_file()
+            })
+            .map(|(path, _)| path)
+            .collect();
+        transfers.sort_unstable();
+
+        let pbar = ProgressBarBuilder::new(PBAR_TRANSFER, self.quiet)
+            .len(transfers.len() as u64)
+            .build();
+
+        for path in transfers.iter() {
+            if Path::new(path).components().any(|c| {
+                !matches!(c, Component::Normal(_) | Component::CurDir)
+            }) {
+                bail!("invalid document path '{path}'");
+            }
+
+            // The index contains only a prefix of the SHA256 digest
+            // (see `datashed index`).
+            let content = source.document(path).await?;
+            if !digest(&content).starts_with(src_hashes[*path].as_str())
+            {
+                bail!("hash mismatch of document '{path}'");
+            }
+
+            let dest_path = base_dir.join(path);
+            if let Some(parent) = dest_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            let mut out = AtomicFile::create(&dest_path)?;
+            out.write_all(&content)?;
+            out.commit()?;
+            if self.verbose {
+                pbar.println(format!("transferred '{path}'"));
+            }
+
+            pbar.inc(1);
+        }
+
+        pbar.finish_using_style();
+
+        let mut removed = 0;
+        if self.delete {
+            for path in dest_hashes.keys() {
+                if src_hashes.contains_key(path) {
+                    continue;
+                }
+
+                let dest_path = base_dir.join(path);
+                if dest_path.is_file() {
+                    fs::remove_file(&dest_path)?;
+                    removed += 1;
+
+                    if self.verbose {
+                        eprintln!("removed '{path}'");
+                    }
+                }
+            }
+        }
+
+        let mut out = AtomicFile::create(&index_path)?;
+        out.write_all(&index)?;
+        out.commit()?;
+
+        let affected = transfers.len() + removed;
+        if affected > 0 {
+            audit::record(dest, "mirror", affected)?;
+        }
+
+        if !self.quiet {
+            eprintln!(
+                "transferred {} of {} documents ({removed} removed).",
+                transfers.len(),
+                src_hashes.len()
+            );
+        }
+
+        if skipped > 0 && !self.quiet {
+            eprintln!(
+                "warning: skipped {skipped} index rows without hash."
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_datashed;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn mirror_parse_url() {
+        assert_eq!(
+            parse_url("http://localhost:9001").unwrap().as_str(),
+            "http://localhost:9001/"
+        );
+        assert_eq!(
+            parse_url("https://example.com/sheds/foo")
+                .unwrap()
+                .join(Datashed::INDEX)
+                .unwrap()
+                .as_str(),
+            "https://example.com/sheds/foo/index.ipc"
+        );
+
+        assert!(parse_url("../foo").is_none());
+        assert!(parse_url("/srv/foo").is_none());
+        assert!(parse_url("file:///srv/foo").is_none());
+    }
+
+    #[test]
+    fn mirror_hashes() -> TestResult {
+        let df = df!(
+            "path" => [Some("data/a.txt"), Some("data/b.txt"), None],
+            "hash" => [Some("1"), None, Some("3")],
+        )?;
+
+        let hashes = hashes(&df)?;
+        assert_eq!(hashes.len(), 1);
+        assert_eq!(hashes["data/a.txt"], "1");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mirror_local_datashed() -> TestResult {
+        let (_src_dir, src) = temp_datashed()?;
+        let (_dest_dir, dest) = temp_datashed()?;
+
+        let data_dir = src.data_dir();
+        fs::create_dir_all(&data_dir)?;
+        fs::write(data_dir.join("a.txt"), "abc")?;
+        fs::write(data_dir.join("b.txt"), "def")?;
+
+        let mut df = df!(
+            "path" => ["data/a.txt", "data/b.txt"],
+            "hash" => [&digest(b"abc")[..8], &digest(b"def")[..8]],
+        )?;
+        IpcWriter::new(fs::File::create(
+            src.base_dir().join(Datashed::INDEX),
+        )?)
+        .finish(&mut df)?;
+
+        fs::create_dir_all(dest.data_dir())?;
+        fs::write(dest.data_dir().join("b.txt"), "old")?;
+        fs::write(dest.data_dir().join("c.txt"), "ghi")?;
+
+        let mirror = Mirror {
+            verbose: false,
+            quiet: true,
+            pull: false,
+            delete: false,
+            wait: false,
+            no_wait: false,
+            target: String::new(),
+        };
+
+        mirror.replicate(&Source::Local(src), &dest).await?;
+
+        let base_dir = dest.base_dir();
+        assert_eq!(
+            fs::read_to_string(base_dir.join("data/a.txt"))?,
+            "abc"
+        );
+        assert_eq!(
+            fs::read_to_string(base_dir.join("data/b.txt"))?,
+            "def"
+        );
+        assert!(base_dir.join("data/c.txt").is_file());
+        assert_eq!(dest.index()?.height(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn mirror_digest() {
+        assert_eq!(
+            digest(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223\
+             b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -1,36 +1,106 @@
+pub(crate) use apply::Apply;
 pub(crate) use archive::Archive;
+pub(crate) use bench::Bench;
 pub(crate) use bibrefs::BibRefs;
 pub(crate) use clean::Clean;
 pub(crate) use completions::Completions;
 pub(crate) use config::Config;
+pub(crate) use contains::Contains;
+pub(crate) use daemon::Daemon;
+pub(crate) use deboilerplate::Deboilerplate;
+pub(crate) use diff_docs::DiffDocs;
+pub(crate) use duckdb_init::DuckdbInit;
+pub(crate) use encoding_report::EncodingReport;
+pub(crate) use gc::Gc;
+pub(crate) use graph::Graph;
 pub(crate) use grep::Grep;
 pub(crate) use index::Index;
 pub(crate) use init::Init;
+pub(crate) use keywords::Keywords;
 pub(crate) use lfreq::Lfreq;
+pub(crate) use link::Link;
+pub(crate) use lm::Lm;
+pub(crate) use log::Log;
+pub(crate) use merge::Merge;
+pub(crate) use mirror::Mirror;
+#[cfg(feature = "fuse")]
+pub(crate) use mount::Mount;
+pub(crate) use ocr_confusions::OcrConfusions;
+pub(crate) use outdated::Outdated;
+pub(crate) use quarantine::Quarantine;
 pub(crate) use rate::Rate;
+pub(crate) use ratings::Ratings;
+pub(crate) use redact_index::RedactIndex;
+pub(crate) use refine::Refine;
+pub(crate) use repeated_lines::RepeatedLines;
+pub(crate) use report::Report;
 pub(crate) use restore::Restore;
+pub(crate) use sample::Sample;
+pub(crate) use select::Select;
 pub(crate) use serve::Serve;
+pub(crate) use shard::Shard;
+pub(crate) use sign::Sign;
 pub(crate) use status::Status;
+pub(crate) use stopwords::Stopwords;
 pub(crate) use summary::Summary;
+pub(crate) use synth::Synth;
+pub(crate) use tag::Tag;
+pub(crate) use tocparse::Tocparse;
+pub(crate) use undo_clean::UndoClean;
 pub(crate) use user::User;
 pub(crate) use verify::Verify;
 pub(crate) use version::Version;
 pub(crate) use vocab::Vocab;
 
+mod apply;
 mod archive;
+mod bench;
 mod bibrefs;
 mod clean;
 mod completions;
 mod config;
+mod contains;
+mod daemon;
+mod deboilerplate;
+mod diff_docs;
+mod duckdb_init;
+mod encoding_report;
+mod gc;
+mod graph;
 mod grep;
 mod index;
 mod init;
+mod keywords;
 mod lfreq;
+mod link;
+mod lm;
+mod log;
+mod merge;
+mod mirror;
+#[cfg(feature = "fuse")]
+mod mount;
+mod ocr_confusions;
+mod outdated;
+mod quarantine;
 mod rate;
+mod ratings;
+mod redact_index;
+mod refine;
+mod repeated_lines;
+mod report;
 mod restore;
+mod sample;
+mod select;
 mod serve;
+mod shard;
+mod sign;
 mod status;
+mod stopwords;
 mod summary;
+mod synth;
+mod tag;
+mod tocparse;
+mod undo_clean;
 mod user;
 mod verify;
 mod version;
@@ -0,0 +1,449 @@
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::Parser;
+use fuser::{
+    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData,
+    ReplyDirectory, ReplyEntry, Request,
+};
+use polars::prelude::*;
+use polars::sql::SQLContext;
+
+use crate::prelude::*;
+use crate::sql::select_where;
+
+/// The time the kernel may cache attributes and entries.
+const TTL: Duration = Duration::from_secs(60);
+
+/// The inode of the root directory.
+const ROOT_INO: u64 = 1;
+
+/// Mount the (selected) documents as a read-only filesystem.
+///
+/// The filesystem mirrors the data directory, restricted to the
+/// documents, which match the `--where` predicate. With `--group-by`,
+/// the documents are restructured into one directory level per given
+/// index column (e.g. `--group-by kind --group-by msc`), named after
+/// the value of the document (missing values are grouped under
+/// `unknown`). Clashing file names get a counter appended to the file
+/// stem. The filesystem is a view on the index at the time of
+/// mounting; the command blocks until the filesystem is unmounted
+/// (e.g. via `fusermount -u <mountpoint>`).
+#[derive(Debug, Parser)]
+pub(crate) struct Mount {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Group the documents by the values of the given index column.
+    /// This option can be specified multiple times.
+    #[arg(long, value_name = "column")]
+    group_by: Vec<String>,
+
+    /// Allow other users to access the filesystem (requires
+    /// `user_allow_other` in `/etc/fuse.conf`).
+    #[arg(long)]
+    allow_other: bool,
+
+    /// An optional predicate to filter the document-set.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+
+    /// The (empty) directory to mount the filesystem at.
+    mountpoint: PathBuf,
+}
+
+#[derive(Debug)]
+enum Node {
+    Dir {
+        parent: u64,
+        children: BTreeMap<OsString, u64>,
+    },
+    File {
+        path: PathBuf,
+        size: u64,
+        mtime: u64,
+    },
+}
+
+/// The (immutable) directory tree of the mounted filesystem. The inode
+/// of a node is its position in the tree plus one.
+#[derive(Debug)]
+struct Tree {
+    nodes: Vec<Node>,
+    uid: u32,
+    gid: u32,
+}
+
+impl Default for Tree {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node::Dir {
+                parent: ROOT_INO,
+                children: BTreeMap::new(),
+            }],
+            uid: 0,
+            gid: 0,
+        }
+    }
+}
+
+impl Tree {
+    fn get(&self, ino: u64) -> Option<&Node> {
+        self.nodes.get(ino.checked_sub(1)? as usize)
+    }
+
+    fn children_mut(
+        &mut self,
+        ino: u64,
+    ) -> &mut BTreeMap<OsString, u64> {
+        match self.nodes.get_mut(ino as usize - 1) {
+            Some(Node::Dir { children, .. }) => children,
+            _ => unreachable!("inode {ino} isn't a directory"),
+        }
+    }
+
+    /// Returns the inode of the directory at `dirs`, which is created
+    /// if necessary.
+    fn mkdir_all(&mut self, dirs: &[String]) -> u64 {
+        let mut ino = ROOT_INO;
+        for name in dirs.iter() {
+            let next = self.nodes.len() as u64 + 1;
+            let child = *self
+                .children_mut(ino)
+                .entry(OsString::from(name))
+                .or_insert(next);
+
+            if child == next {
+                self.nodes.push(Node::Dir {
+                    parent: ino,
+                    children: BTreeMap::new(),
+                });
+            }
+
+            ino = child;
+        }
+
+        ino
+    }
+
+    /// Adds the document at `path` as file `name` below `dirs`. If the
+    /// name is already taken, a counter is appended to the file stem.
+    fn insert(
+        &mut self,
+        dirs: &[String],
+        name: &str,
+        path: PathBuf,
+        size: u64,
+        mtime: u64,
+    ) {
+        let parent = self.mkdir_all(dirs);
+        let ino = self.nodes.len() as u64 + 1;
+        let children = self.children_mut(parent);
+
+        let mut name = OsString::from(name);
+        if children.contains_key(&name) {
+            let file = Path::new(&name).to_path_buf();
+            let stem = file.file_stem().unwrap_or_default();
+            let ext = file.extension();
+
+            name = (1..)
+                .map(|n| {
+                    let mut candidate = stem.to_os_string();
+                    candidate.push(format!("-{n}"));
+                    if let Some(ext) = ext {
+                        candidate.push(".");
+                        candidate.push(ext);
+                    }
+                    candidate
+                })
+                .find(|candidate| !children.contains_key(candidate))
+                .unwrap();
+        }
+
+        children.insert(name, ino);
+        self.nodes.push(Node::File { path, size, mtime });
+    }
+
+    fn attr(&self, ino: u64) -> Option<FileAttr> {
+        let (kind, perm, size, mtime) = match self.get(ino)? {
+            Node::Dir { .. } => (FileType::Directory, 0o555, 0, 0),
+            Node::File { size, mtime, .. } => {
+                (FileType::RegularFile, 0o444, *size, *mtime)
+            }
+        };
+
+        let time = UNIX_EPOCH + Duration::from_secs(mtime);
+        Some(FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: time,
+            mtime: time,
+            ctime: time,
+            crtime: time,
+            kind,
+            perm,
+            nlink: 1,
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: 4096,
+            flags: 0,
+        })
+    }
+}
+
+impl Filesystem for Tree {
+    fn lookup(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        reply: ReplyEntry,
+    ) {
+        let ino = match self.get(parent) {
+            Some(Node::Dir { children, .. }) => children.get(name),
+            _ => None,
+        };
+
+        match ino.and_then(|ino| self.attr(*ino)) {
+            Some(attr) => reply.entry(&TTL, &attr, 0),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn getattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        reply: ReplyAttr,
+    ) {
+        match self.attr(ino) {
+            Some(attr) => reply.attr(&TTL, &attr),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let Some(Node::File { path, .. }) = self.get(ino) else {
+            return reply.error(libc::ENOENT);
+        };
+
+        let mut buf = vec![0; size as usize];
+        let result = File::open(path).and_then(|file| {
+            file.read_at(&mut buf, offset.max(0) as u64)
+        });
+
+        match result {
+            Ok(n) => reply.data(&buf[..n]),
+            Err(_) => reply.error(libc::EIO),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let Some(Node::Dir { parent, children }) = self.get(ino) else {
+            return reply.error(libc::ENOTDIR);
+        };
+
+        let entries = [
+            (ino, FileType::Directory, OsStr::new(".")),
+            (*parent, FileType::Directory, OsStr::new("..")),
+        ]
+        .into_iter()
+        .chain(children.iter().map(|(name, ino)| {
+            let kind = match self.get(*ino) {
+                Some(Node::Dir { .. }) => FileType::Directory,
+                _ => FileType::RegularFile,
+            };
+
+            (*ino, kind, name.as_os_str())
+        }));
+
+        for (idx, (ino, kind, name)) in
+            entries.enumerate().skip(offset.max(0) as usize)
+        {
+            if reply.add(ino, idx as i64 + 1, kind, name) {
+                break;
+            }
+        }
+
+        reply.ok();
+    }
+}
+
+/// Returns the directory name of an index value.
+fn dirname(value: Option<&str>) -> String {
+    match value {
+        Some(value) if !value.is_empty() => value.replace('/', "_"),
+        _ => "unknown".into(),
+    }
+}
+
+impl Mount {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let base_dir = datashed.base_dir();
+        let data_dir = datashed.data_dir();
+        let mut index = datashed.index_lazy()?;
+        if let Some(ref predicate) = self.predicate {
+            let mut ctx = SQLContext::new();
+            ctx.register("df", index);
+            index = ctx.execute(&select_where(predicate))?;
+        }
+
+        let mut columns = vec![col("path")];
+        columns.extend(
+            self.group_by.iter().map(|name| col(name.as_str())),
+        );
+        let df = index.select(columns).collect()?;
+
+        let groups = self
+            .group_by
+            .iter()
+            .map(|name| Ok(df.column(name)?.cast(&DataType::String)?))
+            .collect::<DatashedResult<Vec<_>>>()?;
+        let groups = groups
+            .iter()
+            .map(|column| Ok(column.str()?))
+            .collect::<DatashedResult<Vec<_>>>()?;
+
+        let path = df.column("path")?.str()?;
+        let metadata = fs::metadata(&data_dir)?;
+        let mut tree = Tree {
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            ..Default::default()
+        };
+
+        for idx in 0..df.height() {
+            let Some(path) = path.get(idx) else {
+                continue;
+            };
+
+            let path = base_dir.join(path);
+            let Ok(metadata) = path.metadata() else {
+                continue;
+            };
+
+            let relpath = path.strip_prefix(&data_dir).unwrap_or(&path);
+            let name = relpath
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+
+            let dirs: Vec<String> = if groups.is_empty() {
+                relpath
+                    .parent()
+                    .into_iter()
+                    .flat_map(|parent| parent.iter())
+                    .map(|c| c.to_string_lossy().to_string())
+                    .collect()
+            } else {
+                groups
+                    .iter()
+                    .map(|column| dirname(column.get(idx)))
+                    .collect()
+            };
+
+            let mtime = metadata
+                .modified()
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+
+            tree.insert(&dirs, &name, path, metadata.len(), mtime);
+        }
+
+        if self.verbose {
+            eprintln!(
+                "mounting {} document(s) at {}",
+                df.height(),
+                self.mountpoint.display()
+            );
+        }
+
+        let mut options = vec![
+            MountOption::RO,
+            MountOption::FSName(datashed.config()?.metadata.name),
+        ];
+
+        if self.allow_other {
+            options.push(MountOption::AllowOther);
+        }
+
+        fuser::mount2(tree, &self.mountpoint, &options)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mount_tree() {
+        let mut tree = Tree::default();
+        let dirs = ["book".to_string(), "510".to_string()];
+        tree.insert(&dirs, "1.txt", "a/1.txt".into(), 10, 0);
+        tree.insert(&dirs, "1.txt", "b/1.txt".into(), 20, 0);
+        tree.insert(&dirs[..1], "2.txt", "a/2.txt".into(), 30, 0);
+
+        let Some(Node::Dir { children, .. }) = tree.get(ROOT_INO)
+        else {
+            panic!("missing root directory");
+        };
+        assert_eq!(children.len(), 1);
+
+        let Some(Node::Dir { children, .. }) =
+            tree.get(children[OsStr::new("book")])
+        else {
+            panic!("missing directory");
+        };
+        assert_eq!(children.len(), 2);
+
+        let Some(Node::Dir { children, .. }) =
+            tree.get(children[OsStr::new("510")])
+        else {
+            panic!("missing directory");
+        };
+        assert_eq!(
+            children.keys().collect::<Vec<_>>(),
+            [OsStr::new("1-1.txt"), OsStr::new("1.txt")]
+        );
+
+        let attr = tree.attr(children[OsStr::new("1-1.txt")]).unwrap();
+        assert_eq!(attr.size, 20);
+        assert_eq!(dirname(None), "unknown");
+    }
+}
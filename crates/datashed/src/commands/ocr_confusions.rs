@@ -0,0 +1,383 @@
+use std::io::stdout;
+use std::path::PathBuf;
+
+use bstr::ByteSlice;
+use clap::Parser;
+use hashbrown::HashMap;
+use indicatif::ParallelProgressIterator;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use rayon::prelude::*;
+use similar::{capture_diff_slices, Algorithm, DiffTag};
+
+use crate::atomic::AtomicFile;
+use crate::prelude::*;
+use crate::sql::select_where;
+use crate::utils::{write_df, OutputFormat};
+
+const PBAR_ALIGN: &str =
+    "Aligning documents: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// Compare the documents with their ground-truth transcriptions.
+///
+/// The ground truth of a document is either located in a paired
+/// directory (`--ground-truth`), under the same path relative to the
+/// data directory, or given by an index column (`--column`), which
+/// contains the path of the transcription (relative to the root
+/// directory). Documents without a ground truth are skipped.
+///
+/// Each document is aligned character- and word-wise against its
+/// transcription (whitespace is collapsed). By default, the result is
+/// a table with the columns `path`, `chars`, `char_errors`, `cer`,
+/// `words`, `word_errors` and `wer`. With `--confusions`, the most
+/// frequent character confusions are reported instead (columns
+/// `expected`, `actual` and `count`); an empty `expected` value denotes
+/// an insertion and an empty `actual` value a deletion.
+#[derive(Debug, Parser)]
+pub(crate) struct OcrConfusions {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The directory of the ground-truth transcriptions, which mirrors
+    /// the data directory of the datashed.
+    #[arg(
+        long,
+        value_name = "dir",
+        required_unless_present = "column",
+        conflicts_with = "column"
+    )]
+    ground_truth: Option<PathBuf>,
+
+    /// The index column, which contains the path of the ground-truth
+    /// transcription of a document.
+    #[arg(long, value_name = "column")]
+    column: Option<String>,
+
+    /// Report the character confusion pairs instead of the error rates
+    /// per document.
+    #[arg(long)]
+    confusions: bool,
+
+    /// The number of confusion pairs to report (most frequent first).
+    #[arg(
+        short = 'n',
+        long,
+        default_value = "50",
+        value_name = "n",
+        requires = "confusions"
+    )]
+    limit: usize,
+
+    /// Write output into `filename` instead of `stdout`.
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    /// The output format (default: CSV).
+    #[arg(long, value_name = "format")]
+    format: Option<OutputFormat>,
+
+    /// An optional predicate to filter the document-set.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+}
+
+/// A confusion of an expected (ground truth) and an actual (OCR)
+/// character sequence.
+type Confusion = (String, String);
+
+/// The alignment of a document against its ground truth.
+#[derive(Debug, Default, PartialEq)]
+struct Alignment {
+    chars: u64,
+    char_errors: u64,
+    words: u64,
+    word_errors: u64,
+    confusions: HashMap<Confusion, u64>,
+}
+
+/// Returns the characters of the text with collapsed whitespace.
+fn normalize(text: &str) -> Vec<char> {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .collect()
+}
+
+/// Aligns the OCR text against the ground truth and counts the errors
+/// and character confusions.
+///
+/// A replaced range is paired character by character; the remaining
+/// characters of the longer side count as deletions or insertions.
+fn align(gt: &str, ocr: &str) -> Alignment {
+    let gt_chars = normalize(gt);
+    let ocr_chars = normalize(ocr);
+
+    let mut result = Alignment {
+        chars: gt_chars.len() as u64,
+        ..Default::default()
+    };
+
+    let ops =
+        capture_diff_slices(Algorithm::Myers, &gt_chars, &ocr_chars);
+    for op in ops.iter() {
+        let (tag, old, new) = op.as_tag_tuple();
+        if tag == DiffTag::Equal {
+            continue;
+        }
+
+        let expected = &gt_chars[old];
+        let actual = &ocr_chars[new];
+        result.char_errors += expected.len().max(actual.len()) as u64;
+
+        for idx in 0..expected.len().max(actual.len()) {
+            let confusion = (
+                expected
+                    .get(idx)
+                    .map(char::to_string)
+                    .unwrap_or_default(),
+                actual
+                    .get(idx)
+                    .map(char::to_string)
+                    .unwrap_or_default(),
+            );
+
+            *result.confusions.entry(confusion).or_default() += 1;
+        }
+    }
+
+    let gt_words: Vec<&str> = gt.split_whitespace().collect();
+    let ocr_words: Vec<&str> = ocr.split_whitespace().collect();
+    result.words = gt_words.len() as u64;
+
+    let ops =
+        capture_diff_slices(Algorithm::Myers, &gt_words, &ocr_words);
+    for op in ops.iter() {
+        let (tag, old, new) = op.as_tag_tuple();
+        if tag != DiffTag::Equal {
+            result.word_errors += old.len().max(new.len()) as u64;
+        }
+    }
+
+    result
+}
+
+impl OcrConfusions {
+    /// Returns the path of the ground truth of the document at `path`
+    /// (relative to the root directory).
+    fn ground_truth(
+        &self,
+        datashed: &Datashed,
+        path: &str,
+        column: Option<&str>,
+    ) -> Option<PathBuf> {
+        let base_dir = datashed.base_dir();
+        let gt_path = match self.ground_truth {
+            Some(ref gt_dir) => {
+                let path = base_dir.join(path);
+                let data_dir = datashed.data_dir();
+                let relpath = path.strip_prefix(&data_dir).ok()?;
+                gt_dir.join(relpath)
+            }
+            None => base_dir.join(column?),
+        };
+
+        Some(gt_path).filter(|path| path.is_file())
+    }
+
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let mut index = datashed.index_lazy()?;
+        if let Some(ref predicate) = self.predicate {
+            let mut ctx = SQLContext::new();
+            ctx.register("df", index);
+            index = ctx.execute(&select_where(predicate))?;
+        }
+
+        let mut columns = vec![col("path")];
+        columns.extend(self.column.as_deref().map(col));
+        let df = index.select(columns).collect()?;
+
+        let path = df.column("path")?.str()?;
+        let column = match self.column {
+            Some(ref name) => {
+                Some(df.column(name)?.cast(&DataType::String)?)
+            }
+            None => None,
+        };
+
+        let column = match column {
+            Some(ref column) => Some(column.str()?),
+            None => None,
+        };
+
+        let pbar = ProgressBarBuilder::new(PBAR_ALIGN, self.quiet)
+            .len(df.height() as u64)
+            .build();
+
+        let alignments = (0..df.height())
+            .into_par_iter()
+            .progress_with(pbar)
+            .map(|idx| -> DatashedResult<Option<(String, Alignment)>> {
+                let path = path.get(idx).unwrap();
+                let gt_path = column.and_then(|column| column.get(idx));
+                let Some(gt_path) =
+                    self.ground_truth(&datashed, path, gt_path)
+                else {
+                    return Ok(None);
+                };
+
+                let doc = Document::from_path(
+                    datashed.base_dir().join(path),
+                )?;
+                let gt = Document::from_path(&gt_path)?;
+                let alignment = align(
+                    &gt.as_ref().to_str_lossy(),
+                    &doc.as_ref().to_str_lossy(),
+                );
+
+                Ok(Some((path.to_string(), alignment)))
+            })
+            .collect::<DatashedResult<Vec<_>>>()?;
+
+        let alignments: Vec<_> =
+            alignments.into_iter().flatten().collect();
+
+        if self.verbose {
+            eprintln!(
+                "aligned {} of {} document(s) against the ground truth.",
+                alignments.len(),
+                df.height()
+            );
+        }
+
+        let mut df = if self.confusions {
+            let mut counts: HashMap<Confusion, u64> = HashMap::new();
+            for (_, alignment) in alignments.into_iter() {
+                for (confusion, count) in
+                    alignment.confusions.into_iter()
+                {
+                    *counts.entry(confusion).or_default() += count;
+                }
+            }
+
+            let mut counts: Vec<_> = counts.into_iter().collect();
+            counts.sort_unstable_by(|a, b| {
+                b.1.cmp(&a.1).then(a.0.cmp(&b.0))
+            });
+            counts.truncate(self.limit);
+
+            let (expected, actual): (Vec<_>, Vec<_>) =
+                counts.iter().map(|(c, _)| c.clone()).unzip();
+
+            DataFrame::new(vec![
+                Column::new("expected".into(), expected),
+                Column::new("actual".into(), actual),
+                Column::new(
+                    "count".into(),
+                    counts.iter().map(|(_, n)| *n).collect::<Vec<_>>(),
+                ),
+            ])?
+        } else {
+            let rate = |errors: u64, total: u64| -> Option<f64> {
+                (total > 0).then(|| errors as f64 / total as f64)
+            };
+
+            DataFrame::new(vec![
+                Column::new(
+                    "path".into(),
+                    alignments
+                        .iter()
+                        .map(|(p, _)| p.as_str())
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "chars".into(),
+                    alignments
+                        .iter()
+                        .map(|(_, a)| a.chars)
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "char_errors".into(),
+                    alignments
+                        .iter()
+                        .map(|(_, a)| a.char_errors)
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "cer".into(),
+                    alignments
+                        .iter()
+                        .map(|(_, a)| rate(a.char_errors, a.chars))
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "words".into(),
+                    alignments
+                        .iter()
+                        .map(|(_, a)| a.words)
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "word_errors".into(),
+                    alignments
+                        .iter()
+                        .map(|(_, a)| a.word_errors)
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "wer".into(),
+                    alignments
+                        .iter()
+                        .map(|(_, a)| rate(a.word_errors, a.words))
+                        .collect::<Vec<_>>(),
+                ),
+            ])?
+        };
+
+        let format = self.format.unwrap_or(OutputFormat::Csv);
+        match self.output {
+            Some(path) => {
+                let mut out = AtomicFile::create(path)?;
+                write_df(&mut df, format, &mut out)?;
+                out.commit()?;
+            }
+            None => {
+                write_df(&mut df, format, stdout().lock())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ocr_alignment() {
+        let alignment =
+            align("Die  Katze\nschläft", "Dic Katze schlaft.");
+        assert_eq!(alignment.chars, 17);
+        assert_eq!(alignment.char_errors, 3);
+        assert_eq!(alignment.words, 3);
+        assert_eq!(alignment.word_errors, 2);
+        assert_eq!(alignment.confusions[&("e".into(), "c".into())], 1);
+        assert_eq!(alignment.confusions[&("ä".into(), "a".into())], 1);
+        assert_eq!(alignment.confusions[&("".into(), ".".into())], 1);
+
+        let alignment = align("", "");
+        assert_eq!(alignment, Alignment::default());
+    }
+}
@@ -0,0 +1,86 @@
+use clap::Parser;
+use comfy_table::{presets, Row, Table};
+
+use crate::artifacts::Artifacts;
+use crate::prelude::*;
+
+/// List the derived files, whose inputs changed.
+///
+/// Commands, which derive files from other files (e.g. `datashed
+/// index`, `datashed lm train`, `datashed redact-index` or `datashed
+/// select --output`), register their output together with the digests
+/// of their inputs and the command line in the artifact registry
+/// (`artifacts.toml`). This command lists all artifacts, which are
+/// missing or whose inputs changed or vanished since their creation,
+/// and the command line to re-create them.
+#[derive(Debug, Parser)]
+pub(crate) struct Outdated {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Exit with a non-zero status, if an artifact is outdated.
+    #[arg(long)]
+    check: bool,
+}
+
+impl Outdated {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let base_dir = datashed.base_dir();
+        let registry =
+            Artifacts::from_path(base_dir.join(Datashed::ARTIFACTS))?;
+
+        let mut table = Table::new();
+        table.set_header(Row::from(vec![
+            "artifact", "reason", "command",
+        ]));
+        table.load_preset(presets::UTF8_FULL_CONDENSED);
+
+        let mut outdated = 0;
+        for (key, artifact) in registry.artifacts.iter() {
+            let reasons = registry.staleness(base_dir, key);
+            if reasons.is_empty() {
+                continue;
+            }
+
+            let reasons: Vec<String> =
+                reasons.iter().map(ToString::to_string).collect();
+            table.add_row(vec![
+                key.clone(),
+                reasons.join("\n"),
+                format!("datashed {}", artifact.args.join(" ")),
+            ]);
+
+            outdated += 1;
+        }
+
+        if self.verbose {
+            eprintln!(
+                "{outdated} of {} artifact(s) outdated.",
+                registry.artifacts.len()
+            );
+        }
+
+        if outdated == 0 {
+            if !self.quiet {
+                println!("OK, all artifacts are up to date.");
+            }
+        } else {
+            println!("{table}");
+        }
+
+        if self.check && outdated > 0 {
+            bail!("{outdated} artifact(s) outdated");
+        }
+
+        Ok(())
+    }
+}
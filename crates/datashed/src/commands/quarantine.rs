@@ -0,0 +1,219 @@
+use std::fs::{self, metadata, OpenOptions};
+
+use clap::Parser;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Confirm;
+use polars::prelude::*;
+
+use crate::atomic::AtomicFile;
+use crate::audit;
+use crate::plan::Plan;
+use crate::prelude::*;
+use crate::stats::write_index;
+use crate::suspicious::FLAGS;
+use crate::trash::move_file;
+
+const PBAR_QUARANTINE: &str =
+    "Quarantining documents: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// The list of quarantined documents (in the quarantine directory).
+const QUARANTINE_LIST: &str = "quarantine.csv";
+
+/// Move suspicious documents aside for manual review.
+///
+/// Documents, which are flagged by the suspicious-content heuristics
+/// of the index (column `suspicious`: binary, markup, base64 or pdf),
+/// are moved into the quarantine directory (`quarantine/`), keeping
+/// their path relative to the root directory, and are removed from
+/// the index. The path, the IDN and the flags of each quarantined
+/// document are appended to `quarantine/quarantine.csv`. Reviewed
+/// documents can be moved back into the data directory and
+/// re-indexed.
+#[derive(Debug, Default, Parser)]
+pub(crate) struct Quarantine {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Quarantine only documents with the given flag. This option can
+    /// be specified multiple times. By default, documents with any
+    /// flag are quarantined.
+    #[arg(long = "flag", value_name = "flag", value_parser = FLAGS)]
+    flags: Vec<String>,
+
+    /// Whether to confirm the operation or not.
+    #[arg(short, long)]
+    force: bool,
+
+    /// Print a plan of the operations (counts, example paths and
+    /// affected bytes) without moving anything.
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// Wait for the index lock held by another process to be
+    /// released. By default (`--no-wait`), the command fails
+    /// immediately, if the index is locked.
+    #[arg(long, overrides_with = "no_wait")]
+    wait: bool,
+
+    /// Fail immediately, if the index is locked by another process.
+    #[arg(long, overrides_with = "wait")]
+    no_wait: bool,
+}
+
+impl Quarantine {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let base_dir = datashed.base_dir();
+        let _lock = datashed.lock(self.wait && !self.no_wait)?;
+
+        let index = datashed.index()?;
+        if index.column("suspicious").is_err() {
+            bail!(
+                "index has no `suspicious` column (re-run `datashed \
+                    index`)"
+            );
+        }
+
+        let path = index.column("path")?.str()?;
+        let idn = index.column("idn")?.str()?;
+        let suspicious = index.column("suspicious")?.str()?;
+
+        let mut flagged: Vec<(&str, &str, &str)> = vec![];
+        for idx in 0..index.height() {
+            let (Some(path), Some(flags)) =
+                (path.get(idx), suspicious.get(idx))
+            else {
+                continue;
+            };
+
+            if self.flags.is_empty()
+                || flags
+                    .split(',')
+                    .any(|flag| self.flags.iter().any(|f| f == flag))
+            {
+                flagged.push((
+                    path,
+                    idn.get(idx).unwrap_or_default(),
+                    flags,
+                ));
+            }
+        }
+
+        if flagged.is_empty() {
+            if self.verbose {
+                eprintln!("no suspicious documents found.");
+            }
+
+            return Ok(());
+        }
+
+        if self.dry_run {
+            let mut plan = Plan::default();
+            for (path, _, _) in flagged.iter() {
+                let size = metadata(base_dir.join(path))
+                    .map(|m| m.len())
+                    .unwrap_or_default();
+                plan.add("quarantine", *path, size);
+            }
+
+            let size = metadata(base_dir.join(Datashed::INDEX))?.len();
+            plan.add("rewrite", Datashed::INDEX, size);
+            plan.print()?;
+            return Ok(());
+        }
+
+        let confirm = self.force
+            || Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(format!(
+                    "Quarantine {} suspicious document(s)?",
+                    flagged.len()
+                ))
+                .default(true)
+                .show_default(true)
+                .interact()
+                .unwrap();
+
+        if !confirm {
+            return Ok(());
+        }
+
+        let quarantine_dir = datashed.quarantine_dir();
+        let list = quarantine_dir.join(QUARANTINE_LIST);
+        fs::create_dir_all(&quarantine_dir)?;
+
+        let has_header = list.is_file();
+        let mut writer =
+            csv::WriterBuilder::new().has_headers(false).from_writer(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&list)?,
+            );
+
+        if !has_header {
+            writer.write_record(["path", "idn", "suspicious"])?;
+        }
+
+        let pbar = ProgressBarBuilder::new(PBAR_QUARANTINE, self.quiet)
+            .len(flagged.len() as u64)
+            .build();
+
+        let mut moved = vec![];
+        for (path, idn, flags) in flagged.iter() {
+            pbar.inc(1);
+            let src = base_dir.join(path);
+            if !src.is_file() {
+                if !self.quiet {
+                    eprintln!("warning: document '{path}' not found");
+                }
+
+                continue;
+            }
+
+            move_file(&src, &quarantine_dir.join(path))?;
+            writer.write_record([*path, *idn, *flags])?;
+            moved.push(*path);
+        }
+
+        writer.flush()?;
+        pbar.finish_using_style();
+
+        let mut df = index
+            .clone()
+            .lazy()
+            .filter(
+                col("path")
+                    .is_in(lit(Series::from_iter(
+                        moved.iter().copied(),
+                    )))
+                    .not(),
+            )
+            .collect()?;
+
+        let mut out =
+            AtomicFile::create(base_dir.join(Datashed::INDEX))?;
+        write_index(&mut df, &mut out)?;
+        out.commit()?;
+
+        audit::record(&datashed, "quarantine", moved.len())?;
+
+        if self.verbose {
+            eprintln!(
+                "moved {} document(s) into '{}'.",
+                moved.len(),
+                quarantine_dir.display()
+            );
+        }
+
+        Ok(())
+    }
+}
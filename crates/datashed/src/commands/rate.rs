@@ -3,12 +3,18 @@ use std::io::Cursor;
 use std::path::PathBuf;
 
 use dialoguer::{Confirm, Input, Password, Select};
+use hashbrown::HashMap;
 use minus::{page_all, ExitStrategy, Pager};
 use polars::io::SerReader;
 use polars::prelude::*;
-use reqwest::{Client, StatusCode, Url};
+use rand::Rng;
+use regex::Regex;
+use reqwest::{StatusCode, Url};
 
+use crate::http::{HttpClient, HttpConfig};
 use crate::prelude::*;
+use crate::ratings::RatingScale;
+use crate::seed;
 use crate::utils::state_dir;
 
 /// Rate the data quality of documents.
@@ -45,8 +51,39 @@ pub(crate) struct Rate {
     #[arg(short, long, value_name = "filename")]
     output: Option<PathBuf>,
 
-    /// List of documents to be evaluated (in CSV format).
-    path: Option<PathBuf>,
+    /// The rating campaign. The ratings are given on the rating scale
+    /// of the campaign (see `[campaigns.<name>]`) instead of the
+    /// default scale.
+    #[arg(long, value_name = "name")]
+    campaign: Option<String>,
+
+    /// Show at most `n` bytes of a document in the pager (default:
+    /// `rate.preview-bytes` or the whole document).
+    #[arg(long, value_name = "n")]
+    preview_bytes: Option<usize>,
+
+    /// Instead of the beginning of a document, show `n` randomly
+    /// chosen sections, which share the preview bytes. The sections
+    /// are drawn from evenly spaced parts of the document, so that
+    /// the whole document is covered.
+    #[arg(long, value_name = "n", requires = "preview_bytes")]
+    sections: Option<usize>,
+
+    /// Highlight the matches of the regular expression in the pager
+    /// (e.g. GND labels or suspected OCR errors). This option can be
+    /// given multiple times and extends the patterns of the config
+    /// (`rate.highlight`).
+    #[arg(long, value_name = "pattern")]
+    highlight: Vec<String>,
+
+    /// The worklist of documents to be evaluated: a CSV or IPC file
+    /// (e.g. the output of `datashed select` or `datashed sample`)
+    /// or a HTTP(S) URL of such a file. The worklist must contain the
+    /// column `path`. Documents with a higher `priority` are rated
+    /// first; the optional column `campaign` assigns a document to a
+    /// rating campaign (default: `--campaign`).
+    #[arg(value_name = "worklist")]
+    worklist: Option<String>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -57,6 +94,230 @@ struct Request {
     hash: String,
     rating: String,
     comment: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    campaign: Option<String>,
+}
+
+/// The escape sequences of highlighted text.
+const HIGHLIGHT_START: &str = "\x1B[1;30;43m";
+const HIGHLIGHT_END: &str = "\x1B[0m";
+
+/// Returns the largest char boundary of the text, which isn't greater
+/// than `pos`.
+fn floor_char_boundary(text: &str, pos: usize) -> usize {
+    let mut pos = pos.min(text.len());
+    while !text.is_char_boundary(pos) {
+        pos -= 1;
+    }
+
+    pos
+}
+
+/// Returns the part of the document, which is shown in the pager: the
+/// first `max_bytes` bytes or, if `sections` is given, the given number
+/// of randomly chosen sections (one of each evenly spaced part of the
+/// document), which share the `max_bytes` bytes.
+fn preview<R: Rng>(
+    content: &str,
+    max_bytes: usize,
+    sections: Option<usize>,
+    rng: &mut R,
+) -> String {
+    if content.len() <= max_bytes {
+        return content.to_string();
+    }
+
+    let Some(n) = sections.filter(|n| *n > 1) else {
+        let end = floor_char_boundary(content, max_bytes);
+        return format!(
+            "{}\n\n[... {} more bytes ...]\n",
+            &content[..end],
+            content.len() - end
+        );
+    };
+
+    let part = content.len() / n;
+    let size = (max_bytes / n).min(part);
+    let mut result = String::new();
+
+    for k in 0..n {
+        let lo = k * part + rng.gen_range(0..=part - size);
+        let start = floor_char_boundary(content, lo);
+        let end = floor_char_boundary(content, lo + size);
+
+        result.push_str(&format!(
+            "[... section {}/{n} at byte {start} ...]\n\n{}\n\n",
+            k + 1,
+            &content[start..end]
+        ));
+    }
+
+    result
+}
+
+/// Highlights all matches of the patterns. Overlapping matches are
+/// merged.
+fn highlight(content: &str, patterns: &[Regex]) -> String {
+    let mut ranges: Vec<(usize, usize)> = patterns
+        .iter()
+        .flat_map(|re| re.find_iter(content))
+        .filter(|m| !m.is_empty())
+        .map(|m| (m.start(), m.end()))
+        .collect();
+
+    if ranges.is_empty() {
+        return content.to_string();
+    }
+
+    ranges.sort_unstable();
+    let mut result = String::with_capacity(content.len());
+    let mut pos = 0;
+    let mut iter = ranges.into_iter().peekable();
+
+    while let Some((start, mut end)) = iter.next() {
+        while let Some((next, next_end)) = iter.peek().copied() {
+            if next > end {
+                break;
+            }
+
+            end = end.max(next_end);
+            iter.next();
+        }
+
+        let start = start.max(pos);
+        result.push_str(&content[pos..start]);
+        result.push_str(HIGHLIGHT_START);
+        result.push_str(&content[start..end]);
+        result.push_str(HIGHLIGHT_END);
+        pos = end;
+    }
+
+    result.push_str(&content[pos..]);
+    result
+}
+
+/// Reads a worklist (CSV or IPC) from a local file or a HTTP(S) URL.
+async fn read_worklist(
+    client: &HttpClient,
+    location: &str,
+) -> DatashedResult<DataFrame> {
+    let bytes = if location.starts_with("http://")
+        || location.starts_with("https://")
+    {
+        let url = Url::parse(location).map_err(DatashedError::other)?;
+        let res = client.get(url).await?;
+        if res.status() != StatusCode::OK {
+            bail!("unable to get worklist '{location}'");
+        }
+
+        res.bytes().await?.to_vec()
+    } else {
+        fs::read(location)?
+    };
+
+    // IPC files start with the magic bytes "ARROW1".
+    let df = if bytes.starts_with(b"ARROW1") {
+        IpcReader::new(Cursor::new(bytes)).finish()?
+    } else {
+        CsvReader::new(Cursor::new(bytes)).finish()?
+    };
+
+    if df.column("path").is_err() {
+        bail!("worklist '{location}' has no column 'path'");
+    }
+
+    Ok(df)
+}
+
+/// Restricts the index to the documents of the worklist and orders
+/// them by priority (descending), campaign and the position in the
+/// worklist. The `priority` and `campaign` columns are optional.
+fn apply_worklist(
+    index: DataFrame,
+    worklist: DataFrame,
+) -> DatashedResult<DataFrame> {
+    let mut columns = vec![col("path"), col("worklist_pos")];
+    let mut exprs = vec![];
+    let mut descending = vec![];
+
+    if worklist.column("priority").is_ok() {
+        columns.push(col("priority"));
+        exprs.push(col("priority"));
+        descending.push(true);
+    }
+
+    if worklist.column("campaign").is_ok() {
+        columns.push(col("campaign").cast(DataType::String));
+        exprs.push(col("campaign"));
+        descending.push(false);
+    }
+
+    exprs.push(col("worklist_pos"));
+    descending.push(false);
+
+    Ok(index
+        .lazy()
+        .join(
+            worklist
+                .with_row_index("worklist_pos".into(), None)?
+                .lazy()
+                .select(columns),
+            [col("path")],
+            [col("path")],
+            JoinArgs::new(JoinType::Inner),
+        )
+        .sort_by_exprs(
+            exprs,
+            SortMultipleOptions::default()
+                .with_order_descending_multi(descending)
+                .with_nulls_last(true),
+        )
+        .collect()?)
+}
+
+/// Fetches the rating scale of the campaign from the datashed and
+/// returns the scale and the items of the selection prompt.
+async fn rating_scale(
+    client: &HttpClient,
+    base_uri: &Url,
+    campaign: Option<&str>,
+) -> DatashedResult<(RatingScale, Vec<String>)> {
+    let mut scale_url = base_uri.clone();
+    scale_url.set_path("/ratings/scale");
+    if let Some(campaign) = campaign {
+        scale_url
+            .query_pairs_mut()
+            .append_pair("campaign", campaign);
+    }
+
+    let res = client.get(scale_url).await?;
+    if res.status() != StatusCode::OK {
+        bail!("unable to get rating scale: {}", res.text().await?);
+    }
+
+    let scale: RatingScale = res.json().await?;
+    let width = scale
+        .levels
+        .iter()
+        .map(|level| level.label.chars().count())
+        .max()
+        .unwrap_or_default();
+    let items: Vec<String> = scale
+        .levels
+        .iter()
+        .map(|level| {
+            if level.description.is_empty() {
+                level.label.clone()
+            } else {
+                format!(
+                    "{:width$} ({})",
+                    level.label, level.description
+                )
+            }
+        })
+        .collect();
+
+    Ok((scale, items))
 }
 
 impl Rate {
@@ -77,34 +338,56 @@ impl Rate {
                 .unwrap(),
         };
 
+        // The HTTP and pager options are taken from the config of the
+        // datashed in the current directory (if any).
+        let config = Datashed::discover().and_then(|d| d.config()).ok();
+        let client = match config {
+            Some(ref config) => HttpClient::from_config(&config.http)?,
+            None => HttpClient::from_config(&HttpConfig::default())?,
+        };
+
+        let options = config.and_then(|config| config.rate);
+        let preview_bytes = self.preview_bytes.or(options
+            .as_ref()
+            .and_then(|options| options.preview_bytes));
+        let patterns = options
+            .map(|options| options.highlight)
+            .unwrap_or_default()
+            .iter()
+            .chain(self.highlight.iter())
+            .map(|pattern| Regex::new(pattern))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(DatashedError::other)?;
+
+        let mut rng = seed::rng();
+
         let mut base_uri = Url::parse("http://localhost").unwrap();
         base_uri.set_port(self.port).unwrap();
         if let Some(host) = self.address {
             base_uri.set_host(Some(&host)).unwrap();
         }
 
+        // Rating scales (by campaign)
+        let mut scales = HashMap::new();
+        scales.insert(
+            self.campaign.clone(),
+            rating_scale(&client, &base_uri, self.campaign.as_deref())
+                .await?,
+        );
+
         // Index
         let mut index_url = base_uri.clone();
         index_url.set_path("/index.ipc");
 
-        let body = reqwest::get(index_url).await?.bytes().await?;
+        let body = client.get(index_url).await?.bytes().await?;
         if body.is_empty() {
             bail!("unable to get datashed index");
         }
 
         let mut index = IpcReader::new(Cursor::new(body)).finish()?;
-        if let Some(path) = self.path {
-            let paths = CsvReader::new(File::open(path)?).finish()?;
-            index = index
-                .clone()
-                .lazy()
-                .join(
-                    paths.clone().lazy(),
-                    [col("path")],
-                    [col("path")],
-                    JoinArgs::new(JoinType::Semi),
-                )
-                .collect()?;
+        if let Some(ref location) = self.worklist {
+            let worklist = read_worklist(&client, location).await?;
+            index = apply_worklist(index, worklist)?;
         }
 
         let state_file = state_dir()?.join("ratings.csv");
@@ -116,7 +399,9 @@ impl Rate {
         }
 
         if index.height() > 0 {
-            let remote = index.column("remote")?.str()?.get(0).unwrap();
+            let remote =
+                index.column("remote")?.cast(&DataType::String)?;
+            let remote = remote.str()?.get(0).unwrap().to_string();
             let state_df = CsvReader::new(File::open(&state_file)?)
                 .finish()?
                 .lazy()
@@ -140,21 +425,34 @@ impl Rate {
                 OpenOptions::new().append(true).open(state_file)?,
             );
 
-        let remote = index.column("remote")?.str()?;
+        let remote = index.column("remote")?.cast(&DataType::String)?;
+        let remote = remote.str()?;
         let path = index.column("path")?.str()?;
         let hash = index.column("hash")?.str()?;
         let idn = index.column("idn")?.str()?;
+        let campaign = match index.column("campaign") {
+            Ok(column) => column.cast(&DataType::String)?,
+            Err(_) => Column::full_null(
+                "campaign".into(),
+                index.height(),
+                &DataType::String,
+            ),
+        };
+        let campaign = campaign.str()?;
         let len = index.height();
 
         let mut ratings_url = base_uri.clone();
         ratings_url.set_path("/ratings");
-        let client = Client::new();
 
         for idx in 0..len {
             let remote = remote.get(idx).unwrap();
             let filename = path.get(idx).unwrap();
             let hash = hash.get(idx).unwrap();
             let idn = idn.get(idx).unwrap();
+            let campaign = campaign
+                .get(idx)
+                .map(String::from)
+                .or(self.campaign.clone());
 
             print!("\x1B[2J");
             let header = format!(
@@ -182,8 +480,21 @@ impl Rate {
 
             let mut document_url = base_uri.clone();
             document_url.set_path(filename);
-            let content =
-                reqwest::get(document_url).await?.text().await?;
+            let mut content =
+                client.get(document_url).await?.text().await?;
+
+            if let Some(max_bytes) = preview_bytes {
+                content = preview(
+                    &content,
+                    max_bytes,
+                    self.sections,
+                    &mut rng,
+                );
+            }
+
+            if !patterns.is_empty() {
+                content = highlight(&content, &patterns);
+            }
 
             let pager = Pager::new();
             pager.set_exit_strategy(ExitStrategy::PagerQuit)?;
@@ -192,28 +503,27 @@ impl Rate {
             pager.push_str(&content)?;
             page_all(pager)?;
 
+            if !scales.contains_key(&campaign) {
+                let scale = rating_scale(
+                    &client,
+                    &base_uri,
+                    campaign.as_deref(),
+                )
+                .await?;
+                scales.insert(campaign.clone(), scale);
+            }
+
+            let (scale, items) = &scales[&campaign];
             let prompt = "Select rating of data quality";
             let rating = loop {
                 let interaction = Select::new()
                     .with_prompt(prompt)
-                    .items(&[
-                        "C  (correct)",
-                        "C- (correct minus)",
-                        "P+ (partial plus)",
-                        "P  (partial)",
-                        "P- (partial minus)",
-                        "I  (incorrect)",
-                    ])
+                    .items(items)
                     .default(0)
                     .interact();
 
                 match interaction {
-                    Ok(0) => break "C",
-                    Ok(1) => break "C-",
-                    Ok(2) => break "P+",
-                    Ok(3) => break "P",
-                    Ok(4) => break "P-",
-                    Ok(5) => break "I",
+                    Ok(idx) => break scale.levels[idx].label.as_str(),
                     _ => continue,
                 }
             };
@@ -225,17 +535,20 @@ impl Rate {
                 .interact_text()
                 .unwrap();
 
+            let request = Request {
+                username: username.clone(),
+                secret: secret.clone(),
+                path: filename.to_string(),
+                hash: hash.to_string(),
+                rating: rating.to_string(),
+                comment: comment.to_string(),
+                campaign: campaign.clone(),
+            };
+
             let result = client
-                .post(ratings_url.clone())
-                .json(&Request {
-                    username: username.clone(),
-                    secret: secret.clone(),
-                    path: filename.to_string(),
-                    hash: hash.to_string(),
-                    rating: rating.to_string(),
-                    comment: comment.to_string(),
+                .send_once(|client| {
+                    client.post(ratings_url.clone()).json(&request)
                 })
-                .send()
                 .await;
 
             let Ok(res) = result else {
@@ -265,3 +578,73 @@ impl Rate {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn rate_worklist() -> TestResult {
+        let index = df!(
+            "path" => ["a.txt", "b.txt", "c.txt", "d.txt"],
+            "hash" => ["1", "2", "3", "4"],
+        )?;
+
+        let worklist = df!("path" => ["c.txt", "x.txt", "a.txt"])?;
+        let df = apply_worklist(index.clone(), worklist)?;
+        assert_eq!(
+            df.column("path")?
+                .str()?
+                .into_no_null_iter()
+                .collect::<Vec<_>>(),
+            ["c.txt", "a.txt"]
+        );
+
+        let worklist = df!(
+            "path" => ["a.txt", "b.txt", "c.txt", "d.txt"],
+            "priority" => [Some(1), None, Some(5), Some(1)],
+            "campaign" => ["foo", "foo", "foo", "bar"],
+        )?;
+        let df = apply_worklist(index, worklist)?;
+        assert_eq!(
+            df.column("path")?
+                .str()?
+                .into_no_null_iter()
+                .collect::<Vec<_>>(),
+            ["c.txt", "d.txt", "a.txt", "b.txt"]
+        );
+        assert_eq!(df.column("campaign")?.str()?.get(1), Some("bar"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn pager_preview() {
+        let mut rng = seed::rng();
+        let content = "äbcdefghij".repeat(10);
+
+        assert_eq!(preview("abc", 10, None, &mut rng), "abc");
+        assert!(preview(&content, 2, None, &mut rng)
+            .starts_with("ä\n\n[... 108 more bytes ...]"));
+
+        let sections = preview(&content, 20, Some(4), &mut rng);
+        assert_eq!(sections.matches("[... section").count(), 4);
+        assert!(sections.contains("section 4/4"));
+    }
+
+    #[test]
+    fn pager_highlight() {
+        let patterns = [
+            Regex::new(r"\bGND\b").unwrap(),
+            Regex::new("ND [a-z]+").unwrap(),
+        ];
+
+        assert_eq!(
+            highlight("Die GND ist gut", &patterns),
+            format!("Die {HIGHLIGHT_START}GND ist{HIGHLIGHT_END} gut")
+        );
+        assert_eq!(highlight("nichts", &patterns), "nichts");
+    }
+}
@@ -0,0 +1,447 @@
+use std::fs::{File, OpenOptions};
+use std::io::stdout;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use comfy_table::{presets, Row, Table};
+use polars::prelude::*;
+
+use crate::atomic::AtomicFile;
+use crate::campaign::campaign_progress;
+use crate::prelude::*;
+use crate::ratings::{
+    import_ratings, length_bucket, read_ratings, scored,
+};
+use crate::utils::{write_df, OutputFormat};
+
+/// Manage the human ratings of the datashed.
+#[derive(Debug, clap::Parser)]
+pub(crate) struct Ratings {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    #[clap(subcommand)]
+    cmd: Command,
+}
+
+#[derive(Debug, clap::Parser)]
+pub(crate) enum Command {
+    /// Show the progress of the rating campaign \<name\>.
+    ///
+    /// The progress consists of the number of ratings per user (and
+    /// the expected share of each member of the campaign), the
+    /// coverage per document kind and the number of documents, which
+    /// remain to be rated. The campaigns are defined in the config
+    /// (`[campaigns.<name>]`).
+    Progress {
+        /// Write the progress as JSON to the standard output.
+        #[arg(long, conflicts_with = "remind")]
+        json: bool,
+
+        /// Print a reminder for each member of the campaign, who is
+        /// behind the expected share of ratings.
+        #[arg(long)]
+        remind: bool,
+
+        name: String,
+    },
+
+    /// Import externally collected ratings.
+    ///
+    /// The ratings are read from a CSV file with a header, which
+    /// contains the columns `rating`, `username` (or `user`) and
+    /// either `path` or `ppn`, and optionally the columns `hash`,
+    /// `comment` and `created_at`. The ratings are matched against the
+    /// current documents of the index and merged into the ratings
+    /// store of `datashed serve`. Rows of unknown documents, malformed
+    /// hashes, outdated document versions (hash mismatch), invalid
+    /// ratings and duplicates are rejected and reported (columns
+    /// `row`, `key` and `reason`).
+    Import {
+        /// The rating campaign, whose rating scale is used to validate
+        /// the ratings (default: the default scale).
+        #[arg(long, value_name = "name")]
+        campaign: Option<String>,
+
+        /// Validate the ratings only; don't merge them into the
+        /// ratings store.
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Write the report of rejected rows into `filename` instead
+        /// of `stdout`.
+        #[arg(short, long, value_name = "filename")]
+        output: Option<PathBuf>,
+
+        /// The output format of the report (default: CSV).
+        #[arg(long, value_name = "format")]
+        format: Option<OutputFormat>,
+
+        /// Wait for the lock of the ratings store held by another
+        /// process (e.g. `datashed serve`) to be released. By default
+        /// (`--no-wait`), the command fails immediately, if the
+        /// ratings store is locked.
+        #[arg(long, overrides_with = "no_wait")]
+        wait: bool,
+
+        /// Fail immediately, if the ratings store is locked by another
+        /// process.
+        #[arg(long, overrides_with = "wait")]
+        no_wait: bool,
+
+        /// The CSV file of the external ratings.
+        path: PathBuf,
+    },
+
+    /// Export the ratings as a single analysis table.
+    ///
+    /// The table contains the columns `path`, `hash` (the first eight
+    /// characters), `rating`, `score` (the numeric score of the rating
+    /// on the rating scale) and `username`. With `--with-index`, the
+    /// selected metric columns of the index and the column
+    /// `length_bucket` (a logarithmic bucket of the text length) are
+    /// added. Ratings of outdated document versions have no metrics.
+    Export {
+        /// The rating campaign, whose rating scale is used to compute
+        /// the scores (default: the default scale).
+        #[arg(long, value_name = "name")]
+        campaign: Option<String>,
+
+        /// Add the metric columns of the index.
+        #[arg(long)]
+        with_index: bool,
+
+        /// The index columns to add (comma-separated). By default,
+        /// the document kind, the language and all available quality
+        /// metrics are added.
+        #[arg(
+            long,
+            value_name = "columns",
+            value_delimiter = ',',
+            requires = "with_index"
+        )]
+        columns: Vec<String>,
+
+        /// The ratings file (default: the ratings store of `datashed
+        /// serve`).
+        #[arg(long, value_name = "filename")]
+        ratings: Option<PathBuf>,
+
+        /// Write output into `filename` instead of `stdout`.
+        #[arg(short, long, value_name = "filename")]
+        output: Option<PathBuf>,
+
+        /// The output format (default: CSV).
+        #[arg(long, value_name = "format")]
+        format: Option<OutputFormat>,
+    },
+}
+
+/// The index columns, which are added by `ratings export --with-index`
+/// by default (if available).
+const EXPORT_COLUMNS: [&str; 16] = [
+    "kind",
+    "lang_code",
+    "lang_score",
+    "script",
+    "lfreq",
+    "perplexity",
+    "alpha",
+    "words",
+    "avg_word_len",
+    "ttr",
+    "digit_ratio",
+    "currency_token_ratio",
+    "year_mention_count",
+    "repetition",
+    "size",
+    "strlen",
+];
+
+impl Ratings {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+
+        match self.cmd {
+            Command::Export {
+                campaign,
+                with_index,
+                columns,
+                ratings,
+                output,
+                format,
+            } => {
+                let config = datashed.config()?;
+                let scale = config.rating_scale(campaign.as_deref())?;
+                let path = ratings.unwrap_or_else(|| {
+                    datashed.temp_dir().join(Datashed::RATINGS)
+                });
+
+                if !path.is_file() {
+                    bail!(
+                        "ratings file '{}' not found",
+                        path.display()
+                    );
+                }
+
+                let mut df = scored(&read_ratings(path)?, &scale)?;
+                if with_index {
+                    let index = datashed.index()?;
+                    let columns: Vec<String> = if columns.is_empty() {
+                        EXPORT_COLUMNS
+                            .iter()
+                            .filter(|name| index.column(name).is_ok())
+                            .map(ToString::to_string)
+                            .collect()
+                    } else {
+                        columns
+                    };
+
+                    let hash: Vec<Option<String>> = index
+                        .column("hash")?
+                        .str()?
+                        .iter()
+                        .map(|hash| {
+                            hash.map(|hash| {
+                                hash.get(..8).unwrap_or(hash).into()
+                            })
+                        })
+                        .collect();
+
+                    let mut metrics = index.select(
+                        ["path".to_string()]
+                            .into_iter()
+                            .chain(columns.iter().cloned()),
+                    )?;
+                    metrics.with_column(Column::new(
+                        "hash".into(),
+                        hash,
+                    ))?;
+
+                    if let Ok(strlen) = index.column("strlen") {
+                        let buckets: Vec<Option<&str>> = strlen
+                            .cast(&DataType::UInt64)?
+                            .u64()?
+                            .iter()
+                            .map(|n| n.map(length_bucket))
+                            .collect();
+                        metrics.with_column(Column::new(
+                            "length_bucket".into(),
+                            buckets,
+                        ))?;
+                    }
+
+                    df = df
+                        .lazy()
+                        .join(
+                            metrics.lazy(),
+                            [col("path"), col("hash")],
+                            [col("path"), col("hash")],
+                            JoinArgs::new(JoinType::Left),
+                        )
+                        .collect()?;
+                }
+
+                if self.verbose {
+                    eprintln!("exported {} rating(s).", df.height());
+                }
+
+                let format = format.unwrap_or(OutputFormat::Csv);
+                match output {
+                    Some(path) => {
+                        let mut out = AtomicFile::create(path)?;
+                        write_df(&mut df, format, &mut out)?;
+                        out.commit()?;
+                    }
+                    None => {
+                        write_df(&mut df, format, stdout().lock())?;
+                    }
+                }
+            }
+            Command::Import {
+                campaign,
+                dry_run,
+                output,
+                format,
+                wait,
+                no_wait,
+                path,
+            } => {
+                let config = datashed.config()?;
+                let scale = config.rating_scale(campaign.as_deref())?;
+                let store = datashed.temp_dir().join(Datashed::RATINGS);
+
+                // The lock is held while reading and appending to the
+                // store, so that the duplicate check isn't based on a
+                // stale snapshot.
+                let _lock = if !dry_run {
+                    Some(datashed.lock_ratings(wait && !no_wait)?)
+                } else {
+                    None
+                };
+                let existing = if store.is_file() {
+                    read_ratings(&store)?
+                } else {
+                    DataFrame::empty()
+                };
+
+                let created_at = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_millis()
+                    .to_string();
+
+                let import = import_ratings(
+                    File::open(path)?,
+                    &datashed.index()?,
+                    &existing,
+                    &scale,
+                    &config.metadata.name,
+                    &created_at,
+                )?;
+
+                if !dry_run && !import.records.is_empty() {
+                    let mut writer = csv::WriterBuilder::new()
+                        .from_writer(
+                            OpenOptions::new()
+                                .create(true)
+                                .append(true)
+                                .open(&store)?,
+                        );
+
+                    for record in import.records.iter() {
+                        writer.write_record(record)?;
+                    }
+
+                    writer.flush()?;
+                }
+
+                if !self.quiet {
+                    eprintln!(
+                        "{} {} rating(s), rejected {} row(s).",
+                        if dry_run { "Validated" } else { "Imported" },
+                        import.records.len(),
+                        import.rejected.len()
+                    );
+                }
+
+                let mut rows = vec![];
+                let mut keys = vec![];
+                let mut reasons = vec![];
+                for (row, key, reason) in import.rejected.into_iter() {
+                    rows.push(row as u64);
+                    keys.push(key);
+                    reasons.push(reason.to_string());
+                }
+
+                let mut df = DataFrame::new(vec![
+                    Column::new("row".into(), rows),
+                    Column::new("key".into(), keys),
+                    Column::new("reason".into(), reasons),
+                ])?;
+
+                let format = format.unwrap_or(OutputFormat::Csv);
+                match output {
+                    Some(path) => {
+                        let mut out = AtomicFile::create(path)?;
+                        write_df(&mut df, format, &mut out)?;
+                        out.commit()?;
+                    }
+                    None => {
+                        write_df(&mut df, format, stdout().lock())?;
+                    }
+                }
+            }
+            Command::Progress { json, remind, name } => {
+                let progress = campaign_progress(&datashed, &name)?;
+
+                if json {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&progress)
+                            .map_err(DatashedError::other)?
+                    );
+                    return Ok(());
+                }
+
+                if remind {
+                    for (user, stats) in progress.users.iter() {
+                        let behind = stats.behind();
+                        if behind > 0 {
+                            println!(
+                                "{user}: {} of {} rating(s), {behind} \
+                                    remaining in campaign '{name}'",
+                                stats.ratings,
+                                stats.expected.unwrap_or_default(),
+                            );
+                        }
+                    }
+
+                    return Ok(());
+                }
+
+                let mut table = Table::new();
+                table.load_preset(presets::UTF8_FULL_CONDENSED);
+                table.set_header(Row::from(vec![
+                    "user", "ratings", "expected", "behind",
+                ]));
+
+                for (user, stats) in progress.users.iter() {
+                    table.add_row(vec![
+                        user.clone(),
+                        stats.ratings.to_string(),
+                        stats
+                            .expected
+                            .map(|n| n.to_string())
+                            .unwrap_or_default(),
+                        stats.behind().to_string(),
+                    ]);
+                }
+
+                println!("{table}");
+
+                let mut table = Table::new();
+                table.load_preset(presets::UTF8_FULL_CONDENSED);
+                table.set_header(Row::from(vec![
+                    "kind",
+                    "documents",
+                    "rated",
+                    "coverage",
+                ]));
+
+                for (kind, coverage) in progress.kinds.iter() {
+                    table.add_row(vec![
+                        kind.clone(),
+                        coverage.documents.to_string(),
+                        coverage.rated.to_string(),
+                        format!(
+                            "{:.1}%",
+                            100.0 * coverage.rated as f64
+                                / coverage.documents.max(1) as f64
+                        ),
+                    ]);
+                }
+
+                println!("{table}");
+                println!(
+                    "{} of {} document(s) rated ({} in campaign), {} \
+                        remaining.",
+                    progress.rated,
+                    progress.target,
+                    progress.documents,
+                    progress.remaining
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
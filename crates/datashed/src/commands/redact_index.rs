@@ -0,0 +1,88 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::prelude::*;
+use crate::redact;
+
+/// Redact columns of the index before publishing it.
+///
+/// The column policies of the config (`[redact.<column>]`) are applied
+/// to the index: `drop` removes a column, `hash` replaces the values
+/// by their (salted) SHA256 digest and `generalize` coarsens the
+/// values (dates to years, numbers to multiples of `step` and paths to
+/// their first `depth` components). The redacted index is written to
+/// `index.redacted.ipc`, which is served by `datashed serve` instead of
+/// the index, if any policy is configured. The redacted index is also
+/// regenerated by each `datashed index` run. A manifest of the applied
+/// redactions (JSON) is written next to the redacted index. If the
+/// config contains a signing key (`signing.key-file`), the redacted
+/// index is (re-)signed, so that the published signature matches the
+/// published index.
+#[derive(Debug, Parser)]
+pub(crate) struct RedactIndex {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Write the redacted index into `filename`. By default, the
+    /// index is written to `index.redacted.ipc` into the root
+    /// directory.
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    /// Write the manifest into `filename`. By default, the manifest is
+    /// written next to the redacted index
+    /// (`<output>.redactions.json`).
+    #[arg(short, long, value_name = "filename")]
+    manifest: Option<PathBuf>,
+}
+
+impl RedactIndex {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let config = datashed.config()?;
+
+        if config.redact.is_empty() {
+            bail!("no redaction policies (see `[redact.<column>]`)");
+        }
+
+        let output = self.output.unwrap_or_else(|| {
+            datashed.base_dir().join(Datashed::REDACTED_INDEX)
+        });
+
+        let path = self
+            .manifest
+            .unwrap_or_else(|| redact::manifest_path(&output));
+        let manifest =
+            redact::publish(&datashed, &config, &output, &path)?;
+
+        if !self.quiet {
+            for column in manifest.missing.iter() {
+                eprintln!("warning: column '{column}' not in index");
+            }
+        }
+
+        if self.verbose {
+            for redaction in manifest.redactions.iter() {
+                eprintln!(
+                    "{}: {} ({} value(s))",
+                    redaction.column,
+                    redaction.policy,
+                    redaction.values
+                );
+            }
+
+            eprintln!("wrote redacted index '{}'.", output.display());
+        }
+
+        Ok(())
+    }
+}
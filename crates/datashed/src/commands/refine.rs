@@ -0,0 +1,305 @@
+use std::io::stdout;
+use std::path::PathBuf;
+
+use clap::Parser;
+use hashbrown::{HashMap, HashSet};
+use pica_record::prelude::*;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+
+use super::index::kind::KindMap;
+use crate::atomic::AtomicFile;
+use crate::document::{kind_from_path, DocumentKind};
+use crate::prelude::*;
+use crate::sql::select_where;
+use crate::utils::{write_df, OutputFormat};
+
+const PBAR_MATCH: &str = "Matching records: {human_pos} | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// Preview the document kind refinements of the config.
+///
+/// The refinement rules (`[kinds.<kind>]`) are evaluated against the
+/// PICA+ dump, without modifying the index (the refinements are baked
+/// into the index by `datashed index <path>`). The source kind of a
+/// document is derived from its path. By default, the documents, whose
+/// kind would change, are listed (columns `path`, `idn`, `kind` and
+/// `refined`).
+///
+/// With `--report`, a coverage report per rule is written instead: the
+/// number of matching records (`records`) and indexed documents
+/// (`documents`), the number of conflicts (`conflicts`: records, which
+/// are also matched by a rule of the same source kind with a different
+/// target) and example PPNs (`examples`). In case of a conflict, the
+/// last matching rule (in the order of the report) wins.
+#[derive(Debug, Parser)]
+pub(crate) struct Refine {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Write a coverage report per refinement rule instead of the
+    /// list of refined documents.
+    #[arg(long)]
+    report: bool,
+
+    /// The maximum number of example PPNs per rule.
+    #[arg(
+        long,
+        default_value = "5",
+        value_name = "n",
+        requires = "report"
+    )]
+    examples: usize,
+
+    /// Write output into `filename` instead of `stdout`.
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    /// The output format (default: CSV).
+    #[arg(long, value_name = "format")]
+    format: Option<OutputFormat>,
+
+    /// An optional predicate to filter the document-set.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+
+    /// The path to the PICA+ dump.
+    path: PathBuf,
+}
+
+/// The coverage of a refinement rule.
+#[derive(Debug, Default)]
+struct Coverage {
+    records: u64,
+    documents: u64,
+    conflicts: u64,
+    examples: Vec<String>,
+}
+
+impl Refine {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let config = datashed.config()?;
+        let kind_map = KindMap::from_config(&config)?;
+        let rules = kind_map.rules();
+
+        if rules.is_empty() {
+            bail!("no kind refinements configured");
+        }
+
+        let mut index = datashed.index_lazy()?;
+        if let Some(ref predicate) = self.predicate {
+            let mut ctx = SQLContext::new();
+            ctx.register("df", index);
+            index = ctx.execute(&select_where(predicate))?;
+        }
+
+        let df = index.select([col("path"), col("idn")]).collect()?;
+        let path = df.column("path")?.str()?;
+        let idn = df.column("idn")?.str()?;
+
+        // The (source) kinds of the indexed documents by PPN.
+        let mut documents: HashMap<&str, Vec<(&str, DocumentKind)>> =
+            HashMap::new();
+        for (path, idn) in path.into_iter().zip(idn.into_iter()) {
+            if let (Some(path), Some(idn)) = (path, idn) {
+                documents
+                    .entry(idn)
+                    .or_default()
+                    .push((path, kind_from_path(path)));
+            }
+        }
+
+        let pbar =
+            ProgressBarBuilder::new(PBAR_MATCH, self.quiet).build();
+        let mut coverage: Vec<Coverage> =
+            rules.iter().map(|_| Coverage::default()).collect();
+        let mut refined: Vec<(String, String, String, String)> = vec![];
+        let mut conflicts = HashSet::new();
+
+        let mut reader = ReaderBuilder::new().from_path(&self.path)?;
+        while let Some(result) = reader.next_byte_record() {
+            pbar.inc(1);
+
+            let Ok(record) = result else {
+                continue;
+            };
+
+            let matches = kind_map.matches(&record);
+            if matches.is_empty() {
+                continue;
+            }
+
+            let ppn = record.ppn().to_string();
+            let docs = documents
+                .get(ppn.as_str())
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+
+            for idx in matches.iter() {
+                let rule = &rules[*idx];
+                let stats = &mut coverage[*idx];
+                stats.records += 1;
+                stats.documents += docs
+                    .iter()
+                    .filter(|(_, kind)| kind == &rule.from)
+                    .count() as u64;
+
+                if stats.examples.len() < self.examples {
+                    stats.examples.push(ppn.clone());
+                }
+
+                let conflict = matches.iter().any(|other| {
+                    rules[*other].from == rule.from
+                        && rules[*other].to != rule.to
+                });
+
+                if conflict {
+                    stats.conflicts += 1;
+                    conflicts.insert(ppn.clone());
+                }
+            }
+
+            for (path, kind) in docs.iter() {
+                let Some(rule) = matches
+                    .iter()
+                    .rev()
+                    .map(|idx| &rules[*idx])
+                    .find(|rule| &rule.from == kind)
+                else {
+                    continue;
+                };
+
+                refined.push((
+                    path.to_string(),
+                    ppn.clone(),
+                    kind.to_string(),
+                    rule.to.to_string(),
+                ));
+            }
+        }
+
+        pbar.finish_using_style();
+
+        if self.verbose {
+            eprintln!(
+                "{} document(s) would be refined, {} record(s) with \
+                    conflicting rules.",
+                refined.len(),
+                conflicts.len()
+            );
+        }
+
+        let mut df = if self.report {
+            DataFrame::new(vec![
+                Column::new(
+                    "rule".into(),
+                    (1..=rules.len() as u32).collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "from".into(),
+                    rules
+                        .iter()
+                        .map(|rule| rule.from.to_string())
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "target".into(),
+                    rules
+                        .iter()
+                        .map(|rule| rule.to.to_string())
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "filter".into(),
+                    rules
+                        .iter()
+                        .map(|rule| rule.filter.as_str())
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "records".into(),
+                    coverage
+                        .iter()
+                        .map(|c| c.records)
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "documents".into(),
+                    coverage
+                        .iter()
+                        .map(|c| c.documents)
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "conflicts".into(),
+                    coverage
+                        .iter()
+                        .map(|c| c.conflicts)
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "examples".into(),
+                    coverage
+                        .iter()
+                        .map(|c| c.examples.join(","))
+                        .collect::<Vec<_>>(),
+                ),
+            ])?
+        } else {
+            refined.sort_unstable();
+            DataFrame::new(vec![
+                Column::new(
+                    "path".into(),
+                    refined
+                        .iter()
+                        .map(|r| r.0.as_str())
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "idn".into(),
+                    refined
+                        .iter()
+                        .map(|r| r.1.as_str())
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "kind".into(),
+                    refined
+                        .iter()
+                        .map(|r| r.2.as_str())
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "refined".into(),
+                    refined
+                        .iter()
+                        .map(|r| r.3.as_str())
+                        .collect::<Vec<_>>(),
+                ),
+            ])?
+        };
+
+        let format = self.format.unwrap_or(OutputFormat::Csv);
+        match self.output {
+            Some(path) => {
+                let mut out = AtomicFile::create(path)?;
+                write_df(&mut df, format, &mut out)?;
+                out.commit()?;
+            }
+            None => {
+                write_df(&mut df, format, stdout().lock())?;
+            }
+        }
+
+        Ok(())
+    }
+}
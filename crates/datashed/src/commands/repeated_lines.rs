@@ -0,0 +1,157 @@
+use std::io::stdout;
+use std::path::PathBuf;
+
+use clap::Parser;
+use indicatif::ParallelProgressIterator;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use rayon::prelude::*;
+
+use crate::atomic::AtomicFile;
+use crate::document::MIN_LINE_REPEATS;
+use crate::prelude::*;
+use crate::sql::select_where;
+use crate::utils::{write_df, OutputFormat};
+
+const PBAR_SCAN: &str =
+    "Scanning documents: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// Report lines, which are repeated verbatim within a document.
+///
+/// Running headers, footers and page numbers of paginated scans occur
+/// many times within a document. A line is considered repeated, if its
+/// normalized form (collapsed whitespace, digits replaced) occurs at
+/// least `--min-repeats` times within the document. The result is a
+/// table with the columns `path`, `repetition` (the fraction of
+/// non-empty lines, which are repeated), `line` and `count`, which
+/// contains the most frequent repeated lines of each document with at
+/// least one repeated line (highest score first).
+#[derive(Debug, Parser)]
+pub(crate) struct RepeatedLines {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The minimum number of occurrences of a line within a document
+    /// to be considered repeated.
+    #[arg(long, default_value_t = MIN_LINE_REPEATS, value_name = "n")]
+    min_repeats: usize,
+
+    /// Report only documents with a repetition score of at least
+    /// `score`.
+    #[arg(long, default_value = "0.0", value_name = "score")]
+    min_score: f64,
+
+    /// The number of repeated lines to report per document (most
+    /// frequent first).
+    #[arg(short = 'n', long, default_value = "5", value_name = "n")]
+    limit: usize,
+
+    /// Write output into `filename` instead of `stdout`.
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    /// The output format (default: CSV).
+    #[arg(long, value_name = "format")]
+    format: Option<OutputFormat>,
+
+    /// An optional predicate to filter the document-set.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+}
+
+type Row = (String, f64, Vec<(String, usize)>);
+
+impl RepeatedLines {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let base_dir = datashed.base_dir();
+        let mut index = datashed.index_lazy()?;
+
+        if let Some(ref predicate) = self.predicate {
+            let mut ctx = SQLContext::new();
+            ctx.register("df", index);
+            index = ctx.execute(&select_where(predicate))?;
+        }
+
+        let df = index.select([col("path")]).collect()?;
+
+        let path = df.column("path")?.str()?;
+        let pbar = ProgressBarBuilder::new(PBAR_SCAN, self.quiet)
+            .len(df.height() as u64)
+            .build();
+
+        let mut rows = (0..df.height())
+            .into_par_iter()
+            .progress_with(pbar)
+            .map(|idx| -> DatashedResult<Option<Row>> {
+                let path = path.get(idx).unwrap();
+                let doc = Document::from_path(base_dir.join(path))?;
+
+                let mut lines = doc.repeated_lines(self.min_repeats);
+                let score = doc.repetition(self.min_repeats);
+                if lines.is_empty() || score < self.min_score {
+                    return Ok(None);
+                }
+
+                lines.truncate(self.limit);
+                Ok(Some((path.to_string(), score, lines)))
+            })
+            .collect::<DatashedResult<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<Row>>();
+
+        rows.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        if self.verbose {
+            eprintln!(
+                "found {} documents with repeated lines.",
+                rows.len()
+            );
+        }
+
+        let mut paths = vec![];
+        let mut scores = vec![];
+        let mut lines = vec![];
+        let mut counts = vec![];
+
+        for (path, score, repeated) in rows.into_iter() {
+            for (line, count) in repeated.into_iter() {
+                paths.push(path.clone());
+                scores.push(score);
+                lines.push(line);
+                counts.push(count as u64);
+            }
+        }
+
+        let mut df = DataFrame::new(vec![
+            Column::new("path".into(), paths),
+            Column::new("repetition".into(), scores),
+            Column::new("line".into(), lines),
+            Column::new("count".into(), counts),
+        ])?;
+
+        let format = self.format.unwrap_or(OutputFormat::Csv);
+        match self.output {
+            Some(path) => {
+                let mut out = AtomicFile::create(path)?;
+                write_df(&mut df, format, &mut out)?;
+                out.commit()?;
+            }
+            None => {
+                write_df(&mut df, format, stdout().lock())?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,428 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+
+use clap::Parser;
+use hashbrown::HashMap;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+
+use crate::atomic::AtomicFile;
+use crate::prelude::*;
+use crate::sql::select_where;
+use crate::stats::{fingerprint, IndexStats};
+use crate::utils::escape_xml;
+
+const TEMPLATE: &str = include_str!("../../data/report.html");
+
+/// The metrics, whose distributions are shown in the report.
+const METRICS: [&str; 6] = [
+    "lang_score",
+    "lfreq",
+    "alpha",
+    "ttr",
+    "avg_word_len",
+    "words",
+];
+
+/// The number of bins of a histogram.
+const BINS: usize = 20;
+
+/// The max. number of bars of a bar chart.
+const MAX_BARS: usize = 15;
+
+const CHART_WIDTH: f64 = 360.0;
+const BAR_HEIGHT: f64 = 16.0;
+const HIST_HEIGHT: f64 = 120.0;
+
+/// Render a self-contained HTML report of the datashed.
+///
+/// The report contains the corpus composition (kind, language and
+/// subject area), the distributions of the quality metrics, the
+/// outcome of the quality gates, the duplicates and, if a previous
+/// index is given, the differences to the previous release. All
+/// charts are embedded as inline SVG, so the report can be shared as
+/// a single file.
+#[derive(Debug, Parser)]
+pub(crate) struct Report {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The index of a previous release, which is compared against the
+    /// current index.
+    #[arg(long, value_name = "filename")]
+    previous: Option<PathBuf>,
+
+    /// Write the report into `filename`. By default, the report is
+    /// written to the standard output (`stdout`).
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    /// An optional predicate to filter the document-set.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+}
+
+/// Renders a horizontal bar chart as inline SVG.
+fn bar_chart(data: &[(String, f64)]) -> String {
+    let max = data.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+    let label_width = 120.0;
+    let height = BAR_HEIGHT * data.len() as f64;
+
+    let mut svg = format!(
+        r#"<svg width="{}" height="{height}" role="img">"#,
+        label_width + CHART_WIDTH + 60.0,
+    );
+
+    for (idx, (label, value)) in data.iter().enumerate() {
+        let y = idx as f64 * BAR_HEIGHT;
+        let width = if max > 0.0 {
+            value / max * CHART_WIDTH
+        } else {
+            0.0
+        };
+
+        let _ = write!(
+            svg,
+            r#"<text x="{x}" y="{ty}" text-anchor="end">{label}</text><rect class="bar" x="{label_width}" y="{ry}" width="{width:.1}" height="{h}"/><text x="{vx:.1}" y="{ty}">{value}</text>"#,
+            x = label_width - 4.0,
+            ty = y + BAR_HEIGHT - 4.0,
+            label = escape_xml(label),
+            ry = y + 2.0,
+            h = BAR_HEIGHT - 4.0,
+            vx = label_width + width + 4.0,
+        );
+    }
+
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Renders a histogram of the given values as inline SVG.
+fn histogram(values: &[f64]) -> String {
+    let mut svg = format!(
+        r#"<svg width="{CHART_WIDTH}" height="{}" role="img">"#,
+        HIST_HEIGHT + 14.0
+    );
+
+    if values.is_empty() {
+        svg.push_str("</svg>");
+        return svg;
+    }
+
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let step = (max - min) / BINS as f64;
+
+    let mut bins = [0usize; BINS];
+    for value in values {
+        let idx = if step > 0.0 {
+            (((value - min) / step) as usize).min(BINS - 1)
+        } else {
+            0
+        };
+
+        bins[idx] += 1;
+    }
+
+    let peak = *bins.iter().max().unwrap_or(&1) as f64;
+    let width = CHART_WIDTH / BINS as f64;
+
+    for (idx, count) in bins.iter().enumerate() {
+        let height = *count as f64 / peak * HIST_HEIGHT;
+        let _ = write!(
+            svg,
+            r#"<rect class="bar" x="{:.1}" y="{:.1}" width="{:.1}" height="{height:.1}"><title>{count}</title></rect>"#,
+            idx as f64 * width,
+            HIST_HEIGHT - height,
+            width - 1.0,
+        );
+    }
+
+    let _ = write!(
+        svg,
+        r#"<text x="0" y="{y}">{min:.2}</text><text x="{CHART_WIDTH}" y="{y}" text-anchor="end">{max:.2}</text></svg>"#,
+        y = HIST_HEIGHT + 12.0
+    );
+
+    svg
+}
+
+/// Returns the (most frequent) values of a column and their counts.
+fn counts(
+    df: &DataFrame,
+    column: &str,
+    limit: usize,
+) -> DatashedResult<Vec<(String, f64)>> {
+    if df.column(column).is_err() {
+        return Ok(vec![]);
+    }
+
+    let counts = df
+        .clone()
+        .lazy()
+        .select([col(column).cast(DataType::String)])
+        .group_by([col(column)])
+        .agg([len().alias("count")])
+        .sort(
+            ["count"],
+            SortMultipleOptions::default().with_order_descending(true),
+        )
+        .limit(limit as IdxSize)
+        .collect()?;
+
+    let values = counts.column(column)?.str()?;
+    let count = counts.column("count")?.cast(&DataType::Float64)?;
+    let count = count.f64()?;
+
+    Ok(values
+        .iter()
+        .zip(count.iter())
+        .map(|(value, count)| {
+            (
+                value.unwrap_or("(none)").to_string(),
+                count.unwrap_or_default(),
+            )
+        })
+        .collect())
+}
+
+/// Returns the paths (and hashes) of a data frame.
+fn paths(df: &DataFrame) -> DatashedResult<HashMap<String, String>> {
+    let path = df.column("path")?.str()?;
+    let hash = df.column("hash")?.str()?;
+
+    Ok(path
+        .iter()
+        .zip(hash.iter())
+        .filter_map(|(path, hash)| {
+            Some((
+                path?.to_string(),
+                hash.unwrap_or_default().to_string(),
+            ))
+        })
+        .collect())
+}
+
+impl Report {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let config = datashed.config()?;
+        let mut df = datashed.index()?;
+
+        if let Some(predicate) = self.predicate {
+            let mut ctx = SQLContext::new();
+            ctx.register("df", df.lazy());
+            df = ctx.execute(&select_where(&predicate))?.collect()?;
+        }
+
+        let metadata = &config.metadata;
+        let title = format!("{} {}", metadata.name, metadata.version);
+        let mut body = String::new();
+
+        let _ = write!(body, "<h1>{}</h1>", escape_xml(&title));
+        if let Some(ref description) = metadata.description {
+            let _ = write!(body, "<p>{}</p>", escape_xml(description));
+        }
+        let _ = write!(
+            body,
+            r#"<p class="meta">{} document(s) &middot; fingerprint <code>{}</code></p>"#,
+            df.height(),
+            fingerprint(&df)?
+        );
+
+        // composition
+        body.push_str(r#"<h2>Composition</h2><div class="charts">"#);
+        for (column, caption) in [
+            ("kind", "Documents by kind"),
+            ("lang_code", "Documents by language"),
+            ("msc", "Documents by subject area"),
+        ] {
+            let data = counts(&df, column, MAX_BARS)?;
+            if !data.is_empty() {
+                let _ = write!(
+                    body,
+                    "<figure>{}<figcaption>{caption}</figcaption></figure>",
+                    bar_chart(&data)
+                );
+            }
+        }
+        body.push_str("</div>");
+
+        // metric distributions
+        body.push_str(r#"<h2>Metrics</h2><div class="charts">"#);
+        for name in METRICS {
+            let Ok(column) = df.column(name) else {
+                continue;
+            };
+
+            let values = column.cast(&DataType::Float64)?;
+            let values: Vec<f64> =
+                values.f64()?.into_iter().flatten().collect();
+
+            let _ = write!(
+                body,
+                "<figure>{}<figcaption><code>{name}</code> \
+                    ({} value(s))</figcaption></figure>",
+                histogram(&values),
+                values.len()
+            );
+        }
+        body.push_str("</div>");
+
+        // quality gates
+        let problems = IndexStats::from_df(&df)?.check();
+        body.push_str("<h2>Quality gates</h2>");
+        if problems.is_empty() {
+            body.push_str(r#"<p class="pass">All checks passed.</p>"#);
+        } else {
+            body.push_str("<ul>");
+            for problem in problems.iter() {
+                let _ = write!(
+                    body,
+                    r#"<li class="fail">{}</li>"#,
+                    escape_xml(problem)
+                );
+            }
+            body.push_str("</ul>");
+        }
+
+        // duplicates
+        let dups = df
+            .clone()
+            .lazy()
+            .group_by([col("hash")])
+            .agg([len().alias("count"), col("path").first()])
+            .filter(col("count").gt(lit(1)))
+            .sort(
+                ["count"],
+                SortMultipleOptions::default()
+                    .with_order_descending(true),
+            )
+            .collect()?;
+
+        body.push_str("<h2>Duplicates</h2>");
+        if dups.height() == 0 {
+            body.push_str("<p>No duplicates found.</p>");
+        } else {
+            let hash = dups.column("hash")?.str()?;
+            let path = dups.column("path")?.str()?;
+            let count =
+                dups.column("count")?.cast(&DataType::UInt64)?;
+            let count = count.u64()?;
+
+            let _ = write!(
+                body,
+                "<p>{} group(s) of identical documents.</p><table>\
+                    <tr><th>hash</th><th>example</th>\
+                    <th class=\"num\">count</th></tr>",
+                dups.height()
+            );
+            for idx in 0..dups.height().min(100) {
+                let _ = write!(
+                    body,
+                    r#"<tr><td><code>{}</code></td><td>{}</td><td class="num">{}</td></tr>"#,
+                    escape_xml(hash.get(idx).unwrap_or_default()),
+                    escape_xml(path.get(idx).unwrap_or_default()),
+                    count.get(idx).unwrap_or_default()
+                );
+            }
+            body.push_str("</table>");
+        }
+
+        // release diff
+        if let Some(ref previous) = self.previous {
+            let prev = IpcReader::new(File::open(previous)?)
+                .memory_mapped(None)
+                .finish()?;
+
+            let current = paths(&df)?;
+            let prev = paths(&prev)?;
+
+            let added = current
+                .keys()
+                .filter(|p| !prev.contains_key(*p))
+                .count();
+            let removed = prev
+                .keys()
+                .filter(|p| !current.contains_key(*p))
+                .count();
+            let changed = current
+                .iter()
+                .filter(|(path, hash)| {
+                    prev.get(*path).is_some_and(|prev| prev != *hash)
+                })
+                .count();
+
+            let _ = write!(
+                body,
+                "<h2>Changes</h2><p>Compared to <code>{}</code>.</p>\
+                    <table><tr><th>added</th><td class=\"num\">{added}</td></tr>\
+                    <tr><th>removed</th><td class=\"num\">{removed}</td></tr>\
+                    <tr><th>changed</th><td class=\"num\">{changed}</td></tr>\
+                    </table>",
+                escape_xml(&previous.display().to_string())
+            );
+        }
+
+        let html = TEMPLATE
+            .replace("{{title}}", &escape_xml(&title))
+            .replace("{{body}}", &body);
+
+        match self.output {
+            Some(ref path) => {
+                let mut out = AtomicFile::create(path)?;
+                out.write_all(html.as_bytes())?;
+                out.commit()?;
+            }
+            None => {
+                let mut out = stdout().lock();
+                out.write_all(html.as_bytes())?;
+                out.flush()?;
+            }
+        }
+
+        if self.verbose {
+            eprintln!(
+                "report: {} document(s), {} problem(s), {} duplicate group(s).",
+                df.height(),
+                problems.len(),
+                dups.height()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn charts() -> TestResult {
+        let svg = bar_chart(&[("a<b".into(), 2.0), ("c".into(), 1.0)]);
+        assert_eq!(svg.matches("<rect ").count(), 2);
+        assert!(svg.contains("a&lt;b"));
+
+        let svg = histogram(&[0.0, 0.5, 1.0, 1.0]);
+        assert_eq!(svg.matches("<rect ").count(), BINS);
+        assert!(svg.contains("<title>2</title>"));
+
+        let df = df!("kind" => ["book", "book", "article"])?;
+        let data = counts(&df, "kind", 10)?;
+        assert_eq!(data[0], ("book".into(), 2.0));
+        Ok(())
+    }
+}
@@ -1,13 +1,21 @@
 use std::fs::{create_dir, File};
+use std::io::{BufReader, Read};
 use std::path::PathBuf;
 
 use clap::Parser;
 use flate2::read::GzDecoder;
 use tar::Archive;
 
+use crate::crypto;
+use crate::plan::Plan;
 use crate::prelude::*;
 
 /// Restore a datashed archive (tar.gz).
+///
+/// Encrypted archives are decrypted transparently, either with the
+/// identities of a key file (`--key-file`) or with a passphrase, which
+/// is read from the `DATASHED_PASSPHRASE` environment variable or
+/// prompted for.
 #[derive(Debug, Default, Parser)]
 pub(crate) struct Restore {
     /// Run verbosely. Print additional progress information to the
@@ -25,13 +33,22 @@ pub(crate) struct Restore {
     #[arg(short = 'C', long = "directory", default_value = ".")]
     dest: PathBuf,
 
+    /// The age key file used to decrypt an encrypted archive.
+    #[arg(short, long, value_name = "filename")]
+    key_file: Option<PathBuf>,
+
+    /// Print a plan of the operations (counts, example paths and
+    /// affected bytes) without extracting anything.
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
     /// The datashed archive to be restored.
     archive: PathBuf,
 }
 
 impl Restore {
     pub(crate) fn execute(self) -> DatashedResult<()> {
-        if !self.dest.is_dir() {
+        if !self.dest.is_dir() && !self.dry_run {
             create_dir(&self.dest)?;
 
             if self.verbose {
@@ -42,8 +59,72 @@ impl Restore {
             }
         }
 
-        let reader = GzDecoder::new(File::open(self.archive)?);
+        let mut reader = BufReader::new(File::open(&self.archive)?);
+        let reader: Box<dyn Read> =
+            if crypto::is_encrypted(&mut reader)? {
+                let identities = match self.key_file {
+                    Some(ref path) => crypto::read_identities(path)?,
+                    None => vec![],
+                };
+
+                if self.verbose {
+                    eprintln!(
+                        "decrypting archive '{}'.",
+                        self.archive.display()
+                    );
+                }
+
+                crypto::decrypt(reader, &identities)?
+            } else {
+                Box::new(reader)
+            };
+
+        let reader = GzDecoder::new(reader);
         let mut archive = Archive::new(reader);
+
+        if self.dry_run {
+            let mut plan = Plan::default();
+            let mut required = [
+                (Datashed::DATA_DIR, false),
+                (Datashed::INDEX, false),
+                (Datashed::CONFIG, false),
+            ];
+
+            for entry in archive.entries()? {
+                let entry = entry?;
+                let path = entry.path()?.into_owned();
+                for (name, found) in required.iter_mut() {
+                    *found |= path.starts_with(name);
+                }
+
+                if entry.header().entry_type().is_dir() {
+                    continue;
+                }
+
+                let operation = if self.dest.join(&path).exists() {
+                    "overwrite"
+                } else {
+                    "create"
+                };
+
+                plan.add(
+                    operation,
+                    path.display().to_string(),
+                    entry.size(),
+                );
+            }
+
+            plan.print()?;
+
+            if let Some((name, _)) =
+                required.iter().find(|(_, found)| !found)
+            {
+                bail!("corrupt archive: missing {name}!");
+            }
+
+            return Ok(());
+        }
+
         archive.unpack(&self.dest)?;
 
         if !self.dest.join(Datashed::DATA_DIR).is_dir() {
@@ -0,0 +1,208 @@
+use std::io::stdout;
+use std::path::PathBuf;
+
+use clap::Parser;
+use hashbrown::HashMap;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use rand::Rng;
+
+use crate::atomic::AtomicFile;
+use crate::prelude::*;
+use crate::seed;
+use crate::sql::select_where;
+use crate::utils::{write_df, OutputFormat};
+
+/// Draw a random sample of the indexed documents.
+///
+/// By default, each document is drawn with the same probability. With
+/// `--weights` and/or `--inverse-frequency` the documents are drawn
+/// with a probability proportional to their weight (importance
+/// sampling without replacement), e.g. to oversample rare classes
+/// (`--inverse-frequency msc`) or low-quality documents (`--weights
+/// "1.0 - alpha"`). The realized weight of each document is recorded
+/// in the column `weight` of the sub-index. Documents with a missing
+/// or non-positive weight are never drawn. The sample is reproducible
+/// (see `--seed`).
+#[derive(Debug, Parser)]
+pub(crate) struct Sample {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The number of documents to draw.
+    #[arg(short = 'n', long, default_value = "100", value_name = "n")]
+    size: usize,
+
+    /// The weight of a document given as an SQL expression over the
+    /// index columns, e.g. `1.0 / (alpha + 0.01)`.
+    #[arg(long, value_name = "expr")]
+    weights: Option<String>,
+
+    /// Multiply the weight of a document by the inverse frequency of
+    /// its value in the given column (e.g. `msc` or `kind`), so that
+    /// all values are drawn equally often on average.
+    #[arg(long, value_name = "column")]
+    inverse_frequency: Option<String>,
+
+    /// Write the sub-index into `filename` instead of the standard
+    /// output.
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    /// The format of the sub-index written to the standard output
+    /// (default: CSV) or into `--output` (default: IPC).
+    #[arg(long, value_name = "format")]
+    format: Option<OutputFormat>,
+
+    #[command(flatten)]
+    columns: Columns,
+
+    /// An optional predicate to filter the document-set.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+}
+
+/// Draws up to `n` distinct items with probabilities proportional to
+/// their weights (algorithm A-Res by Efraimidis and Spirakis) and
+/// returns their positions in ascending order.
+///
+/// The items are ranked by the logarithm of the key `u^(1/w)`, which
+/// preserves the order, but doesn't underflow for small weights.
+fn weighted_sample<R: Rng>(
+    weights: &[Option<f64>],
+    n: usize,
+    rng: &mut R,
+) -> Vec<usize> {
+    let mut keys: Vec<(f64, usize)> = weights
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, weight)| {
+            let weight =
+                weight.filter(|w| w.is_finite() && *w > 0.0)?;
+            let u: f64 = rng.gen();
+            Some((u.ln() / weight, idx))
+        })
+        .collect();
+
+    keys.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+    let mut result: Vec<usize> =
+        keys.into_iter().take(n).map(|(_, idx)| idx).collect();
+    result.sort_unstable();
+    result
+}
+
+impl Sample {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let mut df = datashed.index()?;
+
+        if let Some(predicate) = self.predicate {
+            let mut ctx = SQLContext::new();
+            ctx.register("df", df.lazy());
+            df = ctx.execute(&select_where(&predicate))?.collect()?;
+        }
+
+        let mut weights: Vec<Option<f64>> = match self.weights {
+            Some(ref expr) => {
+                let mut ctx = SQLContext::new();
+                ctx.register("df", df.clone().lazy());
+                let result = ctx
+                    .execute(&format!(
+                        "SELECT CAST(({expr}) AS DOUBLE) AS weight \
+                            FROM df"
+                    ))?
+                    .collect()?;
+
+                result.column("weight")?.f64()?.into_iter().collect()
+            }
+            None => vec![Some(1.0); df.height()],
+        };
+
+        if let Some(ref name) = self.inverse_frequency {
+            let column = df.column(name)?.cast(&DataType::String)?;
+            let values = column.str()?;
+
+            let mut freqs: HashMap<Option<&str>, usize> =
+                HashMap::new();
+            for value in values.iter() {
+                *freqs.entry(value).or_default() += 1;
+            }
+
+            for (weight, value) in weights.iter_mut().zip(values.iter())
+            {
+                *weight = weight.map(|w| w / freqs[&value] as f64);
+            }
+        }
+
+        let mut rng = seed::rng();
+        let sample = weighted_sample(&weights, self.size, &mut rng);
+
+        if self.verbose {
+            eprintln!(
+                "sampled {} of {} documents.",
+                sample.len(),
+                df.height()
+            );
+        }
+
+        let idx = IdxCa::from_vec(
+            "idx".into(),
+            sample.iter().map(|idx| *idx as IdxSize).collect(),
+        );
+
+        let mut df = df.take(&idx)?;
+        df.with_column(Column::new(
+            "weight".into(),
+            sample.iter().map(|idx| weights[*idx]).collect::<Vec<_>>(),
+        ))?;
+
+        let mut df = self.columns.project(df, &datashed)?;
+        match self.output {
+            Some(path) => {
+                let mut out = AtomicFile::create(path)?;
+                let format = self.format.unwrap_or(OutputFormat::Ipc);
+                write_df(&mut df, format, &mut out)?;
+                out.commit()?;
+            }
+            None => {
+                let format = self.format.unwrap_or(OutputFormat::Csv);
+                write_df(&mut df, format, stdout().lock())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weighted_sampling() {
+        let mut rng = seed::rng();
+        let weights =
+            [Some(1.0), None, Some(0.0), Some(1.0), Some(2.0)];
+
+        let sample = weighted_sample(&weights, 10, &mut rng);
+        assert_eq!(sample, [0, 3, 4]);
+
+        let weights = [Some(1e-9), Some(1e9), Some(1e-9)];
+        assert_eq!(weighted_sample(&weights, 1, &mut rng), [1]);
+
+        // The documents of a large class (with small weights) are
+        // drawn at random, not in index order.
+        let weights = vec![Some(1e-5); 20_000];
+        let sample = weighted_sample(&weights, 100, &mut rng);
+        assert_eq!(sample.len(), 100);
+        assert!(sample.last().is_some_and(|idx| *idx >= 10_000));
+    }
+}
@@ -0,0 +1,314 @@
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::stdout;
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use polars::prelude::*;
+use polars::sql::SQLContext;
+
+use crate::artifacts;
+use crate::atomic::AtomicFile;
+use crate::prelude::*;
+use crate::ratings::{aggregate, read_ratings};
+use crate::sql::select_where;
+use crate::stats::IndexStats;
+use crate::tags::Tags;
+use crate::utils::{write_df, OutputFormat};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+enum Strategy {
+    /// Prefer documents whose model scores are close to the decision
+    /// boundary (0.5).
+    #[default]
+    Uncertainty,
+
+    /// Prefer documents on which the models (and existing human
+    /// ratings) disagree the most.
+    Disagreement,
+}
+
+/// Select the next documents to be rated (active learning).
+///
+/// The model scores (a table with a `path` or `idn` column and one
+/// numeric column per model, with scores in the range `[0, 1]`) are
+/// joined with the index and the documents are ranked according to the
+/// chosen strategy. The result is a worklist, which can be passed to
+/// `datashed rate`. The worklist contains the tags of the documents
+/// (see `datashed tag`), which can also be used in the `--where`
+/// predicate.
+#[derive(Debug, Parser)]
+pub(crate) struct Select {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The selection strategy.
+    #[arg(long, value_enum, default_value_t = Strategy::Uncertainty)]
+    strategy: Strategy,
+
+    /// The model scores (IPC or CSV).
+    #[arg(long, value_name = "filename")]
+    model_scores: PathBuf,
+
+    /// Existing ratings (see `datashed index --with-ratings`).
+    /// Documents with at least `--max-raters` ratings are skipped.
+    #[arg(long, value_name = "filename")]
+    ratings: Option<PathBuf>,
+
+    /// Skip documents which have been rated at least `n` times.
+    #[arg(long, default_value = "1", value_name = "n")]
+    max_raters: u32,
+
+    /// The number of documents to select.
+    #[arg(short = 'n', long, default_value = "100", value_name = "n")]
+    limit: usize,
+
+    /// Write the worklist into `filename` instead of `stdout`.
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    /// The output format. The `ndjson` format writes one JSON object
+    /// per row, e.g. for the ingestion into a search engine.
+    #[arg(long, value_name = "format", default_value = "csv")]
+    format: OutputFormat,
+
+    #[command(flatten)]
+    columns: Columns,
+
+    /// An optional predicate to filter the document-set.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+}
+
+fn read_scores(path: &PathBuf) -> DatashedResult<DataFrame> {
+    Ok(match path.extension().and_then(OsStr::to_str) {
+        Some("ipc" | "arrow") => IpcReader::new(File::open(path)?)
+            .memory_mapped(None)
+            .finish()?,
+        _ => CsvReadOptions::default()
+            .with_has_header(true)
+            .try_into_reader_with_file_path(Some(path.into()))?
+            .finish()?,
+    })
+}
+
+/// Returns the priority of a document given the model scores (and the
+/// mean human rating, if available).
+fn priority(
+    strategy: Strategy,
+    scores: &[f64],
+    rating: Option<f64>,
+) -> f64 {
+    match strategy {
+        Strategy::Uncertainty => {
+            scores
+                .iter()
+                .map(|s| 1.0 - (2.0 * s - 1.0).abs())
+                .sum::<f64>()
+                / scores.len() as f64
+        }
+        Strategy::Disagreement => {
+            let values: Vec<f64> =
+                scores.iter().copied().chain(rating).collect();
+            let n = values.len() as f64;
+            let mean = values.iter().sum::<f64>() / n;
+            (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n)
+                .sqrt()
+        }
+    }
+}
+
+impl Select {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+
+        if !self.quiet {
+            let path = datashed.base_dir().join(Datashed::INDEX);
+            if let Some(stats) = IndexStats::from_path(path)? {
+                for problem in stats.check() {
+                    eprintln!("warning: {problem}.");
+                }
+            }
+        }
+
+        let tags =
+            Tags::from_path(datashed.base_dir().join(Datashed::TAGS))?;
+        let index = tags.join(datashed.index()?)?;
+
+        let index: DataFrame = if let Some(predicate) = self.predicate {
+            let mut ctx = SQLContext::new();
+            ctx.register("df", index.lazy());
+            ctx.execute(&select_where(&predicate))?.collect()?
+        } else {
+            index
+        };
+
+        let scores = read_scores(&self.model_scores)?;
+        let key = if scores.column("path").is_ok() {
+            "path"
+        } else if scores.column("idn").is_ok() {
+            "idn"
+        } else {
+            bail!("model scores require a `path` or `idn` column");
+        };
+
+        let models: Vec<String> = scores
+            .schema()
+            .iter()
+            .filter(|(name, dtype)| {
+                !["path", "idn"].contains(&name.as_str())
+                    && dtype.is_numeric()
+            })
+            .map(|(name, _)| name.to_string())
+            .collect();
+
+        if models.is_empty() {
+            bail!("model scores don't contain a numeric column");
+        }
+
+        let scores = scores.select(
+            std::iter::once(key.to_string())
+                .chain(models.iter().cloned()),
+        )?;
+
+        let mut df = index.lazy().join(
+            scores.lazy(),
+            [col(key)],
+            [col(key)],
+            JoinArgs::new(JoinType::Inner),
+        );
+
+        let with_ratings = self.ratings.is_some();
+        if let Some(ref path) = self.ratings {
+            let scale = datashed.config()?.rating_scale(None)?;
+            let ratings = aggregate(&read_ratings(path)?, &scale)?;
+            df = df
+                .join(
+                    ratings.lazy().select([
+                        col("path"),
+                        col("hash"),
+                        col("rating_mean"),
+                        col("rating_count"),
+                    ]),
+                    [col("path"), col("hash")],
+                    [col("path"), col("hash")],
+                    JoinArgs::new(JoinType::Left),
+                )
+                .filter(
+                    col("rating_count")
+                        .fill_null(lit(0))
+                        .lt(lit(self.max_raters)),
+                );
+        }
+
+        let mut df = df
+            .with_streaming(datashed.config()?.max_memory()?.is_some())
+            .collect()?;
+        let columns = models
+            .iter()
+            .map(|name| {
+                Ok(df.column(name)?.cast(&DataType::Float64)?)
+            })
+            .collect::<DatashedResult<Vec<_>>>()?;
+
+        let rating = if with_ratings {
+            Some(df.column("rating_mean")?.f64()?.clone())
+        } else {
+            None
+        };
+
+        let mut priorities = Vec::with_capacity(df.height());
+        for idx in 0..df.height() {
+            let scores: Vec<f64> = columns
+                .iter()
+                .filter_map(|c| c.f64().ok().and_then(|c| c.get(idx)))
+                .collect();
+
+            if scores.is_empty() {
+                priorities.push(None);
+                continue;
+            }
+
+            let rating = rating.as_ref().and_then(|r| r.get(idx));
+            priorities.push(Some(priority(
+                self.strategy,
+                &scores,
+                rating,
+            )));
+        }
+
+        df.with_column(Series::new("priority".into(), priorities))?;
+
+        let df = df
+            .lazy()
+            .filter(col("priority").is_not_null())
+            .sort(
+                ["priority"],
+                SortMultipleOptions::default()
+                    .with_order_descending(true),
+            )
+            .limit(self.limit as IdxSize)
+            .collect()?;
+
+        let mut df = self
+            .columns
+            .clone()
+            .with_default(&["path", "idn", "hash", "priority", "tags"])
+            .project(df, &datashed)?;
+
+        if self.verbose {
+            eprintln!(
+                "selected {} documents using {} model(s)",
+                df.height(),
+                models.len()
+            );
+        }
+
+        match self.output {
+            Some(path) => {
+                let mut out = AtomicFile::create(&path)?;
+                write_df(&mut df, self.format, &mut out)?;
+                out.commit()?;
+
+                let base_dir = datashed.base_dir();
+                let mut inputs = vec![
+                    base_dir.join(Datashed::INDEX),
+                    base_dir.join(Datashed::TAGS),
+                    self.model_scores.clone(),
+                ];
+                inputs.extend(self.ratings.clone());
+                artifacts::register(
+                    &datashed, "select", &path, &inputs,
+                )?;
+            }
+            None => {
+                write_df(&mut df, self.format, stdout().lock())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priority_strategies() {
+        use Strategy::*;
+
+        assert_eq!(priority(Uncertainty, &[0.5], None), 1.0);
+        assert_eq!(priority(Uncertainty, &[1.0, 0.0], None), 0.0);
+        assert_eq!(priority(Disagreement, &[0.2, 0.2], None), 0.0);
+        assert_eq!(priority(Disagreement, &[1.0], Some(0.0)), 0.5);
+    }
+}
@@ -1,20 +1,73 @@
-use std::fs::{File, OpenOptions};
+use std::collections::{BTreeMap, HashSet};
+use std::env::current_exe;
+use std::fs::{create_dir_all, read_to_string, File, OpenOptions};
+use std::io::Write;
 use std::net::IpAddr;
-use std::path::PathBuf;
-use std::sync::Mutex;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::path::{Component, Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use actix_files::{Files, NamedFile};
+use actix_files::NamedFile;
+use actix_web::http::header;
 use actix_web::middleware::Logger;
+use actix_web::web::Bytes;
 use actix_web::{
-    get, guard, head, post, web, App, HttpResponse, HttpServer,
+    get, head, post, put, route, web, App, HttpRequest, HttpResponse,
+    HttpServer, Responder, Scope,
 };
-use csv::{Writer, WriterBuilder};
-use serde::Deserialize;
+use bstr::ByteSlice;
+use csv::WriterBuilder;
+use futures::stream;
+use polars::prelude::{col, IntoLazy, IpcReader, SerReader};
+use rayon::prelude::*;
+use regex::bytes::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{channel, Sender};
 
-use crate::error::DatashedResult;
-use crate::prelude::Datashed;
+use crate::access::{self, AccessMap, SharedAccessMap, SharedWriter};
+use crate::atomic::AtomicFile;
+use crate::audit::{self, AuditEntry};
+use crate::campaign;
+use crate::config::{Config, User};
+use crate::document::DocumentKind;
+use crate::error::{bail, DatashedError, DatashedResult};
+use crate::lock::LockGuard;
+use crate::prelude::{Datashed, Document};
+use crate::ratings::is_valid_hash;
+use crate::signature::signature_path;
+use crate::sql::where_expr;
 
+/// The maximum size of an uploaded document (in bytes).
+const UPLOAD_LIMIT: usize = 64 * 1024 * 1024;
+
+/// The delay between an upload and the re-indexing of the datashed.
+/// Uploads within this period are indexed together.
+const REINDEX_DELAY: Duration = Duration::from_secs(60);
+
+/// Serve the datashed via HTTP.
+///
+/// By default the datashed of the current directory is served. If at
+/// least one shed is given (`--shed` or `--workspace`), each shed is
+/// served under `/sheds/{name}/...` with its own index and ratings
+/// store instead.
+///
+/// Access to documents is controlled by the `[[access]]` rules of the
+/// datashed config. Users authenticate via HTTP basic authentication
+/// with their username and secret. All document requests are logged to
+/// `tmp/audit.csv`.
+///
+/// Documents can be searched via `/api/grep`, which streams the
+/// matching documents back as JSON Lines (see `dataset grep`). The
+/// Bloom filter over the PPNs of the index is published as
+/// `/index.bloom`.
+///
+/// Users with the role `admin` or `uploader` can submit new documents
+/// via `PUT /documents/{path}`, where `path` is relative to the data
+/// directory. Uploaded documents are picked up by a re-index of the
+/// datashed, which runs shortly after the last upload.
 #[derive(Debug, Default, clap::Parser)]
 pub(crate) struct Serve {
     /// Run verbosely. Print additional progress information to the
@@ -31,13 +84,252 @@ pub(crate) struct Serve {
     #[arg(short, long)]
     port: Option<u16>,
 
+    /// Serve documents with a restricted access/license code (see the
+    /// `[license]` config section), e.g. embargoed documents.
+    #[arg(long)]
+    force: bool,
+
     #[arg(long)]
     address: Option<IpAddr>,
+
+    /// Serve the datashed located at `path` under the given name. This
+    /// option can be specified multiple times.
+    #[arg(
+        long = "shed",
+        value_name = "name=path",
+        value_parser = parse_shed
+    )]
+    sheds: Vec<(String, PathBuf)>,
+
+    /// A workspace config (TOML), which lists the sheds to serve.
+    /// Relative paths are resolved against the location of the
+    /// workspace config.
+    #[arg(long, value_name = "filename")]
+    workspace: Option<PathBuf>,
+
+    /// Additionally serve the index and the page index via Arrow
+    /// Flight on the given port. The ticket of a `DoGet` request is a
+    /// JSON object with the keys `table` ("index" or "pages"),
+    /// `predicate` (an SQL expression) and `columns`, which are
+    /// evaluated by the server. If redaction policies are configured,
+    /// the redacted index is served instead of the index and the page
+    /// index isn't served. This option can't be used together with
+    /// `--shed` or `--workspace`.
+    #[cfg(feature = "flight")]
+    #[arg(long, value_name = "port")]
+    flight_port: Option<u16>,
+
+    /// Additionally serve the gRPC service of the datashed (index
+    /// query, document fetch, rating submission and health) on the
+    /// given port. The service is defined in `proto/datashed.proto`.
+    /// This option can't be used together with `--shed` or
+    /// `--workspace`.
+    #[cfg(feature = "grpc")]
+    #[arg(long, value_name = "port")]
+    grpc_port: Option<u16>,
+}
+
+/// A workspace config.
+///
+/// ```toml
+/// port = 9001
+///
+/// [sheds]
+/// foo = "/path/to/foo"
+/// bar = "../bar"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+struct Workspace {
+    address: Option<IpAddr>,
+    port: Option<u16>,
+    #[serde(default)]
+    sheds: BTreeMap<String, PathBuf>,
+}
+
+impl Workspace {
+    fn from_path(path: &Path) -> DatashedResult<Self> {
+        let mut workspace: Self =
+            toml::from_str(&read_to_string(path)?)?;
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+
+        for shed in workspace.sheds.values_mut() {
+            if shed.is_relative() {
+                *shed = base_dir.join(&shed);
+            }
+        }
+
+        Ok(workspace)
+    }
+}
+
+fn parse_shed(s: &str) -> Result<(String, PathBuf), String> {
+    match s.split_once('=') {
+        Some((name, path))
+            if !name.is_empty()
+                && !path.is_empty()
+                && name.chars().all(|c| {
+                    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+                }) =>
+        {
+            Ok((name.into(), path.into()))
+        }
+        _ => Err(format!("invalid shed '{s}' (expected name=path)")),
+    }
 }
 
 struct AppState {
     datashed: Datashed,
-    wtr: Mutex<Writer<File>>,
+    wtr: SharedWriter,
+    audit: SharedWriter,
+    acl: SharedAccessMap,
+    force: bool,
+    /// The uploaded documents, which aren't indexed yet.
+    pending: Mutex<HashSet<String>>,
+    reindex: AtomicBool,
+    /// The lock of the ratings store, which is held as long as the
+    /// server is running (see `datashed ratings import`).
+    _ratings_lock: LockGuard,
+}
+
+impl AppState {
+    fn new(datashed: Datashed, force: bool) -> DatashedResult<Self> {
+        let temp_dir = datashed.temp_dir();
+        let config = datashed.config()?;
+
+        let ratings_lock = match datashed.lock_ratings(false) {
+            Err(DatashedError::Other(_)) => bail!(
+                "the ratings store is in use by another process \
+                (lock file = {})",
+                temp_dir.join(Datashed::RATINGS_LOCK).display()
+            ),
+            result => result?,
+        };
+
+        let wtr = WriterBuilder::new().from_writer(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(temp_dir.join(Datashed::RATINGS))?,
+        );
+
+        let audit = WriterBuilder::new().from_writer(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(temp_dir.join(Datashed::ACCESS_LOG))?,
+        );
+
+        let acl = AccessMap::for_datashed(&datashed, &config, force)?;
+
+        Ok(Self {
+            datashed,
+            wtr: Arc::new(Mutex::new(wtr)),
+            audit: Arc::new(Mutex::new(audit)),
+            acl: Arc::new(RwLock::new(Arc::new(acl))),
+            force,
+            pending: Mutex::new(HashSet::new()),
+            reindex: AtomicBool::new(false),
+            _ratings_lock: ratings_lock,
+        })
+    }
+}
+
+/// Re-indexes the datashed (in a separate `datashed index` process),
+/// whenever documents have been uploaded since the last check. The
+/// metadata of the last run with a PICA+ dump (e.g. the license codes)
+/// is taken from the metadata cache.
+///
+/// Uploaded documents are denied until the access map has been rebuilt
+/// from the new index. Documents uploaded while the index is rebuilt
+/// stay denied until the next re-index.
+fn spawn_reindexer(state: web::Data<AppState>) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(REINDEX_DELAY).await;
+            if !state.reindex.swap(false, Ordering::SeqCst) {
+                continue;
+            }
+
+            let uploaded =
+                std::mem::take(&mut *state.pending.lock().unwrap());
+            let inner = state.clone();
+            let result = match tokio::task::spawn_blocking(move || {
+                reindex(&inner)
+            })
+            .await
+            {
+                Ok(result) => result,
+                Err(e) => Err(DatashedError::other(e)),
+            };
+
+            if let Err(e) = result {
+                eprintln!(
+                    "error: re-indexing the datashed failed: {e}"
+                );
+                state.pending.lock().unwrap().extend(uploaded);
+            }
+        }
+    });
+}
+
+/// Runs `datashed index` and replaces the access map of the server.
+/// Documents, which were uploaded in the meantime, stay denied.
+fn reindex(state: &AppState) -> DatashedResult<()> {
+    let status = process::Command::new(current_exe()?)
+        .args(["index", "--wait", "--quiet", "--cached-metadata"])
+        .current_dir(state.datashed.base_dir())
+        .status()?;
+
+    if !status.success() {
+        bail!("index process failed");
+    }
+
+    let config = state.datashed.config()?;
+    let acl =
+        AccessMap::for_datashed(&state.datashed, &config, state.force)?;
+
+    let pending = state.pending.lock().unwrap();
+    for path in pending.iter() {
+        acl.deny(path.as_str());
+    }
+
+    *state.acl.write().unwrap() = Arc::new(acl);
+    Ok(())
+}
+
+/// Returns the name of the authenticated user, if the request contains
+/// valid basic authentication credentials.
+fn authenticate(req: &HttpRequest, config: &Config) -> Option<String> {
+    let value = req.headers().get(header::AUTHORIZATION)?;
+    access::authenticate(value.to_str().ok()?, config)
+}
+
+/// Returns the name of the authenticated user, if the user has one of
+/// the given roles. Otherwise, the error response (401 or 403) is
+/// returned.
+fn require_role(
+    req: &HttpRequest,
+    config: &Config,
+    roles: &[&str],
+) -> Result<String, HttpResponse> {
+    let Some(username) = authenticate(req, config) else {
+        return Err(HttpResponse::Unauthorized()
+            .insert_header((
+                header::WWW_AUTHENTICATE,
+                "Basic realm=\"datashed\"",
+            ))
+            .finish());
+    };
+
+    let allowed = config.users.get(&username).is_some_and(|user| {
+        user.roles.iter().any(|role| roles.contains(&role.as_str()))
+    });
+
+    if !allowed {
+        return Err(HttpResponse::Forbidden().finish());
+    }
+
+    Ok(username)
 }
 
 #[derive(Debug, Deserialize)]
@@ -48,6 +340,8 @@ struct RatingReq {
     comment: String,
     username: String,
     secret: String,
+    #[serde(default)]
+    campaign: Option<String>,
 }
 
 #[post("/ratings")]
@@ -81,11 +375,20 @@ async fn ratings(
             .body(format!("path {} does not exist!", path.display()));
     }
 
-    let rating = match req.rating.as_str() {
-        "C" | "C-" | "P" | "P-" | "P+" | "I" => req.rating.clone(),
-        rating => {
+    if !is_valid_hash(&hash) {
+        return HttpResponse::BadRequest().body("invalid hash!");
+    }
+
+    let Ok(scale) = config.rating_scale(req.campaign.as_deref()) else {
+        return HttpResponse::BadRequest()
+            .body("invalid rating scale!");
+    };
+
+    let rating = match scale.level(&req.rating) {
+        Ok(level) => level.label.clone(),
+        Err(_) => {
             return HttpResponse::BadRequest()
-                .body(format!("invalid rating '{rating}'!"))
+                .body(format!("invalid rating '{}'!", req.rating))
         }
     };
 
@@ -117,12 +420,504 @@ async fn ratings(
     HttpResponse::Ok().finish()
 }
 
-#[get("/index.ipc")]
+/// Returns the path of the published index. If redaction policies are
+/// configured, the redacted index (see `datashed redact-index`) is
+/// published instead of the index.
+fn published_index(datashed: &Datashed) -> PathBuf {
+    match datashed.config() {
+        Ok(config) if config.redact.is_empty() => {
+            datashed.base_dir().join(Datashed::INDEX)
+        }
+        _ => datashed.base_dir().join(Datashed::REDACTED_INDEX),
+    }
+}
+
+#[route("/index.ipc", method = "GET", method = "HEAD")]
 async fn index(
     state: web::Data<AppState>,
 ) -> actix_web::Result<NamedFile> {
-    let path = &state.datashed.base_dir().join("index.ipc");
-    Ok(NamedFile::open(path)?)
+    Ok(NamedFile::open(published_index(&state.datashed))?)
+}
+
+/// Returns the Bloom filter over the PPNs of the index (see `datashed
+/// contains`), which is used by `dataset` to discard labels of
+/// documents not present in the datashed. The filter isn't published,
+/// if it's missing or outdated or if redaction policies are configured.
+#[get("/index.bloom")]
+async fn index_bloom(state: web::Data<AppState>) -> HttpResponse {
+    if published_index(&state.datashed)
+        != state.datashed.base_dir().join(Datashed::INDEX)
+    {
+        return HttpResponse::NotFound().finish();
+    }
+
+    let Ok(Some(filter)) = state.datashed.bloom() else {
+        return HttpResponse::NotFound().finish();
+    };
+
+    let mut body = vec![];
+    if filter.write(&mut body).is_err() {
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .body(body)
+}
+
+#[derive(Debug, Deserialize)]
+struct ScaleReq {
+    campaign: Option<String>,
+}
+
+/// Returns the rating scale of a campaign (query parameter `campaign`)
+/// or the default rating scale as JSON.
+#[get("/ratings/scale")]
+async fn rating_scale(
+    state: web::Data<AppState>,
+    query: web::Query<ScaleReq>,
+) -> HttpResponse {
+    let Ok(config) = state.datashed.config() else {
+        return HttpResponse::InternalServerError().finish();
+    };
+
+    match config.rating_scale(query.campaign.as_deref()) {
+        Ok(scale) => HttpResponse::Ok().json(scale),
+        Err(e) => HttpResponse::BadRequest().body(e.to_string()),
+    }
+}
+
+#[get("/index.ipc.sig")]
+async fn index_signature(
+    state: web::Data<AppState>,
+) -> actix_web::Result<NamedFile> {
+    let path = published_index(&state.datashed);
+    Ok(NamedFile::open(signature_path(path))?)
+}
+
+#[get("/data/{tail:.*}")]
+async fn document(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    tail: web::Path<String>,
+) -> HttpResponse {
+    let Ok(config) = state.datashed.config() else {
+        return HttpResponse::InternalServerError().finish();
+    };
+
+    let tail = tail.into_inner();
+    if tail.split('/').any(|c| c == ".." || c.is_empty()) {
+        return HttpResponse::BadRequest().finish();
+    }
+
+    let path = format!("{}/{tail}", Datashed::DATA_DIR);
+    let username = authenticate(&req, &config);
+    let user =
+        username.as_ref().and_then(|name| config.users.get(name));
+
+    let allowed = access::snapshot(&state.acl).is_allowed(&path, user);
+    let response = if !allowed {
+        if user.is_none() {
+            HttpResponse::Unauthorized()
+                .insert_header((
+                    header::WWW_AUTHENTICATE,
+                    "Basic realm=\"datashed\"",
+                ))
+                .finish()
+        } else {
+            HttpResponse::Forbidden().finish()
+        }
+    } else {
+        match NamedFile::open(state.datashed.base_dir().join(&path)) {
+            Ok(file) => file.respond_to(&req).map_into_boxed_body(),
+            Err(_) => HttpResponse::NotFound().finish(),
+        }
+    };
+
+    access::audit(
+        &state.audit,
+        username.as_deref(),
+        &path,
+        response.status().as_str(),
+    );
+
+    response
+}
+
+#[derive(Debug, Deserialize)]
+struct GrepReq {
+    pattern: String,
+    #[serde(default)]
+    ignore_case: bool,
+    #[serde(default)]
+    invert_match: bool,
+    #[serde(rename = "where")]
+    predicate: Option<String>,
+    max_bytes: Option<usize>,
+    #[serde(default)]
+    snippets: bool,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct GrepMatch<'a> {
+    path: &'a str,
+    hash: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snippet: Option<String>,
+}
+
+/// The number of bytes before and after a match, which are included
+/// in a snippet.
+const SNIPPET_CONTEXT: usize = 80;
+
+/// Searches the documents for the pattern and sends each match as
+/// JSON object (one per line). The search stops early, if the limit
+/// is reached or the receiver is closed (client disconnected).
+fn search(
+    state: &AppState,
+    query: &GrepReq,
+    re: &Regex,
+    user: Option<&User>,
+    tx: &Sender<Bytes>,
+) -> DatashedResult<()> {
+    let datashed = &state.datashed;
+    let base_dir = datashed.base_dir();
+
+    let mut index = datashed.index()?;
+    if let Some(ref predicate) = query.predicate {
+        let predicate = where_expr(predicate)?;
+        index = if datashed.config()?.redact.is_empty() {
+            index.lazy().filter(predicate).collect()?
+        } else {
+            // The predicate is evaluated against the redacted index,
+            // so that redacted values can't be probed. The rows of
+            // both indices are in the same order.
+            let redacted = IpcReader::new(File::open(
+                base_dir.join(Datashed::REDACTED_INDEX),
+            )?)
+            .memory_mapped(None)
+            .finish()?;
+
+            if redacted.height() != index.height() {
+                bail!("redacted index is outdated");
+            }
+
+            let rows = redacted
+                .with_row_index("row_nr".into(), None)?
+                .lazy()
+                .filter(predicate)
+                .select([col("row_nr")])
+                .collect()?;
+
+            index.take(rows.column("row_nr")?.idx()?)?
+        };
+    }
+
+    let path = index.column("path")?.str()?;
+    let hash = index.column("hash")?.str()?;
+    let count = AtomicUsize::new(0);
+    let acl = access::snapshot(&state.acl);
+
+    let _ = (0..index.height()).into_par_iter().try_for_each(
+        |idx| -> Result<(), ()> {
+            let (Some(path), Some(hash)) =
+                (path.get(idx), hash.get(idx))
+            else {
+                return Ok(());
+            };
+
+            if !acl.is_allowed(path, user) {
+                return Ok(());
+            }
+
+            let Ok(doc) = Document::from_path(base_dir.join(path))
+            else {
+                return Ok(());
+            };
+
+            let mut bytes = doc.as_ref();
+            if let Some(n) = query.max_bytes {
+                if n > 0 && n < bytes.len() {
+                    bytes = &bytes[..n];
+                }
+            }
+
+            let found = re.find(bytes);
+            if found.is_some() == query.invert_match {
+                return Ok(());
+            }
+
+            if query.limit.is_some_and(|limit| {
+                count.fetch_add(1, Ordering::Relaxed) >= limit
+            }) {
+                return Err(());
+            }
+
+            let snippet = found.filter(|_| query.snippets).map(|m| {
+                let lo = m.start().saturating_sub(SNIPPET_CONTEXT);
+                let hi = (m.end() + SNIPPET_CONTEXT).min(bytes.len());
+                bytes[lo..hi]
+                    .to_str_lossy()
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            });
+
+            let mut line = serde_json::to_vec(&GrepMatch {
+                path,
+                hash,
+                snippet,
+            })
+            .map_err(|_| ())?;
+            line.push(b'\n');
+
+            tx.blocking_send(Bytes::from(line)).map_err(|_| ())
+        },
+    );
+
+    Ok(())
+}
+
+/// Searches the documents of the datashed for a pattern (query
+/// parameters `pattern`, `ignore_case`, `invert_match`, `where`,
+/// `max_bytes`, `snippets` and `limit`) and streams the matching
+/// documents back as JSON Lines. Only documents the (authenticated)
+/// user is allowed to access are searched.
+#[get("/api/grep")]
+async fn grep(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<GrepReq>,
+) -> HttpResponse {
+    let Ok(config) = state.datashed.config() else {
+        return HttpResponse::InternalServerError().finish();
+    };
+
+    let query = query.into_inner();
+    let Ok(re) = RegexBuilder::new(&query.pattern)
+        .case_insensitive(query.ignore_case)
+        .build()
+    else {
+        return HttpResponse::BadRequest().body("invalid pattern");
+    };
+
+    let username = authenticate(&req, &config);
+    let state = state.into_inner();
+    let (tx, rx) = channel::<Bytes>(64);
+
+    tokio::task::spawn_blocking(move || {
+        let user =
+            username.as_ref().and_then(|name| config.users.get(name));
+        if let Err(e) = search(&state, &query, &re, user, &tx) {
+            let line = serde_json::json!({ "error": e.to_string() });
+            let _ = tx.blocking_send(Bytes::from(format!("{line}\n")));
+        }
+    });
+
+    let body = stream::unfold(rx, |mut rx| async move {
+        rx.recv()
+            .await
+            .map(|line| (Ok::<_, std::io::Error>(line), rx))
+    });
+
+    HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(body)
+}
+
+/// Returns the status of the recurring jobs (see `datashed daemon`).
+/// Only users with the role `admin` are allowed to access this route.
+#[get("/admin/jobs")]
+async fn admin_jobs(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let Ok(config) = state.datashed.config() else {
+        return HttpResponse::InternalServerError().finish();
+    };
+
+    if let Err(response) = require_role(&req, &config, &["admin"]) {
+        return response;
+    }
+
+    let path = state.datashed.jobs_dir().join(Datashed::JOBS_STATUS);
+    match read_to_string(path) {
+        Ok(status) => HttpResponse::Ok()
+            .content_type("application/json")
+            .body(status),
+        Err(_) => HttpResponse::Ok().json(serde_json::json!({})),
+    }
+}
+
+/// Returns the progress of a rating campaign (see `datashed ratings
+/// progress`). Only users with the role `admin` or `coordinator` are
+/// allowed to access this route.
+#[get("/campaigns/{name}/progress")]
+async fn campaign_progress(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    name: web::Path<String>,
+) -> HttpResponse {
+    let Ok(config) = state.datashed.config() else {
+        return HttpResponse::InternalServerError().finish();
+    };
+
+    if let Err(response) =
+        require_role(&req, &config, &["admin", "coordinator"])
+    {
+        return response;
+    }
+
+    let name = name.into_inner();
+    if !config.campaigns.contains_key(&name) {
+        return HttpResponse::NotFound().finish();
+    }
+
+    match campaign::campaign_progress(&state.datashed, &name) {
+        Ok(progress) => HttpResponse::Ok().json(progress),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+/// Validates the path of an uploaded document (relative to the data
+/// directory) and returns the path relative to the root directory.
+///
+/// The path must not contain empty, `.` or `..` components, must be
+/// matched by the `[discovery]` config, must contain a document kind
+/// (e.g. `book/`) and the file stem (IDN) must be alphanumeric.
+fn upload_path(tail: &str, config: &Config) -> Result<PathBuf, String> {
+    if tail
+        .split('/')
+        .any(|c| c.is_empty() || c == "." || c == "..")
+    {
+        return Err(format!("invalid path '{tail}'"));
+    }
+
+    let path = Path::new(Datashed::DATA_DIR).join(tail);
+    let matcher =
+        config.discovery.matcher().map_err(|e| e.to_string())?;
+    if !matcher.is_match(&path) {
+        return Err(format!("path '{tail}' isn't a document path"));
+    }
+
+    let has_kind = path.components().any(|c| match c {
+        Component::Normal(s) => s
+            .to_str()
+            .is_some_and(|s| DocumentKind::from_str(s).is_ok()),
+        _ => false,
+    });
+
+    if !has_kind {
+        return Err(format!("path '{tail}' lacks a document kind"));
+    }
+
+    let is_idn = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| {
+            !stem.is_empty()
+                && stem.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+
+    if !is_idn {
+        return Err(format!("path '{tail}' lacks a valid IDN"));
+    }
+
+    Ok(path)
+}
+
+/// Writes the document atomically to `dest`.
+fn store_document(dest: &Path, body: &[u8]) -> DatashedResult<()> {
+    if let Some(parent) = dest.parent() {
+        create_dir_all(parent)?;
+    }
+
+    let mut out = AtomicFile::create(dest)?;
+    out.write_all(body)?;
+    out.commit()?;
+    Ok(())
+}
+
+/// Stores an uploaded (UTF-8) text document under the given path of
+/// the data directory and schedules a re-index of the datashed. An
+/// existing document is replaced. Only users with the role `admin` or
+/// `uploader` are allowed to access this route.
+#[put("/documents/{tail:.*}")]
+async fn upload_document(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    tail: web::Path<String>,
+    body: Bytes,
+) -> HttpResponse {
+    let Ok(config) = state.datashed.config() else {
+        return HttpResponse::InternalServerError().finish();
+    };
+
+    let username =
+        match require_role(&req, &config, &["admin", "uploader"]) {
+            Ok(username) => username,
+            Err(response) => return response,
+        };
+
+    let path = match upload_path(&tail.into_inner(), &config) {
+        Ok(path) => path,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+
+    if body.is_empty() || body.to_str().is_err() {
+        return HttpResponse::BadRequest()
+            .body("document must be a non-empty UTF-8 text");
+    }
+
+    let dest = state.datashed.base_dir().join(&path);
+    let exists = dest.is_file();
+
+    // The document is denied before it's written, so that the new
+    // version isn't served with the access level of the old one. The
+    // pending documents are locked while the document is denied, so
+    // that a concurrent re-index doesn't miss it.
+    let key = path.to_string_lossy().to_string();
+    let mut pending = state.pending.lock().unwrap();
+    let was_pending = !pending.insert(key.clone());
+    access::snapshot(&state.acl).deny(key.as_str());
+    drop(pending);
+
+    let response = match store_document(&dest, &body) {
+        Ok(()) if exists => HttpResponse::NoContent().finish(),
+        Ok(()) => HttpResponse::Created().finish(),
+        Err(_) => HttpResponse::InternalServerError()
+            .body("could not store document!"),
+    };
+
+    if !response.status().is_success() && !was_pending {
+        // The document is unchanged, so the denial is revoked (unless
+        // the document is still pending from a previous upload).
+        let mut pending = state.pending.lock().unwrap();
+        pending.remove(&key);
+        access::snapshot(&state.acl).allow(key.as_str());
+        drop(pending);
+    }
+
+    if response.status().is_success() {
+        state.reindex.store(true, Ordering::SeqCst);
+
+        let mut entry = AuditEntry::new("upload", 1);
+        entry.user = username.clone();
+        entry.args = vec![key];
+        if let Err(e) = audit::append(&state.datashed, &entry) {
+            eprintln!("error: unable to write audit log: {e}");
+        }
+    }
+
+    access::audit(
+        &state.audit,
+        Some(username.as_str()),
+        path.to_str().unwrap_or_default(),
+        response.status().as_str(),
+    );
+
+    response
 }
 
 #[head("/health-check")]
@@ -130,12 +925,104 @@ async fn health_check() -> HttpResponse {
     HttpResponse::Ok().finish()
 }
 
+#[get("/sheds")]
+async fn list_sheds(names: web::Data<Vec<String>>) -> HttpResponse {
+    HttpResponse::Ok().json(names.as_ref())
+}
+
+/// Returns the routes of a shed, which is served under
+/// `/sheds/{name}/...`.
+fn shed_scope(name: &str, state: web::Data<AppState>) -> Scope {
+    web::scope(&format!("/sheds/{name}"))
+        .app_data(state)
+        .service(index)
+        .service(index_signature)
+        .service(index_bloom)
+        .service(document)
+        .service(ratings)
+        .service(rating_scale)
+        .service(grep)
+        .service(admin_jobs)
+        .service(campaign_progress)
+        .service(upload_document)
+}
+
 impl Serve {
     pub(crate) async fn execute(self) -> DatashedResult<()> {
+        let workspace = match self.workspace {
+            Some(ref path) => Workspace::from_path(path)?,
+            None => Workspace::default(),
+        };
+
+        let mut sheds = workspace.sheds.clone();
+        for (name, path) in self.sheds.iter() {
+            if sheds.insert(name.clone(), path.clone()).is_some() {
+                bail!("duplicate shed '{name}'");
+            }
+        }
+
+        if sheds.is_empty() {
+            return self.serve_single().await;
+        }
+
+        #[cfg(feature = "flight")]
+        if self.flight_port.is_some() {
+            bail!("--flight-port can't be used with multiple sheds");
+        }
+
+        #[cfg(feature = "grpc")]
+        if self.grpc_port.is_some() {
+            bail!("--grpc-port can't be used with multiple sheds");
+        }
+
+        let port = self.port.or(workspace.port).unwrap_or(9001);
+        let addr = self
+            .address
+            .or(workspace.address)
+            .or("0.0.0.0".parse().ok())
+            .unwrap();
+
+        let mut states = vec![];
+        for (name, path) in sheds.into_iter() {
+            let datashed = Datashed::from_path(path)?;
+            let state =
+                web::Data::new(AppState::new(datashed, self.force)?);
+            spawn_reindexer(state.clone());
+            states.push((name, state));
+        }
+
+        let names = web::Data::new(
+            states
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<_>>(),
+        );
+
+        let _ = HttpServer::new(move || {
+            let mut app = App::new()
+                .wrap(Logger::default())
+                .app_data(names.clone())
+                .app_data(web::PayloadConfig::new(UPLOAD_LIMIT))
+                .service(health_check)
+                .service(list_sheds);
+
+            for (name, state) in states.iter() {
+                app = app.service(shed_scope(name, state.clone()));
+            }
+
+            app
+        })
+        .workers(2)
+        .bind((addr, port))?
+        .run()
+        .await;
+
+        Ok(())
+    }
+
+    async fn serve_single(self) -> DatashedResult<()> {
         let datashed = Datashed::discover()?;
         let config = datashed.config()?;
-        let data_dir = datashed.data_dir();
-        let temp_dir = datashed.temp_dir();
 
         let server_config = config.server.unwrap_or_default();
         let port = self.port.or(server_config.port).unwrap_or(9001);
@@ -145,29 +1032,55 @@ impl Serve {
             .or("0.0.0.0".parse().ok())
             .unwrap();
 
-        let app_data = web::Data::new(AppState {
-            datashed,
-            wtr: Mutex::new(
-                WriterBuilder::new().from_writer(
-                    OpenOptions::new()
-                        .create(true)
-                        .append(true)
-                        .open(temp_dir.join(Datashed::RATINGS))?,
-                ),
-            ),
-        });
+        #[cfg(feature = "flight")]
+        if let Some(flight_port) = self.flight_port {
+            let server = crate::flight::FlightServer::new(&datashed);
+            tokio::spawn(async move {
+                if let Err(e) =
+                    server.run((addr, flight_port).into()).await
+                {
+                    eprintln!("error: flight service failed: {e}");
+                }
+            });
+        }
+
+        let app_data =
+            web::Data::new(AppState::new(datashed, self.force)?);
+        spawn_reindexer(app_data.clone());
+
+        #[cfg(feature = "grpc")]
+        if let Some(grpc_port) = self.grpc_port {
+            let server = crate::grpc::GrpcServer::new(
+                Datashed::from_path(app_data.datashed.base_dir())?,
+                app_data.acl.clone(),
+                app_data.wtr.clone(),
+                app_data.audit.clone(),
+            );
+            tokio::spawn(async move {
+                if let Err(e) =
+                    server.run((addr, grpc_port).into()).await
+                {
+                    eprintln!("error: grpc service failed: {e}");
+                }
+            });
+        }
 
         let _ = HttpServer::new(move || {
             App::new()
                 .wrap(Logger::default())
                 .app_data(app_data.clone())
+                .app_data(web::PayloadConfig::new(UPLOAD_LIMIT))
                 .service(health_check)
                 .service(index)
-                .service(
-                    Files::new("/data", data_dir.clone())
-                        .method_guard(guard::Get()),
-                )
+                .service(index_signature)
+                .service(index_bloom)
+                .service(document)
                 .service(ratings)
+                .service(rating_scale)
+                .service(grep)
+                .service(admin_jobs)
+                .service(campaign_progress)
+                .service(upload_document)
         })
         .workers(2)
         .bind((addr, port))?
@@ -177,3 +1090,221 @@ impl Serve {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir, write, File};
+
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use polars::prelude::{df, IpcWriter, SerWriter};
+
+    use super::*;
+    use crate::testing::temp_dir;
+
+    type TestResult = anyhow::Result<()>;
+
+    /// Creates a datashed named `name`, whose index contains the name.
+    fn shed(base_dir: &Path, name: &str) -> anyhow::Result<Datashed> {
+        let root_dir = base_dir.join(name);
+        create_dir(&root_dir)?;
+        create_dir(root_dir.join(Datashed::TEMP_DIR))?;
+        write(
+            root_dir.join(Datashed::CONFIG),
+            format!(
+                "[metadata]\nname = \"{name}\"\nversion = \"0.1.0\"\n"
+            ),
+        )?;
+        write(root_dir.join(Datashed::INDEX), name)?;
+        Ok(Datashed::from_path(root_dir)?)
+    }
+
+    #[test]
+    fn serve_parse_shed() {
+        assert_eq!(
+            parse_shed("foo=/tmp/foo"),
+            Ok(("foo".into(), PathBuf::from("/tmp/foo")))
+        );
+        assert_eq!(
+            parse_shed("foo-bar_1=../a=b"),
+            Ok(("foo-bar_1".into(), PathBuf::from("../a=b")))
+        );
+
+        for s in ["foo", "=/tmp/foo", "foo=", "foo/bar=/tmp", "ä=/tmp"]
+        {
+            assert!(parse_shed(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn serve_workspace() -> TestResult {
+        let dir = temp_dir()?;
+        let path = dir.path().join("workspace.toml");
+        write(
+            &path,
+            r#"
+            port = 9002
+
+            [sheds]
+            foo = "/srv/foo"
+            bar = "../bar"
+            "#,
+        )?;
+
+        let workspace = Workspace::from_path(&path)?;
+        assert_eq!(workspace.port, Some(9002));
+        assert!(workspace.address.is_none());
+        assert_eq!(
+            workspace.sheds.into_iter().collect::<Vec<_>>(),
+            [
+                ("bar".to_string(), dir.path().join("../bar")),
+                ("foo".to_string(), PathBuf::from("/srv/foo")),
+            ]
+        );
+
+        write(&path, "port = \"foo\"")?;
+        assert!(Workspace::from_path(&path).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn serve_search() -> TestResult {
+        let dir = temp_dir()?;
+        let datashed = shed(dir.path(), "foo")?;
+        let base_dir = datashed.base_dir().clone();
+
+        create_dir_all(base_dir.join("data/book"))?;
+        write(base_dir.join("data/book/1.txt"), "Das Haus am See")?;
+        write(base_dir.join("data/book/2.txt"), "Der Baum im Garten")?;
+
+        let mut df = df!(
+            "path" => ["data/book/1.txt", "data/book/2.txt"],
+            "hash" => ["h1", "h2"],
+        )?;
+        IpcWriter::new(File::create(base_dir.join(Datashed::INDEX))?)
+            .finish(&mut df)?;
+
+        let state = AppState::new(datashed, false)?;
+        let find = |pattern: &str, invert_match, snippets| {
+            let query = GrepReq {
+                pattern: pattern.into(),
+                ignore_case: true,
+                invert_match,
+                predicate: None,
+                max_bytes: None,
+                snippets,
+                limit: None,
+            };
+
+            let re = RegexBuilder::new(&query.pattern)
+                .case_insensitive(query.ignore_case)
+                .build()?;
+            let (tx, mut rx) = channel::<Bytes>(64);
+            search(&state, &query, &re, None, &tx)?;
+
+            let mut lines: Vec<serde_json::Value> = vec![];
+            while let Ok(line) = rx.try_recv() {
+                lines.push(serde_json::from_slice(&line)?);
+            }
+
+            anyhow::Ok(lines)
+        };
+
+        let lines = find("haus", false, true)?;
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["path"], "data/book/1.txt");
+        assert_eq!(lines[0]["hash"], "h1");
+        assert_eq!(lines[0]["snippet"], "Das Haus am See");
+
+        let lines = find("haus", true, false)?;
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["path"], "data/book/2.txt");
+        assert!(lines[0].get("snippet").is_none());
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn serve_multiple_sheds() -> TestResult {
+        let dir = temp_dir()?;
+        let names = vec!["bar".to_string(), "foo".to_string()];
+        let mut app = App::new()
+            .app_data(web::Data::new(names.clone()))
+            .service(list_sheds);
+
+        for name in names.iter() {
+            let datashed = shed(dir.path(), name)?;
+            let state = web::Data::new(AppState::new(datashed, false)?);
+            app = app.service(shed_scope(name, state));
+        }
+
+        let app = test::init_service(app).await;
+
+        let req = test::TestRequest::get().uri("/sheds").to_request();
+        let body: Vec<String> =
+            test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body, names);
+
+        for name in names.iter() {
+            let req = test::TestRequest::get()
+                .uri(&format!("/sheds/{name}/index.ipc"))
+                .to_request();
+            let body = test::call_and_read_body(&app, req).await;
+            assert_eq!(body, name.as_bytes());
+        }
+
+        for uri in ["/sheds/baz/index.ipc", "/index.ipc"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{uri}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn upload_paths() -> TestResult {
+        let config: Config = toml::from_str(
+            r#"
+            [metadata]
+            name = "foo"
+            version = "0.1.0"
+            "#,
+        )?;
+
+        assert_eq!(
+            upload_path("book/118540238.txt", &config),
+            Ok(PathBuf::from("data/book/118540238.txt"))
+        );
+        assert_eq!(
+            upload_path("a/toc/1234X.txt", &config),
+            Ok(PathBuf::from("data/a/toc/1234X.txt"))
+        );
+
+        for tail in [
+            "../book/1.txt",
+            "book/../1.txt",
+            "book//1.txt",
+            "/book/1.txt",
+            "./book/1.txt",
+            "",
+        ] {
+            assert!(upload_path(tail, &config).is_err(), "{tail}");
+        }
+
+        // missing document kind
+        assert!(upload_path("1.txt", &config).is_err());
+        assert!(upload_path("foo/1.txt", &config).is_err());
+
+        // non-alphanumeric IDN
+        assert!(upload_path("book/1-2.txt", &config).is_err());
+        assert!(upload_path("book/1 2.txt", &config).is_err());
+        assert!(upload_path("book/ä1.txt", &config).is_err());
+
+        // not a document path (suffix)
+        assert!(upload_path("book/1.pdf", &config).is_err());
+
+        Ok(())
+    }
+}
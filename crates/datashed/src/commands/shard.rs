@@ -0,0 +1,303 @@
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use hashbrown::HashMap;
+use indicatif::ProgressIterator;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use rand::seq::SliceRandom;
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::prelude::*;
+use crate::seed;
+use crate::sql::select_where;
+use crate::stats::write_index;
+
+const PBAR_SHARD: &str =
+    "Sharding documents: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// The partitioning strategy.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ShardBy {
+    /// Balance the total size (in bytes) of the shards.
+    #[default]
+    Size,
+
+    /// Balance the number of documents of the shards. The documents
+    /// are assigned in random order (see `--seed`).
+    Count,
+
+    /// Assign documents by the hash of their path (and the seed). The
+    /// assignment of a document doesn't depend on the other
+    /// documents, but the shards are only balanced on average.
+    Hash,
+}
+
+/// Partition the datashed into self-contained sub-datasheds.
+///
+/// Each shard is a datashed of its own (`shard-{i}`) with a copy of
+/// the config, an index of its documents and the documents itself.
+/// The documents of each kind are partitioned separately, such that
+/// every shard has (roughly) the same composition as the datashed.
+#[derive(Debug, Parser)]
+pub(crate) struct Shard {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The partitioning strategy.
+    #[arg(long, value_enum, default_value_t = ShardBy::Size)]
+    by: ShardBy,
+
+    /// The number of shards.
+    #[arg(short, default_value = "8", value_name = "n")]
+    n: usize,
+
+    /// Create hard links to the documents instead of copying them.
+    /// The shards must be located on the same file system as the
+    /// datashed.
+    #[arg(long)]
+    hardlink: bool,
+
+    /// An optional predicate to filter the document-set.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+
+    /// The directory, in which the shards are created. The directory
+    /// must not exist or must be empty.
+    #[arg(value_name = "dir")]
+    output: PathBuf,
+}
+
+/// Returns the shard of a document by the SHA256 digest of the seed
+/// and its path, which is stable across platforms and releases.
+fn hash_shard(seed: u64, path: &str, n: usize) -> usize {
+    let mut hasher = Sha256::new();
+    hasher.update(seed.to_le_bytes());
+    hasher.update(path.as_bytes());
+
+    let digest = hasher.finalize();
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&digest[..8]);
+    (u64::from_le_bytes(buf) % n as u64) as usize
+}
+
+/// Returns the shard of each document.
+fn partition(
+    kinds: &StringChunked,
+    paths: &StringChunked,
+    sizes: &[u64],
+    by: ShardBy,
+    n: usize,
+) -> Vec<usize> {
+    let mut groups: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (idx, kind) in kinds.iter().enumerate() {
+        groups
+            .entry(kind.unwrap_or_default())
+            .or_default()
+            .push(idx);
+    }
+
+    let mut groups: Vec<(&str, Vec<usize>)> =
+        groups.into_iter().collect();
+    groups.sort_by_key(|(kind, _)| *kind);
+
+    let mut rng = seed::rng();
+    let seed = seed::get().unwrap_or_default();
+    let mut result = vec![0; paths.len()];
+    let mut loads = vec![0u64; n];
+
+    for (_, mut members) in groups.into_iter() {
+        match by {
+            ShardBy::Count => {
+                // The documents are shuffled, so that the shards don't
+                // depend on the order of the index (e.g. by date).
+                members.shuffle(&mut rng);
+
+                // Continue the round robin of the previous kind, so
+                // that the remainders are spread over all shards.
+                let offset = loads
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, load)| **load)
+                    .map(|(i, _)| i)
+                    .unwrap_or_default();
+
+                for (i, idx) in members.into_iter().enumerate() {
+                    let shard = (offset + i) % n;
+                    result[idx] = shard;
+                    loads[shard] += 1;
+                }
+            }
+            ShardBy::Size => {
+                // Longest processing time first: each document is
+                // assigned to the shard with the smallest total size.
+                members.sort_by_key(|idx| {
+                    (std::cmp::Reverse(sizes[*idx]), paths.get(*idx))
+                });
+
+                let mut kind_loads = vec![0u64; n];
+                for idx in members.into_iter() {
+                    let shard = (0..n)
+                        .min_by_key(|i| (kind_loads[*i], loads[*i]))
+                        .unwrap_or_default();
+                    result[idx] = shard;
+                    kind_loads[shard] += sizes[idx];
+                    loads[shard] += sizes[idx];
+                }
+            }
+            ShardBy::Hash => {
+                for idx in members.into_iter() {
+                    let path = paths.get(idx).unwrap_or_default();
+                    result[idx] = hash_shard(seed, path, n);
+                }
+            }
+        }
+    }
+
+    result
+}
+
+impl Shard {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let config = datashed.config()?;
+        let base_dir = datashed.base_dir();
+
+        if self.n == 0 {
+            bail!("the number of shards must be greater than zero");
+        }
+
+        if self.output.exists()
+            && fs::read_dir(&self.output)?.count() > 0
+        {
+            bail!("directory '{}' is not empty", self.output.display());
+        }
+
+        let mut df = datashed.index()?;
+        if let Some(predicate) = self.predicate {
+            let mut ctx = SQLContext::new();
+            ctx.register("df", df.lazy());
+            df = ctx.execute(&select_where(&predicate))?.collect()?;
+        }
+
+        let kinds = df.column("kind")?.cast(&DataType::String)?;
+        let paths = df.column("path")?.str()?.clone();
+        let sizes: Vec<u64> = df
+            .column("size")?
+            .cast(&DataType::UInt64)?
+            .u64()?
+            .iter()
+            .map(Option::unwrap_or_default)
+            .collect();
+
+        let shards =
+            partition(kinds.str()?, &paths, &sizes, self.by, self.n);
+        let width = (self.n - 1).to_string().len();
+        let name = |i: usize| format!("shard-{i:0width$}");
+
+        let pbar = ProgressBarBuilder::new(PBAR_SHARD, self.quiet)
+            .len(df.height() as u64)
+            .build();
+
+        for (idx, path) in paths.iter().enumerate().progress_with(pbar)
+        {
+            let Some(path) = path else {
+                continue;
+            };
+
+            let src = base_dir.join(path);
+            let dest = self.output.join(name(shards[idx])).join(path);
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            if self.hardlink {
+                fs::hard_link(&src, &dest)?;
+            } else {
+                fs::copy(&src, &dest)?;
+            }
+        }
+
+        for i in 0..self.n {
+            let dir = self.output.join(name(i));
+            fs::create_dir_all(dir.join(Datashed::DATA_DIR))?;
+
+            let mask: BooleanChunked =
+                shards.iter().map(|shard| *shard == i).collect();
+            let mut index = df.filter(&mask)?;
+
+            let path = dir.join(Datashed::CONFIG);
+            fs::copy(base_dir.join(Datashed::CONFIG), &path)?;
+            let mut shard = Config::from_path(&path)?;
+            shard.metadata.name =
+                format!("{}-{}", config.metadata.name, name(i));
+            shard.save()?;
+
+            write_index(
+                &mut index,
+                File::create(dir.join(Datashed::INDEX))?,
+            )?;
+
+            if self.verbose {
+                let size: u64 = sizes
+                    .iter()
+                    .zip(shards.iter())
+                    .filter(|(_, shard)| **shard == i)
+                    .map(|(size, _)| size)
+                    .sum();
+
+                eprintln!(
+                    "{}: {} document(s), {size} byte(s)",
+                    name(i),
+                    index.height(),
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partition_by_size() {
+        let kinds = StringChunked::from_slice(
+            "kind".into(),
+            &["book", "book", "book", "article", "article"],
+        );
+        let paths = StringChunked::from_slice(
+            "path".into(),
+            &["a", "b", "c", "d", "e"],
+        );
+        let sizes = [10, 5, 5, 3, 3];
+
+        let shards =
+            partition(&kinds, &paths, &sizes, ShardBy::Size, 2);
+        assert_eq!(shards, [0, 1, 1, 0, 1]);
+
+        let shards =
+            partition(&kinds, &paths, &sizes, ShardBy::Count, 2);
+        assert_eq!(shards.iter().filter(|i| **i == 0).count(), 3);
+    }
+
+    #[test]
+    fn partition_by_hash() {
+        assert_eq!(hash_shard(0, "data/book/1.txt", 7), 3);
+        assert_eq!(hash_shard(42, "data/book/1.txt", 7), 6);
+        assert_eq!(hash_shard(42, "data/book/1.txt", 16), 15);
+    }
+}
@@ -0,0 +1,114 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::prelude::*;
+use crate::signature;
+
+/// Sign the index or archives of the datashed.
+///
+/// Each file is signed with the ed25519 key of the datashed (see the
+/// `signing.key-file` option) and the base64 encoded signature is
+/// written into a detached signature file next to it (`<file>.sig`).
+/// Without any file arguments, the index of the datashed is signed and,
+/// if the config contains redaction policies, the published (redacted)
+/// index as well.
+/// Signatures can be checked with `datashed verify --signature` or by
+/// `dataset fetch`, if the public key of the datashed is pinned in the
+/// remote config.
+#[derive(Debug, Parser)]
+pub(crate) struct Sign {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The signing key. If not set, the key file of the config is
+    /// used.
+    #[arg(short, long, value_name = "filename")]
+    key_file: Option<PathBuf>,
+
+    /// Generate a new signing key, write it into the key file and
+    /// print the public key.
+    #[arg(long)]
+    generate_key: bool,
+
+    /// The files to be signed (default: the index and the redacted
+    /// index of the datashed).
+    files: Vec<PathBuf>,
+}
+
+impl Sign {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let config = datashed.config()?;
+
+        let Some(key_file) = self.key_file.or_else(|| {
+            config.signing.as_ref().and_then(|s| s.key_file.clone())
+        }) else {
+            bail!(
+                "no signing key (set `signing.key-file` or --key-file)"
+            );
+        };
+
+        let key = if self.generate_key {
+            let key = signature::generate_key(&key_file)?;
+            println!("{}", signature::public_key(&key));
+            if self.verbose {
+                eprintln!(
+                    "generated signing key '{}'.",
+                    key_file.display()
+                );
+            }
+
+            key
+        } else {
+            signature::read_key(&key_file)?
+        };
+
+        let files = if self.files.is_empty() {
+            if self.generate_key {
+                return Ok(());
+            }
+
+            let mut files =
+                vec![datashed.base_dir().join(Datashed::INDEX)];
+            if !config.redact.is_empty() {
+                let path =
+                    datashed.base_dir().join(Datashed::REDACTED_INDEX);
+                if !path.is_file() {
+                    bail!(
+                        "missing redacted index (run `datashed \
+                        redact-index` first)"
+                    );
+                }
+
+                files.push(path);
+            }
+
+            files
+        } else {
+            self.files
+        };
+
+        for path in files.iter() {
+            signature::sign_file(&key, path)?;
+
+            if self.verbose {
+                eprintln!("signed '{}'.", path.display());
+            }
+        }
+
+        if !self.quiet {
+            eprintln!("signed {} file(s).", files.len());
+        }
+
+        Ok(())
+    }
+}
@@ -2,9 +2,8 @@ use std::env::current_dir;
 
 use clap::Parser;
 use comfy_table::{presets, Row, Table};
-use glob::{glob_with, MatchOptions};
 use hashbrown::HashSet;
-use polars::prelude::DataType;
+use polars::prelude::{col, DataType};
 
 use crate::prelude::*;
 use crate::utils::relpath;
@@ -35,7 +34,15 @@ impl Status {
         let base_dir = datashed.base_dir();
         let current_dir = current_dir()?;
         let config = datashed.config()?;
-        let index = datashed.index()?;
+        let index = datashed
+            .index_lazy()?
+            .select([
+                col("path"),
+                col("hash"),
+                col("mtime"),
+                col("size"),
+            ])
+            .collect()?;
 
         let mut table = Table::new();
         table.set_header(Row::from(vec![
@@ -43,14 +50,12 @@ impl Status {
         ]));
         table.load_preset(presets::UTF8_FULL_CONDENSED);
 
-        let pattern = format!("{}/**/*.txt", data_dir.display());
-        let options = MatchOptions::default();
-
-        let mut files: HashSet<_> = glob_with(&pattern, options)
-            .map_err(|e| DatashedError::Other(e.to_string()))?
-            .filter_map(Result::ok)
-            .map(|path| relpath(path, base_dir))
-            .collect();
+        let mut files: HashSet<_> = config
+            .discovery
+            .matcher()?
+            .documents(base_dir, &data_dir)?
+            .map(|path| path.map(|path| relpath(path, base_dir)))
+            .collect::<DatashedResult<_>>()?;
 
         let path = index.column("path")?.str()?;
         let hash = index.column("hash")?.str()?;
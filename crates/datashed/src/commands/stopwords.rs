@@ -0,0 +1,289 @@
+use std::fs::{read_to_string, File};
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+
+use bstr::ByteSlice;
+use clap::Parser;
+use hashbrown::{HashMap, HashSet};
+use indicatif::ParallelProgressIterator;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::collate::{Collation, Locale, Strength};
+use crate::prelude::*;
+use crate::preprocess::Preprocess;
+use crate::sql::select_where;
+
+const PBAR_PROCESS: &str =
+    "Processing documents: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// Derive a list of stopword candidates from the corpus.
+///
+/// The candidates are the `n` tokens with the highest document
+/// frequency. Optionally, only those tokens are kept whose mean
+/// TF-IDF score doesn't exceed a given floor. The resulting list (one
+/// token per line) can be passed to `datashed vocab --stopwords`.
+#[derive(Debug, Default, Parser)]
+pub(crate) struct Stopwords {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The maximum number of stopword candidates.
+    #[arg(short = 'n', long, default_value = "200", value_name = "n")]
+    top: usize,
+
+    /// Keep only those candidates whose mean TF-IDF score is less
+    /// than or equal to `floor`.
+    #[arg(long = "tfidf-floor", value_name = "floor")]
+    tfidf_floor: Option<f64>,
+
+    /// The name of a preprocessing profile of the datashed config. If
+    /// not set, the default preprocessing (NFC, lowercase) is used.
+    #[arg(long, value_name = "profile")]
+    preprocess: Option<String>,
+
+    /// Ignore tokens with a length less than `n`. The minimum token
+    /// length of a preprocessing profile takes precedence.
+    #[arg(
+        long = "min-tl",
+        short = 'l',
+        default_value = "2",
+        value_name = "n"
+    )]
+    min_token_len: usize,
+
+    /// A list of stopwords (one per line) the candidates are compared
+    /// against. This option can be specified multiple times.
+    #[arg(long = "baseline", short = 'B', value_name = "filename")]
+    baselines: Vec<PathBuf>,
+
+    /// Add the words of the baseline lists to the output.
+    #[arg(long, requires = "baselines")]
+    union: bool,
+
+    /// The locale used to sort the tokens. Defaults to the locale of
+    /// the `[collation]` config section or `en`.
+    #[arg(long, value_enum, value_name = "locale")]
+    locale: Option<Locale>,
+
+    /// The strength of the collation (primary = base letters,
+    /// secondary = diacritics, tertiary = case). Defaults to the
+    /// strength of the `[collation]` config section or `tertiary`.
+    #[arg(long, value_enum, value_name = "strength")]
+    strength: Option<Strength>,
+
+    /// Write the stopword list into `filename`. By default output will
+    /// be written to the standard output (`stdout`).
+    #[arg(short, long, value_name = "filename")]
+    output: Option<PathBuf>,
+
+    /// An optional predicate to filter the document-set.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+}
+
+/// Maps a token to its document frequency and the sum of its relative
+/// term frequencies.
+type DocFreqMap = HashMap<String, (u64, f64)>;
+
+fn read_stopwords(path: &PathBuf) -> DatashedResult<HashSet<String>> {
+    Ok(read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect())
+}
+
+/// Returns the `top` tokens with the highest document frequency (ties
+/// are sorted by the collation), whose mean TF-IDF score doesn't
+/// exceed the floor. The number of documents is `n`.
+fn candidates(
+    freqs: DocFreqMap,
+    n: usize,
+    top: usize,
+    tfidf_floor: Option<f64>,
+    collation: &Collation,
+) -> Vec<String> {
+    let mut candidates: Vec<(String, u64, f64)> = freqs
+        .into_iter()
+        .map(|(token, (df, tf))| {
+            let idf = (n as f64 / df as f64).ln();
+            (token, df, (tf / df as f64) * idf)
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| {
+        b.1.cmp(&a.1).then_with(|| collation.compare(&a.0, &b.0))
+    });
+    candidates.truncate(top);
+
+    if let Some(floor) = tfidf_floor {
+        candidates.retain(|(_, _, tfidf)| *tfidf <= floor);
+    }
+
+    candidates.into_iter().map(|(token, _, _)| token).collect()
+}
+
+impl Stopwords {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let base_dir = datashed.base_dir();
+        let mut index = datashed.index_lazy()?;
+        let config = datashed.config()?;
+        let preprocess = Preprocess::from_config(
+            &config,
+            self.preprocess.as_deref(),
+        )?
+        .with_min_token_len(self.min_token_len);
+
+        if let Some(predicate) = self.predicate {
+            let mut ctx = SQLContext::new();
+            ctx.register("df", index);
+            index = ctx.execute(&select_where(&predicate))?;
+        }
+
+        let df = index.select([col("path")]).collect()?;
+
+        let n = df.height();
+        let path = df.column("path")?.str()?;
+
+        let pbar = ProgressBarBuilder::new(PBAR_PROCESS, self.quiet)
+            .len(n as u64)
+            .build();
+
+        let freqs = (0..n)
+            .into_par_iter()
+            .progress_with(pbar)
+            .map(|idx| -> DatashedResult<DocFreqMap> {
+                let path = path.get(idx).unwrap();
+                let doc = Document::from_path(base_dir.join(path))?;
+
+                let counts = preprocess
+                    .tokens(&doc.as_ref().to_str_lossy())
+                    .into_iter()
+                    .fold(
+                        HashMap::<String, u64>::new(),
+                        |mut acc, w| {
+                            acc.entry(w)
+                                .and_modify(|e| *e += 1)
+                                .or_insert(1);
+                            acc
+                        },
+                    );
+
+                let total = counts.values().sum::<u64>() as f64;
+
+                Ok(counts
+                    .into_iter()
+                    .map(|(token, tf)| (token, (1, tf as f64 / total)))
+                    .collect())
+            })
+            .try_reduce(DocFreqMap::new, |mut acc, rhs| {
+                for (token, (df, tf)) in rhs.into_iter() {
+                    acc.entry(token)
+                        .and_modify(|e| {
+                            e.0 += df;
+                            e.1 += tf;
+                        })
+                        .or_insert((df, tf));
+                }
+
+                Ok(acc)
+            })?;
+
+        let collation =
+            Collation::from_config(&config, self.locale, self.strength);
+        let mut words = candidates(
+            freqs,
+            n,
+            self.top,
+            self.tfidf_floor,
+            &collation,
+        );
+
+        let mut extra = HashSet::new();
+        for path in self.baselines.iter() {
+            let baseline = read_stopwords(path)?;
+            let found = words
+                .iter()
+                .filter(|word| baseline.contains(*word))
+                .count();
+
+            if !self.quiet {
+                eprintln!(
+                    "{}: {found} of {} candidates in baseline, \
+                        {} baseline words not found",
+                    path.display(),
+                    words.len(),
+                    baseline.len() - found,
+                );
+            }
+
+            if self.union {
+                extra.extend(baseline);
+            }
+        }
+
+        if self.union {
+            extra.retain(|word| !words.contains(word));
+            let mut extra: Vec<String> = extra.into_iter().collect();
+            collation.sort(&mut extra);
+            words.extend(extra);
+        }
+
+        let mut out: Box<dyn Write> = match self.output {
+            Some(path) => Box::new(File::create(path)?),
+            None => Box::new(stdout().lock()),
+        };
+
+        for word in words.iter() {
+            writeln!(out, "{word}")?;
+        }
+
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stopword_candidates() {
+        // (document frequency, sum of the relative term frequencies)
+        let freqs = DocFreqMap::from_iter([
+            ("und".to_string(), (4, 0.4)),
+            ("die".to_string(), (4, 0.8)),
+            ("der".to_string(), (3, 0.3)),
+            ("buch".to_string(), (1, 0.5)),
+        ]);
+
+        let collation = Collation::default();
+        assert_eq!(
+            candidates(freqs.clone(), 4, 3, None, &collation),
+            ["die", "und", "der"]
+        );
+
+        // der: (0.3 / 3) * ln(4 / 3) ≈ 0.029
+        assert_eq!(
+            candidates(freqs.clone(), 4, 10, Some(0.01), &collation),
+            ["die", "und"]
+        );
+        assert_eq!(
+            candidates(freqs, 4, 10, Some(0.1), &collation),
+            ["die", "und", "der"]
+        );
+    }
+}
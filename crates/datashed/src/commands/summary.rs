@@ -5,9 +5,12 @@ use clap::Parser;
 use comfy_table::{presets, Row, Table};
 use humansize::{make_format, BINARY};
 use polars::lazy::dsl::col;
-use polars::prelude::{DataType, IntoLazy, SortMultipleOptions};
+use polars::prelude::{
+    DataFrame, DataType, IntoLazy, SortMultipleOptions,
+};
 use serde_json::{json, Map};
 
+use crate::licenses;
 use crate::prelude::*;
 
 /// Prints a summary of the datashed.
@@ -24,6 +27,11 @@ pub(crate) struct Summary {
     #[arg(short, long, conflicts_with = "verbose")]
     quiet: bool,
 
+    /// Summarize the documents by license code (see the `[license]`
+    /// config section) instead of by kind.
+    #[arg(short, long)]
+    licenses: bool,
+
     /// Write summary in JSON format to `filename` instead of standard
     /// output (stdout).
     #[arg(short, long, value_name = "filename")]
@@ -34,6 +42,11 @@ impl Summary {
     pub(crate) fn execute(self) -> DatashedResult<()> {
         let datashed = Datashed::discover()?;
         let index = datashed.index()?;
+
+        if self.licenses {
+            return self.licenses(&datashed.config()?, &index);
+        }
+
         let df = index
             .lazy()
             .group_by([col("remote"), col("kind")])
@@ -43,14 +56,14 @@ impl Summary {
                 col("hash").n_unique().alias("unique"),
             ])
             .with_columns([(col("docs") - col("unique")).alias("dups")])
+            .sort(["kind"], SortMultipleOptions::default())
             .select([
-                col("remote"),
-                col("kind"),
+                col("remote").cast(DataType::String),
+                col("kind").cast(DataType::String),
                 col("docs"),
                 col("size").cast(DataType::UInt64),
                 col("dups"),
             ])
-            .sort(["kind"], SortMultipleOptions::default())
             .collect()?;
 
         let kinds = df.column("kind")?.str()?;
@@ -110,4 +123,56 @@ impl Summary {
 
         Ok(())
     }
+
+    fn licenses(
+        &self,
+        config: &Config,
+        index: &DataFrame,
+    ) -> DatashedResult<()> {
+        let groups = licenses::groups(config, index)?;
+
+        if let Some(ref path) = self.output {
+            let mut map = Map::new();
+            for (code, group) in groups.iter() {
+                map.insert(
+                    code.clone(),
+                    json!({
+                        "name": group.terms.name,
+                        "url": group.terms.url,
+                        "attribution": group.terms.attribution,
+                        "docs": group.docs,
+                        "size": group.size,
+                    }),
+                );
+            }
+
+            let value: serde_json::Value = map.into();
+            fs::write(path, value.to_string())?;
+        } else {
+            let formatter = make_format(BINARY);
+            let mut table = Table::new();
+            table.load_preset(presets::UTF8_FULL_CONDENSED);
+            table.set_header(Row::from(vec![
+                "license",
+                "name",
+                "attribution",
+                "docs",
+                "size",
+            ]));
+
+            for (code, group) in groups.iter() {
+                table.add_row([
+                    code.clone(),
+                    group.terms.name.clone().unwrap_or_default(),
+                    group.terms.attribution.clone().unwrap_or_default(),
+                    group.docs.to_string(),
+                    formatter(group.size),
+                ]);
+            }
+
+            println!("{table}");
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,165 @@
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::{env, fs};
+
+use clap::Parser;
+
+use crate::document::DocumentKind;
+use crate::prelude::*;
+use crate::seed;
+use crate::synth::{words, Generator};
+
+const PBAR_GENERATE: &str =
+    "Generating documents: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// Generate a synthetic datashed.
+///
+/// The generated shed can be used for benchmarks and integration
+/// tests. Documents are distributed evenly over the given languages
+/// and document kinds; a fraction of the documents (`--duplicates`)
+/// are exact copies of previously generated documents. Afterwards the
+/// shed can be indexed with `datashed index`.
+#[derive(Debug, Parser)]
+pub(crate) struct Synth {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// The number of documents to generate.
+    #[arg(short = 'n', long, default_value = "1000", value_name = "n")]
+    docs: usize,
+
+    /// The minimum number of words per document.
+    #[arg(long, default_value = "50", value_name = "n")]
+    min_words: usize,
+
+    /// The maximum number of words per document.
+    #[arg(long, default_value = "5000", value_name = "n")]
+    max_words: usize,
+
+    /// A comma-separated list of languages (ger, eng, fre).
+    #[arg(
+        long = "lang",
+        value_delimiter = ',',
+        default_value = "ger,eng",
+        value_name = "langs"
+    )]
+    langs: Vec<String>,
+
+    /// A comma-separated list of document kinds.
+    #[arg(
+        long = "kind",
+        value_delimiter = ',',
+        default_value = "book",
+        value_name = "kinds"
+    )]
+    kinds: Vec<String>,
+
+    /// The ratio of duplicate documents (between 0.0 and 1.0).
+    #[arg(long, default_value = "0.0", value_name = "ratio")]
+    duplicates: f64,
+
+    /// The location of the synthetic datashed.
+    path: PathBuf,
+}
+
+impl Synth {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let seed = seed::get().unwrap_or_default();
+        if !(0.0..=1.0).contains(&self.duplicates) {
+            bail!("duplicate ratio must be between 0.0 and 1.0");
+        }
+
+        let langs = self
+            .langs
+            .iter()
+            .map(|lang| words(lang))
+            .collect::<DatashedResult<Vec<_>>>()?;
+
+        let kinds = self
+            .kinds
+            .iter()
+            .map(|kind| DocumentKind::from_str(kind))
+            .collect::<DatashedResult<Vec<_>>>()?;
+
+        let root_dir = env::current_dir()?.join(&self.path);
+        let data_dir = root_dir.join(Datashed::DATA_DIR);
+        let tmp_dir = root_dir.join(Datashed::TEMP_DIR);
+        let config = root_dir.join(Datashed::CONFIG);
+
+        if data_dir.exists() {
+            bail!(
+                "data directory '{}' already exists",
+                data_dir.display()
+            );
+        }
+
+        for kind in kinds.iter() {
+            fs::create_dir_all(data_dir.join(kind.to_string()))?;
+        }
+
+        fs::create_dir_all(&tmp_dir)?;
+
+        if !config.exists() {
+            let mut config = Config::create(config)?;
+            config.metadata.name = root_dir
+                .file_name()
+                .and_then(OsStr::to_str)
+                .unwrap_or("synth")
+                .to_string();
+            config.metadata.description =
+                Some(format!("synthetic datashed (seed = {})", seed));
+            config.save()?;
+        }
+
+        let pbar = ProgressBarBuilder::new(PBAR_GENERATE, self.quiet)
+            .len(self.docs as u64)
+            .build();
+
+        let mut generator =
+            Generator::new(seed, self.min_words, self.max_words);
+        let mut paths: Vec<PathBuf> = Vec::with_capacity(self.docs);
+        let mut dups = 0;
+
+        for i in 0..self.docs {
+            let kind = &kinds[i % kinds.len()];
+            let idn = format!("{:09}", 100_000_000 + i);
+            let path = data_dir
+                .join(kind.to_string())
+                .join(format!("{idn}.txt"));
+
+            if !paths.is_empty() && generator.chance(self.duplicates) {
+                let source = &paths[generator.index(paths.len())];
+                fs::copy(source, &path)?;
+                dups += 1;
+            } else {
+                let doc = generator.document(langs[i % langs.len()]);
+                fs::write(&path, doc)?;
+            }
+
+            paths.push(path);
+            pbar.inc(1);
+        }
+
+        pbar.finish_using_style();
+
+        if self.verbose {
+            eprintln!(
+                "Generated {} documents ({dups} duplicates) in {}",
+                self.docs,
+                root_dir.display()
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashSet;
+use std::env;
+use std::io::stdout;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use polars::prelude::*;
+use polars::sql::SQLContext;
+
+use crate::audit;
+use crate::prelude::*;
+use crate::sql::select_where;
+use crate::tags::{TagEntry, Tags};
+use crate::utils::{write_df, OutputFormat};
+
+/// Manage per-document tags.
+///
+/// The tags are stored in the tags table (`tags.csv`) of the root
+/// directory; each entry consists of the path of the document, the
+/// tag, the user and the time of tagging. The documents are selected
+/// by their paths (relative to the root directory) or by a `--where`
+/// predicate over the index. The tags of a document are available as
+/// the list column `tags` in the predicates of `datashed tag` and
+/// `datashed select`, e.g. `--where "array_contains(tags, 'review')"`,
+/// and in the worklist of `datashed select`.
+#[derive(Debug, clap::Parser)]
+pub(crate) struct Tag {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    #[clap(subcommand)]
+    cmd: Command,
+}
+
+#[derive(Debug, clap::Parser)]
+pub(crate) enum Command {
+    /// Add the tag \<tag\> to the selected documents.
+    Add {
+        /// The user, who tags the documents. By default, the name of
+        /// the current (system) user is used.
+        #[arg(short, long, env = "DATASHED_USERNAME")]
+        username: Option<String>,
+
+        /// Select the documents, which match the predicate.
+        #[arg(long = "where")]
+        predicate: Option<String>,
+
+        tag: String,
+
+        /// The paths of the documents (relative to the root
+        /// directory).
+        paths: Vec<String>,
+    },
+
+    /// Remove the tag \<tag\> from the selected documents.
+    #[clap(visible_alias = "rm")]
+    Remove {
+        /// Select the documents, which match the predicate.
+        #[arg(long = "where")]
+        predicate: Option<String>,
+
+        tag: String,
+
+        /// The paths of the documents (relative to the root
+        /// directory).
+        paths: Vec<String>,
+    },
+
+    /// List the tags of the selected documents. By default, the tags
+    /// of all documents are listed.
+    #[clap(visible_alias = "ls")]
+    List {
+        /// Select the documents, which match the predicate.
+        #[arg(long = "where")]
+        predicate: Option<String>,
+
+        /// The output format.
+        #[arg(long, value_name = "format", default_value = "csv")]
+        format: OutputFormat,
+
+        /// The paths of the documents (relative to the root
+        /// directory).
+        paths: Vec<String>,
+    },
+}
+
+/// Returns the paths of the selected documents.
+fn select(
+    datashed: &Datashed,
+    tags: &Tags,
+    predicate: Option<String>,
+    mut paths: Vec<String>,
+) -> DatashedResult<Vec<String>> {
+    if let Some(predicate) = predicate {
+        let mut ctx = SQLContext::new();
+        ctx.register("df", tags.join(datashed.index()?)?.lazy());
+
+        let df = ctx.execute(&select_where(&predicate))?.collect()?;
+        paths.extend(
+            df.column("path")?
+                .str()?
+                .into_iter()
+                .flatten()
+                .map(String::from),
+        );
+    }
+
+    Ok(paths)
+}
+
+impl Tag {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let tags_path = datashed.base_dir().join(Datashed::TAGS);
+        let mut tags = Tags::from_path(&tags_path)?;
+
+        match self.cmd {
+            Command::Add {
+                username,
+                predicate,
+                tag,
+                paths,
+            } => {
+                if predicate.is_none() && paths.is_empty() {
+                    bail!("no documents selected (path or --where)");
+                }
+
+                let user = username
+                    .or_else(|| env::var("USER").ok())
+                    .unwrap_or_default();
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_millis() as u64;
+
+                let paths = select(&datashed, &tags, predicate, paths)?;
+                let mut count = 0;
+                for path in paths.into_iter() {
+                    count += tags.add(TagEntry {
+                        path,
+                        tag: tag.clone(),
+                        user: user.clone(),
+                        timestamp,
+                    }) as usize;
+                }
+
+                tags.save(&tags_path)?;
+                audit::record(&datashed, "tag", count)?;
+                if self.verbose {
+                    eprintln!(
+                        "tagged {count} document(s) with '{tag}'."
+                    );
+                }
+            }
+            Command::Remove {
+                predicate,
+                tag,
+                paths,
+            } => {
+                if predicate.is_none() && paths.is_empty() {
+                    bail!("no documents selected (path or --where)");
+                }
+
+                let paths = select(&datashed, &tags, predicate, paths)?;
+                let count = paths
+                    .iter()
+                    .filter(|path| tags.remove(path, &tag))
+                    .count();
+
+                tags.save(&tags_path)?;
+                audit::record(&datashed, "tag", count)?;
+                if self.verbose {
+                    eprintln!(
+                        "removed tag '{tag}' from {count} document(s)."
+                    );
+                }
+            }
+            Command::List {
+                predicate,
+                format,
+                paths,
+            } => {
+                let selected = !paths.is_empty() || predicate.is_some();
+                let paths = select(&datashed, &tags, predicate, paths)?;
+
+                let paths: HashSet<String> =
+                    paths.into_iter().collect();
+                let entries: Vec<&TagEntry> = tags
+                    .entries()
+                    .iter()
+                    .filter(|e| !selected || paths.contains(&e.path))
+                    .collect();
+
+                let mut df = DataFrame::new(vec![
+                    Column::new(
+                        "path".into(),
+                        entries
+                            .iter()
+                            .map(|e| e.path.as_str())
+                            .collect::<Vec<_>>(),
+                    ),
+                    Column::new(
+                        "tag".into(),
+                        entries
+                            .iter()
+                            .map(|e| e.tag.as_str())
+                            .collect::<Vec<_>>(),
+                    ),
+                    Column::new(
+                        "user".into(),
+                        entries
+                            .iter()
+                            .map(|e| e.user.as_str())
+                            .collect::<Vec<_>>(),
+                    ),
+                    Column::new(
+                        "timestamp".into(),
+                        entries
+                            .iter()
+                            .map(|e| e.timestamp)
+                            .collect::<Vec<_>>(),
+                    ),
+                ])?;
+
+                write_df(&mut df, format, stdout().lock())?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,414 @@
+use std::fs::create_dir_all;
+use std::io::stdout;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use bstr::ByteSlice;
+use clap::Parser;
+use indicatif::ParallelProgressIterator;
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use regex::Regex;
+
+use crate::atomic::AtomicFile;
+use crate::prelude::*;
+use crate::sql::select_where;
+
+const PBAR_PARSE: &str = "Parsing TOCs: {human_pos} ({percent}%) | \
+        elapsed: {elapsed_precise}{msg}";
+
+/// Headings introducing a table of contents, which aren't entries.
+const HEADINGS: [&str; 5] = [
+    "inhalt",
+    "inhaltsverzeichnis",
+    "contents",
+    "table of contents",
+    "sommaire",
+];
+
+fn page_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"^(?<title>.*?\S)",
+            r"(?<sep>\s*[.·…_]{2,}\s*|\s+)",
+            r"(?<page>\d+|[IVXLCDM]+|[ivxlcdm]+)$",
+        ))
+        .unwrap()
+    })
+}
+
+fn numbering_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^(?<num>\d+(?:\.\d+)*\.?|[IVXLC]+\.|[A-Z]\.)\s+")
+            .unwrap()
+    })
+}
+
+/// A single entry of a table of contents.
+#[derive(Debug, Default, PartialEq)]
+struct Entry {
+    level: u32,
+    numbering: Option<String>,
+    title: String,
+    page: Option<String>,
+}
+
+/// An entry candidate together with its indentation.
+struct Line {
+    indent: usize,
+    numbering: Option<String>,
+    title: String,
+    page: Option<String>,
+}
+
+impl Line {
+    fn from_str(line: &str) -> Option<Self> {
+        let indent = line
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .map(|c| if c == '\t' { 4 } else { 1 })
+            .sum();
+
+        let line = line.trim();
+        if line.is_empty()
+            || HEADINGS
+                .contains(&line.to_lowercase().trim_end_matches(':'))
+        {
+            return None;
+        }
+
+        // Roman page numbers are only accepted after dot leaders or a
+        // wide gap, since they can't be told apart from a title ending
+        // with a roman numeral (e.g. "Teil II") otherwise.
+        let (title, page) = match page_re().captures(line) {
+            Some(caps)
+                if caps["page"]
+                    .starts_with(|c: char| c.is_ascii_digit())
+                    || caps["sep"].len() > 1 =>
+            {
+                (
+                    caps["title"].to_string(),
+                    Some(caps["page"].to_string()),
+                )
+            }
+            _ => (line.to_string(), None),
+        };
+
+        let (numbering, title) = match numbering_re().captures(&title) {
+            Some(caps) => {
+                let num = caps.get(0).unwrap();
+                (
+                    Some(caps["num"].trim_end_matches('.').to_string()),
+                    title[num.end()..].to_string(),
+                )
+            }
+            None => (None, title),
+        };
+
+        Some(Self {
+            indent,
+            numbering,
+            title,
+            page,
+        })
+    }
+
+    /// Returns `true` if the title seems to be continued on the next
+    /// line.
+    fn is_wrapped(&self) -> bool {
+        self.title.ends_with(|c: char| {
+            c.is_lowercase() || matches!(c, '-' | ',' | ':' | ';')
+        })
+    }
+}
+
+/// Parses the text of a table of contents into entries.
+///
+/// Each line is split into an (optional) numbering, the title and an
+/// (optional) page number, which may be separated by dot leaders.
+/// Lines without a page number ending with a lowercase letter or a
+/// punctuation mark, which are followed by an unnumbered line with a
+/// page number, are considered wrapped titles and are merged. The level
+/// of an entry is derived from the depth of its numbering (e.g.
+/// `2.1.3`) or, if unnumbered, from the rank of its indentation among
+/// all indentations of the TOC.
+fn parse(text: &str) -> Vec<Entry> {
+    let mut lines: Vec<Line> = vec![];
+    let mut pending: Option<Line> = None;
+
+    for line in text.lines() {
+        let Some(line) = Line::from_str(line) else {
+            lines.extend(pending.take());
+            continue;
+        };
+
+        match pending.take() {
+            Some(mut prev)
+                if line.numbering.is_none()
+                    && line.page.is_some()
+                    && prev.is_wrapped() =>
+            {
+                if !prev.title.ends_with('-') {
+                    prev.title.push(' ');
+                }
+
+                prev.title.push_str(&line.title);
+                prev.page = line.page;
+                lines.push(prev);
+            }
+            prev => {
+                lines.extend(prev);
+                if line.page.is_some() {
+                    lines.push(line);
+                } else {
+                    pending = Some(line);
+                }
+            }
+        }
+    }
+
+    lines.extend(pending);
+
+    let mut indents: Vec<usize> =
+        lines.iter().map(|l| l.indent).collect();
+    indents.sort_unstable();
+    indents.dedup();
+
+    lines
+        .into_iter()
+        .map(|line| {
+            let level = match line.numbering {
+                Some(ref num)
+                    if num
+                        .starts_with(|c: char| c.is_ascii_digit()) =>
+                {
+                    num.split('.').count() as u32
+                }
+                _ => {
+                    indents.binary_search(&line.indent).unwrap_or(0)
+                        as u32
+                        + 1
+                }
+            };
+
+            Entry {
+                level,
+                numbering: line.numbering,
+                title: line.title,
+                page: line.page,
+            }
+        })
+        .collect()
+}
+
+/// Parse table-of-contents documents into structured entries.
+///
+/// Each TOC document (`kind = 'toc'`) is parsed into entries using
+/// layout heuristics (numbering, indentation, dot leaders and trailing
+/// page numbers). The result is a table with the columns `path`,
+/// `idn`, `entry` (the position of the entry within the TOC), `level`,
+/// `numbering`, `title` and `page`.
+#[derive(Debug, Parser)]
+pub(crate) struct Tocparse {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Write one CSV file per TOC (named `{idn}_{stem}.csv` after the
+    /// IDN and the file stem of the document) into `dir` instead of a
+    /// single table.
+    #[arg(short = 'C', long = "directory", value_name = "dir")]
+    dest: Option<PathBuf>,
+
+    /// Write output into `filename` instead of `stdout`.
+    #[arg(
+        short,
+        long,
+        value_name = "filename",
+        conflicts_with = "dest"
+    )]
+    output: Option<PathBuf>,
+
+    /// An optional predicate to filter the document-set.
+    #[arg(long = "where")]
+    predicate: Option<String>,
+}
+
+/// Returns the name of the CSV file of a TOC. The name consists of the
+/// IDN and the file stem of the document, so that several TOCs of one
+/// IDN don't overwrite each other.
+fn filename(idn: &str, path: &str) -> String {
+    let stem = Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+
+    format!("{idn}_{stem}.csv")
+}
+
+impl Tocparse {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let base_dir = datashed.base_dir();
+        let index = datashed
+            .index()?
+            .lazy()
+            .filter(col("kind").cast(DataType::String).eq(lit("toc")))
+            .collect()?;
+
+        let df: DataFrame = if let Some(predicate) = self.predicate {
+            let mut ctx = SQLContext::new();
+            ctx.register("df", index.lazy());
+            ctx.execute(&select_where(&predicate))?.collect()?
+        } else {
+            index
+        };
+
+        let path = df.column("path")?.str()?;
+        let idn = df.column("idn")?.str()?;
+        let pbar = ProgressBarBuilder::new(PBAR_PARSE, self.quiet)
+            .len(df.height() as u64)
+            .build();
+
+        let tocs = (0..df.height())
+            .into_par_iter()
+            .progress_with(pbar)
+            .map(|idx| -> DatashedResult<DataFrame> {
+                let path = path.get(idx).unwrap();
+                let idn = idn.get(idx).unwrap();
+                let doc = Document::from_path(base_dir.join(path))?;
+                let entries = parse(&doc.as_ref().to_str_lossy());
+
+                let n = entries.len();
+                let mut levels = Vec::with_capacity(n);
+                let mut numberings = Vec::with_capacity(n);
+                let mut titles = Vec::with_capacity(n);
+                let mut pages = Vec::with_capacity(n);
+
+                for entry in entries.into_iter() {
+                    levels.push(entry.level);
+                    numberings.push(entry.numbering);
+                    titles.push(entry.title);
+                    pages.push(entry.page);
+                }
+
+                let mut df = DataFrame::new(vec![
+                    Column::new("path".into(), vec![path; n]),
+                    Column::new("idn".into(), vec![idn; n]),
+                    Column::new(
+                        "entry".into(),
+                        (1..=n as u32).collect::<Vec<_>>(),
+                    ),
+                    Column::new("level".into(), levels),
+                    Column::new("numbering".into(), numberings),
+                    Column::new("title".into(), titles),
+                    Column::new("page".into(), pages),
+                ])?;
+
+                if let Some(ref dest) = self.dest {
+                    create_dir_all(dest)?;
+                    let mut out = AtomicFile::create(
+                        dest.join(filename(idn, path)),
+                    )?;
+                    CsvWriter::new(&mut out).finish(&mut df)?;
+                    out.commit()?;
+                }
+
+                Ok(df)
+            })
+            .collect::<DatashedResult<Vec<_>>>()?;
+
+        if self.verbose {
+            eprintln!(
+                "parsed {} entries of {} TOCs.",
+                tocs.iter().map(DataFrame::height).sum::<usize>(),
+                tocs.len()
+            );
+        }
+
+        if self.dest.is_some() {
+            return Ok(());
+        }
+
+        let mut df = match tocs.is_empty() {
+            true => DataFrame::empty(),
+            false => concat(
+                tocs.into_iter()
+                    .map(IntoLazy::lazy)
+                    .collect::<Vec<_>>(),
+                UnionArgs::default(),
+            )?
+            .collect()?,
+        };
+
+        if let Some(path) = self.output {
+            let mut out = AtomicFile::create(path)?;
+            let mut writer = IpcWriter::new(&mut out)
+                .with_compression(Some(IpcCompression::ZSTD));
+            writer.finish(&mut df)?;
+            out.commit()?;
+        } else {
+            let mut writer = CsvWriter::new(stdout().lock());
+            writer.finish(&mut df)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toc_filename() {
+        assert_eq!(
+            filename("118540238", "data/toc/118540238.txt"),
+            "118540238_118540238.csv"
+        );
+        assert_eq!(
+            filename("118540238", "data/toc/1/118540238_2.txt"),
+            "118540238_118540238_2.csv"
+        );
+    }
+
+    #[test]
+    fn parse_toc() {
+        let entries = parse(
+            "Inhalt\n\n\
+            Vorwort ........ 7\n\
+            1 Einleitung 9\n\
+            1.1 Gegenstand und\n\
+            Methode ..... 11\n\
+            2. Ergebnisse 23\n\
+            Teil II\n\
+            Register ..... XII\n",
+        );
+
+        assert_eq!(entries.len(), 6);
+        assert_eq!(
+            entries[2],
+            Entry {
+                level: 2,
+                numbering: Some("1.1".into()),
+                title: "Gegenstand und Methode".into(),
+                page: Some("11".into()),
+            }
+        );
+        assert_eq!(entries[0].title, "Vorwort");
+        assert_eq!(entries[3].numbering, Some("2".into()));
+        assert_eq!(entries[4].title, "Teil II");
+        assert_eq!(entries[4].page, None);
+        assert_eq!(entries[5].page, Some("XII".into()));
+    }
+}
@@ -0,0 +1,262 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+
+use crate::atomic::AtomicFile;
+use crate::audit;
+use crate::prelude::*;
+use crate::trash::{move_file, Trash};
+
+/// Restore the documents (and the index) removed by `datashed clean`.
+///
+/// By default, the most recent snapshot of the trash is restored.
+/// Documents, which were re-created in the meantime, aren't
+/// overwritten; the snapshot is kept in this case. If the index has
+/// been updated after the snapshot was taken, the command fails unless
+/// `--force` is given. Restored documents are reported as changed by
+/// `datashed status`, until they're indexed again.
+#[derive(Debug, Parser)]
+pub(crate) struct UndoClean {
+    /// Run verbosely. Print additional progress information to the
+    /// standard error stream. This option conflicts with the
+    /// `--quiet` option.
+    #[arg(short, long, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Operate quietly; do not show progress. This option conflicts
+    /// with the `--verbose` option.
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// List the snapshots of the trash (oldest first) and exit.
+    #[arg(short, long, conflicts_with = "snapshot")]
+    list: bool,
+
+    /// Wait for the index lock held by another process to be
+    /// released. By default (`--no-wait`), the command fails
+    /// immediately, if the index is locked.
+    #[arg(long, overrides_with = "no_wait")]
+    wait: bool,
+
+    /// Fail immediately, if the index is locked by another process.
+    #[arg(long, overrides_with = "wait")]
+    no_wait: bool,
+
+    /// Restore the index of the snapshot, even if the index has been
+    /// updated after the snapshot was taken.
+    #[arg(short, long)]
+    force: bool,
+
+    /// The name of the snapshot to restore. If not set, the most
+    /// recent snapshot is restored.
+    snapshot: Option<String>,
+}
+
+impl UndoClean {
+    pub(crate) fn execute(self) -> DatashedResult<()> {
+        let datashed = Datashed::discover()?;
+        let base_dir = datashed.base_dir();
+        let mut snapshots = Trash::list(&datashed)?;
+
+        if self.list {
+            for trash in snapshots.iter() {
+                println!(
+                    "{} ({} file(s))",
+                    trash.name(),
+                    trash.files()?.len()
+                );
+            }
+
+            return Ok(());
+        }
+
+        let trash = match self.snapshot {
+            Some(ref name) => {
+                let Some(pos) = snapshots
+                    .iter()
+                    .position(|trash| trash.name() == *name)
+                else {
+                    bail!("unknown snapshot '{name}'");
+                };
+
+                snapshots.swap_remove(pos)
+            }
+            None => {
+                let Some(trash) = snapshots.pop() else {
+                    bail!("nothing to restore");
+                };
+
+                trash
+            }
+        };
+
+        let _lock = datashed.lock(self.wait && !self.no_wait)?;
+        if !self.force
+            && trash
+                .is_index_outdated(&base_dir.join(Datashed::INDEX))?
+        {
+            bail!(
+                "the index has been updated after snapshot {} was \
+                taken (use --force to restore it anyway)",
+                trash.name()
+            );
+        }
+
+        let (paths, conflicts) = restore(base_dir, &trash)?;
+        let mut restored = 0;
+
+        for path in paths.iter() {
+            if path.as_os_str() == Datashed::INDEX {
+                if self.verbose {
+                    eprintln!("restored index.");
+                }
+
+                continue;
+            }
+
+            restored += 1;
+            if self.verbose {
+                eprintln!("restored '{}'.", path.display());
+            }
+        }
+
+        if !self.quiet {
+            for path in conflicts.iter() {
+                eprintln!(
+                    "warning: skip '{}' (file exists).",
+                    path.display()
+                );
+            }
+        }
+
+        audit::record(&datashed, "undo-clean", restored)?;
+
+        if !self.quiet {
+            eprintln!(
+                "restored {restored} document(s) from snapshot {}.",
+                trash.name()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Moves the files of the snapshot back into the root directory and
+/// returns the restored files and the conflicts (files, which have been
+/// re-created in the meantime). The index is replaced atomically. The
+/// snapshot is removed, unless there are conflicts.
+fn restore(
+    base_dir: &Path,
+    trash: &Trash,
+) -> DatashedResult<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut restored = vec![];
+    let mut conflicts = vec![];
+
+    for path in trash.files()? {
+        let src = trash.dir().join(&path);
+        let dest = base_dir.join(&path);
+
+        if path.as_os_str() == Datashed::INDEX {
+            let mut out = AtomicFile::create(&dest)?;
+            io::copy(&mut File::open(&src)?, &mut out)?;
+            out.commit()?;
+            fs::remove_file(&src)?;
+        } else if dest.exists() {
+            conflicts.push(path);
+            continue;
+        } else {
+            move_file(&src, &dest)?;
+        }
+
+        restored.push(path);
+    }
+
+    if conflicts.is_empty() {
+        fs::remove_dir_all(trash.dir())?;
+    }
+
+    Ok((restored, conflicts))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::testing::temp_datashed;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn undo_clean_restore() -> TestResult {
+        let (_dir, datashed) = temp_datashed()?;
+        let base_dir = datashed.base_dir();
+        let index = base_dir.join(Datashed::INDEX);
+
+        fs::create_dir_all(base_dir.join("data"))?;
+        fs::write(base_dir.join("data/a.txt"), "a")?;
+        fs::write(base_dir.join("data/b.txt"), "b")?;
+        fs::write(&index, "old")?;
+
+        // datashed clean
+        let trash = Trash::create(&datashed)?;
+        trash.put(base_dir, "data/a.txt")?;
+        trash.put(base_dir, "data/b.txt")?;
+        trash.backup(base_dir, Datashed::INDEX)?;
+        fs::write(&index, "new")?;
+        trash.stamp_index(&index)?;
+
+        let snapshots = Trash::list(&datashed)?;
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].name(), trash.name());
+        assert_eq!(
+            snapshots[0].files()?,
+            [
+                PathBuf::from("data/a.txt"),
+                PathBuf::from("data/b.txt"),
+                PathBuf::from(Datashed::INDEX),
+            ]
+        );
+        assert!(!trash.is_index_outdated(&index)?);
+
+        // b.txt is re-created in the meantime.
+        fs::write(base_dir.join("data/b.txt"), "c")?;
+        let (restored, conflicts) = restore(base_dir, &trash)?;
+        assert_eq!(
+            restored,
+            [
+                PathBuf::from("data/a.txt"),
+                PathBuf::from(Datashed::INDEX)
+            ]
+        );
+        assert_eq!(conflicts, [PathBuf::from("data/b.txt")]);
+        assert_eq!(
+            fs::read_to_string(base_dir.join("data/a.txt"))?,
+            "a"
+        );
+        assert_eq!(
+            fs::read_to_string(base_dir.join("data/b.txt"))?,
+            "c"
+        );
+        assert_eq!(fs::read_to_string(&index)?, "old");
+
+        // The snapshot is kept due to the conflict.
+        assert_eq!(trash.files()?, [PathBuf::from("data/b.txt")]);
+
+        sleep(Duration::from_millis(20));
+        fs::write(&index, "newer")?;
+        assert!(trash.is_index_outdated(&index)?);
+
+        fs::remove_file(base_dir.join("data/b.txt"))?;
+        let (restored, conflicts) = restore(base_dir, &trash)?;
+        assert_eq!(restored, [PathBuf::from("data/b.txt")]);
+        assert!(conflicts.is_empty());
+        assert!(Trash::list(&datashed)?.is_empty());
+
+        Ok(())
+    }
+}
@@ -22,7 +22,15 @@ pub(crate) struct User {
 #[derive(Debug, clap::Parser)]
 pub(crate) enum Command {
     /// Add a new user to the datashed.
-    Add { username: String, secret: String },
+    Add {
+        username: String,
+        secret: String,
+
+        /// Assign the role to the user. This option can be specified
+        /// multiple times.
+        #[arg(long = "role", value_name = "role")]
+        roles: Vec<String>,
+    },
 
     /// Remove the user \<username\> from the datashed.
     #[clap(visible_alias = "rm")]
@@ -38,12 +46,18 @@ impl User {
         let mut config = datashed.config()?;
 
         match self.cmd {
-            Command::Add { username, secret } => {
+            Command::Add {
+                username,
+                secret,
+                roles,
+            } => {
                 if config.users.contains_key(&username) {
                     bail!("user '{}' already exist.", username);
                 }
 
-                config.users.insert(username, config::User { secret });
+                config
+                    .users
+                    .insert(username, config::User { secret, roles });
             }
             Command::Remove { username } => {
                 if !config.users.contains_key(&username) {
@@ -57,7 +71,7 @@ impl User {
                     bail!("user '{}' does not exist.", username);
                 };
 
-                *user = config::User { secret };
+                user.secret = secret;
             }
         }
 
@@ -1,3 +1,4 @@
+use std::fs;
 use std::path::Path;
 
 use clap::{Parser, ValueEnum};
@@ -6,6 +7,8 @@ use polars::prelude::DataType;
 use rayon::prelude::*;
 
 use crate::prelude::*;
+use crate::signature::{self, signature_path};
+use crate::stats::{fingerprint, IndexStats};
 
 const PBAR_VERIFY: &str =
     "Verifying documents: {human_pos} ({percent}%) | \
@@ -44,13 +47,104 @@ pub(crate) struct Verify {
         hide_default_value = true
     )]
     mode: VerifyMode,
+
+    /// Verify the signature of the index (see `datashed sign`) against
+    /// the public key of the datashed.
+    #[arg(long)]
+    signature: bool,
+
+    /// Only check the column statistics embedded in the index
+    /// (unexpected nulls and out-of-range values) without a full scan
+    /// of the documents.
+    #[arg(long)]
+    stats: bool,
 }
 
 impl Verify {
+    fn verify_signature(
+        &self,
+        datashed: &Datashed,
+    ) -> DatashedResult<()> {
+        let signing = datashed.config()?.signing.unwrap_or_default();
+        let public_key = match (signing.public_key, signing.key_file) {
+            (Some(public_key), _) => public_key,
+            (None, Some(path)) => {
+                signature::public_key(&signature::read_key(path)?)
+            }
+            (None, None) => {
+                bail!(
+                    "verification failed: no public key (set \
+                        `signing.public-key` or `signing.key-file`)."
+                );
+            }
+        };
+
+        let path = datashed.base_dir().join(Datashed::INDEX);
+        let sig_path = signature_path(&path);
+        if !sig_path.is_file() {
+            bail!("verification failed: index isn't signed.");
+        }
+
+        signature::verify(
+            &public_key,
+            &fs::read(&path)?,
+            &fs::read_to_string(&sig_path)?,
+        )?;
+
+        if self.verbose {
+            eprintln!("index signature is valid.");
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn execute(self) -> DatashedResult<()> {
         let datashed = Datashed::discover()?;
+        if self.signature {
+            self.verify_signature(&datashed)?;
+        }
+
+        let stats = IndexStats::from_path(
+            datashed.base_dir().join(Datashed::INDEX),
+        )?;
+
+        match stats {
+            Some(ref stats) => {
+                if let Some(problem) = stats.check().into_iter().next()
+                {
+                    bail!("verification failed: {problem}.");
+                }
+            }
+            None if self.stats => {
+                bail!(
+                    "verification failed: index doesn't contain column \
+                        statistics (re-run `datashed index`)."
+                );
+            }
+            None => (),
+        }
+
+        if self.stats {
+            if self.verbose {
+                eprintln!("column statistics are valid.");
+            }
+
+            return Ok(());
+        }
+
         let index = datashed.index()?;
 
+        if let Some(ref stats) = stats {
+            if self.mode >= VerifyMode::Strict
+                && stats.fingerprint != fingerprint(&index)?
+            {
+                bail!(
+                    "verification failed: fingerprint mismatch (the \
+                        index was modified by another tool)."
+                );
+            }
+        }
+
         let path = index.column("path")?.str()?;
         let hash = index.column("hash")?.str()?;
 
@@ -1,18 +1,31 @@
 use std::ffi::OsStr;
-use std::fs::{read_to_string, File};
-use std::io::stdout;
-use std::path::PathBuf;
+use std::fs::{self, read_to_string, File};
+use std::hash::{BuildHasher, RandomState};
+use std::io::{stdout, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Mutex;
 
 use bstr::ByteSlice;
 use clap::{Parser, ValueEnum};
+use hashbrown::hash_map::Entry;
 use hashbrown::{HashMap, HashSet};
-use indicatif::ParallelProgressIterator;
 use polars::prelude::*;
 use polars::sql::SQLContext;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rayon::iter::ParallelIterator;
+use serde_json::json;
 use unicode_categories::UnicodeCategories;
 
+use crate::checkpoint::{Checkpoint, Partial};
+use crate::collate::{Collation, Locale, Strength};
+use crate::prefetch::documents;
 use crate::prelude::*;
+use crate::preprocess::Preprocess;
+use crate::schedule::Schedule;
+use crate::sketch::{CountMinSketch, HyperLogLog};
+use crate::sql::select_where;
+use crate::stem::{Normalizer, Stem};
+use crate::utils::fold;
 
 const PBAR_PROCESS: &str =
     "Processing documents: {human_pos} ({percent}%) | \
@@ -34,6 +47,47 @@ enum UnicodeCategory {
     Other,
 }
 
+/// The output format of the vocabulary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum VocabFormat {
+    /// The token table (token, tf, df) in CSV format.
+    Csv,
+
+    /// The token table (token, tf, df) in Arrow IPC format.
+    Ipc,
+
+    /// A hunspell dictionary (`.dic`) without affix flags; the first
+    /// line contains the number of words. N-grams are skipped.
+    Hunspell,
+
+    /// A fastText-compatible vocabulary (`{token} {tf}`, one token per
+    /// line, ordered by frequency).
+    Fasttext,
+
+    /// The JSON variant of the wordfreq `cBpack` format: a header
+    /// followed by lists of tokens, where the list at index `i`
+    /// contains the tokens with a frequency of `-i` centibels.
+    Wordfreq,
+}
+
+/// The casing policy of the exported tokens.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Casing {
+    /// Tokens are written as they are.
+    #[default]
+    Keep,
+
+    /// Tokens are converted to lowercase.
+    Lower,
+
+    /// Tokens are converted to lowercase and diacritics are removed.
+    Fold,
+
+    /// Tokens, which differ only in case, are merged and written in
+    /// their most frequent form.
+    Dominant,
+}
+
 /// Create an index of all available documents.
 #[derive(Debug, Default, Parser)]
 pub(crate) struct Vocab {
@@ -69,7 +123,32 @@ pub(crate) struct Vocab {
     #[arg(long)]
     stopwords: Option<PathBuf>,
 
-    /// Ignore tokens with a length less than `n`.
+    /// The name of a preprocessing profile of the datashed config. If
+    /// not set, the default preprocessing (NFC, lowercase) is used.
+    #[arg(long, value_name = "profile")]
+    preprocess: Option<String>,
+
+    /// Reduce tokens to their stem. The vocabulary is computed over
+    /// the stems (see `--forms`).
+    #[arg(long, value_enum, default_value_t = Stem::None)]
+    stem: Stem,
+
+    /// A command of an external lemmatizer (run via `sh -c`), which
+    /// reads tokens from stdin and writes the lemma of each token to
+    /// stdout (one per line). The vocabulary is computed over the
+    /// lemmas (see `--forms`). If `--stem` is given too, the lemmas
+    /// are stemmed.
+    #[arg(long, value_name = "command")]
+    lemmatizer: Option<String>,
+
+    /// Write the observed surface forms of each stem or lemma to the
+    /// `forms` column. The surface forms are collected in memory,
+    /// regardless of `--max-memory` and `--shards`.
+    #[arg(long)]
+    forms: bool,
+
+    /// Ignore tokens with a length less than `n`. The minimum token
+    /// length of a preprocessing profile takes precedence.
     #[arg(
         long = "min-tl",
         short = 'l',
@@ -86,6 +165,50 @@ pub(crate) struct Vocab {
     #[arg(long = "min-df", default_value = "1", value_name = "n")]
     min_doc_freq: u64,
 
+    /// Read documents sequentially in on-disk order with a read-ahead
+    /// of `n` documents instead of reading them in parallel. This
+    /// reduces random I/O on spinning disks.
+    #[arg(long, value_name = "n")]
+    prefetch: Option<usize>,
+
+    /// Accumulate the vocabulary in `n` hash-partitioned shards, which
+    /// are shared among all threads, instead of building a vocabulary
+    /// per thread. This reduces the peak memory usage for large
+    /// vocabularies. If a memory limit (`runtime.max_memory`) is set,
+    /// shards exceeding their share of the limit are spilled to disk
+    /// and merged shard by shard.
+    #[arg(long, value_name = "n")]
+    shards: Option<usize>,
+
+    /// Estimate the token and document frequencies with a count-min
+    /// sketch in a first pass over the documents and count only those
+    /// tokens exactly, whose estimated frequencies reach the
+    /// thresholds `--min-tf` and `--min-df`. The memory usage is
+    /// bounded by the sketch and the number of frequent tokens. In
+    /// verbose mode, the (estimated) number of types is printed.
+    #[arg(long)]
+    approximate: bool,
+
+    /// The number of counters per row of the count-min sketch.
+    #[arg(
+        long,
+        default_value = "1048576",
+        requires = "approximate",
+        value_name = "n"
+    )]
+    sketch_width: usize,
+
+    /// The scheduling strategy of the parallel document processing:
+    /// uniform (default), size (largest documents first) or dynamic.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Schedule::default(),
+        conflicts_with = "prefetch",
+        value_name = "strategy"
+    )]
+    schedule: Schedule,
+
     /// Ignore documents which are *not* explicitly listed in the given
     /// allow-lists.
     #[arg(long = "allow-list", short = 'A')]
@@ -96,6 +219,34 @@ pub(crate) struct Vocab {
     #[arg(long = "deny-list", short = 'D')]
     deny_list: Option<PathBuf>,
 
+    /// Sort the vocabulary by token (in collation order) instead of
+    /// by frequency.
+    #[arg(long)]
+    alphabetical: bool,
+
+    /// The locale used to sort the tokens. Defaults to the locale of
+    /// the `[collation]` config section or `en`.
+    #[arg(long, value_enum, value_name = "locale")]
+    locale: Option<Locale>,
+
+    /// The strength of the collation (primary = base letters,
+    /// secondary = diacritics, tertiary = case). Defaults to the
+    /// strength of the `[collation]` config section or `tertiary`.
+    #[arg(long, value_enum, value_name = "strength")]
+    strength: Option<Strength>,
+
+    /// The output format. By default, the vocabulary is written as IPC
+    /// file if `--output` is given and as CSV otherwise.
+    #[arg(long, value_enum, value_name = "format")]
+    format: Option<VocabFormat>,
+
+    /// The casing policy, which is applied before the vocabulary is
+    /// written. Tokens, which differ only in case (or diacritics),
+    /// are merged; their document frequencies are summed up, which
+    /// is an upper bound of the true document frequency.
+    #[arg(long, value_enum, default_value_t = Casing::Keep)]
+    case: Casing,
+
     /// If set, the index will be written in CSV format to the standard
     /// output (stdout).
     #[arg(long, conflicts_with = "output")]
@@ -108,10 +259,396 @@ pub(crate) struct Vocab {
 
     #[arg(long = "where")]
     predicate: Option<String>,
+
+    /// Resume an interrupted run from its last checkpoint. Documents,
+    /// which were already processed, are skipped. The checkpoint must
+    /// have been created with the same options, which affect the
+    /// counted tokens. Resuming isn't supported in combination with
+    /// `--approximate`, `--stem` or `--lemmatizer`.
+    #[arg(long, conflicts_with = "approximate")]
+    resume: bool,
+
+    /// Write a checkpoint (into the temp directory) after every `n`
+    /// processed documents. The partial vocabulary of a checkpoint is
+    /// merged before it is written. A value of zero disables
+    /// checkpoints.
+    #[arg(long, default_value = "1000", value_name = "n")]
+    checkpoint_every: usize,
 }
 
 type VocabMap = HashMap<String, (u64, u64)>;
 
+/// Merges two vocabularies.
+fn merge(mut acc: VocabMap, rhs: VocabMap) -> VocabMap {
+    merge_into(&mut acc, rhs);
+    acc
+}
+
+/// Merges `rhs` into `acc` and returns the (estimated) number of
+/// additionally allocated bytes.
+fn merge_into(acc: &mut VocabMap, rhs: VocabMap) -> u64 {
+    let overhead =
+        (size_of::<String>() + size_of::<(u64, u64)>() + 8) as u64;
+    let mut size = 0;
+
+    for (token, count) in rhs.into_iter() {
+        match acc.entry(token) {
+            Entry::Occupied(mut entry) => {
+                let (tf, df) = entry.get_mut();
+                *tf += count.0;
+                *df += count.1;
+            }
+            Entry::Vacant(entry) => {
+                size += entry.key().capacity() as u64 + overhead;
+                entry.insert(count);
+            }
+        }
+    }
+
+    size
+}
+
+/// Converts a vocabulary into a data frame.
+fn to_df(vocab: VocabMap) -> DatashedResult<DataFrame> {
+    let mut tokens = Vec::with_capacity(vocab.len());
+    let mut freqs = Vec::with_capacity(vocab.len());
+    let mut docs = Vec::with_capacity(vocab.len());
+
+    for (token, (tf, df)) in vocab.into_iter() {
+        tokens.push(token);
+        freqs.push(tf);
+        docs.push(df);
+    }
+
+    Ok(DataFrame::new(vec![
+        Column::new("token".into(), tokens),
+        Column::new("tf".into(), freqs),
+        Column::new("df".into(), docs),
+    ])?)
+}
+
+/// Converts a data frame into a vocabulary.
+fn from_df(df: &DataFrame) -> DatashedResult<VocabMap> {
+    let tokens = df.column("token")?.str()?;
+    let tf = df.column("tf")?.u64()?;
+    let df = df.column("df")?.u64()?;
+
+    Ok(tokens
+        .into_no_null_iter()
+        .zip(tf.into_no_null_iter())
+        .zip(df.into_no_null_iter())
+        .map(|((token, tf), df)| (token.to_string(), (tf, df)))
+        .collect())
+}
+
+impl Partial for VocabMap {
+    const EXTENSION: &'static str = "ipc";
+
+    fn write_batch<W: Write>(
+        items: Vec<Self>,
+        out: W,
+    ) -> DatashedResult<()> {
+        let vocab = items.into_iter().fold(VocabMap::new(), merge);
+        IpcWriter::new(out).finish(&mut to_df(vocab)?)?;
+        Ok(())
+    }
+
+    fn read_batch(path: &Path) -> DatashedResult<Vec<Self>> {
+        let df = IpcReader::new(File::open(path)?).finish()?;
+        Ok(vec![from_df(&df)?])
+    }
+}
+
+/// Splits the paths into batches, such that the total size of the
+/// documents of a batch doesn't exceed `max_size` bytes (a batch
+/// consists of at least one document).
+fn batches(paths: Vec<PathBuf>, max_size: u64) -> Vec<Vec<PathBuf>> {
+    let mut batches = vec![];
+    let mut batch = vec![];
+    let mut total = 0;
+
+    for path in paths.into_iter() {
+        let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if !batch.is_empty() && total + size > max_size {
+            batches.push(std::mem::take(&mut batch));
+            total = 0;
+        }
+
+        total += size;
+        batch.push(path);
+    }
+
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+
+    batches
+}
+
+/// Intermediate vocabularies, which were spilled to disk. The spill
+/// files are removed, when the value is dropped.
+struct Spill {
+    dir: PathBuf,
+    shard: usize,
+    files: Vec<PathBuf>,
+}
+
+impl Spill {
+    fn new(dir: PathBuf, shard: usize) -> Self {
+        Self {
+            dir,
+            shard,
+            files: vec![],
+        }
+    }
+
+    /// Writes an intermediate vocabulary to disk.
+    fn write(&mut self, mut df: DataFrame) -> DatashedResult<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!(
+            "vocab-{}-{}-{}.ipc",
+            process::id(),
+            self.shard,
+            self.files.len()
+        ));
+
+        IpcWriter::new(File::create(&path)?).finish(&mut df)?;
+        self.files.push(path);
+        Ok(())
+    }
+
+    /// Merges the spilled vocabularies with the given one.
+    fn merge(&self, df: DataFrame) -> DatashedResult<LazyFrame> {
+        if self.files.is_empty() {
+            return Ok(df.lazy());
+        }
+
+        let mut frames = vec![df.lazy()];
+        for path in self.files.iter() {
+            frames.push(LazyFrame::scan_ipc(path, Default::default())?);
+        }
+
+        Ok(concat(frames, UnionArgs::default())?
+            .group_by([col("token")])
+            .agg([col("tf").sum(), col("df").sum()]))
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        for path in self.files.iter() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// A vocabulary, which is partitioned into shards by the hash of the
+/// tokens. Each shard is guarded by its own lock and is spilled to
+/// disk, if it exceeds its share of the memory limit.
+struct Shards {
+    shards: Vec<Mutex<(VocabMap, u64, Spill)>>,
+    hasher: RandomState,
+    max_size: Option<u64>,
+}
+
+impl Shards {
+    fn new(n: usize, dir: PathBuf, max_memory: Option<u64>) -> Self {
+        let n = n.max(1);
+        Self {
+            shards: (0..n)
+                .map(|i| {
+                    Mutex::new((
+                        VocabMap::new(),
+                        0,
+                        Spill::new(dir.clone(), i),
+                    ))
+                })
+                .collect(),
+            hasher: RandomState::new(),
+            max_size: max_memory.map(|limit| limit / (2 * n as u64)),
+        }
+    }
+
+    /// Merges a vocabulary into the shards.
+    fn insert(&self, vocab: VocabMap) -> DatashedResult<()> {
+        let n = self.shards.len();
+        let mut parts = vec![VocabMap::new(); n];
+        for (token, count) in vocab.into_iter() {
+            let idx =
+                (self.hasher.hash_one(&token) % n as u64) as usize;
+            parts[idx].insert(token, count);
+        }
+
+        for (shard, part) in self.shards.iter().zip(parts.into_iter()) {
+            if part.is_empty() {
+                continue;
+            }
+
+            let mut guard = shard.lock().unwrap();
+            let (ref mut map, ref mut size, ref mut spill) = *guard;
+            *size += merge_into(map, part);
+
+            if self.max_size.is_some_and(|max_size| *size > max_size) {
+                spill.write(to_df(std::mem::take(map))?)?;
+                *size = 0;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Merges each shard with its spilled parts and returns the
+    /// concatenation of all shards.
+    fn finish(self, streaming: bool) -> DatashedResult<LazyFrame> {
+        let mut frames = vec![];
+        for shard in self.shards.into_iter() {
+            let (map, _, spill) = shard.into_inner().unwrap();
+            let df = spill
+                .merge(to_df(map)?)?
+                .with_streaming(streaming)
+                .collect()?;
+            frames.push(df.lazy());
+        }
+
+        Ok(concat(frames, UnionArgs::default())?)
+    }
+}
+
+/// Counts the n-grams of a document.
+fn ngrams(words: &[String], size: usize) -> VocabMap {
+    words
+        .windows(size)
+        .fold(VocabMap::new(), |mut vocab, tokens| {
+            let token = tokens.join(" ");
+            vocab
+                .entry(token)
+                .and_modify(|(tf, _)| *tf += 1)
+                .or_insert((1, 1));
+            vocab
+        })
+}
+
+/// Merges tokens according to the casing policy.
+fn apply_casing(
+    df: DataFrame,
+    case: Casing,
+) -> DatashedResult<DataFrame> {
+    let map: fn(&str) -> String = match case {
+        Casing::Keep => return Ok(df),
+        Casing::Lower | Casing::Dominant => str::to_lowercase,
+        Casing::Fold => |s| fold(s).0,
+    };
+
+    let keys: StringChunked = df
+        .column("token")?
+        .str()?
+        .iter()
+        .map(|token| token.map(map))
+        .collect();
+
+    let token = if case == Casing::Dominant {
+        col("token")
+            .sort_by(
+                [col("tf")],
+                SortMultipleOptions::default()
+                    .with_order_descending(true),
+            )
+            .first()
+    } else {
+        col("key").first()
+    };
+
+    let mut aggs =
+        vec![token.alias("token"), col("tf").sum(), col("df").sum()];
+    if df.column("forms").is_ok() {
+        aggs.push(col("forms").str().join("|", true));
+    }
+
+    Ok(df
+        .lazy()
+        .with_column(lit(keys.into_series()).alias("key"))
+        .group_by([col("key")])
+        .agg(aggs)
+        .drop(["key"])
+        .collect()?)
+}
+
+/// Writes the vocabulary as hunspell dictionary.
+fn write_hunspell<W: Write>(
+    df: &DataFrame,
+    mut out: W,
+) -> DatashedResult<()> {
+    let words: Vec<&str> = df
+        .column("token")?
+        .str()?
+        .into_no_null_iter()
+        .filter(|token| !token.contains(char::is_whitespace))
+        .collect();
+
+    writeln!(out, "{}", words.len())?;
+    for word in words {
+        writeln!(out, "{word}")?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes the vocabulary as fastText-compatible vocabulary.
+fn write_fasttext<W: Write>(
+    df: &DataFrame,
+    mut out: W,
+) -> DatashedResult<()> {
+    let token = df.column("token")?.str()?;
+    let tf = df.column("tf")?.u64()?;
+
+    for (token, tf) in
+        token.into_no_null_iter().zip(tf.into_no_null_iter())
+    {
+        // fastText splits on whitespace, so n-grams are joined by `_`.
+        writeln!(
+            out,
+            "{} {tf}",
+            token.replace(char::is_whitespace, "_")
+        )?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes the vocabulary in the JSON variant of the wordfreq `cBpack`
+/// format.
+fn write_wordfreq<W: Write>(
+    df: &DataFrame,
+    mut out: W,
+) -> DatashedResult<()> {
+    let token = df.column("token")?.str()?;
+    let tf = df.column("tf")?.u64()?;
+    let total = tf.sum().unwrap_or_default().max(1) as f64;
+
+    let mut bins: Vec<Vec<&str>> = vec![];
+    for (token, tf) in
+        token.into_no_null_iter().zip(tf.into_no_null_iter())
+    {
+        let cb =
+            (-100.0 * (tf as f64 / total).log10()).round() as usize;
+        if bins.len() <= cb {
+            bins.resize(cb + 1, vec![]);
+        }
+
+        bins[cb].push(token);
+    }
+
+    let mut value = vec![json!({"format": "cB", "version": 1})];
+    value.extend(bins.into_iter().map(|bin| json!(bin)));
+    serde_json::to_writer(&mut out, &value)
+        .map_err(DatashedError::other)?;
+    writeln!(out)?;
+    out.flush()?;
+    Ok(())
+}
+
 fn read_filter_list(path: PathBuf) -> DatashedResult<DataFrame> {
     Ok(match path.extension().and_then(OsStr::to_str) {
         Some("ipc" | "arrow") => IpcReader::new(File::open(path)?)
@@ -130,13 +667,35 @@ impl Vocab {
         let datashed = Datashed::discover()?;
         let base_dir = datashed.base_dir();
         let index = datashed.index()?;
+        let config = datashed.config()?;
+        let preprocess = Preprocess::from_config(
+            &config,
+            self.preprocess.as_deref(),
+        )?
+        .with_min_token_len(self.min_token_len);
+
+        // The options, which affect the counted tokens, must match
+        // those of a resumed checkpoint.
+        let options = format!(
+            "{:?}",
+            (
+                self.bigrams,
+                self.trigrams,
+                &self.categories,
+                &self.stopwords,
+                &self.preprocess,
+                self.min_token_len,
+                &self.predicate,
+                &self.allow_list,
+                &self.deny_list,
+            )
+        );
 
         let mut df: DataFrame = if let Some(predicate) = self.predicate
         {
             let mut ctx = SQLContext::new();
             ctx.register("df", index.lazy());
-            ctx.execute(&format!("SELECT * FROM df WHERE {predicate}"))?
-                .collect()?
+            ctx.execute(&select_where(&predicate))?.collect()?
         } else {
             index
         };
@@ -167,8 +726,7 @@ impl Vocab {
             if let Some(path) = self.stopwords {
                 read_to_string(path)?
                     .lines()
-                    .filter(|word| word.len() >= self.min_token_len)
-                    .map(String::from)
+                    .map(|word| preprocess.normalize(word))
                     .collect()
             } else {
                 HashSet::new()
@@ -184,10 +742,6 @@ impl Vocab {
 
         let path = df.column("path")?.str()?;
 
-        let pbar = ProgressBarBuilder::new(PBAR_PROCESS, self.quiet)
-            .len(df.height() as u64)
-            .build();
-
         let predicates: Vec<fn(char) -> bool> = self
             .categories
             .iter()
@@ -204,94 +758,363 @@ impl Vocab {
             })
             .collect();
 
-        let mut vocab = (0..df.height())
-            .into_par_iter()
-            .progress_with(pbar)
-            .map(|idx| -> VocabMap {
-                let path = path.get(idx).unwrap();
-                let doc =
-                    Document::from_path(base_dir.join(path)).unwrap();
-
-                let words: Vec<String> = doc
-                    .as_ref()
-                    .words()
-                    .filter(|word| {
-                        word.chars().count() >= self.min_token_len
-                    })
-                    .filter(|word| {
-                        if self.categories.is_empty() {
-                            return true;
+        let normalizer =
+            Normalizer::new(self.stem, self.lemmatizer.as_deref())?;
+        if self.forms && normalizer.is_identity() {
+            bail!("--forms requires either --stem or --lemmatizer");
+        }
+
+        let forms: Mutex<HashMap<String, HashSet<String>>> =
+            Mutex::new(HashMap::new());
+
+        // The surface forms and the sketch aren't part of a
+        // checkpoint, so the checkpoints are only written, if they can
+        // be resumed.
+        let resumable = !self.approximate && normalizer.is_identity();
+        if self.resume && !resumable {
+            bail!("--resume requires neither --stem nor --lemmatizer");
+        }
+
+        let (checkpoint, restored) = Checkpoint::<VocabMap>::open(
+            datashed.checkpoints_dir().join("vocab"),
+            &options,
+            self.resume,
+            if resumable { self.checkpoint_every } else { 0 },
+        )?;
+
+        if self.verbose && !restored.processed.is_empty() {
+            eprintln!(
+                "resuming after {} processed document(s)",
+                restored.processed.len()
+            );
+        }
+
+        let paths: Vec<PathBuf> = path
+            .into_no_null_iter()
+            .map(|path| base_dir.join(path))
+            .filter(|path| !restored.processed.contains(path))
+            .collect();
+
+        let pbar = ProgressBarBuilder::new(PBAR_PROCESS, self.quiet)
+            .len(paths.len() as u64 * (1 + self.approximate as u64))
+            .build();
+
+        // If a memory limit is set, the documents are processed in
+        // batches (by size) and intermediate vocabularies exceeding
+        // the limit are spilled to disk.
+        let max_memory = config.max_memory()?;
+        let batches = match max_memory {
+            Some(limit) => batches(paths, limit / 4),
+            None => vec![paths],
+        };
+
+        let shards = Shards::new(
+            self.shards.unwrap_or(1),
+            datashed.temp_dir(),
+            max_memory,
+        );
+
+        let count = |(_, doc): (usize, DatashedResult<Document>)|
+         -> DatashedResult<VocabMap> {
+                pbar.inc(1);
+                let doc = doc?;
+
+                let surface = preprocess.tokens_filtered(
+                    &doc.as_ref().to_str_lossy(),
+                    |word| {
+                        if !self.categories.is_empty()
+                            && !predicates
+                                .iter()
+                                .any(|f| word.chars().any(f))
+                        {
+                            return false;
                         }
 
-                        predicates.iter().any(|f| word.chars().any(f))
-                    })
-                    .filter(|word| {
                         stopwords.is_empty()
-                            || !stopwords.contains(&word.to_lowercase())
-                    })
-                    .map(str::to_lowercase)
-                    .collect();
-
-                words.windows(size).fold(
-                    VocabMap::new(),
-                    |mut vocab, tokens| {
-                        let token = tokens.join(" ");
-                        vocab
-                            .entry(token)
-                            .and_modify(|(tf, _)| *tf += 1)
-                            .or_insert((1, 1));
-                        vocab
+                            || !stopwords
+                                .contains(&preprocess.normalize(word))
                     },
-                )
-            })
-            .reduce(VocabMap::new, |mut acc, rhs| {
-                for (token, count) in rhs.into_iter() {
-                    acc.entry(token)
-                        .and_modify(|(tf, df)| {
-                            *tf += count.0;
-                            *df += count.1;
-                        })
-                        .or_insert(count);
+                );
+
+                if normalizer.is_identity() {
+                    return Ok(ngrams(&surface, size));
+                }
+
+                let words: Vec<String> = surface
+                    .iter()
+                    .map(|word| normalizer.normalize(word))
+                    .collect::<DatashedResult<_>>()?;
+
+                if !self.forms {
+                    return Ok(ngrams(&words, size));
                 }
 
-                acc
-            });
+                let mut local: HashMap<String, HashSet<String>> =
+                    HashMap::new();
+                for (normalized, surface) in
+                    words.windows(size).zip(surface.windows(size))
+                {
+                    local
+                        .entry(normalized.join(" "))
+                        .or_default()
+                        .insert(surface.join(" "));
+                }
 
-        if self.min_token_freq > 1 || self.min_doc_freq > 1 {
-            vocab.retain(|_, (tf, df)| {
-                *tf >= self.min_token_freq && *df >= self.min_doc_freq
-            });
+                let mut forms = forms.lock().unwrap();
+                for (token, surface) in local.into_iter() {
+                    forms.entry(token).or_default().extend(surface);
+                }
+
+                Ok(ngrams(&words, size))
+            };
+
+        let sketch = if self.approximate {
+            let tf = CountMinSketch::new(self.sketch_width, 4);
+            let df = CountMinSketch::new(self.sketch_width, 4);
+            let types = HyperLogLog::new(14);
+
+            for batch in batches.iter() {
+                documents(batch.clone(), self.prefetch, self.schedule)
+                    .map(&count)
+                    .try_for_each(|vocab| -> DatashedResult<()> {
+                        for (token, (n, _)) in vocab?.iter() {
+                            tf.add(token, *n);
+                            df.add(token, 1);
+                            types.add(token);
+                        }
+
+                        Ok(())
+                    })?;
+            }
+
+            if self.verbose {
+                eprintln!(
+                    "estimated number of types: {}",
+                    types.estimate()
+                );
+            }
+
+            Some((tf, df))
+        } else {
+            None
+        };
+
+        // Tokens whose estimated frequencies are below the thresholds
+        // can't reach them, since a count-min sketch never
+        // underestimates.
+        let filter = |mut vocab: VocabMap| -> VocabMap {
+            if let Some((ref tf, ref df)) = sketch {
+                vocab.retain(|token, _| {
+                    tf.estimate(token) >= self.min_token_freq
+                        && df.estimate(token) >= self.min_doc_freq
+                });
+            }
+
+            vocab
+        };
+
+        for vocab in restored.items.into_iter() {
+            shards.insert(filter(vocab))?;
         }
 
-        let mut tokens = Vec::with_capacity(vocab.len());
-        let mut freqs = Vec::with_capacity(vocab.len());
-        let mut docs = Vec::with_capacity(vocab.len());
+        for batch in batches {
+            let docs =
+                documents(batch.clone(), self.prefetch, self.schedule)
+                    .map(|(idx, doc)| -> DatashedResult<VocabMap> {
+                        let vocab = filter(count((idx, doc))?);
+                        checkpoint
+                            .record(&batch[idx], vocab.clone())?;
+                        Ok(vocab)
+                    });
 
-        for (token, (tf, df)) in vocab.into_iter() {
-            tokens.push(token);
-            freqs.push(tf);
-            docs.push(df);
+            if self.shards.is_some() {
+                docs.try_for_each(|vocab| shards.insert(vocab?))?;
+            } else {
+                shards.insert(
+                    docs.try_reduce(VocabMap::new, |acc, rhs| {
+                        Ok(merge(acc, rhs))
+                    })?,
+                )?;
+            }
         }
 
-        let sort_options = SortMultipleOptions::default()
-            .with_order_descending_multi([true, true, false]);
+        pbar.finish_using_style();
+
+        let mut df = shards
+            .finish(max_memory.is_some())?
+            .filter(
+                col("tf")
+                    .gt_eq(lit(self.min_token_freq))
+                    .and(col("df").gt_eq(lit(self.min_doc_freq))),
+            )
+            .with_streaming(max_memory.is_some())
+            .collect()?;
+
+        let collation =
+            Collation::from_config(&config, self.locale, self.strength);
 
-        let mut df = DataFrame::new(vec![
-            Column::new("token".into(), tokens),
-            Column::new("tf".into(), freqs),
-            Column::new("df".into(), docs),
-        ])?
-        .sort(["tf", "df", "token"], sort_options)?;
+        if self.forms {
+            let forms = forms.into_inner().unwrap();
+            let mut tokens = Vec::with_capacity(forms.len());
+            let mut surface = Vec::with_capacity(forms.len());
+            for (token, forms) in forms.into_iter() {
+                let mut forms: Vec<String> =
+                    forms.into_iter().collect();
+                collation.sort(&mut forms);
+                tokens.push(token);
+                surface.push(forms.join("|"));
+            }
 
-        if let Some(path) = self.output {
-            let mut writer = IpcWriter::new(File::create(path)?)
-                .with_compression(Some(IpcCompression::ZSTD));
-            writer.finish(&mut df)?;
+            let forms = DataFrame::new(vec![
+                Column::new("token".into(), tokens),
+                Column::new("forms".into(), surface),
+            ])?;
+
+            df = df
+                .lazy()
+                .join(
+                    forms.lazy(),
+                    [col("token")],
+                    [col("token")],
+                    JoinArgs::new(JoinType::Left),
+                )
+                .collect()?;
+        }
+
+        let df = apply_casing(df, self.case)?;
+
+        // Tokens are ordered by the rank of the token in collation
+        // order, since polars sorts strings bytewise.
+        let rank = collation.rank(df.column("token")?.str()?);
+        let (exprs, descending) = if self.alphabetical {
+            (vec![col("rank")], vec![false])
         } else {
-            let mut writer = CsvWriter::new(stdout().lock());
-            writer.finish(&mut df)?;
+            (
+                vec![col("tf"), col("df"), col("rank")],
+                vec![true, true, false],
+            )
+        };
+
+        let mut df = df
+            .lazy()
+            .with_column(lit(Series::from(rank)).alias("rank"))
+            .sort_by_exprs(
+                exprs,
+                SortMultipleOptions::default()
+                    .with_order_descending_multi(descending),
+            )
+            .drop(["rank"])
+            .collect()?;
+
+        let format = match self.format {
+            Some(format) => format,
+            None if self.output.is_some() => VocabFormat::Ipc,
+            None => VocabFormat::Csv,
+        };
+
+        let out: Box<dyn Write> = match self.output {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(stdout().lock()),
+        };
+
+        match format {
+            VocabFormat::Csv => {
+                CsvWriter::new(out).finish(&mut df)?;
+            }
+            VocabFormat::Ipc => {
+                IpcWriter::new(out)
+                    .with_compression(Some(IpcCompression::ZSTD))
+                    .finish(&mut df)?;
+            }
+            VocabFormat::Hunspell => write_hunspell(&df, out)?,
+            VocabFormat::Fasttext => write_fasttext(&df, out)?,
+            VocabFormat::Wordfreq => write_wordfreq(&df, out)?,
+        }
+
+        checkpoint.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn export_formats() -> TestResult {
+        let df = df!(
+            "token" => ["Haus", "haus", "häuser", "das haus"],
+            "tf" => [2u64, 6, 1, 1],
+            "df" => [1u64, 2, 1, 1],
+        )?;
+
+        let dominant = apply_casing(df.clone(), Casing::Dominant)?
+            .sort(["token"], Default::default())?;
+        assert_eq!(dominant.height(), 3);
+        assert_eq!(
+            dominant.column("token")?.str()?.get(1),
+            Some("haus")
+        );
+        assert_eq!(dominant.column("tf")?.u64()?.get(1), Some(8));
+
+        let folded = apply_casing(df.clone(), Casing::Fold)?;
+        assert_eq!(folded.height(), 3);
+
+        let mut out = vec![];
+        write_hunspell(&df, &mut out)?;
+        assert_eq!(String::from_utf8(out)?, "3\nHaus\nhaus\nhäuser\n");
+
+        let mut out = vec![];
+        write_fasttext(&df, &mut out)?;
+        assert!(String::from_utf8(out)?.ends_with("das_haus 1\n"));
+
+        let mut out = vec![];
+        write_wordfreq(&df, &mut out)?;
+        let value: serde_json::Value = serde_json::from_slice(&out)?;
+        assert_eq!(value[0]["format"], "cB");
+        assert_eq!(value[22][0], "haus");
+        Ok(())
+    }
+
+    #[test]
+    fn vocab_shards() -> TestResult {
+        let dir = crate::testing::temp_dir()?;
+        let vocabs = || {
+            [
+                VocabMap::from_iter([
+                    ("a".to_string(), (1, 1)),
+                    ("b".to_string(), (2, 1)),
+                ]),
+                VocabMap::from_iter([
+                    ("a".to_string(), (3, 1)),
+                    ("c".to_string(), (1, 1)),
+                ]),
+            ]
+        };
+
+        // Without a memory limit nothing is spilled; with a tiny limit
+        // each insert spills the affected shards.
+        for max_memory in [None, Some(1)] {
+            let shards = Shards::new(4, dir.path().into(), max_memory);
+            for vocab in vocabs() {
+                shards.insert(vocab)?;
+            }
+
+            let df = shards
+                .finish(false)?
+                .sort(["token"], Default::default())
+                .collect()?;
+
+            let [lhs, rhs] = vocabs();
+            assert_eq!(from_df(&df)?, merge(lhs, rhs));
+            assert_eq!(df.column("tf")?.u64()?.get(0), Some(4));
+            assert_eq!(df.column("df")?.u64()?.get(0), Some(2));
+            assert_eq!(df.height(), 3);
         }
 
+        // the spill files are removed
+        assert_eq!(fs::read_dir(dir.path())?.count(), 0);
         Ok(())
     }
 }
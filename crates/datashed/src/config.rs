@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{self, File};
 use std::io::Write;
 use std::net::IpAddr;
@@ -7,8 +7,17 @@ use std::path::{Path, PathBuf};
 use semver::Version;
 use serde::{Deserialize, Serialize};
 
+use crate::campaign::Campaign;
+use crate::collate::Collation;
+use crate::discovery::Discovery;
 use crate::document::DocumentKind;
-use crate::error::DatashedResult;
+use crate::error::{bail, DatashedResult};
+use crate::http::HttpConfig;
+use crate::metrics::Metrics;
+use crate::preprocess::Preprocess;
+use crate::ratings::RatingScale;
+use crate::redact::ColumnPolicy;
+use crate::schema::Schema;
 
 /// Datashed config.
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -23,17 +32,82 @@ pub(crate) struct Config {
     /// Runtime options.
     pub(crate) runtime: Option<Runtime>,
 
+    /// Document discovery rules (suffixes, include and exclude
+    /// patterns).
+    #[serde(skip_serializing_if = "Discovery::is_default", default)]
+    pub(crate) discovery: Discovery,
+
     /// Server options.
     pub(crate) server: Option<Server>,
 
+    /// HTTP client options.
+    #[serde(skip_serializing_if = "HttpConfig::is_empty", default)]
+    pub(crate) http: HttpConfig,
+
+    /// Signing options.
+    pub(crate) signing: Option<Signing>,
+
+    /// Notification options.
+    pub(crate) notify: Option<Notify>,
+
+    /// Recurring jobs (see `datashed daemon`).
+    #[serde(skip_serializing_if = "HashMap::is_empty", default)]
+    pub(crate) schedule: HashMap<String, Job>,
+
+    /// Letter frequency options.
+    pub(crate) lfreq: Option<Lfreq>,
+
+    /// License and embargo options.
+    pub(crate) license: Option<License>,
+
+    /// Subject category options.
+    pub(crate) msc: Option<Msc>,
+
+    /// Collation options for sorting tokens (e.g. `datashed vocab`).
+    pub(crate) collation: Option<Collation>,
+
+    /// Named text preprocessing profiles.
+    #[serde(skip_serializing_if = "HashMap::is_empty", default)]
+    pub(crate) preprocess: HashMap<String, Preprocess>,
+
     /// List of users.
     #[serde(skip_serializing_if = "HashMap::is_empty", default)]
     pub(crate) users: HashMap<String, User>,
 
+    /// Access rules for serving documents. The first matching rule
+    /// decides whether a document is accessible or not.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) access: Vec<AccessRule>,
+
     /// A set of document kind refinements.
     #[serde(skip_serializing_if = "HashMap::is_empty", default)]
     pub(crate) kinds: HashMap<DocumentKind, KindSpec>,
 
+    /// Column rename and cast rules applied when writing the index.
+    #[serde(skip_serializing_if = "Schema::is_empty", default)]
+    pub(crate) schema: Schema,
+
+    /// Column redaction policies applied to published indices (see
+    /// `datashed redact-index`).
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub(crate) redact: BTreeMap<String, ColumnPolicy>,
+
+    /// Rating campaigns (see `datashed ratings progress`).
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub(crate) campaigns: BTreeMap<String, Campaign>,
+
+    /// Options of the rating client (see `datashed rate`).
+    pub(crate) rate: Option<RateOptions>,
+
+    /// Named rating scales (see `datashed rate`). The scale `default`
+    /// replaces the built-in scale (C, C-, P+, P, P-, I).
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub(crate) scales: BTreeMap<String, RatingScale>,
+
+    /// External metrics (see `datashed index`).
+    #[serde(skip_serializing_if = "Metrics::is_empty", default)]
+    pub(crate) metrics: Metrics,
+
     /// This structure should always be constructed using a public
     /// constructor or using the update syntax:
     ///
@@ -77,17 +151,151 @@ impl Default for Metadata {
     }
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub(crate) struct Runtime {
     /// Number of threads to use. If this options isn't set or a value
     /// of "0" is chosen, the maximum number of available threads
     /// is used.
     pub(crate) num_jobs: Option<usize>,
+
+    /// An upper bound of the memory usage (e.g. "8GiB" or "512M").
+    /// If set, queries are evaluated with the streaming engine, and
+    /// memory-intensive commands (e.g. `vocab`) process documents in
+    /// batches and spill intermediate results to disk.
+    pub(crate) max_memory: Option<String>,
+
+    /// The seed of the random number generator (see `--seed`).
+    pub(crate) seed: Option<u64>,
+
+    /// Caps of the document reads, e.g. to share the bandwidth of a
+    /// network file system with other users.
+    pub(crate) io_limit: Option<IoLimit>,
+
+    /// The behaviour of commands reading an index, which is older than
+    /// the data directory: `warn` (default), `error` or `ignore`. The
+    /// policy can be overridden with `--allow-stale`.
+    pub(crate) stale_index: Option<StalePolicy>,
+}
+
+/// The behaviour of commands reading an outdated index.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum StalePolicy {
+    /// Use the index without a check.
+    Ignore,
+
+    /// Print a warning to the standard error stream.
+    #[default]
+    Warn,
+
+    /// Refuse to use the index.
+    Error,
+}
+
+impl Runtime {
+    /// Returns the memory limit in bytes.
+    pub(crate) fn max_memory(&self) -> DatashedResult<Option<u64>> {
+        self.max_memory.as_deref().map(parse_size).transpose()
+    }
+}
+
+/// The caps of the document reads (`runtime.io_limit`).
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct IoLimit {
+    /// The maximum number of bytes read per second (e.g. "100MB").
+    pub(crate) bandwidth: Option<String>,
+
+    /// The maximum number of documents read per second.
+    pub(crate) iops: Option<u32>,
+}
+
+impl IoLimit {
+    /// Returns the bandwidth limit in bytes per second.
+    pub(crate) fn bandwidth(&self) -> DatashedResult<Option<u64>> {
+        self.bandwidth.as_deref().map(parse_size).transpose()
+    }
+}
+
+/// Parses a size with an optional unit (B, K, KB, KiB, M, MB, MiB,
+/// G, GB, GiB, T, TB, TiB). Units are interpreted as powers of 1024.
+pub(crate) fn parse_size(s: &str) -> DatashedResult<u64> {
+    let s = s.trim();
+    let pos = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+
+    let (value, unit) = s.split_at(pos);
+    let factor: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => bail!("invalid size '{s}'"),
+    };
+
+    let Ok(value) = value.parse::<f64>() else {
+        bail!("invalid size '{s}'");
+    };
+
+    Ok((value * factor as f64) as u64)
+}
+
+/// Options of the rating client.
+///
+/// ```toml
+/// [rate]
+/// preview-bytes = 20000
+/// highlight = ["\\bGND\\b", "[a-z][A-Z][a-z]"]
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct RateOptions {
+    /// The maximum number of bytes of a document shown in the pager.
+    pub(crate) preview_bytes: Option<usize>,
+
+    /// Regular expressions, whose matches are highlighted in the
+    /// pager (e.g. GND labels or suspected OCR errors).
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) highlight: Vec<String>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub(crate) struct User {
     pub(crate) secret: String,
+
+    /// The roles of the user, which are used to grant access to
+    /// documents.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) roles: Vec<String>,
+}
+
+/// An access rule.
+///
+/// A rule matches a document, if the document is of the given `kind`
+/// and satisfies the `predicate` (an SQL expression over the index).
+/// Matching documents are accessible by everyone, if the rule is
+/// `public`, or by users having at least one of the given `roles`.
+///
+/// ```toml
+/// [[access]]
+/// kind = "blurb"
+/// public = true
+///
+/// [[access]]
+/// kind = "book"
+/// roles = ["internal"]
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct AccessRule {
+    pub(crate) kind: Option<DocumentKind>,
+    pub(crate) predicate: Option<String>,
+    #[serde(default)]
+    pub(crate) public: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) roles: Vec<String>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -96,6 +304,182 @@ pub(crate) struct Server {
     pub(crate) port: Option<u16>,
 }
 
+/// Signing options.
+///
+/// ```toml
+/// [signing]
+/// key-file = "/etc/datashed/signing.key"
+/// public-key = "..."
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Signing {
+    /// Path to the (secret) ed25519 signing key.
+    pub(crate) key_file: Option<PathBuf>,
+
+    /// The (base64 encoded) public key used to verify signatures. If
+    /// not set, the public key is derived from the signing key.
+    pub(crate) public_key: Option<String>,
+}
+
+/// A recurring job.
+///
+/// ```toml
+/// [schedule.nightly-index]
+/// cron = "0 2 * * *"
+/// command = "index"
+/// args = ["--wait"]
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct Job {
+    /// A cron expression, which is evaluated in UTC.
+    pub(crate) cron: String,
+
+    /// The datashed command to run.
+    pub(crate) command: String,
+
+    /// The arguments of the command.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) args: Vec<String>,
+}
+
+/// Notifications, which are sent when a long-running command
+/// finishes.
+///
+/// ```toml
+/// [notify]
+/// url = "https://hooks.slack.com/services/..."
+/// format = "slack"
+/// commands = ["index", "vocab"]
+/// only-failures = false
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Notify {
+    /// The URL of the webhook.
+    pub(crate) url: String,
+
+    /// The payload format: `json` (default), `slack` or `matrix`.
+    #[serde(default)]
+    pub(crate) format: NotifyFormat,
+
+    /// The commands which trigger a notification. By default, a
+    /// notification is sent when `index`, `archive`, `vocab` or
+    /// `verify` finish.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) commands: Vec<String>,
+
+    /// If set, notifications are only sent if a command fails.
+    #[serde(default)]
+    pub(crate) only_failures: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum NotifyFormat {
+    /// A JSON object with the keys `shed`, `command`, `status`,
+    /// `duration` (in seconds) and `error`.
+    #[default]
+    Json,
+
+    /// A Slack incoming webhook message (`{"text": "..."}`).
+    Slack,
+
+    /// A Matrix message (`{"msgtype": "m.text", "body": "..."}`),
+    /// e.g. for a hookshot webhook.
+    Matrix,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Lfreq {
+    /// Path to a file of additional reference profiles (TOML or CSV),
+    /// relative to the root directory of the datashed. Profiles of
+    /// this file take precedence over the embedded profiles.
+    pub(crate) profiles: Option<PathBuf>,
+}
+
+/// Subject category (MSC) options.
+///
+/// The subject categories of a document are extracted from the PICA+
+/// record of the document (see `datashed index`). All categories,
+/// which are yielded by the `paths` and contained in `allow`, are
+/// recorded in the `msc_all` index column in the order of the paths
+/// (priority); the first one is recorded in the `msc` column. By
+/// default, the built-in paths and categories of the DNB are used.
+///
+/// ```toml
+/// [msc]
+/// paths = ['045E{ e | E == "i" && H == "dnb" }', '045E{ e | E == "a" }']
+/// allow = ["004", "510", "530", "540"]
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Msc {
+    /// PICA+ paths of the subject categories in the order of priority.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) paths: Vec<String>,
+
+    /// The subject categories, which are recorded.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) allow: Vec<String>,
+}
+
+/// License and embargo options.
+///
+/// The access/license code of a document is extracted from the PICA+
+/// record of the document (see `datashed index`) into the `license`
+/// index column. Documents with a restricted code aren't archived or
+/// served, unless `--force` is given.
+///
+/// Alternatively, the code is read from a sidecar file next to the
+/// document (e.g. `data/book/123.txt.license`), which takes precedence
+/// over the PICA+ record. The terms of each code are used for the
+/// `LICENSES` manifest of an archive.
+///
+/// ```toml
+/// [license]
+/// paths = ["047I.u"]
+/// sidecar = ".license"
+/// restricted = ["embargo", "licensed"]
+///
+/// [license.terms.cc-by-4]
+/// name = "CC BY 4.0"
+/// url = "https://creativecommons.org/licenses/by/4.0/"
+/// attribution = "Deutsche Nationalbibliothek"
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct License {
+    /// PICA+ paths of the access/license code. The first path yielding
+    /// a value takes precedence.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) paths: Vec<String>,
+
+    /// The suffix of sidecar files, which contain the access/license
+    /// code of a document (first line).
+    pub(crate) sidecar: Option<String>,
+
+    /// Codes of documents, which must not be redistributed. If set,
+    /// documents without a code are treated as restricted, too.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) restricted: Vec<String>,
+
+    /// The license terms of each code.
+    #[serde(skip_serializing_if = "HashMap::is_empty", default)]
+    pub(crate) terms: HashMap<String, LicenseTerms>,
+}
+
+/// The terms of a license code.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct LicenseTerms {
+    /// The name of the license.
+    pub(crate) name: Option<String>,
+
+    /// The URL of the license text.
+    pub(crate) url: Option<String>,
+
+    /// The required attribution.
+    pub(crate) attribution: Option<String>,
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, Clone, Hash)]
 pub(crate) struct KindSpec {
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
@@ -133,6 +517,43 @@ impl Config {
         Ok(config)
     }
 
+    /// Returns the memory limit (`runtime.max_memory`) in bytes.
+    pub(crate) fn max_memory(&self) -> DatashedResult<Option<u64>> {
+        match self.runtime {
+            Some(ref runtime) => runtime.max_memory(),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the (validated) rating scale of the campaign or the
+    /// default scale, if no campaign is given or the campaign doesn't
+    /// specify a scale.
+    pub(crate) fn rating_scale(
+        &self,
+        campaign: Option<&str>,
+    ) -> DatashedResult<RatingScale> {
+        let name = match campaign {
+            Some(name) => match self.campaigns.get(name) {
+                Some(campaign) => campaign.scale.as_deref(),
+                None => bail!("unknown campaign '{name}'"),
+            },
+            None => None,
+        };
+
+        let scale = match name {
+            Some(name) => match self.scales.get(name) {
+                Some(scale) => scale.clone(),
+                None => bail!("unknown rating scale '{name}'"),
+            },
+            None => {
+                self.scales.get("default").cloned().unwrap_or_default()
+            }
+        };
+
+        scale.validate()?;
+        Ok(scale)
+    }
+
     /// Saves the config.
     pub(crate) fn save(&self) -> DatashedResult<()> {
         let content = toml::to_string(self).expect("valid toml");
@@ -141,3 +562,47 @@ impl Config {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn parse_max_memory() -> TestResult {
+        assert_eq!(parse_size("512")?, 512);
+        assert_eq!(parse_size("8GiB")?, 8 << 30);
+        assert_eq!(parse_size("1.5 M")?, 3 << 19);
+        assert!(parse_size("8 parsecs").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn campaign_rating_scale() -> TestResult {
+        let config: Config = toml::from_str(
+            r#"
+            [metadata]
+            name = "test"
+            version = "0.1.0"
+
+            [campaigns.relevance]
+            scale = "binary"
+
+            [campaigns.ocr]
+
+            [scales.binary]
+            levels = [{ label = "yes" }, { label = "no" }]
+            "#,
+        )?;
+
+        assert_eq!(
+            config.rating_scale(Some("relevance"))?.levels.len(),
+            2
+        );
+        assert_eq!(config.rating_scale(Some("ocr"))?.levels.len(), 6);
+        assert_eq!(config.rating_scale(None)?.levels[0].label, "C");
+        assert!(config.rating_scale(Some("unknown")).is_err());
+        Ok(())
+    }
+}
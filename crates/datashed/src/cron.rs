@@ -0,0 +1,201 @@
+use std::str::FromStr;
+
+use crate::error::{bail, DatashedError, DatashedResult};
+
+/// A cron expression (`minute hour day-of-month month day-of-week`),
+/// which is evaluated in UTC.
+///
+/// Each field is either `*`, a value, a range (`1-5`), a list of the
+/// former (`1,3,5`) or a step (`*/15`, `0-30/10`). The day of the week
+/// ranges from 0 (Sunday) to 7 (Sunday). The aliases `@hourly`,
+/// `@daily`, `@weekly` and `@monthly` are supported. If both the day
+/// of the month and the day of the week are restricted, a day matches
+/// if either field matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// Parses a field into a bitset of the matching values.
+fn parse_field(s: &str, min: u32, max: u32) -> DatashedResult<u64> {
+    let mut bits = 0u64;
+
+    for part in s.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => bail!("invalid step '{part}'"),
+            },
+            None => (part, 1),
+        };
+
+        let value = |s: &str| -> DatashedResult<u32> {
+            match s.parse::<u32>() {
+                Ok(v) if (min..=max).contains(&v) => Ok(v),
+                _ => Err(DatashedError::other(format!(
+                    "invalid value '{s}' (expected {min}-{max})"
+                ))),
+            }
+        };
+
+        let (lo, hi) = if range == "*" {
+            (min, max)
+        } else if let Some((lo, hi)) = range.split_once('-') {
+            (value(lo)?, value(hi)?)
+        } else if step > 1 {
+            (value(range)?, max)
+        } else {
+            let v = value(range)?;
+            (v, v)
+        };
+
+        if lo > hi {
+            bail!("invalid range '{range}'");
+        }
+
+        for v in (lo..=hi).step_by(step as usize) {
+            bits |= 1 << v;
+        }
+    }
+
+    Ok(bits)
+}
+
+impl FromStr for CronExpr {
+    type Err = DatashedError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            s => s,
+        };
+
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            bail!("invalid cron expression '{s}'");
+        };
+
+        let mut weekdays = parse_field(weekday, 0, 7)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+}
+
+/// Converts days since the epoch into a (year, month, day) triple.
+fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let y = yoe + era * 400 + (m <= 2) as i64;
+
+    (y, m, d)
+}
+
+impl CronExpr {
+    /// Returns `true` if the expression matches the given day (days
+    /// since the epoch).
+    fn matches_day(&self, days: i64) -> bool {
+        let (_, month, day) = civil_from_days(days);
+        let weekday = (days + 4).rem_euclid(7) as u32;
+
+        if self.months & (1 << month) == 0 {
+            return false;
+        }
+
+        let day_match = self.days & (1 << day) != 0;
+        let weekday_match = self.weekdays & (1 << weekday) != 0;
+
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day_match || weekday_match,
+            (true, false) => weekday_match,
+            (false, true) => day_match,
+            (true, true) => true,
+        }
+    }
+
+    /// Returns the next point in time (seconds since the epoch) after
+    /// `secs`, which matches the expression.
+    pub(crate) fn next_after(&self, secs: u64) -> Option<u64> {
+        let start = secs / 60 + 1;
+        let mut days = (start / 1440) as i64;
+        let mut minute_of_day = (start % 1440) as u32;
+
+        // Every valid expression matches within eight years (e.g. the
+        // 29th of February on a Monday).
+        for _ in 0..(366 * 8) {
+            if self.matches_day(days) {
+                for m in minute_of_day..1440 {
+                    if self.hours & (1 << (m / 60)) != 0
+                        && self.minutes & (1 << (m % 60)) != 0
+                    {
+                        return Some(
+                            (days as u64 * 1440 + m as u64) * 60,
+                        );
+                    }
+                }
+            }
+
+            days += 1;
+            minute_of_day = 0;
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn cron_next_after() -> TestResult {
+        // 2024-01-01T00:00:00Z (Monday)
+        let t0 = 1_704_067_200;
+
+        let expr: CronExpr = "30 2 * * *".parse()?;
+        assert_eq!(expr.next_after(t0), Some(t0 + 2 * 3600 + 1800));
+
+        let expr: CronExpr = "0 3 * * 0".parse()?;
+        assert_eq!(
+            expr.next_after(t0),
+            Some(t0 + 6 * 86400 + 3 * 3600)
+        );
+
+        let expr: CronExpr = "*/15 * * * *".parse()?;
+        assert_eq!(expr.next_after(t0), Some(t0 + 15 * 60));
+
+        let expr: CronExpr = "@monthly".parse()?;
+        assert_eq!(expr.next_after(t0), Some(t0 + 31 * 86400));
+
+        assert!("61 * * * *".parse::<CronExpr>().is_err());
+        assert!("* * *".parse::<CronExpr>().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+use std::fs::read_to_string;
+use std::io::{BufRead, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use age::secrecy::SecretString;
+use age::stream::StreamWriter;
+use age::{x25519, Decryptor, Encryptor, Identity, Recipient};
+use dialoguer::Password;
+
+use crate::error::{bail, DatashedError, DatashedResult};
+
+/// The magic string at the beginning of each age file.
+const MAGIC: &[u8] = b"age-encryption.org/";
+
+/// The environment variable, which holds the passphrase.
+const PASSPHRASE_ENV: &str = "DATASHED_PASSPHRASE";
+
+/// The key used to encrypt an archive.
+pub(crate) enum Key {
+    Recipients(Vec<x25519::Recipient>),
+    Passphrase(SecretString),
+}
+
+/// Reads all X25519 identities (lines starting with
+/// `AGE-SECRET-KEY-`) of an age key file.
+pub(crate) fn read_identities<P: AsRef<Path>>(
+    path: P,
+) -> DatashedResult<Vec<x25519::Identity>> {
+    let identities = read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("AGE-SECRET-KEY-"))
+        .map(|line| {
+            x25519::Identity::from_str(line)
+                .map_err(DatashedError::other)
+        })
+        .collect::<DatashedResult<Vec<_>>>()?;
+
+    if identities.is_empty() {
+        bail!("key file doesn't contain an identity");
+    }
+
+    Ok(identities)
+}
+
+/// Parses an age recipient (`age1...`).
+pub(crate) fn parse_recipient(
+    s: &str,
+) -> Result<x25519::Recipient, String> {
+    x25519::Recipient::from_str(s).map_err(|e| e.to_string())
+}
+
+/// Returns the passphrase from the environment or prompts the user
+/// for it.
+pub(crate) fn passphrase(
+    confirm: bool,
+) -> DatashedResult<SecretString> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(SecretString::new(passphrase));
+    }
+
+    let mut prompt = Password::new().with_prompt("Passphrase");
+    if confirm {
+        prompt = prompt.with_confirmation(
+            "Confirm passphrase",
+            "Passphrases mismatch",
+        );
+    }
+
+    let passphrase = prompt.interact().map_err(DatashedError::other)?;
+    Ok(SecretString::new(passphrase))
+}
+
+/// Returns `true` if the reader starts with an age header.
+#[inline]
+pub(crate) fn is_encrypted<R: BufRead>(
+    reader: &mut R,
+) -> DatashedResult<bool> {
+    Ok(reader.fill_buf()?.starts_with(MAGIC))
+}
+
+/// Wraps the writer, so that all data written is encrypted with the
+/// given key. The returned writer must be finished by calling
+/// [StreamWriter::finish].
+pub(crate) fn encrypt<W: Write>(
+    out: W,
+    key: Key,
+) -> DatashedResult<StreamWriter<W>> {
+    let encryptor = match key {
+        Key::Passphrase(passphrase) => {
+            Encryptor::with_user_passphrase(passphrase)
+        }
+        Key::Recipients(recipients) => {
+            let recipients = recipients
+                .into_iter()
+                .map(|r| Box::new(r) as Box<dyn Recipient + Send>)
+                .collect();
+
+            let Some(encryptor) =
+                Encryptor::with_recipients(recipients)
+            else {
+                bail!("missing recipient");
+            };
+
+            encryptor
+        }
+    };
+
+    Ok(encryptor.wrap_output(out)?)
+}
+
+/// Wraps the reader, so that all data read is decrypted. If the input
+/// is encrypted with a passphrase, the passphrase is read from the
+/// environment or the user is prompted for it.
+pub(crate) fn decrypt<R: Read + 'static>(
+    input: R,
+    identities: &[x25519::Identity],
+) -> DatashedResult<Box<dyn Read>> {
+    let decryptor =
+        Decryptor::new(input).map_err(DatashedError::other)?;
+
+    Ok(match decryptor {
+        Decryptor::Recipients(decryptor) => {
+            if identities.is_empty() {
+                bail!("archive is encrypted; missing key file");
+            }
+
+            Box::new(
+                decryptor
+                    .decrypt(
+                        identities.iter().map(|i| i as &dyn Identity),
+                    )
+                    .map_err(DatashedError::other)?,
+            )
+        }
+        Decryptor::Passphrase(decryptor) => {
+            let passphrase = passphrase(false)?;
+            Box::new(
+                decryptor
+                    .decrypt(&passphrase, None)
+                    .map_err(DatashedError::other)?,
+            )
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn encrypt_decrypt_roundtrip() -> TestResult {
+        let identity = x25519::Identity::generate();
+        let key = Key::Recipients(vec![identity.to_public()]);
+
+        let mut stream = encrypt(vec![], key)?;
+        stream.write_all(b"foo")?;
+        let data = stream.finish()?;
+
+        let mut reader = BufReader::new(Cursor::new(data));
+        assert!(is_encrypted(&mut reader)?);
+
+        let mut out = String::new();
+        decrypt(reader, &[identity])?.read_to_string(&mut out)?;
+        assert_eq!(out, "foo");
+
+        Ok(())
+    }
+}
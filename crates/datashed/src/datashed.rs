@@ -1,11 +1,20 @@
 use std::fs::File;
+use std::io::{BufReader, Read, Write};
 use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
 use std::{env, fs};
 
 use polars::prelude::*;
 
+use crate::atomic::AtomicFile;
 use crate::config::Config;
 use crate::error::{bail, DatashedError, DatashedResult};
+use crate::freshness;
+use crate::lock::LockGuard;
+use crate::sketch::BloomFilter;
+
+/// The false positive rate of the PPN Bloom filter (`index.bloom`).
+const BLOOM_FP_RATE: f64 = 0.001;
 
 pub(crate) struct Datashed {
     /// The root directory of the datashed.
@@ -15,10 +24,28 @@ pub(crate) struct Datashed {
 impl Datashed {
     pub(crate) const CONFIG: &'static str = "datashed.toml";
     pub(crate) const RATINGS: &'static str = "ratings.csv";
+    pub(crate) const RATINGS_LOCK: &'static str = "ratings.lock";
+    pub(crate) const TAGS: &'static str = "tags.csv";
+    pub(crate) const AUDIT_LOG: &'static str = "audit.jsonl";
+    pub(crate) const ACCESS_LOG: &'static str = "audit.csv";
+    pub(crate) const LM: &'static str = "lm.json";
+    pub(crate) const ARTIFACTS: &'static str = "artifacts.toml";
     pub(crate) const INDEX: &'static str = "index.ipc";
+    pub(crate) const REDACTED_INDEX: &'static str =
+        "index.redacted.ipc";
+    pub(crate) const PAGES: &'static str = "pages.ipc";
+    pub(crate) const BLOOM: &'static str = "index.bloom";
+    pub(crate) const LOCK: &'static str = "index.lock";
 
     pub(crate) const DATA_DIR: &'static str = "data";
+    pub(crate) const QUARANTINE_DIR: &'static str = "quarantine";
     pub(crate) const TEMP_DIR: &'static str = "tmp";
+    pub(crate) const PLUGINS_DIR: &'static str = "plugins";
+    pub(crate) const JOBS_DIR: &'static str = "jobs";
+    pub(crate) const JOBS_STATUS: &'static str = "status.json";
+    pub(crate) const TRASH_DIR: &'static str = "trash";
+    pub(crate) const CHECKPOINTS_DIR: &'static str = "checkpoints";
+    pub(crate) const METADATA_DIR: &'static str = "metadata";
 
     /// Discovers the root of the datashed.
     ///
@@ -44,6 +71,23 @@ impl Datashed {
         Ok(Self { root_dir })
     }
 
+    /// Opens the datashed located at the given root directory.
+    ///
+    /// This function fails, if the directory doesn't contain a
+    /// datashed [Config].
+    pub(crate) fn from_path<P: Into<PathBuf>>(
+        root_dir: P,
+    ) -> DatashedResult<Self> {
+        let root_dir = root_dir.into();
+        if !root_dir.join(Self::CONFIG).is_file() {
+            bail!("not a datashed: {}", root_dir.display());
+        }
+
+        Ok(Self {
+            root_dir: root_dir.canonicalize()?,
+        })
+    }
+
     /// Returns the config associated with the datashed.
     #[inline]
     pub(crate) fn config(&self) -> DatashedResult<Config> {
@@ -62,19 +106,220 @@ impl Datashed {
         self.root_dir.join(Self::DATA_DIR)
     }
 
+    /// Returns the directory of the documents moved aside by
+    /// `datashed quarantine`.
+    #[inline]
+    pub(crate) fn quarantine_dir(&self) -> PathBuf {
+        self.root_dir.join(Self::QUARANTINE_DIR)
+    }
+
+    /// Returns the directory of the WASM plugins of the datashed.
+    #[inline]
+    pub(crate) fn plugins_dir(&self) -> PathBuf {
+        self.root_dir.join(Self::PLUGINS_DIR)
+    }
+
     /// Returns the temp directory of the datashed.
     #[inline]
     pub(crate) fn temp_dir(&self) -> PathBuf {
         self.root_dir.join(Self::TEMP_DIR)
     }
 
+    /// Returns the directory of the job logs and the job status (see
+    /// `datashed daemon`).
+    #[inline]
+    pub(crate) fn jobs_dir(&self) -> PathBuf {
+        self.temp_dir().join(Self::JOBS_DIR)
+    }
+
+    /// Returns the directory of the files removed by `datashed clean`
+    /// (see `datashed undo-clean`).
+    #[inline]
+    pub(crate) fn trash_dir(&self) -> PathBuf {
+        self.temp_dir().join(Self::TRASH_DIR)
+    }
+
+    /// Returns the directory of the checkpoints of long-running
+    /// commands (see `--resume`).
+    #[inline]
+    pub(crate) fn checkpoints_dir(&self) -> PathBuf {
+        self.temp_dir().join(Self::CHECKPOINTS_DIR)
+    }
+
+    /// Returns the directory of the metadata extracted from PICA+ dumps
+    /// (see `datashed index --refresh-metadata`).
+    #[inline]
+    pub(crate) fn metadata_dir(&self) -> PathBuf {
+        self.temp_dir().join(Self::METADATA_DIR)
+    }
+
+    /// Acquires the (advisory) lock of the index, which must be held
+    /// by all commands writing the index.
+    #[inline]
+    pub(crate) fn lock(&self, wait: bool) -> DatashedResult<LockGuard> {
+        LockGuard::acquire(self.root_dir.join(Self::LOCK), wait)
+    }
+
+    /// Acquires the (advisory) lock of the ratings store, which must
+    /// be held by all processes appending to the store. The lock is
+    /// held by `datashed serve` as long as the server is running.
+    #[inline]
+    pub(crate) fn lock_ratings(
+        &self,
+        wait: bool,
+    ) -> DatashedResult<LockGuard> {
+        LockGuard::acquire(
+            self.temp_dir().join(Self::RATINGS_LOCK),
+            wait,
+        )
+    }
+
     /// Returns the index associated with the datashed.
     #[inline]
     pub(crate) fn index(&self) -> DatashedResult<DataFrame> {
+        freshness::check(self)?;
         Ok(IpcReader::new(File::open(
             self.base_dir().join(Self::INDEX),
         )?)
         .memory_mapped(None)
         .finish()?)
     }
+
+    /// Returns a lazy scan of the index.
+    ///
+    /// In contrast to [Datashed::index], only the columns and rows,
+    /// which are needed by the query, are read from the index
+    /// (projection and predicate pushdown). Commands, which touch only
+    /// a few columns, should prefer this method.
+    pub(crate) fn index_lazy(&self) -> DatashedResult<LazyFrame> {
+        let path = self.base_dir().join(Self::INDEX);
+        let _ = fs::metadata(&path)?;
+        freshness::check(self)?;
+
+        Ok(LazyFrame::scan_ipc(path, ScanArgsIpc::default())?)
+    }
+
+    /// Returns the fingerprint (size and modification time) of the
+    /// index file.
+    fn index_fingerprint(&self) -> DatashedResult<[u64; 2]> {
+        let metadata = fs::metadata(self.base_dir().join(Self::INDEX))?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|mtime| mtime.as_nanos() as u64)
+            .unwrap_or_default();
+
+        Ok([metadata.len(), mtime])
+    }
+
+    /// Writes a Bloom filter over the `idn` (PPN) column of the index,
+    /// which is used for fast membership tests (see `datashed
+    /// contains`). The filter is bound to the fingerprint of the
+    /// current index file, so the index must be written first.
+    pub(crate) fn write_bloom(
+        &self,
+        df: &DataFrame,
+    ) -> DatashedResult<()> {
+        let Ok(idn) = df.column("idn") else {
+            return Ok(());
+        };
+
+        let idn = idn.cast(&DataType::String)?;
+        let idn = idn.str()?;
+
+        let mut filter = BloomFilter::new(idn.len(), BLOOM_FP_RATE);
+        for value in idn.into_iter().flatten() {
+            filter.insert(value);
+        }
+
+        let mut out =
+            AtomicFile::create(self.base_dir().join(Self::BLOOM))?;
+        for value in self.index_fingerprint()? {
+            out.write_all(&value.to_le_bytes())?;
+        }
+
+        filter.write(&mut out)?;
+        out.commit()?;
+        Ok(())
+    }
+
+    /// Returns the Bloom filter of the index. If the filter is missing
+    /// or outdated (the index was rewritten after the filter had been
+    /// written), `None` is returned.
+    pub(crate) fn bloom(&self) -> DatashedResult<Option<BloomFilter>> {
+        let Ok(file) = File::open(self.base_dir().join(Self::BLOOM))
+        else {
+            return Ok(None);
+        };
+
+        let mut rdr = BufReader::new(file);
+        for value in self.index_fingerprint()? {
+            let mut buf = [0u8; 8];
+            if rdr.read_exact(&mut buf).is_err()
+                || u64::from_le_bytes(buf) != value
+            {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(BloomFilter::read(rdr)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_datashed;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn datashed_index_lazy() -> TestResult {
+        let (_dir, datashed) = temp_datashed()?;
+        assert!(datashed.index_lazy().is_err());
+
+        let mut df = df!(
+            "path" => ["data/book/1.txt", "data/toc/2.txt"],
+            "kind" => ["book", "toc"],
+            "size" => [10u64, 20],
+        )?;
+        IpcWriter::new(File::create(
+            datashed.base_dir().join(Datashed::INDEX),
+        )?)
+        .finish(&mut df)?;
+
+        let df = datashed
+            .index_lazy()?
+            .filter(col("kind").eq(lit("toc")))
+            .select([col("path")])
+            .collect()?;
+
+        assert_eq!(df.width(), 1);
+        assert_eq!(
+            df.column("path")?.str()?.get(0),
+            Some("data/toc/2.txt")
+        );
+        assert_eq!(df.height(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn datashed_bloom() -> TestResult {
+        let (_dir, datashed) = temp_datashed()?;
+        let path = datashed.base_dir().join(Datashed::INDEX);
+        assert!(datashed.bloom()?.is_none());
+
+        let mut df = df!("idn" => ["118540238", "118607626"])?;
+        IpcWriter::new(File::create(&path)?).finish(&mut df)?;
+        datashed.write_bloom(&df)?;
+
+        let filter = datashed.bloom()?.unwrap();
+        assert!(filter.contains("118540238"));
+        assert!(!filter.contains("040000000"));
+
+        let mut df = df!("idn" => ["118540238"])?;
+        IpcWriter::new(File::create(&path)?).finish(&mut df)?;
+        assert!(datashed.bloom()?.is_none());
+        Ok(())
+    }
 }
@@ -0,0 +1,326 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use glob::{glob_with, MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{DatashedError, DatashedResult};
+use crate::utils::relpath;
+
+/// The document suffixes, which are discovered by default.
+const DEFAULT_SUFFIXES: [&str; 1] = ["txt"];
+
+/// The rules of the document discovery, which are honored by all
+/// commands scanning the data directory (e.g. `index`, `status` and
+/// `clean`).
+///
+/// The `include` and `exclude` patterns are matched against the path
+/// of a document relative to the root directory (e.g.
+/// `data/drafts/1.txt`). A document is discovered, if it has one of
+/// the `suffixes`, matches any `include` pattern (if given) and
+/// doesn't match any `exclude` pattern.
+///
+/// Symlinked documents (or documents below a symlinked directory) are
+/// handled according to the `symlinks` policy. Documents, which refer
+/// to the same file (symlinks or hardlinks), are discovered only once
+/// under the first path (in path order).
+///
+/// ```toml
+/// [discovery]
+/// suffixes = ["txt", "md"]
+/// exclude = ["**/drafts/**"]
+/// symlinks = "skip"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Discovery {
+    /// The suffixes (file extensions) of documents.
+    #[serde(default = "default_suffixes")]
+    pub(crate) suffixes: Vec<String>,
+
+    /// Glob patterns of the documents to include.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) include: Vec<String>,
+
+    /// Glob patterns of the documents to exclude.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) exclude: Vec<String>,
+
+    /// How to handle symlinked documents.
+    #[serde(
+        skip_serializing_if = "SymlinkPolicy::is_default",
+        default
+    )]
+    pub(crate) symlinks: SymlinkPolicy,
+}
+
+/// The handling of symlinked documents.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SymlinkPolicy {
+    /// Symlinks are followed and the target of the link is recorded
+    /// in the index (column `link_target`).
+    #[default]
+    Follow,
+
+    /// Symlinked documents are ignored.
+    Skip,
+
+    /// A symlinked document is an error.
+    Error,
+}
+
+impl SymlinkPolicy {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+fn default_suffixes() -> Vec<String> {
+    DEFAULT_SUFFIXES.iter().map(ToString::to_string).collect()
+}
+
+impl Default for Discovery {
+    fn default() -> Self {
+        Self {
+            suffixes: default_suffixes(),
+            include: vec![],
+            exclude: vec![],
+            symlinks: SymlinkPolicy::default(),
+        }
+    }
+}
+
+impl Discovery {
+    /// Returns `true` if the default rules are used.
+    pub(crate) fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Compiles the discovery rules.
+    pub(crate) fn matcher(&self) -> DatashedResult<DocumentMatcher> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    Pattern::new(pattern).map_err(|e| {
+                        DatashedError::other(format!(
+                            "invalid pattern '{pattern}': {e}"
+                        ))
+                    })
+                })
+                .collect::<DatashedResult<Vec<_>>>()
+        };
+
+        Ok(DocumentMatcher {
+            suffixes: self
+                .suffixes
+                .iter()
+                .map(|suffix| {
+                    suffix.trim_start_matches('.').to_string()
+                })
+                .collect(),
+            include: compile(&self.include)?,
+            exclude: compile(&self.exclude)?,
+            symlinks: self.symlinks,
+        })
+    }
+}
+
+/// The compiled rules of the document discovery.
+#[derive(Debug)]
+pub(crate) struct DocumentMatcher {
+    suffixes: Vec<String>,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    symlinks: SymlinkPolicy,
+}
+
+impl DocumentMatcher {
+    /// Returns `true`, if the document at `relpath` (relative to the
+    /// root directory) is discovered.
+    pub(crate) fn is_match<P: AsRef<Path>>(&self, relpath: P) -> bool {
+        let path = relpath.as_ref();
+        let options = MatchOptions::default();
+
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| self.suffixes.iter().any(|s| s == ext))
+            && (self.include.is_empty()
+                || self
+                    .include
+                    .iter()
+                    .any(|p| p.matches_path_with(path, options)))
+            && !self
+                .exclude
+                .iter()
+                .any(|p| p.matches_path_with(path, options))
+    }
+
+    /// Returns an iterator over the (absolute) paths of all discovered
+    /// documents in the data directory. The paths are ordered by
+    /// suffix and path.
+    pub(crate) fn documents<'a>(
+        &'a self,
+        base_dir: &'a Path,
+        data_dir: &Path,
+    ) -> DatashedResult<
+        impl Iterator<Item = DatashedResult<PathBuf>> + 'a,
+    > {
+        let paths = self
+            .suffixes
+            .iter()
+            .map(|suffix| {
+                let pattern =
+                    format!("{}/**/*.{suffix}", data_dir.display());
+                glob_with(&pattern, MatchOptions::default())
+                    .map_err(|e| DatashedError::Other(e.to_string()))
+            })
+            .collect::<DatashedResult<Vec<_>>>()?;
+
+        let mut seen = HashSet::new();
+
+        Ok(paths
+            .into_iter()
+            .flatten()
+            .filter_map(Result::ok)
+            .filter(|path| path.is_file())
+            .filter(move |path| self.is_match(relpath(path, base_dir)))
+            .filter_map(move |path| {
+                if self.symlinks != SymlinkPolicy::Follow
+                    && link_target(&path, base_dir).is_some()
+                {
+                    if self.symlinks == SymlinkPolicy::Error {
+                        return Some(Err(DatashedError::other(
+                            format!(
+                                "document '{}' is a symlink",
+                                relpath(&path, base_dir)
+                            ),
+                        )));
+                    }
+
+                    return None;
+                }
+
+                match file_id(&path) {
+                    Some(id) if !seen.insert(id) => None,
+                    _ => Some(Ok(path)),
+                }
+            }))
+    }
+}
+
+/// Returns the (canonical) target of a document, if the document or
+/// any of its parent directories below the root directory is a
+/// symlink.
+pub(crate) fn link_target(
+    path: &Path,
+    base_dir: &Path,
+) -> Option<PathBuf> {
+    let target = fs::canonicalize(path).ok()?;
+    let expected = fs::canonicalize(base_dir)
+        .ok()?
+        .join(relpath(path, base_dir));
+
+    (target != expected).then_some(target)
+}
+
+/// Returns the identity (device and inode) of a file.
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    path.metadata()
+        .ok()
+        .map(|metadata| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn discovery_matcher() -> TestResult {
+        let matcher = Discovery::default().matcher()?;
+        assert!(matcher.is_match("data/1.txt"));
+        assert!(!matcher.is_match("data/1.md"));
+
+        let discovery: Discovery = toml::from_str(
+            "suffixes = [\"txt\", \".md\"]\n\
+             exclude = [\"**/drafts/**\"]\n",
+        )?;
+
+        let matcher = discovery.matcher()?;
+        assert!(matcher.is_match("data/1.md"));
+        assert!(matcher.is_match("data/a/1.txt"));
+        assert!(!matcher.is_match("data/drafts/1.txt"));
+        assert!(!matcher.is_match("data/1.pdf"));
+
+        let discovery = Discovery {
+            include: vec!["data/book/**".into()],
+            ..Default::default()
+        };
+
+        let matcher = discovery.matcher()?;
+        assert!(matcher.is_match("data/book/1.txt"));
+        assert!(!matcher.is_match("data/toc/1.txt"));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn discovery_symlinks() -> TestResult {
+        use std::os::unix::fs::symlink;
+
+        let dir = crate::testing::temp_dir()?;
+        let base_dir = dir.path();
+        let data_dir = base_dir.join("data");
+        fs::create_dir_all(&data_dir)?;
+
+        fs::write(data_dir.join("1.txt"), "foo")?;
+        fs::hard_link(data_dir.join("1.txt"), data_dir.join("2.txt"))?;
+        fs::write(base_dir.join("3.txt"), "bar")?;
+        symlink(base_dir.join("3.txt"), data_dir.join("3.txt"))?;
+
+        let documents = |discovery: Discovery| -> DatashedResult<_> {
+            discovery
+                .matcher()?
+                .documents(base_dir, &data_dir)?
+                .map(|path| path.map(|path| relpath(path, base_dir)))
+                .collect::<DatashedResult<Vec<_>>>()
+        };
+
+        assert_eq!(
+            documents(Discovery::default())?,
+            ["data/1.txt", "data/3.txt"]
+        );
+        assert!(
+            link_target(&data_dir.join("3.txt"), base_dir).is_some()
+        );
+        assert!(
+            link_target(&data_dir.join("1.txt"), base_dir).is_none()
+        );
+
+        let discovery = Discovery {
+            symlinks: SymlinkPolicy::Skip,
+            ..Default::default()
+        };
+        assert_eq!(documents(discovery)?, ["data/1.txt"]);
+
+        let discovery = Discovery {
+            symlinks: SymlinkPolicy::Error,
+            ..Default::default()
+        };
+        assert!(documents(discovery).is_err());
+        Ok(())
+    }
+}
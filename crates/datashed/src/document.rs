@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{self, Display, Write};
 use std::fs::{File, Metadata};
 use std::io::Read;
@@ -13,8 +13,15 @@ use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
 use crate::error::DatashedResult;
-use crate::lfreq::{lfreq_eng, lfreq_ger};
+use crate::lfreq::LfreqProfiles;
 use crate::prelude::{bail, DatashedError};
+use crate::script::{script_stats, Script};
+use crate::throttle;
+
+/// The minimum number of occurrences of a line within a document to
+/// be considered as running header or footer (`repetition` column of
+/// the index).
+pub(crate) const MIN_LINE_REPEATS: usize = 3;
 
 fn language_detector() -> &'static LanguageDetector {
     static DETECTOR: OnceLock<LanguageDetector> = OnceLock::new();
@@ -90,28 +97,83 @@ impl AsRef<[u8]> for Document {
     }
 }
 
+/// Derives the kind of a document from the components of its path.
+/// If the kind can be derived by multiple path components, the
+/// function chooses the broadest.
+pub(crate) fn kind_from_path<P: AsRef<Path>>(path: P) -> DocumentKind {
+    path.as_ref()
+        .components()
+        .filter_map(|component| {
+            if let Component::Normal(s) = component {
+                s.to_str()
+            } else {
+                None
+            }
+        })
+        .find_map(|s| DocumentKind::from_str(s).ok())
+        .unwrap_or_default()
+}
+
 impl Document {
     pub(crate) fn from_path<P: AsRef<Path>>(
         path: P,
     ) -> DatashedResult<Self> {
         let path = path.as_ref().to_path_buf();
         let metadata = path.metadata()?;
+        throttle::acquire(metadata.len());
+
         let mut file = File::open(&path)?;
         let mut buf = Vec::new();
 
         let _ = file.read_to_end(&mut buf)?;
-        let buf = BString::from(buf);
+        Ok(Self::from_parts(path, metadata, BString::from(buf)))
+    }
+
+    fn from_parts(
+        path: PathBuf,
+        metadata: Metadata,
+        buf: BString,
+    ) -> Self {
         let word_cnt = buf.words().count();
         let char_cnt = buf.chars().count();
 
-        Ok(Self {
+        Self {
             path,
             metadata,
             buf,
             word_cnt,
             char_cnt,
             _lang: None,
-        })
+        }
+    }
+
+    /// Splits the document into pages.
+    ///
+    /// Pages are separated by form-feed characters (U+000C). Each page
+    /// is a document on its own, which shares the path and metadata of
+    /// the original document. A trailing form-feed doesn't start a new
+    /// page and a document without any form-feed consists of a single
+    /// page.
+    pub(crate) fn pages(&self) -> Vec<Document> {
+        let mut pages: Vec<Document> = self
+            .buf
+            .split_str("\x0c")
+            .map(|page| {
+                Self::from_parts(
+                    self.path.clone(),
+                    self.metadata.clone(),
+                    BString::from(page),
+                )
+            })
+            .collect();
+
+        if pages.len() > 1
+            && pages.last().is_some_and(|p| p.size() == 0)
+        {
+            pages.pop();
+        }
+
+        pages
     }
 
     pub(crate) fn idn(&self) -> String {
@@ -125,17 +187,7 @@ impl Document {
     /// If the kind can be derived by multiple path components, the
     /// function chooses the broadest.
     pub(crate) fn kind(&self) -> DocumentKind {
-        self.path
-            .components()
-            .filter_map(|component| {
-                if let Component::Normal(s) = component {
-                    s.to_str()
-                } else {
-                    None
-                }
-            })
-            .find_map(|s| DocumentKind::from_str(s).ok())
-            .unwrap_or_default()
+        kind_from_path(&self.path)
     }
 
     /// Returns the length of the document in bytes.
@@ -227,17 +279,24 @@ impl Document {
 
     /// Returns the letter frequency of the document.
     ///
-    /// The letter frequency is computed against reference values.
+    /// The letter frequency is computed against the embedded reference
+    /// profile of the detected language.
+    #[inline]
     pub(crate) fn lfreq(&mut self) -> Option<f64> {
-        if let Some((lang, _)) = self.lang() {
-            match lang.as_str() {
-                "ger" => lfreq_ger(&self.buf),
-                "eng" => lfreq_eng(&self.buf),
-                _ => None,
-            }
-        } else {
-            None
-        }
+        self.lfreq_with(LfreqProfiles::embedded())
+    }
+
+    /// Returns the letter frequency of the document.
+    ///
+    /// The letter frequency is computed against the reference profile
+    /// of the detected language. If there is no profile for the
+    /// language, the function returns `None`.
+    pub(crate) fn lfreq_with(
+        &mut self,
+        profiles: &LfreqProfiles,
+    ) -> Option<f64> {
+        let (lang, _) = self.lang()?;
+        profiles.get(&lang)?.distance(&self.buf)
     }
 
     /// Returns the average word length of the document.
@@ -296,6 +355,16 @@ impl Document {
         alpha / total
     }
 
+    /// Returns the dominant Unicode script of the document (the script
+    /// of most letters) and the ratio of right-to-left letters (Hebrew,
+    /// Arabic and Syriac) to all letters.
+    ///
+    /// A document without letters has no dominant script and a ratio
+    /// of $0.0$.
+    pub(crate) fn script(&self) -> (Option<Script>, f64) {
+        script_stats(&self.buf.to_str_lossy())
+    }
+
     /// Returns the type-token ratio (TTR) of the document.
     ///
     /// The TTR is the ratio of unique words (types) to the total number
@@ -317,6 +386,146 @@ impl Document {
 
         unique / total
     }
+
+    /// Returns the (normalized) lines, which occur at least
+    /// `min_repeats` times within the document, and their number of
+    /// occurrences (most frequent first). Empty lines are ignored.
+    pub(crate) fn repeated_lines(
+        &self,
+        min_repeats: usize,
+    ) -> Vec<(String, usize)> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for line in self.buf.to_str_lossy().lines().map(normalize_line)
+        {
+            if !line.is_empty() {
+                *counts.entry(line).or_default() += 1;
+            }
+        }
+
+        let mut lines: Vec<_> = counts
+            .into_iter()
+            .filter(|(_, count)| *count >= min_repeats)
+            .collect();
+        lines
+            .sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        lines
+    }
+
+    /// Returns the repetition score of the document.
+    ///
+    /// The score is the fraction of the non-empty lines, whose
+    /// normalized form (see [normalize_line]) occurs at least
+    /// `min_repeats` times within the document. Heavily paginated
+    /// scans with running headers, footers and page numbers have a
+    /// high score. The score of an empty document is defined to $0.0$.
+    pub(crate) fn repetition(&self, min_repeats: usize) -> f64 {
+        let total = self
+            .buf
+            .to_str_lossy()
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .count();
+
+        if total == 0 {
+            return 0.0;
+        }
+
+        let repeated: usize = self
+            .repeated_lines(min_repeats)
+            .iter()
+            .map(|(_, count)| count)
+            .sum();
+
+        repeated as f64 / total as f64
+    }
+
+    /// Returns the ratio of decimal digits to all characters of the
+    /// document. The ratio of an empty document is defined to $0.0$.
+    pub(crate) fn digit_ratio(&self) -> f64 {
+        let total = self.strlen() as f64;
+        if total <= 0.0 {
+            return 0.0;
+        }
+
+        let digits = self
+            .buf
+            .chars()
+            .filter(|c: &char| c.is_ascii_digit())
+            .count() as f64;
+
+        digits / total
+    }
+
+    /// Returns the ratio of currency tokens to all (whitespace
+    /// separated) tokens of the document.
+    ///
+    /// A token is a currency token, if it contains a currency symbol
+    /// (e.g. `$`, `€` or `£`) or is a common currency code (e.g. `EUR`
+    /// or `DM`). The ratio of an empty document is defined to $0.0$.
+    pub(crate) fn currency_token_ratio(&self) -> f64 {
+        let mut total = 0;
+        let mut currency = 0;
+
+        for token in self.buf.fields() {
+            total += 1;
+            if is_currency_token(&token.to_str_lossy()) {
+                currency += 1;
+            }
+        }
+
+        if total == 0 {
+            return 0.0;
+        }
+
+        currency as f64 / total as f64
+    }
+
+    /// Returns the number of year mentions in the document.
+    ///
+    /// A year mention is a sequence of exactly four digits between
+    /// 1000 and 2099, which isn't part of a longer alphanumeric
+    /// sequence (e.g. `1984` in `1984-1990` or `(1984)`).
+    pub(crate) fn year_mention_count(&self) -> u64 {
+        self.buf
+            .to_str_lossy()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|s| {
+                s.len() == 4
+                    && s.bytes().all(|b| b.is_ascii_digit())
+                    && s.parse::<u16>()
+                        .is_ok_and(|year| (1000..=2099).contains(&year))
+            })
+            .count() as u64
+    }
+}
+
+/// Normalizes a line for the repeated-line detection. Surrounding
+/// whitespace is removed, inner whitespace is collapsed and all digits
+/// are replaced by `0`, so that running headers and footers with page
+/// numbers are considered equal.
+pub(crate) fn normalize_line(line: &str) -> String {
+    line.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .map(|c| if c.is_numeric() { '0' } else { c })
+        .collect()
+}
+
+/// The currency codes, which are counted as currency tokens.
+const CURRENCY_CODES: [&str; 6] =
+    ["EUR", "USD", "GBP", "CHF", "JPY", "DM"];
+
+/// Returns true, if the token contains a currency symbol or is a
+/// currency code (surrounding punctuation is ignored).
+fn is_currency_token(token: &str) -> bool {
+    token.chars().any(|c| {
+        matches!(
+            c,
+            '$' | '¢' | '£' | '¤' | '¥' | '\u{20a0}'..='\u{20c0}'
+        )
+    }) || CURRENCY_CODES
+        .contains(&token.trim_matches(|c: char| !c.is_alphanumeric()))
 }
 
 #[cfg(test)]
@@ -360,6 +569,23 @@ mod tests {
         assert!(Document::from_path("tests/data/cat.txt").is_err());
     }
 
+    #[test]
+    fn document_pages() -> TestResult {
+        let doc = Document::from_path("tests/data/fox.txt")?;
+        assert_eq!(doc.pages().len(), 1);
+
+        let doc = Document::from_parts(
+            doc.path.clone(),
+            doc.metadata.clone(),
+            BString::from("foo\x0cbar baz\x0c"),
+        );
+
+        let pages = doc.pages();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[1].word_count(), 2);
+        Ok(())
+    }
+
     #[test]
     fn document_idn() -> TestResult {
         let doc = Document::from_path("tests/data/fox.txt")?;
@@ -474,6 +700,63 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn document_script() -> TestResult {
+        let doc = Document::from_path("tests/data/fox.txt")?;
+        assert_eq!(doc.script(), (Some(Script::Latin), 0.0));
+        Ok(())
+    }
+
+    #[test]
+    fn document_numeric_metrics() {
+        let doc = Document::from_parts(
+            "price.txt".into(),
+            std::fs::metadata("tests/data/fox.txt").unwrap(),
+            BString::from(
+                "Preis (1984): 12,50 € oder 25 DM; 1984-1990 / 12345",
+            ),
+        );
+
+        assert_abs_diff_eq!(
+            doc.digit_ratio(),
+            23.0 / 51.0,
+            epsilon = 1e-4
+        );
+        assert_abs_diff_eq!(doc.currency_token_ratio(), 2.0 / 10.0);
+        assert_eq!(doc.year_mention_count(), 3);
+    }
+
+    #[test]
+    fn document_normalize_line() {
+        assert_eq!(normalize_line("  Seite  12 \n"), "Seite 00");
+        assert_eq!(normalize_line("Verlag X, 2024"), "Verlag X, 0000");
+    }
+
+    #[test]
+    fn document_repetition() {
+        let doc = Document::from_parts(
+            "scan.txt".into(),
+            std::fs::metadata("tests/data/fox.txt").unwrap(),
+            BString::from(
+                "Kapitel 1\nText\n- 1 -\n\nKapitel 1\nMehr\n- 2 -\n\
+                    Kapitel 1\n- 3 -\n",
+            ),
+        );
+
+        assert_eq!(
+            doc.repeated_lines(MIN_LINE_REPEATS),
+            vec![
+                ("- 0 -".to_string(), 3),
+                ("Kapitel 0".to_string(), 3)
+            ]
+        );
+        assert_abs_diff_eq!(
+            doc.repetition(MIN_LINE_REPEATS),
+            6.0 / 8.0
+        );
+        assert_abs_diff_eq!(doc.repetition(4), 0.0);
+    }
+
     #[test]
     fn document_type_token_ratio() -> TestResult {
         let doc = Document::from_path("tests/data/fox.txt")?;
@@ -0,0 +1,327 @@
+use std::fs::File;
+use std::io::Cursor;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_service_server::{
+    FlightService, FlightServiceServer,
+};
+use arrow_flight::{
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor,
+    FlightEndpoint, FlightInfo, HandshakeRequest, HandshakeResponse,
+    PollInfo, PutResult, SchemaResult, Ticket,
+};
+use arrow_ipc::reader::StreamReader;
+use futures::stream::{self, BoxStream};
+use futures::StreamExt;
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use tonic::transport::Server;
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::datashed::Datashed;
+use crate::error::{bail, DatashedError, DatashedResult};
+use crate::sql::where_expr;
+
+/// The tables exposed by the Flight service.
+const TABLES: [&str; 2] = ["index", "pages"];
+
+/// The ticket of a `DoGet` request (JSON).
+///
+/// ```json
+/// {"table": "index", "predicate": "kind = 'toc'", "columns": ["path"]}
+/// ```
+///
+/// The predicate (an SQL expression) and the projection are evaluated
+/// by the server, so that only the requested data is transferred.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct FlightTicket {
+    #[serde(default = "default_table")]
+    pub(crate) table: String,
+    pub(crate) predicate: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) columns: Vec<String>,
+}
+
+fn default_table() -> String {
+    "index".into()
+}
+
+/// Returns the filename of the given table. If `redacted` is set, the
+/// redacted index is exposed instead of the index and the (unredacted)
+/// page index isn't exposed at all.
+fn filename(table: &str, redacted: bool) -> Option<&'static str> {
+    match table {
+        "index" if redacted => Some(Datashed::REDACTED_INDEX),
+        "index" => Some(Datashed::INDEX),
+        "pages" if !redacted => Some(Datashed::PAGES),
+        _ => None,
+    }
+}
+
+/// An Arrow Flight service, which exposes the index and the derived
+/// tables of a datashed.
+pub(crate) struct FlightServer {
+    base_dir: PathBuf,
+    redacted: bool,
+}
+
+impl FlightServer {
+    pub(crate) fn new(datashed: &Datashed) -> Self {
+        Self {
+            base_dir: datashed.base_dir().clone(),
+            redacted: datashed
+                .config()
+                .map_or(true, |config| !config.redact.is_empty()),
+        }
+    }
+
+    /// Runs the Flight service on the given address.
+    pub(crate) async fn run(
+        self,
+        addr: SocketAddr,
+    ) -> DatashedResult<()> {
+        Server::builder()
+            .add_service(FlightServiceServer::new(self))
+            .serve(addr)
+            .await
+            .map_err(DatashedError::other)
+    }
+
+    /// Loads the requested table, applies the predicate and the
+    /// projection and returns the result as IPC stream.
+    fn load(&self, ticket: &FlightTicket) -> DatashedResult<Vec<u8>> {
+        let Some(filename) = filename(&ticket.table, self.redacted)
+        else {
+            bail!("unknown table '{}'", ticket.table);
+        };
+
+        let df =
+            IpcReader::new(File::open(self.base_dir.join(filename))?)
+                .memory_mapped(None)
+                .finish()?;
+
+        let mut df = if let Some(ref predicate) = ticket.predicate {
+            df.lazy().filter(where_expr(predicate)?).collect()?
+        } else {
+            df
+        };
+
+        if !ticket.columns.is_empty() {
+            df =
+                df.select(ticket.columns.iter().map(String::as_str))?;
+        }
+
+        let mut buf = vec![];
+        IpcStreamWriter::new(&mut buf)
+            .with_compat_level(CompatLevel::oldest())
+            .finish(&mut df)?;
+
+        Ok(buf)
+    }
+}
+
+fn parse_ticket(ticket: &[u8]) -> Result<FlightTicket, Status> {
+    serde_json::from_slice(ticket).map_err(|e| {
+        Status::invalid_argument(format!("invalid ticket: {e}"))
+    })
+}
+
+type FlightStream<T> = BoxStream<'static, Result<T, Status>>;
+
+#[tonic::async_trait]
+impl FlightService for FlightServer {
+    type HandshakeStream = FlightStream<HandshakeResponse>;
+    type ListFlightsStream = FlightStream<FlightInfo>;
+    type DoGetStream = FlightStream<FlightData>;
+    type DoPutStream = FlightStream<PutResult>;
+    type DoActionStream = FlightStream<arrow_flight::Result>;
+    type ListActionsStream = FlightStream<ActionType>;
+    type DoExchangeStream = FlightStream<FlightData>;
+
+    async fn handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("handshake"))
+    }
+
+    async fn list_flights(
+        &self,
+        _request: Request<Criteria>,
+    ) -> Result<Response<Self::ListFlightsStream>, Status> {
+        let infos: Vec<Result<FlightInfo, Status>> = TABLES
+            .iter()
+            .filter(|table| {
+                filename(table, self.redacted).is_some_and(|name| {
+                    self.base_dir.join(name).is_file()
+                })
+            })
+            .map(|table| {
+                let ticket = serde_json::to_vec(&FlightTicket {
+                    table: table.to_string(),
+                    ..Default::default()
+                })
+                .expect("valid ticket");
+
+                Ok(FlightInfo::new()
+                    .with_descriptor(FlightDescriptor::new_path(vec![
+                        table.to_string(),
+                    ]))
+                    .with_endpoint(
+                        FlightEndpoint::new()
+                            .with_ticket(Ticket::new(ticket)),
+                    ))
+            })
+            .collect();
+
+        Ok(Response::new(stream::iter(infos).boxed()))
+    }
+
+    async fn get_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        Err(Status::unimplemented("get_flight_info"))
+    }
+
+    async fn poll_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented("poll_flight_info"))
+    }
+
+    async fn get_schema(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<SchemaResult>, Status> {
+        Err(Status::unimplemented("get_schema"))
+    }
+
+    async fn do_get(
+        &self,
+        request: Request<Ticket>,
+    ) -> Result<Response<Self::DoGetStream>, Status> {
+        let ticket = parse_ticket(&request.into_inner().ticket)?;
+        let buf = self
+            .load(&ticket)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let reader = StreamReader::try_new(Cursor::new(buf), None)
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let batches: Vec<_> = reader
+            .map(|batch| batch.map_err(FlightError::from))
+            .collect();
+
+        let stream = FlightDataEncoderBuilder::new()
+            .build(stream::iter(batches))
+            .map(|data| {
+                data.map_err(|e| Status::internal(e.to_string()))
+            });
+
+        Ok(Response::new(stream.boxed()))
+    }
+
+    async fn do_put(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("do_put"))
+    }
+
+    async fn do_action(
+        &self,
+        _request: Request<Action>,
+    ) -> Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("do_action"))
+    }
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::ListActionsStream>, Status> {
+        Ok(Response::new(stream::empty().boxed()))
+    }
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("do_exchange"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn flight_parse_ticket() -> TestResult {
+        let ticket = parse_ticket(b"{}")?;
+        assert_eq!(ticket.table, "index");
+        assert!(ticket.predicate.is_none());
+        assert!(ticket.columns.is_empty());
+
+        let ticket = parse_ticket(
+            br#"{"table":"pages","predicate":"kind = 'toc'"}"#,
+        )?;
+        assert_eq!(ticket.table, "pages");
+        assert_eq!(ticket.predicate.as_deref(), Some("kind = 'toc'"));
+
+        assert!(parse_ticket(b"index").is_err());
+        assert!(parse_ticket(br#"{"columns":"path"}"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn flight_filename() {
+        assert_eq!(filename("index", false), Some(Datashed::INDEX));
+        assert_eq!(
+            filename("index", true),
+            Some(Datashed::REDACTED_INDEX)
+        );
+        assert_eq!(filename("pages", false), Some(Datashed::PAGES));
+        assert_eq!(filename("pages", true), None);
+        assert_eq!(filename("ratings", false), None);
+    }
+
+    #[test]
+    fn flight_load() -> TestResult {
+        let dir = temp_dir()?;
+        let mut df = df!(
+            "path" => ["a.txt", "b.txt", "c.txt"],
+            "kind" => ["toc", "book", "toc"],
+        )?;
+        IpcWriter::new(File::create(dir.path().join(Datashed::INDEX))?)
+            .finish(&mut df)?;
+
+        let server = FlightServer {
+            base_dir: dir.path().into(),
+            redacted: false,
+        };
+
+        let buf = server.load(&FlightTicket {
+            table: "index".into(),
+            predicate: Some("kind = 'toc'".into()),
+            columns: vec!["path".into()],
+        })?;
+
+        let df = IpcStreamReader::new(Cursor::new(buf)).finish()?;
+        assert_eq!(df.width(), 1);
+        assert!(df.column("path").is_ok());
+        assert_eq!(df.height(), 2);
+
+        let ticket = FlightTicket {
+            table: "ratings".into(),
+            ..Default::default()
+        };
+        assert!(server.load(&ticket).is_err());
+        Ok(())
+    }
+}
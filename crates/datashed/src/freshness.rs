@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+use std::{fs, io};
+
+use crate::config::StalePolicy;
+use crate::datashed::Datashed;
+use crate::error::{bail, DatashedResult};
+
+/// The stale-index policy of the current process (`--allow-stale` or
+/// `runtime.stale_index`).
+static POLICY: OnceLock<StalePolicy> = OnceLock::new();
+
+/// The results of the freshness check (by root directory) and the
+/// modification time of the index, which was checked. The check is
+/// repeated, if the index has been rewritten since (e.g. by the
+/// reindexer of `datashed serve`).
+static STALE: Mutex<BTreeMap<PathBuf, (Option<SystemTime>, bool)>> =
+    Mutex::new(BTreeMap::new());
+
+/// Sets the stale-index policy of the current process. Subsequent calls
+/// have no effect.
+pub(crate) fn init(policy: StalePolicy) {
+    let _ = POLICY.set(policy);
+}
+
+/// Returns the latest modification time of the data directory `dir`
+/// and its immediate subdirectories (e.g. the kind directories). Since
+/// adding or removing a file changes the modification time of its
+/// parent directory, added and removed documents are taken into
+/// account without walking the whole data directory; documents, which
+/// are changed in place, are not.
+fn latest_mtime(dir: &Path) -> io::Result<Option<SystemTime>> {
+    let mut latest = fs::metadata(dir)?.modified().ok();
+
+    for entry in fs::read_dir(dir)? {
+        let metadata = entry?.metadata()?;
+        if metadata.is_dir() {
+            latest = latest.max(metadata.modified().ok());
+        }
+    }
+
+    Ok(latest)
+}
+
+/// Returns true, if the data directory has been modified after the
+/// index was written.
+pub(crate) fn is_stale(datashed: &Datashed) -> DatashedResult<bool> {
+    let index = datashed.base_dir().join(Datashed::INDEX);
+    let Ok(written) = fs::metadata(index).and_then(|m| m.modified())
+    else {
+        return Ok(false);
+    };
+
+    let data_dir = datashed.data_dir();
+    if !data_dir.is_dir() {
+        return Ok(false);
+    }
+
+    Ok(latest_mtime(&data_dir)?.is_some_and(|mtime| mtime > written))
+}
+
+/// Checks whether the index of the datashed is outdated and, depending
+/// on the stale-index policy, prints a warning or fails. All reads of
+/// the index must call this function.
+pub(crate) fn check(datashed: &Datashed) -> DatashedResult<()> {
+    let policy = POLICY.get().copied().unwrap_or_default();
+    if policy == StalePolicy::Ignore {
+        return Ok(());
+    }
+
+    let written =
+        fs::metadata(datashed.base_dir().join(Datashed::INDEX))
+            .and_then(|m| m.modified())
+            .ok();
+
+    let stale = {
+        let mut cache = STALE.lock().unwrap();
+        match cache.get(datashed.base_dir()) {
+            Some((mtime, stale)) if *mtime == written => *stale,
+            _ => {
+                let stale = is_stale(datashed).unwrap_or(false);
+                cache.insert(
+                    datashed.base_dir().clone(),
+                    (written, stale),
+                );
+                stale
+            }
+        }
+    };
+
+    if !stale {
+        return Ok(());
+    }
+
+    match policy {
+        StalePolicy::Error => bail!(
+            "the index is older than the data directory; update the \
+            index with `datashed index` or pass `--allow-stale`"
+        ),
+        _ => {
+            eprintln!(
+                "warning: the index is older than the data directory \
+                (update the index with `datashed index`)."
+            );
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::testing::temp_datashed;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn stale_index() -> TestResult {
+        let (_dir, datashed) = temp_datashed()?;
+        let root_dir = datashed.base_dir();
+        fs::create_dir_all(root_dir.join("data/book"))?;
+        fs::write(root_dir.join("data/book/1.txt"), "foo")?;
+
+        assert!(!is_stale(&datashed)?);
+
+        std::thread::sleep(Duration::from_millis(20));
+        File::create(root_dir.join(Datashed::INDEX))?;
+        assert!(!is_stale(&datashed)?);
+
+        std::thread::sleep(Duration::from_millis(20));
+        fs::write(root_dir.join("data/book/2.txt"), "bar")?;
+        assert!(is_stale(&datashed)?);
+        Ok(())
+    }
+}
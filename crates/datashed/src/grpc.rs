@@ -0,0 +1,325 @@
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::stream::{self, BoxStream};
+use polars::prelude::*;
+use tonic::transport::Server;
+use tonic::{Code, Request, Response, Status};
+
+use crate::access::{self, SharedAccessMap, SharedWriter};
+use crate::config::Config;
+use crate::datashed::Datashed;
+use crate::document::Document;
+use crate::error::{DatashedError, DatashedResult};
+use crate::ratings::is_valid_hash;
+use crate::sql::where_expr;
+
+/// The generated messages and service traits (see
+/// `proto/datashed.proto`).
+pub(crate) mod proto {
+    tonic::include_proto!("datashed.v1");
+}
+
+use proto::shed_server::{Shed, ShedServer};
+use proto::{
+    GetDocumentRequest, HealthRequest, HealthResponse, IndexRecord,
+    QueryIndexRequest, SubmitRatingRequest, SubmitRatingResponse,
+};
+
+/// The index columns, which are part of each [IndexRecord].
+const RECORD_COLUMNS: [&str; 4] = ["path", "hash", "kind", "idn"];
+
+/// A gRPC service, which exposes typed operations of a datashed
+/// (index query, document fetch, rating submission and health).
+pub(crate) struct GrpcServer {
+    datashed: Datashed,
+    acl: SharedAccessMap,
+    wtr: SharedWriter,
+    audit: SharedWriter,
+}
+
+impl GrpcServer {
+    /// Creates a new service, which shares the access map, the ratings
+    /// writer and the audit log with the HTTP server (see `datashed
+    /// serve`).
+    pub(crate) fn new(
+        datashed: Datashed,
+        acl: SharedAccessMap,
+        wtr: SharedWriter,
+        audit: SharedWriter,
+    ) -> Self {
+        Self {
+            datashed,
+            acl,
+            wtr,
+            audit,
+        }
+    }
+
+    /// Runs the gRPC service on the given address.
+    pub(crate) async fn run(
+        self,
+        addr: SocketAddr,
+    ) -> DatashedResult<()> {
+        Server::builder()
+            .add_service(ShedServer::new(self))
+            .serve(addr)
+            .await
+            .map_err(DatashedError::other)
+    }
+
+    fn config(&self) -> Result<Config, Status> {
+        self.datashed
+            .config()
+            .map_err(|e| Status::internal(e.to_string()))
+    }
+
+    /// Loads the published index (the redacted index, if the config
+    /// contains redaction policies), applies the predicate and returns
+    /// the requested records.
+    fn query(
+        &self,
+        req: &QueryIndexRequest,
+        config: &Config,
+    ) -> DatashedResult<Vec<IndexRecord>> {
+        let filename = if config.redact.is_empty() {
+            Datashed::INDEX
+        } else {
+            Datashed::REDACTED_INDEX
+        };
+
+        let mut index = LazyFrame::scan_ipc(
+            self.datashed.base_dir().join(filename),
+            ScanArgsIpc::default(),
+        )?;
+
+        if !req.predicate.is_empty() {
+            index = index.filter(where_expr(&req.predicate)?);
+        }
+
+        if req.limit > 0 {
+            index = index.limit(req.limit as IdxSize);
+        }
+
+        let df = index.collect()?;
+        let columns = RECORD_COLUMNS
+            .iter()
+            .map(|name| name.to_string())
+            .chain(req.columns.iter().cloned())
+            .map(|name| {
+                let column = match df.column(&name) {
+                    Ok(column) => column.cast(&DataType::String)?,
+                    Err(_)
+                        if RECORD_COLUMNS.contains(&name.as_str()) =>
+                    {
+                        Column::full_null(
+                            name.as_str().into(),
+                            df.height(),
+                            &DataType::String,
+                        )
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+
+                Ok((name, column.str()?.clone()))
+            })
+            .collect::<DatashedResult<Vec<_>>>()?;
+
+        let mut records = Vec::with_capacity(df.height());
+        for idx in 0..df.height() {
+            let value = |pos: usize| {
+                columns[pos].1.get(idx).unwrap_or_default().to_string()
+            };
+
+            records.push(IndexRecord {
+                path: value(0),
+                hash: value(1),
+                kind: value(2),
+                idn: value(3),
+                values: columns[RECORD_COLUMNS.len()..]
+                    .iter()
+                    .filter_map(|(name, values)| {
+                        values
+                            .get(idx)
+                            .map(|v| (name.clone(), v.into()))
+                    })
+                    .collect(),
+            });
+        }
+
+        Ok(records)
+    }
+}
+
+/// Checks that the path refers to a file within the data directory.
+fn check_path(path: &str) -> Result<(), Status> {
+    let Some(tail) =
+        path.strip_prefix(&format!("{}/", Datashed::DATA_DIR))
+    else {
+        return Err(Status::invalid_argument("invalid path"));
+    };
+
+    if tail.split('/').any(|c| c == ".." || c.is_empty()) {
+        return Err(Status::invalid_argument("invalid path"));
+    }
+
+    Ok(())
+}
+
+/// Returns the name of the authenticated user (see
+/// [access::authenticate]).
+fn authenticate<T>(
+    req: &Request<T>,
+    config: &Config,
+) -> Option<String> {
+    let value = req.metadata().get("authorization")?;
+    access::authenticate(value.to_str().ok()?, config)
+}
+
+#[tonic::async_trait]
+impl Shed for GrpcServer {
+    type QueryIndexStream =
+        BoxStream<'static, Result<IndexRecord, Status>>;
+
+    async fn health(
+        &self,
+        _request: Request<HealthRequest>,
+    ) -> Result<Response<HealthResponse>, Status> {
+        let config = self.config()?;
+
+        Ok(Response::new(HealthResponse {
+            name: config.metadata.name,
+            version: config.metadata.version.to_string(),
+            server_version: env!("CARGO_PKG_VERSION").into(),
+        }))
+    }
+
+    async fn query_index(
+        &self,
+        request: Request<QueryIndexRequest>,
+    ) -> Result<Response<Self::QueryIndexStream>, Status> {
+        let config = self.config()?;
+        let records = self
+            .query(request.get_ref(), &config)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        Ok(Response::new(Box::pin(stream::iter(
+            records.into_iter().map(Ok),
+        ))))
+    }
+
+    async fn get_document(
+        &self,
+        request: Request<GetDocumentRequest>,
+    ) -> Result<Response<proto::Document>, Status> {
+        let config = self.config()?;
+        let username = authenticate(&request, &config);
+        let path = &request.get_ref().path;
+        check_path(path)?;
+
+        let user =
+            username.as_ref().and_then(|name| config.users.get(name));
+        let allowed =
+            access::snapshot(&self.acl).is_allowed(path, user);
+        let result = if !allowed {
+            Err(if user.is_none() {
+                Status::unauthenticated("authentication required")
+            } else {
+                Status::permission_denied("access denied")
+            })
+        } else {
+            Document::from_path(self.datashed.base_dir().join(path))
+                .map_err(|_| {
+                    Status::not_found(format!("{path} not found"))
+                })
+        };
+
+        // The audit log records the status codes of the HTTP server.
+        let status = match result {
+            Ok(_) => "200",
+            Err(ref e) if e.code() == Code::Unauthenticated => "401",
+            Err(ref e) if e.code() == Code::PermissionDenied => "403",
+            Err(_) => "404",
+        };
+        access::audit(&self.audit, username.as_deref(), path, status);
+
+        let doc = result?;
+        Ok(Response::new(proto::Document {
+            path: path.clone(),
+            hash: doc.hash(),
+            content: doc.as_ref().to_vec(),
+        }))
+    }
+
+    async fn submit_rating(
+        &self,
+        request: Request<SubmitRatingRequest>,
+    ) -> Result<Response<SubmitRatingResponse>, Status> {
+        let config = self.config()?;
+        let Some(username) = authenticate(&request, &config) else {
+            return Err(Status::unauthenticated("invalid credentials"));
+        };
+
+        let req = request.into_inner();
+        check_path(&req.path)?;
+        if !self.datashed.base_dir().join(&req.path).is_file() {
+            return Err(Status::not_found(format!(
+                "{} not found",
+                req.path
+            )));
+        }
+
+        if !is_valid_hash(&req.hash) {
+            return Err(Status::invalid_argument("invalid hash"));
+        }
+
+        let campaign =
+            Some(req.campaign.as_str()).filter(|c| !c.is_empty());
+        let scale = config
+            .rating_scale(campaign)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let rating = scale
+            .level(&req.rating)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?
+            .label
+            .clone();
+
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis()
+            .to_string();
+
+        let mut writer = self.wtr.lock().unwrap();
+        let result = writer.write_record([
+            &config.metadata.name,
+            &req.path,
+            &req.hash,
+            &rating,
+            &req.comment,
+            &username,
+            &created_at,
+        ]);
+
+        if result.is_err() {
+            return Err(Status::internal("could not write record"));
+        }
+
+        let _ = writer.flush();
+
+        Ok(Response::new(SubmitRatingResponse { rating }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grpc_check_path() {
+        assert!(check_path("data/book/1.txt").is_ok());
+        assert!(check_path("data/../datashed.toml").is_err());
+        assert!(check_path("data//1.txt").is_err());
+        assert!(check_path("tmp/ratings.csv").is_err());
+    }
+}
@@ -0,0 +1,294 @@
+use std::fs::read;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use reqwest::{
+    Certificate, Client, Proxy, RequestBuilder, Response, StatusCode,
+    Url,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{DatashedError, DatashedResult};
+
+/// HTTP client options.
+///
+/// ```toml
+/// [http]
+/// connect-timeout = 10
+/// timeout = 60
+/// retries = 3
+/// backoff = 500
+/// proxy = "http://proxy.example.com:3128"
+/// ca-bundle = "/etc/ssl/certs/internal-ca.pem"
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct HttpConfig {
+    /// The timeout (in seconds) for establishing a connection.
+    pub(crate) connect_timeout: Option<u64>,
+
+    /// The timeout (in seconds) of a whole request, including the
+    /// transfer of the response body.
+    pub(crate) timeout: Option<u64>,
+
+    /// The number of retries of failed requests (connection errors,
+    /// timeouts and status codes 429 and 5xx).
+    pub(crate) retries: Option<u32>,
+
+    /// The initial delay (in milliseconds) between two retries. The
+    /// delay is doubled on each retry.
+    pub(crate) backoff: Option<u64>,
+
+    /// The proxy used for all requests. If not set, the proxy is taken
+    /// from the environment (`HTTP_PROXY`, `HTTPS_PROXY`).
+    pub(crate) proxy: Option<String>,
+
+    /// A PEM file with additional root certificates.
+    pub(crate) ca_bundle: Option<PathBuf>,
+}
+
+impl HttpConfig {
+    /// Returns `true` if no option is set.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.connect_timeout.is_none()
+            && self.timeout.is_none()
+            && self.retries.is_none()
+            && self.backoff.is_none()
+            && self.proxy.is_none()
+            && self.ca_bundle.is_none()
+    }
+}
+
+/// A HTTP client, which retries failed requests with an exponential
+/// backoff.
+#[derive(Debug, Clone)]
+pub(crate) struct HttpClient {
+    client: Client,
+    retries: u32,
+    backoff: Duration,
+}
+
+impl HttpClient {
+    const DEFAULT_CONNECT_TIMEOUT: u64 = 10;
+    const DEFAULT_RETRIES: u32 = 3;
+    const DEFAULT_BACKOFF: u64 = 500;
+
+    pub(crate) fn from_config(
+        config: &HttpConfig,
+    ) -> DatashedResult<Self> {
+        let mut builder =
+            Client::builder().connect_timeout(Duration::from_secs(
+                config
+                    .connect_timeout
+                    .unwrap_or(Self::DEFAULT_CONNECT_TIMEOUT),
+            ));
+
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(Duration::from_secs(timeout));
+        }
+
+        if let Some(ref proxy) = config.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+
+        if let Some(ref path) = config.ca_bundle {
+            for cert in Certificate::from_pem_bundle(&read(path)?)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        Ok(Self {
+            client: builder.build()?,
+            retries: config.retries.unwrap_or(Self::DEFAULT_RETRIES),
+            backoff: Duration::from_millis(
+                config.backoff.unwrap_or(Self::DEFAULT_BACKOFF),
+            ),
+        })
+    }
+
+    /// Returns the delay before the given retry (starting at 0).
+    fn delay(&self, retry: u32) -> Duration {
+        self.backoff * 2u32.saturating_pow(retry)
+    }
+
+    /// Sends the request built by `f`. The request is retried, if it
+    /// fails with a connection error, a timeout or a status code,
+    /// which indicates a temporary failure (429 and 5xx).
+    pub(crate) async fn send<F>(&self, f: F) -> DatashedResult<Response>
+    where
+        F: Fn(&Client) -> RequestBuilder,
+    {
+        self.send_with_retries(f, true).await
+    }
+
+    /// Sends the non-idempotent request built by `f` (e.g. a POST
+    /// request). The request is only retried, if the connection
+    /// couldn't be established or the server rejected it (429); after
+    /// a timeout or a server error the request may have been processed
+    /// already.
+    pub(crate) async fn send_once<F>(
+        &self,
+        f: F,
+    ) -> DatashedResult<Response>
+    where
+        F: Fn(&Client) -> RequestBuilder,
+    {
+        self.send_with_retries(f, false).await
+    }
+
+    async fn send_with_retries<F>(
+        &self,
+        f: F,
+        idempotent: bool,
+    ) -> DatashedResult<Response>
+    where
+        F: Fn(&Client) -> RequestBuilder,
+    {
+        let mut retry = 0;
+
+        loop {
+            let result = f(&self.client).send().await;
+            let retryable = match result {
+                Ok(ref response) => {
+                    response.status() == StatusCode::TOO_MANY_REQUESTS
+                        || (idempotent
+                            && response.status().is_server_error())
+                }
+                Err(ref e) => {
+                    e.is_connect() || (idempotent && e.is_timeout())
+                }
+            };
+
+            if !retryable || retry >= self.retries {
+                return result.map_err(DatashedError::from);
+            }
+
+            tokio::time::sleep(self.delay(retry)).await;
+            retry += 1;
+        }
+    }
+
+    /// Sends a GET request.
+    #[inline]
+    pub(crate) async fn get(
+        &self,
+        url: Url,
+    ) -> DatashedResult<Response> {
+        self.send(|client| client.get(url.clone())).await
+    }
+}
+
+/// A blocking HTTP client (e.g. for the worker threads of `datashed
+/// index`), which honors the same options and retries failed requests
+/// like [HttpClient].
+#[derive(Debug, Clone)]
+pub(crate) struct BlockingHttpClient {
+    client: reqwest::blocking::Client,
+    retries: u32,
+    backoff: Duration,
+}
+
+impl BlockingHttpClient {
+    pub(crate) fn from_config(
+        config: &HttpConfig,
+    ) -> DatashedResult<Self> {
+        let mut builder = reqwest::blocking::Client::builder()
+            .connect_timeout(Duration::from_secs(
+                config
+                    .connect_timeout
+                    .unwrap_or(HttpClient::DEFAULT_CONNECT_TIMEOUT),
+            ));
+
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(Duration::from_secs(timeout));
+        }
+
+        if let Some(ref proxy) = config.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
+        }
+
+        if let Some(ref path) = config.ca_bundle {
+            for cert in Certificate::from_pem_bundle(&read(path)?)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        Ok(Self {
+            client: builder.build()?,
+            retries: config
+                .retries
+                .unwrap_or(HttpClient::DEFAULT_RETRIES),
+            backoff: Duration::from_millis(
+                config.backoff.unwrap_or(HttpClient::DEFAULT_BACKOFF),
+            ),
+        })
+    }
+
+    /// Sends the non-idempotent request built by `f` (see
+    /// [HttpClient::send_once]).
+    pub(crate) fn send_once<F>(
+        &self,
+        f: F,
+    ) -> DatashedResult<reqwest::blocking::Response>
+    where
+        F: Fn(
+            &reqwest::blocking::Client,
+        ) -> reqwest::blocking::RequestBuilder,
+    {
+        self.send_with_retries(f, false)
+    }
+
+    fn send_with_retries<F>(
+        &self,
+        f: F,
+        idempotent: bool,
+    ) -> DatashedResult<reqwest::blocking::Response>
+    where
+        F: Fn(
+            &reqwest::blocking::Client,
+        ) -> reqwest::blocking::RequestBuilder,
+    {
+        let mut retry = 0;
+
+        loop {
+            let result = f(&self.client).send();
+            let retryable = match result {
+                Ok(ref response) => {
+                    response.status() == StatusCode::TOO_MANY_REQUESTS
+                        || (idempotent
+                            && response.status().is_server_error())
+                }
+                Err(ref e) => {
+                    e.is_connect() || (idempotent && e.is_timeout())
+                }
+            };
+
+            if !retryable || retry >= self.retries {
+                return result.map_err(DatashedError::from);
+            }
+
+            thread::sleep(self.backoff * 2u32.saturating_pow(retry));
+            retry += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn http_client_delay() -> TestResult {
+        let client = HttpClient::from_config(&HttpConfig {
+            backoff: Some(100),
+            ..Default::default()
+        })?;
+
+        assert_eq!(client.delay(0), Duration::from_millis(100));
+        assert_eq!(client.delay(3), Duration::from_millis(800));
+        Ok(())
+    }
+}
@@ -1,12 +1,26 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::sync::OnceLock;
 
 use bstr::{BString, ByteSlice};
 use ndarray::Array1;
 use ndarray_stats::DeviationExt;
+use serde::Deserialize;
 use unicode_normalization::UnicodeNormalization;
 
+use crate::config::Config;
+use crate::error::{bail, DatashedError, DatashedResult};
+
+/// Reference profiles shipped with datashed.
+const EMBEDDED: &str = include_str!("../data/lfreq.toml");
+
 #[inline]
-fn frequencies(buf: &BString, alphabet: &[char]) -> HashMap<char, u64> {
+pub(crate) fn frequencies(
+    buf: &BString,
+    alphabet: &[char],
+) -> HashMap<char, u64> {
     buf.chars()
         .nfc()
         .to_string()
@@ -22,63 +36,173 @@ fn frequencies(buf: &BString, alphabet: &[char]) -> HashMap<char, u64> {
         })
 }
 
-pub(crate) fn lfreq_ger(buf: &BString) -> Option<f64> {
-    let alphabet: Vec<char> =
-        "abcdefghijklmnopqrstuvwxyzßäöü".chars().collect();
-
-    let freqs = frequencies(buf, &alphabet);
-    let n: f64 = freqs.values().sum::<u64>() as f64;
-    let x = if n > 0.0 {
-        Array1::from_iter(
-            alphabet
-                .iter()
-                .map(|c| *freqs.get(c).unwrap_or(&0) as f64 / n),
-        )
-    } else {
-        Array1::zeros(alphabet.len())
-    };
-
-    let y = Array1::from_vec(vec![
-        0.06006, 0.02148, 0.02690, 0.04718, 0.16006, 0.01832, 0.03064,
-        0.04249, 0.07752, 0.00297, 0.01536, 0.03787, 0.02798, 0.09660,
-        0.02684, 0.01049, 0.00028, 0.07737, 0.06343, 0.06369, 0.03820,
-        0.00918, 0.01427, 0.00051, 0.00107, 0.01237, 0.00170, 0.00548,
-        0.00269, 0.00683,
-    ]);
-
-    x.l2_dist(&y).ok()
+/// A row of a CSV file containing reference letter frequencies.
+#[derive(Debug, Deserialize)]
+struct Entry {
+    lang: Option<String>,
+    letter: String,
+    frequency: f64,
 }
 
-pub(crate) fn lfreq_eng(buf: &BString) -> Option<f64> {
-    let alphabet: Vec<char> =
-        "abcdefghijklmnopqrstuvwxyz".chars().collect();
-
-    let freqs = frequencies(buf, &alphabet);
-    let n: f64 = freqs.values().sum::<u64>() as f64;
-    let x = if n > 0.0 {
-        Array1::from_iter(
-            alphabet
-                .iter()
-                .map(|c| *freqs.get(c).unwrap_or(&0) as f64 / n),
-        )
-    } else {
-        Array1::zeros(alphabet.len())
-    };
-
-    let y = Array1::from_vec(vec![
-        0.08167, 0.01492, 0.02782, 0.04253, 0.12702, 0.02228, 0.02015,
-        0.06094, 0.06966, 0.00253, 0.01772, 0.04025, 0.02406, 0.06749,
-        0.07507, 0.01929, 0.00950, 0.05987, 0.06327, 0.09056, 0.02758,
-        0.00978, 0.02360, 0.00250, 0.01974, 0.00074,
-    ]);
-
-    x.l2_dist(&y).ok()
+/// A reference profile of relative letter frequencies.
+///
+/// The letters of the profile define the alphabet, which is used to
+/// compute the letter frequencies of a document.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct LfreqProfile(BTreeMap<char, f64>);
+
+impl LfreqProfile {
+    /// Loads a single profile from a CSV file with the columns
+    /// `letter` and `frequency`. Additional columns are ignored.
+    pub(crate) fn from_csv<P: AsRef<Path>>(
+        path: P,
+    ) -> DatashedResult<Self> {
+        let mut reader = csv::Reader::from_path(path)?;
+        let mut profile = BTreeMap::new();
+
+        for result in reader.deserialize() {
+            let entry: Entry = result?;
+            profile
+                .insert(parse_letter(&entry.letter)?, entry.frequency);
+        }
+
+        Ok(Self(profile))
+    }
+
+    /// Returns the alphabet of the profile.
+    pub(crate) fn alphabet(&self) -> Vec<char> {
+        self.0.keys().copied().collect()
+    }
+
+    /// Returns the L2 distance between the letter frequencies of the
+    /// buffer and the reference frequencies.
+    pub(crate) fn distance(&self, buf: &BString) -> Option<f64> {
+        self.distance_from_counts(&frequencies(buf, &self.alphabet()))
+    }
+
+    /// Returns the L2 distance between the given absolute letter
+    /// counts and the reference frequencies. Letters which are not
+    /// part of the profile's alphabet are ignored.
+    pub(crate) fn distance_from_counts(
+        &self,
+        counts: &HashMap<char, u64>,
+    ) -> Option<f64> {
+        let n: f64 = self
+            .0
+            .keys()
+            .map(|c| *counts.get(c).unwrap_or(&0))
+            .sum::<u64>() as f64;
+
+        let x = if n > 0.0 {
+            Array1::from_iter(
+                self.0
+                    .keys()
+                    .map(|c| *counts.get(c).unwrap_or(&0) as f64 / n),
+            )
+        } else {
+            Array1::zeros(self.0.len())
+        };
+
+        let y = Array1::from_iter(self.0.values().copied());
+        x.l2_dist(&y).ok()
+    }
+}
+
+/// A set of reference profiles keyed by language code.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct LfreqProfiles(HashMap<String, LfreqProfile>);
+
+#[inline]
+fn parse_letter(s: &str) -> DatashedResult<char> {
+    let s = s.nfc().collect::<String>().to_lowercase();
+    let mut chars = s.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => bail!("invalid letter '{s}'"),
+    }
+}
+
+impl LfreqProfiles {
+    /// Returns the reference profiles shipped with datashed.
+    pub(crate) fn embedded() -> &'static Self {
+        static PROFILES: OnceLock<LfreqProfiles> = OnceLock::new();
+        PROFILES.get_or_init(|| {
+            toml::from_str(EMBEDDED).expect("valid lfreq profiles")
+        })
+    }
+
+    /// Loads a set of profiles from a TOML file (a table per language
+    /// code) or a CSV file with the columns `lang`, `letter` and
+    /// `frequency`.
+    pub(crate) fn from_path<P: AsRef<Path>>(
+        path: P,
+    ) -> DatashedResult<Self> {
+        let path = path.as_ref();
+
+        match path.extension().and_then(OsStr::to_str) {
+            Some("toml") => Ok(toml::from_str(&read_to_string(path)?)?),
+            Some("csv") => {
+                let mut reader = csv::Reader::from_path(path)?;
+                let mut profiles =
+                    HashMap::<String, LfreqProfile>::new();
+
+                for result in reader.deserialize() {
+                    let entry: Entry = result?;
+                    let Some(lang) = entry.lang else {
+                        bail!("missing language code");
+                    };
+
+                    profiles.entry(lang).or_default().0.insert(
+                        parse_letter(&entry.letter)?,
+                        entry.frequency,
+                    );
+                }
+
+                Ok(Self(profiles))
+            }
+            _ => Err(DatashedError::other(format!(
+                "unsupported lfreq profiles '{}'",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Returns the embedded profiles extended (or overwritten) by the
+    /// user-provided profiles of the datashed config.
+    pub(crate) fn from_config<P: AsRef<Path>>(
+        config: &Config,
+        base_dir: P,
+    ) -> DatashedResult<Self> {
+        let mut profiles = Self::embedded().clone();
+
+        if let Some(path) = config
+            .lfreq
+            .as_ref()
+            .and_then(|lfreq| lfreq.profiles.as_ref())
+        {
+            let user = Self::from_path(base_dir.as_ref().join(path))?;
+            profiles.0.extend(user.0);
+        }
+
+        Ok(profiles)
+    }
+
+    /// Returns the profile of the given language.
+    #[inline]
+    pub(crate) fn get(&self, lang: &str) -> Option<&LfreqProfile> {
+        self.0.get(lang)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use bstr::BString;
 
+    use super::*;
+
     type TestResult = anyhow::Result<()>;
 
     #[test]
@@ -95,4 +219,50 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn embedded_profiles() {
+        let profiles = LfreqProfiles::embedded();
+        for lang in ["eng", "fre", "ger", "ita", "lat", "spa"] {
+            assert!(profiles.get(lang).is_some());
+        }
+
+        let ger = profiles.get("ger").unwrap();
+        assert_eq!(ger.alphabet().len(), 30);
+        assert!(ger.alphabet().contains(&'ß'));
+    }
+
+    #[test]
+    fn parse_letter() {
+        use super::parse_letter;
+
+        assert_eq!(parse_letter("A").unwrap(), 'a');
+        assert_eq!(parse_letter("Ä").unwrap(), 'ä');
+        assert!(parse_letter("ab").is_err());
+        assert!(parse_letter("").is_err());
+    }
+
+    #[test]
+    fn profile_from_csv() -> TestResult {
+        let dir = crate::testing::temp_dir()?;
+        let path = dir.path().join("profile.csv");
+
+        // the output of `datashed lfreq --aggregate`
+        std::fs::write(
+            &path,
+            "letter,count,frequency\nA,3,0.75\nb,1,0.25\n",
+        )?;
+
+        let profile = LfreqProfile::from_csv(&path)?;
+        assert_eq!(profile.alphabet(), ['a', 'b']);
+
+        let counts = HashMap::from([('a', 6), ('b', 2), ('c', 5)]);
+        assert_eq!(profile.distance_from_counts(&counts), Some(0.0));
+
+        let counts = HashMap::from([('a', 1), ('b', 1)]);
+        let distance = profile.distance_from_counts(&counts).unwrap();
+        assert!((distance - 0.125f64.sqrt()).abs() < 1e-9);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use polars::prelude::*;
+
+use crate::config::{Config, LicenseTerms};
+use crate::error::DatashedResult;
+
+/// The name of the license manifest.
+pub(crate) const MANIFEST: &str = "LICENSES";
+
+/// The license code of documents without a code.
+const UNKNOWN: &str = "unknown";
+
+/// The documents of a license code.
+#[derive(Debug, Default)]
+pub(crate) struct LicenseGroup {
+    pub(crate) terms: LicenseTerms,
+    pub(crate) docs: u64,
+    pub(crate) size: u64,
+    pub(crate) paths: Vec<String>,
+}
+
+/// Groups the documents of the index by their license code.
+pub(crate) fn groups(
+    config: &Config,
+    df: &DataFrame,
+) -> DatashedResult<BTreeMap<String, LicenseGroup>> {
+    let mut groups: BTreeMap<String, LicenseGroup> = BTreeMap::new();
+    let path = df.column("path")?.str()?;
+    let size = df.column("size")?.cast(&DataType::UInt64)?;
+    let size = size.u64()?;
+    let license = match df.column("license") {
+        Ok(column) => column.cast(&DataType::String)?,
+        Err(_) => Column::full_null(
+            "license".into(),
+            df.height(),
+            &DataType::String,
+        ),
+    };
+
+    for (idx, code) in license.str()?.iter().enumerate() {
+        let code = code.unwrap_or(UNKNOWN);
+        let group =
+            groups.entry(code.to_string()).or_insert_with(|| {
+                LicenseGroup {
+                    terms: config
+                        .license
+                        .as_ref()
+                        .and_then(|license| license.terms.get(code))
+                        .cloned()
+                        .unwrap_or_default(),
+                    ..Default::default()
+                }
+            });
+
+        group.docs += 1;
+        group.size += size.get(idx).unwrap_or_default();
+        if let Some(path) = path.get(idx) {
+            group.paths.push(path.to_string());
+        }
+    }
+
+    Ok(groups)
+}
+
+/// Returns the license manifest, which lists the documents grouped by
+/// their license terms.
+pub(crate) fn manifest(
+    config: &Config,
+    df: &DataFrame,
+) -> DatashedResult<String> {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "Licenses of {} {}\n",
+        config.metadata.name, config.metadata.version
+    );
+
+    for (code, group) in groups(config, df)?.iter_mut() {
+        let terms = &group.terms;
+        let _ = writeln!(
+            out,
+            "== {} ({code}) ==",
+            terms.name.as_deref().unwrap_or(code)
+        );
+        if let Some(ref url) = terms.url {
+            let _ = writeln!(out, "License: {url}");
+        }
+        if let Some(ref attribution) = terms.attribution {
+            let _ = writeln!(out, "Attribution: {attribution}");
+        }
+
+        let _ = writeln!(out, "Documents: {}\n", group.docs);
+        group.paths.sort_unstable();
+        for path in group.paths.iter() {
+            let _ = writeln!(out, "{path}");
+        }
+        out.push('\n');
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::License;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn license_manifest() -> TestResult {
+        let config = Config {
+            license: Some(License {
+                terms: [(
+                    "cc-by".to_string(),
+                    LicenseTerms {
+                        name: Some("CC BY 4.0".into()),
+                        attribution: Some("DNB".into()),
+                        ..Default::default()
+                    },
+                )]
+                .into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let df = df!(
+            "path" => ["b.txt", "a.txt", "c.txt"],
+            "size" => [1u64, 2, 3],
+            "license" => [Some("cc-by"), Some("cc-by"), None],
+        )?;
+
+        let groups = groups(&config, &df)?;
+        assert_eq!(groups["cc-by"].docs, 2);
+        assert_eq!(groups["unknown"].size, 3);
+
+        let manifest = manifest(&config, &df)?;
+        assert!(manifest.contains("== CC BY 4.0 (cc-by) =="));
+        assert!(manifest.contains(
+            "Attribution: DNB\nDocuments: 2\n\na.txt\nb.txt\n"
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::Path;
+
+use clap::ValueEnum;
+use hashbrown::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::error::{DatashedError, DatashedResult};
+
+/// The separator of the units of an n-gram key.
+const SEP: char = '\u{1f}';
+
+/// The padding unit at the start of a text.
+const BOS: &str = "\u{2}";
+
+/// The unit of an n-gram language model.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LmUnit {
+    /// Characters (lowercase, NFC, whitespace collapsed).
+    #[default]
+    Char,
+
+    /// Whitespace-separated (lowercase) words.
+    Word,
+}
+
+impl LmUnit {
+    /// Splits a text into units.
+    fn units(&self, text: &str) -> Vec<String> {
+        let text = text.nfc().collect::<String>().to_lowercase();
+        match self {
+            Self::Char => {
+                let mut units = vec![];
+                for word in text.split_whitespace() {
+                    if !units.is_empty() {
+                        units.push(" ".to_string());
+                    }
+
+                    units.extend(word.chars().map(String::from));
+                }
+
+                units
+            }
+            Self::Word => {
+                text.split_whitespace().map(String::from).collect()
+            }
+        }
+    }
+}
+
+/// An n-gram language model with add-k smoothing.
+///
+/// The probability of a unit `w` given its context `c` (the `order -
+/// 1` preceding units) is `(count(c, w) + k) / (count(c) + k * V)`,
+/// where `V` is the size of the vocabulary (including an unknown
+/// unit).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct NgramModel {
+    unit: LmUnit,
+    order: usize,
+    k: f64,
+    vocab: usize,
+    ngrams: HashMap<String, u64>,
+
+    #[serde(skip)]
+    contexts: HashMap<String, u64>,
+}
+
+impl NgramModel {
+    /// Creates an empty model.
+    pub(crate) fn new(unit: LmUnit, order: usize, k: f64) -> Self {
+        Self {
+            unit,
+            order: order.max(1),
+            k,
+            vocab: 1,
+            ngrams: HashMap::new(),
+            contexts: HashMap::new(),
+        }
+    }
+
+    /// Loads a model (JSON).
+    pub(crate) fn from_path<P: AsRef<Path>>(
+        path: P,
+    ) -> DatashedResult<Self> {
+        let mut model: Self =
+            serde_json::from_reader(BufReader::new(File::open(path)?))
+                .map_err(DatashedError::other)?;
+        model.finish();
+        Ok(model)
+    }
+
+    /// Writes the model (JSON).
+    pub(crate) fn write<W: Write>(&self, out: W) -> DatashedResult<()> {
+        serde_json::to_writer(out, self).map_err(DatashedError::other)
+    }
+
+    /// Returns the padded units of a text.
+    fn padded(&self, text: &str) -> Vec<String> {
+        let mut units = vec![BOS.to_string(); self.order - 1];
+        units.extend(self.unit.units(text));
+        units
+    }
+
+    /// Adds the n-grams of a text to the model.
+    pub(crate) fn add(&mut self, text: &str) {
+        let units = self.padded(text);
+        for ngram in units.windows(self.order) {
+            *self
+                .ngrams
+                .entry(ngram.join(&SEP.to_string()))
+                .or_default() += 1;
+        }
+    }
+
+    /// Merges the counts of another model (of the same unit and
+    /// order) into the model.
+    pub(crate) fn merge(mut self, other: Self) -> Self {
+        for (ngram, count) in other.ngrams.into_iter() {
+            *self.ngrams.entry(ngram).or_default() += count;
+        }
+
+        self
+    }
+
+    /// Computes the context counts and the vocabulary size after
+    /// training or loading.
+    pub(crate) fn finish(&mut self) {
+        let mut vocab: HashSet<&str> = HashSet::new();
+        self.contexts.clear();
+
+        for (ngram, count) in self.ngrams.iter() {
+            let (context, unit) = match ngram.rfind(SEP) {
+                Some(pos) => (&ngram[..pos], &ngram[pos + 1..]),
+                None => ("", ngram.as_str()),
+            };
+
+            vocab.insert(unit);
+            *self.contexts.entry(context.to_string()).or_default() +=
+                count;
+        }
+
+        self.vocab = vocab.len() + 1;
+    }
+
+    /// Returns the number of distinct n-grams.
+    pub(crate) fn len(&self) -> usize {
+        self.ngrams.len()
+    }
+
+    /// Returns the perplexity of the text or `None`, if the text is
+    /// empty.
+    pub(crate) fn perplexity(&self, text: &str) -> Option<f64> {
+        let units = self.padded(text);
+        let n = units.len() - (self.order - 1);
+        if n == 0 {
+            return None;
+        }
+
+        let denom = self.k * self.vocab as f64;
+        let log_prob: f64 = units
+            .windows(self.order)
+            .map(|ngram| {
+                let context =
+                    ngram[..self.order - 1].join(&SEP.to_string());
+                let ngram = ngram.join(&SEP.to_string());
+                let count = self
+                    .ngrams
+                    .get(&ngram)
+                    .copied()
+                    .unwrap_or_default();
+                let total = self
+                    .contexts
+                    .get(&context)
+                    .copied()
+                    .unwrap_or_default();
+
+                ((count as f64 + self.k) / (total as f64 + denom)).ln()
+            })
+            .sum();
+
+        Some((-log_prob / n as f64).exp())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ngram_perplexity() {
+        let mut model = NgramModel::new(LmUnit::Char, 3, 0.01);
+        for _ in 0..10 {
+            model.add("der die das und der die das");
+        }
+        model.finish();
+
+        let known = model.perplexity("die das und der").unwrap();
+        let noise = model.perplexity("x1q zzv 0ks").unwrap();
+        assert!(known < noise);
+        assert!(known >= 1.0);
+        assert_eq!(model.perplexity("  "), None);
+
+        let json = serde_json::to_string(&model).unwrap();
+        let mut loaded: NgramModel =
+            serde_json::from_str(&json).unwrap();
+        loaded.finish();
+        assert_eq!(
+            loaded.perplexity("die das"),
+            model.perplexity("die das")
+        );
+
+        let words = LmUnit::Word.units("Der  Hund\nbellt");
+        assert_eq!(words, ["der", "hund", "bellt"]);
+    }
+}
@@ -0,0 +1,232 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::Duration;
+use std::{env, process};
+
+use crate::error::{bail, DatashedResult};
+
+/// An advisory lock, which protects the index of a datashed against
+/// concurrent writers.
+///
+/// The lock is held as long as the guard is alive; the lock file is
+/// removed when the guard is dropped. The lock file contains the PID
+/// and the hostname of the owning process (`pid@host`). A lock file
+/// is considered stale (and is replaced), if it was created on this
+/// host and the owning process no longer exists. Locks of other hosts
+/// (e.g. on a shared file system) are never considered stale.
+#[derive(Debug)]
+pub(crate) struct LockGuard {
+    path: PathBuf,
+    owner: String,
+}
+
+impl LockGuard {
+    /// The interval of lock attempts, if waiting for a lock.
+    const INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Acquires the lock at the given path. If the lock is held by
+    /// another process, this function either waits until the lock is
+    /// released (`wait = true`) or fails immediately.
+    pub(crate) fn acquire<P: Into<PathBuf>>(
+        path: P,
+        wait: bool,
+    ) -> DatashedResult<Self> {
+        let path = path.into();
+        let owner = format!("{}@{}", process::id(), hostname());
+        let mut notified = false;
+
+        loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    writeln!(file, "{owner}")?;
+                    return Ok(Self { path, owner });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    if let Some(stale) = stale_owner(&path) {
+                        // The stale lock file is removed and the lock
+                        // is acquired with `create_new` again, which
+                        // only one of the competing processes wins.
+                        remove_stale(&path, &stale)?;
+                        continue;
+                    }
+
+                    if !wait {
+                        bail!(
+                            "'{}' is locked by another process; use \
+                            --wait to wait for the lock to be released",
+                            path.display()
+                        );
+                    }
+
+                    if !notified {
+                        eprintln!(
+                            "waiting for lock '{}'.",
+                            path.display()
+                        );
+                        notified = true;
+                    }
+
+                    sleep(Self::INTERVAL);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        // The lock file is only removed, if it hasn't been replaced by
+        // another process in the meantime.
+        if read_owner(&self.path).as_deref()
+            == Some(self.owner.as_str())
+        {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Returns the name of this host.
+fn hostname() -> String {
+    ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok())
+        .or_else(|| env::var("HOSTNAME").ok())
+        .or_else(|| env::var("COMPUTERNAME").ok())
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Returns the path of the temporary file, to which a stale lock file
+/// is moved (see [remove_stale]).
+fn temp_path(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}.tmp", process::id()));
+    tmp.into()
+}
+
+/// Returns the owner (`pid@host`) of a lock file.
+fn read_owner(path: &Path) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|content| content.trim().to_string())
+        .filter(|owner| !owner.is_empty())
+}
+
+/// Returns the owner of the lock file, if the lock file is stale, i.e.
+/// it was created on this host and the owning process no longer
+/// exists. Lock files without a hostname are considered to be created
+/// on this host.
+fn stale_owner(path: &Path) -> Option<String> {
+    let owner = read_owner(path)?;
+    let (pid, host) = match owner.split_once('@') {
+        Some((pid, host)) => (pid, Some(host)),
+        None => (owner.as_str(), None),
+    };
+
+    if host.is_some_and(|host| host != hostname()) {
+        return None;
+    }
+
+    // The liveness of the owning process can only be checked, if the
+    // proc filesystem is available.
+    let proc = Path::new("/proc");
+    if !proc.is_dir() {
+        return None;
+    }
+
+    pid.parse::<u32>()
+        .is_ok_and(|pid| !proc.join(pid.to_string()).exists())
+        .then_some(owner)
+}
+
+/// Removes the stale lock file of `owner`.
+///
+/// The lock file is moved aside first, which only one process can do.
+/// If the moved file isn't the stale one (another process has removed
+/// the stale file and acquired the lock in the meantime), it's moved
+/// back without replacing an existing lock file.
+fn remove_stale(path: &Path, owner: &str) -> io::Result<()> {
+    let tmp = temp_path(path);
+    match fs::rename(path, &tmp) {
+        Ok(()) => (),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    }
+
+    let restored = if read_owner(&tmp).as_deref() != Some(owner) {
+        fs::hard_link(&tmp, path)
+    } else {
+        Ok(())
+    };
+
+    fs::remove_file(&tmp)?;
+    match restored {
+        Err(e) if e.kind() != ErrorKind::AlreadyExists => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn lock_guard_acquire() -> TestResult {
+        let dir = temp_dir()?;
+        let path = dir.path().join("index.lock");
+
+        let guard = LockGuard::acquire(&path, false)?;
+        assert!(path.is_file());
+        assert!(LockGuard::acquire(&path, false).is_err());
+
+        drop(guard);
+        assert!(!path.exists());
+
+        // A lock file of a non-existing process is stale, unless it
+        // was created on another host.
+        if Path::new("/proc").is_dir() {
+            fs::write(&path, format!("{}@other-host\n", u32::MAX))?;
+            assert!(LockGuard::acquire(&path, false).is_err());
+
+            fs::write(&path, format!("{}@{}\n", u32::MAX, hostname()))?;
+            let guard = LockGuard::acquire(&path, false)?;
+            assert_eq!(read_owner(&path), Some(guard.owner.clone()));
+            assert!(!temp_path(&path).exists());
+
+            drop(guard);
+            assert!(!path.exists());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn lock_guard_remove_stale() -> TestResult {
+        let dir = temp_dir()?;
+        let path = dir.path().join("index.lock");
+
+        // The lock file has been replaced by another process.
+        fs::write(&path, "1@foo\n")?;
+        remove_stale(&path, "2@foo")?;
+        assert_eq!(read_owner(&path).as_deref(), Some("1@foo"));
+        assert!(!temp_path(&path).exists());
+
+        remove_stale(&path, "1@foo")?;
+        assert!(!path.exists());
+        assert!(!temp_path(&path).exists());
+
+        // The stale lock file has already been removed.
+        remove_stale(&path, "1@foo")?;
+        Ok(())
+    }
+}
@@ -1,8 +1,10 @@
 use std::io::ErrorKind;
 use std::process;
+use std::time::Instant;
 
 use clap::Parser;
 use cli::{Args, Command};
+use config::{Config, Runtime, StalePolicy};
 use datashed::Datashed;
 use env_logger::Env;
 use error::{DatashedError, DatashedResult};
@@ -10,53 +12,153 @@ use jemallocator::Jemalloc;
 use polars::error::PolarsError;
 use rayon::ThreadPoolBuilder;
 
+mod access;
+mod artifacts;
+mod atomic;
+mod audit;
+mod campaign;
+mod checkpoint;
 mod cli;
+mod collate;
 mod commands;
 mod config;
+mod cron;
+mod crypto;
 mod datashed;
+mod discovery;
 mod document;
 mod error;
+#[cfg(feature = "flight")]
+mod flight;
+mod freshness;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod http;
 mod lfreq;
+mod licenses;
+mod lm;
+mod lock;
+mod metrics;
+mod notify;
+mod plan;
+#[cfg(feature = "wasm")]
+mod plugins;
+mod prefetch;
 mod prelude;
+mod preprocess;
 mod progress;
+mod projection;
+mod ratings;
+mod redact;
+mod schedule;
+mod schema;
+mod script;
+mod seed;
+mod signature;
+mod sketch;
+mod sql;
+mod stats;
+mod stem;
+mod suspicious;
+mod synth;
+mod tags;
+#[cfg(test)]
+mod testing;
+mod throttle;
+mod trash;
 mod utils;
 
 #[global_allocator]
 static GLOBAL: Jemalloc = Jemalloc;
 
-fn num_threads(args: &Args) -> usize {
-    if let Some(num_threads) = args.num_jobs {
-        return num_threads;
+/// Returns the config of the datashed in the current directory (or one
+/// of its parents), if any.
+fn config() -> DatashedResult<Option<Config>> {
+    match Datashed::discover() {
+        Ok(datashed) => datashed.config().map(Some),
+        Err(_) => Ok(None),
     }
+}
+
+fn num_threads(args: &Args, runtime: &Runtime) -> usize {
+    args.num_jobs.or(runtime.num_jobs).unwrap_or(0)
+}
+
+fn seed(args: &Args, runtime: &Runtime) -> Option<u64> {
+    args.seed.or(runtime.seed)
+}
 
-    if let Ok(config) = Datashed::discover().and_then(|dp| dp.config())
+fn stale_policy(args: &Args, runtime: &Runtime) -> StalePolicy {
+    // Commands, which (re-)create the index, compare the index with
+    // the data directory or remove/restore documents, expect an
+    // outdated index.
+    if args.allow_stale
+        || matches!(
+            args.cmd,
+            Command::Clean(_)
+                | Command::Index(_)
+                | Command::Status(_)
+                | Command::UndoClean(_)
+                | Command::Verify(_)
+        )
     {
-        if let Some(runtime) = config.runtime {
-            if let Some(num_threads) = runtime.num_jobs {
-                return num_threads;
-            }
-        }
+        return StalePolicy::Ignore;
     }
 
-    0
+    runtime.stale_index.unwrap_or_default()
 }
 
 async fn run(args: Args) -> DatashedResult<()> {
     match args.cmd {
+        Command::Apply(cmd) => cmd.execute(),
         Command::Archive(cmd) => cmd.execute(),
+        Command::Bench(cmd) => cmd.execute(),
         Command::Bibrefs(cmd) => cmd.execute(),
         Command::Clean(cmd) => cmd.execute(),
         Command::Completions(cmd) => cmd.execute(),
         Command::Config(cmd) => cmd.execute(),
+        Command::Contains(cmd) => cmd.execute(),
+        Command::Daemon(cmd) => cmd.execute(),
+        Command::Deboilerplate(cmd) => cmd.execute(),
+        Command::DiffDocs(cmd) => cmd.execute(),
+        Command::DuckdbInit(cmd) => cmd.execute(),
+        Command::EncodingReport(cmd) => cmd.execute(),
+        Command::Gc(cmd) => cmd.execute(),
+        Command::Graph(cmd) => cmd.execute(),
         Command::Grep(cmd) => cmd.execute(),
         Command::Index(cmd) => cmd.execute(),
         Command::Init(cmd) => cmd.execute(),
+        Command::Keywords(cmd) => cmd.execute(),
         Command::Lfreq(cmd) => cmd.execute(),
+        Command::Link(cmd) => cmd.execute(),
+        Command::Lm(cmd) => cmd.execute(),
+        Command::Log(cmd) => cmd.execute(),
+        Command::Merge(cmd) => cmd.execute(),
+        Command::Mirror(cmd) => cmd.execute().await,
+        #[cfg(feature = "fuse")]
+        Command::Mount(cmd) => cmd.execute(),
+        Command::OcrConfusions(cmd) => cmd.execute(),
+        Command::Outdated(cmd) => cmd.execute(),
+        Command::Quarantine(cmd) => cmd.execute(),
+        Command::Ratings(cmd) => cmd.execute(),
+        Command::RedactIndex(cmd) => cmd.execute(),
+        Command::Refine(cmd) => cmd.execute(),
+        Command::RepeatedLines(cmd) => cmd.execute(),
+        Command::Report(cmd) => cmd.execute(),
         Command::Restore(cmd) => cmd.execute(),
+        Command::Sample(cmd) => cmd.execute(),
+        Command::Select(cmd) => cmd.execute(),
         Command::Rate(cmd) => cmd.execute().await,
         Command::Serve(cmd) => cmd.execute().await,
+        Command::Shard(cmd) => cmd.execute(),
+        Command::Sign(cmd) => cmd.execute(),
         Command::Status(cmd) => cmd.execute(),
+        Command::Stopwords(cmd) => cmd.execute(),
         Command::Summary(cmd) => cmd.execute(),
+        Command::Synth(cmd) => cmd.execute(),
+        Command::Tag(cmd) => cmd.execute(),
+        Command::Tocparse(cmd) => cmd.execute(),
+        Command::UndoClean(cmd) => cmd.execute(),
         Command::User(cmd) => cmd.execute(),
         Command::Verify(cmd) => cmd.execute(),
         Command::Version(cmd) => cmd.execute(),
@@ -79,15 +181,52 @@ fn init_logger() {
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
+    let config = match config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {e:#}");
+            process::exit(1);
+        }
+    };
+
+    let runtime = config
+        .as_ref()
+        .and_then(|config| config.runtime.clone())
+        .unwrap_or_default();
 
     ThreadPoolBuilder::new()
-        .num_threads(num_threads(&args))
+        .num_threads(num_threads(&args, &runtime))
         .build_global()
         .unwrap();
 
+    // The categorical columns of indices read from different files
+    // must share their categories (e.g. when merging indices).
+    polars::enable_string_cache();
+
     init_logger();
+    seed::init(seed(&args, &runtime));
+    freshness::init(stale_policy(&args, &runtime));
+
+    if let Err(e) = throttle::init(runtime.io_limit.as_ref()) {
+        eprintln!("error: {e:#}");
+        process::exit(1);
+    }
+
+    let command = notify::command_name(&args.cmd);
+    let start = Instant::now();
+    let result = run(args).await;
+
+    if let Some(command) = command {
+        notify::notify(
+            config.as_ref(),
+            command,
+            start.elapsed(),
+            &result,
+        )
+        .await;
+    }
 
-    match run(args).await {
+    match result {
         Ok(()) => process::exit(0),
         Err(DatashedError::IO(e))
             if e.kind() == ErrorKind::BrokenPipe =>
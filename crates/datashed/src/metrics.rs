@@ -0,0 +1,321 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{bail, DatashedError, DatashedResult};
+use crate::http::{BlockingHttpClient, HttpConfig};
+
+/// The default timeout of an external metric (in seconds).
+const DEFAULT_TIMEOUT: f64 = 30.0;
+
+/// Metric options.
+///
+/// External metrics are computed by commands (or HTTP endpoints),
+/// which receive the text of a document on the standard input (or as
+/// the body of a POST request) and return a JSON object. The values of
+/// the `columns` of the object are merged into the index as columns
+/// named `{name}_{column}` (numbers as is, booleans as 0 or 1, all
+/// other values as null).
+///
+/// ```toml
+/// [metrics.external.readability]
+/// command = ["python3", "scripts/readability.py"]
+/// columns = ["flesch", "smog"]
+/// timeout = 10
+/// on-failure = "null"
+///
+/// [metrics.external.toxicity]
+/// url = "http://localhost:8000/score"
+/// columns = ["score"]
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Metrics {
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub(crate) external: BTreeMap<String, ExternalMetric>,
+}
+
+impl Metrics {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.external.is_empty()
+    }
+
+    /// Checks that each external metric has either a command or an URL
+    /// and at least one column.
+    pub(crate) fn validate(&self) -> DatashedResult<()> {
+        for (name, metric) in self.external.iter() {
+            if metric.command.is_empty() == metric.url.is_none() {
+                bail!("metric '{name}' requires either command or url");
+            }
+
+            if metric.columns.is_empty() {
+                bail!("metric '{name}' has no columns");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the external metrics, which use a HTTP client with the
+    /// given options (see `[http]`).
+    pub(crate) fn with_client(
+        &self,
+        config: &HttpConfig,
+    ) -> DatashedResult<ExternalMetrics<'_>> {
+        Ok(ExternalMetrics {
+            metrics: self,
+            client: BlockingHttpClient::from_config(config)?,
+        })
+    }
+}
+
+/// A metric, which is computed for each document (or page) by
+/// `datashed index` and recorded in one or more index columns.
+pub(crate) trait Metric: Sync {
+    /// Returns the names of the index columns.
+    fn columns(&self) -> Vec<String>;
+
+    /// Computes the values of the text (in the order of the columns).
+    fn evaluate(&self, text: &[u8])
+        -> DatashedResult<Vec<Option<f64>>>;
+}
+
+/// Returns the index columns of all metrics.
+pub(crate) fn columns(metrics: &[&dyn Metric]) -> Vec<String> {
+    metrics.iter().flat_map(|metric| metric.columns()).collect()
+}
+
+/// Evaluates all metrics on the text.
+pub(crate) fn evaluate(
+    metrics: &[&dyn Metric],
+    text: &[u8],
+) -> DatashedResult<Vec<Option<f64>>> {
+    let mut result = vec![];
+    for metric in metrics.iter() {
+        result.extend(metric.evaluate(text)?);
+    }
+
+    Ok(result)
+}
+
+/// The external metrics of the config and the HTTP client of the
+/// metrics with an URL.
+pub(crate) struct ExternalMetrics<'a> {
+    metrics: &'a Metrics,
+    client: BlockingHttpClient,
+}
+
+impl Metric for ExternalMetrics<'_> {
+    /// Returns the names of the index columns of the external metrics.
+    fn columns(&self) -> Vec<String> {
+        self.metrics
+            .external
+            .iter()
+            .flat_map(|(name, metric)| {
+                metric
+                    .columns
+                    .iter()
+                    .map(move |column| format!("{name}_{column}"))
+            })
+            .collect()
+    }
+
+    /// Evaluates all external metrics on the text.
+    fn evaluate(
+        &self,
+        text: &[u8],
+    ) -> DatashedResult<Vec<Option<f64>>> {
+        let mut result = vec![];
+        for (name, metric) in self.metrics.external.iter() {
+            match metric.run(text, &self.client) {
+                Ok(values) => result.extend(values),
+                Err(e) if metric.on_failure == FailurePolicy::Error => {
+                    bail!("metric '{name}' failed: {e}");
+                }
+                Err(_) => {
+                    result.extend(metric.columns.iter().map(|_| None))
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// The behaviour of the indexing, if an external metric fails (error,
+/// timeout or invalid output).
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum FailurePolicy {
+    /// Record null values.
+    #[default]
+    Null,
+
+    /// Abort the indexing.
+    Error,
+}
+
+/// An external metric.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct ExternalMetric {
+    /// The command (program and arguments).
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) command: Vec<String>,
+
+    /// The URL of an HTTP endpoint.
+    pub(crate) url: Option<String>,
+
+    /// The keys of the JSON object, which are merged into the index.
+    pub(crate) columns: Vec<String>,
+
+    /// The timeout per document in seconds (default: 30).
+    pub(crate) timeout: Option<f64>,
+
+    /// The failure policy.
+    #[serde(default)]
+    pub(crate) on_failure: FailurePolicy,
+}
+
+impl ExternalMetric {
+    fn timeout(&self) -> Duration {
+        Duration::from_secs_f64(self.timeout.unwrap_or(DEFAULT_TIMEOUT))
+    }
+
+    /// Computes the metric for the text.
+    fn run(
+        &self,
+        text: &[u8],
+        client: &BlockingHttpClient,
+    ) -> DatashedResult<Vec<Option<f64>>> {
+        let output = match self.url {
+            Some(ref url) => client
+                .send_once(|client| {
+                    client
+                        .post(url)
+                        .body(text.to_vec())
+                        .timeout(self.timeout())
+                })?
+                .error_for_status()
+                .and_then(|res| res.bytes())
+                .map(|bytes| bytes.to_vec())?,
+            None => self.execute(text)?,
+        };
+
+        let Value::Object(object) = serde_json::from_slice(&output)
+            .map_err(DatashedError::other)?
+        else {
+            bail!("expected a JSON object");
+        };
+
+        Ok(self
+            .columns
+            .iter()
+            .map(|column| match object.get(column) {
+                Some(Value::Number(n)) => n.as_f64(),
+                Some(Value::Bool(b)) => {
+                    Some(if *b { 1.0 } else { 0.0 })
+                }
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Runs the command with the text on the standard input and returns
+    /// the standard output. The command is killed, if it doesn't
+    /// finish within the timeout.
+    fn execute(&self, text: &[u8]) -> DatashedResult<Vec<u8>> {
+        let mut child = Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let mut stdin = child.stdin.take().unwrap();
+        let text = text.to_vec();
+        let writer = thread::spawn(move || {
+            // The command may exit without reading the whole input.
+            let _ = stdin.write_all(&text);
+        });
+
+        let mut stdout = child.stdout.take().unwrap();
+        let reader = thread::spawn(move || {
+            let mut buf = vec![];
+            stdout.read_to_end(&mut buf).map(|_| buf)
+        });
+
+        let deadline = Instant::now() + self.timeout();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                bail!("timeout after {:?}", self.timeout());
+            }
+
+            thread::sleep(Duration::from_millis(5));
+        };
+
+        let _ = writer.join();
+        let output = reader.join().unwrap()?;
+
+        if !status.success() {
+            bail!("command exited with {status}");
+        }
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn external_metrics() -> TestResult {
+        let mut metrics: Metrics = toml::from_str(
+            r#"
+            [external.echo]
+            command = ["cat"]
+            columns = ["a", "b", "c"]
+
+            [external.slow]
+            command = ["sleep", "5"]
+            columns = ["x"]
+            timeout = 0.1
+            "#,
+        )?;
+
+        assert!(metrics.validate().is_ok());
+        let external = metrics.with_client(&HttpConfig::default())?;
+        assert_eq!(
+            external.columns(),
+            ["echo_a", "echo_b", "echo_c", "slow_x"]
+        );
+        assert_eq!(
+            external.evaluate(br#"{"a": 1.5, "b": true, "c": "x"}"#)?,
+            [Some(1.5), Some(1.0), None, None]
+        );
+
+        metrics.external.get_mut("slow").unwrap().on_failure =
+            FailurePolicy::Error;
+        let external = metrics.with_client(&HttpConfig::default())?;
+        assert!(external.evaluate(b"{}").is_err());
+
+        metrics.external.get_mut("slow").unwrap().command = vec![];
+        assert!(metrics.validate().is_err());
+        Ok(())
+    }
+}
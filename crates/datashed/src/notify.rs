@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::cli::Command;
+use crate::config::{Notify, NotifyFormat};
+use crate::http::HttpClient;
+use crate::prelude::*;
+
+/// The commands which trigger a notification by default.
+const DEFAULT_COMMANDS: [&str; 4] =
+    ["index", "archive", "vocab", "verify"];
+
+/// Returns the name of a command, which may trigger a notification.
+pub(crate) fn command_name(cmd: &Command) -> Option<&'static str> {
+    match cmd {
+        Command::Archive(_) => Some("archive"),
+        Command::Index(_) => Some("index"),
+        Command::Verify(_) => Some("verify"),
+        Command::Vocab(_) => Some("vocab"),
+        _ => None,
+    }
+}
+
+/// Returns the payload of a notification.
+fn payload(
+    format: NotifyFormat,
+    shed: &str,
+    command: &str,
+    duration: Duration,
+    error: Option<String>,
+) -> Value {
+    let status = if error.is_none() {
+        "success"
+    } else {
+        "failure"
+    };
+    let text = match error {
+        Some(ref error) => format!(
+            "datashed {command} ({shed}) failed after {:.1}s: {error}",
+            duration.as_secs_f64()
+        ),
+        None => format!(
+            "datashed {command} ({shed}) finished after {:.1}s.",
+            duration.as_secs_f64()
+        ),
+    };
+
+    match format {
+        NotifyFormat::Json => json!({
+            "shed": shed,
+            "command": command,
+            "status": status,
+            "duration": duration.as_secs_f64(),
+            "error": error,
+        }),
+        NotifyFormat::Slack => json!({ "text": text }),
+        NotifyFormat::Matrix => {
+            json!({ "msgtype": "m.text", "body": text })
+        }
+    }
+}
+
+/// Sends a notification about the completion of a command, if the
+/// datashed config contains a `[notify]` section. Failures to send the
+/// notification are reported, but don't affect the result of the
+/// command.
+pub(crate) async fn notify(
+    config: Option<&Config>,
+    command: &str,
+    duration: Duration,
+    result: &DatashedResult<()>,
+) {
+    let Some(config) = config else {
+        return;
+    };
+
+    let Some(Notify {
+        ref url,
+        format,
+        ref commands,
+        only_failures,
+    }) = config.notify
+    else {
+        return;
+    };
+
+    let enabled = if commands.is_empty() {
+        DEFAULT_COMMANDS.contains(&command)
+    } else {
+        commands.iter().any(|name| name == command)
+    };
+
+    if !enabled || (only_failures && result.is_ok()) {
+        return;
+    }
+
+    let payload = payload(
+        format,
+        &config.metadata.name,
+        command,
+        duration,
+        result.as_ref().err().map(ToString::to_string),
+    );
+
+    let response = match HttpClient::from_config(&config.http) {
+        Ok(client) => {
+            client
+                .send_once(|client| client.post(url).json(&payload))
+                .await
+        }
+        Err(e) => Err(e),
+    };
+
+    match response {
+        Ok(response) if response.status().is_success() => (),
+        Ok(response) => eprintln!(
+            "warning: notification failed (status = {})",
+            response.status()
+        ),
+        Err(e) => eprintln!("warning: notification failed: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notify_payload() {
+        let duration = Duration::from_millis(1500);
+        let value =
+            payload(NotifyFormat::Json, "foo", "index", duration, None);
+        assert_eq!(value["status"], "success");
+        assert_eq!(value["duration"], 1.5);
+
+        let value = payload(
+            NotifyFormat::Slack,
+            "foo",
+            "vocab",
+            duration,
+            Some("out of memory".into()),
+        );
+        assert_eq!(
+            value["text"],
+            "datashed vocab (foo) failed after 1.5s: out of memory"
+        );
+    }
+}
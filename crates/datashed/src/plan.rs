@@ -0,0 +1,119 @@
+use std::io::{self, Write};
+
+use indicatif::HumanBytes;
+
+/// The number of example paths, which are printed per operation.
+const EXAMPLES: usize = 5;
+
+/// The operations of a mutating command, which are printed instead of
+/// being executed, if the command is run with `--dry-run`.
+#[derive(Debug, Default)]
+pub(crate) struct Plan {
+    operations: Vec<(&'static str, Operation)>,
+}
+
+#[derive(Debug, Default)]
+struct Operation {
+    count: usize,
+    bytes: u64,
+    examples: Vec<String>,
+}
+
+impl Plan {
+    /// Adds an operation on a path (e.g. "delete"), which affects the
+    /// given number of bytes.
+    pub(crate) fn add<S: Into<String>>(
+        &mut self,
+        operation: &'static str,
+        path: S,
+        bytes: u64,
+    ) {
+        let pos = match self
+            .operations
+            .iter()
+            .position(|(name, _)| *name == operation)
+        {
+            Some(pos) => pos,
+            None => {
+                self.operations.push((operation, Operation::default()));
+                self.operations.len() - 1
+            }
+        };
+
+        let entry = &mut self.operations[pos].1;
+        if entry.examples.len() < EXAMPLES {
+            entry.examples.push(path.into());
+        }
+
+        entry.count += 1;
+        entry.bytes += bytes;
+    }
+
+    /// Returns `true` if the plan contains no operations.
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Writes the plan to `wtr`.
+    pub(crate) fn write<W: Write>(&self, mut wtr: W) -> io::Result<()> {
+        if self.is_empty() {
+            return writeln!(wtr, "nothing to do.");
+        }
+
+        for (name, op) in self.operations.iter() {
+            writeln!(
+                wtr,
+                "{name}: {} path(s), {}",
+                op.count,
+                HumanBytes(op.bytes)
+            )?;
+
+            for path in op.examples.iter() {
+                writeln!(wtr, "    {path}")?;
+            }
+
+            if op.count > op.examples.len() {
+                writeln!(
+                    wtr,
+                    "    ... and {} more",
+                    op.count - op.examples.len()
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prints the plan to the standard output stream.
+    pub(crate) fn print(&self) -> io::Result<()> {
+        self.write(io::stdout().lock())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn plan_write() -> TestResult {
+        let mut plan = Plan::default();
+        for i in 0..7 {
+            plan.add("delete", format!("data/{i}.txt"), 1024);
+        }
+
+        plan.add("overwrite", "index.ipc", 10);
+
+        let mut out = vec![];
+        plan.write(&mut out)?;
+        let out = String::from_utf8(out)?;
+
+        assert!(out.starts_with("delete: 7 path(s), 7.00 KiB\n"));
+        assert!(out.contains("    data/4.txt\n    ... and 2 more\n"));
+        assert!(out
+            .ends_with("overwrite: 1 path(s), 10 B\n    index.ipc\n"));
+        Ok(())
+    }
+}
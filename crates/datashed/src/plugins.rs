@@ -0,0 +1,325 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+use wasmtime::{
+    Engine, Instance, InstancePre, Linker, Module, Store, StoreLimits,
+    StoreLimitsBuilder,
+};
+
+use crate::error::{bail, DatashedError, DatashedResult};
+use crate::metrics::Metric;
+
+/// The fuel (roughly the number of WASM instructions) of a single call.
+const FUEL: u64 = 10_000_000_000;
+
+/// The maximum size of the linear memory of a plugin instance.
+const MAX_MEMORY: usize = 512 << 20;
+
+/// A reference found by a matcher plugin.
+#[derive(Debug, PartialEq, Deserialize)]
+pub(crate) struct PluginMatch {
+    /// The type of the reference. By default, the name of the plugin
+    /// is used.
+    #[serde(rename = "type")]
+    pub(crate) kind: Option<String>,
+    pub(crate) value: String,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+}
+
+/// A WASM plugin.
+///
+/// A plugin is a core WASM module without any imports, so that it
+/// can't access the file system, the network or the environment. It
+/// must export its `memory`, a function `alloc(len: i32) -> i32`, which
+/// returns a buffer of `len` bytes for the text of a document, and at
+/// least one of the following functions:
+///
+///   * `metric(ptr: i32, len: i32) -> f64` computes a metric of the
+///     text (NaN is recorded as null, see `datashed index`),
+///   * `matches(ptr: i32, len: i32) -> i64` finds references in the
+///     text (see `datashed bibrefs`) and returns the location of a JSON
+///     array (`ptr << 32 | len`) of objects with the fields `type`
+///     (optional), `value`, `start` and `end`.
+///
+/// Each call runs in a fresh instance, which is limited in memory and
+/// fuel (instructions).
+#[derive(Clone)]
+pub(crate) struct Plugin {
+    name: String,
+    engine: Engine,
+    pre: InstancePre<StoreLimits>,
+    fuel: u64,
+    metric: bool,
+    matcher: bool,
+}
+
+impl Plugin {
+    /// Returns the name of the plugin (the file stem of the module).
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Creates a new instance and copies the text into its memory.
+    fn instantiate(
+        &self,
+        text: &[u8],
+    ) -> DatashedResult<(Store<StoreLimits>, Instance, i32, i32)> {
+        let limits =
+            StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build();
+
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(self.fuel).map_err(DatashedError::other)?;
+
+        let instance = self
+            .pre
+            .instantiate(&mut store)
+            .map_err(DatashedError::other)?;
+
+        let len =
+            i32::try_from(text.len()).map_err(DatashedError::other)?;
+        let ptr = instance
+            .get_typed_func::<i32, i32>(&mut store, "alloc")
+            .and_then(|alloc| alloc.call(&mut store, len))
+            .map_err(DatashedError::other)?;
+
+        let Some(memory) = instance.get_memory(&mut store, "memory")
+        else {
+            bail!("plugin '{}' doesn't export memory", self.name);
+        };
+
+        memory
+            .write(&mut store, ptr as usize, text)
+            .map_err(DatashedError::other)?;
+
+        Ok((store, instance, ptr, len))
+    }
+
+    /// Computes the metric of the text.
+    pub(crate) fn metric(
+        &self,
+        text: &[u8],
+    ) -> DatashedResult<Option<f64>> {
+        let (mut store, instance, ptr, len) = self.instantiate(text)?;
+        let value = instance
+            .get_typed_func::<(i32, i32), f64>(&mut store, "metric")
+            .and_then(|metric| metric.call(&mut store, (ptr, len)))
+            .map_err(DatashedError::other)?;
+
+        Ok((!value.is_nan()).then_some(value))
+    }
+
+    /// Finds the references in the text.
+    pub(crate) fn matches(
+        &self,
+        text: &[u8],
+    ) -> DatashedResult<Vec<PluginMatch>> {
+        let (mut store, instance, ptr, len) = self.instantiate(text)?;
+        let result = instance
+            .get_typed_func::<(i32, i32), i64>(&mut store, "matches")
+            .and_then(|matches| matches.call(&mut store, (ptr, len)))
+            .map_err(DatashedError::other)?;
+
+        let Some(memory) = instance.get_memory(&mut store, "memory")
+        else {
+            bail!("plugin '{}' doesn't export memory", self.name);
+        };
+
+        let mut buf = vec![0; (result & 0xffff_ffff) as usize];
+        memory
+            .read(&store, (result >> 32) as usize, &mut buf)
+            .map_err(DatashedError::other)?;
+
+        serde_json::from_slice(&buf).map_err(DatashedError::other)
+    }
+}
+
+impl Metric for Plugin {
+    fn columns(&self) -> Vec<String> {
+        vec![self.name.clone()]
+    }
+
+    /// Computes the metric of the text. A failing plugin (e.g. a trap
+    /// or exhausted fuel) is recorded as null.
+    fn evaluate(
+        &self,
+        text: &[u8],
+    ) -> DatashedResult<Vec<Option<f64>>> {
+        Ok(vec![self.metric(text).unwrap_or(None)])
+    }
+}
+
+/// The WASM plugins of a datashed.
+pub(crate) struct Plugins {
+    engine: Engine,
+    plugins: Vec<Plugin>,
+}
+
+impl Plugins {
+    pub(crate) fn new() -> DatashedResult<Self> {
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+
+        Ok(Self {
+            engine: Engine::new(&config)
+                .map_err(DatashedError::other)?,
+            plugins: vec![],
+        })
+    }
+
+    /// Loads all plugins (`*.wasm`) of the given directory. A missing
+    /// directory is treated as empty.
+    pub(crate) fn load<P: AsRef<Path>>(dir: P) -> DatashedResult<Self> {
+        let mut plugins = Self::new()?;
+        let dir = dir.as_ref();
+        if !dir.is_dir() {
+            return Ok(plugins);
+        }
+
+        let mut paths: Vec<_> = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        paths.sort_unstable();
+
+        for path in paths {
+            if path.extension().and_then(OsStr::to_str) != Some("wasm")
+            {
+                continue;
+            }
+
+            let Some(name) = path.file_stem().and_then(OsStr::to_str)
+            else {
+                bail!("invalid plugin name '{}'", path.display());
+            };
+
+            plugins.add(name, fs::read(&path)?)?;
+        }
+
+        Ok(plugins)
+    }
+
+    /// Compiles a module (binary or text format) and adds it as plugin.
+    pub(crate) fn add<B: AsRef<[u8]>>(
+        &mut self,
+        name: &str,
+        bytes: B,
+    ) -> DatashedResult<()> {
+        let module = Module::new(&self.engine, bytes).map_err(|e| {
+            DatashedError::other(format!("{name}: {e}"))
+        })?;
+
+        let export = |name| module.get_export(name).is_some();
+        if !export("memory") || !export("alloc") {
+            bail!("plugin '{name}' must export memory and alloc");
+        }
+
+        let (metric, matcher) = (export("metric"), export("matches"));
+        if !metric && !matcher {
+            bail!("plugin '{name}' must export metric or matches");
+        }
+
+        // Plugins don't get any imports (sandbox).
+        let pre = Linker::new(&self.engine)
+            .instantiate_pre(&module)
+            .map_err(|e| {
+                DatashedError::other(format!("{name}: {e}"))
+            })?;
+
+        self.plugins.push(Plugin {
+            name: name.into(),
+            engine: self.engine.clone(),
+            pre,
+            fuel: FUEL,
+            metric,
+            matcher,
+        });
+
+        Ok(())
+    }
+
+    /// Returns the plugins, which export a metric.
+    pub(crate) fn metrics(&self) -> impl Iterator<Item = &dyn Metric> {
+        self.plugins
+            .iter()
+            .filter(|plugin| plugin.metric)
+            .map(|plugin| plugin as &dyn Metric)
+    }
+
+    /// Returns the plugins, which export a matcher.
+    pub(crate) fn matchers(&self) -> impl Iterator<Item = &Plugin> {
+        self.plugins.iter().filter(|plugin| plugin.matcher)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    const MEMORY: &str = r#"
+        (memory (export "memory") 1)
+        (global $next (mut i32) (i32.const 1024))
+        (func (export "alloc") (param $len i32) (result i32)
+            (global.get $next)
+            (global.set $next
+                (i32.add (global.get $next) (local.get $len))))
+    "#;
+
+    #[test]
+    fn wasm_plugins() -> TestResult {
+        let mut plugins = Plugins::new()?;
+        plugins.add(
+            "len",
+            format!(
+                r#"(module {MEMORY}
+                    (data (i32.const 0)
+                        "[{{\"value\":\"x\",\"start\":0,\"end\":1}}]")
+                    (func (export "metric")
+                        (param i32 i32) (result f64)
+                        (f64.convert_i32_u (local.get 1)))
+                    (func (export "matches")
+                        (param i32 i32) (result i64)
+                        (i64.const 33)))"#
+            ),
+        )?;
+        plugins.add(
+            "loop",
+            format!(
+                r#"(module {MEMORY}
+                    (func (export "metric")
+                        (param i32 i32) (result f64)
+                        (loop $l (br $l))
+                        (f64.const 0)))"#
+            ),
+        )?;
+        plugins.plugins[1].fuel = 10_000;
+
+        let metrics: Vec<_> = plugins.metrics().collect();
+        assert_eq!(crate::metrics::columns(&metrics), ["len", "loop"]);
+        assert_eq!(
+            crate::metrics::evaluate(&metrics, b"abc")?,
+            [Some(3.0), None]
+        );
+
+        let matchers: Vec<_> = plugins.matchers().collect();
+        assert_eq!(matchers.len(), 1);
+        assert_eq!(
+            matchers[0].matches(b"xyz")?,
+            [PluginMatch {
+                kind: None,
+                value: "x".into(),
+                start: 0,
+                end: 1
+            }]
+        );
+
+        assert!(plugins
+            .add("invalid", format!("(module {MEMORY})"))
+            .is_err());
+        Ok(())
+    }
+}
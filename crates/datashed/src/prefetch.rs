@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::sync_channel;
+use std::thread;
+
+use rayon::iter::Either;
+use rayon::prelude::*;
+
+use crate::prelude::*;
+use crate::schedule::Schedule;
+
+/// Returns the position of a file on disk. Files are read in inode
+/// order, which approximates the on-disk order on most file systems
+/// and avoids random seeks on spinning disks.
+#[cfg(unix)]
+fn disk_order(path: &Path) -> (u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+
+    path.metadata()
+        .map(|metadata| (metadata.dev(), metadata.ino()))
+        .unwrap_or((u64::MAX, u64::MAX))
+}
+
+#[cfg(not(unix))]
+fn disk_order(_path: &Path) -> (u64, u64) {
+    (0, 0)
+}
+
+/// Sorts the (enumerated) paths by their on-disk order.
+fn sorted(paths: Vec<PathBuf>) -> Vec<(usize, PathBuf)> {
+    let mut paths: Vec<_> = paths
+        .into_iter()
+        .enumerate()
+        .map(|(idx, path)| (disk_order(&path), idx, path))
+        .collect();
+
+    paths.sort_by_key(|(key, idx, _)| (*key, *idx));
+    paths
+        .into_iter()
+        .map(|(_, idx, path)| (idx, path))
+        .collect()
+}
+
+/// Loads the documents of the given paths.
+///
+/// Without a read-ahead, the documents are read in parallel (random
+/// I/O) in the order given by the schedule. Otherwise the documents are
+/// read sequentially in on-disk order by a background thread, which
+/// keeps at most `read_ahead` documents in memory, and are processed in
+/// parallel. The items are tagged with the position of the path in
+/// `paths`; the order of the items is unspecified.
+pub(crate) fn documents(
+    paths: Vec<PathBuf>,
+    read_ahead: Option<usize>,
+    schedule: Schedule,
+) -> impl ParallelIterator<Item = (usize, DatashedResult<Document>)> {
+    match read_ahead {
+        None => {
+            let (order, max_len) = schedule.order(&paths);
+            Either::Left(
+                order.into_par_iter().with_max_len(max_len).map(
+                    move |idx| (idx, Document::from_path(&paths[idx])),
+                ),
+            )
+        }
+        Some(n) => {
+            let (tx, rx) = sync_channel(n.max(1));
+            thread::spawn(move || {
+                for (idx, path) in sorted(paths) {
+                    if tx
+                        .send((idx, Document::from_path(path)))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+
+            Either::Right(rx.into_iter().par_bridge())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn documents_read_ahead() -> TestResult {
+        let dir = crate::testing::temp_dir()?;
+        let paths: Vec<PathBuf> = (0..8)
+            .map(|i| {
+                let path = dir.path().join(format!("{i}.txt"));
+                std::fs::write(&path, format!("doc {i}")).unwrap();
+                path
+            })
+            .collect();
+
+        let mut docs: Vec<(usize, String)> =
+            documents(paths.clone(), Some(2), Schedule::default())
+                .map(|(idx, doc)| {
+                    (
+                        idx,
+                        String::from_utf8_lossy(doc.unwrap().as_ref())
+                            .into(),
+                    )
+                })
+                .collect();
+
+        docs.sort();
+        assert_eq!(docs.len(), 8);
+        assert!(docs
+            .iter()
+            .all(|(idx, doc)| *doc == format!("doc {idx}")));
+        Ok(())
+    }
+}
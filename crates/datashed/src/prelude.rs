@@ -3,3 +3,4 @@ pub(crate) use crate::datashed::Datashed;
 pub(crate) use crate::document::Document;
 pub(crate) use crate::error::{bail, DatashedError, DatashedResult};
 pub(crate) use crate::progress::ProgressBarBuilder;
+pub(crate) use crate::projection::Columns;
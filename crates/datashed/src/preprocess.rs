@@ -0,0 +1,195 @@
+use std::sync::LazyLock;
+
+use bstr::ByteSlice;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use unicode_categories::UnicodeCategories;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::config::Config;
+use crate::error::{bail, DatashedResult};
+
+static HYPHENATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(\p{L})[-\u{00AD}][ \t]*\r?\n\s*(\p{Ll})").unwrap()
+});
+
+/// Unicode normalization forms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Normalization {
+    Nfc,
+    Nfd,
+    Nfkc,
+    Nfkd,
+}
+
+/// A named preprocessing profile.
+///
+/// Profiles are defined in the `[preprocess.<name>]` tables of the
+/// datashed config and are shared by all token-level computations
+/// (`vocab`, `keywords`, `lfreq`, `stopwords`). The steps are applied
+/// in the following order: de-hyphenation, unicode normalization,
+/// tokenization (unicode word boundaries), punctuation stripping,
+/// filtering by token length and lowercasing.
+///
+/// ```toml
+/// [preprocess.default]
+/// lowercase = true
+/// normalization = "nfkc"
+/// strip-punctuation = true
+/// dehyphenate = true
+/// min-token-len = 3
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub(crate) struct Preprocess {
+    /// Whether to convert the text to lowercase or not.
+    pub(crate) lowercase: bool,
+
+    /// The unicode normalization form.
+    pub(crate) normalization: Option<Normalization>,
+
+    /// Whether to remove punctuation characters from tokens or not.
+    pub(crate) strip_punctuation: bool,
+
+    /// Whether to join words, which are hyphenated at line breaks.
+    pub(crate) dehyphenate: bool,
+
+    /// Ignore tokens with a length (in characters) less than `n`.
+    pub(crate) min_token_len: Option<usize>,
+}
+
+impl Default for Preprocess {
+    fn default() -> Self {
+        Self {
+            lowercase: true,
+            normalization: Some(Normalization::Nfc),
+            strip_punctuation: false,
+            dehyphenate: false,
+            min_token_len: None,
+        }
+    }
+}
+
+impl Preprocess {
+    /// Returns the profile `name` of the config or the default
+    /// profile, if no name is given.
+    pub(crate) fn from_config(
+        config: &Config,
+        name: Option<&str>,
+    ) -> DatashedResult<Self> {
+        let Some(name) = name else {
+            return Ok(Self::default());
+        };
+
+        match config.preprocess.get(name) {
+            Some(profile) => Ok(profile.clone()),
+            None => bail!("unknown preprocessing profile '{name}'"),
+        }
+    }
+
+    /// Sets the minimum token length, unless the profile defines one.
+    pub(crate) fn with_min_token_len(mut self, n: usize) -> Self {
+        self.min_token_len.get_or_insert(n);
+        self
+    }
+
+    /// Applies the character-level steps (de-hyphenation,
+    /// normalization and lowercasing) to the given text.
+    pub(crate) fn normalize(&self, text: &str) -> String {
+        let text = self.normalize_case_preserving(text);
+        if self.lowercase {
+            text.to_lowercase()
+        } else {
+            text
+        }
+    }
+
+    fn normalize_case_preserving(&self, text: &str) -> String {
+        let text = if self.dehyphenate {
+            HYPHENATION.replace_all(text, "$1$2")
+        } else {
+            text.into()
+        };
+
+        match self.normalization {
+            None => text.into_owned(),
+            Some(Normalization::Nfc) => text.nfc().collect(),
+            Some(Normalization::Nfd) => text.nfd().collect(),
+            Some(Normalization::Nfkc) => text.nfkc().collect(),
+            Some(Normalization::Nfkd) => text.nfkd().collect(),
+        }
+    }
+
+    /// Returns the tokens of the given text.
+    #[inline]
+    pub(crate) fn tokens(&self, text: &str) -> Vec<String> {
+        self.tokens_filtered(text, |_| true)
+    }
+
+    /// Returns the tokens of the given text, which satisfy the given
+    /// predicate. The predicate is evaluated before lowercasing.
+    pub(crate) fn tokens_filtered<F>(
+        &self,
+        text: &str,
+        mut predicate: F,
+    ) -> Vec<String>
+    where
+        F: FnMut(&str) -> bool,
+    {
+        let text = self.normalize_case_preserving(text);
+        let min_len = self.min_token_len.unwrap_or(1);
+
+        text.as_bytes()
+            .words()
+            .map(|word| {
+                if self.strip_punctuation {
+                    word.chars()
+                        .filter(|c| !c.is_punctuation())
+                        .collect()
+                } else {
+                    word.to_string()
+                }
+            })
+            .filter(|word| word.chars().count() >= min_len)
+            .filter(|word| predicate(word))
+            .map(|word| {
+                if self.lowercase {
+                    word.to_lowercase()
+                } else {
+                    word
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preprocess_default() {
+        let profile = Preprocess::default();
+        assert_eq!(
+            profile.tokens("Die Straße, der Weg."),
+            vec!["die", "straße", "der", "weg"]
+        );
+    }
+
+    #[test]
+    fn preprocess_profile() {
+        let profile = Preprocess {
+            lowercase: false,
+            normalization: Some(Normalization::Nfkc),
+            strip_punctuation: true,
+            dehyphenate: true,
+            min_token_len: Some(3),
+        };
+
+        assert_eq!(
+            profile.tokens("Ein Bei-\nspiel für U.S. ﬁle"),
+            vec!["Ein", "Beispiel", "für", "file"]
+        );
+    }
+}
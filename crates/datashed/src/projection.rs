@@ -0,0 +1,164 @@
+use polars::prelude::*;
+
+use crate::datashed::Datashed;
+use crate::error::{bail, DatashedResult};
+
+/// The `--columns` option of commands, which write a table of
+/// documents.
+#[derive(Debug, Default, Clone, clap::Args)]
+pub(crate) struct Columns {
+    /// A comma-separated list of the columns of the output (in the
+    /// given order). Columns, which aren't part of the output, are
+    /// taken from the index (joined on `path` and, if present,
+    /// `page_no`). By default, all columns are written.
+    #[arg(long, value_name = "columns", value_delimiter = ',')]
+    columns: Vec<String>,
+}
+
+impl Columns {
+    /// Returns the selected columns or the `default` columns, if no
+    /// column was selected.
+    pub(crate) fn with_default(mut self, default: &[&str]) -> Self {
+        if self.columns.is_empty() {
+            self.columns =
+                default.iter().map(|s| s.to_string()).collect();
+        }
+
+        self
+    }
+
+    /// Projects the data frame onto the selected columns.
+    pub(crate) fn project(
+        &self,
+        df: DataFrame,
+        datashed: &Datashed,
+    ) -> DatashedResult<DataFrame> {
+        if self.columns.is_empty() {
+            return Ok(df);
+        }
+
+        let missing: Vec<&str> = self
+            .columns
+            .iter()
+            .map(String::as_str)
+            .filter(|name| df.column(name).is_err())
+            .collect();
+
+        let has_page_no = df.column("page_no").is_ok();
+        let mut df = df.lazy();
+        if !missing.is_empty() {
+            let mut index = datashed.index_lazy()?;
+            let schema = index.collect_schema()?;
+
+            if let Some(name) =
+                missing.iter().find(|name| !schema.contains(name))
+            {
+                bail!("unknown column '{name}'");
+            }
+
+            // A per-page index contains several rows per path, which
+            // are joined on the page number, if the data frame has
+            // one. Otherwise the first row of each path is taken.
+            let mut keys = vec![col("path")];
+            if schema.contains("page_no") {
+                if has_page_no {
+                    keys.push(col("page_no"));
+                } else {
+                    index = index.unique_stable(
+                        Some(vec!["path".into()]),
+                        UniqueKeepStrategy::First,
+                    );
+                }
+            }
+
+            let mut columns = keys.clone();
+            columns.extend(missing.iter().map(|name| col(*name)));
+
+            df = df.join(
+                index.select(columns),
+                keys.clone(),
+                keys,
+                JoinArgs::new(JoinType::Left),
+            );
+        }
+
+        Ok(df
+            .select(self.columns.iter().map(|name| col(name.as_str())))
+            .collect()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+    use crate::testing::temp_datashed;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn project_columns() -> TestResult {
+        let (_dir, datashed) = temp_datashed()?;
+        let root_dir = datashed.base_dir();
+
+        let mut index = df!(
+            "path" => ["a.txt", "b.txt"],
+            "kind" => ["book", "toc"],
+        )?;
+        IpcWriter::new(File::create(root_dir.join(Datashed::INDEX))?)
+            .finish(&mut index)?;
+
+        let df = df!("path" => ["b.txt"], "score" => [0.5])?;
+
+        let columns = Columns::default();
+        assert_eq!(columns.project(df.clone(), &datashed)?, df);
+
+        let columns =
+            Columns::default().with_default(&["score", "kind"]);
+        let result = columns.project(df.clone(), &datashed)?;
+        assert_eq!(result, df!("score" => [0.5], "kind" => ["toc"])?);
+
+        let columns = Columns::default().with_default(&["foo"]);
+        assert!(columns.project(df, &datashed).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn project_columns_per_page() -> TestResult {
+        let (_dir, datashed) = temp_datashed()?;
+        let root_dir = datashed.base_dir();
+
+        let mut index = df!(
+            "path" => ["a.txt", "a.txt", "b.txt"],
+            "page_no" => [1u32, 2, 1],
+            "kind" => ["book", "book", "toc"],
+            "lang_code" => ["ger", "eng", "ger"],
+        )?;
+        IpcWriter::new(File::create(root_dir.join(Datashed::INDEX))?)
+            .finish(&mut index)?;
+
+        let df =
+            df!("path" => ["a.txt", "b.txt"], "score" => [0.5, 0.7])?;
+        let columns =
+            Columns::default().with_default(&["path", "kind"]);
+        let result = columns.project(df, &datashed)?;
+        assert_eq!(
+            result,
+            df!(
+                "path" => ["a.txt", "b.txt"],
+                "kind" => ["book", "toc"],
+            )?
+        );
+
+        let df = df!("path" => ["a.txt"], "page_no" => [2u32])?;
+        let columns =
+            Columns::default().with_default(&["page_no", "lang_code"]);
+        let result = columns.project(df, &datashed)?;
+        assert_eq!(
+            result,
+            df!("page_no" => [2u32], "lang_code" => ["eng"])?
+        );
+        Ok(())
+    }
+}
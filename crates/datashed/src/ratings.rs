@@ -0,0 +1,702 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use hashbrown::{HashMap, HashSet};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{bail, DatashedResult};
+
+/// The columns of the ratings file written by `datashed serve`.
+pub(crate) const SERVE_COLUMNS: [&str; 7] = [
+    "remote",
+    "path",
+    "hash",
+    "rating",
+    "comment",
+    "username",
+    "created_at",
+];
+
+/// A level of a rating scale.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct RatingLevel {
+    /// The label of the level, which is stored in the ratings file
+    /// (e.g. `C` or `relevant`).
+    pub(crate) label: String,
+
+    /// A short description of the level, which is shown to the
+    /// raters.
+    #[serde(skip_serializing_if = "String::is_empty", default)]
+    pub(crate) description: String,
+
+    /// The numeric score of the level in the range `[0, 1]`. If not
+    /// set, the score is derived from the position of the level.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) score: Option<f64>,
+}
+
+impl RatingLevel {
+    fn new(label: &str, description: &str, score: f64) -> Self {
+        Self {
+            label: label.into(),
+            description: description.into(),
+            score: Some(score),
+        }
+    }
+}
+
+/// A scale of human judgments of the data quality of a document.
+///
+/// The levels are ordered from the best to the worst judgment. The
+/// default scale consists of the levels `C` (correct), `C-`, `P+`,
+/// `P` (partial), `P-` and `I` (incorrect).
+///
+/// ```toml
+/// [scales.relevance]
+/// levels = [
+///     { label = "relevant", description = "relevant" },
+///     { label = "irrelevant", description = "not relevant" },
+/// ]
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct RatingScale {
+    pub(crate) levels: Vec<RatingLevel>,
+}
+
+impl Default for RatingScale {
+    fn default() -> Self {
+        Self {
+            levels: vec![
+                RatingLevel::new("C", "correct", 1.0),
+                RatingLevel::new("C-", "correct minus", 0.8),
+                RatingLevel::new("P+", "partial plus", 0.6),
+                RatingLevel::new("P", "partial", 0.5),
+                RatingLevel::new("P-", "partial minus", 0.4),
+                RatingLevel::new("I", "incorrect", 0.0),
+            ],
+        }
+    }
+}
+
+impl RatingScale {
+    /// Checks that the scale is non-empty, the labels are unique and
+    /// the scores are in the range `[0, 1]`.
+    pub(crate) fn validate(&self) -> DatashedResult<()> {
+        if self.levels.is_empty() {
+            bail!("rating scale without levels");
+        }
+
+        let mut seen = HashSet::new();
+        for level in self.levels.iter() {
+            if level.label.is_empty() {
+                bail!("rating level without label");
+            }
+
+            if !seen.insert(level.label.as_str()) {
+                bail!("duplicate rating level '{}'", level.label);
+            }
+
+            if let Some(score) = level.score {
+                if !(0.0..=1.0).contains(&score) {
+                    bail!(
+                        "score of rating level '{}' out of range",
+                        level.label
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the position of the level `label` (0 is the best
+    /// level).
+    pub(crate) fn position(&self, label: &str) -> Option<usize> {
+        self.levels.iter().position(|level| level.label == label)
+    }
+
+    /// Returns the level `label` or an error, if the label isn't
+    /// part of the scale.
+    pub(crate) fn level(
+        &self,
+        label: &str,
+    ) -> DatashedResult<&RatingLevel> {
+        match self.position(label) {
+            Some(pos) => Ok(&self.levels[pos]),
+            None => bail!("invalid rating '{label}'"),
+        }
+    }
+
+    /// Returns the numeric score of the level at `pos`. Levels without
+    /// an explicit score are mapped linearly from $1.0$ (best level)
+    /// to $0.0$ (worst level).
+    pub(crate) fn score(&self, pos: usize) -> f64 {
+        match self.levels[pos].score {
+            Some(score) => score,
+            None if self.levels.len() == 1 => 1.0,
+            None => 1.0 - pos as f64 / (self.levels.len() - 1) as f64,
+        }
+    }
+}
+
+/// Returns `true`, if the document hash of a submitted rating is
+/// well-formed (8 to 64 hex digits).
+pub(crate) fn is_valid_hash(hash: &str) -> bool {
+    (8..=64).contains(&hash.len())
+        && hash.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Reads a ratings file.
+///
+/// The file is either the (headerless) ratings file written by
+/// `datashed serve` or a CSV file with a header containing at least
+/// the columns `path`, `hash`, `rating` and `username` (or `user`).
+/// The result consists of exactly these four columns.
+pub(crate) fn read_ratings<P: AsRef<Path>>(
+    path: P,
+) -> DatashedResult<DataFrame> {
+    let path = path.as_ref();
+    let header = BufReader::new(File::open(path)?)
+        .lines()
+        .next()
+        .transpose()?
+        .unwrap_or_default();
+
+    if header.is_empty() {
+        return Ok(DataFrame::new(
+            ["path", "hash", "rating", "username"]
+                .into_iter()
+                .map(|name| {
+                    Column::new_empty(name.into(), &DataType::String)
+                })
+                .collect(),
+        )?);
+    }
+
+    let has_header = header
+        .split(',')
+        .any(|name| name == "path" || name == "rating");
+
+    let mut df = CsvReadOptions::default()
+        .with_has_header(has_header)
+        .with_infer_schema_length(Some(0))
+        .try_into_reader_with_file_path(Some(path.into()))?
+        .finish()?;
+
+    if !has_header {
+        df.set_column_names(SERVE_COLUMNS)?;
+    } else if df.column("username").is_err() {
+        df.rename("user", "username".into())?;
+    }
+
+    Ok(df.select(["path", "hash", "rating", "username"])?)
+}
+
+/// Aggregates the ratings per document version (path and hash).
+///
+/// Only the latest rating of each user is taken into account. Ratings,
+/// whose label isn't part of the `scale` (e.g. ratings of a campaign
+/// with another scale), are ignored. The result contains the columns
+/// `path`, `hash` (the first eight characters), `rating_majority` (the
+/// most frequent rating; ties are broken in favour of the worse
+/// rating), `rating_mean` (the mean score) and `rating_count` (the
+/// number of raters).
+pub(crate) fn aggregate(
+    ratings: &DataFrame,
+    scale: &RatingScale,
+) -> DatashedResult<DataFrame> {
+    let path = ratings.column("path")?.str()?;
+    let hash = ratings.column("hash")?.str()?;
+    let rating = ratings.column("rating")?.str()?;
+    let username = ratings.column("username")?.str()?;
+
+    let mut latest: HashMap<(&str, &str, &str), usize> = HashMap::new();
+    for idx in 0..ratings.height() {
+        let (Some(path), Some(hash), Some(rating)) =
+            (path.get(idx), hash.get(idx), rating.get(idx))
+        else {
+            continue;
+        };
+
+        let Some(pos) = scale.position(rating) else {
+            continue;
+        };
+
+        let hash = hash.get(..8).unwrap_or(hash);
+        let username = username.get(idx).unwrap_or_default();
+        latest.insert((path, hash, username), pos);
+    }
+
+    let mut docs: HashMap<(&str, &str), Vec<usize>> = HashMap::new();
+    for ((path, hash, _), rating) in latest.into_iter() {
+        docs.entry((path, hash)).or_default().push(rating);
+    }
+
+    let mut paths = Vec::with_capacity(docs.len());
+    let mut hashes = Vec::with_capacity(docs.len());
+    let mut majority = Vec::with_capacity(docs.len());
+    let mut mean = Vec::with_capacity(docs.len());
+    let mut count = Vec::with_capacity(docs.len());
+
+    for ((path, hash), ratings) in docs.into_iter() {
+        let mut freqs: HashMap<usize, usize> = HashMap::new();
+        for pos in ratings.iter() {
+            *freqs.entry(*pos).or_default() += 1;
+        }
+
+        let (pos, _) = freqs
+            .into_iter()
+            .max_by(|(a, n), (b, m)| n.cmp(m).then(a.cmp(b)))
+            .unwrap();
+
+        paths.push(path.to_string());
+        hashes.push(hash.to_string());
+        majority.push(scale.levels[pos].label.clone());
+        mean.push(
+            ratings.iter().map(|pos| scale.score(*pos)).sum::<f64>()
+                / ratings.len() as f64,
+        );
+        count.push(ratings.len() as u32);
+    }
+
+    Ok(DataFrame::new(vec![
+        Column::new("path".into(), paths),
+        Column::new("hash".into(), hashes),
+        Column::new("rating_majority".into(), majority),
+        Column::new("rating_mean".into(), mean),
+        Column::new("rating_count".into(), count),
+    ])?)
+}
+
+/// The upper bounds (exclusive) and names of the text length buckets
+/// (see [length_bucket]).
+const LENGTH_BUCKETS: [(u64, &str); 5] = [
+    (1_000, "<1k"),
+    (10_000, "1k-10k"),
+    (100_000, "10k-100k"),
+    (1_000_000, "100k-1M"),
+    (u64::MAX, ">=1M"),
+];
+
+/// Returns the name of the (logarithmic) bucket of a text length (in
+/// characters).
+pub(crate) fn length_bucket(strlen: u64) -> &'static str {
+    LENGTH_BUCKETS
+        .iter()
+        .find(|(bound, _)| strlen < *bound)
+        .map(|(_, name)| *name)
+        .unwrap_or(">=1M")
+}
+
+/// Converts the ratings (see [read_ratings]) into an analysis table.
+///
+/// The result contains the columns `path`, `hash` (the first eight
+/// characters), `rating`, `score` (the numeric score of the rating on
+/// the `scale`; null, if the rating isn't part of the scale) and
+/// `username`.
+pub(crate) fn scored(
+    ratings: &DataFrame,
+    scale: &RatingScale,
+) -> DatashedResult<DataFrame> {
+    let hash: Vec<Option<String>> = ratings
+        .column("hash")?
+        .str()?
+        .iter()
+        .map(|hash| {
+            hash.map(|hash| hash.get(..8).unwrap_or(hash).into())
+        })
+        .collect();
+
+    let score: Vec<Option<f64>> = ratings
+        .column("rating")?
+        .str()?
+        .iter()
+        .map(|rating| {
+            rating
+                .and_then(|rating| scale.position(rating))
+                .map(|pos| scale.score(pos))
+        })
+        .collect();
+
+    Ok(DataFrame::new(vec![
+        ratings.column("path")?.clone(),
+        Column::new("hash".into(), hash),
+        ratings.column("rating")?.clone(),
+        Column::new("score".into(), score),
+        ratings.column("username")?.clone(),
+    ])?)
+}
+
+/// The reason why an imported rating was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Rejection {
+    /// The document isn't part of the index.
+    UnknownDocument,
+
+    /// The hash of the rating isn't well-formed.
+    InvalidHash,
+
+    /// The rating refers to another version of the document.
+    OutdatedHash,
+
+    /// The rating isn't part of the rating scale.
+    InvalidRating,
+
+    /// The rating is already part of the ratings store.
+    Duplicate,
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownDocument => write!(f, "unknown document"),
+            Self::InvalidHash => write!(f, "invalid hash"),
+            Self::OutdatedHash => write!(f, "outdated hash"),
+            Self::InvalidRating => write!(f, "invalid rating"),
+            Self::Duplicate => write!(f, "duplicate"),
+        }
+    }
+}
+
+/// The result of an import of external ratings.
+#[derive(Debug, Default)]
+pub(crate) struct Import {
+    /// The accepted ratings (see [SERVE_COLUMNS]).
+    pub(crate) records: Vec<[String; 7]>,
+
+    /// The rejected ratings: the row number (starting at 1), the key
+    /// of the document (path or PPN) and the reason of the rejection.
+    pub(crate) rejected: Vec<(usize, String, Rejection)>,
+}
+
+/// Validates external ratings and converts them into records of the
+/// ratings store.
+///
+/// The ratings are read from a CSV file with a header, which contains
+/// the columns `rating`, `username` (or `user`) and either `path` or
+/// `ppn` (or `idn`). The optional columns `comment` and `created_at`
+/// are taken over. The optional column `hash` (8 to 64 hex digits) must
+/// start with the hash of the current document version; the stored
+/// hash is always the hash of the index. Ratings of unknown documents,
+/// ratings with a malformed hash or of outdated document versions,
+/// ratings not part of the `scale` and ratings already contained in
+/// the ratings store (`existing`, see [read_ratings]; an empty frame,
+/// if there is no store yet) are rejected.
+pub(crate) fn import_ratings<R: std::io::Read>(
+    reader: R,
+    index: &DataFrame,
+    existing: &DataFrame,
+    scale: &RatingScale,
+    remote: &str,
+    created_at: &str,
+) -> DatashedResult<Import> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let header = reader.headers()?.clone();
+    let column = |names: &[&str]| {
+        header.iter().position(|name| names.contains(&name))
+    };
+
+    let (Some(rating_idx), Some(username_idx)) =
+        (column(&["rating"]), column(&["username", "user"]))
+    else {
+        bail!("missing column 'rating' or 'username'");
+    };
+
+    let path_idx = column(&["path"]);
+    let ppn_idx = column(&["ppn", "idn"]);
+    if path_idx.is_none() && ppn_idx.is_none() {
+        bail!("missing column 'path' or 'ppn'");
+    }
+
+    let hash_idx = column(&["hash"]);
+    let comment_idx = column(&["comment"]);
+    let created_at_idx = column(&["created_at"]);
+
+    let idn = index.column("idn")?.cast(&DataType::String)?;
+    let (idn, path, hash) = (
+        idn.str()?,
+        index.column("path")?.str()?,
+        index.column("hash")?.str()?,
+    );
+
+    let mut by_path: HashMap<&str, &str> = HashMap::new();
+    let mut by_ppn: HashMap<&str, (&str, &str)> = HashMap::new();
+    for idx in 0..index.height() {
+        let (Some(path), Some(hash)) = (path.get(idx), hash.get(idx))
+        else {
+            continue;
+        };
+
+        by_path.insert(path, hash);
+        if let Some(idn) = idn.get(idx) {
+            by_ppn.insert(idn, (path, hash));
+        }
+    }
+
+    let mut seen: HashSet<(String, String, String, String)> =
+        HashSet::new();
+    if existing.height() > 0 {
+        let paths = existing.column("path")?.str()?;
+        let hashes = existing.column("hash")?.str()?;
+        let usernames = existing.column("username")?.str()?;
+        let ratings = existing.column("rating")?.str()?;
+
+        for idx in 0..existing.height() {
+            let hash = hashes.get(idx).unwrap_or_default();
+            seen.insert((
+                paths.get(idx).unwrap_or_default().into(),
+                hash.get(..8).unwrap_or(hash).into(),
+                usernames.get(idx).unwrap_or_default().into(),
+                ratings.get(idx).unwrap_or_default().into(),
+            ));
+        }
+    }
+
+    let mut result = Import::default();
+    for (row, record) in reader.records().enumerate() {
+        let record = record?;
+        let row = row + 1;
+        let get = |idx: Option<usize>| {
+            idx.and_then(|idx| record.get(idx))
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+
+        let key = get(path_idx).or(get(ppn_idx)).unwrap_or_default();
+        let document = match get(path_idx) {
+            Some(path) => {
+                by_path.get_key_value(path).map(|(p, h)| (*p, *h))
+            }
+            None => {
+                get(ppn_idx).and_then(|ppn| by_ppn.get(ppn).copied())
+            }
+        };
+
+        let Some((path, current)) = document else {
+            result.rejected.push((
+                row,
+                key.into(),
+                Rejection::UnknownDocument,
+            ));
+            continue;
+        };
+
+        if let Some(hash) = get(hash_idx) {
+            if !is_valid_hash(hash) {
+                result.rejected.push((
+                    row,
+                    key.into(),
+                    Rejection::InvalidHash,
+                ));
+                continue;
+            }
+
+            if hash.get(..current.len()) != Some(current) {
+                result.rejected.push((
+                    row,
+                    key.into(),
+                    Rejection::OutdatedHash,
+                ));
+                continue;
+            }
+        }
+
+        let rating = get(Some(rating_idx)).unwrap_or_default();
+        if scale.position(rating).is_none() {
+            result.rejected.push((
+                row,
+                key.into(),
+                Rejection::InvalidRating,
+            ));
+            continue;
+        }
+
+        let username = get(Some(username_idx)).unwrap_or_default();
+        if !seen.insert((
+            path.into(),
+            current.into(),
+            username.into(),
+            rating.into(),
+        )) {
+            result.rejected.push((
+                row,
+                key.into(),
+                Rejection::Duplicate,
+            ));
+            continue;
+        }
+
+        result.records.push([
+            remote.into(),
+            path.into(),
+            current.into(),
+            rating.into(),
+            get(comment_idx).unwrap_or_default().into(),
+            username.into(),
+            get(created_at_idx).unwrap_or(created_at).into(),
+        ]);
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn valid_hash() {
+        assert!(is_valid_hash("0a1b2c3d"));
+        assert!(is_valid_hash(&"f".repeat(64)));
+        assert!(!is_valid_hash("0a1b2c3"));
+        assert!(!is_valid_hash(&"f".repeat(65)));
+        assert!(!is_valid_hash("0a1b2c3dü"));
+        assert!(!is_valid_hash("0a1b2c3g"));
+    }
+
+    #[test]
+    fn aggregate_ratings() -> TestResult {
+        let ratings = df!(
+            "path" => [
+                "a.txt", "a.txt", "a.txt", "a.txt", "b.txt", "b.txt",
+            ],
+            "hash" => [
+                "0123456789",
+                "01234567",
+                "01234567",
+                "01234567",
+                "ff",
+                "ff",
+            ],
+            "rating" => ["I", "C", "P", "C-", "C", "X"],
+            "username" => ["x", "x", "y", "z", "x", "y"],
+        )?;
+
+        let df = aggregate(&ratings, &RatingScale::default())?
+            .sort(["path"], Default::default())?;
+
+        let majority = df.column("rating_majority")?.str()?;
+        assert_eq!(majority.get(0), Some("P"));
+        assert_eq!(majority.get(1), Some("C"));
+
+        let count = df.column("rating_count")?.u32()?;
+        assert_eq!(count.get(0), Some(3));
+        assert_eq!(count.get(1), Some(1));
+
+        let mean = df.column("rating_mean")?.f64()?;
+        assert!((mean.get(0).unwrap() - 2.3 / 3.0).abs() < 1e-9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rating_scale() -> TestResult {
+        let scale: RatingScale = toml::from_str(
+            r#"levels = [
+                { label = "1" }, { label = "2" }, { label = "3" },
+                { label = "4" }, { label = "5", score = 0.1 },
+            ]"#,
+        )?;
+
+        assert!(scale.validate().is_ok());
+        assert_eq!(scale.position("2"), Some(1));
+        assert_eq!(scale.score(1), 0.75);
+        assert_eq!(scale.score(4), 0.1);
+        assert!(scale.level("6").is_err());
+        assert!(RatingScale::default().validate().is_ok());
+
+        let scale = RatingScale {
+            levels: vec![
+                RatingLevel::new("yes", "", 1.0),
+                RatingLevel::new("yes", "", 0.0),
+            ],
+        };
+        assert!(scale.validate().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn import_external_ratings() -> TestResult {
+        let index = df!(
+            "path" => ["a.txt", "b.txt"],
+            "hash" => ["01234567", "fedcba98"],
+            "idn" => ["1", "2"],
+        )?;
+
+        let existing = df!(
+            "path" => ["a.txt"],
+            "hash" => ["01234567"],
+            "rating" => ["C"],
+            "username" => ["x"],
+        )?;
+
+        let full = format!("01234567{}", "89abcdef".repeat(7));
+        let input = format!(
+            "ppn,path,hash,rating,user\n\
+            1,,01234567,C,x\n\
+            1,,01234567,P,y\n\
+            ,b.txt,,I,x\n\
+            ,b.txt,00000000,C,x\n\
+            ,c.txt,,C,x\n\
+            2,,,Z,x\n\
+            1,,{full},C,z\n\
+            1,,{full},C,x\n\
+            ,a.txt,0,C,w\n"
+        );
+
+        let import = import_ratings(
+            input.as_bytes(),
+            &index,
+            &existing,
+            &RatingScale::default(),
+            "test",
+            "0",
+        )?;
+
+        assert_eq!(import.records.len(), 3);
+        assert_eq!(import.records[0][1], "a.txt");
+        assert_eq!(import.records[1][2], "fedcba98");
+        assert_eq!(import.records[2][2], "01234567");
+        assert_eq!(
+            import
+                .rejected
+                .iter()
+                .map(|(row, _, reason)| (*row, reason.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, Rejection::Duplicate),
+                (4, Rejection::OutdatedHash),
+                (5, Rejection::UnknownDocument),
+                (6, Rejection::InvalidRating),
+                (8, Rejection::Duplicate),
+                (9, Rejection::InvalidHash),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn scored_ratings() -> TestResult {
+        let ratings = df!(
+            "path" => ["a.txt", "b.txt"],
+            "hash" => ["0123456789", "ff"],
+            "rating" => ["C-", "X"],
+            "username" => ["x", "y"],
+        )?;
+
+        let df = scored(&ratings, &RatingScale::default())?;
+        assert_eq!(df.column("hash")?.str()?.get(0), Some("01234567"));
+        assert_eq!(df.column("score")?.f64()?.get(0), Some(0.8));
+        assert_eq!(df.column("score")?.f64()?.get(1), None);
+
+        assert_eq!(length_bucket(0), "<1k");
+        assert_eq!(length_bucket(10_000), "10k-100k");
+        assert_eq!(length_bucket(5_000_000), ">=1M");
+        Ok(())
+    }
+}
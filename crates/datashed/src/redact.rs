@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::atomic::AtomicFile;
+use crate::prelude::*;
+use crate::stats::write_index;
+use crate::{artifacts, signature};
+
+/// The redaction policy of an index column.
+///
+/// ```toml
+/// [redact.path]
+/// policy = "hash"
+/// salt = "s3cr3t"
+///
+/// [redact.annotator]
+/// policy = "drop"
+///
+/// [redact.first_entered]
+/// policy = "generalize"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "policy", rename_all = "kebab-case")]
+pub(crate) enum ColumnPolicy {
+    /// Removes the column.
+    Drop,
+
+    /// Replaces each value by the hex-encoded SHA256 digest of the
+    /// (salted) value. Equal values are mapped to equal digests.
+    Hash {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        salt: Option<String>,
+    },
+
+    /// Coarsens the values: dates are reduced to the year, numbers are
+    /// rounded down to a multiple of `step` (default: 10) and strings
+    /// are cut after the first `depth` path components (default: 1).
+    Generalize {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        step: Option<f64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        depth: Option<usize>,
+    },
+}
+
+impl ColumnPolicy {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Drop => "drop",
+            Self::Hash { .. } => "hash",
+            Self::Generalize { .. } => "generalize",
+        }
+    }
+}
+
+/// An applied redaction.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct Redaction {
+    pub(crate) column: String,
+    pub(crate) policy: &'static str,
+    pub(crate) dtype: String,
+    pub(crate) values: usize,
+}
+
+/// The manifest of the applied redactions.
+#[derive(Debug, Serialize)]
+pub(crate) struct Manifest {
+    pub(crate) rows: usize,
+    pub(crate) redactions: Vec<Redaction>,
+
+    /// The configured columns, which don't exist in the index.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) missing: Vec<String>,
+}
+
+fn sha256(salt: &str, value: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(value.as_bytes());
+    hasher.finalize().iter().fold(String::new(), |mut out, b| {
+        let _ = write!(out, "{b:02x}");
+        out
+    })
+}
+
+/// Returns the first `depth` path components of the value.
+fn path_prefix(value: &str, depth: usize) -> String {
+    value
+        .split('/')
+        .take(depth.max(1))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Maps the (non-null) values of a column to strings.
+fn map_strings<F>(column: &Column, f: F) -> DatashedResult<Column>
+where
+    F: Fn(&str) -> String,
+{
+    let ca: StringChunked = column
+        .cast(&DataType::String)?
+        .str()?
+        .iter()
+        .map(|value| value.map(&f))
+        .collect();
+
+    Ok(ca.with_name(column.name().clone()).into_column())
+}
+
+/// Generalizes the values of a column.
+fn generalize(
+    column: &Column,
+    step: Option<f64>,
+    depth: Option<usize>,
+) -> DatashedResult<Column> {
+    let dtype = column.dtype();
+    match dtype {
+        DataType::Date | DataType::Datetime(_, _) => {
+            map_strings(column, |value| {
+                value.split('-').next().unwrap_or_default().to_string()
+            })
+        }
+        dtype if dtype.is_numeric() => {
+            let step = step.unwrap_or(10.0);
+            let ca: Float64Chunked = column
+                .cast(&DataType::Float64)?
+                .f64()?
+                .iter()
+                .map(|value| value.map(|v| (v / step).floor() * step))
+                .collect();
+
+            Ok(ca
+                .with_name(column.name().clone())
+                .into_column()
+                .cast(dtype)?)
+        }
+        _ => {
+            let depth = depth.unwrap_or(1);
+            map_strings(column, |value| path_prefix(value, depth))
+        }
+    }
+}
+
+/// Applies the column policies to the index and returns the redacted
+/// index and the manifest of the applied redactions.
+pub(crate) fn apply(
+    mut df: DataFrame,
+    policies: &BTreeMap<String, ColumnPolicy>,
+) -> DatashedResult<(DataFrame, Manifest)> {
+    let mut manifest = Manifest {
+        rows: df.height(),
+        redactions: vec![],
+        missing: vec![],
+    };
+
+    for (name, policy) in policies.iter() {
+        let Ok(column) = df.column(name) else {
+            manifest.missing.push(name.clone());
+            continue;
+        };
+
+        manifest.redactions.push(Redaction {
+            column: name.clone(),
+            policy: policy.name(),
+            dtype: column.dtype().to_string(),
+            values: column.len() - column.null_count(),
+        });
+
+        let column = match policy {
+            ColumnPolicy::Drop => {
+                df = df.drop(name)?;
+                continue;
+            }
+            ColumnPolicy::Hash { salt } => {
+                let salt = salt.as_deref().unwrap_or_default();
+                map_strings(column, |value| sha256(salt, value))?
+            }
+            ColumnPolicy::Generalize { step, depth } => {
+                generalize(column, *step, *depth)?
+            }
+        };
+
+        df.with_column(column)?;
+    }
+
+    Ok((df, manifest))
+}
+
+/// Returns the default path of the manifest of a redacted index.
+pub(crate) fn manifest_path(output: &Path) -> PathBuf {
+    let mut path = output.to_path_buf().into_os_string();
+    path.push(".redactions.json");
+    path.into()
+}
+
+/// Redacts the index of the datashed and writes the redacted index and
+/// the manifest of the applied redactions (JSON). If the config
+/// contains a signing key (`signing.key-file`), the redacted index is
+/// (re-)signed.
+pub(crate) fn publish(
+    datashed: &Datashed,
+    config: &Config,
+    output: &Path,
+    manifest: &Path,
+) -> DatashedResult<Manifest> {
+    let (mut df, redactions) =
+        apply(datashed.index()?, &config.redact)?;
+
+    let mut out = AtomicFile::create(output)?;
+    write_index(&mut df, &mut out)?;
+    out.commit()?;
+
+    let base_dir = datashed.base_dir();
+    artifacts::register(
+        datashed,
+        "redact-index",
+        output,
+        &[
+            base_dir.join(Datashed::INDEX),
+            base_dir.join(Datashed::CONFIG),
+        ],
+    )?;
+
+    if let Some(key_file) =
+        config.signing.as_ref().and_then(|s| s.key_file.as_ref())
+    {
+        signature::sign_file(&signature::read_key(key_file)?, output)?;
+    }
+
+    serde_json::to_writer_pretty(File::create(manifest)?, &redactions)
+        .map_err(DatashedError::other)?;
+
+    Ok(redactions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn redact_columns() -> TestResult {
+        let df = df!(
+            "path" => ["data/a/1.txt", "data/b/2.txt"],
+            "annotator" => ["alice", "bob"],
+            "size" => [1234u64, 99],
+            "hash" => ["x", "y"],
+        )?;
+
+        let policies = BTreeMap::from([
+            ("annotator".into(), ColumnPolicy::Drop),
+            (
+                "hash".into(),
+                ColumnPolicy::Hash {
+                    salt: Some("s".into()),
+                },
+            ),
+            (
+                "path".into(),
+                ColumnPolicy::Generalize {
+                    step: None,
+                    depth: Some(2),
+                },
+            ),
+            (
+                "size".into(),
+                ColumnPolicy::Generalize {
+                    step: Some(100.0),
+                    depth: None,
+                },
+            ),
+            ("user".into(), ColumnPolicy::Drop),
+        ]);
+
+        let (df, manifest) = apply(df, &policies)?;
+        assert_eq!(df.width(), 3);
+        assert!(df.column("annotator").is_err());
+        assert_eq!(df.column("path")?.str()?.get(1), Some("data/b"));
+        assert_eq!(df.column("size")?.u64()?.get(0), Some(1200));
+        assert_eq!(
+            df.column("hash")?.str()?.get(0),
+            Some(sha256("s", "x").as_str())
+        );
+        assert_eq!(manifest.redactions.len(), 4);
+        assert_eq!(manifest.missing, ["user"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+use std::fs;
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+
+/// The scheduling strategy of parallel document processing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Schedule {
+    /// Documents are distributed uniformly among the workers (in index
+    /// order).
+    #[default]
+    Uniform,
+
+    /// Documents are processed in descending order of their size,
+    /// one document per task, so that large documents don't stall
+    /// the workers at the end.
+    Size,
+
+    /// Documents are processed in index order, one document per task,
+    /// so that idle workers steal work from busy ones.
+    Dynamic,
+}
+
+impl Schedule {
+    /// Returns the processing order of the given paths and the
+    /// maximum number of documents per task.
+    pub(crate) fn order(
+        &self,
+        paths: &[PathBuf],
+    ) -> (Vec<usize>, usize) {
+        let mut order: Vec<usize> = (0..paths.len()).collect();
+        match self {
+            Self::Uniform => (order, usize::MAX),
+            Self::Dynamic => (order, 1),
+            Self::Size => {
+                let sizes: Vec<u64> = paths
+                    .iter()
+                    .map(|path| {
+                        fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+                    })
+                    .collect();
+
+                order.sort_by_key(|idx| std::cmp::Reverse(sizes[*idx]));
+                (order, 1)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn schedule_by_size() -> TestResult {
+        let dir = crate::testing::temp_dir()?;
+        let paths: Vec<PathBuf> = [3, 10, 1]
+            .iter()
+            .enumerate()
+            .map(|(i, len)| {
+                let path = dir.path().join(format!("{i}.txt"));
+                fs::write(&path, "x".repeat(*len)).unwrap();
+                path
+            })
+            .collect();
+
+        assert_eq!(Schedule::Size.order(&paths), (vec![1, 0, 2], 1));
+        assert_eq!(
+            Schedule::Uniform.order(&paths),
+            (vec![0, 1, 2], usize::MAX)
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::error::DatashedResult;
+
+/// Column names of older datashed versions and their current names.
+const LEGACY_ALIASES: [(&str, &str); 2] =
+    [("ppn", "idn"), ("doctype", "kind")];
+
+/// The target type of a column cast.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ColumnType {
+    String,
+    Int,
+    UInt,
+    Float,
+    Bool,
+}
+
+impl From<ColumnType> for DataType {
+    fn from(value: ColumnType) -> Self {
+        match value {
+            ColumnType::String => DataType::String,
+            ColumnType::Int => DataType::Int64,
+            ColumnType::UInt => DataType::UInt64,
+            ColumnType::Float => DataType::Float64,
+            ColumnType::Bool => DataType::Boolean,
+        }
+    }
+}
+
+/// A rule, which renames and/or casts a single column.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct ColumnRule {
+    pub(crate) rename: Option<String>,
+    pub(crate) cast: Option<ColumnType>,
+}
+
+/// A schema mapping applied to an index.
+///
+/// ```toml
+/// [schema]
+/// compat = true
+///
+/// [schema.columns.ppn]
+/// rename = "idn"
+/// cast = "string"
+/// ```
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct Schema {
+    /// Rename legacy columns (`ppn`, `doctype`) to their current names
+    /// (`idn`, `kind`), unless a column with the current name exists.
+    #[serde(default)]
+    pub(crate) compat: bool,
+
+    /// Rules keyed by the (original) column name. Rules of missing
+    /// columns are ignored.
+    #[serde(skip_serializing_if = "HashMap::is_empty", default)]
+    pub(crate) columns: HashMap<String, ColumnRule>,
+}
+
+impl Schema {
+    /// Returns `true` if the schema doesn't change any column.
+    pub(crate) fn is_empty(&self) -> bool {
+        !self.compat && self.columns.is_empty()
+    }
+
+    /// Applies the schema mapping to the given data frame.
+    pub(crate) fn apply(
+        &self,
+        mut df: DataFrame,
+    ) -> DatashedResult<DataFrame> {
+        if self.compat {
+            for (legacy, name) in LEGACY_ALIASES {
+                if df.column(legacy).is_ok() && df.column(name).is_err()
+                {
+                    df.rename(legacy, name.into())?;
+                }
+            }
+        }
+
+        for (name, rule) in self.columns.iter() {
+            if df.column(name).is_err() {
+                continue;
+            }
+
+            if let Some(dtype) = rule.cast {
+                let column = df.column(name)?.cast(&dtype.into())?;
+                df.with_column(column)?;
+            }
+
+            if let Some(ref new_name) = rule.rename {
+                df.rename(name, new_name.into())?;
+            }
+        }
+
+        Ok(df)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn schema_apply() -> TestResult {
+        let schema: Schema = toml::from_str(
+            r#"
+            compat = true
+
+            [columns.size]
+            rename = "bytes"
+            cast = "float"
+            "#,
+        )?;
+
+        let df = df!(
+            "ppn" => ["123"],
+            "doctype" => ["toc"],
+            "size" => [42u64],
+        )?;
+
+        let df = schema.apply(df)?;
+        assert_eq!(
+            df.get_column_names()
+                .into_iter()
+                .map(PlSmallStr::as_str)
+                .collect::<Vec<_>>(),
+            ["idn", "kind", "bytes"]
+        );
+        assert_eq!(df.column("bytes")?.dtype(), &DataType::Float64);
+
+        Ok(())
+    }
+}
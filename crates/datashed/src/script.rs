@@ -0,0 +1,177 @@
+use std::fmt::{self, Display};
+
+/// A Unicode script of letters.
+///
+/// Only the scripts, which are relevant to route documents to a
+/// processing branch, are distinguished; the letters of all other
+/// scripts are classified as [Script::Other].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Georgian,
+    Hebrew,
+    Arabic,
+    Syriac,
+    Devanagari,
+    Thai,
+    Hangul,
+    Kana,
+    Han,
+    Other,
+}
+
+impl Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Latin => "latin",
+            Self::Greek => "greek",
+            Self::Cyrillic => "cyrillic",
+            Self::Armenian => "armenian",
+            Self::Georgian => "georgian",
+            Self::Hebrew => "hebrew",
+            Self::Arabic => "arabic",
+            Self::Syriac => "syriac",
+            Self::Devanagari => "devanagari",
+            Self::Thai => "thai",
+            Self::Hangul => "hangul",
+            Self::Kana => "kana",
+            Self::Han => "han",
+            Self::Other => "other",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+impl Script {
+    /// All scripts (in the order of precedence of ties).
+    const ALL: [Script; 14] = [
+        Script::Latin,
+        Script::Greek,
+        Script::Cyrillic,
+        Script::Armenian,
+        Script::Georgian,
+        Script::Hebrew,
+        Script::Arabic,
+        Script::Syriac,
+        Script::Devanagari,
+        Script::Thai,
+        Script::Hangul,
+        Script::Kana,
+        Script::Han,
+        Script::Other,
+    ];
+
+    /// Returns the script of the character, if the character is a
+    /// letter.
+    pub(crate) fn of(c: char) -> Option<Self> {
+        if !c.is_alphabetic() {
+            return None;
+        }
+
+        Some(match c as u32 {
+            0x0041..=0x024F | 0x1E00..=0x1EFF | 0x2C60..=0x2C7F => {
+                Self::Latin
+            }
+            0xA720..=0xA7FF | 0xAB30..=0xAB6F | 0xFF21..=0xFF5A => {
+                Self::Latin
+            }
+            0x0370..=0x03FF | 0x1F00..=0x1FFF => Self::Greek,
+            0x0400..=0x052F | 0x1C80..=0x1C8F | 0x2DE0..=0x2DFF => {
+                Self::Cyrillic
+            }
+            0xA640..=0xA69F => Self::Cyrillic,
+            0x0530..=0x058F | 0xFB13..=0xFB17 => Self::Armenian,
+            0x10A0..=0x10FF | 0x1C90..=0x1CBF | 0x2D00..=0x2D2F => {
+                Self::Georgian
+            }
+            0x0590..=0x05FF | 0xFB1D..=0xFB4F => Self::Hebrew,
+            0x0600..=0x06FF | 0x0750..=0x077F | 0x08A0..=0x08FF => {
+                Self::Arabic
+            }
+            0xFB50..=0xFDFF | 0xFE70..=0xFEFF => Self::Arabic,
+            0x0700..=0x074F | 0x0860..=0x086F => Self::Syriac,
+            0x0900..=0x097F | 0xA8E0..=0xA8FF => Self::Devanagari,
+            0x0E00..=0x0E7F => Self::Thai,
+            0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => {
+                Self::Hangul
+            }
+            0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F => {
+                Self::Kana
+            }
+            0x2E80..=0x2FDF | 0x3005..=0x3007 | 0x3400..=0x4DBF => {
+                Self::Han
+            }
+            0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x3134F => {
+                Self::Han
+            }
+            _ => Self::Other,
+        })
+    }
+
+    /// Returns true, if the script is written from right to left.
+    pub(crate) fn is_rtl(&self) -> bool {
+        matches!(self, Self::Hebrew | Self::Arabic | Self::Syriac)
+    }
+}
+
+/// Returns the dominant script (the script of most letters) of the
+/// text and the ratio of right-to-left letters to all letters. A text
+/// without letters has no dominant script and a ratio of $0.0$.
+pub(crate) fn script_stats(text: &str) -> (Option<Script>, f64) {
+    let mut counts = [0usize; Script::Other as usize + 1];
+    let mut letters = 0;
+
+    for script in text.chars().filter_map(Script::of) {
+        counts[script as usize] += 1;
+        letters += 1;
+    }
+
+    if letters == 0 {
+        return (None, 0.0);
+    }
+
+    let rtl: usize = Script::ALL
+        .iter()
+        .filter(|script| script.is_rtl())
+        .map(|script| counts[*script as usize])
+        .sum();
+
+    // Ties are resolved in favour of the script listed first.
+    let dominant = Script::ALL
+        .iter()
+        .rev()
+        .max_by_key(|script| counts[**script as usize])
+        .copied();
+
+    (dominant, rtl as f64 / letters as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dominant_script() {
+        assert_eq!(
+            script_stats("Der Fuchs"),
+            (Some(Script::Latin), 0.0)
+        );
+        assert_eq!(script_stats("1984 ..."), (None, 0.0));
+
+        let (script, rtl) = script_stats("Война и мир (War)");
+        assert_eq!(script, Some(Script::Cyrillic));
+        assert_eq!(rtl, 0.0);
+
+        let (script, rtl) = script_stats("שלום abc");
+        assert_eq!(script, Some(Script::Hebrew));
+        assert_eq!(rtl, 4.0 / 7.0);
+
+        assert_eq!(Script::of('漢'), Some(Script::Han));
+        assert_eq!(Script::of('ق'), Some(Script::Arabic));
+        assert_eq!(Script::Kana.to_string(), "kana");
+    }
+}
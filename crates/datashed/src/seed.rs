@@ -0,0 +1,27 @@
+use std::sync::OnceLock;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// The seed of the current process (`--seed` or `runtime.seed`).
+static SEED: OnceLock<Option<u64>> = OnceLock::new();
+
+/// Sets the seed of the current process. Subsequent calls have no
+/// effect.
+pub(crate) fn init(seed: Option<u64>) {
+    let _ = SEED.set(seed);
+}
+
+/// Returns the seed, if one was given on the command line or in the
+/// config.
+pub(crate) fn get() -> Option<u64> {
+    SEED.get().copied().flatten()
+}
+
+/// Returns a random number generator, which is seeded with the given
+/// seed or a default seed (0). All commands, which sample, split or
+/// shuffle documents, must use this generator, so that their results
+/// are reproducible.
+pub(crate) fn rng() -> StdRng {
+    StdRng::seed_from_u64(get().unwrap_or_default())
+}
@@ -0,0 +1,140 @@
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use base64::prelude::*;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+
+use crate::error::{bail, DatashedError, DatashedResult};
+
+/// Returns the path of the detached signature of the given file.
+pub(crate) fn signature_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut path = path.as_ref().as_os_str().to_owned();
+    path.push(".sig");
+    path.into()
+}
+
+/// Generates a new signing key and writes it (base64 encoded) into
+/// the given file. The file must not exist and is only readable by the
+/// owner (on unix).
+pub(crate) fn generate_key<P: AsRef<Path>>(
+    path: P,
+) -> DatashedResult<SigningKey> {
+    let path = path.as_ref();
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = match options.open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            bail!("key file '{}' already exists", path.display());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let key = SigningKey::from_bytes(&rand::random());
+    writeln!(file, "{}", BASE64_STANDARD.encode(key.to_bytes()))?;
+    Ok(key)
+}
+
+/// Reads a (base64 encoded) signing key from the given file.
+pub(crate) fn read_key<P: AsRef<Path>>(
+    path: P,
+) -> DatashedResult<SigningKey> {
+    let path = path.as_ref();
+    let bytes = BASE64_STANDARD
+        .decode(fs::read_to_string(path)?.trim())
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .ok_or_else(|| {
+            DatashedError::other(format!(
+                "invalid key file '{}'",
+                path.display()
+            ))
+        })?;
+
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+/// Returns the (base64 encoded) public key of a signing key.
+#[inline]
+pub(crate) fn public_key(key: &SigningKey) -> String {
+    BASE64_STANDARD.encode(key.verifying_key().to_bytes())
+}
+
+/// Signs the data and returns the (base64 encoded) signature.
+#[inline]
+pub(crate) fn sign(key: &SigningKey, data: &[u8]) -> String {
+    BASE64_STANDARD.encode(key.sign(data).to_bytes())
+}
+
+/// Signs the file and writes the signature into the detached signature
+/// file next to it (see [signature_path]).
+pub(crate) fn sign_file<P: AsRef<Path>>(
+    key: &SigningKey,
+    path: P,
+) -> DatashedResult<()> {
+    let path = path.as_ref();
+    let sig = sign(key, &fs::read(path)?);
+    fs::write(signature_path(path), sig + "\n")?;
+    Ok(())
+}
+
+/// Verifies the (base64 encoded) signature of the data against the
+/// (base64 encoded) public key.
+pub(crate) fn verify(
+    public_key: &str,
+    data: &[u8],
+    signature: &str,
+) -> DatashedResult<()> {
+    let Some(key) = BASE64_STANDARD
+        .decode(public_key.trim())
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+    else {
+        bail!("invalid public key '{public_key}'");
+    };
+
+    let Some(signature) = BASE64_STANDARD
+        .decode(signature.trim())
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+    else {
+        bail!("invalid signature");
+    };
+
+    if key.verify_strict(data, &signature).is_err() {
+        bail!("signature verification failed");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn sign_and_verify() -> TestResult {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let signature = sign(&key, b"index");
+
+        assert!(verify(&public_key(&key), b"index", &signature).is_ok());
+        assert!(verify(&public_key(&key), b"indx", &signature).is_err());
+        assert_eq!(
+            signature_path("data/index.ipc"),
+            PathBuf::from("data/index.ipc.sig")
+        );
+
+        Ok(())
+    }
+}
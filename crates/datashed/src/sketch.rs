@@ -0,0 +1,291 @@
+use std::hash::{BuildHasher, Hash, RandomState};
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use sha2::{Digest, Sha256};
+
+/// A count-min sketch, which estimates the frequencies of items with
+/// bounded memory. The estimate of an item never underestimates its
+/// true frequency.
+///
+/// The counters are atomic, so that a single sketch can be shared by
+/// all threads.
+pub(crate) struct CountMinSketch {
+    counters: Vec<AtomicU64>,
+    width: usize,
+    depth: usize,
+    hasher: RandomState,
+}
+
+impl CountMinSketch {
+    /// Creates a new sketch with `depth` rows of `width` counters.
+    pub(crate) fn new(width: usize, depth: usize) -> Self {
+        let width = width.max(1);
+        let depth = depth.max(1);
+
+        Self {
+            counters: (0..width * depth)
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+            hasher: RandomState::new(),
+            width,
+            depth,
+        }
+    }
+
+    /// Returns the counter positions of an item (one per row).
+    fn positions<T: Hash + ?Sized>(
+        &self,
+        item: &T,
+    ) -> impl Iterator<Item = usize> + '_ {
+        let hash = self.hasher.hash_one(item);
+        let (h1, h2) = (hash & 0xffff_ffff, (hash >> 32) | 1);
+
+        (0..self.depth).map(move |row| {
+            let col = h1.wrapping_add((row as u64).wrapping_mul(h2));
+            row * self.width + (col % self.width as u64) as usize
+        })
+    }
+
+    /// Adds `count` occurrences of the item.
+    pub(crate) fn add<T: Hash + ?Sized>(&self, item: &T, count: u64) {
+        for pos in self.positions(item) {
+            self.counters[pos].fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the estimated frequency of the item.
+    pub(crate) fn estimate<T: Hash + ?Sized>(&self, item: &T) -> u64 {
+        self.positions(item)
+            .map(|pos| self.counters[pos].load(Ordering::Relaxed))
+            .min()
+            .unwrap_or_default()
+    }
+}
+
+/// A HyperLogLog sketch, which estimates the number of distinct
+/// items with bounded memory.
+pub(crate) struct HyperLogLog {
+    registers: Vec<AtomicU8>,
+    precision: u32,
+    hasher: RandomState,
+}
+
+impl HyperLogLog {
+    /// Creates a new sketch with `2^precision` registers.
+    pub(crate) fn new(precision: u32) -> Self {
+        let precision = precision.clamp(4, 18);
+        Self {
+            registers: (0..1usize << precision)
+                .map(|_| AtomicU8::new(0))
+                .collect(),
+            hasher: RandomState::new(),
+            precision,
+        }
+    }
+
+    /// Adds an item.
+    pub(crate) fn add<T: Hash + ?Sized>(&self, item: &T) {
+        let hash = self.hasher.hash_one(item);
+        let idx = (hash >> (64 - self.precision)) as usize;
+        let rank = ((hash << self.precision).leading_zeros() + 1)
+            .min(64 - self.precision + 1) as u8;
+
+        self.registers[idx].fetch_max(rank, Ordering::Relaxed);
+    }
+
+    /// Returns the estimated number of distinct items.
+    pub(crate) fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+
+        let (sum, zeros) = self.registers.iter().fold(
+            (0.0, 0),
+            |(sum, zeros), reg| {
+                let rank = reg.load(Ordering::Relaxed);
+                (
+                    sum + 2f64.powi(-(rank as i32)),
+                    zeros + (rank == 0) as u64,
+                )
+            },
+        );
+
+        let estimate = alpha * m * m / sum;
+        if estimate <= 2.5 * m && zeros > 0 {
+            // small range correction (linear counting)
+            return (m * (m / zeros as f64).ln()).round() as u64;
+        }
+
+        estimate.round() as u64
+    }
+}
+
+/// A Bloom filter, which tests whether an item is (probably) part of a
+/// set. False positives are possible, false negatives are not.
+///
+/// In contrast to the other sketches, the hash function is stable, so
+/// that a filter can be persisted (see [BloomFilter::write]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    const MAGIC: &'static [u8; 4] = b"DSBF";
+
+    /// Creates a new filter for `n` items with the given false
+    /// positive rate.
+    pub(crate) fn new(n: usize, fp_rate: f64) -> Self {
+        let n = n.max(1) as f64;
+        let fp_rate = fp_rate.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+
+        let num_bits =
+            (-n * fp_rate.ln() / (ln2 * ln2)).ceil().max(64.0);
+        let num_hashes =
+            ((num_bits / n) * ln2).round().clamp(1.0, 32.0);
+        let num_bits = num_bits as u64;
+
+        Self {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes: num_hashes as u32,
+        }
+    }
+
+    /// Returns the bit positions of an item.
+    fn positions(&self, item: &[u8]) -> impl Iterator<Item = u64> + '_ {
+        let digest = Sha256::digest(item);
+        let h1 = u64::from_le_bytes(digest[0..8].try_into().unwrap());
+        let h2 =
+            u64::from_le_bytes(digest[8..16].try_into().unwrap()) | 1;
+
+        (0..self.num_hashes as u64).map(move |i| {
+            h1.wrapping_add(i.wrapping_mul(h2)) % self.num_bits
+        })
+    }
+
+    /// Adds an item.
+    pub(crate) fn insert<T: AsRef<[u8]> + ?Sized>(&mut self, item: &T) {
+        let positions: Vec<u64> =
+            self.positions(item.as_ref()).collect();
+        for pos in positions {
+            self.bits[(pos / 64) as usize] |= 1 << (pos % 64);
+        }
+    }
+
+    /// Returns `true` if the item is probably part of the set and
+    /// `false` if it's definitely not.
+    pub(crate) fn contains<T: AsRef<[u8]> + ?Sized>(
+        &self,
+        item: &T,
+    ) -> bool {
+        self.positions(item.as_ref()).all(|pos| {
+            self.bits[(pos / 64) as usize] & (1 << (pos % 64)) != 0
+        })
+    }
+
+    /// Writes the filter in a compact binary format.
+    pub(crate) fn write<W: Write>(&self, mut wtr: W) -> io::Result<()> {
+        wtr.write_all(Self::MAGIC)?;
+        wtr.write_all(&self.num_hashes.to_le_bytes())?;
+        wtr.write_all(&self.num_bits.to_le_bytes())?;
+        for word in self.bits.iter() {
+            wtr.write_all(&word.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a filter written by [BloomFilter::write].
+    pub(crate) fn read<R: Read>(mut rdr: R) -> io::Result<Self> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid bloom filter",
+            )
+        };
+
+        let mut magic = [0u8; 4];
+        rdr.read_exact(&mut magic)?;
+        if &magic != Self::MAGIC {
+            return Err(invalid());
+        }
+
+        let mut buf = [0u8; 4];
+        rdr.read_exact(&mut buf)?;
+        let num_hashes = u32::from_le_bytes(buf);
+
+        let mut buf = [0u8; 8];
+        rdr.read_exact(&mut buf)?;
+        let num_bits = u64::from_le_bytes(buf);
+        if num_bits == 0 || num_hashes == 0 {
+            return Err(invalid());
+        }
+
+        let mut bits =
+            Vec::with_capacity(num_bits.div_ceil(64) as usize);
+        for _ in 0..num_bits.div_ceil(64) {
+            rdr.read_exact(&mut buf)?;
+            bits.push(u64::from_le_bytes(buf));
+        }
+
+        Ok(Self {
+            bits,
+            num_bits,
+            num_hashes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_min_sketch() {
+        let sketch = CountMinSketch::new(1 << 10, 4);
+        for i in 0..1000u64 {
+            sketch.add(&format!("token{}", i % 100), 1);
+        }
+
+        sketch.add("foo", 42);
+        assert!(sketch.estimate("foo") >= 42);
+        assert!(sketch.estimate("token7") >= 10);
+    }
+
+    #[test]
+    fn hyper_log_log() {
+        let hll = HyperLogLog::new(14);
+        for i in 0..10_000u64 {
+            hll.add(&i);
+            hll.add(&i);
+        }
+
+        let estimate = hll.estimate() as f64;
+        assert!((estimate - 10_000.0).abs() < 500.0);
+    }
+
+    #[test]
+    fn bloom_filter() -> io::Result<()> {
+        let mut filter = BloomFilter::new(1000, 0.01);
+        for i in 0..1000 {
+            filter.insert(&format!("{i:09}"));
+        }
+
+        assert!((0..1000).all(|i| filter.contains(&format!("{i:09}"))));
+
+        let false_positives = (1000..11_000)
+            .filter(|i| filter.contains(&format!("{i:09}")))
+            .count();
+        assert!(false_positives < 300);
+
+        let mut buf = vec![];
+        filter.write(&mut buf)?;
+        assert_eq!(BloomFilter::read(&buf[..])?, filter);
+        assert!(BloomFilter::read(&b"XXXX"[..]).is_err());
+        Ok(())
+    }
+}
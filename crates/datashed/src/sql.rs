@@ -0,0 +1,152 @@
+use polars::prelude::Expr;
+use polars::sql::sql_expr;
+
+use crate::error::{DatashedError, DatashedResult};
+
+/// The date parts, which can be used as functions in predicates, e.g.
+/// `YEAR(first_entered) = 2020`.
+const DATE_PARTS: [&str; 3] = ["year", "month", "day"];
+
+/// Returns the query of a `--where` predicate over the data frame
+/// `df`.
+///
+/// In addition to the SQL dialect of polars, the predicate may contain
+/// date literals (`DATE '2019-01-01'`) and the date part functions
+/// `YEAR(..)`, `MONTH(..)` and `DAY(..)`, which are rewritten into
+/// `DATE('2019-01-01')` and `DATE_PART('year', ..)` respectively.
+pub(crate) fn select_where(predicate: &str) -> String {
+    format!("SELECT * FROM df WHERE {}", rewrite(predicate))
+}
+
+/// Parses a `--where` predicate (see [select_where]) as a single
+/// expression, e.g. a predicate of a remote client. Unlike the query of
+/// [select_where], the expression can't refer to any table.
+pub(crate) fn where_expr(predicate: &str) -> DatashedResult<Expr> {
+    sql_expr(rewrite(predicate)).map_err(|e| {
+        DatashedError::other(format!("invalid predicate: {e}"))
+    })
+}
+
+/// Returns the length of the keyword `kw` at the start of `s`, if `s`
+/// starts with the keyword (case-insensitive).
+fn keyword(s: &str, kw: &str) -> Option<usize> {
+    let prefix = s.get(..kw.len())?;
+    let next = s[kw.len()..].chars().next();
+    if prefix.eq_ignore_ascii_case(kw)
+        && !next.is_some_and(|c| c.is_alphanumeric() || c == '_')
+    {
+        Some(kw.len())
+    } else {
+        None
+    }
+}
+
+/// Returns the length of a quoted string at the start of `s`.
+fn quoted(s: &str) -> usize {
+    let mut chars = s.char_indices().skip(1).peekable();
+    while let Some((idx, c)) = chars.next() {
+        if c == '\'' {
+            if chars.peek().is_some_and(|(_, c)| *c == '\'') {
+                chars.next();
+                continue;
+            }
+
+            return idx + 1;
+        }
+    }
+
+    s.len()
+}
+
+fn rewrite(predicate: &str) -> String {
+    let mut out = String::with_capacity(predicate.len());
+    let mut rest = predicate;
+    let mut boundary = true;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '\'' || c == '"' {
+            let len = if c == '\'' {
+                quoted(rest)
+            } else {
+                rest[1..].find('"').map(|i| i + 2).unwrap_or(rest.len())
+            };
+
+            out.push_str(&rest[..len]);
+            rest = &rest[len..];
+            boundary = true;
+            continue;
+        }
+
+        if boundary {
+            if let Some(len) = keyword(rest, "date") {
+                let tail = rest[len..].trim_start();
+                if tail.starts_with('\'') {
+                    let literal = quoted(tail);
+                    out.push_str("DATE(");
+                    out.push_str(&tail[..literal]);
+                    out.push(')');
+                    rest = &tail[literal..];
+                    continue;
+                }
+            }
+
+            if let Some((part, len)) =
+                DATE_PARTS.iter().find_map(|part| {
+                    keyword(rest, part).map(|len| (part, len))
+                })
+            {
+                if rest[len..].trim_start().starts_with('(') {
+                    let tail = rest[len..].trim_start();
+                    out.push_str(&format!("DATE_PART('{part}', "));
+                    rest = &tail[1..];
+                    continue;
+                }
+            }
+        }
+
+        boundary = !(c.is_alphanumeric() || c == '_');
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrite_dates() {
+        assert_eq!(
+            rewrite("first_entered >= DATE '2019-01-01'"),
+            "first_entered >= DATE('2019-01-01')"
+        );
+        assert_eq!(
+            rewrite("year(last_changed) BETWEEN 2019 AND 2021"),
+            "DATE_PART('year', last_changed) BETWEEN 2019 AND 2021"
+        );
+        assert_eq!(
+            rewrite("kind = 'date ''2019''' AND birthday > 1"),
+            "kind = 'date ''2019''' AND birthday > 1"
+        );
+        assert_eq!(rewrite("update_date = 1"), "update_date = 1");
+    }
+
+    #[test]
+    fn where_expr_filter() -> anyhow::Result<()> {
+        use polars::prelude::*;
+
+        let df = df!(
+            "path" => ["a.txt", "b.txt", "c.txt"],
+            "kind" => ["book", "toc", "book"],
+        )?;
+
+        let expr = where_expr("kind = 'book' AND path <> 'c.txt'")?;
+        let df = df.lazy().filter(expr).collect()?;
+        assert_eq!(df.height(), 1);
+
+        assert!(where_expr("kind =").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{DatashedError, DatashedResult};
+use crate::seed;
+
+/// The key of the statistics in the Arrow schema metadata.
+pub(crate) const STATS_KEY: &str = "datashed.stats";
+
+/// Columns, which must not contain null values.
+const REQUIRED: [&str; 6] =
+    ["path", "idn", "kind", "size", "mtime", "hash"];
+
+/// Columns, whose values must be in the given (closed) range.
+const RANGES: [(&str, f64, f64); 8] = [
+    ("lang_score", 0.0, 1.0),
+    ("rtl_ratio", 0.0, 1.0),
+    ("alpha", 0.0, 1.0),
+    ("lfreq", 0.0, 1.0),
+    ("ttr", 0.0, 1.0),
+    ("digit_ratio", 0.0, 1.0),
+    ("currency_token_ratio", 0.0, 1.0),
+    ("repetition", 0.0, 1.0),
+];
+
+/// Low-cardinality string columns, which are stored as categoricals
+/// (dictionary encoded).
+pub(crate) const CATEGORICAL: [&str; 5] =
+    ["remote", "kind", "lang_code", "script", "license"];
+
+/// Statistics of a single index column.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ColumnStats {
+    /// The minimum (numeric columns only).
+    pub(crate) min: Option<f64>,
+
+    /// The maximum (numeric columns only).
+    pub(crate) max: Option<f64>,
+
+    /// The number of null values.
+    pub(crate) null_count: usize,
+}
+
+/// Column-level statistics of the index, which are embedded in the
+/// Arrow schema metadata of `index.ipc`.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct IndexStats {
+    /// The number of rows.
+    pub(crate) rows: usize,
+
+    /// The SHA256 digest of all (path, hash) pairs (sorted by path),
+    /// which identifies the corpus.
+    pub(crate) fingerprint: String,
+
+    /// The statistics of each column.
+    pub(crate) columns: BTreeMap<String, ColumnStats>,
+
+    /// The seed of the random number generator (see `--seed`), which
+    /// was used to create the index.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) seed: Option<u64>,
+}
+
+/// Returns the fingerprint of the corpus (all (path, hash) pairs).
+pub(crate) fn fingerprint(df: &DataFrame) -> DatashedResult<String> {
+    let (Ok(path), Ok(hash)) = (df.column("path"), df.column("hash"))
+    else {
+        return Ok(String::new());
+    };
+
+    let mut pairs: Vec<(&str, &str)> = path
+        .str()?
+        .into_iter()
+        .zip(hash.str()?.into_iter())
+        .map(|(path, hash)| {
+            (path.unwrap_or_default(), hash.unwrap_or_default())
+        })
+        .collect();
+    pairs.sort_unstable();
+
+    let mut hasher = Sha256::new();
+    for (path, hash) in pairs {
+        hasher.update(path.as_bytes());
+        hasher.update([0]);
+        hasher.update(hash.as_bytes());
+        hasher.update([b'\n']);
+    }
+
+    Ok(hasher.finalize().iter().fold(String::new(), |mut out, b| {
+        let _ = write!(out, "{b:02x}");
+        out
+    }))
+}
+
+impl IndexStats {
+    /// Computes the statistics of a data frame.
+    pub(crate) fn from_df(df: &DataFrame) -> DatashedResult<Self> {
+        let mut columns = BTreeMap::new();
+
+        for column in df.get_columns() {
+            let mut stats = ColumnStats {
+                null_count: column.null_count(),
+                ..Default::default()
+            };
+
+            if column.dtype().is_numeric() {
+                let values = column.cast(&DataType::Float64)?;
+                let values = values.f64()?;
+                stats.min = values.min();
+                stats.max = values.max();
+            }
+
+            columns.insert(column.name().to_string(), stats);
+        }
+
+        Ok(Self {
+            rows: df.height(),
+            fingerprint: fingerprint(df)?,
+            columns,
+            seed: seed::get(),
+        })
+    }
+
+    /// Reads the statistics from the schema metadata of an IPC file.
+    /// Returns `None`, if the file doesn't contain statistics (e.g. an
+    /// index written by an older version).
+    pub(crate) fn from_path<P: AsRef<Path>>(
+        path: P,
+    ) -> DatashedResult<Option<Self>> {
+        let mut reader = IpcReader::new(File::open(path)?);
+        let Some(metadata) = reader.custom_metadata()? else {
+            return Ok(None);
+        };
+
+        match metadata.get(STATS_KEY) {
+            Some(value) => Ok(Some(
+                serde_json::from_str(value)
+                    .map_err(DatashedError::other)?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Runs sanity checks (unexpected nulls and out-of-range values)
+    /// and returns a description of each problem.
+    pub(crate) fn check(&self) -> Vec<String> {
+        let mut problems = vec![];
+
+        for name in REQUIRED {
+            if let Some(stats) = self.columns.get(name) {
+                if stats.null_count > 0 {
+                    problems.push(format!(
+                        "column '{name}' contains {} null value(s)",
+                        stats.null_count
+                    ));
+                }
+            }
+        }
+
+        for (name, lo, hi) in RANGES {
+            let Some(stats) = self.columns.get(name) else {
+                continue;
+            };
+
+            let min = stats.min.unwrap_or(lo);
+            let max = stats.max.unwrap_or(hi);
+            if min < lo || max > hi {
+                problems.push(format!(
+                    "column '{name}' is out of range [{lo}, {hi}] \
+                        (min = {min}, max = {max})"
+                ));
+            }
+        }
+
+        problems
+    }
+}
+
+/// Writes the index (IPC, ZSTD compressed) with the column statistics
+/// embedded in the schema metadata.
+pub(crate) fn write_index<W: Write>(
+    df: &mut DataFrame,
+    out: W,
+) -> DatashedResult<()> {
+    categorize(df)?;
+
+    let stats = IndexStats::from_df(df)?;
+    let value =
+        serde_json::to_string(&stats).map_err(DatashedError::other)?;
+
+    let mut metadata = BTreeMap::new();
+    metadata
+        .insert(PlSmallStr::from(STATS_KEY), PlSmallStr::from(value));
+
+    let mut writer = IpcWriter::new(out)
+        .with_compression(Some(IpcCompression::ZSTD));
+    writer.set_custom_schema_metadata(Arc::new(metadata));
+    writer.finish(df)?;
+    Ok(())
+}
+
+/// Casts the low-cardinality string columns of the index (see
+/// [CATEGORICAL]) to categoricals. Columns, which are missing or have
+/// another type, are left untouched.
+pub(crate) fn categorize(df: &mut DataFrame) -> DatashedResult<()> {
+    for name in CATEGORICAL {
+        let Ok(column) = df.column(name) else {
+            continue;
+        };
+
+        if column.dtype() == &DataType::String {
+            let column = column.cast(&DataType::Categorical(
+                None,
+                CategoricalOrdering::Lexical,
+            ))?;
+            df.with_column(column)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn index_stats() -> TestResult {
+        let df = df!(
+            "path" => ["b.txt", "a.txt"],
+            "hash" => ["0002", "0001"],
+            "lang_score" => [Some(0.5), None],
+            "alpha" => [0.9, 1.2],
+        )?;
+
+        let stats = IndexStats::from_df(&df)?;
+        assert_eq!(stats.rows, 2);
+        assert_eq!(stats.columns["lang_score"].null_count, 1);
+        assert_eq!(stats.columns["alpha"].max, Some(1.2));
+        assert_eq!(stats.columns["path"].min, None);
+
+        let problems = stats.check();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("column 'alpha'"));
+
+        let reversed = df.reverse();
+        assert_eq!(stats.fingerprint, fingerprint(&reversed)?);
+
+        let mut out = vec![];
+        write_index(&mut df.clone(), &mut out)?;
+        let read = IpcReader::new(std::io::Cursor::new(out))
+            .custom_metadata()?
+            .and_then(|metadata| metadata.get(STATS_KEY).cloned());
+        assert!(read.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn index_categoricals() -> TestResult {
+        let _cache = StringCacheHolder::hold();
+        let mut frames = vec![];
+        for kind in [["book", "toc"], ["blurb", "book"]] {
+            let mut df = df!(
+                "path" => ["a.txt", "b.txt"],
+                "hash" => ["0001", "0002"],
+                "kind" => kind,
+            )?;
+
+            let mut out = vec![];
+            write_index(&mut df, &mut out)?;
+            frames.push(
+                IpcReader::new(std::io::Cursor::new(out)).finish()?,
+            );
+        }
+
+        assert!(matches!(
+            frames[0].column("kind")?.dtype(),
+            DataType::Categorical(..)
+        ));
+
+        let df = frames[0].vstack(&frames[1])?;
+        let kind = df.column("kind")?.cast(&DataType::String)?;
+        assert_eq!(
+            kind.str()?.into_no_null_iter().collect::<Vec<_>>(),
+            ["book", "toc", "blurb", "book"]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Mutex, RwLock};
+
+use clap::ValueEnum;
+use hashbrown::HashMap;
+use rust_stemmers::{Algorithm, Stemmer};
+
+use crate::error::{bail, DatashedError, DatashedResult};
+
+/// The stemming algorithm.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Stem {
+    /// Tokens are not stemmed.
+    #[default]
+    None,
+
+    /// Snowball stemmer for German.
+    SnowballDe,
+
+    /// Snowball stemmer for English.
+    SnowballEn,
+}
+
+/// An external lemmatizer process.
+///
+/// The lemmatizer is started once (via `sh -c`) and communicates via
+/// a line-based protocol: for each token written to its standard
+/// input (one token per line), the lemmatizer must write exactly one
+/// line (the lemma) to its standard output and flush it. An empty
+/// line keeps the token unchanged. The lemmas are cached, so each
+/// distinct token is passed to the lemmatizer only once.
+pub(crate) struct Lemmatizer {
+    child: Child,
+    pipe: Mutex<(ChildStdin, BufReader<ChildStdout>)>,
+    cache: RwLock<HashMap<String, String>>,
+}
+
+impl Lemmatizer {
+    /// Starts the lemmatizer command.
+    pub(crate) fn spawn(cmd: &str) -> DatashedResult<Self> {
+        let mut child = Command::new("sh")
+            .args(["-c", cmd])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let (Some(stdin), Some(stdout)) =
+            (child.stdin.take(), child.stdout.take())
+        else {
+            bail!("unable to connect to lemmatizer '{cmd}'");
+        };
+
+        Ok(Self {
+            child,
+            pipe: Mutex::new((stdin, BufReader::new(stdout))),
+            cache: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Returns the lemma of the given token.
+    pub(crate) fn lemma(&self, token: &str) -> DatashedResult<String> {
+        if let Some(lemma) = self.cache.read().unwrap().get(token) {
+            return Ok(lemma.clone());
+        }
+
+        if token.contains('\n') {
+            return Ok(token.into());
+        }
+
+        let mut guard = self.pipe.lock().unwrap();
+        let (ref mut stdin, ref mut stdout) = *guard;
+        writeln!(stdin, "{token}")?;
+        stdin.flush()?;
+
+        let mut line = String::new();
+        if stdout.read_line(&mut line)? == 0 {
+            return Err(DatashedError::other(
+                "lemmatizer closed its output stream",
+            ));
+        }
+
+        let lemma = match line.trim_end_matches(['\r', '\n']) {
+            "" => token.to_string(),
+            lemma => lemma.to_string(),
+        };
+
+        self.cache
+            .write()
+            .unwrap()
+            .insert(token.into(), lemma.clone());
+
+        Ok(lemma)
+    }
+}
+
+impl Drop for Lemmatizer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Maps tokens to their normalized form (stem or lemma).
+pub(crate) struct Normalizer {
+    stemmer: Option<Stemmer>,
+    lemmatizer: Option<Lemmatizer>,
+}
+
+impl Normalizer {
+    pub(crate) fn new(
+        stem: Stem,
+        lemmatizer: Option<&str>,
+    ) -> DatashedResult<Self> {
+        let stemmer = match stem {
+            Stem::None => None,
+            Stem::SnowballDe => {
+                Some(Stemmer::create(Algorithm::German))
+            }
+            Stem::SnowballEn => {
+                Some(Stemmer::create(Algorithm::English))
+            }
+        };
+
+        let lemmatizer =
+            lemmatizer.map(Lemmatizer::spawn).transpose()?;
+
+        Ok(Self {
+            stemmer,
+            lemmatizer,
+        })
+    }
+
+    /// Returns true, if tokens are left unchanged.
+    pub(crate) fn is_identity(&self) -> bool {
+        self.stemmer.is_none() && self.lemmatizer.is_none()
+    }
+
+    /// Returns the normalized form of a token. The token is
+    /// lemmatized first and the lemma is stemmed afterwards.
+    pub(crate) fn normalize(
+        &self,
+        token: &str,
+    ) -> DatashedResult<String> {
+        let token = match self.lemmatizer {
+            Some(ref lemmatizer) => lemmatizer.lemma(token)?,
+            None => token.to_string(),
+        };
+
+        Ok(match self.stemmer {
+            Some(ref stemmer) => stemmer.stem(&token).into_owned(),
+            None => token,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn normalizer() -> TestResult {
+        let normalizer = Normalizer::new(Stem::SnowballDe, None)?;
+        assert_eq!(normalizer.normalize("häuser")?, "haus");
+
+        let normalizer = Normalizer::new(Stem::None, Some("cat"))?;
+        assert_eq!(normalizer.normalize("Häuser")?, "Häuser");
+        assert!(!normalizer.is_identity());
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt::{self, Display};
+use std::sync::LazyLock;
+
+use bstr::ByteSlice;
+use regex::bytes::Regex;
+
+/// The maximum ratio of NUL and control bytes (except whitespace and
+/// form-feeds) of a plain text document.
+const MAX_CONTROL_RATIO: f64 = 0.01;
+
+/// The maximum ratio of bytes within HTML/XML tags or entities.
+const MAX_MARKUP_RATIO: f64 = 0.1;
+
+/// The minimum length of a base64 block.
+const MIN_BASE64_LEN: usize = 256;
+
+static MARKUP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?x)
+        </?[A-Za-z][A-Za-z0-9:_-]*(\s[^<>]{0,256})?/?>
+        | &(\#[0-9]{1,7}|\#x[0-9A-Fa-f]{1,6}|[A-Za-z]{2,8});
+        | <!--
+        | <!\[CDATA\[",
+    )
+    .unwrap()
+});
+
+/// The heuristics, which flag a document as likely not being plain
+/// text.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Suspicious {
+    /// The ratio of NUL and control bytes exceeds the limit.
+    pub(crate) binary: bool,
+
+    /// The density of HTML/XML tags and entities exceeds the limit.
+    pub(crate) markup: bool,
+
+    /// The document contains a base64 encoded block.
+    pub(crate) base64: bool,
+
+    /// The document contains leftovers of a PDF file (header or
+    /// object syntax).
+    pub(crate) pdf: bool,
+}
+
+/// The names of the heuristics.
+pub(crate) const FLAGS: [&str; 4] =
+    ["binary", "markup", "base64", "pdf"];
+
+impl Suspicious {
+    /// Applies the heuristics to the content of a document.
+    pub(crate) fn detect(content: &[u8]) -> Self {
+        Self {
+            binary: is_binary(content),
+            markup: is_markup(content),
+            base64: has_base64(content),
+            pdf: has_pdf(content),
+        }
+    }
+
+    /// Returns the names of the flags, which are set.
+    pub(crate) fn flags(&self) -> Vec<&'static str> {
+        [self.binary, self.markup, self.base64, self.pdf]
+            .into_iter()
+            .zip(FLAGS)
+            .filter_map(|(set, name)| set.then_some(name))
+            .collect()
+    }
+
+    /// Returns `true`, if no flag is set.
+    pub(crate) fn is_clean(&self) -> bool {
+        self.flags().is_empty()
+    }
+}
+
+impl Display for Suspicious {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.flags().join(","))
+    }
+}
+
+fn is_binary(content: &[u8]) -> bool {
+    if content.is_empty() {
+        return false;
+    }
+
+    let control = content
+        .iter()
+        .filter(|b| {
+            (**b < 0x20 && !matches!(**b, b'\t' | b'\n' | b'\r' | 0x0c))
+                || **b == 0x7f
+        })
+        .count();
+
+    control as f64 / content.len() as f64 > MAX_CONTROL_RATIO
+}
+
+fn is_markup(content: &[u8]) -> bool {
+    if content.is_empty() {
+        return false;
+    }
+
+    let markup: usize =
+        MARKUP.find_iter(content).map(|m| m.len()).sum::<usize>();
+
+    markup as f64 / content.len() as f64 > MAX_MARKUP_RATIO
+}
+
+fn has_base64(content: &[u8]) -> bool {
+    let is_base64 =
+        |b: &u8| b.is_ascii_alphanumeric() || b"+/=".contains(b);
+
+    // Encoded data is usually wrapped into lines (e.g. of 76 chars in
+    // MIME), so consecutive lines form a block.
+    let mut block = 0;
+    for line in content.lines() {
+        let line = line.trim();
+        if !line.is_empty()
+            && line.iter().all(is_base64)
+            && line.iter().any(u8::is_ascii_digit)
+            && line.iter().any(u8::is_ascii_alphabetic)
+        {
+            block += line.len();
+            if block >= MIN_BASE64_LEN {
+                return true;
+            }
+        } else {
+            block = 0;
+        }
+    }
+
+    false
+}
+
+fn has_pdf(content: &[u8]) -> bool {
+    content.contains_str("%PDF-")
+        || (content.contains_str("endobj")
+            && content.contains_str("endstream"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suspicious_flags() {
+        let text = b"The quick brown fox jumps over the lazy dog.\n";
+        assert!(Suspicious::detect(text).is_clean());
+
+        let flags = Suspicious::detect(b"abc\0\0def\x01ghi");
+        assert_eq!(flags.flags(), ["binary"]);
+
+        let flags = Suspicious::detect(
+            b"<html><body><p class=\"x\">Fox</p>&nbsp;</body></html>",
+        );
+        assert_eq!(flags.to_string(), "markup");
+
+        let block =
+            "QUJDREVGR0hJSktMTU5PUFFSU1RVVldYWVo0MTIz\n".repeat(8);
+        let flags = Suspicious::detect(block.as_bytes());
+        assert_eq!(flags.flags(), ["base64"]);
+
+        let flags = Suspicious::detect(b"%PDF-1.4\n1 0 obj\nendobj\n");
+        assert!(flags.pdf);
+    }
+}
@@ -0,0 +1,263 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::error::{bail, DatashedResult};
+
+const GER: &[&str] = &[
+    "der",
+    "die",
+    "das",
+    "und",
+    "in",
+    "zu",
+    "den",
+    "nicht",
+    "von",
+    "sie",
+    "ist",
+    "des",
+    "sich",
+    "mit",
+    "dem",
+    "dass",
+    "er",
+    "es",
+    "ein",
+    "ich",
+    "auf",
+    "so",
+    "eine",
+    "auch",
+    "als",
+    "an",
+    "nach",
+    "wie",
+    "im",
+    "für",
+    "über",
+    "müssen",
+    "größe",
+    "straße",
+    "bibliothek",
+    "buch",
+    "geschichte",
+    "wissenschaft",
+    "zeitschrift",
+    "verlag",
+    "forschung",
+    "entwicklung",
+    "gesellschaft",
+    "sprache",
+    "kultur",
+    "jahrhundert",
+];
+
+const ENG: &[&str] = &[
+    "the",
+    "of",
+    "and",
+    "to",
+    "a",
+    "in",
+    "is",
+    "you",
+    "that",
+    "it",
+    "he",
+    "was",
+    "for",
+    "on",
+    "are",
+    "as",
+    "with",
+    "his",
+    "they",
+    "at",
+    "be",
+    "this",
+    "have",
+    "from",
+    "or",
+    "one",
+    "had",
+    "by",
+    "word",
+    "library",
+    "book",
+    "history",
+    "science",
+    "journal",
+    "publisher",
+    "research",
+    "development",
+    "society",
+    "language",
+    "culture",
+    "century",
+    "knowledge",
+    "information",
+    "collection",
+];
+
+const FRE: &[&str] = &[
+    "le",
+    "de",
+    "un",
+    "être",
+    "et",
+    "à",
+    "il",
+    "avoir",
+    "ne",
+    "je",
+    "son",
+    "que",
+    "se",
+    "qui",
+    "ce",
+    "dans",
+    "en",
+    "du",
+    "elle",
+    "au",
+    "pour",
+    "pas",
+    "sur",
+    "bibliothèque",
+    "livre",
+    "histoire",
+    "science",
+    "revue",
+    "éditeur",
+    "recherche",
+    "développement",
+    "société",
+    "langue",
+    "culture",
+    "siècle",
+    "connaissance",
+    "français",
+    "première",
+];
+
+/// Returns the word list of a supported language.
+pub(crate) fn words(
+    lang: &str,
+) -> DatashedResult<&'static [&'static str]> {
+    match lang {
+        "ger" => Ok(GER),
+        "eng" => Ok(ENG),
+        "fre" => Ok(FRE),
+        _ => bail!("unsupported language '{lang}'"),
+    }
+}
+
+/// A generator of synthetic documents.
+///
+/// Documents consist of paragraphs of sentences, whose words are drawn
+/// from a small embedded word list. The number of words per document
+/// is log-uniformly distributed between `min_words` and `max_words`.
+/// Given the same seed, the generator produces the same documents.
+pub(crate) struct Generator {
+    rng: StdRng,
+    min_words: usize,
+    max_words: usize,
+}
+
+impl Generator {
+    pub(crate) fn new(
+        seed: u64,
+        min_words: usize,
+        max_words: usize,
+    ) -> Self {
+        let min_words = min_words.max(1);
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            max_words: max_words.max(min_words),
+            min_words,
+        }
+    }
+
+    /// Returns a random number in the range `0..n`.
+    #[inline]
+    pub(crate) fn index(&mut self, n: usize) -> usize {
+        self.rng.gen_range(0..n)
+    }
+
+    /// Returns `true` with the given probability.
+    #[inline]
+    pub(crate) fn chance(&mut self, p: f64) -> bool {
+        self.rng.gen_bool(p.clamp(0.0, 1.0))
+    }
+
+    /// Returns the number of words of the next document.
+    fn size(&mut self) -> usize {
+        let lo = (self.min_words as f64).ln();
+        let hi = (self.max_words as f64).ln();
+        if hi <= lo {
+            return self.min_words;
+        }
+
+        self.rng.gen_range(lo..=hi).exp().round() as usize
+    }
+
+    /// Generates a new document from the given word list.
+    pub(crate) fn document(&mut self, words: &[&str]) -> String {
+        let size = self.size();
+        let mut doc = String::with_capacity(size * 8);
+        let mut sentence = 0;
+        let mut len = self.rng.gen_range(5..=15);
+
+        for i in 0..size {
+            let word = words[self.rng.gen_range(0..words.len())];
+            if sentence == 0 {
+                let mut chars = word.chars();
+                if let Some(c) = chars.next() {
+                    doc.extend(c.to_uppercase());
+                    doc.push_str(chars.as_str());
+                }
+            } else {
+                doc.push(' ');
+                doc.push_str(word);
+            }
+
+            sentence += 1;
+            if sentence == len || i + 1 == size {
+                doc.push('.');
+                sentence = 0;
+                len = self.rng.gen_range(5..=15);
+
+                if self.rng.gen_bool(0.2) {
+                    doc.push_str("\n\n");
+                } else {
+                    doc.push(' ');
+                }
+            }
+        }
+
+        doc.truncate(doc.trim_end().len());
+        doc.push('\n');
+        doc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    #[test]
+    fn generator_is_deterministic() -> TestResult {
+        let words = words("ger")?;
+        let mut lhs = Generator::new(42, 10, 100);
+        let mut rhs = Generator::new(42, 10, 100);
+        assert_eq!(lhs.document(words), rhs.document(words));
+
+        let doc = lhs.document(words);
+        let n = doc.split_whitespace().count();
+        assert!((10..=100).contains(&n));
+
+        Ok(())
+    }
+}
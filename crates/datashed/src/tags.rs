@@ -0,0 +1,169 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use hashbrown::HashMap;
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::atomic::AtomicFile;
+use crate::error::DatashedResult;
+
+/// A tag of a document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct TagEntry {
+    /// The path of the document (relative to the root directory).
+    pub(crate) path: String,
+
+    /// The name of the tag.
+    pub(crate) tag: String,
+
+    /// The user, who has tagged the document.
+    pub(crate) user: String,
+
+    /// The time of tagging (milliseconds since the UNIX epoch).
+    pub(crate) timestamp: u64,
+}
+
+/// The tags table of a datashed (`tags.csv`).
+#[derive(Debug, Default)]
+pub(crate) struct Tags {
+    entries: Vec<TagEntry>,
+}
+
+impl Tags {
+    /// Reads the tags table. A missing file is an empty table.
+    pub(crate) fn from_path<P: AsRef<Path>>(
+        path: P,
+    ) -> DatashedResult<Self> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+
+        let entries = csv::Reader::from_path(path)?
+            .into_deserialize()
+            .collect::<Result<Vec<TagEntry>, _>>()?;
+
+        Ok(Self { entries })
+    }
+
+    /// Writes the tags table (atomically).
+    pub(crate) fn save<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> DatashedResult<()> {
+        let mut out = AtomicFile::create(path)?;
+        let mut writer = csv::Writer::from_writer(&mut out);
+        for entry in self.entries.iter() {
+            writer.serialize(entry)?;
+        }
+
+        writer.flush()?;
+        drop(writer);
+        out.commit()?;
+        Ok(())
+    }
+
+    /// Returns the entries of the table.
+    pub(crate) fn entries(&self) -> &[TagEntry] {
+        &self.entries
+    }
+
+    /// Adds the tag to the document. Returns `false`, if the document
+    /// is already tagged.
+    pub(crate) fn add(&mut self, entry: TagEntry) -> bool {
+        if self
+            .entries
+            .iter()
+            .any(|e| e.path == entry.path && e.tag == entry.tag)
+        {
+            return false;
+        }
+
+        self.entries.push(entry);
+        true
+    }
+
+    /// Removes the tag from the document. Returns `false`, if the
+    /// document isn't tagged.
+    pub(crate) fn remove(&mut self, path: &str, tag: &str) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|e| e.path != path || e.tag != tag);
+        self.entries.len() != len
+    }
+
+    /// Adds the list column `tags` (sorted tag names) to the index.
+    /// Documents without tags have an empty list.
+    pub(crate) fn join(
+        &self,
+        index: DataFrame,
+    ) -> DatashedResult<DataFrame> {
+        let mut names: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+        for entry in self.entries.iter() {
+            names
+                .entry(entry.path.as_str())
+                .or_default()
+                .insert(entry.tag.as_str());
+        }
+
+        let paths = index.column("path")?.str()?;
+        let tags: Vec<Series> = paths
+            .into_iter()
+            .map(|path| {
+                Series::from_iter(
+                    path.and_then(|path| names.get(path))
+                        .into_iter()
+                        .flatten()
+                        .copied(),
+                )
+            })
+            .collect();
+
+        let mut tags = Series::new("tags".into(), tags);
+        if index.height() == 0 {
+            tags =
+                tags.cast(&DataType::List(Box::new(DataType::String)))?;
+        }
+
+        let mut index = index;
+        index.with_column(tags)?;
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestResult = anyhow::Result<()>;
+
+    fn entry(path: &str, tag: &str) -> TagEntry {
+        TagEntry {
+            path: path.into(),
+            tag: tag.into(),
+            user: "x".into(),
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn tags_join() -> TestResult {
+        let mut tags = Tags::default();
+        assert!(tags.add(entry("data/1.txt", "review")));
+        assert!(tags.add(entry("data/1.txt", "ocr")));
+        assert!(!tags.add(entry("data/1.txt", "ocr")));
+        assert!(tags.add(entry("data/2.txt", "ocr")));
+        assert!(tags.remove("data/2.txt", "ocr"));
+        assert!(!tags.remove("data/2.txt", "ocr"));
+
+        let index = df!("path" => ["data/1.txt", "data/2.txt"])?;
+        let index = tags.join(index)?;
+        let column = index.column("tags")?.list()?;
+
+        let first = column.get_as_series(0).unwrap();
+        let first: Vec<_> = first.str()?.into_no_null_iter().collect();
+        assert_eq!(first, ["ocr", "review"]);
+        assert_eq!(column.get_as_series(1).unwrap().len(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+use std::fs::File;
+
+use tempfile::TempDir;
+
+use crate::datashed::Datashed;
+
+/// Creates a temporary directory, which is removed when the returned
+/// handle is dropped.
+pub(crate) fn temp_dir() -> anyhow::Result<TempDir> {
+    Ok(tempfile::Builder::new().prefix("datashed-").tempdir()?)
+}
+
+/// Creates an empty datashed (an empty config) in a temporary
+/// directory (see [temp_dir]).
+pub(crate) fn temp_datashed() -> anyhow::Result<(TempDir, Datashed)> {
+    let dir = temp_dir()?;
+    File::create(dir.path().join(Datashed::CONFIG))?;
+    let datashed = Datashed::from_path(dir.path())?;
+    Ok((dir, datashed))
+}
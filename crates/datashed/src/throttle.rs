@@ -0,0 +1,134 @@
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::IoLimit;
+use crate::error::DatashedResult;
+
+/// The I/O throttle of the current process (`runtime.io_limit`).
+static THROTTLE: OnceLock<Throttle> = OnceLock::new();
+
+/// A token bucket, which is refilled at a constant rate and holds at
+/// most the tokens of one second.
+///
+/// A request, which exceeds the available tokens, is granted anyway,
+/// but the bucket goes into debt and the caller has to wait until the
+/// debt is paid off. Thus, requests larger than the capacity (e.g.
+/// huge documents) don't block forever.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, now: Instant) -> Self {
+        Self {
+            rate,
+            state: Mutex::new((rate, now)),
+        }
+    }
+
+    /// Takes `n` tokens from the bucket and returns the time the
+    /// caller has to wait.
+    fn reserve(&self, n: f64, now: Instant) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let (ref mut tokens, ref mut last) = *state;
+
+        let elapsed =
+            now.saturating_duration_since(*last).as_secs_f64();
+        *tokens = (*tokens + elapsed * self.rate).min(self.rate) - n;
+        *last = now.max(*last);
+
+        if *tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-*tokens / self.rate)
+        }
+    }
+}
+
+/// The caps of the document reads.
+#[derive(Debug, Default)]
+struct Throttle {
+    bandwidth: Option<TokenBucket>,
+    iops: Option<TokenBucket>,
+}
+
+impl Throttle {
+    fn reserve(&self, bytes: u64, now: Instant) -> Duration {
+        let bandwidth = self
+            .bandwidth
+            .as_ref()
+            .map(|bucket| bucket.reserve(bytes as f64, now))
+            .unwrap_or_default();
+
+        let iops = self
+            .iops
+            .as_ref()
+            .map(|bucket| bucket.reserve(1.0, now))
+            .unwrap_or_default();
+
+        bandwidth.max(iops)
+    }
+}
+
+/// Sets the I/O caps of the current process. Subsequent calls have no
+/// effect.
+pub(crate) fn init(limit: Option<&IoLimit>) -> DatashedResult<()> {
+    let Some(limit) = limit else {
+        return Ok(());
+    };
+
+    let now = Instant::now();
+    let throttle = Throttle {
+        bandwidth: limit
+            .bandwidth()?
+            .filter(|rate| *rate > 0)
+            .map(|rate| TokenBucket::new(rate as f64, now)),
+        iops: limit
+            .iops
+            .filter(|rate| *rate > 0)
+            .map(|rate| TokenBucket::new(rate as f64, now)),
+    };
+
+    let _ = THROTTLE.set(throttle);
+    Ok(())
+}
+
+/// Blocks the calling thread until a read of `bytes` bytes is within
+/// the I/O caps. All reads of documents must call this function before
+/// reading the document.
+pub(crate) fn acquire(bytes: u64) {
+    if let Some(throttle) = THROTTLE.get() {
+        let wait = throttle.reserve(bytes, Instant::now());
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_bucket() {
+        let now = Instant::now();
+        let throttle = Throttle {
+            bandwidth: Some(TokenBucket::new(100.0, now)),
+            iops: Some(TokenBucket::new(2.0, now)),
+        };
+
+        assert_eq!(throttle.reserve(50, now), Duration::ZERO);
+        assert_eq!(throttle.reserve(50, now), Duration::ZERO);
+        assert_eq!(throttle.reserve(100, now), Duration::from_secs(1));
+
+        let later = now + Duration::from_secs(2);
+        assert_eq!(throttle.reserve(50, later), Duration::ZERO);
+        assert_eq!(
+            throttle.reserve(100, later),
+            Duration::from_millis(500)
+        );
+    }
+}
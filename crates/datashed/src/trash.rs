@@ -0,0 +1,182 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::datashed::Datashed;
+use crate::error::DatashedResult;
+
+/// The file of a snapshot, which holds the modification time of the
+/// index written by the command (see [Trash::stamp_index]).
+const INDEX_STAMP: &str = ".index-mtime";
+
+/// Returns the modification time of a file (nanoseconds since the
+/// epoch).
+fn mtime(path: &Path) -> DatashedResult<u128> {
+    Ok(fs::metadata(path)?
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos())
+}
+
+/// A snapshot of the trash, which holds the files removed by a single
+/// command run. Files keep their path relative to the root directory
+/// of the datashed.
+#[derive(Debug)]
+pub(crate) struct Trash {
+    dir: PathBuf,
+}
+
+impl Trash {
+    /// Creates a new (empty) snapshot, named after the current time
+    /// (milliseconds since the epoch).
+    pub(crate) fn create(datashed: &Datashed) -> DatashedResult<Self> {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+
+        let dir = datashed.trash_dir().join(format!("{millis:013}"));
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Opens an existing snapshot.
+    pub(crate) fn open<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Returns all snapshots of the datashed (oldest first).
+    pub(crate) fn list(
+        datashed: &Datashed,
+    ) -> DatashedResult<Vec<Self>> {
+        let trash_dir = datashed.trash_dir();
+        if !trash_dir.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let mut dirs = fs::read_dir(trash_dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        dirs.retain(|dir| dir.is_dir());
+        dirs.sort();
+
+        Ok(dirs.into_iter().map(Self::open).collect())
+    }
+
+    /// Returns the directory of the snapshot.
+    #[inline]
+    pub(crate) fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the name of the snapshot.
+    pub(crate) fn name(&self) -> String {
+        self.dir
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Moves the file at `base_dir/relpath` into the snapshot.
+    pub(crate) fn put(
+        &self,
+        base_dir: &Path,
+        relpath: &str,
+    ) -> DatashedResult<()> {
+        move_file(&base_dir.join(relpath), &self.dir.join(relpath))
+    }
+
+    /// Copies the file at `base_dir/relpath` into the snapshot.
+    pub(crate) fn backup(
+        &self,
+        base_dir: &Path,
+        relpath: &str,
+    ) -> DatashedResult<()> {
+        let dest = self.dir.join(relpath);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::copy(base_dir.join(relpath), dest)?;
+        Ok(())
+    }
+
+    /// Records the modification time of the index, which has been
+    /// rewritten by the command, so that a later update of the index
+    /// can be detected (see [Trash::is_index_outdated]).
+    pub(crate) fn stamp_index(
+        &self,
+        index: &Path,
+    ) -> DatashedResult<()> {
+        fs::write(
+            self.dir.join(INDEX_STAMP),
+            mtime(index)?.to_string(),
+        )?;
+        Ok(())
+    }
+
+    /// Returns `true`, if the index has been modified after the
+    /// command, which created the snapshot. Snapshots without a
+    /// stamp are never outdated.
+    pub(crate) fn is_index_outdated(
+        &self,
+        index: &Path,
+    ) -> DatashedResult<bool> {
+        let Ok(stamp) = fs::read_to_string(self.dir.join(INDEX_STAMP))
+        else {
+            return Ok(false);
+        };
+
+        let Ok(stamp) = stamp.trim().parse::<u128>() else {
+            return Ok(false);
+        };
+
+        Ok(index.is_file() && mtime(index)? > stamp)
+    }
+
+    /// Returns the paths (relative to the snapshot) of all files of
+    /// the snapshot.
+    pub(crate) fn files(&self) -> DatashedResult<Vec<PathBuf>> {
+        let mut files = vec![];
+        let mut stack = vec![self.dir.clone()];
+
+        while let Some(dir) = stack.pop() {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    stack.push(path);
+                } else if let Ok(path) = path.strip_prefix(&self.dir) {
+                    if path.as_os_str() != INDEX_STAMP {
+                        files.push(path.to_path_buf());
+                    }
+                }
+            }
+        }
+
+        files.sort();
+        Ok(files)
+    }
+}
+
+/// Moves a file, creating the parent directories of the destination.
+/// If the file can't be renamed (e.g. across file systems), it's
+/// copied and removed.
+pub(crate) fn move_file(src: &Path, dest: &Path) -> DatashedResult<()> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    match fs::rename(src, dest) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Err(e.into()),
+        Err(_) => {
+            fs::copy(src, dest)?;
+            fs::remove_file(src)?;
+            Ok(())
+        }
+    }
+}
@@ -1,7 +1,12 @@
 use std::fs::create_dir_all;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
+use clap::ValueEnum;
 use directories::ProjectDirs;
+use polars::prelude::*;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
 
 use crate::error::{bail, DatashedError, DatashedResult};
 
@@ -35,11 +40,170 @@ pub(crate) fn state_dir() -> DatashedResult<PathBuf> {
     bail!("unable determine state directory!")
 }
 
+/// Escapes a string for the use in XML/HTML attributes or text.
+pub(crate) fn escape_xml(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Folds the given string (lowercase and removal of diacritics).
+///
+/// Returns the folded string together with an offset map, which maps
+/// each byte of the folded string to the byte range of the originating
+/// character. Since folding may change the number of characters (e.g.
+/// Hangul syllables are decomposed into their jamo), the range of a
+/// match in the folded string must be mapped back through the start of
+/// its first and the end of its last byte. Removed combining marks are
+/// part of the range of the preceding character.
+pub(crate) fn fold(s: &str) -> (String, Vec<(usize, usize)>) {
+    let mut folded = String::with_capacity(s.len());
+    let mut offsets: Vec<(usize, usize)> = Vec::with_capacity(s.len());
+
+    for (i, c) in s.char_indices() {
+        let end = i + c.len_utf8();
+        let len = offsets.len();
+
+        for c in c.to_lowercase().nfd() {
+            if is_combining_mark(c) {
+                continue;
+            }
+
+            folded.push(c);
+            offsets.resize(offsets.len() + c.len_utf8(), (i, end));
+        }
+
+        if offsets.len() == len {
+            if let Some(&(start, _)) = offsets.last() {
+                for offset in offsets.iter_mut().rev() {
+                    if offset.0 != start {
+                        break;
+                    }
+
+                    offset.1 = end;
+                }
+            }
+        }
+    }
+
+    (folded, offsets)
+}
+
+/// The format of a tabular output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum OutputFormat {
+    Csv,
+    Ipc,
+    Ndjson,
+}
+
+/// Writes the data frame in the given format.
+///
+/// NDJSON output contains one JSON object per row. Columns of nested
+/// structs are flattened (`{column}_{field}`) and categorical columns
+/// are written as strings, so that the objects have a stable schema.
+/// CSV output contains list columns (e.g. `tags`) as comma-separated
+/// strings.
+pub(crate) fn write_df<W: Write>(
+    df: &mut DataFrame,
+    format: OutputFormat,
+    writer: W,
+) -> DatashedResult<()> {
+    match format {
+        OutputFormat::Csv => {
+            let mut columns = Vec::with_capacity(df.width());
+            for column in df.get_columns() {
+                let DataType::List(_) = column.dtype() else {
+                    columns.push(column.clone());
+                    continue;
+                };
+
+                let values = column
+                    .list()?
+                    .into_iter()
+                    .map(|value| {
+                        value
+                            .map(|value| {
+                                let value =
+                                    value.cast(&DataType::String)?;
+                                Ok(value
+                                    .str()?
+                                    .into_iter()
+                                    .flatten()
+                                    .collect::<Vec<_>>()
+                                    .join(","))
+                            })
+                            .transpose()
+                    })
+                    .collect::<DatashedResult<Vec<Option<String>>>>()?;
+
+                columns
+                    .push(Column::new(column.name().clone(), values));
+            }
+
+            CsvWriter::new(writer)
+                .finish(&mut DataFrame::new(columns)?)?;
+        }
+        OutputFormat::Ipc => {
+            IpcWriter::new(writer)
+                .with_compression(Some(IpcCompression::ZSTD))
+                .finish(df)?;
+        }
+        OutputFormat::Ndjson => {
+            let mut columns = Vec::with_capacity(df.width());
+            for column in df.get_columns() {
+                match column.dtype() {
+                    DataType::Struct(_) => {
+                        let fields = column
+                            .as_materialized_series()
+                            .struct_()?
+                            .fields_as_series();
+
+                        for field in fields {
+                            let name = format!(
+                                "{}_{}",
+                                column.name(),
+                                field.name()
+                            );
+
+                            columns.push(
+                                field
+                                    .with_name(name.into())
+                                    .into_column(),
+                            );
+                        }
+                    }
+                    DataType::Categorical(..) | DataType::Enum(..) => {
+                        columns.push(column.cast(&DataType::String)?);
+                    }
+                    _ => columns.push(column.clone()),
+                }
+            }
+
+            JsonWriter::new(writer)
+                .with_json_format(JsonFormat::JsonLines)
+                .finish(&mut DataFrame::new(columns)?)?;
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
 
-    use super::relpath;
+    use super::*;
 
     #[test]
     fn relpath_ok() {
@@ -55,4 +219,54 @@ mod tests {
         let prefix = PathBuf::from("/home/bar");
         let _ = relpath(path, prefix);
     }
+
+    #[test]
+    fn fold_ok() {
+        let (folded, offsets) = fold("Ärger");
+        assert_eq!(folded, "arger");
+        assert_eq!(offsets, [(0, 2), (2, 3), (3, 4), (4, 5), (5, 6)]);
+
+        let (folded, offsets) = fold("İx");
+        assert_eq!(folded, "ix");
+        assert_eq!(offsets, [(0, 2), (2, 3)]);
+
+        // a Hangul syllable is decomposed into three jamo
+        let (folded, offsets) = fold("한!");
+        assert_eq!(folded.chars().count(), 4);
+        assert_eq!(offsets[..9], [(0, 3); 9]);
+        assert_eq!(offsets[9], (3, 4));
+
+        let (folded, offsets) = fold("Cafe\u{301}!");
+        assert_eq!(folded, "cafe!");
+        assert_eq!(offsets[3], (3, 6));
+        assert_eq!(offsets[4], (6, 7));
+    }
+
+    #[test]
+    fn write_df_ndjson() -> anyhow::Result<()> {
+        let lang = StructChunked::from_series(
+            "lang".into(),
+            2,
+            [
+                Series::new("code".into(), ["ger", "eng"]),
+                Series::new("score".into(), [0.9, 0.8]),
+            ]
+            .iter(),
+        )?;
+
+        let mut df = DataFrame::new(vec![
+            Column::new("path".into(), ["a.txt", "b.txt"]),
+            lang.into_series().into_column(),
+        ])?;
+
+        let mut out = vec![];
+        write_df(&mut df, OutputFormat::Ndjson, &mut out)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "{\"path\":\"a.txt\",\"lang_code\":\"ger\",\"lang_score\":0.9}\n\
+             {\"path\":\"b.txt\",\"lang_code\":\"eng\",\"lang_score\":0.8}\n"
+        );
+
+        Ok(())
+    }
 }